@@ -0,0 +1,155 @@
+/* PeerSend protocol C API. Generated by cbindgen, do not edit. */
+
+#ifndef PEERSEND_H
+#define PEERSEND_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// 错误码
+typedef enum PeerSendStatus {
+  PEER_SEND_STATUS_OK = 0,
+  PEER_SEND_STATUS_INVALID_ARGUMENT = 1,
+  PEER_SEND_STATUS_IO = 2,
+  PEER_SEND_STATUS_NETWORK = 3,
+  PEER_SEND_STATUS_REJECTED = 4,
+  PEER_SEND_STATUS_TIMEOUT = 5,
+  PEER_SEND_STATUS_CANCELLED = 6,
+  PEER_SEND_STATUS_OTHER = 7,
+} PeerSendStatus;
+
+// 传输状态
+typedef enum PeerSendTransferState {
+  PEER_SEND_TRANSFER_STATE_PENDING = 0,
+  PEER_SEND_TRANSFER_STATE_TRANSFERRING = 1,
+  PEER_SEND_TRANSFER_STATE_FINISHED = 2,
+  PEER_SEND_TRANSFER_STATE_CANCELLED = 3,
+  PEER_SEND_TRANSFER_STATE_FAILED = 4,
+} PeerSendTransferState;
+
+// 客户端句柄
+typedef struct PeerSendClient PeerSendClient;
+
+// 发送任务句柄
+typedef struct PeerSendTransfer PeerSendTransfer;
+
+// 传输进度快照
+typedef struct PeerSendProgress {
+  uint64_t bytes_transferred;
+  uint64_t total_bytes;
+  enum PeerSendTransferState state;
+} PeerSendProgress;
+
+// 接收事件回调，event_json 仅在回调期间有效
+typedef void (*PeerSendReceiveCallback)(const char *event_json, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// 获取当前线程最近一次错误的描述
+//
+// 返回的指针由库持有，在同一线程下一次出错前有效；没有错误时返回 NULL
+const char *peersend_last_error(void);
+
+// 释放库返回的字符串
+//
+// # Safety
+//
+// s 必须为 NULL 或由本库返回且尚未释放的字符串
+void peersend_string_free(char *s);
+
+// 创建客户端
+//
+// config_json 为 NULL 时加载共享配置文件，否则按 JSON 解析，缺失字段使用默认值。
+// 失败时返回 NULL
+//
+// # Safety
+//
+// config_json 必须为 NULL 或有效的 C 字符串
+struct PeerSendClient *peersend_client_new(const char *config_json);
+
+// 释放客户端，进行中的操作将被取消
+//
+// # Safety
+//
+// client 必须为 NULL 或由 [`peersend_client_new`] 返回且尚未释放的句柄
+void peersend_client_free(struct PeerSendClient *client);
+
+// 在局域网中发现设备，阻塞 timeout_ms 毫秒
+//
+// 返回设备列表的 JSON 数组，失败时返回 NULL
+//
+// # Safety
+//
+// client 必须为有效的客户端句柄
+char *peersend_discover(const struct PeerSendClient *client, uint32_t timeout_ms);
+
+// 开始向设备发送文件，立即返回
+//
+// device_json 为 [`peersend_discover`] 返回的设备对象之一。
+// 通过 [`peersend_transfer_poll`] 查询进度，完成后以 [`peersend_transfer_free`] 释放。
+// 失败时返回 NULL
+//
+// # Safety
+//
+// client 必须为有效的客户端句柄，paths 必须指向 count 个有效的 C 字符串
+struct PeerSendTransfer *peersend_send_files(const struct PeerSendClient *client,
+                                             const char *device_json,
+                                             const char *const *paths,
+                                             uintptr_t count);
+
+// 查询传输进度
+//
+// # Safety
+//
+// transfer 必须为有效的传输句柄，out 必须指向可写的 [`PeerSendProgress`]
+enum PeerSendStatus peersend_transfer_poll(const struct PeerSendTransfer *transfer,
+                                           struct PeerSendProgress *out);
+
+// 获取失败传输的错误描述，未失败时返回 NULL
+//
+// # Safety
+//
+// transfer 必须为有效的传输句柄
+char *peersend_transfer_error(const struct PeerSendTransfer *transfer);
+
+// 取消传输
+//
+// # Safety
+//
+// transfer 必须为有效的传输句柄
+void peersend_transfer_cancel(const struct PeerSendTransfer *transfer);
+
+// 释放传输句柄，未完成的传输将被取消
+//
+// # Safety
+//
+// transfer 必须为 NULL 或由 [`peersend_send_files`] 返回且尚未释放的句柄
+void peersend_transfer_free(struct PeerSendTransfer *transfer);
+
+// 启动接收服务，每个接收事件以 JSON 调用一次 callback
+//
+// 回调在库的工作线程中执行，user_data 原样传回
+//
+// # Safety
+//
+// client 必须为有效的客户端句柄，user_data 必须在接收服务停止前保持有效且可跨线程访问
+enum PeerSendStatus peersend_receive_start(const struct PeerSendClient *client,
+                                           PeerSendReceiveCallback callback,
+                                           void *user_data);
+
+// 停止接收服务
+//
+// # Safety
+//
+// client 必须为有效的客户端句柄
+void peersend_receive_stop(const struct PeerSendClient *client);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PEERSEND_H */
@@ -0,0 +1,508 @@
+//! PeerSend 协议的 C 接口
+//!
+//! 供非 Rust 的桌面应用和插件嵌入协议引擎，头文件由 build.rs 通过 cbindgen 生成到
+//! `include/peersend.h`。
+//!
+//! 约定:
+//! - 所有字符串均为 UTF-8 编码、以 NUL 结尾
+//! - 返回的 `char *` 需通过 [`peersend_string_free`] 释放
+//! - 失败时返回错误码或 NULL，详细信息通过 [`peersend_last_error`] 获取
+//! - 设备和事件以 JSON 字符串传递
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use peersend_protocol::{
+    DeviceInfo, LocalSendClient, LocalSendConfig, ProtocolError, ProtocolEvent, ReceiveEvent,
+    SessionState,
+};
+use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// 错误码
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerSendStatus {
+    Ok = 0,
+    InvalidArgument = 1,
+    Io = 2,
+    Network = 3,
+    Rejected = 4,
+    Timeout = 5,
+    Cancelled = 6,
+    Other = 7,
+}
+
+impl From<&ProtocolError> for PeerSendStatus {
+    fn from(e: &ProtocolError) -> Self {
+        match e {
+            ProtocolError::Io(_) => PeerSendStatus::Io,
+            ProtocolError::Http(_) | ProtocolError::Status(_) => PeerSendStatus::Network,
+            ProtocolError::Rejected(_) | ProtocolError::PinRequired => PeerSendStatus::Rejected,
+            ProtocolError::Timeout => PeerSendStatus::Timeout,
+            ProtocolError::Cancelled => PeerSendStatus::Cancelled,
+            ProtocolError::InvalidConfig(_) => PeerSendStatus::InvalidArgument,
+            _ => PeerSendStatus::Other,
+        }
+    }
+}
+
+/// 传输状态
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerSendTransferState {
+    Pending = 0,
+    Transferring = 1,
+    Finished = 2,
+    Cancelled = 3,
+    Failed = 4,
+}
+
+/// 传输进度快照
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PeerSendProgress {
+    pub bytes_transferred: u64,
+    pub total_bytes: u64,
+    pub state: PeerSendTransferState,
+}
+
+/// 接收事件回调，event_json 仅在回调期间有效
+pub type PeerSendReceiveCallback =
+    Option<unsafe extern "C" fn(event_json: *const c_char, user_data: *mut c_void)>;
+
+/// 客户端句柄
+pub struct PeerSendClient {
+    runtime: Runtime,
+    config: LocalSendConfig,
+    client: LocalSendClient,
+    receive: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+/// 发送任务句柄
+pub struct PeerSendTransfer {
+    client: LocalSendClient,
+    progress: Arc<Mutex<PeerSendProgress>>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+/// 回调的用户数据指针，由调用方保证可跨线程使用
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+
+fn set_last_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+fn fail(e: &ProtocolError) -> PeerSendStatus {
+    set_last_error(e.to_string());
+    e.into()
+}
+
+fn invalid_argument(message: &str) -> PeerSendStatus {
+    set_last_error(message);
+    PeerSendStatus::InvalidArgument
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    CString::new(s.replace('\0', " ")).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// 读取 C 字符串，NULL 或非 UTF-8 时返回 None
+unsafe fn read_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+fn receive_event_json(event: &ReceiveEvent) -> serde_json::Value {
+    match event {
+        ReceiveEvent::Started { addr } => {
+            serde_json::json!({ "type": "started", "addr": addr.to_string() })
+        }
+        ReceiveEvent::SessionRequested {
+            session_id,
+            sender,
+            files,
+        } => serde_json::json!({
+            "type": "sessionRequested",
+            "sessionId": session_id,
+            "sender": sender,
+            "files": files,
+        }),
+        ReceiveEvent::FileReceived { session_id, path } => serde_json::json!({
+            "type": "fileReceived",
+            "sessionId": session_id,
+            "path": path.to_string_lossy(),
+        }),
+        ReceiveEvent::TextReceived { sender, text } => {
+            serde_json::json!({ "type": "textReceived", "sender": sender, "text": text })
+        }
+        ReceiveEvent::SessionFinished { session_id } => {
+            serde_json::json!({ "type": "sessionFinished", "sessionId": session_id })
+        }
+    }
+}
+
+/// 获取当前线程最近一次错误的描述
+///
+/// 返回的指针由库持有，在同一线程下一次出错前有效；没有错误时返回 NULL
+#[no_mangle]
+pub extern "C" fn peersend_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// 释放库返回的字符串
+///
+/// # Safety
+///
+/// s 必须为 NULL 或由本库返回且尚未释放的字符串
+#[no_mangle]
+pub unsafe extern "C" fn peersend_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// 创建客户端
+///
+/// config_json 为 NULL 时加载共享配置文件，否则按 JSON 解析，缺失字段使用默认值。
+/// 失败时返回 NULL
+///
+/// # Safety
+///
+/// config_json 必须为 NULL 或有效的 C 字符串
+#[no_mangle]
+pub unsafe extern "C" fn peersend_client_new(config_json: *const c_char) -> *mut PeerSendClient {
+    let config = if config_json.is_null() {
+        LocalSendConfig::load_or_init()
+    } else {
+        match read_str(config_json) {
+            Some(json) => serde_json::from_str::<LocalSendConfig>(json)
+                .map_err(ProtocolError::from)
+                .and_then(|config| config.validate().map(|()| config)),
+            None => {
+                invalid_argument("配置不是有效的 UTF-8 字符串");
+                return ptr::null_mut();
+            }
+        }
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            fail(&e);
+            return ptr::null_mut();
+        }
+    };
+
+    let runtime = match tokio::runtime::Builder::new_multi_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            set_last_error(format!("创建运行时失败: {}", e));
+            return ptr::null_mut();
+        }
+    };
+
+    let client = {
+        let _guard = runtime.enter();
+        LocalSendClient::new(config.clone())
+    };
+    Box::into_raw(Box::new(PeerSendClient {
+        runtime,
+        config,
+        client,
+        receive: Mutex::new(None),
+    }))
+}
+
+/// 释放客户端，进行中的操作将被取消
+///
+/// # Safety
+///
+/// client 必须为 NULL 或由 [`peersend_client_new`] 返回且尚未释放的句柄
+#[no_mangle]
+pub unsafe extern "C" fn peersend_client_free(client: *mut PeerSendClient) {
+    if client.is_null() {
+        return;
+    }
+    let client = Box::from_raw(client);
+    client.client.cancel();
+    if let Some(stop) = client.receive.lock().ok().and_then(|mut r| r.take()) {
+        let _ = stop.send(());
+    }
+    client.runtime.shutdown_background();
+}
+
+/// 在局域网中发现设备，阻塞 timeout_ms 毫秒
+///
+/// 返回设备列表的 JSON 数组，失败时返回 NULL
+///
+/// # Safety
+///
+/// client 必须为有效的客户端句柄
+#[no_mangle]
+pub unsafe extern "C" fn peersend_discover(
+    client: *const PeerSendClient,
+    timeout_ms: u32,
+) -> *mut c_char {
+    let Some(client) = client.as_ref() else {
+        invalid_argument("client 为 NULL");
+        return ptr::null_mut();
+    };
+
+    let timeout = Duration::from_millis(timeout_ms as u64);
+    match client.runtime.block_on(client.client.discover(timeout)) {
+        Ok(devices) => match serde_json::to_string(&devices) {
+            Ok(json) => into_c_string(json),
+            Err(e) => {
+                fail(&e.into());
+                ptr::null_mut()
+            }
+        },
+        Err(e) => {
+            fail(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// 开始向设备发送文件，立即返回
+///
+/// device_json 为 [`peersend_discover`] 返回的设备对象之一。
+/// 通过 [`peersend_transfer_poll`] 查询进度，完成后以 [`peersend_transfer_free`] 释放。
+/// 失败时返回 NULL
+///
+/// # Safety
+///
+/// client 必须为有效的客户端句柄，paths 必须指向 count 个有效的 C 字符串
+#[no_mangle]
+pub unsafe extern "C" fn peersend_send_files(
+    client: *const PeerSendClient,
+    device_json: *const c_char,
+    paths: *const *const c_char,
+    count: usize,
+) -> *mut PeerSendTransfer {
+    let Some(client) = client.as_ref() else {
+        invalid_argument("client 为 NULL");
+        return ptr::null_mut();
+    };
+    let device: DeviceInfo = match read_str(device_json).map(serde_json::from_str) {
+        Some(Ok(device)) => device,
+        Some(Err(e)) => {
+            fail(&e.into());
+            return ptr::null_mut();
+        }
+        None => {
+            invalid_argument("device_json 无效");
+            return ptr::null_mut();
+        }
+    };
+    if paths.is_null() || count == 0 {
+        invalid_argument("没有要发送的文件");
+        return ptr::null_mut();
+    }
+
+    let mut files = Vec::with_capacity(count);
+    let mut total_bytes = 0;
+    for &path in std::slice::from_raw_parts(paths, count) {
+        let Some(path) = read_str(path) else {
+            invalid_argument("文件路径无效");
+            return ptr::null_mut();
+        };
+        match std::fs::metadata(path) {
+            Ok(metadata) => total_bytes += metadata.len(),
+            Err(e) => {
+                fail(&e.into());
+                return ptr::null_mut();
+            }
+        }
+        files.push(PathBuf::from(path));
+    }
+
+    // 每个传输使用独立的客户端，事件总线和取消令牌互不影响
+    let transfer_client = {
+        let _guard = client.runtime.enter();
+        LocalSendClient::new(client.config.clone())
+    };
+    let progress = Arc::new(Mutex::new(PeerSendProgress {
+        bytes_transferred: 0,
+        total_bytes,
+        state: PeerSendTransferState::Pending,
+    }));
+    let error = Arc::new(Mutex::new(None));
+
+    let mut events = transfer_client.subscribe();
+    let tracked = progress.clone();
+    client.runtime.spawn(async move {
+        while let Ok(event) = events.recv().await {
+            let mut progress = tracked.lock().unwrap();
+            match event {
+                ProtocolEvent::FileProgress {
+                    bytes_transferred, ..
+                } => progress.bytes_transferred = bytes_transferred,
+                ProtocolEvent::SessionStateChanged {
+                    state: SessionState::Transferring,
+                    ..
+                } => progress.state = PeerSendTransferState::Transferring,
+                _ => {}
+            }
+        }
+    });
+
+    let sender = transfer_client.clone();
+    let finished = progress.clone();
+    let failed = error.clone();
+    client.runtime.spawn(async move {
+        let result = sender.send_files(&device, &files).await;
+        let mut progress = finished.lock().unwrap();
+        progress.state = match result {
+            Ok(_) => {
+                progress.bytes_transferred = progress.total_bytes;
+                PeerSendTransferState::Finished
+            }
+            Err(ProtocolError::Cancelled) => PeerSendTransferState::Cancelled,
+            Err(e) => {
+                *failed.lock().unwrap() = Some(e.to_string());
+                PeerSendTransferState::Failed
+            }
+        };
+    });
+
+    Box::into_raw(Box::new(PeerSendTransfer {
+        client: transfer_client,
+        progress,
+        error,
+    }))
+}
+
+/// 查询传输进度
+///
+/// # Safety
+///
+/// transfer 必须为有效的传输句柄，out 必须指向可写的 [`PeerSendProgress`]
+#[no_mangle]
+pub unsafe extern "C" fn peersend_transfer_poll(
+    transfer: *const PeerSendTransfer,
+    out: *mut PeerSendProgress,
+) -> PeerSendStatus {
+    let (Some(transfer), false) = (transfer.as_ref(), out.is_null()) else {
+        return invalid_argument("transfer 或 out 为 NULL");
+    };
+    *out = *transfer.progress.lock().unwrap();
+    PeerSendStatus::Ok
+}
+
+/// 获取失败传输的错误描述，未失败时返回 NULL
+///
+/// # Safety
+///
+/// transfer 必须为有效的传输句柄
+#[no_mangle]
+pub unsafe extern "C" fn peersend_transfer_error(transfer: *const PeerSendTransfer) -> *mut c_char {
+    transfer
+        .as_ref()
+        .and_then(|t| t.error.lock().unwrap().clone())
+        .map_or(ptr::null_mut(), into_c_string)
+}
+
+/// 取消传输
+///
+/// # Safety
+///
+/// transfer 必须为有效的传输句柄
+#[no_mangle]
+pub unsafe extern "C" fn peersend_transfer_cancel(transfer: *const PeerSendTransfer) {
+    if let Some(transfer) = transfer.as_ref() {
+        transfer.client.cancel();
+    }
+}
+
+/// 释放传输句柄，未完成的传输将被取消
+///
+/// # Safety
+///
+/// transfer 必须为 NULL 或由 [`peersend_send_files`] 返回且尚未释放的句柄
+#[no_mangle]
+pub unsafe extern "C" fn peersend_transfer_free(transfer: *mut PeerSendTransfer) {
+    if !transfer.is_null() {
+        let transfer = Box::from_raw(transfer);
+        transfer.client.cancel();
+    }
+}
+
+/// 启动接收服务，每个接收事件以 JSON 调用一次 callback
+///
+/// 回调在库的工作线程中执行，user_data 原样传回
+///
+/// # Safety
+///
+/// client 必须为有效的客户端句柄，user_data 必须在接收服务停止前保持有效且可跨线程访问
+#[no_mangle]
+pub unsafe extern "C" fn peersend_receive_start(
+    client: *const PeerSendClient,
+    callback: PeerSendReceiveCallback,
+    user_data: *mut c_void,
+) -> PeerSendStatus {
+    let Some(client) = client.as_ref() else {
+        return invalid_argument("client 为 NULL");
+    };
+    let Some(callback) = callback else {
+        return invalid_argument("callback 为 NULL");
+    };
+
+    let mut receive = client.receive.lock().unwrap();
+    if receive.is_some() {
+        return invalid_argument("接收服务已在运行");
+    }
+
+    let mut handle = match client.runtime.block_on(client.client.receive()) {
+        Ok(handle) => handle,
+        Err(e) => return fail(&e),
+    };
+    let (stop_tx, mut stop_rx) = oneshot::channel();
+    let user_data = UserData(user_data);
+    client.runtime.spawn(async move {
+        let user_data = user_data;
+        loop {
+            let event = tokio::select! {
+                _ = &mut stop_rx => break,
+                event = handle.next_event() => event,
+            };
+            let Some(event) = event else {
+                break;
+            };
+            if let Ok(json) = CString::new(receive_event_json(&event).to_string()) {
+                callback(json.as_ptr(), user_data.0);
+            }
+        }
+        handle.stop();
+    });
+
+    *receive = Some(stop_tx);
+    PeerSendStatus::Ok
+}
+
+/// 停止接收服务
+///
+/// # Safety
+///
+/// client 必须为有效的客户端句柄
+#[no_mangle]
+pub unsafe extern "C" fn peersend_receive_stop(client: *const PeerSendClient) {
+    if let Some(client) = client.as_ref() {
+        if let Some(stop) = client.receive.lock().unwrap().take() {
+            let _ = stop.send(());
+        }
+    }
+}
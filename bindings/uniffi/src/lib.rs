@@ -0,0 +1,563 @@
+//! PeerSend 协议的 Kotlin/Swift 绑定
+//!
+//! 通过 UniFFI 导出 [`LocalSendClient`] 外观，供 Android/iOS 应用复用同一份协议实现。
+//! 生成绑定:
+//!
+//! ```text
+//! cargo build -p peersend-uniffi --release
+//! cargo run -p peersend-uniffi --features cli --bin uniffi-bindgen -- \
+//!     generate --library target/release/libpeersend_uniffi.so --language kotlin --out-dir out
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use peersend_protocol::{
+    DeviceInfo, LocalSendClient, LocalSendConfig, ProtocolError, ProtocolEvent, ReceiveEvent,
+    SessionState,
+};
+use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
+
+uniffi::setup_scaffolding!();
+
+/// 错误
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum PeerSendError {
+    #[error("IO 错误: {message}")]
+    Io { message: String },
+    #[error("网络错误: {message}")]
+    Network { message: String },
+    #[error("请求被拒绝: {message}")]
+    Rejected { message: String },
+    #[error("操作超时")]
+    Timeout,
+    #[error("传输已取消")]
+    Cancelled,
+    #[error("配置无效: {message}")]
+    InvalidConfig { message: String },
+    #[error("{message}")]
+    Other { message: String },
+}
+
+impl From<ProtocolError> for PeerSendError {
+    fn from(e: ProtocolError) -> Self {
+        let message = e.to_string();
+        match e {
+            ProtocolError::Io(_) => PeerSendError::Io { message },
+            ProtocolError::Http(_) | ProtocolError::Status(_) => {
+                PeerSendError::Network { message }
+            }
+            ProtocolError::Rejected(_) | ProtocolError::PinRequired => {
+                PeerSendError::Rejected { message }
+            }
+            ProtocolError::Timeout => PeerSendError::Timeout,
+            ProtocolError::Cancelled => PeerSendError::Cancelled,
+            ProtocolError::InvalidConfig(_) => PeerSendError::InvalidConfig { message },
+            _ => PeerSendError::Other { message },
+        }
+    }
+}
+
+/// 客户端配置
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Config {
+    pub device_id: String,
+    pub device_name: String,
+    pub device_type: String,
+    pub api_key: String,
+    pub port: u16,
+    pub use_tls: bool,
+    pub download_dir: String,
+    pub transports: Vec<String>,
+    pub quick_save: QuickSave,
+    pub favorites: Vec<String>,
+}
+
+/// 快速保存模式
+#[derive(Debug, Clone, Copy, uniffi::Enum)]
+pub enum QuickSave {
+    Off,
+    Favorites,
+    On,
+}
+
+impl From<peersend_protocol::QuickSave> for QuickSave {
+    fn from(q: peersend_protocol::QuickSave) -> Self {
+        match q {
+            peersend_protocol::QuickSave::Off => Self::Off,
+            peersend_protocol::QuickSave::Favorites => Self::Favorites,
+            peersend_protocol::QuickSave::On => Self::On,
+        }
+    }
+}
+
+impl From<QuickSave> for peersend_protocol::QuickSave {
+    fn from(q: QuickSave) -> Self {
+        match q {
+            QuickSave::Off => Self::Off,
+            QuickSave::Favorites => Self::Favorites,
+            QuickSave::On => Self::On,
+        }
+    }
+}
+
+impl From<LocalSendConfig> for Config {
+    fn from(c: LocalSendConfig) -> Self {
+        Self {
+            device_id: c.device_id,
+            device_name: c.device_name,
+            device_type: c.device_type,
+            api_key: c.api_key,
+            port: c.port,
+            use_tls: c.use_tls,
+            download_dir: c.download_dir,
+            transports: c.transports,
+            quick_save: c.quick_save.into(),
+            favorites: c.favorites,
+        }
+    }
+}
+
+impl From<Config> for LocalSendConfig {
+    fn from(c: Config) -> Self {
+        Self {
+            device_id: c.device_id,
+            device_name: c.device_name,
+            device_type: c.device_type,
+            api_key: c.api_key,
+            port: c.port,
+            ip_mode: peersend_protocol::IpMode::Dual,
+            use_tls: c.use_tls,
+            download_dir: c.download_dir,
+            transports: c.transports,
+            quick_save: c.quick_save.into(),
+            favorites: c.favorites,
+            allowlist: Vec::new(),
+            blocklist: Vec::new(),
+            pin: None,
+            pin_policy: peersend_protocol::PinPolicy::All,
+            pin_devices: Vec::new(),
+            audit_log: false,
+            quarantine_extensions: peersend_protocol::DEFAULT_QUARANTINE_EXTENSIONS
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+            max_file_size: None,
+            max_session_size: None,
+            max_files: None,
+            retention: None,
+            duplicate_policy: peersend_protocol::DuplicatePolicy::Duplicate,
+            browse_port: None,
+            browse_password: None,
+            share_port: None,
+            metrics_port: None,
+            api_port: None,
+            api_token: None,
+            outboxes: Vec::new(),
+            webhooks: Vec::new(),
+            mqtt: None,
+            bandwidth_limit: None,
+            bandwidth_schedule: Vec::new(),
+            proxy: None,
+        }
+    }
+}
+
+/// 默认配置，设备 ID 和密钥随机生成
+#[uniffi::export]
+pub fn default_config() -> Config {
+    LocalSendConfig::default().into()
+}
+
+/// 设备信息
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+    pub device_type: String,
+    pub ip: String,
+    pub port: u16,
+    pub version: String,
+    pub protocol_version: String,
+    pub announcement_id: String,
+    pub uses_password: bool,
+    pub transports: Vec<String>,
+    pub addresses: Vec<String>,
+}
+
+impl From<DeviceInfo> for Device {
+    fn from(d: DeviceInfo) -> Self {
+        Self {
+            id: d.id,
+            name: d.name,
+            device_type: d.device_type,
+            ip: d.ip,
+            port: d.port,
+            version: d.version,
+            protocol_version: d.protocol_version,
+            announcement_id: d.announcement_id,
+            uses_password: d.uses_password,
+            transports: d.transports,
+            addresses: d.addresses,
+        }
+    }
+}
+
+impl From<Device> for DeviceInfo {
+    fn from(d: Device) -> Self {
+        Self {
+            id: d.id,
+            name: d.name,
+            device_type: d.device_type,
+            ip: d.ip,
+            port: d.port,
+            version: d.version,
+            protocol_version: d.protocol_version,
+            announcement_id: d.announcement_id,
+            uses_password: d.uses_password,
+            transports: d.transports,
+            addresses: d.addresses,
+        }
+    }
+}
+
+/// 一秒内的吞吐
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ThroughputSample {
+    /// 距开始传输的秒数
+    pub offset_secs: u64,
+    /// 这一秒内传输的字节数
+    pub bytes: u64,
+}
+
+impl From<peersend_protocol::ThroughputSample> for ThroughputSample {
+    fn from(s: peersend_protocol::ThroughputSample) -> Self {
+        Self {
+            offset_secs: s.offset_secs,
+            bytes: s.bytes,
+        }
+    }
+}
+
+/// 文件信息
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct File {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    pub file_type: String,
+}
+
+/// 会话状态
+#[derive(Debug, Clone, uniffi::Enum)]
+pub enum TransferState {
+    Waiting,
+    Transferring,
+    Finished,
+    Cancelled,
+    Error { message: String },
+}
+
+impl From<SessionState> for TransferState {
+    fn from(s: SessionState) -> Self {
+        match s {
+            SessionState::Waiting => TransferState::Waiting,
+            SessionState::Transferring => TransferState::Transferring,
+            SessionState::Finished => TransferState::Finished,
+            SessionState::Cancelled => TransferState::Cancelled,
+            SessionState::Error(message) => TransferState::Error { message },
+        }
+    }
+}
+
+/// 协议事件
+#[derive(Debug, Clone, uniffi::Enum)]
+pub enum Event {
+    DeviceDiscovered {
+        device: Device,
+    },
+    SessionStateChanged {
+        session_id: String,
+        state: TransferState,
+    },
+    FileProgress {
+        session_id: String,
+        file_id: String,
+        bytes_transferred: u64,
+        total_bytes: u64,
+    },
+    ReceiveStarted {
+        addr: String,
+    },
+    SessionRequested {
+        session_id: String,
+        sender: String,
+        files: Vec<File>,
+    },
+    FileReceived {
+        session_id: String,
+        path: String,
+    },
+    FileQuarantined {
+        session_id: String,
+        path: String,
+    },
+    TextReceived {
+        sender: String,
+        text: String,
+    },
+    SessionFinished {
+        session_id: String,
+    },
+    Error {
+        session_id: Option<String>,
+        message: String,
+    },
+}
+
+impl From<ProtocolEvent> for Event {
+    fn from(e: ProtocolEvent) -> Self {
+        match e {
+            ProtocolEvent::DeviceDiscovered(device) => Event::DeviceDiscovered {
+                device: device.into(),
+            },
+            ProtocolEvent::SessionRequested {
+                session_id,
+                sender,
+                files,
+            } => Event::SessionRequested {
+                session_id,
+                sender,
+                files: files.into_iter().map(Into::into).collect(),
+            },
+            ProtocolEvent::SessionStateChanged { session_id, state } => {
+                Event::SessionStateChanged {
+                    session_id,
+                    state: state.into(),
+                }
+            }
+            ProtocolEvent::FileProgress {
+                session_id,
+                file_id,
+                bytes_transferred,
+                total_bytes,
+            } => Event::FileProgress {
+                session_id,
+                file_id,
+                bytes_transferred,
+                total_bytes,
+            },
+            ProtocolEvent::FileQuarantined { session_id, path } => Event::FileQuarantined {
+                session_id,
+                path: path.to_string_lossy().into_owned(),
+            },
+            ProtocolEvent::TextReceived { sender, text } => Event::TextReceived { sender, text },
+            ProtocolEvent::Error {
+                session_id,
+                message,
+            } => Event::Error {
+                session_id,
+                message,
+            },
+        }
+    }
+}
+
+impl From<ReceiveEvent> for Event {
+    fn from(e: ReceiveEvent) -> Self {
+        match e {
+            ReceiveEvent::Started { addr } => Event::ReceiveStarted {
+                addr: addr.to_string(),
+            },
+            ReceiveEvent::SessionRequested {
+                session_id,
+                sender,
+                files,
+            } => Event::SessionRequested {
+                session_id,
+                sender,
+                files: files.into_iter().map(Into::into).collect(),
+            },
+            ReceiveEvent::FileReceived { session_id, path } => Event::FileReceived {
+                session_id,
+                path: path.to_string_lossy().into_owned(),
+            },
+            ReceiveEvent::TextReceived { sender, text } => Event::TextReceived { sender, text },
+            ReceiveEvent::SessionFinished { session_id } => Event::SessionFinished { session_id },
+        }
+    }
+}
+
+impl From<peersend_protocol::FileInfo> for File {
+    fn from(f: peersend_protocol::FileInfo) -> Self {
+        Self {
+            id: f.id,
+            name: f.name,
+            size: f.size,
+            file_type: f.file_type,
+        }
+    }
+}
+
+/// 由宿主应用实现的事件监听器，回调在后台线程中执行
+#[uniffi::export(with_foreign)]
+pub trait EventListener: Send + Sync {
+    fn on_event(&self, event: Event);
+}
+
+/// PeerSend 客户端
+#[derive(uniffi::Object)]
+pub struct PeerSendClient {
+    // 在 Drop 中取出并后台关闭，避免在异步上下文中阻塞
+    runtime: Option<Runtime>,
+    client: LocalSendClient,
+    receive: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl PeerSendClient {
+    /// 使用指定配置创建客户端
+    #[uniffi::constructor]
+    pub fn new(config: Config) -> Result<Arc<Self>, PeerSendError> {
+        let config = LocalSendConfig::from(config);
+        config.validate()?;
+        Self::build(config)
+    }
+
+    /// 使用共享配置文件创建客户端
+    #[uniffi::constructor]
+    pub fn with_shared_config() -> Result<Arc<Self>, PeerSendError> {
+        Self::build(LocalSendConfig::load_or_init()?)
+    }
+
+    /// 当前配置
+    pub fn config(&self) -> Config {
+        self.client.config().clone().into()
+    }
+
+    /// 在局域网中发现设备
+    pub async fn discover(&self, timeout_ms: u64) -> Result<Vec<Device>, PeerSendError> {
+        let devices = self
+            .client
+            .discover(Duration::from_millis(timeout_ms))
+            .await?;
+        Ok(devices.into_iter().map(Into::into).collect())
+    }
+
+    /// 检查指定地址是否运行 LocalSend
+    pub async fn check_device(&self, ip: String) -> Option<Device> {
+        self.client.check_device(&ip).await.map(Into::into)
+    }
+
+    /// 向设备发送文件，返回会话 ID
+    pub async fn send_files(
+        &self,
+        device: Device,
+        paths: Vec<String>,
+    ) -> Result<String, PeerSendError> {
+        let session = self.client.send_files(&device.into(), &paths).await?;
+        Ok(session.id)
+    }
+
+    /// 向设备发送文本
+    pub async fn send_text(&self, device: Device, text: String) -> Result<(), PeerSendError> {
+        Ok(self.client.send_text(&device.into(), &text).await?)
+    }
+
+    /// 会话最近每秒的吞吐，用于绘制速度曲线，会话不存在时返回 None
+    pub async fn get_transfer_timeseries(&self, session_id: String) -> Option<Vec<ThroughputSample>> {
+        let sessions = self.client.session_manager().lock().await.clone();
+        let samples = sessions.get_transfer_timeseries(&session_id).await?;
+        Some(samples.into_iter().map(Into::into).collect())
+    }
+
+    /// 订阅发现、会话和进度事件
+    pub fn set_listener(&self, listener: Arc<dyn EventListener>) {
+        let mut events = self.client.subscribe();
+        self.runtime().spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => listener.on_event(event.into()),
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// 启动接收服务，接收事件通过 listener 回调
+    pub fn start_receiving(&self, listener: Arc<dyn EventListener>) -> Result<(), PeerSendError> {
+        let mut receive = self.receive.lock().unwrap();
+        if receive.is_some() {
+            return Err(PeerSendError::Other {
+                message: "接收服务已在运行".to_string(),
+            });
+        }
+
+        let mut handle = self.runtime().block_on(self.client.receive())?;
+        let (stop_tx, mut stop_rx) = oneshot::channel();
+        self.runtime().spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    _ = &mut stop_rx => break,
+                    event = handle.next_event() => event,
+                };
+                match event {
+                    Some(event) => listener.on_event(event.into()),
+                    None => break,
+                }
+            }
+            handle.stop();
+        });
+
+        *receive = Some(stop_tx);
+        Ok(())
+    }
+
+    /// 停止接收服务
+    pub fn stop_receiving(&self) {
+        if let Some(stop) = self.receive.lock().unwrap().take() {
+            let _ = stop.send(());
+        }
+    }
+
+    /// 取消所有进行中的操作，之后客户端不可再用
+    pub fn cancel(&self) {
+        self.client.cancel();
+    }
+}
+
+impl PeerSendClient {
+    fn runtime(&self) -> &Runtime {
+        self.runtime.as_ref().expect("运行时仅在 Drop 中取出")
+    }
+
+    fn build(config: LocalSendConfig) -> Result<Arc<Self>, PeerSendError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| PeerSendError::Io {
+                message: e.to_string(),
+            })?;
+        let client = {
+            let _guard = runtime.enter();
+            LocalSendClient::new(config)
+        };
+        Ok(Arc::new(Self {
+            runtime: Some(runtime),
+            client,
+            receive: Mutex::new(None),
+        }))
+    }
+}
+
+impl Drop for PeerSendClient {
+    fn drop(&mut self) {
+        self.stop_receiving();
+        self.client.cancel();
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
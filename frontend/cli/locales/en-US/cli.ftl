@@ -0,0 +1,220 @@
+# PeerSend CLI message catalog (English)
+#
+# Add new messages to every catalog with the same id
+
+## Common errors
+
+error-load-config = Failed to read the configuration
+error-config-dir = Could not determine the configuration directory
+error-open-audit = Failed to open the audit log
+error-verify-audit = Audit log verification failed
+error-open-journal = Failed to open the session journal
+error-read-journal = Failed to read the session journal
+error-discard-session = Failed to discard the session
+error-read-inbox = Failed to read the inbox
+error-write-inbox = Failed to save the inbox
+error-control-connect = Could not connect to the receiver daemon's control channel. Is the daemon running?
+error-control-unexpected = Unexpected reply from the control channel
+error-current-exe = Failed to get the program path
+error-current-dir = Failed to get the current directory
+device-not-found = Device not found: { $device }
+otlp-failed = Failed to enable OTLP export: { $error }
+
+## Network
+
+network-started = PeerSend network started
+network-stopped = PeerSend network stopped
+network-status = Status: { $running ->
+        [yes] running
+       *[no] stopped
+    }
+network-pid = PID: { $pid }
+network-peers = Peers: { $count }
+network-name = Network name: { $name }
+
+## System service
+
+service-installed = Service { $name } installed
+service-uninstalled = Service { $name } uninstalled
+service-started = Service { $name } started
+service-stopped = Service { $name } stopped
+service-restarted = Service { $name } restarted
+service-reloaded = Service { $name } reloaded its configuration
+service-status = Service { $name }: { $status }
+service-healthy = Service { $name } is healthy
+service-unhealthy = Service { $name } failed its health check
+
+## File manager integration
+
+bridge-sent = Sent, session { $session }
+bridge-written = Wrote { $path }
+
+## Access control
+
+access-allowlist = Allowlist: { $rules }
+access-allowlist-empty = not set, all devices are allowed
+access-blocklist = Blocklist: { $rules }
+access-blocklist-empty = none
+access-already-listed = { $rule } is already in the list
+access-not-listed = { $rule } is not in the list
+config-updated = Updated. A running receiver applies the change after reloading its configuration
+
+## Config bundles
+
+error-read-bundle = Failed to read the config bundle
+config-exported = Exported the config bundle to { $path }
+config-imported = Imported the configuration. A running receiver applies it after reloading its configuration
+
+## Device groups
+
+group-list-empty = No device groups
+group-not-found = Device group not found: { $name }
+group-send-failed = Failed to send to { $device }: { $error }
+group-send-incomplete = Sending failed for { $count ->
+        [one] { $count } device
+       *[other] { $count } devices
+    }
+
+## Session summary
+
+summary-result = { $device }: { $transferred } transferred, { $skipped } skipped, { $failed } failed
+summary-stats = { $size } in { $duration } s, average { $speed }/s
+summary-verification = { $verified } verified, { $mismatched } hash mismatches
+summary-file-failed = Failed: { $name }: { $reason }
+summary-warning = Note: { $name }: { $reason }
+
+## Audit log
+
+audit-verified = { $records ->
+        [one] { $records } record
+       *[other] { $records } records
+    }, hash chain intact
+audit-head = Latest record hash: { $head }
+audit-exported = Exported { $records ->
+        [one] { $records } record
+       *[other] { $records } records
+    } to { $path }
+
+## Cleanup
+
+cleanup-no-policy = No cleanup policy (retention) is configured
+cleanup-planned = Would clean up { $count ->
+        [one] { $count } file
+       *[other] { $count } files
+    }, { $size } in total
+cleanup-done = Cleaned up { $count ->
+        [one] { $count } file
+       *[other] { $count } files
+    }, { $size } in total
+
+## Doctor
+
+doctor-config-invalid = [problem] Invalid configuration
+doctor-config-valid = [ok] Configuration is valid
+doctor-download-dir-ok = [ok] Download directory { $dir }
+doctor-download-dir-created = [fixed] Created download directory { $dir }
+doctor-download-dir-create-failed = [problem] Failed to create download directory { $dir }: { $error }
+doctor-download-dir-missing = [problem] Download directory { $dir } does not exist
+doctor-local-network-ok = [ok] Local network access
+doctor-local-network-denied = [problem] { $error }
+doctor-local-network-unknown = [unknown] Could not determine local network access
+doctor-firewall-read-failed = [problem] Could not read firewall rules: { $error }
+doctor-firewall-ok = [ok] Firewall allows discovery port { $discovery } and receive port { $http }
+doctor-firewall-missing = [problem] Missing firewall rules: { $rules }
+doctor-firewall-fixed = [fixed] Added firewall rules: { $rules }
+doctor-firewall-fix-failed = [problem] Failed to add firewall rules, run as administrator: { $error }
+doctor-problems = Found { $count ->
+        [one] { $count } problem
+       *[other] { $count } problems
+    }
+doctor-problems-fix-hint = Found { $count ->
+        [one] { $count } problem
+       *[other] { $count } problems
+    }, try --fix to repair
+doctor-no-problems = No problems found
+
+## Session journal
+
+journal-empty = No incomplete sessions
+journal-session = { $session }  from { $peer }  { $received } / { $total }
+journal-discarded = Discarded session { $session }, deleted { $count ->
+        [one] { $count } incomplete file
+       *[other] { $count } incomplete files
+    }
+journal-not-found = No incomplete session { $session }
+
+## Inbox
+
+inbox-empty = The inbox is empty
+inbox-not-found = No message { $id } in the inbox
+inbox-copied = Copied message { $id } to the clipboard
+inbox-copy-hold = Serving the clipboard for up to { $secs } seconds, until a clipboard manager or another program takes it over
+inbox-deleted = Deleted message { $id }
+
+## Pending requests
+
+requests-empty = No requests awaiting confirmation
+requests-pending = { $session }  from { $sender }  { $size }  { $files }
+requests-accepted = Accepted request { $session }
+requests-rejected = Rejected request { $session }
+
+## Events
+
+error-api-disabled = The management API is not enabled. Set api_port and api_token in the configuration
+error-api-connect = Could not connect to the management API: { $url }
+
+## Open gate
+
+gate-open = Open for receiving: { $minutes }m { $seconds }s left. Requests from the LAN are accepted without prompting
+gate-closed = Not open for receiving. Requests are confirmed according to the quick save setting
+
+## Pairing
+
+pair-already-trusted = { $name } is already trusted
+pair-device = Device: { $name } ({ $ip })
+pair-fingerprint = Fingerprint: { $id }
+pair-code = Pairing code  { $code }
+pair-instructions = Run `peersend pair` on the other device and compare the pairing codes shown on both.
+pair-confirm = Do the pairing codes match? [y/N]
+pair-not-confirmed = Pairing codes differ or were not confirmed; the device is not trusted. If they really differ, someone on the network may be intercepting
+pair-trusted = Marked { $name } as trusted
+
+## Screenshot
+
+screenshot-failed = Screenshot failed
+screenshot-captured = Captured screenshot: { $path }
+screenshot-sent = Sent to { $device }
+pin-prompt = { $device } requires a PIN:
+pin-retry = Wrong PIN, try again for { $device }:
+
+## Share links
+
+share-not-found = Share link not found: { $token }
+share-revoked = Share link revoked
+share-no-port = share_port is not configured, the receiver does not serve share links
+share-no-host = Could not determine the local address, use --host to specify it
+share-never-expires = never
+
+## EasyTier process management
+
+daemon-systemd-starting = Starting easytier via systemd...
+daemon-systemd-started = easytier started via systemd
+daemon-systemd-stopped = easytier stopped via systemd
+daemon-process-starting = Starting the easytier-core process...
+daemon-exec = Running: { $command }
+daemon-core-started = easytier-core started (PID: { $pid })
+daemon-already-running = easytier is already running, stopping it first...
+daemon-started = easytier started
+daemon-sigterm = Sending SIGTERM to PID { $pid }...
+daemon-sigkill = Sending SIGKILL...
+daemon-stopped = easytier stopped
+daemon-no-pid = PID file not found, cannot stop
+daemon-waiting-rpc = Waiting for RPC port { $addr } ...
+daemon-rpc-ready = RPC port is ready
+daemon-waiting = Waiting... ({ $attempt })
+daemon-rpc-timeout = Timed out connecting to the RPC port
+daemon-core-not-found = Could not find the easytier-core binary
+error-systemctl-start = Failed to run systemctl start
+error-spawn-core = Failed to start easytier-core
+error-write-pid = Failed to write the PID file
+error-which = Failed to run which
@@ -0,0 +1,196 @@
+# PeerSend 命令行消息目录 (简体中文)
+#
+# 新增消息时同时在其他语言的目录中添加相同的 id
+
+## 通用错误
+
+error-load-config = 读取配置失败
+error-config-dir = 无法确定配置目录
+error-open-audit = 打开审计日志失败
+error-verify-audit = 审计日志校验失败
+error-open-journal = 打开会话日志失败
+error-read-journal = 读取会话日志失败
+error-discard-session = 清理会话失败
+error-read-inbox = 读取收件箱失败
+error-write-inbox = 保存收件箱失败
+error-control-connect = 无法连接接收守护进程的控制通道，守护进程是否在运行?
+error-control-unexpected = 控制通道返回了意外的回复
+error-current-exe = 获取程序路径失败
+error-current-dir = 获取当前目录失败
+device-not-found = 未找到设备: { $device }
+otlp-failed = 启用 OTLP 导出失败: { $error }
+
+## 网络
+
+network-started = PeerSend 网络已启动
+network-stopped = PeerSend 网络已停止
+network-status = 状态: { $running ->
+        [yes] 运行中
+       *[no] 已停止
+    }
+network-pid = PID: { $pid }
+network-peers = 对等点数量: { $count }
+network-name = 网络名称: { $name }
+
+## 系统服务
+
+service-installed = 服务 { $name } 已安装
+service-uninstalled = 服务 { $name } 已卸载
+service-started = 服务 { $name } 已启动
+service-stopped = 服务 { $name } 已停止
+service-restarted = 服务 { $name } 已重启
+service-reloaded = 服务 { $name } 已重新加载配置
+service-status = 服务 { $name }: { $status }
+service-healthy = 服务 { $name } 运行正常
+service-unhealthy = 服务 { $name } 自检失败
+
+## 文件管理器集成
+
+bridge-sent = 已发送，会话 { $session }
+bridge-written = 已写入 { $path }
+
+## 访问控制
+
+access-allowlist = 允许列表: { $rules }
+access-allowlist-empty = 未设置，允许所有设备
+access-blocklist = 屏蔽列表: { $rules }
+access-blocklist-empty = 无
+access-already-listed = { $rule } 已在列表中
+access-not-listed = 列表中没有 { $rule }
+config-updated = 已更新，运行中的接收服务重新加载配置后生效
+
+## 配置包
+
+error-read-bundle = 读取配置包失败
+config-exported = 已导出配置包到 { $path }
+config-imported = 已导入配置，运行中的接收服务重新加载配置后生效
+
+## 设备组
+
+group-list-empty = 没有设备组
+group-not-found = 未找到设备组: { $name }
+group-send-failed = 发送到 { $device } 失败: { $error }
+group-send-incomplete = { $count } 台设备发送失败
+
+## 会话摘要
+
+summary-result = { $device }: 完成 { $transferred } 个文件，跳过 { $skipped } 个，失败 { $failed } 个
+summary-stats = 共 { $size }，用时 { $duration } 秒，平均 { $speed }/s
+summary-verification = 哈希校验通过 { $verified } 个，不一致 { $mismatched } 个
+summary-file-failed = 失败: { $name }: { $reason }
+summary-warning = 注意: { $name }: { $reason }
+
+## 审计日志
+
+audit-verified = 共 { $records } 条记录，哈希链完整
+audit-head = 最新记录哈希: { $head }
+audit-exported = 已导出 { $records } 条记录到 { $path }
+
+## 清理
+
+cleanup-no-policy = 未配置清理策略 (retention)
+cleanup-planned = 将清理 { $count } 个文件，共 { $size }
+cleanup-done = 已清理 { $count } 个文件，共 { $size }
+
+## 自检
+
+doctor-config-invalid = [问题] 配置无效
+doctor-config-valid = [正常] 配置有效
+doctor-download-dir-ok = [正常] 下载目录 { $dir }
+doctor-download-dir-created = [已修复] 已创建下载目录 { $dir }
+doctor-download-dir-create-failed = [问题] 创建下载目录 { $dir } 失败: { $error }
+doctor-download-dir-missing = [问题] 下载目录 { $dir } 不存在
+doctor-local-network-ok = [正常] 本地网络访问权限
+doctor-local-network-denied = [问题] { $error }
+doctor-local-network-unknown = [未知] 无法确定本地网络访问权限
+doctor-firewall-read-failed = [问题] 无法读取防火墙规则: { $error }
+doctor-firewall-ok = [正常] 防火墙已放行发现端口 { $discovery } 和接收端口 { $http }
+doctor-firewall-missing = [问题] 缺少防火墙规则: { $rules }
+doctor-firewall-fixed = [已修复] 已添加防火墙规则: { $rules }
+doctor-firewall-fix-failed = [问题] 添加防火墙规则失败，请以管理员身份运行: { $error }
+doctor-problems = 发现 { $count } 个问题
+doctor-problems-fix-hint = 发现 { $count } 个问题，可使用 --fix 尝试修复
+doctor-no-problems = 未发现问题
+
+## 会话日志
+
+journal-empty = 没有未完成的会话
+journal-session = { $session }  来自 { $peer }  { $received } / { $total }
+journal-discarded = 已放弃会话 { $session }，删除 { $count } 个未完成的文件
+journal-not-found = 没有未完成的会话 { $session }
+
+## 收件箱
+
+inbox-empty = 收件箱中没有文本
+inbox-not-found = 收件箱中没有第 { $id } 条文本
+inbox-copied = 已把第 { $id } 条文本复制到剪贴板
+inbox-copy-hold = 剪贴板内容由本进程提供，最多等待 { $secs } 秒，直到剪贴板管理器或其他程序接管
+inbox-deleted = 已删除第 { $id } 条文本
+
+## 待确认的请求
+
+requests-empty = 没有等待确认的请求
+requests-pending = { $session }  来自 { $sender }  { $size }  { $files }
+requests-accepted = 已接受请求 { $session }
+requests-rejected = 已拒绝请求 { $session }
+
+## 事件
+
+error-api-disabled = 未启用管理 API，请在配置中设置 api_port 和 api_token
+error-api-connect = 无法连接管理 API: { $url }
+
+## 开放接收
+
+gate-open = 正在开放接收，剩余 { $minutes } 分 { $seconds } 秒，期间来自局域网的请求自动接受
+gate-closed = 未开放接收，请求按快速保存设置确认
+
+## 配对
+
+pair-already-trusted = { $name } 已是受信任的设备
+pair-device = 设备: { $name } ({ $ip })
+pair-fingerprint = 指纹: { $id }
+pair-code = 配对码  { $code }
+pair-instructions = 请在对方设备上运行 `peersend pair` 并比对两端显示的配对码。
+pair-confirm = 两端的配对码一致吗? [y/N]
+pair-not-confirmed = 配对码不一致或未确认，未信任该设备。若确认不一致，网络中可能存在中间人
+pair-trusted = 已将 { $name } 标记为受信任
+
+## 截屏
+
+screenshot-failed = 截屏失败
+screenshot-captured = 已截屏: { $path }
+screenshot-sent = 已发送到 { $device }
+pin-prompt = { $device } 要求输入 PIN:
+pin-retry = PIN 不正确，请重新输入 { $device } 的 PIN:
+
+## 分享链接
+
+share-not-found = 未找到分享链接: { $token }
+share-revoked = 已撤销分享链接
+share-no-port = 未配置 share_port，接收服务不会提供分享链接
+share-no-host = 无法确定本机地址，请使用 --host 指定
+share-never-expires = 不过期
+
+## EasyTier 进程管理
+
+daemon-systemd-starting = 通过 systemd 启动 easytier...
+daemon-systemd-started = easytier 已通过 systemd 启动
+daemon-systemd-stopped = easytier 已通过 systemd 停止
+daemon-process-starting = 启动 easytier-core 进程...
+daemon-exec = 执行: { $command }
+daemon-core-started = easytier-core 已启动 (PID: { $pid })
+daemon-already-running = easytier 已在运行，先停止...
+daemon-started = easytier 已启动
+daemon-sigterm = 发送 SIGTERM 到 PID { $pid }...
+daemon-sigkill = 发送 SIGKILL...
+daemon-stopped = easytier 已停止
+daemon-no-pid = 未找到 PID 文件，无法停止
+daemon-waiting-rpc = 等待 RPC 端口 { $addr } ...
+daemon-rpc-ready = RPC 端口已就绪
+daemon-waiting = 等待中... ({ $attempt })
+daemon-rpc-timeout = RPC 端口连接超时
+daemon-core-not-found = 找不到 easytier-core 二进制文件
+error-systemctl-start = 执行 systemctl start 失败
+error-spawn-core = 启动 easytier-core 失败
+error-write-pid = 写入 PID 文件失败
+error-which = 执行 which 命令失败
@@ -2,5 +2,7 @@
 //!
 //! 复制自 easytier-core/src/arch/mod.rs
 
+// 复制来的其余功能暂未全部使用
 #[cfg(target_os = "windows")]
+#[allow(dead_code)]
 pub mod windows;
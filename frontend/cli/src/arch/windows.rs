@@ -350,6 +350,97 @@ pub fn remove_interface_firewall_rules(interface_name: &str) -> anyhow::Result<(
     Ok(())
 }
 
+/// 设备发现的 UDP 入站规则名称
+fn discovery_rule_name(port: u16) -> String {
+    format!("PeerSend Discovery (UDP {})", port)
+}
+
+/// 接收服务的 TCP 入站规则名称
+fn receiver_rule_name(port: u16) -> String {
+    format!("PeerSend Receiver (TCP {})", port)
+}
+
+/// 按端口放行设备发现 (UDP 多播) 和接收服务 (HTTP) 的入站流量
+///
+/// 只放行程序的规则在部分机器上仍会拦截多播发现，按端口的规则不依赖可执行文件路径。
+/// 同名规则先删除再添加，可重复调用。需要管理员权限
+pub fn add_discovery_firewall_rules(discovery_port: u16, http_port: u16) -> anyhow::Result<()> {
+    let _com = ComInitializer::new()?;
+    let policy: INetFwPolicy2 = unsafe {
+        CoCreateInstance(
+            &windows::Win32::NetworkManagement::WindowsFirewall::NetFwPolicy2,
+            None,
+            CLSCTX_ALL,
+        )
+    }?;
+
+    let rules = [
+        (
+            discovery_rule_name(discovery_port),
+            17,
+            discovery_port,
+            "Allow PeerSend device discovery",
+        ),
+        (
+            receiver_rule_name(http_port),
+            6,
+            http_port,
+            "Allow PeerSend to receive files",
+        ),
+    ];
+    for (name, protocol, port, description) in rules {
+        let rule: INetFwRule = unsafe {
+            CoCreateInstance(
+                &windows::Win32::NetworkManagement::WindowsFirewall::NetFwRule,
+                None,
+                CLSCTX_ALL,
+            )
+        }?;
+        let name = BSTR::from(&name);
+        unsafe {
+            rule.SetName(&name)?;
+            rule.SetDescription(&BSTR::from(description))?;
+            // 须先设置协议才能设置端口
+            rule.SetProtocol(protocol)?;
+            rule.SetLocalPorts(&BSTR::from(port.to_string()))?;
+            rule.SetAction(NET_FW_ACTION_ALLOW)?;
+            rule.SetDirection(NET_FW_RULE_DIR_IN)?;
+            rule.SetEnabled(windows::Win32::Foundation::VARIANT_TRUE)?;
+            rule.SetProfiles(
+                NET_FW_PROFILE2_PRIVATE.0 | NET_FW_PROFILE2_PUBLIC.0 | NET_FW_PROFILE2_DOMAIN.0,
+            )?;
+            rule.SetGrouping(&BSTR::from("PeerSend"))?;
+
+            let rules = policy.Rules()?;
+            rules.Remove(&name)?;
+            rules.Add(&rule)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 列出缺少的设备发现和接收服务入站规则名称
+pub fn missing_discovery_firewall_rules(
+    discovery_port: u16,
+    http_port: u16,
+) -> anyhow::Result<Vec<String>> {
+    let _com = ComInitializer::new()?;
+    let policy: INetFwPolicy2 = unsafe {
+        CoCreateInstance(
+            &windows::Win32::NetworkManagement::WindowsFirewall::NetFwPolicy2,
+            None,
+            CLSCTX_ALL,
+        )
+    }?;
+
+    let rules = unsafe { policy.Rules()? };
+    Ok([discovery_rule_name(discovery_port), receiver_rule_name(http_port)]
+        .into_iter()
+        .filter(|name| unsafe { rules.Item(&BSTR::from(name)) }.is_err())
+        .collect())
+}
+
 /// 移除程序相关的防火墙规则
 pub fn remove_program_from_firewall() -> anyhow::Result<()> {
     let _com = ComInitializer::new()?;
@@ -0,0 +1,278 @@
+//! 桌面分享桥接 (Linux)
+//!
+//! 在会话总线上注册 `org.peersend.Share`，文件管理器的「通过 PeerSend 发送」菜单
+//! 调用其中的 `SendFiles` 把文件交给 PeerSend 发送。
+//!
+//! 同时监听 KDE Connect 的 `shareReceived` 信号：手机经 KDE Connect 分享到本机的文件
+//! 落盘后，转发给指定的 PeerSend 设备。GSConnect 不在总线上公布收到的分享，
+//! 只能使用发送菜单
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use peersend_protocol::client::LocalSendClient;
+use peersend_protocol::{DeviceInfo, LocalSendConfig};
+use zbus::{fdo, interface, MatchRule, MessageStream};
+
+/// 总线名称
+pub const BUS_NAME: &str = "org.peersend.Share";
+
+/// 对象路径
+pub const OBJECT_PATH: &str = "/org/peersend/Share";
+
+/// KDE Connect 分享插件的接口
+const KDECONNECT_SHARE_INTERFACE: &str = "org.kde.kdeconnect.device.share";
+
+/// 查找目标设备时的发现时长
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Dolphin 服务菜单
+const DOLPHIN_SERVICE_MENU: &str = "\
+[Desktop Entry]
+Type=Service
+MimeType=all/allfiles;
+X-KDE-ServiceTypes=KonqPopupMenu/Plugin
+Actions=peersend
+
+[Desktop Action peersend]
+Name=Send via PeerSend
+Name[zh_CN]=通过 PeerSend 发送
+Icon=document-send
+Exec={exe} bridge send %U
+";
+
+/// Nautilus 脚本，选中的文件通过环境变量传入
+const NAUTILUS_SCRIPT: &str = "\
+#!/bin/sh
+# 通过 PeerSend 发送选中的文件
+exec '{exe}' bridge send $NAUTILUS_SCRIPT_SELECTED_URIS
+";
+
+/// 总线激活文件，调用 SendFiles 时按需启动桥接服务
+const DBUS_SERVICE: &str = "\
+[D-BUS Service]
+Name=org.peersend.Share
+Exec={exe} bridge run
+";
+
+/// 会话总线上的分享服务
+struct ShareService {
+    client: LocalSendClient,
+    /// 未指定设备时的默认目标
+    default_target: Option<String>,
+}
+
+#[interface(name = "org.peersend.Share1")]
+impl ShareService {
+    /// 发送文件，uris 为 `file://` URI 或本地路径，device 为空时使用默认目标
+    ///
+    /// 返回会话 ID
+    async fn send_files(&self, uris: Vec<String>, device: String) -> fdo::Result<String> {
+        let target = if device.is_empty() {
+            self.default_target
+                .clone()
+                .ok_or_else(|| fdo::Error::InvalidArgs("未指定目标设备".to_string()))?
+        } else {
+            device
+        };
+        let paths = uris
+            .iter()
+            .map(|uri| to_path(uri))
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| fdo::Error::InvalidArgs(e.to_string()))?;
+
+        let device = resolve_device(&self.client, &target)
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        let session = self
+            .client
+            .send_files(&device, &paths)
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        Ok(session.id)
+    }
+
+    /// 列出局域网中的设备，每项为 (ID, 名称, IP)
+    async fn list_devices(&self) -> fdo::Result<Vec<(String, String, String)>> {
+        let devices = self
+            .client
+            .discover(DISCOVERY_TIMEOUT)
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        Ok(devices.into_iter().map(|d| (d.id, d.name, d.ip)).collect())
+    }
+}
+
+/// 运行桥接服务，直到 shutdown 完成
+///
+/// forward_to 为 KDE Connect 收到的分享的转发目标，同时作为 `SendFiles` 的默认目标
+pub async fn run<F>(forward_to: Option<String>, shutdown: F) -> Result<()>
+where
+    F: std::future::Future<Output = ()>,
+{
+    let config = LocalSendConfig::load_or_init().context("读取配置失败")?;
+    let client = LocalSendClient::new(config);
+    let service = ShareService {
+        client: client.clone(),
+        default_target: forward_to.clone(),
+    };
+
+    let connection = zbus::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, service)?
+        .build()
+        .await
+        .context("注册会话总线服务失败")?;
+    tracing::info!(bus_name = BUS_NAME, "分享服务已在会话总线上注册");
+
+    let rule = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface(KDECONNECT_SHARE_INTERFACE)?
+        .member("shareReceived")?
+        .build();
+    let mut shares = MessageStream::for_match_rule(rule, &connection, None).await?;
+
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            message = shares.next() => {
+                let Some(message) = message else { break };
+                let url: String = match message.and_then(|m| m.body().deserialize()) {
+                    Ok(url) => url,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "无法解析 KDE Connect 分享信号");
+                        continue;
+                    }
+                };
+                let Some(target) = &forward_to else {
+                    tracing::debug!(%url, "未设置转发目标，忽略 KDE Connect 分享");
+                    continue;
+                };
+                let client = client.clone();
+                let target = target.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = forward(&client, &url, &target).await {
+                        tracing::warn!(error = format!("{:#}", e), "转发 KDE Connect 分享失败");
+                    }
+                });
+            }
+        }
+    }
+
+    client.cancel();
+    Ok(())
+}
+
+/// 转发 KDE Connect 收到的文件
+async fn forward(client: &LocalSendClient, url: &str, target: &str) -> Result<()> {
+    let path = to_path(url)?;
+    // KDE Connect 也会分享链接和文本，只转发本地文件
+    if !path.is_file() {
+        return Ok(());
+    }
+
+    let device = resolve_device(client, target).await?;
+    client.send_files(&device, &[&path]).await?;
+    tracing::info!(path = %path.display(), device = %device.name, "已转发 KDE Connect 分享");
+    Ok(())
+}
+
+/// 通过会话总线上的桥接服务发送文件
+pub async fn send(paths: &[String], device: Option<String>) -> Result<String> {
+    let uris = paths
+        .iter()
+        .map(|p| Ok(to_path(p)?.to_string_lossy().into_owned()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let connection = zbus::Connection::session().await.context("连接会话总线失败")?;
+    let reply = connection
+        .call_method(
+            Some(BUS_NAME),
+            OBJECT_PATH,
+            Some("org.peersend.Share1"),
+            "SendFiles",
+            &(uris, device.unwrap_or_default()),
+        )
+        .await
+        .context("调用分享服务失败")?;
+    Ok(reply.body().deserialize()?)
+}
+
+/// 为当前用户安装文件管理器菜单和总线激活文件，返回写入的文件
+pub fn install() -> Result<Vec<PathBuf>> {
+    let exe = std::env::current_exe().context("获取程序路径失败")?;
+    let exe = exe.to_string_lossy();
+    let data_dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .context("无法确定用户数据目录")?;
+
+    let files = [
+        (
+            data_dir.join("kio/servicemenus/peersend.desktop"),
+            DOLPHIN_SERVICE_MENU,
+            0o755,
+        ),
+        (
+            data_dir.join("nautilus/scripts/Send via PeerSend"),
+            NAUTILUS_SCRIPT,
+            0o755,
+        ),
+        (
+            data_dir.join(format!("dbus-1/services/{}.service", BUS_NAME)),
+            DBUS_SERVICE,
+            0o644,
+        ),
+    ];
+
+    let mut written = Vec::with_capacity(files.len());
+    for (path, template, mode) in files {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("创建目录 {} 失败", parent.display()))?;
+        }
+        std::fs::write(&path, template.replace("{exe}", &exe))
+            .with_context(|| format!("写入 {} 失败", path.display()))?;
+
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// 把 `file://` URI 或路径转换为绝对路径
+fn to_path(uri: &str) -> Result<PathBuf> {
+    if uri.starts_with("file://") {
+        let url = url::Url::parse(uri).with_context(|| format!("无效的 URI: {}", uri))?;
+        return url
+            .to_file_path()
+            .map_err(|()| anyhow::anyhow!("不是本地文件: {}", uri));
+    }
+    if uri.contains("://") {
+        anyhow::bail!("不是本地文件: {}", uri);
+    }
+    std::path::absolute(uri).with_context(|| format!("无效的路径: {}", uri))
+}
+
+/// 按设备 ID、名称、IP 或主机名查找目标设备
+async fn resolve_device(client: &LocalSendClient, target: &str) -> Result<DeviceInfo> {
+    let devices = client.discover(DISCOVERY_TIMEOUT).await?;
+    if let Some(device) = devices
+        .into_iter()
+        .find(|d| d.id == target || d.name == target || d.ip == target)
+    {
+        return Ok(device);
+    }
+
+    // 多播不可达时直接探测地址，主机名先解析
+    if peersend_protocol::net::is_host(target) {
+        if let Some(device) = client.check_device(target).await {
+            return Ok(device);
+        }
+    }
+    anyhow::bail!("未找到设备: {}", target)
+}
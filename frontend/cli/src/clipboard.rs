@@ -0,0 +1,53 @@
+//! 系统剪贴板
+//!
+//! 通过 arboard 读写系统剪贴板，供接收守护进程的剪贴板同步和 `peersend inbox copy` 使用。
+//! Linux 上需要 X11 显示 (或 XWayland)，没有图形会话时无法打开
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use peersend_protocol::clipboard::Clipboard;
+use peersend_protocol::ProtocolError;
+
+/// 系统剪贴板
+///
+/// X11 上写入的内容由持有剪贴板的进程提供，同步期间须保持此对象存活
+pub struct SystemClipboard(arboard::Clipboard);
+
+/// Linux 上一次性复制后继续提供剪贴板内容的时长
+pub const COPY_HOLD: Duration = Duration::from_secs(30);
+
+impl SystemClipboard {
+    /// 打开系统剪贴板
+    pub fn open() -> Result<Self> {
+        Ok(Self(arboard::Clipboard::new().context("无法访问系统剪贴板")?))
+    }
+
+    /// 写入文本后返回，供运行后即退出的命令使用
+    ///
+    /// Linux 上剪贴板内容由写入的进程提供，进程退出后即失效，因此最多等待
+    /// [`COPY_HOLD`]，直到剪贴板管理器或其他程序接管剪贴板
+    pub fn copy(mut self, text: &str) -> Result<()> {
+        #[cfg(target_os = "linux")]
+        {
+            use arboard::SetExtLinux;
+            let deadline = std::time::Instant::now() + COPY_HOLD;
+            self.0.set().wait_until(deadline).text(text).context("写入剪贴板失败")?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        self.0.set_text(text).context("写入剪贴板失败")?;
+        Ok(())
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        self.0.get_text().ok()
+    }
+
+    fn set_text(&mut self, text: &str) -> peersend_protocol::Result<()> {
+        self.0
+            .set_text(text)
+            .map_err(|e| ProtocolError::Io(std::io::Error::other(e)))
+    }
+}
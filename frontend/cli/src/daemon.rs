@@ -11,6 +11,8 @@ use std::{
 };
 use tokio::time::sleep;
 
+use crate::i18n::t;
+
 const DEFAULT_RPC_PORTAL: &str = "127.0.0.1:15888";
 const PID_FILE: &str = "/tmp/peersend-easytier.pid";
 
@@ -23,6 +25,9 @@ pub struct NetworkConfig {
     pub dhcp: bool,
     pub ipv4: Option<String>,
     pub enable_wg: bool,
+    pub enable_kcp_proxy: bool,
+    pub enable_quic_proxy: bool,
+    pub latency_first: bool,
     pub rpc_portal: SocketAddr,
 }
 
@@ -35,6 +40,9 @@ impl Default for NetworkConfig {
             dhcp: true,
             ipv4: None,
             enable_wg: false,
+            enable_kcp_proxy: false,
+            enable_quic_proxy: false,
+            latency_first: false,
             rpc_portal: DEFAULT_RPC_PORTAL.parse().unwrap(),
         }
     }
@@ -112,12 +120,12 @@ impl EasyTierDaemon {
             .status();
 
         if check.map(|s| s.success()).unwrap_or(false) {
-            println!("通过 systemd 启动 easytier...");
+            println!("{}", t!("daemon-systemd-starting"));
             let output = Command::new("systemctl")
                 .arg("start")
                 .arg("easytier")
                 .output()
-                .context("执行 systemctl start 失败")?;
+                .with_context(|| t!("error-systemctl-start"))?;
 
             if output.status.success() {
                 self.wait_for_rpc().await?;
@@ -129,7 +137,7 @@ impl EasyTierDaemon {
 
     /// 直接启动进程
     async fn start_via_process(&self, config: &NetworkConfig) -> Result<()> {
-        println!("启动 easytier-core 进程...");
+        println!("{}", t!("daemon-process-starting"));
 
         // 构建 easytier-core 命令行参数
         let rpc_portal_str = self.rpc_portal.to_string();
@@ -159,9 +167,23 @@ impl EasyTierDaemon {
             args.push("--enable-wireguard");
         }
 
+        // 传输层选项，适用于丢包较高的链路
+        if config.enable_kcp_proxy {
+            args.push("--enable-kcp-proxy");
+        }
+
+        if config.enable_quic_proxy {
+            args.push("--enable-quic-proxy");
+        }
+
+        if config.latency_first {
+            args.push("--latency-first");
+        }
+
         // 启动进程
         let bin_path = self.find_easytier_binary()?;
-        println!("执行: {} {}", bin_path.display(), args.join(" "));
+        let command = format!("{} {}", bin_path.display(), args.join(" "));
+        println!("{}", t!("daemon-exec", command = command));
 
         let child = Command::new(&bin_path)
             .args(&args)
@@ -169,13 +191,13 @@ impl EasyTierDaemon {
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
             .spawn()
-            .context("启动 easytier-core 失败")?;
+            .with_context(|| t!("error-spawn-core"))?;
 
         // 写入 PID
         let pid = child.id();
         std::fs::write(&self.pid_file, pid.to_string())
-            .context("写入 PID 文件失败")?;
-        println!("easytier-core 已启动 (PID: {})", pid);
+            .with_context(|| t!("error-write-pid"))?;
+        println!("{}", t!("daemon-core-started", pid = pid));
 
         // 等待 RPC 端口就绪
         self.wait_for_rpc().await?;
@@ -187,20 +209,20 @@ impl EasyTierDaemon {
     pub async fn start(&self, config: &NetworkConfig) -> Result<()> {
         // 1. 如果已运行，先停止
         if self.is_running() {
-            println!("easytier 已在运行，先停止...");
+            println!("{}", t!("daemon-already-running"));
             self.stop().await?;
             sleep(Duration::from_secs(1)).await;
         }
 
         // 2. 优先尝试 systemd
         if let Ok(true) = self.start_via_systemd(config).await {
-            println!("easytier 已通过 systemd 启动");
+            println!("{}", t!("daemon-systemd-started"));
             return Ok(());
         }
 
         // 3. 回退到直接进程管理
         self.start_via_process(config).await?;
-        println!("easytier 已启动");
+        println!("{}", t!("daemon-started"));
 
         Ok(())
     }
@@ -214,14 +236,14 @@ impl EasyTierDaemon {
             .output();
 
         if systemd_stop.map(|s| s.status.success()).unwrap_or(false) {
-            println!("easytier 已通过 systemd 停止");
+            println!("{}", t!("daemon-systemd-stopped"));
             self.cleanup_pid();
             return Ok(());
         }
 
         // 2. 通过 PID 停止
         if let Some(pid) = self.read_pid() {
-            println!("发送 SIGTERM 到 PID {}...", pid);
+            println!("{}", t!("daemon-sigterm", pid = pid));
             let _ = Command::new("kill")
                 .arg(pid.to_string())
                 .output();
@@ -233,7 +255,7 @@ impl EasyTierDaemon {
                     break;
                 }
                 if i >= 9 {
-                    println!("发送 SIGKILL...");
+                    println!("{}", t!("daemon-sigkill"));
                     let _ = Command::new("kill")
                         .arg("-9")
                         .arg(pid.to_string())
@@ -241,9 +263,9 @@ impl EasyTierDaemon {
                 }
             }
             self.cleanup_pid();
-            println!("easytier 已停止");
+            println!("{}", t!("daemon-stopped"));
         } else {
-            println!("未找到 PID 文件，无法停止");
+            println!("{}", t!("daemon-no-pid"));
         }
 
         Ok(())
@@ -274,24 +296,24 @@ impl EasyTierDaemon {
 
     /// 等待 RPC 端口就绪
     async fn wait_for_rpc(&self) -> Result<()> {
-        println!("等待 RPC 端口 {} ...", self.rpc_portal);
+        println!("{}", t!("daemon-waiting-rpc", addr = self.rpc_portal.to_string()));
 
         for i in 0..30 {
             if self.check_rpc_connection().await {
-                println!("RPC 端口已就绪");
+                println!("{}", t!("daemon-rpc-ready"));
                 return Ok(());
             }
             sleep(Duration::from_secs(1)).await;
             if i % 5 == 0 {
-                println!("等待中... ({})", i + 1);
+                println!("{}", t!("daemon-waiting", attempt = i + 1));
             }
         }
 
-        anyhow::bail!("RPC 端口连接超时")
+        anyhow::bail!(t!("daemon-rpc-timeout"))
     }
 
     /// 检查 RPC 连接
-    async fn check_rpc_connection(&self) -> bool {
+    pub async fn check_rpc_connection(&self) -> bool {
         tokio::net::TcpStream::connect(self.rpc_portal).await.is_ok()
     }
 
@@ -333,7 +355,7 @@ impl EasyTierDaemon {
         let output = Command::new("which")
             .arg("easytier-core")
             .output()
-            .context("执行 which 命令失败")?;
+            .with_context(|| t!("error-which"))?;
 
         if output.status.success() {
             let path = String::from_utf8(output.stdout)?;
@@ -343,6 +365,6 @@ impl EasyTierDaemon {
             }
         }
 
-        anyhow::bail!("找不到 easytier-core 二进制文件")
+        anyhow::bail!(t!("daemon-core-not-found"))
     }
 }
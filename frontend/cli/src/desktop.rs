@@ -0,0 +1,351 @@
+//! 桌面通知与会话总线接口 (Linux)
+//!
+//! 接收守护进程在会话总线可用时发送 freedesktop 通知：收到文件发送请求时弹出
+//! 带「接受」「拒绝」按钮的通知，传输完成或失败时再发送一条通知。
+//!
+//! 同时在会话总线上注册 `org.peersend.Receiver`，桌面环境的扩展或小部件可以通过
+//! `Accept`、`Reject` 答复请求，通过 `ListTransfers` 查询当前的传输，
+//! 并订阅 `TransferRequested`、`TransferFinished` 信号
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use peersend_protocol::server::accept::AcceptGate;
+use peersend_protocol::{FileInfo, ProtocolEvent, SessionManager, SessionState};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use zbus::zvariant::Value;
+use zbus::{interface, Connection, MatchRule, MessageStream, SignalContext};
+
+/// 总线名称
+pub const BUS_NAME: &str = "org.peersend.Receiver";
+
+/// 对象路径
+pub const OBJECT_PATH: &str = "/org/peersend/Receiver";
+
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+
+/// 通知按钮
+const ACTION_ACCEPT: &str = "accept";
+const ACTION_REJECT: &str = "reject";
+
+/// 已发布请求的会话，会话 ID 到发送方名称和文件
+type Requests = Arc<Mutex<HashMap<String, (String, Vec<FileInfo>)>>>;
+
+/// 会话总线上的接收服务
+struct ReceiverService {
+    sessions: SessionManager,
+    accept: AcceptGate,
+    requests: Requests,
+}
+
+#[interface(name = "org.peersend.Receiver1")]
+impl ReceiverService {
+    /// 接受等待确认的请求，会话不在等待中时返回 false
+    fn accept(&self, session_id: String) -> bool {
+        self.accept.respond(&session_id, true)
+    }
+
+    /// 拒绝等待确认的请求，会话不在等待中时返回 false
+    fn reject(&self, session_id: String) -> bool {
+        self.accept.respond(&session_id, false)
+    }
+
+    /// 列出当前的传输，每项为 (会话 ID, 发送方, 状态, 文件数, 已传输字节, 总字节)
+    ///
+    /// 状态为 `waiting`、`transferring`、`finished`、`cancelled` 或 `error`
+    async fn list_transfers(&self) -> Vec<(String, String, String, u32, u64, u64)> {
+        let sessions = self.sessions.get_all_sessions().await;
+        let mut transfers = Vec::with_capacity(sessions.len());
+        for session in sessions.iter() {
+            let state = session.state.lock().await.clone();
+            let sender = self
+                .requests
+                .lock()
+                .unwrap()
+                .get(&session.id)
+                .map_or_else(|| session.sender_id.clone(), |(name, _)| name.clone());
+            transfers.push((
+                session.id.clone(),
+                sender,
+                state_name(&state).to_string(),
+                session.files.len() as u32,
+                session.progress.bytes_transferred(),
+                session.progress.total_bytes(),
+            ));
+        }
+        transfers
+    }
+
+    /// 收到需要确认的文件发送请求
+    #[zbus(signal)]
+    async fn transfer_requested(
+        ctxt: &SignalContext<'_>,
+        session_id: &str,
+        sender: &str,
+        files: Vec<String>,
+    ) -> zbus::Result<()>;
+
+    /// 传输结束，state 为 `finished`、`cancelled` 或 `error`
+    #[zbus(signal)]
+    async fn transfer_finished(
+        ctxt: &SignalContext<'_>,
+        session_id: &str,
+        state: &str,
+        error: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// 运行桌面集成，直到事件总线关闭或 cancel 取消
+///
+/// 会话总线不可用 (例如在无桌面的服务器上) 时返回错误
+pub async fn run(
+    mut events: broadcast::Receiver<ProtocolEvent>,
+    sessions: SessionManager,
+    accept: AcceptGate,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let requests = Requests::default();
+    let service = ReceiverService {
+        sessions: sessions.clone(),
+        accept: accept.clone(),
+        requests: requests.clone(),
+    };
+    let connection = zbus::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, service)?
+        .build()
+        .await
+        .context("注册会话总线服务失败")?;
+    let signals = SignalContext::new(&connection, OBJECT_PATH)?;
+
+    let rule = MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface(NOTIFICATIONS_NAME)?
+        .member("ActionInvoked")?
+        .build();
+    let mut actions = MessageStream::for_match_rule(rule, &connection, None).await?;
+
+    // 请求通知 ID 到会话 ID
+    let mut prompts: HashMap<u32, String> = HashMap::new();
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "桌面通知处理过慢，丢失部分事件");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                match event {
+                    ProtocolEvent::SessionRequested { session_id, sender, files } => {
+                        let names: Vec<String> = files.iter().map(|f| f.name.clone()).collect();
+                        requests
+                            .lock()
+                            .unwrap()
+                            .insert(session_id.clone(), (sender.clone(), files));
+                        let signal = ReceiverService::transfer_requested(
+                            &signals,
+                            &session_id,
+                            &sender,
+                            names.clone(),
+                        );
+                        if let Err(e) = signal.await {
+                            tracing::warn!(error = %e, "发送总线信号失败");
+                        }
+
+                        let body = describe_files(&names);
+                        let summary = format!("{} 想要发送文件", sender);
+                        let buttons = [ACTION_ACCEPT, "接受", ACTION_REJECT, "拒绝"];
+                        match notify(&connection, &summary, &body, &buttons).await {
+                            Ok(id) => {
+                                prompts.insert(id, session_id);
+                            }
+                            Err(e) => tracing::warn!(error = %e, "发送桌面通知失败"),
+                        }
+                    }
+                    ProtocolEvent::SessionStateChanged { session_id, state } => {
+                        match state {
+                            SessionState::Waiting => continue,
+                            // 已在别处答复的请求不再需要确认
+                            SessionState::Transferring => {
+                                close_prompt(&connection, &mut prompts, &session_id).await;
+                                continue;
+                            }
+                            _ => {}
+                        }
+                        close_prompt(&connection, &mut prompts, &session_id).await;
+
+                        let error = match &state {
+                            SessionState::Error(message) => message.clone(),
+                            _ => String::new(),
+                        };
+                        let signal = ReceiverService::transfer_finished(
+                            &signals,
+                            &session_id,
+                            state_name(&state),
+                            &error,
+                        );
+                        if let Err(e) = signal.await {
+                            tracing::warn!(error = %e, "发送总线信号失败");
+                        }
+
+                        let known = requests.lock().unwrap().remove(&session_id);
+                        let (sender, files) = match known {
+                            Some(known) => known,
+                            None => match sessions.get_session(&session_id).await {
+                                Some(session) => (session.sender_id.clone(), session.files.clone()),
+                                None => continue,
+                            },
+                        };
+                        let names: Vec<String> = files.iter().map(|f| f.name.clone()).collect();
+                        let (summary, body) = match state {
+                            SessionState::Finished => (
+                                format!("已接收来自 {} 的 {} 个文件", sender, names.len()),
+                                describe_files(&names),
+                            ),
+                            SessionState::Error(message) => {
+                                (format!("接收来自 {} 的文件失败", sender), message)
+                            }
+                            // 拒绝或取消由用户发起，不再提示
+                            _ => continue,
+                        };
+                        if let Err(e) = notify(&connection, &summary, &body, &[]).await {
+                            tracing::warn!(error = %e, "发送桌面通知失败");
+                        }
+                    }
+                    ProtocolEvent::FileQuarantined { path, .. } => {
+                        let name = path.file_name().unwrap_or_default().to_string_lossy();
+                        let dir = path.parent().unwrap_or(&path).display();
+                        let body = format!("{} 可能包含可执行代码，已放入 {}", name, dir);
+                        if let Err(e) = notify(&connection, "已隔离有风险的文件", &body, &[]).await {
+                            tracing::warn!(error = %e, "发送桌面通知失败");
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            message = actions.next() => {
+                let Some(message) = message else { break };
+                let (id, action): (u32, String) = match message.and_then(|m| m.body().deserialize()) {
+                    Ok(body) => body,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "无法解析通知按钮信号");
+                        continue;
+                    }
+                };
+                // 其他程序的通知也会发出此信号
+                let Some(session_id) = prompts.remove(&id) else { continue };
+                match action.as_str() {
+                    ACTION_ACCEPT => {
+                        accept.respond(&session_id, true);
+                    }
+                    ACTION_REJECT => {
+                        accept.respond(&session_id, false);
+                    }
+                    _ => {
+                        prompts.insert(id, session_id);
+                    }
+                }
+            }
+        }
+    }
+
+    for id in prompts.into_keys() {
+        let _ = close_notification(&connection, id).await;
+    }
+    Ok(())
+}
+
+/// 发送通知，actions 为交替的按钮键和标签，返回通知 ID
+async fn notify(
+    connection: &Connection,
+    summary: &str,
+    body: &str,
+    actions: &[&str],
+) -> Result<u32> {
+    // 请求通知常驻，直到用户答复
+    let (urgency, timeout): (u8, i32) = if actions.is_empty() { (1, -1) } else { (2, 0) };
+    let hints = HashMap::from([("urgency", Value::from(urgency))]);
+    let reply = connection
+        .call_method(
+            Some(NOTIFICATIONS_NAME),
+            NOTIFICATIONS_PATH,
+            Some(NOTIFICATIONS_NAME),
+            "Notify",
+            &(
+                "PeerSend",
+                0u32,
+                "document-save",
+                summary,
+                body,
+                actions,
+                hints,
+                timeout,
+            ),
+        )
+        .await?;
+    Ok(reply.body().deserialize()?)
+}
+
+/// 关闭会话的请求通知
+async fn close_prompt(
+    connection: &Connection,
+    prompts: &mut HashMap<u32, String>,
+    session_id: &str,
+) {
+    let ids: Vec<u32> = prompts
+        .iter()
+        .filter(|(_, id)| id.as_str() == session_id)
+        .map(|(&id, _)| id)
+        .collect();
+    for id in ids {
+        prompts.remove(&id);
+        let _ = close_notification(connection, id).await;
+    }
+}
+
+async fn close_notification(connection: &Connection, id: u32) -> Result<()> {
+    connection
+        .call_method(
+            Some(NOTIFICATIONS_NAME),
+            NOTIFICATIONS_PATH,
+            Some(NOTIFICATIONS_NAME),
+            "CloseNotification",
+            &(id,),
+        )
+        .await?;
+    Ok(())
+}
+
+/// 通知正文中的文件列表，过长时截断
+fn describe_files(names: &[String]) -> String {
+    const SHOWN: usize = 5;
+    let mut body = names
+        .iter()
+        .take(SHOWN)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if names.len() > SHOWN {
+        body.push_str(&format!("\n… 等 {} 个文件", names.len()));
+    }
+    body
+}
+
+fn state_name(state: &SessionState) -> &'static str {
+    match state {
+        SessionState::Waiting => "waiting",
+        SessionState::Transferring => "transferring",
+        SessionState::Finished => "finished",
+        SessionState::Cancelled => "cancelled",
+        SessionState::Error(_) => "error",
+    }
+}
@@ -0,0 +1,117 @@
+//! 命令行输出的多语言支持
+//!
+//! 消息目录为 Fluent 格式，位于 `locales/<语言>/cli.ftl`，编译时嵌入程序。
+//! 语言依次取自 `PEERSEND_LANG`、`LC_ALL`、`LC_MESSAGES`、`LANG` 环境变量和系统设置，
+//! 没有对应目录时使用简体中文；当前语言的目录中缺少的消息也回退到简体中文。
+//!
+//! 命令的输出和错误经 [`t!`] 取得，日志仍使用原文
+
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+/// 默认语言，也是缺少消息时的回退语言
+const DEFAULT_LOCALE: &str = "zh-CN";
+
+/// 内置的消息目录
+const CATALOGS: &[(&str, &str)] = &[
+    ("zh-CN", include_str!("../locales/zh-CN/cli.ftl")),
+    ("en-US", include_str!("../locales/en-US/cli.ftl")),
+];
+
+/// 按 id 取得当前语言的消息，args 为消息中的参数
+///
+/// ```ignore
+/// println!("{}", t!("service-installed", name = name));
+/// ```
+macro_rules! t {
+    ($id:literal) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+pub(crate) use t;
+
+struct Catalog {
+    bundle: FluentBundle<FluentResource>,
+    fallback: Option<FluentBundle<FluentResource>>,
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// 当前语言中 id 对应的消息，各语言的目录中都没有时返回 id 本身
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    let catalog = CATALOG.get_or_init(|| {
+        let locale = locale();
+        Catalog {
+            bundle: bundle(locale),
+            fallback: (locale != DEFAULT_LOCALE).then(|| bundle(DEFAULT_LOCALE)),
+        }
+    });
+    std::iter::once(&catalog.bundle)
+        .chain(&catalog.fallback)
+        .find_map(|bundle| format(bundle, id, args))
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// 当前使用的语言
+pub fn locale() -> &'static str {
+    ["PEERSEND_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find(|value| !value.is_empty())
+        .or_else(sys_locale::get_locale)
+        .and_then(|tag| match_locale(&tag))
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// 把 `en_US.UTF-8`、`zh-Hans-CN` 等写法对应到内置的目录，没有对应目录时返回 None
+///
+/// `C` 和 `POSIX` 视为英文
+pub fn match_locale(tag: &str) -> Option<&'static str> {
+    let tag = tag.split(['.', '@']).next()?.replace('_', "-");
+    if tag == "C" || tag == "POSIX" {
+        return Some("en-US");
+    }
+    let id: LanguageIdentifier = tag.parse().ok()?;
+    match id.language.as_str() {
+        "zh" => Some("zh-CN"),
+        "en" => Some("en-US"),
+        _ => None,
+    }
+}
+
+fn bundle(locale: &str) -> FluentBundle<FluentResource> {
+    let (_, source) = CATALOGS
+        .iter()
+        .find(|(name, _)| *name == locale)
+        .expect("内置语言的消息目录");
+    let id: LanguageIdentifier = locale.parse().expect("内置语言的标识有效");
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // 终端中的 Unicode 隔离符会显示为乱码
+    bundle.set_use_isolating(false);
+    let resource = FluentResource::try_new(source.to_string()).expect("内置消息目录语法正确");
+    bundle.add_resource(resource).expect("内置消息目录中没有重复的消息");
+    bundle
+}
+
+fn format(
+    bundle: &FluentBundle<FluentResource>,
+    id: &str,
+    args: Option<&FluentArgs>,
+) -> Option<String> {
+    let pattern = bundle.get_message(id)?.value()?;
+    let mut errors = Vec::new();
+    let text = bundle.format_pattern(pattern, args, &mut errors);
+    if !errors.is_empty() {
+        tracing::debug!(id, ?errors, "格式化消息出错");
+    }
+    Some(Cow::into_owned(text))
+}
@@ -2,9 +2,19 @@
 //!
 //! P2P 文件传输命令行工具，参考 EasyTier CLI 实现
 
+mod arch;
+#[cfg(target_os = "linux")]
+mod bridge;
+mod clipboard;
 mod daemon;
+#[cfg(target_os = "linux")]
+mod desktop;
+mod i18n;
+mod receiver;
+mod service;
 
 use std::{
+    io::{IsTerminal, Write},
     net::{IpAddr, SocketAddr},
     str::FromStr,
 };
@@ -14,6 +24,20 @@ use cidr::Ipv4Inet;
 use clap::{Args, Parser, Subcommand};
 use daemon::{EasyTierDaemon, NetworkConfig};
 use humansize::format_size;
+use i18n::t;
+use peersend_protocol::audit::{self, AuditLog};
+use peersend_protocol::config::{group_name, ConfigBundle};
+use peersend_protocol::inbox::Inbox;
+use peersend_protocol::journal::SessionJournal;
+use peersend_protocol::retention::Retention;
+use peersend_protocol::routes::RouteStats;
+use peersend_protocol::screenshot::Region;
+use peersend_protocol::control::{ControlClient, ControlRequest, ControlResponse, SendRequest};
+use peersend_protocol::logging::LogFormat;
+use peersend_protocol::pairing::Pairing;
+use peersend_protocol::server::api::{self, EventSubscription};
+use peersend_protocol::server::share::{ShareLink, ShareStore};
+use service::{ServiceInstallOptions, ServiceManager, SystemServiceManager};
 use tabled::settings::{location::ByColumnName, object::Columns, Disable, Modify, Style, Width};
 use terminal_size::{terminal_size, Width as TerminalWidth};
 use unicode_width::UnicodeWidthStr;
@@ -68,6 +92,15 @@ struct StartArgs {
     #[arg(long, help = "启用 WireGuard")]
     enable_wg: bool,
 
+    #[arg(long, help = "启用 KCP 代理（适合丢包较高的链路）")]
+    enable_kcp_proxy: bool,
+
+    #[arg(long, help = "启用 QUIC 代理")]
+    enable_quic_proxy: bool,
+
+    #[arg(long, help = "优先选择低延迟路径")]
+    latency_first: bool,
+
     #[arg(long, help = "RPC 端口（默认 15888）")]
     rpc_portal: Option<SocketAddr>,
 }
@@ -86,6 +119,23 @@ struct Cli {
     #[arg(short, long, default_value = "false", help = "verbose output")]
     verbose: bool,
 
+    #[arg(
+        long,
+        global = true,
+        value_name = "FORMAT",
+        help = "日志格式: text 或 json (每个事件一行 JSON)，也可通过 PEERSEND_LOG_FORMAT 设置"
+    )]
+    log_format: Option<LogFormat>,
+
+    #[cfg(feature = "otel")]
+    #[arg(
+        long,
+        global = true,
+        value_name = "URL",
+        help = "经 OTLP (gRPC) 导出 tracing span 的地址，例如 http://localhost:4317"
+    )]
+    otlp_endpoint: Option<String>,
+
     #[arg(
         short = 'o',
         long = "output",
@@ -135,6 +185,39 @@ enum SubCommand {
     Proxy,
     #[command(about = "show statistics information")]
     Stats(StatsArgs),
+    #[command(about = "管理 PeerSend 系统服务")]
+    Service(ServiceArgs),
+    #[cfg(target_os = "linux")]
+    #[command(about = "文件管理器与 KDE Connect 分享桥接")]
+    Bridge(BridgeArgs),
+    #[command(about = "生成或管理文件分享链接")]
+    Share(ShareArgs),
+    #[command(about = "管理设备允许和屏蔽列表")]
+    Access(AccessArgs),
+    #[command(about = "管理设备组")]
+    Devices(DevicesArgs),
+    #[command(about = "导出或导入配置包，便于迁移到新机器")]
+    Config(ConfigArgs),
+    #[command(about = "校验或导出传输审计日志")]
+    Audit(AuditArgs),
+    #[command(about = "比对配对码后将设备标记为受信任")]
+    Pair(PairArgs),
+    #[command(about = "经本地控制通道查看或答复接收守护进程中等待确认的请求")]
+    Requests(RequestsArgs),
+    #[command(about = "经管理 API 查看接收守护进程最近的事件，每行输出一个 JSON")]
+    Events(EventsArgs),
+    #[command(about = "临时开放接收，期间自动接受来自局域网的所有请求，不带参数时查看状态")]
+    Receive(ReceiveArgs),
+    #[command(about = "按配置的清理策略立即清理下载目录")]
+    Cleanup(CleanupArgs),
+    #[command(about = "查看或清理上次运行中断的接收会话")]
+    Journal(JournalArgs),
+    #[command(about = "查看、复制或删除收到的文本消息")]
+    Inbox(InboxArgs),
+    #[command(about = "检查配置、下载目录、防火墙和本地网络权限等常见问题")]
+    Doctor(DoctorArgs),
+    #[command(about = "截取屏幕或区域并发送给设备")]
+    Screenshot(ScreenshotArgs),
 }
 
 #[derive(clap::ValueEnum, Debug, Clone, PartialEq)]
@@ -247,6 +330,344 @@ enum StatsSubCommand {
     Prometheus,
 }
 
+#[derive(Args, Debug)]
+struct ServiceArgs {
+    #[arg(long, default_value = service::DEFAULT_SERVICE_NAME, help = "服务名称")]
+    name: String,
+
+    #[arg(long, help = "以当前用户身份管理服务（systemd --user 或 LaunchAgent，无需 root）")]
+    user: bool,
+
+    #[command(subcommand)]
+    sub_command: ServiceSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum ServiceSubCommand {
+    #[command(about = "安装接收服务")]
+    Install(ServiceInstallArgs),
+    #[command(about = "卸载接收服务")]
+    Uninstall,
+    #[command(about = "启动接收服务")]
+    Start,
+    #[command(about = "停止接收服务")]
+    Stop,
+    #[command(about = "重启接收服务")]
+    Restart,
+    #[command(about = "通知接收服务重新加载配置")]
+    Reload,
+    #[command(about = "查看接收服务状态")]
+    Status,
+    #[command(about = "检查接收服务健康状态")]
+    Health,
+    #[command(about = "查看接收服务日志")]
+    Logs(ServiceLogsArgs),
+    /// 由服务管理器调用，运行接收守护进程
+    #[command(hide = true)]
+    Run,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Args, Debug)]
+struct BridgeArgs {
+    #[command(subcommand)]
+    sub_command: BridgeSubCommand,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Subcommand, Debug)]
+enum BridgeSubCommand {
+    #[command(about = "在会话总线上运行分享服务")]
+    Run {
+        #[arg(long, value_name = "DEVICE", help = "KDE Connect 收到的文件转发至此设备（ID、名称、IP 或主机名）")]
+        forward_to: Option<String>,
+    },
+    #[command(about = "通过分享服务发送文件")]
+    Send {
+        #[arg(required = true, help = "文件路径或 file:// URI")]
+        files: Vec<String>,
+
+        #[arg(short, long, help = "目标设备（ID、名称、IP 或主机名），默认使用转发目标")]
+        device: Option<String>,
+    },
+    #[command(about = "安装文件管理器菜单和总线激活文件")]
+    Install,
+}
+
+#[derive(Args, Debug)]
+struct AccessArgs {
+    #[command(subcommand)]
+    sub_command: AccessSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum AccessSubCommand {
+    #[command(about = "列出允许和屏蔽列表")]
+    List,
+    #[command(about = "屏蔽设备，不再接收其请求，也不在发现结果中列出")]
+    Block {
+        #[arg(help = "设备 ID、IP 地址或 CIDR 网段，如 192.168.1.0/24")]
+        rule: String,
+    },
+    #[command(about = "取消屏蔽")]
+    Unblock { rule: String },
+    #[command(about = "加入允许列表，列表非空时只允许其中的设备")]
+    Allow {
+        #[arg(help = "设备 ID、IP 地址或 CIDR 网段，如 192.168.1.0/24")]
+        rule: String,
+    },
+    #[command(about = "移出允许列表")]
+    Disallow { rule: String },
+}
+
+#[derive(Args, Debug)]
+struct DevicesArgs {
+    #[command(subcommand)]
+    sub_command: DevicesSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum DevicesSubCommand {
+    #[command(about = "管理设备组，发送时以 @组名 向组内所有设备发送")]
+    Group {
+        #[command(subcommand)]
+        sub_command: GroupSubCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum GroupSubCommand {
+    #[command(about = "列出设备组")]
+    List,
+    #[command(about = "创建设备组，已存在时替换组内的设备")]
+    Create {
+        name: String,
+        #[arg(required = true, help = "设备 ID、名称、IP 或主机名")]
+        devices: Vec<String>,
+    },
+    #[command(about = "删除设备组")]
+    Delete { name: String },
+}
+
+#[derive(Args, Debug)]
+struct ConfigArgs {
+    #[command(subcommand)]
+    sub_command: ConfigSubCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigSubCommand {
+    #[command(about = "把设置和受信任的设备导出为配置包")]
+    Export {
+        #[arg(long, help = "去掉 API 密钥、PIN 和各项密码")]
+        redact_secrets: bool,
+
+        #[arg(short, long, help = "输出文件，默认输出到标准输出")]
+        output: Option<std::path::PathBuf>,
+    },
+    #[command(about = "导入配置包并替换当前配置，配置包中去掉的密钥沿用本机的值")]
+    Import { file: std::path::PathBuf },
+}
+
+#[derive(Args, Debug)]
+struct AuditArgs {
+    #[command(subcommand)]
+    sub_command: AuditSubCommand,
+
+    #[arg(long, global = true, help = "审计日志文件，默认为配置目录中的 audit.jsonl")]
+    file: Option<std::path::PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct PairArgs {
+    #[arg(help = "设备 ID、名称、IP 地址或主机名")]
+    device: String,
+
+    #[arg(short, long, help = "不询问，直接信任 (仅在已通过其他途径比对配对码时使用)")]
+    yes: bool,
+}
+
+#[derive(Args, Debug)]
+struct RequestsArgs {
+    #[command(subcommand)]
+    sub_command: Option<RequestsSubCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+enum RequestsSubCommand {
+    #[command(about = "列出等待确认的请求")]
+    List,
+    #[command(about = "接受请求")]
+    Accept { id: String },
+    #[command(about = "拒绝请求")]
+    Reject { id: String },
+}
+
+#[derive(Args, Debug)]
+struct EventsArgs {
+    #[arg(short, long, help = "持续输出新事件")]
+    follow: bool,
+
+    #[arg(long, help = "管理 API 的地址，默认为 http://127.0.0.1:<api_port>")]
+    url: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ReceiveArgs {
+    #[arg(long, value_parser = parse_duration, help = "开放时长，如 10m、1h，最长 24h")]
+    open_for: Option<std::time::Duration>,
+
+    #[arg(long, conflicts_with = "open_for", help = "提前结束开放接收")]
+    close: bool,
+}
+
+#[derive(Args, Debug)]
+struct CleanupArgs {
+    #[arg(long, help = "只列出将清理的文件，不删除或移动")]
+    dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+struct DoctorArgs {
+    #[arg(long, help = "尝试修复发现的问题，例如创建下载目录、添加防火墙规则")]
+    fix: bool,
+}
+
+#[derive(Args, Debug)]
+struct ScreenshotArgs {
+    #[arg(long, help = "目标设备的 ID、名称、IP 或主机名，@组名 表示设备组中的所有设备")]
+    to: String,
+
+    #[arg(long, help = "只截取此区域，格式为 x,y,宽,高，默认截取主显示器")]
+    region: Option<Region>,
+
+    #[arg(long, help = "发送后保留截图文件")]
+    keep: bool,
+
+    #[arg(long, help = "对方要求的 PIN，未指定时在终端询问")]
+    pin: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct JournalArgs {
+    #[command(subcommand)]
+    sub_command: Option<JournalSubCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+enum JournalSubCommand {
+    #[command(about = "列出未完成的会话及各文件已接收的大小")]
+    List,
+    #[command(about = "放弃会话并删除其未完成的 .part 文件")]
+    Discard { id: String },
+}
+
+#[derive(Args, Debug)]
+struct InboxArgs {
+    #[command(subcommand)]
+    sub_command: Option<InboxSubCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+enum InboxSubCommand {
+    #[command(about = "列出收到的文本，每条显示第一行")]
+    List,
+    #[command(about = "显示文本全文")]
+    Show { id: u64 },
+    #[command(about = "把文本复制到系统剪贴板")]
+    Copy { id: u64 },
+    #[command(about = "删除文本")]
+    Delete { id: u64 },
+}
+
+#[derive(Subcommand, Debug)]
+enum AuditSubCommand {
+    #[command(about = "校验哈希链，确认日志未被篡改")]
+    Verify,
+    #[command(about = "校验并导出为 JSONL")]
+    Export {
+        #[arg(short, long, help = "输出文件，默认输出到标准输出")]
+        output: Option<std::path::PathBuf>,
+    },
+}
+
+#[derive(Args, Debug)]
+struct ShareArgs {
+    #[arg(required_unless_present_any = ["list", "revoke"], help = "要分享的文件")]
+    file: Option<std::path::PathBuf>,
+
+    #[arg(long, value_parser = parse_duration, help = "链接有效期，如 30m、1h、7d，默认不过期")]
+    expires: Option<std::time::Duration>,
+
+    #[arg(long, value_name = "N", help = "下载次数上限，默认不限")]
+    max_downloads: Option<u32>,
+
+    #[arg(long, help = "链接中使用的主机名或地址，默认使用本机局域网地址")]
+    host: Option<String>,
+
+    #[arg(long, conflicts_with_all = ["file", "revoke"], help = "列出有效的分享链接")]
+    list: bool,
+
+    #[arg(long, value_name = "TOKEN", conflicts_with = "file", help = "撤销分享链接")]
+    revoke: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ServiceInstallArgs {
+    #[arg(long, help = "服务描述")]
+    description: Option<String>,
+
+    #[arg(long, help = "工作目录")]
+    work_dir: Option<String>,
+
+    #[arg(long, help = "不随系统启动")]
+    disable_autostart: bool,
+
+    #[arg(long, value_name = "USER", help = "以指定用户运行服务")]
+    run_as: Option<String>,
+
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env_var, help = "服务进程的环境变量，可重复指定")]
+    env: Vec<(String, String)>,
+
+    #[arg(long, help = "使用 systemd 套接字激活，按需启动接收服务")]
+    socket_activation: bool,
+}
+
+#[derive(Args, Debug)]
+struct ServiceLogsArgs {
+    #[arg(short, long, help = "持续输出新日志")]
+    follow: bool,
+
+    #[arg(long, value_parser = parse_duration, help = "只显示最近一段时间的日志，如 30m、2h、1d")]
+    since: Option<std::time::Duration>,
+}
+
+/// 解析形如 30s、10m、2h、1d 的时长
+fn parse_duration(s: &str) -> Result<std::time::Duration, String> {
+    let s = s.trim();
+    let (value, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => s.split_at(idx),
+        None => (s, "s"),
+    };
+    let value: u64 = value.parse().map_err(|_| format!("无效的时长: {}", s))?;
+    let secs = match unit {
+        "s" => value,
+        "m" => value * 60,
+        "h" => value * 3600,
+        "d" => value * 86400,
+        _ => return Err(format!("无效的时长单位: {}", unit)),
+    };
+    Ok(std::time::Duration::from_secs(secs))
+}
+
+/// 解析形如 KEY=VALUE 的环境变量
+fn parse_env_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("环境变量格式应为 KEY=VALUE: {}", s)),
+    }
+}
+
 struct CommandHandler<'a> {
     client: tokio::sync::Mutex<RpcClient>,
     verbose: bool,
@@ -678,6 +1099,879 @@ impl CommandHandler<'_> {
     }
 }
 
+async fn handle_service(args: &ServiceArgs) -> Result<(), Error> {
+    let manager = if args.user {
+        SystemServiceManager::new_user()
+    } else {
+        SystemServiceManager::new()
+    };
+    let name = args.name.as_str();
+
+    match &args.sub_command {
+        ServiceSubCommand::Install(install_args) => {
+            let program = std::env::current_exe().with_context(|| t!("error-current-exe"))?;
+            let work_directory = match &install_args.work_dir {
+                Some(dir) => dir.clone(),
+                None => std::env::current_dir()
+                    .with_context(|| t!("error-current-dir"))?
+                    .to_string_lossy()
+                    .into_owned(),
+            };
+            let options = ServiceInstallOptions {
+                name: name.to_string(),
+                program: program.to_string_lossy().into_owned(),
+                args: vec![
+                    "service".to_string(),
+                    "--name".to_string(),
+                    name.to_string(),
+                    "run".to_string(),
+                ],
+                work_directory,
+                disable_autostart: install_args.disable_autostart,
+                description: install_args.description.clone(),
+                user: install_args.run_as.clone(),
+                env: install_args.env.clone(),
+                socket_port: install_args
+                    .socket_activation
+                    .then_some(peersend_protocol::DEFAULT_PORT),
+            };
+            manager.install(&options)?;
+            println!("{}", t!("service-installed", name = name));
+        }
+        ServiceSubCommand::Uninstall => {
+            manager.uninstall(name)?;
+            println!("{}", t!("service-uninstalled", name = name));
+        }
+        ServiceSubCommand::Start => {
+            manager.start(name)?;
+            println!("{}", t!("service-started", name = name));
+        }
+        ServiceSubCommand::Stop => {
+            manager.stop(name)?;
+            println!("{}", t!("service-stopped", name = name));
+        }
+        ServiceSubCommand::Restart => {
+            manager.restart(name)?;
+            println!("{}", t!("service-restarted", name = name));
+        }
+        ServiceSubCommand::Reload => {
+            manager.reload(name)?;
+            println!("{}", t!("service-reloaded", name = name));
+        }
+        ServiceSubCommand::Status => {
+            let status = format!("{:?}", manager.status(name)?);
+            println!("{}", t!("service-status", name = name, status = status));
+        }
+        ServiceSubCommand::Health => {
+            if receiver::check_health().await? {
+                println!("{}", t!("service-healthy", name = name));
+            } else {
+                anyhow::bail!(t!("service-unhealthy", name = name));
+            }
+        }
+        ServiceSubCommand::Logs(logs_args) => {
+            manager.logs(name, logs_args.follow, logs_args.since)?;
+        }
+        ServiceSubCommand::Run => {
+            #[cfg(target_os = "windows")]
+            {
+                let name = name.to_string();
+                tokio::task::spawn_blocking(move || service::windows::run(&name)).await??;
+            }
+            #[cfg(not(target_os = "windows"))]
+            {
+                let reload = receiver::reload_on_sighup()?;
+                receiver::run(
+                    async {
+                        let _ = tokio::signal::ctrl_c().await;
+                    },
+                    reload,
+                )
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+async fn handle_bridge(args: &BridgeArgs) -> Result<(), Error> {
+    match &args.sub_command {
+        BridgeSubCommand::Run { forward_to } => {
+            bridge::run(forward_to.clone(), async {
+                let _ = tokio::signal::ctrl_c().await;
+            })
+            .await?;
+        }
+        BridgeSubCommand::Send { files, device } => {
+            let session_id = bridge::send(files, device.clone()).await?;
+            println!("{}", t!("bridge-sent", session = session_id.as_str()));
+        }
+        BridgeSubCommand::Install => {
+            for path in bridge::install()? {
+                println!("{}", t!("bridge-written", path = path.display().to_string()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_access(args: &AccessArgs) -> Result<(), Error> {
+    let mut config = peersend_protocol::LocalSendConfig::load_or_init()
+        .with_context(|| t!("error-load-config"))?;
+
+    let (list, rule, add) = match &args.sub_command {
+        AccessSubCommand::List => {
+            let rules = describe_rules(&config.allowlist, &t!("access-allowlist-empty"));
+            println!("{}", t!("access-allowlist", rules = rules));
+            let rules = describe_rules(&config.blocklist, &t!("access-blocklist-empty"));
+            println!("{}", t!("access-blocklist", rules = rules));
+            return Ok(());
+        }
+        AccessSubCommand::Block { rule } => (&mut config.blocklist, rule, true),
+        AccessSubCommand::Unblock { rule } => (&mut config.blocklist, rule, false),
+        AccessSubCommand::Allow { rule } => (&mut config.allowlist, rule, true),
+        AccessSubCommand::Disallow { rule } => (&mut config.allowlist, rule, false),
+    };
+    let rule = rule.trim().to_string();
+    if add {
+        if list.contains(&rule) {
+            println!("{}", t!("access-already-listed", rule = rule.as_str()));
+            return Ok(());
+        }
+        list.push(rule);
+    } else {
+        let before = list.len();
+        list.retain(|r| *r != rule);
+        if list.len() == before {
+            anyhow::bail!(t!("access-not-listed", rule = rule.as_str()));
+        }
+    }
+
+    config.validate()?;
+    let path = peersend_protocol::LocalSendConfig::default_path()
+        .with_context(|| t!("error-config-dir"))?;
+    config.save(&path)?;
+    println!("{}", t!("config-updated"));
+    Ok(())
+}
+
+fn handle_devices(args: &DevicesArgs) -> Result<(), Error> {
+    let mut config = peersend_protocol::LocalSendConfig::load_or_init()
+        .with_context(|| t!("error-load-config"))?;
+
+    let DevicesSubCommand::Group { sub_command } = &args.sub_command;
+    match sub_command {
+        GroupSubCommand::List => {
+            if config.groups.is_empty() {
+                println!("{}", t!("group-list-empty"));
+            }
+            for (name, devices) in &config.groups {
+                println!("@{}  {}", name, devices.join(", "));
+            }
+            return Ok(());
+        }
+        GroupSubCommand::Create { name, devices } => {
+            let name = name.strip_prefix('@').unwrap_or(name);
+            config.groups.insert(name.to_string(), devices.clone());
+        }
+        GroupSubCommand::Delete { name } => {
+            let name = name.strip_prefix('@').unwrap_or(name);
+            if config.groups.remove(name).is_none() {
+                anyhow::bail!(t!("group-not-found", name = name));
+            }
+        }
+    }
+
+    config.validate()?;
+    let path = peersend_protocol::LocalSendConfig::default_path()
+        .with_context(|| t!("error-config-dir"))?;
+    config.save(&path)?;
+    println!("{}", t!("config-updated"));
+    Ok(())
+}
+
+fn handle_config(args: &ConfigArgs) -> Result<(), Error> {
+    let local = peersend_protocol::LocalSendConfig::load_or_init()
+        .with_context(|| t!("error-load-config"))?;
+    match &args.sub_command {
+        ConfigSubCommand::Export {
+            redact_secrets,
+            output,
+        } => {
+            let mut bundle = ConfigBundle::new(&local);
+            if *redact_secrets {
+                bundle = bundle.redact_secrets();
+            }
+            match output {
+                Some(output) => {
+                    bundle.save(output)?;
+                    let path = output.display().to_string();
+                    println!("{}", t!("config-exported", path = path));
+                }
+                None => println!("{}", serde_json::to_string_pretty(&bundle)?),
+            }
+        }
+        ConfigSubCommand::Import { file } => {
+            let bundle = ConfigBundle::load(file).with_context(|| t!("error-read-bundle"))?;
+            let config = bundle.into_config(&local)?;
+            let path = peersend_protocol::LocalSendConfig::default_path()
+                .with_context(|| t!("error-config-dir"))?;
+            config.save(&path)?;
+            println!("{}", t!("config-imported"));
+        }
+    }
+    Ok(())
+}
+
+fn handle_audit(args: &AuditArgs) -> Result<(), Error> {
+    let path = match &args.file {
+        Some(path) => path.clone(),
+        None => AuditLog::default_path().with_context(|| t!("error-config-dir"))?,
+    };
+
+    match &args.sub_command {
+        AuditSubCommand::Verify => {
+            let summary = audit::verify(&path).with_context(|| t!("error-verify-audit"))?;
+            println!("{}", t!("audit-verified", records = summary.records));
+            println!("{}", t!("audit-head", head = summary.head.as_str()));
+        }
+        AuditSubCommand::Export { output: Some(output) } => {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(output)?);
+            let summary = audit::export(&path, &mut file)
+                .with_context(|| t!("error-verify-audit"))?;
+            file.flush()?;
+            let path = output.display().to_string();
+            println!("{}", t!("audit-exported", records = summary.records, path = path));
+        }
+        AuditSubCommand::Export { output: None } => {
+            let mut stdout = std::io::stdout().lock();
+            audit::export(&path, &mut stdout).with_context(|| t!("error-verify-audit"))?;
+        }
+    }
+    Ok(())
+}
+
+fn handle_cleanup(args: &CleanupArgs) -> Result<(), Error> {
+    let config = peersend_protocol::LocalSendConfig::load_or_init()
+        .with_context(|| t!("error-load-config"))?;
+    let Some(mut retention) = Retention::from_config(&config) else {
+        anyhow::bail!(t!("cleanup-no-policy"));
+    };
+    if args.dry_run {
+        retention = retention.with_dry_run(true);
+    }
+    if config.audit_log {
+        let audit = AuditLog::open_default().with_context(|| t!("error-open-audit"))?;
+        retention = retention.with_audit_log(audit);
+    }
+
+    let cleanup = retention.apply(std::time::SystemTime::now())?;
+    for file in &cleanup.files {
+        println!("{}  {}", file.path.display(), format_size(file.size, humansize::BINARY));
+    }
+    let total: u64 = cleanup.files.iter().map(|f| f.size).sum();
+    let count = cleanup.files.len();
+    let size = format_size(total, humansize::BINARY);
+    if cleanup.dry_run {
+        println!("{}", t!("cleanup-planned", count = count, size = size));
+    } else {
+        println!("{}", t!("cleanup-done", count = count, size = size));
+    }
+    Ok(())
+}
+
+fn handle_doctor(args: &DoctorArgs) -> Result<(), Error> {
+    // 配置无效时无法继续检查其他项
+    let config = peersend_protocol::LocalSendConfig::load_or_init()
+        .with_context(|| t!("doctor-config-invalid"))?;
+    println!("{}", t!("doctor-config-valid"));
+    let mut problems = 0;
+
+    let download_dir = std::path::Path::new(&config.download_dir);
+    let dir = download_dir.display().to_string();
+    if download_dir.is_dir() {
+        println!("{}", t!("doctor-download-dir-ok", dir = dir.as_str()));
+    } else if args.fix {
+        match std::fs::create_dir_all(download_dir) {
+            Ok(()) => println!("{}", t!("doctor-download-dir-created", dir = dir.as_str())),
+            Err(e) => {
+                let error = e.to_string();
+                println!("{}", t!("doctor-download-dir-create-failed", dir = dir, error = error));
+                problems += 1;
+            }
+        }
+    } else {
+        println!("{}", t!("doctor-download-dir-missing", dir = dir));
+        problems += 1;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        problems += check_firewall(&config, args.fix);
+    }
+    if cfg!(target_os = "macos") {
+        use peersend_protocol::discovery::{local_network_access, LocalNetworkAccess};
+        match local_network_access() {
+            LocalNetworkAccess::Granted => println!("{}", t!("doctor-local-network-ok")),
+            LocalNetworkAccess::Denied => {
+                let error = peersend_protocol::ProtocolError::LocalNetworkDenied.to_string();
+                println!("{}", t!("doctor-local-network-denied", error = error));
+                problems += 1;
+            }
+            LocalNetworkAccess::Unknown => println!("{}", t!("doctor-local-network-unknown")),
+        }
+    }
+
+    if problems > 0 {
+        if args.fix {
+            anyhow::bail!(t!("doctor-problems", count = problems));
+        }
+        anyhow::bail!(t!("doctor-problems-fix-hint", count = problems));
+    }
+    println!("{}", t!("doctor-no-problems"));
+    Ok(())
+}
+
+/// 检查设备发现和接收端口的入站规则，fix 时补齐，返回未解决的问题数
+#[cfg(target_os = "windows")]
+fn check_firewall(config: &peersend_protocol::LocalSendConfig, fix: bool) -> usize {
+    use arch::windows::{add_discovery_firewall_rules, missing_discovery_firewall_rules};
+
+    // 多播发现固定使用默认端口，接收服务使用配置的端口
+    let (discovery_port, http_port) = (peersend_protocol::DEFAULT_PORT, config.port);
+    let missing = match missing_discovery_firewall_rules(discovery_port, http_port) {
+        Ok(missing) => missing,
+        Err(e) => {
+            println!("{}", t!("doctor-firewall-read-failed", error = format!("{:#}", e)));
+            return 1;
+        }
+    };
+    if missing.is_empty() {
+        println!("{}", t!("doctor-firewall-ok", discovery = discovery_port, http = http_port));
+        return 0;
+    }
+    if !fix {
+        println!("{}", t!("doctor-firewall-missing", rules = missing.join(", ")));
+        return 1;
+    }
+    match add_discovery_firewall_rules(discovery_port, http_port) {
+        Ok(()) => {
+            println!("{}", t!("doctor-firewall-fixed", rules = missing.join(", ")));
+            0
+        }
+        Err(e) => {
+            println!("{}", t!("doctor-firewall-fix-failed", error = format!("{:#}", e)));
+            1
+        }
+    }
+}
+
+fn handle_journal(args: &JournalArgs) -> Result<(), Error> {
+    let journal = SessionJournal::open_default().with_context(|| t!("error-open-journal"))?;
+    match &args.sub_command {
+        Some(JournalSubCommand::List) | None => {
+            let sessions = journal.incomplete().with_context(|| t!("error-read-journal"))?;
+            if sessions.is_empty() {
+                println!("{}", t!("journal-empty"));
+                return Ok(());
+            }
+            for session in &sessions {
+                let line = t!(
+                    "journal-session",
+                    session = session.session_id.as_str(),
+                    peer = session.peer_name.as_str(),
+                    received = format_size(session.received_bytes(), humansize::BINARY),
+                    total = format_size(session.total_bytes(), humansize::BINARY),
+                );
+                println!("{}", line);
+                for file in session.files.iter().filter(|f| !f.is_complete()) {
+                    println!(
+                        "    {}  {} / {}",
+                        session.part_path(file).display(),
+                        format_size(file.offset, humansize::BINARY),
+                        format_size(file.file.size, humansize::BINARY)
+                    );
+                }
+            }
+        }
+        Some(JournalSubCommand::Discard { id }) => {
+            match journal.discard(id).with_context(|| t!("error-discard-session"))? {
+                Some(removed) => {
+                    println!("{}", t!("journal-discarded", session = id.as_str(), count = removed))
+                }
+                None => anyhow::bail!(t!("journal-not-found", session = id.as_str())),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 列表中每条文本显示的最大字符数
+const INBOX_PREVIEW_CHARS: usize = 60;
+
+fn handle_inbox(args: &InboxArgs) -> Result<(), Error> {
+    let inbox = Inbox::open_default().with_context(|| t!("error-config-dir"))?;
+    let find = |id: u64| -> Result<_, Error> {
+        inbox
+            .get(id)
+            .with_context(|| t!("error-read-inbox"))?
+            .with_context(|| t!("inbox-not-found", id = id))
+    };
+    match &args.sub_command {
+        Some(InboxSubCommand::List) | None => {
+            let messages = inbox.list().with_context(|| t!("error-read-inbox"))?;
+            if messages.is_empty() {
+                println!("{}", t!("inbox-empty"));
+            }
+            for message in &messages {
+                let received = chrono::DateTime::from_timestamp(message.received_at as i64, 0)
+                    .map(|at| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    message.id,
+                    received,
+                    message.sender,
+                    message.preview(INBOX_PREVIEW_CHARS)
+                );
+            }
+        }
+        Some(InboxSubCommand::Show { id }) => println!("{}", find(*id)?.text),
+        Some(InboxSubCommand::Copy { id }) => {
+            let message = find(*id)?;
+            let clipboard = clipboard::SystemClipboard::open()?;
+            #[cfg(target_os = "linux")]
+            println!("{}", t!("inbox-copy-hold", secs = clipboard::COPY_HOLD.as_secs()));
+            clipboard.copy(&message.text)?;
+            println!("{}", t!("inbox-copied", id = *id));
+        }
+        Some(InboxSubCommand::Delete { id }) => {
+            if !inbox.delete(*id).with_context(|| t!("error-write-inbox"))? {
+                anyhow::bail!(t!("inbox-not-found", id = *id));
+            }
+            println!("{}", t!("inbox-deleted", id = *id));
+        }
+    }
+    Ok(())
+}
+
+async fn handle_requests(args: &RequestsArgs) -> Result<(), Error> {
+    let mut client = ControlClient::connect_default()
+        .await
+        .with_context(|| t!("error-control-connect"))?;
+
+    let (session_id, accept) = match &args.sub_command {
+        Some(RequestsSubCommand::List) | None => {
+            let ControlResponse::Sessions { sessions } =
+                control_request(&mut client, ControlRequest::Sessions).await?
+            else {
+                anyhow::bail!(t!("error-control-unexpected"));
+            };
+            let pending: Vec<_> = sessions.into_iter().filter(|s| s.pending).collect();
+            if pending.is_empty() {
+                println!("{}", t!("requests-empty"));
+            }
+            for session in pending {
+                let size: u64 = session.files.iter().map(|f| f.size).sum();
+                let names: Vec<_> = session.files.iter().map(|f| f.name.as_str()).collect();
+                let line = t!(
+                    "requests-pending",
+                    session = session.id.as_str(),
+                    sender = session.sender_id.as_str(),
+                    size = format_size(size, humansize::DECIMAL),
+                    files = names.join(", "),
+                );
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        Some(RequestsSubCommand::Accept { id }) => (id, true),
+        Some(RequestsSubCommand::Reject { id }) => (id, false),
+    };
+    let request = ControlRequest::Respond {
+        session_id: session_id.clone(),
+        accept,
+    };
+    control_request(&mut client, request).await?;
+    if accept {
+        println!("{}", t!("requests-accepted", session = session_id.as_str()));
+    } else {
+        println!("{}", t!("requests-rejected", session = session_id.as_str()));
+    }
+    Ok(())
+}
+
+async fn handle_events(args: &EventsArgs) -> Result<(), Error> {
+    let config = peersend_protocol::LocalSendConfig::load_or_init()
+        .with_context(|| t!("error-load-config"))?;
+    let Some(token) = config.api_token.as_deref().filter(|t| !t.is_empty()) else {
+        anyhow::bail!(t!("error-api-disabled"));
+    };
+    let base = match (&args.url, config.api_port) {
+        (Some(url), _) => url.clone(),
+        (None, Some(port)) => format!("http://127.0.0.1:{}", port),
+        (None, None) => anyhow::bail!(t!("error-api-disabled")),
+    };
+
+    if !args.follow {
+        let records = api::recent_events(&base, token)
+            .await
+            .with_context(|| t!("error-api-connect", url = base.as_str()))?;
+        for record in records {
+            println!("{}", record.event);
+        }
+        return Ok(());
+    }
+    // 先输出保留的最近事件，断开后从最后一个事件之后继续
+    let mut last_id = 0;
+    loop {
+        let mut events = EventSubscription::connect(&base, token, Some(last_id))
+            .await
+            .with_context(|| t!("error-api-connect", url = base.as_str()))?;
+        while let Some(record) = events.next().await? {
+            println!("{}", record.event);
+            last_id = record.id;
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+async fn handle_receive(args: &ReceiveArgs) -> Result<(), Error> {
+    let mut client = ControlClient::connect_default()
+        .await
+        .with_context(|| t!("error-control-connect"))?;
+    let request = match (args.open_for, args.close) {
+        (Some(duration), _) => ControlRequest::OpenGate {
+            secs: duration.as_secs(),
+        },
+        (None, true) => ControlRequest::CloseGate,
+        (None, false) => ControlRequest::Gate,
+    };
+    let ControlResponse::Gate { remaining } = control_request(&mut client, request).await? else {
+        anyhow::bail!(t!("error-control-unexpected"));
+    };
+    match remaining {
+        Some(secs) => {
+            let line = t!("gate-open", minutes = secs / 60, seconds = secs % 60);
+            println!("{}", line);
+        }
+        None => println!("{}", t!("gate-closed")),
+    }
+    Ok(())
+}
+
+/// 发送控制请求，守护进程返回错误时转为 Err
+async fn control_request(
+    client: &mut ControlClient,
+    request: ControlRequest,
+) -> Result<ControlResponse, Error> {
+    match client.request(&request).await? {
+        ControlResponse::Error { message } => anyhow::bail!(message),
+        response => Ok(response),
+    }
+}
+
+async fn handle_pair(args: &PairArgs) -> Result<(), Error> {
+    let mut config = peersend_protocol::LocalSendConfig::load_or_init()
+        .with_context(|| t!("error-load-config"))?;
+    let client = peersend_protocol::LocalSendClient::new(config.clone());
+
+    let device = find_device(&client, args.device.trim()).await?;
+
+    let pairing = Pairing::new(&config, device);
+    if config.is_trusted(&pairing.remote.id) {
+        println!("{}", t!("pair-already-trusted", name = pairing.remote.name.as_str()));
+        return Ok(());
+    }
+    let ip = pairing.remote.ip.to_string();
+    println!("{}", t!("pair-device", name = pairing.remote.name.as_str(), ip = ip));
+    println!("{}", t!("pair-fingerprint", id = pairing.remote.id.as_str()));
+    println!();
+    println!("    {}", t!("pair-code", code = pairing.code.as_str()));
+    println!();
+    println!("{}", t!("pair-instructions"));
+
+    if !args.yes {
+        print!("{} ", t!("pair-confirm"));
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            anyhow::bail!(t!("pair-not-confirmed"));
+        }
+    }
+
+    pairing.confirm(&mut config);
+    let path = peersend_protocol::LocalSendConfig::default_path()
+        .with_context(|| t!("error-config-dir"))?;
+    config.save(&path)?;
+    println!("{}", t!("pair-trusted", name = pairing.remote.name.as_str()));
+    Ok(())
+}
+
+/// 按 ID、名称、IP 或主机名查找设备
+async fn find_device(
+    client: &peersend_protocol::LocalSendClient,
+    target: &str,
+) -> Result<peersend_protocol::DeviceInfo, Error> {
+    let mut devices = find_devices(client, &[target.to_string()]).await?;
+    Ok(devices.remove(0))
+}
+
+/// 查找多台设备，只进行一次发现，任一设备未找到时返回错误
+async fn find_devices(
+    client: &peersend_protocol::LocalSendClient,
+    targets: &[String],
+) -> Result<Vec<peersend_protocol::DeviceInfo>, Error> {
+    let discovered = client.discover(std::time::Duration::from_secs(3)).await?;
+    let mut devices = Vec::with_capacity(targets.len());
+    for target in targets {
+        let device = match discovered
+            .iter()
+            .find(|d| d.id == *target || d.name == *target || d.ip == *target)
+        {
+            Some(device) => Some(device.clone()),
+            // 多播不可达时直接探测地址，主机名先解析
+            None if peersend_protocol::net::is_host(target) => client.check_device(target).await,
+            None => None,
+        };
+        let device = device
+            .ok_or_else(|| anyhow::anyhow!(t!("device-not-found", device = target.as_str())))?;
+        devices.push(device);
+    }
+    Ok(devices)
+}
+
+async fn handle_screenshot(args: &ScreenshotArgs) -> Result<(), Error> {
+    let region = args.region;
+    let path = tokio::task::spawn_blocking(move || peersend_protocol::screenshot::capture(region))
+        .await?
+        .with_context(|| t!("screenshot-failed"))?;
+    println!("{}", t!("screenshot-captured", path = path.display().to_string()));
+
+    let result = send_screenshot(args.to.trim(), &path, args.pin.clone()).await;
+    if !args.keep {
+        let _ = std::fs::remove_file(&path);
+    }
+    result?;
+    println!("{}", t!("screenshot-sent", device = args.to.as_str()));
+    Ok(())
+}
+
+/// 守护进程在运行时经控制通道发送，与其他会话列在一起并共用带宽上限，否则直接发送
+///
+/// target 为设备组时向组内所有设备发送，部分设备失败时逐个列出并返回错误
+async fn send_screenshot(
+    target: &str,
+    path: &std::path::Path,
+    pin: Option<String>,
+) -> Result<(), Error> {
+    if let Ok(mut client) = ControlClient::connect_default().await {
+        let request = ControlRequest::Send(SendRequest {
+            device: target.to_string(),
+            paths: vec![path.to_path_buf()],
+            pin,
+        });
+        match control_request(&mut client, request).await? {
+            ControlResponse::Recipients { results } => {
+                for result in &results {
+                    let session = result.session.as_ref();
+                    if let Some(summary) = session.and_then(|s| s.summary.as_ref()) {
+                        print_summary(&result.device, summary);
+                    }
+                }
+                let failed = results.iter().filter_map(|r| Some((&r.device, r.error.as_ref()?)));
+                return report_group_send(failed);
+            }
+            ControlResponse::Session { session } => {
+                if let Some(summary) = &session.summary {
+                    print_summary(target, summary);
+                }
+            }
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    let config = peersend_protocol::LocalSendConfig::load_or_init()
+        .with_context(|| t!("error-load-config"))?;
+    let targets = config.expand_target(target)?;
+    let mut client = peersend_protocol::LocalSendClient::new(config)
+        .with_route_stats(RouteStats::open_default()?);
+    if std::io::stdin().is_terminal() {
+        client = client.with_pin_prompt(|device, attempt| prompt_pin(device.clone(), attempt));
+    }
+    let devices = find_devices(&client, &targets).await?;
+    if pin.is_some() {
+        for device in &devices {
+            client.set_pin(&device.id, pin.clone());
+        }
+    }
+    if group_name(target).is_none() {
+        let session = client.send_files(&devices[0], &[path]).await?;
+        if let Some(summary) = session.summary.get() {
+            print_summary(target, summary);
+        }
+        return Ok(());
+    }
+    let results = client.broadcast_files(&devices, &[path]).await?;
+    for (device, result) in targets.iter().zip(&results) {
+        if let Some(summary) = result.as_ref().ok().and_then(|s| s.summary.get()) {
+            print_summary(device, summary);
+        }
+    }
+    let errors: Vec<_> = results.into_iter().map(|r| r.err().map(|e| e.to_string())).collect();
+    let failed = targets.iter().zip(&errors).filter_map(|(device, e)| Some((device, e.as_ref()?)));
+    report_group_send(failed)
+}
+
+/// 对方要求 PIN 时在终端询问，输入为空表示放弃
+async fn prompt_pin(device: peersend_protocol::DeviceInfo, attempt: u32) -> Option<String> {
+    tokio::task::spawn_blocking(move || {
+        let key = if attempt == 1 { "pin-prompt" } else { "pin-retry" };
+        print!("{} ", t!(key, device = device.name.as_str()));
+        std::io::stdout().flush().ok()?;
+        let mut pin = String::new();
+        std::io::stdin().read_line(&mut pin).ok()?;
+        let pin = pin.trim();
+        (!pin.is_empty()).then(|| pin.to_string())
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// 打印会话结束时的摘要
+fn print_summary(device: &str, summary: &peersend_protocol::SessionSummary) {
+    use peersend_protocol::summary::FileOutcome;
+
+    println!(
+        "{}",
+        t!(
+            "summary-result",
+            device = device,
+            transferred = summary.transferred,
+            skipped = summary.skipped,
+            failed = summary.failed
+        )
+    );
+    println!(
+        "  {}",
+        t!(
+            "summary-stats",
+            size = format_size(summary.total_bytes, humansize::BINARY),
+            duration = format!("{:.1}", summary.duration_ms as f64 / 1000.0),
+            speed = format_size(summary.average_speed, humansize::BINARY)
+        )
+    );
+    if summary.verified + summary.mismatched > 0 {
+        println!(
+            "  {}",
+            t!(
+                "summary-verification",
+                verified = summary.verified,
+                mismatched = summary.mismatched
+            )
+        );
+    }
+    for file in summary.files.iter().filter(|f| f.outcome == FileOutcome::Failed) {
+        let reason = file.reason.as_deref().unwrap_or_default();
+        println!("  {}", t!("summary-file-failed", name = file.name.as_str(), reason = reason));
+    }
+    for warning in &summary.warnings {
+        let (name, reason) = (warning.name.as_str(), warning.reason.as_str());
+        println!("  {}", t!("summary-warning", name = name, reason = reason));
+    }
+}
+
+/// 列出向设备组中发送失败的设备，有失败时返回错误
+fn report_group_send<'a>(
+    failed: impl Iterator<Item = (&'a String, &'a String)>,
+) -> Result<(), Error> {
+    let mut count = 0;
+    for (device, error) in failed {
+        eprintln!("{}", t!("group-send-failed", device = device.as_str(), error = error.as_str()));
+        count += 1;
+    }
+    if count > 0 {
+        anyhow::bail!(t!("group-send-incomplete", count = count));
+    }
+    Ok(())
+}
+
+fn describe_rules(rules: &[String], empty: &str) -> String {
+    if rules.is_empty() {
+        empty.to_string()
+    } else {
+        rules.join(", ")
+    }
+}
+
+async fn handle_share(args: &ShareArgs) -> Result<(), Error> {
+    let store = ShareStore::open_default()?;
+
+    if let Some(token) = &args.revoke {
+        if !store.revoke(token)? {
+            anyhow::bail!(t!("share-not-found", token = token.as_str()));
+        }
+        println!("{}", t!("share-revoked"));
+        return Ok(());
+    }
+
+    let config = peersend_protocol::LocalSendConfig::load_or_init()
+        .with_context(|| t!("error-load-config"))?;
+    let Some(port) = config.share_port else {
+        anyhow::bail!(t!("share-no-port"));
+    };
+    let host = match &args.host {
+        Some(host) => host.clone(),
+        None => local_ip()
+            .map(|ip| ip.to_string())
+            .with_context(|| t!("share-no-host"))?,
+    };
+    let url = |link: &ShareLink| {
+        format!("http://{}{}", peersend_protocol::net::host_port(&host, port), link.url_path())
+    };
+
+    if args.list {
+        for link in store.list()? {
+            let expires = link
+                .expires_at
+                .and_then(|at| chrono::DateTime::from_timestamp(at as i64, 0))
+                .map(|at| at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| t!("share-never-expires"));
+            let downloads = match link.max_downloads {
+                Some(max) => format!("{}/{}", link.downloads, max),
+                None => link.downloads.to_string(),
+            };
+            println!("{}\t{}\t{}\t{}", url(&link), link.path.display(), expires, downloads);
+        }
+        return Ok(());
+    }
+
+    let Some(file) = &args.file else {
+        return Ok(());
+    };
+    let link = store.create(file, args.expires, args.max_downloads)?;
+    println!("{}", url(&link));
+    Ok(())
+}
+
+/// 默认路由使用的本机地址，只选择出口网卡，不发送数据
+///
+/// 没有 IPv4 默认路由时 (例如仅 IPv6 的网段) 使用 IPv6 地址
+fn local_ip() -> Option<IpAddr> {
+    let route = |bind: &str, target: &str| {
+        let socket = std::net::UdpSocket::bind(bind).ok()?;
+        socket.connect(target).ok()?;
+        Some(socket.local_addr().ok()?.ip())
+    };
+    route("0.0.0.0:0", "192.0.2.1:9").or_else(|| route("[::]:0", "[2001:db8::1]:9"))
+}
+
 fn print_output<T>(
     items: &[T],
     format: &OutputFormat,
@@ -899,6 +2193,27 @@ fn optional_column_targets(
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     let cli = Cli::parse();
+    let log_filter = if cli.verbose { "debug" } else { "info" };
+    // 保留到 main 返回，退出前导出剩余的 span
+    #[cfg(feature = "otel")]
+    let _otlp = match cli.otlp_endpoint.as_deref() {
+        Some(endpoint) => {
+            match peersend_protocol::logging::init_tracing_with_otlp(log_filter, cli.log_format, Some(endpoint)) {
+                Ok(guard) => Some(guard),
+                Err(e) => {
+                    eprintln!("{}", t!("otlp-failed", error = e.to_string()));
+                    let _ = peersend_protocol::logging::init_tracing_with_format(log_filter, cli.log_format);
+                    None
+                }
+            }
+        }
+        None => {
+            let _ = peersend_protocol::logging::init_tracing_with_format(log_filter, cli.log_format);
+            None
+        }
+    };
+    #[cfg(not(feature = "otel"))]
+    let _ = peersend_protocol::logging::init_tracing_with_format(log_filter, cli.log_format);
 
     // 处理不需要 RPC 连接的命令
     match &cli.sub_command {
@@ -915,30 +2230,83 @@ async fn main() -> Result<(), Error> {
                 dhcp: args.dhcp,
                 ipv4: args.ipv4.clone(),
                 enable_wg: args.enable_wg,
+                enable_kcp_proxy: args.enable_kcp_proxy,
+                enable_quic_proxy: args.enable_quic_proxy,
+                latency_first: args.latency_first,
                 rpc_portal,
             };
 
             daemon.start(&config).await?;
-            println!("PeerSend 网络已启动");
+            println!("{}", t!("network-started"));
             return Ok(());
         }
         SubCommand::Stop => {
             let daemon = EasyTierDaemon::new(None);
             daemon.stop().await?;
-            println!("PeerSend 网络已停止");
+            println!("{}", t!("network-stopped"));
             return Ok(());
         }
         SubCommand::Status => {
             let daemon = EasyTierDaemon::new(None);
             let status = daemon.status().await;
-            println!("状态: {}", if status.running { "运行中" } else { "已停止" });
+            let running = if status.running { "yes" } else { "no" };
+            println!("{}", t!("network-status", running = running));
             if let Some(pid) = status.pid {
-                println!("PID: {}", pid);
+                println!("{}", t!("network-pid", pid = pid));
             }
-            println!("对等点数量: {}", status.peer_count);
-            println!("网络名称: {}", status.network_name);
+            println!("{}", t!("network-peers", count = status.peer_count));
+            println!("{}", t!("network-name", name = status.network_name.as_str()));
             return Ok(());
         }
+        SubCommand::Service(args) => {
+            return handle_service(args).await;
+        }
+        #[cfg(target_os = "linux")]
+        SubCommand::Bridge(args) => {
+            return handle_bridge(args).await;
+        }
+        SubCommand::Share(args) => {
+            return handle_share(args).await;
+        }
+        SubCommand::Access(args) => {
+            return handle_access(args);
+        }
+        SubCommand::Devices(args) => {
+            return handle_devices(args);
+        }
+        SubCommand::Config(args) => {
+            return handle_config(args);
+        }
+        SubCommand::Audit(args) => {
+            return handle_audit(args);
+        }
+        SubCommand::Pair(args) => {
+            return handle_pair(args).await;
+        }
+        SubCommand::Requests(args) => {
+            return handle_requests(args).await;
+        }
+        SubCommand::Events(args) => {
+            return handle_events(args).await;
+        }
+        SubCommand::Receive(args) => {
+            return handle_receive(args).await;
+        }
+        SubCommand::Cleanup(args) => {
+            return handle_cleanup(args);
+        }
+        SubCommand::Journal(args) => {
+            return handle_journal(args);
+        }
+        SubCommand::Inbox(args) => {
+            return handle_inbox(args);
+        }
+        SubCommand::Doctor(args) => {
+            return handle_doctor(args);
+        }
+        SubCommand::Screenshot(args) => {
+            return handle_screenshot(args).await;
+        }
         _ => {}
     }
 
@@ -957,9 +2325,28 @@ async fn main() -> Result<(), Error> {
     };
 
     match cli.sub_command {
-        SubCommand::Start(_) | SubCommand::Stop | SubCommand::Status => {
+        SubCommand::Start(_)
+        | SubCommand::Stop
+        | SubCommand::Status
+        | SubCommand::Service(_) => {
             // 已经在前面处理过了
         }
+        #[cfg(target_os = "linux")]
+        SubCommand::Bridge(_) => {}
+        SubCommand::Share(_)
+        | SubCommand::Access(_)
+        | SubCommand::Devices(_)
+        | SubCommand::Config(_)
+        | SubCommand::Audit(_)
+        | SubCommand::Pair(_)
+        | SubCommand::Requests(_)
+        | SubCommand::Events(_)
+        | SubCommand::Receive(_)
+        | SubCommand::Cleanup(_)
+        | SubCommand::Journal(_)
+        | SubCommand::Inbox(_)
+        | SubCommand::Doctor(_)
+        | SubCommand::Screenshot(_) => {}
         SubCommand::Peer(peer_args) => match &peer_args.sub_command {
             Some(PeerSubCommand::Add) => {
                 println!("add peer");
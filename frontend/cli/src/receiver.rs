@@ -0,0 +1,578 @@
+//! 接收守护进程
+//!
+//! 运行 LocalSend 接收服务，直到收到停止信号
+//! 收到重载信号时重新读取配置并重启服务
+//!
+//! 配置了 `browse_port` 时同时提供带密码的已接收文件浏览页面，配置了 `share_port` 时
+//! 提供 `peersend share` 生成的分享链接，
+//! 配置了 `outboxes` 时监视各发件箱目录并自动发送放入的文件，
+//! 发送时按 `route_preferences` 或记录在 `routes.json` 中的实测速度选择上传途径，
+//! 配置了 `clipboard_sync` 时与指定的已配对设备双向同步剪贴板文本，
+//! 配置了 `extract` 时把收到的 zip、tar.gz 压缩包解压到以压缩包命名的文件夹，
+//! 配置了 `hooks` 时在每个文件或会话接收完成后执行命令，输出记入审计日志，
+//! 启用 `hash_manifest` 时在接收的会话完成后于下载目录写入 `SHA256SUMS-` 哈希清单，
+//! 配置了 `webhooks` 时在收到请求、会话完成或失败时发送通知，
+//! 配置了 `mqtt` 时向 MQTT 代理发布发现和传输事件，
+//! 配置了 `api_port` 时提供带令牌认证的管理 API 和网页管理界面，
+//! 启用 `audit_log` 时在审计日志中记录请求、决定和传输结果，
+//! 配置了 `bandwidth_limit` 或 `bandwidth_schedule` 时按时段限制收发的合计速度，
+//! 配置了 `metered` 时在按流量计费或使用电池时进入省流模式，暂缓发件箱中的大文件并限速，
+//! 收到的文件哈希记入索引，`duplicate_policy` 为 skip 或 link 时不再重复接收相同的文件，
+//! 配置了 `retention` 时每小时按保留天数和大小上限删除或归档下载目录中的旧文件。
+//! 接受的会话和文件进度记入会话日志，启动时列出上次运行中断的会话。
+//! 收到的文本消息保存到收件箱，可用 `peersend inbox` 稍后查看。
+//! Windows 上首次运行时按端口添加设备发现和接收服务的防火墙入站规则，
+//! macOS 上启动时检查本地网络权限，被拒绝时提示到系统设置中允许。
+//! 本地控制通道 (Unix 域套接字或 Windows 命名管道) 始终开启，
+//! 供同一用户的命令行和图形界面查询会话、答复请求，无需开放 TCP 端口。
+//! Linux 上会话总线可用时发送桌面通知，并注册供桌面环境答复请求的总线接口
+//!
+//! 守护进程定期自检并在本地提供健康检查端点，自检连续失败时退出，
+//! 交由服务管理器重启。配置了 `metrics_port` 时在该端口提供 Prometheus 格式的
+//! `/metrics`，包括协议指标、运行时长和 easytier 的连通状态
+
+use std::{
+    future::Future,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use peersend_protocol::{
+    audit::AuditLog,
+    clipboard::ClipboardSync,
+    control::{self, Controller},
+    discovery::{self, LocalNetworkAccess},
+    hooks::PostReceiveHooks,
+    inbox::Inbox,
+    journal::SessionJournal,
+    metered::Metered,
+    metrics::{self, Metrics, TextEncoder},
+    mqtt::MqttPublisher,
+    net,
+    outbox::Outbox,
+    retention::Retention,
+    routes::RouteStats,
+    server::{accept::AcceptGate, LocalSendServer},
+    storage::{dedup::HashIndex, manifest::ManifestWriter},
+    throttle::Throttle,
+    webhook::WebhookNotifier,
+    DiscoveryManager, EventBus, LocalSendClient, LocalSendConfig, ProtocolError, ProtocolEvent,
+    SessionManager,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{broadcast, mpsc, Mutex},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::clipboard::SystemClipboard;
+use crate::daemon::EasyTierDaemon;
+use crate::service::notify;
+
+/// 本地健康检查端口
+pub const HEALTH_PORT: u16 = 53318;
+
+/// 自检间隔
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 单次自检超时
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 自检连续失败次数上限
+const MAX_HEALTH_FAILURES: u32 = 3;
+
+/// 读取接收服务配置
+fn load_config() -> Result<LocalSendConfig> {
+    LocalSendConfig::load_or_init().context("读取配置失败")
+}
+
+/// 首次运行时的一次性设置，失败只记录警告
+fn on_first_run(config: &LocalSendConfig) {
+    // 部分机器上只放行程序仍会拦截多播发现，按端口添加入站规则
+    #[cfg(target_os = "windows")]
+    if let Err(e) = crate::arch::windows::add_discovery_firewall_rules(
+        peersend_protocol::DEFAULT_PORT,
+        config.port,
+    ) {
+        tracing::warn!(
+            error = format!("{:#}", e),
+            "添加防火墙规则失败，可以管理员身份运行 peersend doctor --fix"
+        );
+    }
+    #[cfg(not(target_os = "windows"))]
+    let _ = config;
+}
+
+/// 运行接收守护进程
+pub async fn run<F>(shutdown: F, mut reload: mpsc::Receiver<()>) -> Result<()>
+where
+    F: Future<Output = ()>,
+{
+    tokio::pin!(shutdown);
+
+    let started = Instant::now();
+    let healthy = Arc::new(AtomicBool::new(false));
+    let health_listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], HEALTH_PORT)))
+        .await
+        .context("启动健康检查端点失败")?;
+    tokio::spawn(serve_health(health_listener, healthy.clone()));
+
+    let check_interval = notify::watchdog_interval()
+        .map_or(HEALTH_CHECK_INTERVAL, |interval| interval.min(HEALTH_CHECK_INTERVAL));
+    let mut ticker = tokio::time::interval(check_interval);
+    let mut failures = 0;
+
+    // systemd 套接字激活时沿用传入的监听套接字，重载期间端口保持占用
+    let inherited = notify::listen_fds().into_iter().next();
+    #[cfg(target_os = "linux")]
+    let mut desktop: Option<tokio::task::JoinHandle<()>> = None;
+    let mut control: Option<tokio::task::JoinHandle<()>> = None;
+    // macOS 未授予本地网络权限时发现和接收都会静默失败
+    if discovery::local_network_access() == LocalNetworkAccess::Denied {
+        tracing::warn!("{}", ProtocolError::LocalNetworkDenied);
+    }
+    // 首次运行时尚未生成配置文件
+    let mut first_run = LocalSendConfig::default_path().is_some_and(|path| !path.exists());
+
+    loop {
+        let config = load_config()?;
+        if std::mem::take(&mut first_run) {
+            on_first_run(&config);
+        }
+        let addr = SocketAddr::new(config.ip_mode.unspecified(), config.port);
+        let browse_port = config.browse_port;
+        let api_port = config.api_port;
+        let cancel = CancellationToken::new();
+        let _stop = cancel.clone().drop_guard();
+        let metered = Metered::from_config(&config);
+        tokio::spawn(metered.clone().run(cancel.child_token()));
+        // 发件箱和控制通道的发送共用上传途径的实测结果
+        let route_stats = RouteStats::open_default().context("打开上传途径实测结果失败")?;
+        start_outboxes(&config, &metered, &route_stats, &cancel);
+
+        // 会话和发现共用事件总线，Webhook 和 MQTT 从中订阅
+        let events = EventBus::default();
+        let sessions = SessionManager::with_event_bus(events.clone());
+        start_clipboard_sync(&config, &events, &cancel);
+        let inbox = Inbox::open_default().context("打开收件箱失败")?;
+        tokio::spawn(inbox.run(events.subscribe(), cancel.child_token()));
+        log_summaries(&events, &cancel);
+        if !config.webhooks.is_empty() {
+            let notifier =
+                WebhookNotifier::new(config.webhooks.clone()).context("创建 Webhook 客户端失败")?;
+            tokio::spawn(notifier.run(events.subscribe(), sessions.clone(), cancel.child_token()));
+        }
+        if let Some(mqtt) = &config.mqtt {
+            let publisher = MqttPublisher::new(mqtt, &config.device_id).context("MQTT 配置无效")?;
+            tokio::spawn(publisher.run(events.subscribe(), cancel.child_token()));
+        }
+        let audit = if config.audit_log {
+            let audit = AuditLog::open_default().context("打开审计日志失败")?;
+            tokio::spawn(audit.clone().run(
+                events.subscribe(),
+                sessions.clone(),
+                config.device_id.clone(),
+                cancel.child_token(),
+            ));
+            Some(audit)
+        } else {
+            None
+        };
+        if let Some(mut retention) = Retention::from_config(&config) {
+            if let Some(audit) = &audit {
+                retention = retention.with_audit_log(audit.clone());
+            }
+            tokio::spawn(retention.run(cancel.child_token()));
+        }
+        if let Some(mut hooks) = PostReceiveHooks::from_config(&config) {
+            if let Some(audit) = &audit {
+                hooks = hooks.with_audit_log(audit.clone());
+            }
+            tokio::spawn(hooks.run(events.subscribe(), sessions.clone(), cancel.child_token()));
+        }
+        if let Some(manifests) = ManifestWriter::from_config(&config) {
+            tokio::spawn(manifests.run(events.subscribe(), cancel.child_token()));
+        }
+        let hash_index = HashIndex::open_default().context("打开哈希索引失败")?;
+        let journal = SessionJournal::open_default().context("打开会话日志失败")?;
+        for session in journal.incomplete().context("读取会话日志失败")? {
+            tracing::warn!(
+                session_id = %session.session_id,
+                peer = %session.peer_name,
+                received = session.received_bytes(),
+                total = session.total_bytes(),
+                "上次运行中断的接收会话，可用 peersend journal 查看或清理"
+            );
+        }
+        let metrics = Metrics::new();
+        tokio::spawn(metrics.clone().run(events.subscribe(), sessions.clone(), cancel.child_token()));
+        let accept = AcceptGate::new();
+        #[cfg(target_os = "linux")]
+        {
+            // 等待上一轮释放总线名称
+            if let Some(previous) = desktop.take() {
+                let _ = previous.await;
+            }
+            let run = crate::desktop::run(
+                events.subscribe(),
+                sessions.clone(),
+                accept.clone(),
+                cancel.child_token(),
+            );
+            desktop = Some(tokio::spawn(async move {
+                if let Err(e) = run.await {
+                    tracing::warn!(error = format!("{:#}", e), "桌面集成不可用");
+                }
+            }));
+        }
+        let metrics_port = config.metrics_port;
+        let ip_mode = config.ip_mode;
+        // 控制通道发起的发送与接收的会话列在一起，并共用带宽上限
+        let throttle = Throttle::from_config(&config).with_metered(metered);
+        let control_client = LocalSendClient::new(config.clone())
+            .with_session_manager(sessions.clone())
+            .with_route_stats(route_stats.clone())
+            .with_throttle(throttle.clone());
+        let audit_path = audit.as_ref().map(AuditLog::path);
+        let session_manager = Arc::new(Mutex::new(sessions));
+        let discovery_manager = Arc::new(Mutex::new(DiscoveryManager::with_event_bus(events)));
+        let mut controller = Controller::new(
+            control_client,
+            session_manager.clone(),
+            discovery_manager.clone(),
+            accept.clone(),
+        );
+        if let Some(path) = audit_path {
+            controller = controller.with_audit_log(path);
+        }
+        let mut server = LocalSendServer::new(
+            addr,
+            config,
+            session_manager.clone(),
+            discovery_manager.clone(),
+        )
+        .with_accept_gate(accept)
+        .with_hash_index(hash_index)
+        .with_journal(journal)
+        .with_throttle(throttle)
+        .with_cancellation(cancel.child_token());
+        if let Some(audit) = audit {
+            server = server.with_audit_log(audit);
+        }
+        if let Some(listener) = &inherited {
+            server = server.with_listener(listener.try_clone().context("复制监听套接字失败")?);
+        }
+        server.start().await.context("启动接收服务失败")?;
+        // 等待上一轮删除套接字
+        if let Some(previous) = control.take() {
+            let _ = previous.await;
+        }
+        control = control::default_path().map(|path| {
+            let cancel = cancel.child_token();
+            tokio::spawn(async move {
+                if let Err(e) = control::serve(controller, &path, cancel).await {
+                    tracing::warn!(error = %e, "本地控制通道不可用");
+                }
+            })
+        });
+        if let Some(port) = browse_port {
+            tracing::info!(port, "已接收文件浏览页面: http://<本机地址>:{}/", port);
+        }
+        if let Some(port) = api_port {
+            tracing::info!(port, "管理界面: http://<本机地址>:{}/", port);
+        }
+        if let Some(port) = metrics_port {
+            let listener = net::bind_tcp(SocketAddr::new(addr.ip(), port), ip_mode)
+                .and_then(|listener| {
+                    listener.set_nonblocking(true)?;
+                    TcpListener::from_std(listener)
+                })
+                .context("启动指标端点失败")?;
+            let source = MetricsSource {
+                metrics,
+                sessions: session_manager.clone(),
+                discovery: discovery_manager,
+                started,
+                healthy: healthy.clone(),
+            };
+            tokio::spawn(serve_metrics(listener, source, cancel.child_token()));
+            tracing::info!(port, "Prometheus 指标: http://<本机地址>:{}/metrics", port);
+        }
+        healthy.store(true, Ordering::Relaxed);
+        notify::notify("READY=1");
+
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => {
+                    notify::notify("STOPPING=1");
+                    tracing::info!("接收服务已停止");
+                    return Ok(());
+                }
+                Some(()) = reload.recv() => {
+                    tracing::info!("收到重载信号，重新加载配置");
+                    notify::notify("RELOADING=1");
+                    break;
+                }
+                _ = ticker.tick() => {
+                    let ok = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, session_manager.lock())
+                        .await
+                        .is_ok();
+                    healthy.store(ok, Ordering::Relaxed);
+
+                    if ok {
+                        failures = 0;
+                        notify::notify("WATCHDOG=1");
+                    } else {
+                        failures += 1;
+                        tracing::warn!(failures, max = MAX_HEALTH_FAILURES, "接收服务自检失败");
+                        if failures >= MAX_HEALTH_FAILURES {
+                            anyhow::bail!("接收服务无响应，退出等待服务管理器重启");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 为配置中的每个发件箱启动监视任务，cancel 取消时停止
+fn start_outboxes(
+    config: &LocalSendConfig,
+    metered: &Metered,
+    route_stats: &RouteStats,
+    cancel: &CancellationToken,
+) {
+    if config.outboxes.is_empty() {
+        return;
+    }
+    let throttle = Throttle::from_config(config).with_metered(metered.clone());
+    let client = LocalSendClient::new(config.clone())
+        .with_route_stats(route_stats.clone())
+        .with_throttle(throttle);
+    for outbox in &config.outboxes {
+        let outbox = Outbox::from_config(client.clone(), outbox).with_metered(metered.clone());
+        let cancel = cancel.child_token();
+        tracing::info!(dir = %outbox.dir().display(), "监视发件箱");
+        tokio::spawn(async move {
+            if let Err(e) = outbox.watch(cancel).await {
+                tracing::error!(
+                    dir = %outbox.dir().display(),
+                    error = format!("{:#}", e),
+                    "监视发件箱失败"
+                );
+            }
+        });
+    }
+}
+
+/// 配置了剪贴板同步时启动同步任务，cancel 取消时停止
+fn start_clipboard_sync(config: &LocalSendConfig, events: &EventBus, cancel: &CancellationToken) {
+    let Some(sync_config) = &config.clipboard_sync else {
+        return;
+    };
+    let clipboard = match SystemClipboard::open() {
+        Ok(clipboard) => clipboard,
+        Err(e) => {
+            tracing::warn!(error = format!("{:#}", e), "剪贴板同步不可用");
+            return;
+        }
+    };
+    let sync = ClipboardSync::from_config(LocalSendClient::new(config.clone()), sync_config);
+    let device = sync_config.device.clone();
+    let (events, cancel) = (events.subscribe(), cancel.child_token());
+    tokio::spawn(async move {
+        if let Err(e) = sync.run(clipboard, events, cancel).await {
+            tracing::error!(%device, error = format!("{:#}", e), "剪贴板同步失败");
+        }
+    });
+}
+
+/// 把会话结束时的摘要写入日志
+fn log_summaries(events: &EventBus, cancel: &CancellationToken) {
+    let (mut events, cancel) = (events.subscribe(), cancel.child_token());
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = events.recv() => event,
+            };
+            match event {
+                Ok(ProtocolEvent::SessionSummary(summary)) => tracing::info!(
+                    session_id = %summary.session_id,
+                    state = %summary.state,
+                    transferred = summary.transferred,
+                    skipped = summary.skipped,
+                    failed = summary.failed,
+                    bytes = summary.total_bytes,
+                    duration_ms = summary.duration_ms,
+                    speed = summary.average_speed,
+                    verified = summary.verified,
+                    mismatched = summary.mismatched,
+                    "会话结束"
+                ),
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// 提供本地健康检查端点，对任意请求返回当前健康状态
+async fn serve_health(listener: TcpListener, healthy: Arc<AtomicBool>) {
+    while let Ok((mut stream, _)) = listener.accept().await {
+        let healthy = healthy.load(Ordering::Relaxed);
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await;
+
+            let (status, body) = if healthy {
+                ("200 OK", "ok")
+            } else {
+                ("503 Service Unavailable", "unhealthy")
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+/// `/metrics` 的数据来源
+struct MetricsSource {
+    metrics: Metrics,
+    sessions: Arc<Mutex<SessionManager>>,
+    discovery: Arc<Mutex<DiscoveryManager>>,
+    started: Instant,
+    healthy: Arc<AtomicBool>,
+}
+
+impl MetricsSource {
+    /// 协议指标加上守护进程自身的状态
+    async fn render(&self) -> String {
+        let mut encoder = TextEncoder::new();
+        let sessions = self.sessions.lock().await.clone();
+        let discovery = self.discovery.lock().await.clone();
+        self.metrics.encode(&mut encoder, &sessions, &discovery).await;
+
+        encoder.gauge(
+            "peersend_uptime_seconds",
+            "接收守护进程的运行时长",
+            self.started.elapsed().as_secs_f64(),
+        );
+        encoder.gauge(
+            "peersend_healthy",
+            "最近一次自检是否通过",
+            f64::from(u8::from(self.healthy.load(Ordering::Relaxed))),
+        );
+
+        let easytier = EasyTierDaemon::new(None);
+        let running = tokio::task::spawn_blocking(|| EasyTierDaemon::new(None).is_running())
+            .await
+            .unwrap_or(false);
+        encoder.gauge(
+            "peersend_easytier_running",
+            "easytier-core 进程是否在运行",
+            f64::from(u8::from(running)),
+        );
+        encoder.gauge(
+            "peersend_easytier_rpc_up",
+            "easytier RPC 端口是否可连接",
+            f64::from(u8::from(easytier.check_rpc_connection().await)),
+        );
+        encoder.finish()
+    }
+}
+
+/// 提供 Prometheus 指标端点，直到 cancel 取消
+///
+/// 只响应 `GET /metrics`，其他路径返回 404
+async fn serve_metrics(listener: TcpListener, source: MetricsSource, cancel: CancellationToken) {
+    let source = Arc::new(source);
+    loop {
+        let mut stream = tokio::select! {
+            _ = cancel.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!(error = %e, "指标端点接受连接失败");
+                    continue;
+                }
+            },
+        };
+        let source = source.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let Ok(Ok(len)) = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, stream.read(&mut buf)).await
+            else {
+                return;
+            };
+            let request = String::from_utf8_lossy(&buf[..len]);
+            let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+            let (status, content_type, body) = if request.starts_with("GET ")
+                && path.split('?').next() == Some("/metrics")
+            {
+                ("200 OK", metrics::CONTENT_TYPE, source.render().await)
+            } else {
+                ("404 Not Found", "text/plain", "not found".to_string())
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                content_type,
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
+/// 查询本地接收守护进程的健康状态
+pub async fn check_health() -> Result<bool> {
+    let mut stream = tokio::time::timeout(
+        HEALTH_CHECK_TIMEOUT,
+        TcpStream::connect(SocketAddr::from(([127, 0, 0, 1], HEALTH_PORT))),
+    )
+    .await
+    .context("连接健康检查端点超时")?
+    .context("接收服务未运行")?;
+
+    stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await?;
+
+    let mut response = String::new();
+    tokio::time::timeout(HEALTH_CHECK_TIMEOUT, stream.read_to_string(&mut response))
+        .await
+        .context("读取健康检查结果超时")??;
+
+    Ok(response.starts_with("HTTP/1.1 200"))
+}
+
+/// 将 SIGHUP 转换为重载通知
+#[cfg(unix)]
+pub fn reload_on_sighup() -> Result<mpsc::Receiver<()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup()).context("注册 SIGHUP 失败")?;
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            if tx.send(()).await.is_err() {
+                break;
+            }
+        }
+    });
+    Ok(rx)
+}
@@ -2,14 +2,84 @@
 //!
 //! 提供跨平台服务管理功能
 
+pub mod notify;
+#[cfg(target_os = "windows")]
+pub mod windows;
+
+use std::time::Duration;
+
+use anyhow::Context;
+
+/// 默认服务名称
+pub const DEFAULT_SERVICE_NAME: &str = "peersend";
+
 /// 服务安装选项
 #[derive(Debug, Default)]
 pub struct ServiceInstallOptions {
+    pub name: String,
     pub program: String,
     pub args: Vec<String>,
     pub work_directory: String,
     pub disable_autostart: bool,
     pub description: Option<String>,
+    /// 运行服务的用户，为空时使用服务管理器默认账户
+    pub user: Option<String>,
+    /// 服务进程的环境变量
+    pub env: Vec<(String, String)>,
+    /// 启用 systemd 套接字激活时监听的端口
+    pub socket_port: Option<u16>,
+}
+
+impl ServiceInstallOptions {
+    /// 校验服务名称、运行用户和环境变量，避免生成无效的服务定义
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.name.is_empty() || self.name.len() > 64 {
+            anyhow::bail!("服务名称长度必须在 1 到 64 之间");
+        }
+        if self.name.starts_with(['.', '-'])
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            anyhow::bail!("服务名称只能包含字母、数字、'-'、'_' 和 '.': {}", self.name);
+        }
+
+        if let Some(user) = &self.user {
+            // Windows 账户名可以包含空格，如 "NT AUTHORITY\LocalService"
+            let invalid_char = |c: char| c.is_control() || (!cfg!(windows) && c.is_whitespace());
+            if user.trim().is_empty() || user.chars().any(invalid_char) {
+                anyhow::bail!("无效的运行用户: {:?}", user);
+            }
+        }
+
+        for (key, value) in &self.env {
+            let valid_key = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid_key {
+                anyhow::bail!("无效的环境变量名: {:?}", key);
+            }
+            if value.chars().any(|c| matches!(c, '\0' | '\n' | '\r')) {
+                anyhow::bail!("环境变量 {} 的值不能包含换行或空字符", key);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 将字符串转为单引号包裹的 shell 字面量
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// 生成 shell 脚本中的环境变量导出语句
+#[cfg(target_os = "linux")]
+fn shell_exports(env: &[(String, String)]) -> String {
+    env.iter()
+        .map(|(key, value)| format!("export {}={}\n", key, shell_quote(value)))
+        .collect()
 }
 
 /// 服务管理器 trait
@@ -18,7 +88,10 @@ pub trait ServiceManager {
     fn uninstall(&self, name: &str) -> Result<(), anyhow::Error>;
     fn start(&self, name: &str) -> Result<(), anyhow::Error>;
     fn stop(&self, name: &str) -> Result<(), anyhow::Error>;
+    fn restart(&self, name: &str) -> Result<(), anyhow::Error>;
+    fn reload(&self, name: &str) -> Result<(), anyhow::Error>;
     fn status(&self, name: &str) -> Result<ServiceStatus, anyhow::Error>;
+    fn logs(&self, name: &str, follow: bool, since: Option<Duration>) -> Result<(), anyhow::Error>;
 }
 
 /// 服务状态
@@ -29,21 +102,43 @@ pub enum ServiceStatus {
     NotInstalled,
 }
 
+/// 运行日志查看命令，输出直接交给终端
+fn run_log_command(cmd: &mut std::process::Command) -> Result<(), anyhow::Error> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let status = cmd
+        .status()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
+
 /// 系统服务管理器
-pub struct SystemServiceManager;
+pub struct SystemServiceManager {
+    /// 是否以当前用户身份管理服务（无需 root）
+    user: bool,
+}
 
 impl SystemServiceManager {
     /// 创建新的系统服务管理器
     pub fn new() -> Self {
-        Self
+        Self { user: false }
+    }
+
+    /// 创建用户级服务管理器
+    pub fn new_user() -> Self {
+        Self { user: true }
     }
 }
 
 impl ServiceManager for SystemServiceManager {
     fn install(&self, options: &ServiceInstallOptions) -> Result<(), anyhow::Error> {
+        options.validate()?;
+
         #[cfg(target_os = "linux")]
         {
-            self.install_systemd(options)
+            self.install_linux(options)
         }
         #[cfg(target_os = "macos")]
         {
@@ -62,7 +157,7 @@ impl ServiceManager for SystemServiceManager {
     fn uninstall(&self, name: &str) -> Result<(), anyhow::Error> {
         #[cfg(target_os = "linux")]
         {
-            self.uninstall_systemd(name)
+            self.uninstall_linux(name)
         }
         #[cfg(target_os = "macos")]
         {
@@ -81,7 +176,7 @@ impl ServiceManager for SystemServiceManager {
     fn start(&self, name: &str) -> Result<(), anyhow::Error> {
         #[cfg(target_os = "linux")]
         {
-            self.start_systemd(name)
+            self.start_linux(name)
         }
         #[cfg(target_os = "macos")]
         {
@@ -100,7 +195,7 @@ impl ServiceManager for SystemServiceManager {
     fn stop(&self, name: &str) -> Result<(), anyhow::Error> {
         #[cfg(target_os = "linux")]
         {
-            self.stop_systemd(name)
+            self.stop_linux(name)
         }
         #[cfg(target_os = "macos")]
         {
@@ -116,10 +211,48 @@ impl ServiceManager for SystemServiceManager {
         }
     }
 
+    fn restart(&self, name: &str) -> Result<(), anyhow::Error> {
+        #[cfg(target_os = "linux")]
+        {
+            self.restart_linux(name)
+        }
+        #[cfg(target_os = "macos")]
+        {
+            self.restart_launchd(name)
+        }
+        #[cfg(target_os = "windows")]
+        {
+            self.restart_windows(name)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+        {
+            anyhow::bail!("不支持的操作系统")
+        }
+    }
+
+    fn reload(&self, name: &str) -> Result<(), anyhow::Error> {
+        #[cfg(target_os = "linux")]
+        {
+            self.reload_linux(name)
+        }
+        #[cfg(target_os = "macos")]
+        {
+            self.reload_launchd(name)
+        }
+        #[cfg(target_os = "windows")]
+        {
+            self.reload_windows(name)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+        {
+            anyhow::bail!("不支持的操作系统")
+        }
+    }
+
     fn status(&self, name: &str) -> Result<ServiceStatus, anyhow::Error> {
         #[cfg(target_os = "linux")]
         {
-            self.status_systemd(name)
+            self.status_linux(name)
         }
         #[cfg(target_os = "macos")]
         {
@@ -134,13 +267,86 @@ impl ServiceManager for SystemServiceManager {
             anyhow::bail!("不支持的操作系统")
         }
     }
+
+    fn logs(&self, name: &str, follow: bool, since: Option<Duration>) -> Result<(), anyhow::Error> {
+        #[cfg(target_os = "linux")]
+        {
+            self.logs_linux(name, follow, since)
+        }
+        #[cfg(target_os = "macos")]
+        {
+            self.logs_launchd(name, follow, since)
+        }
+        #[cfg(target_os = "windows")]
+        {
+            self.logs_windows(name, follow, since)
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+        {
+            anyhow::bail!("不支持的操作系统")
+        }
+    }
 }
 
 #[cfg(target_os = "linux")]
 impl SystemServiceManager {
+    /// 构造 systemctl 命令，用户模式下附加 --user
+    fn systemctl(&self) -> std::process::Command {
+        let mut cmd = std::process::Command::new("systemctl");
+        if self.user {
+            cmd.arg("--user");
+        }
+        cmd
+    }
+
+    /// systemd 单元文件目录
+    fn systemd_unit_dir(&self) -> Result<std::path::PathBuf, anyhow::Error> {
+        if !self.user {
+            return Ok(std::path::PathBuf::from("/etc/systemd/system"));
+        }
+
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => std::path::PathBuf::from(dir),
+            _ => std::env::var_os("HOME")
+                .map(|home| std::path::PathBuf::from(home).join(".config"))
+                .context("Failed to determine home directory")?,
+        };
+        Ok(config_dir.join("systemd").join("user"))
+    }
+
+    /// 检查当前用户是否启用了 lingering（注销后服务继续运行）
+    fn user_lingering_enabled(&self) -> bool {
+        let Ok(user) = std::env::var("USER") else {
+            return false;
+        };
+
+        std::process::Command::new("loginctl")
+            .arg("show-user")
+            .arg(&user)
+            .arg("--property=Linger")
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim() == "Linger=yes")
+            .unwrap_or(false)
+    }
+
     fn install_systemd(&self, options: &ServiceInstallOptions) -> Result<(), anyhow::Error> {
         use std::fs;
-        use std::path::Path;
+
+        if self.user && options.user.is_some() {
+            anyhow::bail!("用户级服务不能指定运行用户");
+        }
+
+        let mut extra = String::new();
+        if let Some(user) = &options.user {
+            extra.push_str(&format!("User = {}\n", user));
+        }
+        for (key, value) in &options.env {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('%', "%%");
+            extra.push_str(&format!("Environment = \"{}={}\"\n", key, value));
+        }
 
         let unit_content = format!(
             r#"[Unit]
@@ -148,64 +354,99 @@ Description = {}
 After = network.target
 
 [Service]
-Type = simple
+Type = notify
+NotifyAccess = main
+WatchdogSec = 30
 WorkingDirectory = {}
-ExecStart = {} {}
+{}ExecStart = {} {}
+ExecReload = /bin/kill -HUP $MAINPID
 Restart = always
 RestartSec = 1
 
 [Install]
-WantedBy = multi-user.target
+WantedBy = {}
 "#,
             options.description.as_deref().unwrap_or("PeerSend Service"),
             options.work_directory,
+            extra,
             options.program,
-            options.args.join(" ")
+            options.args.join(" "),
+            if self.user { "default.target" } else { "multi-user.target" }
         );
 
-        let unit_path = format!("/etc/systemd/system/{}.service", options.program);
-        fs::write(&unit_path, unit_content)
-            .with_context(|| format!("Failed to write systemd unit file: {}", unit_path))?;
+        let unit_dir = self.systemd_unit_dir()?;
+        fs::create_dir_all(&unit_dir)
+            .with_context(|| format!("Failed to create unit directory: {}", unit_dir.display()))?;
 
-        std::process::Command::new("systemctl")
+        let unit_path = unit_dir.join(format!("{}.service", options.name));
+        fs::write(&unit_path, unit_content).with_context(|| {
+            format!("Failed to write systemd unit file: {}", unit_path.display())
+        })?;
+
+        // 套接字激活时由 .socket 单元按需拉起服务
+        let enable_unit = match options.socket_port {
+            Some(port) => {
+                let socket_content = format!(
+                    r#"[Unit]
+Description = {} Socket
+
+[Socket]
+ListenStream = {}
+
+[Install]
+WantedBy = sockets.target
+"#,
+                    options.description.as_deref().unwrap_or("PeerSend Service"),
+                    port
+                );
+                let socket_path = unit_dir.join(format!("{}.socket", options.name));
+                fs::write(&socket_path, socket_content).with_context(|| {
+                    format!("Failed to write systemd socket file: {}", socket_path.display())
+                })?;
+                format!("{}.socket", options.name)
+            }
+            None => format!("{}.service", options.name),
+        };
+
+        self.systemctl()
             .arg("daemon-reload")
             .output()
             .with_context(|| "Failed to reload systemd daemon")?;
 
         if !options.disable_autostart {
-            std::process::Command::new("systemctl")
+            self.systemctl()
                 .arg("enable")
-                .arg(&format!("{}.service", options.program))
+                .arg(&enable_unit)
                 .output()
                 .with_context(|| "Failed to enable service")?;
         }
 
+        if self.user && !self.user_lingering_enabled() {
+            println!(
+                "提示: 当前用户未启用 lingering，服务仅在登录期间运行。\n\
+                 如需注销后继续运行，请执行: loginctl enable-linger"
+            );
+        }
+
         Ok(())
     }
 
     fn uninstall_systemd(&self, name: &str) -> Result<(), anyhow::Error> {
-        let _ = std::process::Command::new("systemctl")
-            .arg("stop")
-            .arg(format!("{}.service", name))
-            .output();
+        let unit_dir = self.systemd_unit_dir()?;
 
-        let _ = std::process::Command::new("systemctl")
-            .arg("disable")
-            .arg(format!("{}.service", name))
-            .output();
-
-        let unit_path = format!("/etc/systemd/system/{}.service", name);
-        let _ = std::fs::remove_file(&unit_path);
+        for unit in [format!("{}.socket", name), format!("{}.service", name)] {
+            let _ = self.systemctl().arg("stop").arg(&unit).output();
+            let _ = self.systemctl().arg("disable").arg(&unit).output();
+            let _ = std::fs::remove_file(unit_dir.join(&unit));
+        }
 
-        let _ = std::process::Command::new("systemctl")
-            .arg("daemon-reload")
-            .output();
+        let _ = self.systemctl().arg("daemon-reload").output();
 
         Ok(())
     }
 
     fn start_systemd(&self, name: &str) -> Result<(), anyhow::Error> {
-        std::process::Command::new("systemctl")
+        self.systemctl()
             .arg("start")
             .arg(format!("{}.service", name))
             .output()
@@ -214,7 +455,7 @@ WantedBy = multi-user.target
     }
 
     fn stop_systemd(&self, name: &str) -> Result<(), anyhow::Error> {
-        std::process::Command::new("systemctl")
+        self.systemctl()
             .arg("stop")
             .arg(format!("{}.service", name))
             .output()
@@ -222,8 +463,27 @@ WantedBy = multi-user.target
         Ok(())
     }
 
+    fn restart_systemd(&self, name: &str) -> Result<(), anyhow::Error> {
+        self.systemctl()
+            .arg("restart")
+            .arg(format!("{}.service", name))
+            .output()
+            .with_context(|| format!("Failed to restart service: {}", name))?;
+        Ok(())
+    }
+
+    fn reload_systemd(&self, name: &str) -> Result<(), anyhow::Error> {
+        self.systemctl()
+            .arg("reload")
+            .arg(format!("{}.service", name))
+            .output()
+            .with_context(|| format!("Failed to reload service: {}", name))?;
+        Ok(())
+    }
+
     fn status_systemd(&self, name: &str) -> Result<ServiceStatus, anyhow::Error> {
-        let output = std::process::Command::new("systemctl")
+        let output = self
+            .systemctl()
             .arg("is-active")
             .arg(format!("{}.service", name))
             .output()?;
@@ -231,21 +491,576 @@ WantedBy = multi-user.target
         if output.status.success() {
             Ok(ServiceStatus::Running)
         } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            if stderr.contains("inactive") {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if stdout.contains("inactive") || stdout.contains("failed") {
                 Ok(ServiceStatus::Stopped)
             } else {
                 Ok(ServiceStatus::NotInstalled)
             }
         }
     }
+
+    fn logs_systemd(
+        &self,
+        name: &str,
+        follow: bool,
+        since: Option<Duration>,
+    ) -> Result<(), anyhow::Error> {
+        let mut cmd = std::process::Command::new("journalctl");
+        if self.user {
+            cmd.arg("--user");
+        }
+        cmd.arg("--unit").arg(format!("{}.service", name)).arg("--no-pager");
+        if follow {
+            cmd.arg("--follow");
+        }
+        if let Some(since) = since {
+            cmd.arg(format!("--since=-{}s", since.as_secs()));
+        }
+        run_log_command(&mut cmd)
+    }
+}
+
+/// Linux 初始化系统
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InitSystem {
+    Systemd,
+    OpenRc,
+    Runit,
+    SysV,
+}
+
+#[cfg(target_os = "linux")]
+impl InitSystem {
+    /// 检测当前运行的初始化系统
+    fn detect() -> Self {
+        use std::path::Path;
+
+        if Path::new("/run/systemd/system").exists() {
+            InitSystem::Systemd
+        } else if Path::new("/run/openrc").exists() || Path::new("/sbin/openrc-run").exists() {
+            InitSystem::OpenRc
+        } else if Path::new("/run/runit").exists() || Path::new("/etc/runit").exists() {
+            InitSystem::Runit
+        } else {
+            InitSystem::SysV
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl SystemServiceManager {
+    /// 当前使用的初始化系统，用户级服务仅支持 systemd
+    fn init_system(&self) -> Result<InitSystem, anyhow::Error> {
+        let init = InitSystem::detect();
+        if self.user && init != InitSystem::Systemd {
+            anyhow::bail!("用户级服务仅支持 systemd，当前初始化系统: {:?}", init);
+        }
+        Ok(init)
+    }
+
+    fn install_linux(&self, options: &ServiceInstallOptions) -> Result<(), anyhow::Error> {
+        let init = self.init_system()?;
+        if options.socket_port.is_some() && init != InitSystem::Systemd {
+            anyhow::bail!("套接字激活仅支持 systemd，当前初始化系统: {:?}", init);
+        }
+
+        match init {
+            InitSystem::Systemd => self.install_systemd(options),
+            InitSystem::OpenRc => self.install_openrc(options),
+            InitSystem::Runit => self.install_runit(options),
+            InitSystem::SysV => self.install_sysv(options),
+        }
+    }
+
+    fn uninstall_linux(&self, name: &str) -> Result<(), anyhow::Error> {
+        match self.init_system()? {
+            InitSystem::Systemd => self.uninstall_systemd(name),
+            InitSystem::OpenRc => self.uninstall_openrc(name),
+            InitSystem::Runit => self.uninstall_runit(name),
+            InitSystem::SysV => self.uninstall_sysv(name),
+        }
+    }
+
+    fn start_linux(&self, name: &str) -> Result<(), anyhow::Error> {
+        match self.init_system()? {
+            InitSystem::Systemd => self.start_systemd(name),
+            InitSystem::OpenRc => rc_service(name, "start"),
+            InitSystem::Runit => sv(name, "start"),
+            InitSystem::SysV => init_script(name, "start"),
+        }
+    }
+
+    fn stop_linux(&self, name: &str) -> Result<(), anyhow::Error> {
+        match self.init_system()? {
+            InitSystem::Systemd => self.stop_systemd(name),
+            InitSystem::OpenRc => rc_service(name, "stop"),
+            InitSystem::Runit => sv(name, "stop"),
+            InitSystem::SysV => init_script(name, "stop"),
+        }
+    }
+
+    fn restart_linux(&self, name: &str) -> Result<(), anyhow::Error> {
+        match self.init_system()? {
+            InitSystem::Systemd => self.restart_systemd(name),
+            InitSystem::OpenRc => rc_service(name, "restart"),
+            InitSystem::Runit => sv(name, "restart"),
+            InitSystem::SysV => init_script(name, "restart"),
+        }
+    }
+
+    fn reload_linux(&self, name: &str) -> Result<(), anyhow::Error> {
+        match self.init_system()? {
+            InitSystem::Systemd => self.reload_systemd(name),
+            InitSystem::OpenRc => rc_service(name, "reload"),
+            InitSystem::Runit => sv(name, "hup"),
+            InitSystem::SysV => init_script(name, "reload"),
+        }
+    }
+
+    fn status_linux(&self, name: &str) -> Result<ServiceStatus, anyhow::Error> {
+        match self.init_system()? {
+            InitSystem::Systemd => self.status_systemd(name),
+            InitSystem::OpenRc | InitSystem::SysV => status_init_script(name),
+            InitSystem::Runit => status_runit(name),
+        }
+    }
+
+    fn logs_linux(
+        &self,
+        name: &str,
+        follow: bool,
+        since: Option<Duration>,
+    ) -> Result<(), anyhow::Error> {
+        let log_path = match self.init_system()? {
+            InitSystem::Systemd => return self.logs_systemd(name, follow, since),
+            InitSystem::OpenRc | InitSystem::SysV => format!("/var/log/{}.log", name),
+            InitSystem::Runit => format!("/var/log/{}/current", name),
+        };
+
+        if since.is_some() {
+            anyhow::bail!("--since is only supported with systemd");
+        }
+
+        let mut cmd = std::process::Command::new("tail");
+        cmd.arg("-n").arg("+1");
+        if follow {
+            cmd.arg("-F");
+        }
+        cmd.arg(log_path);
+        run_log_command(&mut cmd)
+    }
+}
+
+/// 写入可执行脚本
+#[cfg(target_os = "linux")]
+fn write_script(path: &std::path::Path, content: &str) -> Result<(), anyhow::Error> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::write(path, content)
+        .with_context(|| format!("Failed to write script: {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed to set permissions: {}", path.display()))?;
+    Ok(())
+}
+
+/// 执行 rc-service 命令
+#[cfg(target_os = "linux")]
+fn rc_service(name: &str, action: &str) -> Result<(), anyhow::Error> {
+    std::process::Command::new("rc-service")
+        .arg(name)
+        .arg(action)
+        .output()
+        .with_context(|| format!("Failed to {} service: {}", action, name))?;
+    Ok(())
+}
+
+/// 执行 runit sv 命令
+#[cfg(target_os = "linux")]
+fn sv(name: &str, action: &str) -> Result<(), anyhow::Error> {
+    std::process::Command::new("sv")
+        .arg(action)
+        .arg(name)
+        .output()
+        .with_context(|| format!("Failed to {} service: {}", action, name))?;
+    Ok(())
+}
+
+/// 执行 /etc/init.d 脚本
+#[cfg(target_os = "linux")]
+fn init_script(name: &str, action: &str) -> Result<(), anyhow::Error> {
+    std::process::Command::new(format!("/etc/init.d/{}", name))
+        .arg(action)
+        .output()
+        .with_context(|| format!("Failed to {} service: {}", action, name))?;
+    Ok(())
+}
+
+/// 通过 /etc/init.d 脚本查询状态（OpenRC 与 SysV 通用）
+#[cfg(target_os = "linux")]
+fn status_init_script(name: &str) -> Result<ServiceStatus, anyhow::Error> {
+    if !std::path::Path::new(&format!("/etc/init.d/{}", name)).exists() {
+        return Ok(ServiceStatus::NotInstalled);
+    }
+
+    let output = std::process::Command::new(format!("/etc/init.d/{}", name))
+        .arg("status")
+        .output()?;
+    if output.status.success() {
+        Ok(ServiceStatus::Running)
+    } else {
+        Ok(ServiceStatus::Stopped)
+    }
+}
+
+/// 查询 runit 服务状态
+#[cfg(target_os = "linux")]
+fn status_runit(name: &str) -> Result<ServiceStatus, anyhow::Error> {
+    if !std::path::Path::new(&format!("/etc/sv/{}", name)).exists() {
+        return Ok(ServiceStatus::NotInstalled);
+    }
+
+    let output = std::process::Command::new("sv")
+        .arg("status")
+        .arg(name)
+        .output()?;
+    if String::from_utf8_lossy(&output.stdout).starts_with("run:") {
+        Ok(ServiceStatus::Running)
+    } else {
+        Ok(ServiceStatus::Stopped)
+    }
+}
+
+/// runit 受监管服务目录
+#[cfg(target_os = "linux")]
+fn runit_service_dir() -> std::path::PathBuf {
+    if let Some(dir) = std::env::var_os("SVDIR") {
+        return std::path::PathBuf::from(dir);
+    }
+    // Void Linux 使用 /var/service，其余发行版多为 /etc/service
+    if std::path::Path::new("/var/service").is_dir() {
+        std::path::PathBuf::from("/var/service")
+    } else {
+        std::path::PathBuf::from("/etc/service")
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl SystemServiceManager {
+    fn install_openrc(&self, options: &ServiceInstallOptions) -> Result<(), anyhow::Error> {
+        let mut extra = shell_exports(&options.env);
+        if let Some(user) = &options.user {
+            extra.push_str(&format!("command_user={}\n", shell_quote(user)));
+        }
+
+        let script = format!(
+            r#"#!/sbin/openrc-run
+
+description="{}"
+supervisor=supervise-daemon
+command="{}"
+command_args="{}"
+directory="{}"
+{}output_log="/var/log/{}.log"
+error_log="/var/log/{}.log"
+respawn_delay=1
+extra_started_commands="reload"
+
+depend() {{
+    need net
+    after firewall
+}}
+
+reload() {{
+    ebegin "Reloading ${{RC_SVCNAME}}"
+    supervise-daemon "${{RC_SVCNAME}}" --signal HUP
+    eend $?
+}}
+"#,
+            options.description.as_deref().unwrap_or("PeerSend Service"),
+            options.program,
+            options.args.join(" "),
+            options.work_directory,
+            extra,
+            options.name,
+            options.name,
+        );
+
+        let script_path = std::path::PathBuf::from(format!("/etc/init.d/{}", options.name));
+        write_script(&script_path, &script)?;
+
+        if !options.disable_autostart {
+            std::process::Command::new("rc-update")
+                .arg("add")
+                .arg(&options.name)
+                .arg("default")
+                .output()
+                .with_context(|| "Failed to enable service")?;
+        }
+
+        Ok(())
+    }
+
+    fn uninstall_openrc(&self, name: &str) -> Result<(), anyhow::Error> {
+        let _ = rc_service(name, "stop");
+        let _ = std::process::Command::new("rc-update")
+            .arg("del")
+            .arg(name)
+            .arg("default")
+            .output();
+        let _ = std::fs::remove_file(format!("/etc/init.d/{}", name));
+        Ok(())
+    }
+
+    fn install_runit(&self, options: &ServiceInstallOptions) -> Result<(), anyhow::Error> {
+        use std::fs;
+        use std::path::PathBuf;
+
+        let sv_dir = PathBuf::from(format!("/etc/sv/{}", options.name));
+        let log_dir = PathBuf::from(format!("/var/log/{}", options.name));
+        fs::create_dir_all(sv_dir.join("log"))
+            .with_context(|| format!("Failed to create service directory: {}", sv_dir.display()))?;
+        fs::create_dir_all(&log_dir)
+            .with_context(|| format!("Failed to create log directory: {}", log_dir.display()))?;
+
+        let run_as = options
+            .user
+            .as_ref()
+            .map(|user| format!("chpst -u {} ", shell_quote(user)))
+            .unwrap_or_default();
+        let run_script = format!(
+            "#!/bin/sh\nexec 2>&1\n{}cd {}\nexec {}{} {}\n",
+            shell_exports(&options.env),
+            options.work_directory,
+            run_as,
+            options.program,
+            options.args.join(" "),
+        );
+        write_script(&sv_dir.join("run"), &run_script)?;
+
+        let log_script = format!("#!/bin/sh\nexec svlogd -tt {}\n", log_dir.display());
+        write_script(&sv_dir.join("log").join("run"), &log_script)?;
+
+        // down 文件让 runit 监管服务但不自动启动
+        if options.disable_autostart {
+            fs::write(sv_dir.join("down"), "")
+                .with_context(|| "Failed to disable autostart")?;
+        }
+
+        let link = runit_service_dir().join(&options.name);
+        if !link.exists() {
+            std::os::unix::fs::symlink(&sv_dir, &link)
+                .with_context(|| format!("Failed to enable service: {}", link.display()))?;
+        }
+
+        Ok(())
+    }
+
+    fn uninstall_runit(&self, name: &str) -> Result<(), anyhow::Error> {
+        let _ = sv(name, "stop");
+        let _ = std::fs::remove_file(runit_service_dir().join(name));
+        let _ = std::fs::remove_dir_all(format!("/etc/sv/{}", name));
+        Ok(())
+    }
+
+    fn install_sysv(&self, options: &ServiceInstallOptions) -> Result<(), anyhow::Error> {
+        // setpriv 直接 exec 目标程序，保证 PID 文件记录的是服务进程本身
+        let run_as = options
+            .user
+            .as_ref()
+            .map(|user| {
+                let user = shell_quote(user);
+                format!(
+                    "setpriv --reuid={} --regid=\"$(id -g {})\" --init-groups ",
+                    user, user
+                )
+            })
+            .unwrap_or_default();
+
+        let script = format!(
+            r#"#!/bin/sh
+### BEGIN INIT INFO
+# Provides:          {name}
+# Required-Start:    $network $remote_fs
+# Required-Stop:     $network $remote_fs
+# Default-Start:     2 3 4 5
+# Default-Stop:      0 1 6
+# Short-Description: {description}
+### END INIT INFO
+
+PIDFILE=/var/run/{name}.pid
+LOGFILE=/var/log/{name}.log
+{exports}
+is_running() {{
+    [ -f "$PIDFILE" ] && kill -0 "$(cat "$PIDFILE")" 2>/dev/null
+}}
+
+start() {{
+    is_running && return 0
+    cd {work_directory} || return 1
+    nohup {run_as}{program} {args} >>"$LOGFILE" 2>&1 &
+    echo $! >"$PIDFILE"
+}}
+
+stop() {{
+    is_running || return 0
+    kill "$(cat "$PIDFILE")"
+    rm -f "$PIDFILE"
+}}
+
+case "$1" in
+    start) start ;;
+    stop) stop ;;
+    restart) stop; sleep 1; start ;;
+    reload) is_running && kill -HUP "$(cat "$PIDFILE")" ;;
+    status) is_running ;;
+    *) echo "Usage: $0 {{start|stop|restart|reload|status}}"; exit 2 ;;
+esac
+"#,
+            name = options.name,
+            description = options.description.as_deref().unwrap_or("PeerSend Service"),
+            work_directory = options.work_directory,
+            exports = shell_exports(&options.env),
+            run_as = run_as,
+            program = options.program,
+            args = options.args.join(" "),
+        );
+
+        let script_path = std::path::PathBuf::from(format!("/etc/init.d/{}", options.name));
+        write_script(&script_path, &script)?;
+
+        if !options.disable_autostart {
+            // Debian 系使用 update-rc.d，RHEL 系使用 chkconfig
+            let enabled = std::process::Command::new("update-rc.d")
+                .arg(&options.name)
+                .arg("defaults")
+                .status()
+                .map(|s| s.success())
+                .unwrap_or(false)
+                || std::process::Command::new("chkconfig")
+                    .arg("--add")
+                    .arg(&options.name)
+                    .status()
+                    .map(|s| s.success())
+                    .unwrap_or(false);
+            if !enabled {
+                println!("提示: 未能注册开机自启，请手动将 /etc/init.d/{} 加入启动脚本", options.name);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn uninstall_sysv(&self, name: &str) -> Result<(), anyhow::Error> {
+        let _ = init_script(name, "stop");
+        let _ = std::process::Command::new("update-rc.d")
+            .arg("-f")
+            .arg(name)
+            .arg("remove")
+            .output();
+        let _ = std::process::Command::new("chkconfig")
+            .arg("--del")
+            .arg(name)
+            .output();
+        let _ = std::fs::remove_file(format!("/etc/init.d/{}", name));
+        Ok(())
+    }
 }
 
 #[cfg(target_os = "macos")]
 impl SystemServiceManager {
+    /// launchd 作业标签
+    fn launchd_label(&self, name: &str) -> String {
+        format!("com.peersend.{}", name)
+    }
+
+    /// launchd 域，用户模式下为当前用户的图形会话
+    fn launchd_domain(&self) -> Result<String, anyhow::Error> {
+        if !self.user {
+            return Ok("system".to_string());
+        }
+
+        let output = std::process::Command::new("id")
+            .arg("-u")
+            .output()
+            .context("Failed to get current user id")?;
+        let uid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if uid.is_empty() {
+            anyhow::bail!("Failed to get current user id");
+        }
+        Ok(format!("gui/{}", uid))
+    }
+
+    /// launchd 服务目标，如 system/com.peersend.peersend
+    fn launchd_target(&self, name: &str) -> Result<String, anyhow::Error> {
+        Ok(format!("{}/{}", self.launchd_domain()?, self.launchd_label(name)))
+    }
+
+    /// plist 路径，用户模式下位于 ~/Library/LaunchAgents
+    fn launchd_plist_path(&self, name: &str) -> Result<std::path::PathBuf, anyhow::Error> {
+        let dir = if self.user {
+            std::env::var_os("HOME")
+                .map(|home| std::path::PathBuf::from(home).join("Library/LaunchAgents"))
+                .context("Failed to determine home directory")?
+        } else {
+            std::path::PathBuf::from("/Library/LaunchDaemons")
+        };
+        Ok(dir.join(format!("{}.plist", self.launchd_label(name))))
+    }
+
+    /// 执行 launchctl 命令
+    fn launchctl(
+        &self,
+        args: &[&str],
+        name: &str,
+        action: &str,
+    ) -> Result<std::process::Output, anyhow::Error> {
+        std::process::Command::new("launchctl")
+            .args(args)
+            .output()
+            .with_context(|| format!("Failed to {} service: {}", action, name))
+    }
+
     fn install_launchd(&self, options: &ServiceInstallOptions) -> Result<(), anyhow::Error> {
         use std::fs;
-        use std::path::Path;
+
+        if self.user && options.user.is_some() {
+            anyhow::bail!("用户级服务不能指定运行用户");
+        }
+        if options.socket_port.is_some() {
+            anyhow::bail!("套接字激活仅支持 systemd");
+        }
+
+        let xml_escape = |value: &str| {
+            value
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+        };
+
+        let mut extra = String::new();
+        if let Some(user) = &options.user {
+            extra.push_str(&format!(
+                "    <key>UserName</key>\n    <string>{}</string>\n",
+                xml_escape(user)
+            ));
+        }
+        if !options.env.is_empty() {
+            extra.push_str("    <key>EnvironmentVariables</key>\n    <dict>\n");
+            for (key, value) in &options.env {
+                extra.push_str(&format!(
+                    "        <key>{}</key>\n        <string>{}</string>\n",
+                    key,
+                    xml_escape(value)
+                ));
+            }
+            extra.push_str("    </dict>\n");
+        }
+        if self.user {
+            // 仅在用户登录的图形会话中运行
+            extra.push_str("    <key>LimitLoadToSessionType</key>\n    <string>Aqua</string>\n");
+        }
 
         let plist_content = format!(
             r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -265,111 +1080,369 @@ impl SystemServiceManager {
     <{} />
     <key>KeepAlive</key>
     <true />
-</dict>
+{}</dict>
 </plist>
 "#,
-            options.program,
+            options.name,
             options.program,
             options.args.iter().map(|a| format!("<string>{}</string>", a)).collect::<Vec<_>>().join("\n        "),
             options.work_directory,
-            if options.disable_autostart { "false" } else { "true" }
+            if options.disable_autostart { "false" } else { "true" },
+            extra
         );
 
-        let plist_path = format!("/Library/LaunchDaemons/com.peersend.{}.plist", options.program);
-        fs::write(&plist_path, plist_content)
-            .with_context(|| format!("Failed to write launchd plist: {}", plist_path))?;
+        let plist_path = self.launchd_plist_path(&options.name)?;
+        if let Some(dir) = plist_path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+        }
+        fs::write(&plist_path, plist_content).with_context(|| {
+            format!("Failed to write launchd plist: {}", plist_path.display())
+        })?;
 
         Ok(())
     }
 
     fn uninstall_launchd(&self, name: &str) -> Result<(), anyhow::Error> {
-        let plist_path = format!("/Library/LaunchDaemons/com.peersend.{}.plist", name);
-        let _ = std::fs::remove_file(&plist_path);
+        let _ = self.stop_launchd(name);
+        let _ = std::fs::remove_file(self.launchd_plist_path(name)?);
         Ok(())
     }
 
     fn start_launchd(&self, name: &str) -> Result<(), anyhow::Error> {
-        let plist_path = format!("/Library/LaunchDaemons/com.peersend.{}.plist", name);
-        std::process::Command::new("launchctl")
-            .arg("load")
-            .arg("-F")
-            .arg(&plist_path)
-            .output()
-            .with_context(|| format!("Failed to start service: {}", name))?;
+        let plist_path = self.launchd_plist_path(name)?;
+        if !plist_path.exists() {
+            anyhow::bail!("Service is not installed: {}", name);
+        }
+
+        let domain = self.launchd_domain()?;
+        let target = self.launchd_target(name)?;
+
+        // 已加载的作业无法再次 bootstrap，直接启动即可
+        let loaded = self.launchctl(&["print", &target], name, "start")?.status.success();
+        if loaded {
+            self.launchctl(&["kickstart", &target], name, "start")?;
+        } else {
+            let output = self.launchctl(
+                &["bootstrap", &domain, &plist_path.to_string_lossy()],
+                name,
+                "start",
+            )?;
+            if !output.status.success() {
+                anyhow::bail!(
+                    "Failed to start service: {}: {}",
+                    name,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+        }
         Ok(())
     }
 
     fn stop_launchd(&self, name: &str) -> Result<(), anyhow::Error> {
-        let _ = std::process::Command::new("launchctl")
-            .arg("unload")
-            .arg(format!("/Library/LaunchDaemons/com.peersend.{}.plist", name))
-            .output();
+        let target = self.launchd_target(name)?;
+        self.launchctl(&["bootout", &target], name, "stop")?;
+        Ok(())
+    }
+
+    fn restart_launchd(&self, name: &str) -> Result<(), anyhow::Error> {
+        let target = self.launchd_target(name)?;
+        self.launchctl(&["kickstart", "-k", &target], name, "restart")?;
+        Ok(())
+    }
+
+    fn reload_launchd(&self, name: &str) -> Result<(), anyhow::Error> {
+        let target = self.launchd_target(name)?;
+        self.launchctl(&["kill", "HUP", &target], name, "reload")?;
         Ok(())
     }
 
     fn status_launchd(&self, name: &str) -> Result<ServiceStatus, anyhow::Error> {
-        let output = std::process::Command::new("launchctl")
-            .arg("print")
-            .arg(format!("system/com.peersend.{}", name))
-            .output()?;
+        let target = self.launchd_target(name)?;
+        let output = self.launchctl(&["print", &target], name, "query")?;
 
         if output.status.success() {
-            Ok(ServiceStatus::Running)
-        } else {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if stdout.contains("state = running") {
+                Ok(ServiceStatus::Running)
+            } else {
+                Ok(ServiceStatus::Stopped)
+            }
+        } else if self.launchd_plist_path(name)?.exists() {
             Ok(ServiceStatus::Stopped)
+        } else {
+            Ok(ServiceStatus::NotInstalled)
         }
     }
+
+    fn logs_launchd(
+        &self,
+        _name: &str,
+        follow: bool,
+        since: Option<Duration>,
+    ) -> Result<(), anyhow::Error> {
+        let process = std::env::current_exe()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "peersend".to_string());
+        let predicate = format!("process == \"{}\"", process);
+
+        let mut cmd = std::process::Command::new("log");
+        if follow {
+            cmd.arg("stream");
+        } else {
+            cmd.arg("show");
+            if let Some(since) = since {
+                cmd.arg("--last").arg(format!("{}s", since.as_secs()));
+            }
+        }
+        cmd.arg("--predicate").arg(predicate);
+        run_log_command(&mut cmd)
+    }
 }
 
 #[cfg(target_os = "windows")]
 impl SystemServiceManager {
-    fn install_windows(&self, _options: &ServiceInstallOptions) -> Result<(), anyhow::Error> {
-        // Windows 服务安装需要使用 windows-service crate
-        // 这里提供一个简单的实现框架
-        anyhow::bail!("Windows 服务安装需要完整的 windows-service 实现")
+    fn open_windows_service(
+        &self,
+        name: &str,
+        access: windows_service::service::ServiceAccess,
+    ) -> Result<windows_service::service::Service, anyhow::Error> {
+        use windows_service::service_manager::{
+            ServiceManager as ScManager, ServiceManagerAccess,
+        };
+
+        let manager = ScManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+            .context("Failed to connect to service control manager")?;
+        manager
+            .open_service(name, access)
+            .with_context(|| format!("Failed to open service: {}", name))
     }
 
-    fn uninstall_windows(&self, _name: &str) -> Result<(), anyhow::Error> {
-        anyhow::bail!("Windows 服务卸载需要完整的 windows-service 实现")
+    fn install_windows(&self, options: &ServiceInstallOptions) -> Result<(), anyhow::Error> {
+        use std::ffi::OsString;
+        use std::path::PathBuf;
+        use std::time::Duration;
+        use windows_service::service::{
+            ServiceAccess, ServiceAction, ServiceActionType, ServiceErrorControl,
+            ServiceFailureActions, ServiceFailureResetPeriod, ServiceInfo, ServiceStartType,
+            ServiceType,
+        };
+        use windows_service::service_manager::{
+            ServiceManager as ScManager, ServiceManagerAccess,
+        };
+
+        if self.user {
+            anyhow::bail!("Windows 不支持用户级服务");
+        }
+        if options.socket_port.is_some() {
+            anyhow::bail!("套接字激活仅支持 systemd");
+        }
+
+        let manager = ScManager::local_computer(
+            None::<&str>,
+            ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+        )
+        .context("Failed to connect to service control manager")?;
+
+        let service_info = ServiceInfo {
+            name: OsString::from(&options.name),
+            display_name: OsString::from(&options.name),
+            service_type: ServiceType::OWN_PROCESS,
+            start_type: if options.disable_autostart {
+                ServiceStartType::OnDemand
+            } else {
+                ServiceStartType::AutoStart
+            },
+            error_control: ServiceErrorControl::Normal,
+            executable_path: PathBuf::from(&options.program),
+            launch_arguments: options.args.iter().map(OsString::from).collect(),
+            dependencies: vec![],
+            account_name: options.user.as_ref().map(OsString::from),
+            account_password: None,
+        };
+
+        let service = manager
+            .create_service(
+                &service_info,
+                ServiceAccess::CHANGE_CONFIG | ServiceAccess::START,
+            )
+            .with_context(|| format!("Failed to create service: {}", options.name))?;
+
+        service
+            .set_description(
+                options.description.as_deref().unwrap_or("PeerSend Service"),
+            )
+            .context("Failed to set service description")?;
+
+        // 崩溃后自动重启，一天内无故障则重置计数
+        let restart = ServiceAction {
+            action_type: ServiceActionType::Restart,
+            delay: Duration::from_secs(5),
+        };
+        service
+            .update_failure_actions(ServiceFailureActions {
+                reset_period: ServiceFailureResetPeriod::After(Duration::from_secs(86400)),
+                reboot_msg: None,
+                command: None,
+                actions: Some(vec![restart.clone(), restart.clone(), restart]),
+            })
+            .context("Failed to set service recovery options")?;
+        service
+            .set_failure_actions_on_non_crash_failures(true)
+            .context("Failed to set service recovery options")?;
+
+        if !options.env.is_empty() {
+            use winreg::{enums::HKEY_LOCAL_MACHINE, RegKey};
+
+            // SCM 启动服务时从服务注册表项的 Environment 值读取环境变量
+            let env: Vec<String> = options
+                .env
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            RegKey::predef(HKEY_LOCAL_MACHINE)
+                .open_subkey_with_flags(
+                    format!(r"SYSTEM\CurrentControlSet\Services\{}", options.name),
+                    winreg::enums::KEY_SET_VALUE,
+                )
+                .and_then(|key| key.set_value("Environment", &env))
+                .context("Failed to set service environment")?;
+        }
+
+        Ok(())
     }
 
-    fn start_windows(&self, _name: &str) -> Result<(), anyhow::Error> {
-        let output = std::process::Command::new("sc")
-            .arg("start")
-            .arg(_name)
-            .output()?;
-        if output.status.success() {
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Failed to start service: {}", String::from_utf8_lossy(&output.stderr)))
+    fn uninstall_windows(&self, name: &str) -> Result<(), anyhow::Error> {
+        use windows_service::service::{ServiceAccess, ServiceState};
+
+        let service = self.open_windows_service(
+            name,
+            ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+        )?;
+
+        if service.query_status()?.current_state != ServiceState::Stopped {
+            let _ = service.stop();
         }
+
+        service
+            .delete()
+            .with_context(|| format!("Failed to delete service: {}", name))?;
+        Ok(())
     }
 
-    fn stop_windows(&self, _name: &str) -> Result<(), anyhow::Error> {
-        let output = std::process::Command::new("sc")
-            .arg("stop")
-            .arg(_name)
-            .output()?;
-        if output.status.success() {
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Failed to stop service: {}", String::from_utf8_lossy(&output.stderr)))
+    fn start_windows(&self, name: &str) -> Result<(), anyhow::Error> {
+        use windows_service::service::ServiceAccess;
+
+        let service = self.open_windows_service(name, ServiceAccess::START)?;
+        service
+            .start::<&str>(&[])
+            .with_context(|| format!("Failed to start service: {}", name))?;
+        Ok(())
+    }
+
+    fn stop_windows(&self, name: &str) -> Result<(), anyhow::Error> {
+        use windows_service::service::ServiceAccess;
+
+        let service = self.open_windows_service(name, ServiceAccess::STOP)?;
+        service
+            .stop()
+            .with_context(|| format!("Failed to stop service: {}", name))?;
+        Ok(())
+    }
+
+    fn restart_windows(&self, name: &str) -> Result<(), anyhow::Error> {
+        use windows_service::service::{ServiceAccess, ServiceState};
+
+        let service = self.open_windows_service(
+            name,
+            ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::START,
+        )?;
+
+        if service.query_status()?.current_state != ServiceState::Stopped {
+            service
+                .stop()
+                .with_context(|| format!("Failed to stop service: {}", name))?;
+        }
+
+        // 等待服务完全停止后再启动
+        let deadline = std::time::Instant::now() + Duration::from_secs(30);
+        while service.query_status()?.current_state != ServiceState::Stopped {
+            if std::time::Instant::now() > deadline {
+                anyhow::bail!("Timed out waiting for service to stop: {}", name);
+            }
+            std::thread::sleep(Duration::from_millis(500));
         }
+
+        service
+            .start::<&str>(&[])
+            .with_context(|| format!("Failed to start service: {}", name))?;
+        Ok(())
+    }
+
+    fn reload_windows(&self, name: &str) -> Result<(), anyhow::Error> {
+        use windows_service::service::ServiceAccess;
+
+        let service = self.open_windows_service(name, ServiceAccess::USER_DEFINED_CONTROL)?;
+        service
+            .notify(windows::RELOAD_CONTROL)
+            .with_context(|| format!("Failed to reload service: {}", name))?;
+        Ok(())
     }
 
     fn status_windows(&self, name: &str) -> Result<ServiceStatus, anyhow::Error> {
-        let output = std::process::Command::new("sc")
-            .arg("query")
-            .arg(name)
-            .output()?;
+        use windows_service::service::{ServiceAccess, ServiceState};
+        use windows_service::service_manager::{
+            ServiceManager as ScManager, ServiceManagerAccess,
+        };
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        if stdout.contains("RUNNING") {
-            Ok(ServiceStatus::Running)
-        } else if stdout.contains("STOPPED") {
-            Ok(ServiceStatus::Stopped)
-        } else {
-            Ok(ServiceStatus::NotInstalled)
+        /// ERROR_SERVICE_DOES_NOT_EXIST
+        const SERVICE_DOES_NOT_EXIST: i32 = 1060;
+
+        let manager = ScManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+            .context("Failed to connect to service control manager")?;
+        let service = match manager.open_service(name, ServiceAccess::QUERY_STATUS) {
+            Ok(service) => service,
+            Err(windows_service::Error::Winapi(e))
+                if e.raw_os_error() == Some(SERVICE_DOES_NOT_EXIST) =>
+            {
+                return Ok(ServiceStatus::NotInstalled);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        match service.query_status()?.current_state {
+            ServiceState::Running | ServiceState::StartPending | ServiceState::ContinuePending => {
+                Ok(ServiceStatus::Running)
+            }
+            _ => Ok(ServiceStatus::Stopped),
+        }
+    }
+
+    fn logs_windows(
+        &self,
+        name: &str,
+        follow: bool,
+        since: Option<Duration>,
+    ) -> Result<(), anyhow::Error> {
+        if follow {
+            anyhow::bail!("Windows 事件日志不支持 --follow");
+        }
+
+        let mut script = format!(
+            "Get-EventLog -LogName Application -Source '{}'",
+            name.replace('\'', "''")
+        );
+        if let Some(since) = since {
+            script.push_str(&format!(" -After (Get-Date).AddSeconds(-{})", since.as_secs()));
         }
+        script.push_str(" | Sort-Object TimeGenerated | Format-Table -AutoSize -Wrap");
+
+        run_log_command(
+            std::process::Command::new("powershell")
+                .arg("-NoProfile")
+                .arg("-Command")
+                .arg(script),
+        )
     }
 }
@@ -0,0 +1,88 @@
+//! systemd 通知协议 (sd_notify) 与套接字激活 (sd_listen_fds)
+//!
+//! 未由 systemd 以 Type=notify 启动或在其他平台上时，所有操作均为空操作
+
+use std::time::Duration;
+
+/// 向服务管理器发送状态通知，例如 `READY=1`、`WATCHDOG=1`
+pub fn notify(state: &str) {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+        let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+            return;
+        };
+
+        // 以 @ 开头表示抽象命名空间套接字
+        let addr = match path.as_bytes() {
+            [b'@', name @ ..] => SocketAddr::from_abstract_name(name),
+            _ => SocketAddr::from_pathname(&path),
+        };
+
+        if let (Ok(addr), Ok(socket)) = (addr, UnixDatagram::unbound()) {
+            let _ = socket.send_to_addr(state.as_bytes(), &addr);
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = state;
+    }
+}
+
+/// systemd 要求的看门狗通知间隔
+///
+/// 返回 WatchdogSec 的一半，未启用看门狗时返回 None
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+
+    // WATCHDOG_PID 存在时只对指定进程生效
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+
+    Some(Duration::from_micros(usec) / 2)
+}
+
+/// 获取 systemd 套接字激活传入的监听套接字
+///
+/// 读取后清除相关环境变量，避免子进程误用；未启用套接字激活时返回空列表
+pub fn listen_fds() -> Vec<std::net::TcpListener> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::FromRawFd;
+
+        /// 传入套接字的起始文件描述符
+        const SD_LISTEN_FDS_START: i32 = 3;
+
+        let pid_matches = std::env::var("LISTEN_PID")
+            .ok()
+            .and_then(|pid| pid.parse::<u32>().ok())
+            == Some(std::process::id());
+        let count = std::env::var("LISTEN_FDS")
+            .ok()
+            .and_then(|n| n.parse::<i32>().ok())
+            .unwrap_or(0);
+
+        std::env::remove_var("LISTEN_PID");
+        std::env::remove_var("LISTEN_FDS");
+        std::env::remove_var("LISTEN_FDNAMES");
+
+        if !pid_matches {
+            return Vec::new();
+        }
+
+        (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count)
+            // 文件描述符由 systemd 传入且只在此处取得所有权
+            .map(|fd| unsafe { std::net::TcpListener::from_raw_fd(fd) })
+            .collect()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Vec::new()
+    }
+}
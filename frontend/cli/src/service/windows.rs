@@ -0,0 +1,101 @@
+//! Windows 服务入口
+//!
+//! 由服务控制管理器 (SCM) 启动时，通过服务调度器运行接收守护进程
+
+use std::{ffi::OsString, sync::OnceLock, time::Duration};
+
+use anyhow::Context;
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+        ServiceType, UserEventCode,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult},
+    service_dispatcher,
+};
+
+use super::DEFAULT_SERVICE_NAME;
+
+static SERVICE_NAME: OnceLock<String> = OnceLock::new();
+
+/// 重载配置的自定义控制码
+pub const RELOAD_CONTROL: UserEventCode = unsafe { UserEventCode::from_unchecked(128) };
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// 以 Windows 服务方式运行，阻塞直到服务停止
+pub fn run(name: &str) -> Result<(), anyhow::Error> {
+    let _ = SERVICE_NAME.set(name.to_string());
+    service_dispatcher::start(name, ffi_service_main)
+        .context("Failed to start service dispatcher")?;
+    Ok(())
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        eprintln!("服务运行失败: {:?}", e);
+    }
+}
+
+fn run_service() -> Result<(), anyhow::Error> {
+    let name = SERVICE_NAME
+        .get()
+        .cloned()
+        .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let mut shutdown_tx = Some(shutdown_tx);
+    let (reload_tx, reload_rx) = tokio::sync::mpsc::channel::<()>(1);
+
+    let event_handler = move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            if let Some(tx) = shutdown_tx.take() {
+                let _ = tx.send(());
+            }
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::UserEvent(code) if code == RELOAD_CONTROL => {
+            let _ = reload_tx.try_send(());
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    };
+
+    let status_handle = service_control_handler::register(&name, event_handler)
+        .context("Failed to register service control handler")?;
+
+    status_handle.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: ServiceState::Running,
+        controls_accepted: ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    })?;
+
+    let result = tokio::runtime::Runtime::new()
+        .context("Failed to create tokio runtime")
+        .and_then(|runtime| {
+            runtime.block_on(crate::receiver::run(
+                async {
+                    let _ = shutdown_rx.await;
+                },
+                reload_rx,
+            ))
+        });
+
+    status_handle.set_service_status(ServiceStatus {
+        service_type: ServiceType::OWN_PROCESS,
+        current_state: ServiceState::Stopped,
+        controls_accepted: ServiceControlAccept::empty(),
+        exit_code: ServiceExitCode::Win32(if result.is_ok() { 0 } else { 1 }),
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    })?;
+
+    result
+}
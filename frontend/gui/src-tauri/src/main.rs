@@ -37,6 +37,9 @@ pub struct NetworkConfig {
     pub dhcp: bool,
     pub ipv4: Option<String>,
     pub enable_wg: bool,
+    pub enable_kcp_proxy: bool,
+    pub enable_quic_proxy: bool,
+    pub latency_first: bool,
     pub rpc_portal: SocketAddr,
 }
 
@@ -171,11 +174,23 @@ impl EasyTierDaemon {
 
         let peersend_cli = PathBuf::from("/home/ryanz/Documents/PeerSend/PeerSend/target/debug/peersend");
         if peersend_cli.exists() {
+            let mut transport_flags = Vec::new();
+            if config.enable_kcp_proxy {
+                transport_flags.push("--enable-kcp-proxy");
+            }
+            if config.enable_quic_proxy {
+                transport_flags.push("--enable-quic-proxy");
+            }
+            if config.latency_first {
+                transport_flags.push("--latency-first");
+            }
+
             let output = Command::new(&peersend_cli)
                 .arg("start")
                 .arg("--network-name")
                 .arg(&config.network_name)
                 .args(config.peers.iter().flat_map(|p| vec!["--peers", p]))
+                .args(&transport_flags)
                 .stdout(Stdio::inherit())
                 .stderr(Stdio::inherit())
                 .output()
@@ -213,6 +228,18 @@ impl EasyTierDaemon {
             args.push("--enable-wireguard");
         }
 
+        if config.enable_kcp_proxy {
+            args.push("--enable-kcp-proxy");
+        }
+
+        if config.enable_quic_proxy {
+            args.push("--enable-quic-proxy");
+        }
+
+        if config.latency_first {
+            args.push("--latency-first");
+        }
+
         let bin_path = self.find_easytier_binary()?;
         let child = Command::new(&bin_path)
             .args(&args)
@@ -422,6 +449,9 @@ async fn start_daemon(
     network_name: String,
     network_secret: Option<String>,
     peers: Vec<String>,
+    enable_kcp_proxy: Option<bool>,
+    enable_quic_proxy: Option<bool>,
+    latency_first: Option<bool>,
 ) -> Result<(), String> {
     let daemon = EasyTierDaemon::new(None);
 
@@ -432,6 +462,9 @@ async fn start_daemon(
         dhcp: true,
         ipv4: None,
         enable_wg: false,
+        enable_kcp_proxy: enable_kcp_proxy.unwrap_or(false),
+        enable_quic_proxy: enable_quic_proxy.unwrap_or(false),
+        latency_first: latency_first.unwrap_or(false),
         rpc_portal: DEFAULT_RPC_PORTAL.parse().unwrap(),
     };
 
@@ -606,6 +639,18 @@ async fn send_files(
     Ok(())
 }
 
+/// 截取主显示器并发送给设备，返回截图文件路径
+#[tauri::command]
+async fn send_screenshot(window: tauri::Window, peer_id: String) -> Result<String, String> {
+    let path = tokio::task::spawn_blocking(|| peersend_protocol::screenshot::capture(None))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    let path = path.to_string_lossy().into_owned();
+    send_files(window, vec![path.clone()], peer_id).await?;
+    Ok(path)
+}
+
 #[tauri::command]
 async fn get_transfers() -> Result<Vec<serde_json::Value>, String> {
     let state = APP_STATE.clone();
@@ -790,7 +835,281 @@ async fn get_download_dir() -> Result<String, String> {
     Ok(format!("{}/Downloads/PeerSend", home))
 }
 
+/// 获取设备允许和屏蔽列表
+#[tauri::command]
+async fn get_device_rules() -> Result<serde_json::Value, String> {
+    let config = peersend_protocol::LocalSendConfig::load_or_init().map_err(|e| e.to_string())?;
+    Ok(serde_json::json!({
+        "allowlist": config.allowlist,
+        "blocklist": config.blocklist
+    }))
+}
+
+/// 保存设备允许和屏蔽列表，列表项为设备 ID、IP 地址或 CIDR 网段
+#[tauri::command]
+async fn set_device_rules(allowlist: Vec<String>, blocklist: Vec<String>) -> Result<(), String> {
+    let mut config = peersend_protocol::LocalSendConfig::load_or_init().map_err(|e| e.to_string())?;
+    config.allowlist = allowlist;
+    config.blocklist = blocklist;
+    save_config(&config)
+}
+
+/// 屏蔽设备
+#[tauri::command]
+async fn block_device(id: String) -> Result<(), String> {
+    let mut config = peersend_protocol::LocalSendConfig::load_or_init().map_err(|e| e.to_string())?;
+    if !config.blocklist.contains(&id) {
+        config.blocklist.push(id.clone());
+    }
+    save_config(&config)?;
+
+    // 从当前列表中移除
+    let state = APP_STATE.clone();
+    state.devices.lock().await.retain(|d| d.id != id);
+    Ok(())
+}
+
+/// 检查本地网络访问权限，macOS 上尚未决定时触发系统授权提示
+///
+/// status 为 granted、denied 或 unknown，被拒绝时 message 给出处理办法
+#[tauri::command]
+async fn request_local_network_access() -> serde_json::Value {
+    use peersend_protocol::discovery::{local_network_access, LocalNetworkAccess};
+
+    let access = tokio::task::spawn_blocking(local_network_access)
+        .await
+        .unwrap_or(LocalNetworkAccess::Unknown);
+    let (status, message) = match access {
+        LocalNetworkAccess::Granted => ("granted", None),
+        LocalNetworkAccess::Denied => (
+            "denied",
+            Some(peersend_protocol::ProtocolError::LocalNetworkDenied.to_string()),
+        ),
+        LocalNetworkAccess::Unknown => ("unknown", None),
+    };
+    serde_json::json!({
+        "status": status,
+        "message": message
+    })
+}
+
+/// 把配置 (含受信任的设备) 导出为配置包，redact_secrets 时去掉密钥和密码
+#[tauri::command]
+async fn export_config(path: String, redact_secrets: bool) -> Result<(), String> {
+    use peersend_protocol::config::ConfigBundle;
+
+    let config = peersend_protocol::LocalSendConfig::load_or_init().map_err(|e| e.to_string())?;
+    let mut bundle = ConfigBundle::new(&config);
+    if redact_secrets {
+        bundle = bundle.redact_secrets();
+    }
+    bundle.save(std::path::Path::new(&path)).map_err(|e| e.to_string())
+}
+
+/// 导入配置包并替换当前配置，配置包中去掉的密钥沿用本机的值
+#[tauri::command]
+async fn import_config(path: String) -> Result<(), String> {
+    use peersend_protocol::config::ConfigBundle;
+
+    let bundle = ConfigBundle::load(std::path::Path::new(&path)).map_err(|e| e.to_string())?;
+    let local = peersend_protocol::LocalSendConfig::load_or_init().map_err(|e| e.to_string())?;
+    let config = bundle.into_config(&local).map_err(|e| e.to_string())?;
+    save_config(&config)
+}
+
+/// 收件箱中的文本，按收到的顺序排列
+#[tauri::command]
+async fn get_inbox() -> Result<Vec<peersend_protocol::inbox::InboxMessage>, String> {
+    let inbox = peersend_protocol::inbox::Inbox::open_default().map_err(|e| e.to_string())?;
+    inbox.list().map_err(|e| e.to_string())
+}
+
+/// 收件箱中的一条文本，复制由前端经剪贴板接口完成
+#[tauri::command]
+async fn get_inbox_message(id: u64) -> Result<peersend_protocol::inbox::InboxMessage, String> {
+    let inbox = peersend_protocol::inbox::Inbox::open_default().map_err(|e| e.to_string())?;
+    inbox
+        .get(id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("收件箱中没有第 {} 条文本", id))
+}
+
+/// 删除收件箱中的文本
+#[tauri::command]
+async fn delete_inbox_message(id: u64) -> Result<(), String> {
+    let inbox = peersend_protocol::inbox::Inbox::open_default().map_err(|e| e.to_string())?;
+    if !inbox.delete(id).map_err(|e| e.to_string())? {
+        return Err(format!("收件箱中没有第 {} 条文本", id));
+    }
+    Ok(())
+}
+
+/// 检查设备的连接: 设备信息、证书指纹、延迟和经过的网络路径
+///
+/// device_id 也可以是设备名称或 IP，守护进程在运行时使用其发现的设备，否则先发现 3 秒
+#[tauri::command]
+async fn check_device(
+    device_id: String,
+) -> Result<peersend_protocol::diagnose::DeviceReport, String> {
+    use peersend_protocol::control::{ControlClient, ControlRequest, ControlResponse};
+    use peersend_protocol::diagnose;
+
+    let config = peersend_protocol::LocalSendConfig::load_or_init().map_err(|e| e.to_string())?;
+    let client = peersend_protocol::LocalSendClient::new(config);
+    let devices = match ControlClient::connect_default().await {
+        Ok(mut control) => match control.request(&ControlRequest::Devices).await {
+            Ok(ControlResponse::Devices { devices }) => devices,
+            _ => Vec::new(),
+        },
+        Err(_) => client
+            .discover(Duration::from_secs(3))
+            .await
+            .map_err(|e| e.to_string())?,
+    };
+    let device = devices
+        .into_iter()
+        .find(|d| d.id == device_id || d.name == device_id || d.ip == device_id);
+    let device = match device {
+        Some(device) => device,
+        None if peersend_protocol::net::is_host(&device_id) => client
+            .check_device(&device_id)
+            .await
+            .ok_or_else(|| format!("未找到设备: {}", device_id))?,
+        None => return Err(format!("未找到设备: {}", device_id)),
+    };
+
+    let mut report = client.diagnose(&device).await;
+    let local = report.local_address.as_deref().and_then(|ip| ip.parse().ok());
+    let (tunnel_ips, relayed) = easytier_route(&device.ip).await;
+    report.path = diagnose::classify(local, &tunnel_ips, relayed);
+    Ok(report)
+}
+
+/// 本机在 EasyTier 网络中的虚拟 IP，以及到 ip 所在节点是否需经其他节点中转
+///
+/// EasyTier 未运行或没有到该地址的路由时分别为空
+async fn easytier_route(ip: &str) -> (Vec<std::net::IpAddr>, Option<bool>) {
+    let rpc_portal: SocketAddr = DEFAULT_RPC_PORTAL.parse().unwrap();
+    if tokio::net::TcpStream::connect(rpc_portal).await.is_err() {
+        return (Vec::new(), None);
+    }
+    let tcp_connector = TcpTunnelConnector::new(format!("tcp://{}", rpc_portal).parse().unwrap());
+    let mut client = StandAloneClient::new(tcp_connector);
+    let Ok(mut peer_client) = client
+        .scoped_client::<PeerManageRpcClientFactory<BaseController>>("".to_string())
+        .await
+    else {
+        return (Vec::new(), None);
+    };
+    let instance = || {
+        Some(InstanceIdentifier {
+            selector: Some(easytier::proto::api::instance::instance_identifier::Selector::Id(
+                easytier::proto::common::Uuid {
+                    part1: 0,
+                    part2: 0,
+                    part3: 0,
+                    part4: 0,
+                },
+            )),
+        })
+    };
+
+    // 虚拟 IP 形如 10.144.144.1/24
+    let tunnel_ips = match peer_client
+        .show_node_info(BaseController::default(), ShowNodeInfoRequest { instance: instance() })
+        .await
+    {
+        Ok(response) => response
+            .node_info
+            .and_then(|n| n.ipv4_addr.split('/').next()?.parse().ok())
+            .into_iter()
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    let Ok(target) = ip.parse::<std::net::Ipv4Addr>() else {
+        return (tunnel_ips, None);
+    };
+    let request = easytier::proto::api::instance::ListRouteRequest { instance: instance() };
+    // 开销为 1 表示与对方节点直接连接
+    let relayed = match peer_client.list_route(BaseController::default(), request).await {
+        Ok(response) => response
+            .routes
+            .iter()
+            .find(|r| {
+                r.ipv4_addr
+                    .as_ref()
+                    .and_then(|inet| inet.address.as_ref())
+                    .is_some_and(|addr| std::net::Ipv4Addr::from(addr.addr) == target)
+            })
+            .map(|r| r.cost > 1),
+        Err(_) => None,
+    };
+    (tunnel_ips, relayed)
+}
+
+/// 最近 limit 个结束的会话的摘要，按结束的顺序排列
+///
+/// 摘要随审计日志保存，未启用 `audit_log` 时为空
+#[tauri::command]
+async fn get_session_summaries(
+    limit: usize,
+) -> Result<Vec<peersend_protocol::SessionSummary>, String> {
+    use peersend_protocol::audit::{self, AuditLog};
+
+    let path = AuditLog::default_path().ok_or("无法确定配置目录")?;
+    let records = tokio::task::spawn_blocking(move || audit::records(&path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    let mut summaries: Vec<_> = records.into_iter().filter_map(|r| r.entry.summary).collect();
+    let skip = summaries.len().saturating_sub(limit);
+    summaries.drain(..skip);
+    Ok(summaries)
+}
+
+/// 临时开放接收的剩余秒数，未开放时为空
+#[tauri::command]
+async fn get_open_gate() -> Result<Option<u64>, String> {
+    gate_request(peersend_protocol::control::ControlRequest::Gate).await
+}
+
+/// 在 secs 秒内自动接受来自局域网的所有请求
+#[tauri::command]
+async fn open_gate(secs: u64) -> Result<Option<u64>, String> {
+    gate_request(peersend_protocol::control::ControlRequest::OpenGate { secs }).await
+}
+
+/// 提前结束临时开放接收
+#[tauri::command]
+async fn close_gate() -> Result<Option<u64>, String> {
+    gate_request(peersend_protocol::control::ControlRequest::CloseGate).await
+}
+
+/// 经控制通道发送开放接收相关的请求，返回剩余秒数
+async fn gate_request(
+    request: peersend_protocol::control::ControlRequest,
+) -> Result<Option<u64>, String> {
+    use peersend_protocol::control::{ControlClient, ControlResponse};
+
+    let mut client = ControlClient::connect_default()
+        .await
+        .map_err(|_| "无法连接接收守护进程，守护进程是否在运行?".to_string())?;
+    match client.request(&request).await.map_err(|e| e.to_string())? {
+        ControlResponse::Gate { remaining } => Ok(remaining),
+        ControlResponse::Error { message } => Err(message),
+        _ => Err("控制通道返回了意外的回复".to_string()),
+    }
+}
+
+fn save_config(config: &peersend_protocol::LocalSendConfig) -> Result<(), String> {
+    config.validate().map_err(|e| e.to_string())?;
+    let path = peersend_protocol::LocalSendConfig::default_path().ok_or("无法确定配置目录")?;
+    config.save(&path).map_err(|e| e.to_string())
+}
+
 fn main() {
+    let _ = peersend_protocol::logging::init_tracing("info");
+
     tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
             get_version,
@@ -799,6 +1118,7 @@ fn main() {
             stop_daemon,
             discover_peers,
             send_files,
+            send_screenshot,
             get_transfers,
             cancel_transfer,
             accept_transfer,
@@ -809,6 +1129,20 @@ fn main() {
             get_listener_port,
             set_download_dir,
             get_download_dir,
+            get_device_rules,
+            set_device_rules,
+            block_device,
+            export_config,
+            import_config,
+            request_local_network_access,
+            get_open_gate,
+            open_gate,
+            close_gate,
+            get_inbox,
+            get_inbox_message,
+            delete_inbox_message,
+            check_device,
+            get_session_summaries,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -12,7 +12,10 @@ export async function startDaemon(config) {
   return await invoke('start_daemon', {
     network_name: config.networkName,
     network_secret: config.networkSecret,
-    peers: config.peers
+    peers: config.peers,
+    enable_kcp_proxy: config.enableKcpProxy,
+    enable_quic_proxy: config.enableQuicProxy,
+    latency_first: config.latencyFirst
   })
 }
 
@@ -4,6 +4,11 @@ export async function sendFiles(paths, peerId) {
   return await invoke('send_files', { paths, peer_id: peerId })
 }
 
+// 截取主显示器并发送，返回截图文件路径
+export async function sendScreenshot(peerId) {
+  return await invoke('send_screenshot', { peer_id: peerId })
+}
+
 export async function getTransfers() {
   return await invoke('get_transfers')
 }
@@ -15,3 +20,70 @@ export async function cancelTransfer(id) {
 export async function acceptTransfer(id, path) {
   return await invoke('accept_transfer', { id, path })
 }
+
+export async function getDeviceRules() {
+  return await invoke('get_device_rules')
+}
+
+export async function setDeviceRules(allowlist, blocklist) {
+  return await invoke('set_device_rules', { allowlist, blocklist })
+}
+
+export async function blockDevice(id) {
+  return await invoke('block_device', { id })
+}
+
+export async function exportConfig(path, redactSecrets) {
+  return await invoke('export_config', { path, redactSecrets })
+}
+
+// 配置包中去掉的密钥沿用本机的值
+export async function importConfig(path) {
+  return await invoke('import_config', { path })
+}
+
+// macOS 上尚未决定时会弹出本地网络授权提示
+export async function requestLocalNetworkAccess() {
+  return await invoke('request_local_network_access')
+}
+
+// 开放接收的剩余秒数，未开放时为 null
+export async function getOpenGate() {
+  return await invoke('get_open_gate')
+}
+
+export async function openGate(secs) {
+  return await invoke('open_gate', { secs })
+}
+
+export async function closeGate() {
+  return await invoke('close_gate')
+}
+
+// 收到的文本消息，按收到的顺序排列
+export async function getInbox() {
+  return await invoke('get_inbox')
+}
+
+export async function getInboxMessage(id) {
+  return await invoke('get_inbox_message', { id })
+}
+
+export async function deleteInboxMessage(id) {
+  return await invoke('delete_inbox_message', { id })
+}
+
+export async function copyInboxMessage(id) {
+  const message = await getInboxMessage(id)
+  await navigator.clipboard.writeText(message.text)
+}
+
+// 设备连接诊断：设备信息、证书指纹、延迟和网络路径 (lan / tunnel / relay)
+export async function checkDevice(deviceId) {
+  return await invoke('check_device', { deviceId })
+}
+
+// 最近结束的会话的摘要：传输、跳过和失败的文件数、总字节数、平均速度和校验结果
+export async function getSessionSummaries(limit = 20) {
+  return await invoke('get_session_summaries', { limit })
+}
@@ -1,6 +1,7 @@
 import { defineStore } from 'pinia'
 import { ref, computed } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
+import { blockDevice as blockDeviceApi, requestLocalNetworkAccess } from '../api/localsend'
 
 export const useDeviceStore = defineStore('device', () => {
   const devices = ref([])
@@ -8,6 +9,18 @@ export const useDeviceStore = defineStore('device', () => {
   const incomingRequest = ref(null)
   const incomingRequests = ref([])
   const selectedDevice = ref(null)
+  // 本地网络权限被拒绝时的处理办法，为空表示正常
+  const localNetworkDenied = ref(null)
+
+  // 检查本地网络权限，macOS 上首次调用时触发系统授权提示
+  async function checkLocalNetworkAccess() {
+    try {
+      const access = await requestLocalNetworkAccess()
+      localNetworkDenied.value = access.status === 'denied' ? access.message : null
+    } catch (e) {
+      console.error('检查本地网络权限失败:', e)
+    }
+  }
 
   async function startDiscovery() {
     discovering.value = true
@@ -38,6 +51,12 @@ export const useDeviceStore = defineStore('device', () => {
     devices.value = devices.value.filter(d => d.id !== id)
   }
 
+  // 加入屏蔽列表，之后不再接收其请求，也不在发现结果中列出
+  async function blockDevice(id) {
+    await blockDeviceApi(id)
+    removeDevice(id)
+  }
+
   function setIncomingRequest(request) {
     incomingRequest.value = request
     if (request && !incomingRequests.value.find(r => r.sessionId === request.sessionId)) {
@@ -72,10 +91,13 @@ export const useDeviceStore = defineStore('device', () => {
     incomingRequest,
     incomingRequests,
     selectedDevice,
+    localNetworkDenied,
     hasIncomingRequests,
+    checkLocalNetworkAccess,
     startDiscovery,
     addDevice,
     removeDevice,
+    blockDevice,
     setIncomingRequest,
     clearIncomingRequest,
     removeIncomingRequest,
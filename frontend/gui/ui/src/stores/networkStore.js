@@ -36,7 +36,10 @@ export const useNetworkStore = defineStore('network', () => {
       await invoke('start_daemon', {
         network_name: config.networkName,
         network_secret: config.networkSecret,
-        peers: config.peers
+        peers: config.peers,
+        enable_kcp_proxy: config.enableKcpProxy,
+        enable_quic_proxy: config.enableQuicProxy,
+        latency_first: config.latencyFirst
       })
 
       status.value = 'connected'
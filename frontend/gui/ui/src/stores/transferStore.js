@@ -18,6 +18,16 @@ export const useTransferStore = defineStore('transfer', () => {
     }
   }
 
+  async function sendScreenshot(deviceId) {
+    try {
+      await invoke('send_screenshot', { peer_id: deviceId })
+      await refreshTransfers()
+    } catch (e) {
+      console.error('截屏发送失败:', e)
+      throw e
+    }
+  }
+
   async function acceptReceive(sessionId, path) {
     try {
       await invoke('accept_transfer', { id: sessionId, path })
@@ -103,6 +113,7 @@ export const useTransferStore = defineStore('transfer', () => {
     completed,
     downloadDir,
     sendFiles,
+    sendScreenshot,
     acceptReceive,
     rejectReceive,
     cancelTransfer,
@@ -0,0 +1,75 @@
+//! 文件系统后端吞吐量对比
+//!
+//! ```text
+//! cargo bench -p peersend-protocol --features io-uring --bench fs_backends
+//! ```
+//!
+//! 文件大小可通过 `PEERSEND_BENCH_MB` 调整，默认 512MB。
+//! 读取在写入之后立即进行，数据通常位于页缓存中，衡量的是系统调用开销
+
+use std::path::Path;
+use std::time::Instant;
+
+use peersend_protocol::fs::uring::UringFs;
+use peersend_protocol::fs::{FileSystem, LocalFs};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// 每次写入的块大小，与发送端的块大小一致
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+async fn write(fs: &dyn FileSystem, path: &Path, total: usize) {
+    let chunk = vec![0xa5u8; CHUNK_SIZE];
+    let mut writer = fs.open_write(path, false).await.unwrap();
+    for _ in 0..total / CHUNK_SIZE {
+        writer.write_all(&chunk).await.unwrap();
+    }
+    writer.shutdown().await.unwrap();
+}
+
+async fn read(fs: &dyn FileSystem, path: &Path) -> usize {
+    let mut reader = fs.open_read(path).await.unwrap();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut total = 0;
+    loop {
+        let n = reader.read(&mut buf).await.unwrap();
+        if n == 0 {
+            return total;
+        }
+        total += n;
+    }
+}
+
+async fn bench(name: &str, fs: &dyn FileSystem, dir: &Path, total: usize) {
+    let path = dir.join(name);
+    let mb = total as f64 / (1024.0 * 1024.0);
+
+    let start = Instant::now();
+    write(fs, &path, total).await;
+    fs.sync_file(&path).await.unwrap();
+    let write_secs = start.elapsed().as_secs_f64();
+
+    let start = Instant::now();
+    assert_eq!(read(fs, &path).await, total);
+    let read_secs = start.elapsed().as_secs_f64();
+
+    println!(
+        "{:<8} 写入 {:>8.1} MB/s  读取 {:>8.1} MB/s",
+        name,
+        mb / write_secs,
+        mb / read_secs
+    );
+    fs.remove_file(&path).await.unwrap();
+}
+
+#[tokio::main]
+async fn main() {
+    let mb: usize = std::env::var("PEERSEND_BENCH_MB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(512);
+    let total = mb * 1024 * 1024;
+    let dir = tempfile::tempdir().unwrap();
+
+    bench("tokio", &LocalFs, dir.path(), total).await;
+    bench("uring", &UringFs::new().unwrap(), dir.path(), total).await;
+}
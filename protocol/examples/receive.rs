@@ -0,0 +1,27 @@
+//! 启动接收服务并打印接收事件
+//!
+//! 用法: cargo run -p peersend-protocol --example receive
+
+use peersend_protocol::{LocalSendClient, LocalSendConfig, ReceiveEvent};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let _ = peersend_protocol::logging::init_tracing("info");
+
+    let client = LocalSendClient::new(LocalSendConfig::load_or_init()?);
+    let mut handle = client.receive().await?;
+
+    while let Some(event) = handle.next_event().await {
+        match event {
+            ReceiveEvent::Started { addr } => println!("接收服务已启动: {}", addr),
+            ReceiveEvent::SessionRequested { sender, files, .. } => {
+                println!("{} 请求发送 {} 个文件", sender, files.len())
+            }
+            ReceiveEvent::FileReceived { path, .. } => println!("已接收: {}", path.display()),
+            ReceiveEvent::TextReceived { sender, text, .. } => println!("{}: {}", sender, text),
+            ReceiveEvent::SessionFinished { session_id } => println!("会话 {} 结束", session_id),
+        }
+    }
+
+    Ok(())
+}
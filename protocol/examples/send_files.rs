@@ -0,0 +1,33 @@
+//! 发现局域网内的设备并向第一个设备发送文件
+//!
+//! 用法: cargo run -p peersend-protocol --example send_files -- <文件>...
+
+use std::time::Duration;
+
+use peersend_protocol::{LocalSendClient, LocalSendConfig};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let _ = peersend_protocol::logging::init_tracing("info");
+
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("用法: send_files <文件>...");
+        std::process::exit(1);
+    }
+
+    let client = LocalSendClient::new(LocalSendConfig::load_or_init()?);
+
+    println!("正在发现设备...");
+    let devices = client.discover(Duration::from_secs(5)).await?;
+    let Some(device) = devices.first() else {
+        println!("未发现设备");
+        return Ok(());
+    };
+
+    println!("发送到 {} ({}:{})", device.name, device.ip, device.port);
+    let session = client.send_files(device, &paths).await?;
+    println!("发送完成，会话 {}", session.id);
+
+    Ok(())
+}
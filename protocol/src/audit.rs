@@ -0,0 +1,391 @@
+//! 传输审计日志
+//!
+//! 记录文件发送请求、接受或拒绝的决定和传输结果，连同对方的证书指纹和文件哈希，
+//! 供在办公室等场合部署时留存备查。
+//!
+//! 日志是只追加的 JSONL 文件，每条记录的 `prev` 为上一行原始内容的 SHA-256，
+//! 第一条为全零。修改、插入或删除任何一行都会使之后的链断开，[`verify`] 据此发现篡改。
+//! 截断末尾的记录无法仅凭日志发现，可定期把 [`AuditSummary::head`] 另行保存用于比对
+
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::hash::sha256_hex;
+use crate::{
+    FileInfo, LocalSendConfig, ProtocolError, ProtocolEvent, SessionManager, SessionState,
+    SessionSummary,
+};
+
+/// 审计日志文件名，与共享配置文件位于同一目录
+const AUDIT_FILE_NAME: &str = "audit.jsonl";
+
+/// 第一条记录的 `prev`
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// 记录的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    /// 收到文件发送请求
+    Requested,
+    /// 接受请求
+    Accepted,
+    /// 拒绝请求
+    Rejected,
+    /// 传输完成
+    Completed,
+    /// 传输失败或中途取消
+    Failed,
+    /// 发送的源文件在读取文件信息后被修改
+    Changed,
+    /// 已接收的文件按清理策略删除或归档
+    Expired,
+    /// 执行接收后命令
+    Hook,
+}
+
+/// 记录中的文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditFile {
+    pub name: String,
+    pub size: u64,
+    /// 发送方声明的 SHA-256
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl From<&FileInfo> for AuditFile {
+    fn from(file: &FileInfo) -> Self {
+        Self {
+            name: file.name.clone(),
+            size: file.size,
+            sha256: file.sha256.clone(),
+        }
+    }
+}
+
+/// 待记录的事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub action: AuditAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// 对方的证书指纹，即设备 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<AuditFile>,
+    /// 决定或失败的原因，执行命令时为退出状态
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 执行的命令模板
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// 命令的输出，过长时被截断
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// 完成或失败时的会话摘要
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<SessionSummary>,
+}
+
+impl AuditEntry {
+    /// 只有动作的记录，其余字段按需填写
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            action,
+            session_id: None,
+            peer: None,
+            peer_name: None,
+            ip: None,
+            files: Vec::new(),
+            reason: None,
+            command: None,
+            output: None,
+            summary: None,
+        }
+    }
+}
+
+/// 日志中的一行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// 序号，从 1 开始
+    pub seq: u64,
+    /// Unix 时间戳 (秒)
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+    /// 上一行的 SHA-256
+    pub prev: String,
+}
+
+/// 校验结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditSummary {
+    /// 记录条数
+    pub records: u64,
+    /// 最后一行的 SHA-256，日志为空时为全零
+    pub head: String,
+}
+
+/// 审计日志
+///
+/// 克隆后写入同一个文件，写入在锁内完成并立即落盘
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    inner: Arc<Mutex<Chain>>,
+}
+
+#[derive(Debug)]
+struct Chain {
+    path: PathBuf,
+    seq: u64,
+    head: String,
+}
+
+impl AuditLog {
+    /// 打开日志，文件不存在时在首次写入时创建
+    ///
+    /// 只读取最后一行以接续哈希链，不校验已有记录
+    pub fn open(path: impl Into<PathBuf>) -> crate::Result<Self> {
+        let path = path.into();
+        let (mut seq, mut head) = (0, GENESIS.to_string());
+        match File::open(&path) {
+            Ok(file) => {
+                let mut last = None;
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    if !line.is_empty() {
+                        last = Some(line);
+                    }
+                }
+                if let Some(line) = last {
+                    let record: AuditRecord = serde_json::from_str(&line)?;
+                    seq = record.seq;
+                    head = sha256_hex(line.as_bytes());
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Chain { path, seq, head })),
+        })
+    }
+
+    /// 与共享配置文件位于同一目录的日志文件
+    pub fn default_path() -> Option<PathBuf> {
+        Some(LocalSendConfig::default_path()?.with_file_name(AUDIT_FILE_NAME))
+    }
+
+    /// 打开默认位置的日志
+    pub fn open_default() -> crate::Result<Self> {
+        let path = Self::default_path()
+            .ok_or_else(|| ProtocolError::InvalidConfig("无法确定配置目录".to_string()))?;
+        Self::open(path)
+    }
+
+    /// 日志文件路径
+    pub fn path(&self) -> PathBuf {
+        self.inner.lock().unwrap().path.clone()
+    }
+
+    /// 追加一条记录
+    pub fn record(&self, entry: AuditEntry) -> crate::Result<()> {
+        let mut chain = self.inner.lock().unwrap();
+        let record = AuditRecord {
+            seq: chain.seq + 1,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            entry,
+            prev: chain.head.clone(),
+        };
+        let line = serde_json::to_string(&record)?;
+
+        if let Some(parent) = chain.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        // 日志包含对方设备和文件名，仅允许当前用户读取
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&chain.path)?;
+        file.write_all(format!("{}\n", line).as_bytes())?;
+        file.sync_data()?;
+
+        chain.seq = record.seq;
+        chain.head = sha256_hex(line.as_bytes());
+        Ok(())
+    }
+
+    /// 写入记录，失败只记录日志，不影响传输
+    pub fn record_or_warn(&self, entry: AuditEntry) {
+        if let Err(e) = self.record(entry) {
+            warn!(error = %e, "写入审计日志失败");
+        }
+    }
+
+    /// 记录本机参与的会话的传输结果，直到事件总线关闭或 cancel 取消
+    ///
+    /// 请求和决定由接收服务记录，这里只记录完成、失败和发送时发现的源文件改变，
+    /// 完成和失败的记录附带会话摘要。
+    /// device_id 为本机设备 ID，用于区分会话中的对方
+    pub async fn run(
+        self,
+        mut events: broadcast::Receiver<ProtocolEvent>,
+        sessions: SessionManager,
+        device_id: String,
+        cancel: CancellationToken,
+    ) {
+        // 已开始传输的会话，被拒绝的请求也会变为取消状态，不应记为失败
+        let mut transferring = HashSet::new();
+
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = events.recv() => event,
+            };
+            let (session_id, state) = match event {
+                Ok(ProtocolEvent::SessionStateChanged { session_id, state }) => (session_id, state),
+                Ok(ProtocolEvent::SourceChanged {
+                    session_id,
+                    file_id,
+                    path,
+                    reason,
+                }) => {
+                    let mut entry = AuditEntry {
+                        session_id: Some(session_id.clone()),
+                        reason: Some(reason),
+                        ..AuditEntry::new(AuditAction::Changed)
+                    };
+                    match sessions.get_session(&session_id).await {
+                        Some(session) => {
+                            entry.peer = Some(session.receiver_id.clone());
+                            let files = session.files.iter().filter(|f| f.id == file_id);
+                            entry.files = files.map(AuditFile::from).collect();
+                        }
+                        None => entry.files.push(AuditFile {
+                            name: path.to_string_lossy().into_owned(),
+                            size: 0,
+                            sha256: None,
+                        }),
+                    }
+                    self.record_or_warn(entry);
+                    continue;
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "审计日志处理过慢，丢失部分事件");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            let started = transferring.remove(&session_id);
+            let (action, reason) = match state {
+                SessionState::Transferring => {
+                    transferring.insert(session_id);
+                    continue;
+                }
+                SessionState::Finished => (AuditAction::Completed, None),
+                SessionState::Error(message) => (AuditAction::Failed, Some(message)),
+                SessionState::Cancelled if started => {
+                    (AuditAction::Failed, Some("已取消".to_string()))
+                }
+                SessionState::Cancelled | SessionState::Waiting => continue,
+            };
+
+            let mut entry = AuditEntry {
+                session_id: Some(session_id.clone()),
+                reason,
+                ..AuditEntry::new(action)
+            };
+            if let Some(session) = sessions.get_session(&session_id).await {
+                let peer = if session.sender_id == device_id {
+                    &session.receiver_id
+                } else {
+                    &session.sender_id
+                };
+                entry.peer = Some(peer.clone());
+                entry.files = session.files.iter().map(AuditFile::from).collect();
+                entry.summary = session.summary.get().cloned();
+            }
+            self.record_or_warn(entry);
+        }
+    }
+}
+
+/// 校验日志的哈希链，文件不存在时视为空日志
+pub fn verify(path: &Path) -> crate::Result<AuditSummary> {
+    export(path, &mut std::io::sink())
+}
+
+/// 校验日志并把记录按 JSONL 写入 out
+///
+/// 遇到被篡改的记录时返回错误，此前的记录已写入
+pub fn export(path: &Path, out: &mut impl Write) -> crate::Result<AuditSummary> {
+    walk(path, |line, _| Ok(writeln!(out, "{}", line)?))
+}
+
+/// 校验日志并读取全部记录，按写入顺序排列
+pub fn records(path: &Path) -> crate::Result<Vec<AuditRecord>> {
+    let mut records = Vec::new();
+    walk(path, |_, record| {
+        records.push(record);
+        Ok(())
+    })?;
+    Ok(records)
+}
+
+/// 按顺序校验每条记录并交给 f
+fn walk(
+    path: &Path,
+    mut f: impl FnMut(&str, AuditRecord) -> crate::Result<()>,
+) -> crate::Result<AuditSummary> {
+    let mut summary = AuditSummary {
+        records: 0,
+        head: GENESIS.to_string(),
+    };
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(summary),
+        Err(e) => return Err(e.into()),
+    };
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let broken = || ProtocolError::InvalidData(format!("审计日志第 {} 行校验失败", index + 1));
+        let record: AuditRecord = serde_json::from_str(&line).map_err(|_| broken())?;
+        if record.seq != summary.records + 1 || record.prev != summary.head {
+            return Err(broken());
+        }
+        summary.records = record.seq;
+        summary.head = sha256_hex(line.as_bytes());
+        f(&line, record)?;
+    }
+    Ok(summary)
+}
@@ -0,0 +1,1564 @@
+//! LocalSend 客户端
+//!
+//! 封装设备发现、文件发送和接收服务，提供一站式异步接口
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::sync::{broadcast, mpsc, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info_span, instrument, warn, Instrument};
+
+use crate::diagnose::DeviceReport;
+use crate::discovery::{DiscoveryManagerRef, HttpDiscoverer, UdpDiscoverer};
+use crate::fingerprint::SourceFingerprint;
+use crate::fs::{FileSystemRef, LocalFs};
+use crate::hash;
+use crate::keepalive::Liveness;
+use crate::mime;
+use crate::net;
+use crate::routes::{self, RouteStats};
+#[cfg(feature = "pairdrop")]
+use crate::pairdrop::{self, PairDrop};
+use crate::http::{HttpClientRef, HttpRequest, HttpResponse, ReqwestClient, Scheme};
+use crate::dto::v2::{
+    FileDto, FileTimesDto, PrepareUploadRequestDto, PrepareUploadResponseDto, RegisterDto,
+    UploadOffsetDto, CANCEL_PATH, PREPARE_UPLOAD_PATH, UPLOAD_PATH,
+};
+use crate::dto::{FileRequest, FileResponse};
+use crate::server::LocalSendServer;
+use crate::session::{BroadcastReader, TransferManager};
+use crate::storage::links;
+use crate::throttle::{Throttle, Throttled};
+use crate::transport::{self, sparse, BoxedStream, StreamHeader, TransportRef};
+use crate::{
+    DeviceInfo, DiscoveryManager, EventBus, FileInfo, FileSession, FileWarning, LocalSendConfig,
+    ProtocolError, ProtocolEvent, ReceiveEvent, SessionManager, SessionState, SymlinkPolicy,
+};
+
+/// 不发送管道、套接字和设备文件的原因
+const SPECIAL_FILE: &str = "不是普通文件 (管道、套接字或设备)，已跳过";
+
+/// 探测单个候选地址的超时
+const ADDRESS_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 通知接收方取消会话的超时
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 对方拒绝 PIN 后最多询问的次数
+const MAX_PIN_ATTEMPTS: u32 = 3;
+
+/// HTTP 上传中断后最多续传的次数
+const MAX_RESUME_ATTEMPTS: u32 = 3;
+
+/// 接收服务句柄
+///
+/// 丢弃句柄即停止接收事件
+#[derive(Debug)]
+pub struct ReceiveHandle {
+    server: LocalSendServer,
+    events: mpsc::UnboundedReceiver<ReceiveEvent>,
+}
+
+impl ReceiveHandle {
+    /// 等待下一个接收事件，服务停止后返回 None
+    pub async fn next_event(&mut self) -> Option<ReceiveEvent> {
+        self.events.recv().await
+    }
+
+    /// 获取底层服务器
+    pub fn server(&self) -> &LocalSendServer {
+        &self.server
+    }
+
+    /// 接受或拒绝挂起的文件发送请求
+    pub fn respond(&self, session_id: &str, accept: bool) -> bool {
+        self.server.respond(session_id, accept)
+    }
+
+    /// 停止接收服务
+    pub fn stop(&self) {
+        self.server.shutdown();
+    }
+}
+
+/// 向设备上传文件的途径
+#[derive(Debug)]
+enum Route {
+    /// 协商出的传输及对方的各个地址
+    Transport(TransportRef, Vec<SocketAddr>),
+    Http(Scheme),
+}
+
+impl Route {
+    /// 途径名称，即传输名称或 `https`、`http`
+    fn name(&self) -> &str {
+        match self {
+            Route::Transport(transport, _) => transport.name(),
+            Route::Http(scheme) => scheme.as_str(),
+        }
+    }
+}
+
+/// 途径本身无法连接，可改用其他途径
+fn is_route_unavailable(error: &ProtocolError) -> bool {
+    match error {
+        ProtocolError::Io(e) => net::is_unreachable(e),
+        ProtocolError::Http(e) => e.is_connect(),
+        _ => false,
+    }
+}
+
+/// 上传在传输途中中断，可查询对方已接收的长度后续传
+fn is_interrupted(error: &ProtocolError) -> bool {
+    matches!(error, ProtocolError::Io(_) | ProtocolError::Http(_) | ProtocolError::Timeout)
+}
+
+/// 待发送的本地路径的处理方式
+#[derive(Debug)]
+enum LocalEntry {
+    /// 读取内容发送
+    File,
+    /// 作为链接条目发送，附带链接目标
+    Link(String),
+    /// 不发送，附带原因
+    Skipped(&'static str),
+}
+
+impl LocalEntry {
+    /// 按文件本身 (已解析链接) 的类型判断，目录交由读取长度时报错
+    fn regular(metadata: &std::fs::Metadata) -> Self {
+        if metadata.is_file() || metadata.is_dir() {
+            LocalEntry::File
+        } else {
+            LocalEntry::Skipped(SPECIAL_FILE)
+        }
+    }
+}
+
+/// 会话中的文件对应的本地文件
+#[derive(Debug)]
+struct LocalFile {
+    /// 会话中的文件 ID
+    id: String,
+    path: PathBuf,
+    /// 读取文件信息时的指纹，链接条目没有内容，为 None
+    source: Option<SourceFingerprint>,
+}
+
+/// 源文件自读取文件信息后的改变
+#[derive(Debug, Clone)]
+struct SourceChange {
+    /// 改变的各项
+    changes: String,
+    /// 长度不变且未声明哈希，可以从头发送当前内容
+    restartable: bool,
+}
+
+/// 对方接受的文件及其上传令牌，按会话中的顺序
+fn accepted<'a>(
+    session: &'a FileSession,
+    response: &'a PrepareUploadResponseDto,
+) -> impl Iterator<Item = (&'a FileInfo, &'a str)> {
+    session
+        .files
+        .iter()
+        .filter_map(|file| Some((file, response.files.get(&file.id)?.as_str())))
+}
+
+/// prepare-upload 中的文件信息，符号链接条目在元数据中带上链接目标
+fn file_dto(file: &FileInfo) -> FileDto {
+    FileDto {
+        id: file.id.clone(),
+        file_name: file.name.clone(),
+        size: file.size,
+        file_type: file.file_type.clone(),
+        sha256: file.sha256.clone(),
+        preview: None,
+        metadata: links::link_target(file).map(|target| FileTimesDto {
+            modified: None,
+            accessed: None,
+            symlink: Some(target.to_string()),
+        }),
+    }
+}
+
+/// 依次尝试 addrs，跳过不可达的地址
+async fn connect_any(transport: &TransportRef, addrs: &[SocketAddr]) -> crate::Result<BoxedStream> {
+    let mut last = None;
+    for addr in addrs {
+        match transport.connect(*addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(ProtocolError::Io(e)) if net::is_unreachable(&e) => {
+                debug!(peer = %addr, error = %e, "地址不可达，尝试下一个地址");
+                last = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last.map_or_else(
+        || ProtocolError::InvalidData("没有可用的设备地址".to_string()),
+        ProtocolError::Io,
+    ))
+}
+
+/// 发送期间同时读取接收端的保活字节，接收端掉线或提前关闭流时中止发送
+async fn watch_send<F, R>(send: F, liveness: &mut Liveness<R>) -> crate::Result<u64>
+where
+    F: Future<Output = crate::Result<u64>>,
+    R: AsyncRead + Unpin,
+{
+    tokio::select! {
+        biased;
+        sent = send => sent,
+        closed = liveness.wait() => {
+            closed?;
+            Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                "接收端提前关闭了文件流",
+            )
+            .into())
+        }
+    }
+}
+
+/// 对方要求 PIN 时向用户询问的回调，见 [`LocalSendClient::with_pin_prompt`]
+#[derive(Clone)]
+struct PinPrompt(Arc<PinPromptFn>);
+
+type PinPromptFn = dyn Fn(&DeviceInfo, u32) -> BoxFuture<'static, Option<String>> + Send + Sync;
+
+impl std::fmt::Debug for PinPrompt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PinPrompt")
+    }
+}
+
+/// LocalSend 客户端
+#[derive(Debug, Clone)]
+pub struct LocalSendClient {
+    config: LocalSendConfig,
+    http: HttpClientRef,
+    fs: FileSystemRef,
+    discovery: DiscoveryManagerRef,
+    sessions: Arc<Mutex<SessionManager>>,
+    events: EventBus,
+    transports: Vec<TransportRef>,
+    route_stats: RouteStats,
+    throttle: Throttle,
+    verify_hashes: bool,
+    /// `IP:端口` 到协商出的协议
+    schemes: Arc<std::sync::Mutex<HashMap<String, Scheme>>>,
+    /// 设备 ID 到发送时附带的 PIN
+    pins: Arc<std::sync::Mutex<HashMap<String, String>>>,
+    pin_prompt: Option<PinPrompt>,
+    #[cfg(feature = "pairdrop")]
+    pairdrop: Option<PairDrop>,
+    cancel: CancellationToken,
+}
+
+impl LocalSendClient {
+    /// 创建新的客户端
+    pub fn new(config: LocalSendConfig) -> Self {
+        let events = EventBus::default();
+        let throttle = Throttle::from_config(&config);
+        let http = ReqwestClient::for_config(&config);
+        Self {
+            config,
+            http,
+            fs: LocalFs::shared(),
+            discovery: Arc::new(Mutex::new(DiscoveryManager::with_event_bus(events.clone()))),
+            sessions: Arc::new(Mutex::new(SessionManager::with_event_bus(events.clone()))),
+            events,
+            transports: Vec::new(),
+            route_stats: RouteStats::new(),
+            throttle,
+            verify_hashes: false,
+            schemes: Arc::default(),
+            pins: Arc::default(),
+            pin_prompt: None,
+            #[cfg(feature = "pairdrop")]
+            pairdrop: None,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// 使用指定的 HTTP 客户端发送请求
+    pub fn with_http(mut self, http: HttpClientRef) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// 使用指定的文件系统读取待发送文件
+    pub fn with_fs(mut self, fs: FileSystemRef) -> Self {
+        self.fs = fs;
+        self
+    }
+
+    /// 使用外部的会话管理器，发送的会话记录在其中，事件发布到其事件总线
+    ///
+    /// 应在 [`with_pairdrop`](Self::with_pairdrop) 之前调用
+    pub fn with_session_manager(mut self, sessions: SessionManager) -> Self {
+        self.events = sessions.events().clone();
+        self.sessions = Arc::new(Mutex::new(sessions));
+        self
+    }
+
+    /// 添加可用于上传文件的传输，按添加顺序决定优先级
+    ///
+    /// 仅当对方公告了同名传输时使用，否则仍通过 HTTP 上传
+    pub fn with_transport(mut self, transport: TransportRef) -> Self {
+        self.transports.push(transport);
+        self
+    }
+
+    /// 使用外部的上传途径实测结果，例如 [`RouteStats::open_default`] 以便在多次运行间积累
+    ///
+    /// 默认只在内存中记录
+    pub fn with_route_stats(mut self, stats: RouteStats) -> Self {
+        self.route_stats = stats;
+        self
+    }
+
+    /// 使用外部的限速器，与接收服务或其他客户端共用带宽上限
+    ///
+    /// 默认按配置创建独立的限速器
+    pub fn with_throttle(mut self, throttle: Throttle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// 发送前计算文件的 SHA-256 并随 prepare-upload 声明，接收端据此校验
+    ///
+    /// 需要额外读取一遍文件，默认关闭
+    pub fn with_hash_verification(mut self, enabled: bool) -> Self {
+        self.verify_hashes = enabled;
+        self
+    }
+
+    /// 对方要求 PIN 而未设置或不正确时调用 prompt 向用户询问
+    ///
+    /// prompt 收到目标设备和第几次询问 (从 1 开始)，返回 None 表示放弃发送。
+    /// 输入的 PIN 按设备记录，之后的发送直接附带；连续 3 次不正确后返回
+    /// [`ProtocolError::PinRequired`]
+    pub fn with_pin_prompt<F, Fut>(mut self, prompt: F) -> Self
+    where
+        F: Fn(&DeviceInfo, u32) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<String>> + Send + 'static,
+    {
+        self.pin_prompt = Some(PinPrompt(Arc::new(move |device, attempt| {
+            Box::pin(prompt(device, attempt))
+        })));
+        self
+    }
+
+    /// 通过 url 指向的 PairDrop / Snapdrop 信令服务器与浏览器互通
+    ///
+    /// 调用 [`start_pairdrop`](Self::start_pairdrop) 后，房间中的浏览器出现在发现列表中，
+    /// 向它们发送文件和文本时改用 WebRTC 数据通道
+    #[cfg(feature = "pairdrop")]
+    pub fn with_pairdrop(mut self, url: impl Into<String>) -> Self {
+        self.pairdrop = Some(
+            PairDrop::new(url, self.config.clone(), self.sessions.clone(), self.discovery.clone())
+                .with_cancellation(self.cancel.child_token()),
+        );
+        self
+    }
+
+    /// 连接 PairDrop 信令服务器
+    #[cfg(feature = "pairdrop")]
+    pub async fn start_pairdrop(&self) -> crate::Result<()> {
+        self.pairdrop
+            .as_ref()
+            .ok_or_else(|| ProtocolError::InvalidConfig("未配置 PairDrop 信令服务器".to_string()))?
+            .start()
+            .await
+    }
+
+    /// PairDrop 实例，用于答复浏览器发来的文件请求
+    #[cfg(feature = "pairdrop")]
+    pub fn pairdrop(&self) -> Option<&PairDrop> {
+        self.pairdrop.as_ref()
+    }
+
+    /// 取消该客户端上所有进行中的操作
+    ///
+    /// 取消后客户端不可再用于发送或接收
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// 订阅发现、会话和传输事件
+    pub fn subscribe(&self) -> broadcast::Receiver<ProtocolEvent> {
+        self.events.subscribe()
+    }
+
+    /// 获取客户端配置
+    pub fn config(&self) -> &LocalSendConfig {
+        &self.config
+    }
+
+    /// 与设备通信时使用的协议，尚未联系过的设备返回 None
+    pub fn device_scheme(&self, device: &DeviceInfo) -> Option<Scheme> {
+        self.schemes.lock().unwrap().get(&device_key(device)).copied()
+    }
+
+    /// 设置向设备发送文件时附带的 PIN，None 清除
+    ///
+    /// 对方要求 PIN 而未设置或不正确时 [`send_files`](Self::send_files) 返回
+    /// [`ProtocolError::PinRequired`]，可向用户询问后设置并重试
+    pub fn set_pin(&self, device_id: &str, pin: Option<String>) {
+        let mut pins = self.pins.lock().unwrap();
+        match pin {
+            Some(pin) => pins.insert(device_id.to_string(), pin),
+            None => pins.remove(device_id),
+        };
+    }
+
+    /// 向设备发送 JSON 请求
+    ///
+    /// 首次联系设备时先尝试 HTTPS，连接或握手失败再改用 HTTP，成功的协议按设备记录。
+    /// 对端已应答（包括错误状态码）或证书指纹不一致时不会降级
+    async fn post_json<T: serde::Serialize>(
+        &self,
+        device: &DeviceInfo,
+        path: &str,
+        query: &[(&str, &str)],
+        body: &T,
+    ) -> crate::Result<HttpResponse> {
+        let request = |scheme| {
+            let mut request = HttpRequest::post(device_url(device, scheme, path));
+            for (key, value) in query {
+                request = request.query(key, value);
+            }
+            request.json(body)
+        };
+        if let Some(scheme) = self.device_scheme(device) {
+            return self.http.execute(request(scheme)?).await;
+        }
+
+        let scheme = match self.http.execute(request(Scheme::Https)?).await {
+            Ok(response) => {
+                self.record_scheme(device, Scheme::Https);
+                return Ok(response);
+            }
+            Err(e @ (ProtocolError::Io(_) | ProtocolError::Http(_))) => {
+                debug!(peer = %device.ip, error = %e, "HTTPS 不可用，改用 HTTP");
+                Scheme::Http
+            }
+            Err(e) => return Err(e),
+        };
+        let response = self.http.execute(request(scheme)?).await?;
+        self.record_scheme(device, scheme);
+        Ok(response)
+    }
+
+    fn record_scheme(&self, device: &DeviceInfo, scheme: Scheme) {
+        self.schemes.lock().unwrap().insert(device_key(device), scheme);
+    }
+
+    /// 获取会话管理器
+    pub fn session_manager(&self) -> Arc<Mutex<SessionManager>> {
+        self.sessions.clone()
+    }
+
+    /// 通过 UDP 多播发现设备，在指定时长后返回已发现的设备
+    pub async fn discover(&self, duration: Duration) -> crate::Result<Vec<DeviceInfo>> {
+        let udp = UdpDiscoverer::new(self.config.clone(), self.discovery.clone());
+        let cancel = self.cancel.child_token();
+        let _guard = cancel.clone().drop_guard();
+
+        // 发现过程不会主动结束，超时即视为完成
+        if let Ok(Err(e)) = tokio::time::timeout(duration, udp.start_discovery(cancel)).await {
+            return Err(e);
+        }
+        Ok(self.discovery.lock().await.get_devices().await.to_vec())
+    }
+
+    /// 通过 HTTP 扫描 base_ip 之后的 range 个地址
+    pub async fn scan(&self, base_ip: &str, range: u8) -> crate::Result<Vec<DeviceInfo>> {
+        HttpDiscoverer::new(self.config.clone(), self.discovery.clone(), self.http.clone())
+            .scan_range(base_ip, range)
+            .await?;
+        Ok(self.discovery.lock().await.get_devices().await.to_vec())
+    }
+
+    /// 检查指定地址是否运行 LocalSend
+    pub async fn check_device(&self, host: &str) -> Option<DeviceInfo> {
+        HttpDiscoverer::new(self.config.clone(), self.discovery.clone(), self.http.clone())
+            .check_device(host)
+            .await
+    }
+
+    /// 诊断设备的连接，见 [`diagnose`](crate::diagnose)
+    ///
+    /// 对方公告了候选地址时先经 [`reachable`](Self::reachable) 选择地址，
+    /// 此前与设备通信成功的协议优先
+    pub async fn diagnose(&self, device: &DeviceInfo) -> DeviceReport {
+        let device = self.reachable(device).await;
+        crate::diagnose::diagnose(self.http.as_ref(), &device, self.device_scheme(&device)).await
+    }
+
+    /// 按设备公告的候选地址选择可达的地址
+    ///
+    /// 依次探测各候选地址和公告的来源地址上的接收端口，返回的设备信息改用第一个可连接的地址。
+    /// 设备没有公告候选地址 (例如官方 LocalSend) 或全部不可达时原样返回
+    pub async fn reachable(&self, device: &DeviceInfo) -> DeviceInfo {
+        let mut device = device.clone();
+        if device.addresses.is_empty() {
+            return device;
+        }
+        let mut candidates: Vec<IpAddr> = Vec::new();
+        for ip in device.addresses.iter().chain([&device.ip]).filter_map(|a| net::parse_ip(a)) {
+            if !candidates.contains(&ip) {
+                candidates.push(ip);
+            }
+        }
+        match net::first_reachable(&candidates, device.port, ADDRESS_PROBE_TIMEOUT).await {
+            Some(ip) => {
+                let ip = ip.to_string();
+                if ip != device.ip {
+                    debug!(device_id = %device.id, from = %device.ip, to = %ip, "改用可达的候选地址");
+                    device.ip = ip;
+                }
+            }
+            None => debug!(device_id = %device.id, "候选地址均不可达，使用公告的来源地址"),
+        }
+        device
+    }
+
+    /// 向设备发送文件
+    ///
+    /// 按 LocalSend v2 的流程先经 prepare-upload 协商会话，再用对方分配的会话 ID
+    /// 和各文件的令牌上传对方接受的文件，上传失败或取消时通知对方取消会话。符号链接按配置的
+    /// [`SymlinkPolicy`] 处理，跳过的路径记入会话的 `warnings`。
+    /// 对方公告了候选地址时先经 [`reachable`](Self::reachable) 选择地址
+    #[instrument(skip(self, device, paths), fields(peer = %device.ip, device_id = %device.id))]
+    pub async fn send_files<P: AsRef<Path>>(
+        &self,
+        device: &DeviceInfo,
+        paths: &[P],
+    ) -> crate::Result<FileSession> {
+        let device = &self.reachable(device).await;
+        let (files, local_paths, warnings) =
+            self.describe_files(paths, device.is_peersend()).await?;
+        let session = self
+            .sessions
+            .lock()
+            .await
+            .create_session(self.config.device_id.clone(), device.id.clone(), files)
+            .await;
+        *session.warnings.lock().await = warnings;
+
+        let result = self.upload_session(device, &session, &local_paths).await;
+        self.finish_session(session, result).await
+    }
+
+    /// 向多台设备发送相同的文件，每个文件只从磁盘读取一次
+    ///
+    /// 先与各设备分别协商会话，再逐个文件把同一份读取的内容同时上传给接受该文件的设备，
+    /// 见 [`TransferManager::broadcast`](crate::session::TransferManager::broadcast)。
+    /// 单台设备失败不影响其他设备，按 devices 的顺序返回每台设备的结果；
+    /// 读取本地文件信息失败时整体返回错误。PairDrop 设备单独读取文件发送
+    #[instrument(skip_all, fields(devices = devices.len()))]
+    pub async fn broadcast_files<P: AsRef<Path>>(
+        &self,
+        devices: &[DeviceInfo],
+        paths: &[P],
+    ) -> crate::Result<Vec<crate::Result<FileSession>>> {
+        let reachable = futures::future::join_all(devices.iter().map(|d| self.reachable(d))).await;
+        let devices = reachable.as_slice();
+        // 链接只能原样发送给 PeerSend，有其他设备时全部按 follow 处理
+        let preserve_links = devices.iter().all(DeviceInfo::is_peersend);
+        let (files, local_paths, warnings) = self.describe_files(paths, preserve_links).await?;
+        let mut sessions = Vec::with_capacity(devices.len());
+        for device in devices {
+            let session = self
+                .sessions
+                .lock()
+                .await
+                .create_session(self.config.device_id.clone(), device.id.clone(), files.clone())
+                .await;
+            *session.warnings.lock().await = warnings.clone();
+            sessions.push(session);
+        }
+
+        let prepared = futures::future::join_all(
+            devices
+                .iter()
+                .zip(&sessions)
+                .map(|(device, session)| self.prepare_recipient(device, session, &local_paths)),
+        )
+        .await;
+        let mut results = Vec::with_capacity(devices.len());
+        let mut routes = Vec::with_capacity(devices.len());
+        for prepared in prepared {
+            match prepared {
+                Ok(route) => {
+                    results.push(Ok(()));
+                    routes.push(route);
+                }
+                Err(e) => {
+                    results.push(Err(e));
+                    routes.push(None);
+                }
+            }
+        }
+
+        for (file, local) in files.iter().zip(&local_paths) {
+            let id = &local.id;
+            let targets: Vec<usize> = (0..devices.len())
+                .filter(|&i| results[i].is_ok())
+                .filter(|&i| {
+                    routes[i]
+                        .as_ref()
+                        .is_some_and(|(_, response)| response.files.contains_key(id))
+                })
+                .collect();
+            if targets.is_empty() {
+                continue;
+            }
+            if self.cancel.is_cancelled() {
+                for &i in &targets {
+                    results[i] = Err(ProtocolError::Cancelled);
+                }
+                break;
+            }
+            let checked = match self.source_change(file, local).await {
+                Ok(Some(change)) => {
+                    let mut checked = Ok(());
+                    for &i in &targets {
+                        checked = self.record_source_change(&sessions[i], local, &change).await;
+                    }
+                    checked
+                }
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            };
+            let reader = match checked {
+                Ok(()) => self.fs.open_read(&local.path).await.map_err(|e| {
+                    format!("读取 {} 失败: {}", local.path.display(), e)
+                }),
+                Err(ProtocolError::InvalidData(message)) => Err(message),
+                Err(e) => Err(e.to_string()),
+            };
+            let reader = match reader {
+                Ok(reader) => reader,
+                Err(message) => {
+                    for &i in &targets {
+                        results[i] = Err(ProtocolError::InvalidData(message.clone()));
+                    }
+                    continue;
+                }
+            };
+
+            debug!(file_id = %id, size = file.size, recipients = targets.len(), "广播文件");
+            let (pump, readers) = TransferManager::broadcast(reader, targets.len());
+            let uploads = targets.iter().zip(readers).map(|(&i, reader)| {
+                let (route, response) = routes[i].as_ref().unwrap();
+                let header = StreamHeader {
+                    session_id: response.session_id.clone(),
+                    file_id: id.clone(),
+                    token: response.files[id].clone(),
+                    size: file.size,
+                    offset: 0,
+                    sparse: None,
+                };
+                self.upload_reader(&devices[i], route, header, reader).instrument(info_span!(
+                    "upload_file",
+                    session_id = %sessions[i].id,
+                    file_id = %id,
+                    size = file.size
+                ))
+            });
+            // 读取出错时各上传都会收到该错误，这里无需单独处理
+            let transfer = async { tokio::join!(pump, futures::future::join_all(uploads)).1 };
+            let uploaded = tokio::select! {
+                _ = self.cancel.cancelled() => {
+                    for &i in &targets {
+                        results[i] = Err(ProtocolError::Cancelled);
+                    }
+                    break;
+                }
+                uploaded = transfer => uploaded,
+            };
+            for (&i, result) in targets.iter().zip(uploaded) {
+                match result {
+                    Ok(()) => {
+                        self.sessions
+                            .lock()
+                            .await
+                            .report_progress(&sessions[i].id, id, file.size)
+                            .await
+                    }
+                    Err(e) => results[i] = Err(e),
+                }
+            }
+        }
+
+        for (device, (route, result)) in devices.iter().zip(routes.iter().zip(&results)) {
+            if let (Some((_, response)), Err(_)) = (route, result) {
+                self.cancel_upload(device, &response.session_id).await;
+            }
+        }
+        let mut finished = Vec::with_capacity(devices.len());
+        for (session, result) in sessions.into_iter().zip(results) {
+            finished.push(self.finish_session(session, result).await);
+        }
+        Ok(finished)
+    }
+
+    /// 读取待发送文件的信息，返回文件列表、对应的本地文件和跳过的路径
+    ///
+    /// preserve_links 为 true 且策略为 [`SymlinkPolicy::Preserve`] 时，符号链接作为
+    /// 链接条目发送，见 [`links`]。给出的路径全部被跳过时返回错误。
+    /// 同时记录普通文件的指纹，上传前据此发现源文件的改变，见 [`check_source`](Self::check_source)
+    async fn describe_files<P: AsRef<Path>>(
+        &self,
+        paths: &[P],
+        preserve_links: bool,
+    ) -> crate::Result<(Vec<FileInfo>, Vec<LocalFile>, Vec<FileWarning>)> {
+        let mut files = Vec::with_capacity(paths.len());
+        let mut local_paths = Vec::with_capacity(paths.len());
+        let mut warnings = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let id = uuid::Uuid::new_v4().to_string();
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| id.clone());
+            match self.classify(path, preserve_links).await? {
+                LocalEntry::File => {}
+                LocalEntry::Link(target) => {
+                    debug!(path = %path.display(), %target, "发送符号链接");
+                    files.push(FileInfo {
+                        id: id.clone(),
+                        name,
+                        size: 0,
+                        file_type: links::SYMLINK_TYPE.to_string(),
+                        metadata: Some(links::link_metadata(&target)),
+                        sha256: None,
+                    });
+                    local_paths.push(LocalFile {
+                        id,
+                        path: path.to_path_buf(),
+                        source: None,
+                    });
+                    continue;
+                }
+                LocalEntry::Skipped(reason) => {
+                    warn!(path = %path.display(), reason, "跳过文件");
+                    warnings.push(FileWarning::new(path.to_string_lossy(), reason));
+                    continue;
+                }
+            }
+
+            let source = SourceFingerprint::read(self.fs.as_ref(), path).await?;
+            let size = source.size;
+            let sha256 = if self.verify_hashes {
+                Some(hash::sha256_reader(self.fs.open_read(path).await?).await?)
+            } else {
+                None
+            };
+
+            let file_type = mime::detect(&name, &self.read_head(path).await?);
+            files.push(FileInfo {
+                id: id.clone(),
+                name,
+                size,
+                file_type,
+                metadata: None,
+                sha256,
+            });
+            local_paths.push(LocalFile {
+                id,
+                path: path.to_path_buf(),
+                source: Some(source),
+            });
+        }
+        if files.is_empty() && !warnings.is_empty() {
+            return Err(ProtocolError::InvalidData(format!(
+                "没有可发送的文件: {}",
+                warnings[0].reason
+            )));
+        }
+        Ok((files, local_paths, warnings))
+    }
+
+    /// 按符号链接策略判断本地路径的处理方式，非本地文件系统上一律视为普通文件
+    async fn classify(&self, path: &Path, preserve_links: bool) -> crate::Result<LocalEntry> {
+        if !self.fs.is_local() {
+            return Ok(LocalEntry::File);
+        }
+        let metadata = tokio::fs::symlink_metadata(path).await?;
+        if !metadata.file_type().is_symlink() {
+            return Ok(LocalEntry::regular(&metadata));
+        }
+        match self.config.symlink_policy {
+            SymlinkPolicy::Skip => return Ok(LocalEntry::Skipped("符号链接，已跳过")),
+            SymlinkPolicy::Preserve if preserve_links => {
+                let target = tokio::fs::read_link(path).await?;
+                return Ok(LocalEntry::Link(target.to_string_lossy().into_owned()));
+            }
+            SymlinkPolicy::Follow | SymlinkPolicy::Preserve => {}
+        }
+        match tokio::fs::metadata(path).await {
+            Ok(metadata) => Ok(LocalEntry::regular(&metadata)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(LocalEntry::Skipped("符号链接指向的文件不存在，已跳过"))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 按上传结果更新会话状态，失败时发布错误事件
+    async fn finish_session(
+        &self,
+        session: FileSession,
+        result: crate::Result<()>,
+    ) -> crate::Result<FileSession> {
+        let state = match &result {
+            Ok(()) => SessionState::Finished,
+            Err(e) => {
+                self.events.emit(ProtocolEvent::Error {
+                    session_id: Some(session.id.clone()),
+                    message: e.to_string(),
+                });
+                SessionState::Error(e.to_string())
+            }
+        };
+        self.sessions.lock().await.set_state(&session.id, state).await;
+        result.map(|()| session)
+    }
+
+    /// 上传文件前确认源文件自读取文件信息后未被修改
+    ///
+    /// 改变后仍能从头发送当前内容时返回 Ok，否则返回错误，需重新发送。
+    /// 改变记入会话的 `warnings`，并发布 [`ProtocolEvent::SourceChanged`]
+    async fn check_source(&self, session: &FileSession, local: &LocalFile) -> crate::Result<()> {
+        let Some(file) = session.files.iter().find(|f| f.id == local.id) else {
+            return Ok(());
+        };
+        match self.source_change(file, local).await? {
+            Some(change) => self.record_source_change(session, local, &change).await,
+            None => Ok(()),
+        }
+    }
+
+    /// 比较源文件当前的指纹与读取文件信息时的指纹，未改变时返回 None
+    ///
+    /// declared 为会话中声明的文件信息。声明了哈希时重新计算，
+    /// 内容与声明一致 (如只改变了修改时间) 视为未改变
+    async fn source_change(
+        &self,
+        declared: &FileInfo,
+        local: &LocalFile,
+    ) -> crate::Result<Option<SourceChange>> {
+        let Some(source) = &local.source else {
+            return Ok(None);
+        };
+        let current = SourceFingerprint::read(self.fs.as_ref(), &local.path).await?;
+        let changes = source.changes(&current);
+        if changes.is_empty() {
+            return Ok(None);
+        }
+        let restartable = match &declared.sha256 {
+            _ if current.size != declared.size => false,
+            Some(expected) => {
+                let actual = hash::sha256_reader(self.fs.open_read(&local.path).await?).await?;
+                if actual == *expected {
+                    return Ok(None);
+                }
+                false
+            }
+            None => true,
+        };
+        Ok(Some(SourceChange {
+            changes: changes.join("、"),
+            restartable,
+        }))
+    }
+
+    /// 在会话中记录源文件的改变，不能从头发送时返回错误
+    ///
+    /// 同一文件的改变只记录一次，换用途径重新上传时不重复
+    async fn record_source_change(
+        &self,
+        session: &FileSession,
+        local: &LocalFile,
+        change: &SourceChange,
+    ) -> crate::Result<()> {
+        let reason = if change.restartable {
+            format!("源文件在发送前已改变 ({})，已从头发送当前内容", change.changes)
+        } else {
+            format!("源文件在发送前已改变 ({})，与声明的长度或哈希不符，需重新发送", change.changes)
+        };
+        let name = local.path.to_string_lossy().into_owned();
+        let mut warnings = session.warnings.lock().await;
+        if !warnings.iter().any(|w| w.name == name && w.reason == reason) {
+            warn!(
+                session_id = %session.id,
+                path = %local.path.display(),
+                changes = %change.changes,
+                restartable = change.restartable,
+                "源文件已改变"
+            );
+            warnings.push(FileWarning::new(&name, &reason));
+            self.events.emit(ProtocolEvent::SourceChanged {
+                session_id: session.id.clone(),
+                file_id: local.id.clone(),
+                path: local.path.clone(),
+                reason: reason.clone(),
+            });
+        }
+        drop(warnings);
+
+        if change.restartable {
+            return Ok(());
+        }
+        let err = ProtocolError::InvalidData(format!("{}: {}", name, reason));
+        session.record_failure(&local.id, &err);
+        Err(err)
+    }
+
+    /// 读取文件开头，用于推断类型
+    async fn read_head(&self, path: &Path) -> crate::Result<Vec<u8>> {
+        let mut head = Vec::with_capacity(mime::SNIFF_LEN);
+        self.fs
+            .open_read(path)
+            .await?
+            .take(mime::SNIFF_LEN as u64)
+            .read_to_end(&mut head)
+            .await?;
+        Ok(head)
+    }
+
+    async fn upload_session(
+        &self,
+        device: &DeviceInfo,
+        session: &FileSession,
+        local_paths: &[LocalFile],
+    ) -> crate::Result<()> {
+        #[cfg(feature = "pairdrop")]
+        if let Some(pairdrop) = self.pairdrop.as_ref().filter(|_| pairdrop::is_pairdrop_device(device)) {
+            return self.upload_pairdrop(pairdrop, device, session, local_paths).await;
+        }
+
+        let response = self.prepare_upload(device, session).await?;
+        let uploaded = self.upload_prepared(device, session, &response, local_paths).await;
+        if uploaded.is_err() {
+            self.cancel_upload(device, &response.session_id).await;
+        }
+        uploaded
+    }
+
+    /// 按途径的优先顺序上传 prepare-upload 中对方接受的文件
+    async fn upload_prepared(
+        &self,
+        device: &DeviceInfo,
+        session: &FileSession,
+        response: &PrepareUploadResponseDto,
+        local_paths: &[LocalFile],
+    ) -> crate::Result<()> {
+        let mut routes = self.routes(device).await?.into_iter().peekable();
+        while let Some(route) = routes.next() {
+            let started = Instant::now();
+            let before = session.progress.bytes_transferred();
+            let uploaded = match &route {
+                Route::Transport(transport, addrs) => {
+                    self.upload_over(transport, addrs, session, response, local_paths)
+                        .await
+                }
+                Route::Http(scheme) => {
+                    self.upload_http(device, *scheme, session, response, local_paths)
+                        .await
+                }
+            };
+            let sent = session.progress.bytes_transferred() - before;
+            match uploaded {
+                Ok(()) => {
+                    let elapsed = started.elapsed();
+                    self.route_stats.record_or_warn(&device.id, route.name(), sent, elapsed);
+                    return Ok(());
+                }
+                // 已有文件经该途径上传完成时不再回退，避免重复发送
+                Err(e) if is_route_unavailable(&e) && sent == 0 && routes.peek().is_some() => {
+                    let route = route.name();
+                    debug!(device_id = %device.id, route, error = %e, "上传途径无法连接，尝试下一个途径");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(ProtocolError::InvalidData("没有可用的上传途径".to_string()))
+    }
+
+    /// 广播前与设备协商会话，返回上传途径和对方接受的文件
+    ///
+    /// PairDrop 设备不参与广播，在此直接发送完毕并返回 None
+    async fn prepare_recipient(
+        &self,
+        device: &DeviceInfo,
+        session: &FileSession,
+        local_paths: &[LocalFile],
+    ) -> crate::Result<Option<(Route, PrepareUploadResponseDto)>> {
+        #[cfg(feature = "pairdrop")]
+        if let Some(pairdrop) = self.pairdrop.as_ref().filter(|_| pairdrop::is_pairdrop_device(device)) {
+            self.upload_pairdrop(pairdrop, device, session, local_paths).await?;
+            return Ok(None);
+        }
+        #[cfg(not(feature = "pairdrop"))]
+        let _ = local_paths;
+
+        let response = self.prepare_upload(device, session).await?;
+        let route = match self.routes(device).await {
+            Ok(routes) => routes.into_iter().next(),
+            Err(e) => {
+                self.cancel_upload(device, &response.session_id).await;
+                return Err(e);
+            }
+        };
+        let route = route.ok_or_else(|| ProtocolError::InvalidData("没有可用的上传途径".to_string()))?;
+        Ok(Some((route, response)))
+    }
+
+    /// 发送 prepare-upload 请求，成功后会话进入传输状态
+    ///
+    /// 返回接收方分配的会话 ID 和对方接受的文件的上传令牌，
+    /// 对方没有需要接收的文件 (204) 时令牌为空
+    async fn prepare_upload(
+        &self,
+        device: &DeviceInfo,
+        session: &FileSession,
+    ) -> crate::Result<PrepareUploadResponseDto> {
+        let prepare = PrepareUploadRequestDto {
+            info: RegisterDto::from_config(&self.config),
+            files: session.files.iter().map(|f| (f.id.clone(), file_dto(f))).collect(),
+        };
+
+        let mut attempt = 0;
+        let response = loop {
+            let pin = self.pins.lock().unwrap().get(&device.id).cloned();
+            let query: Vec<(&str, &str)> = pin.iter().map(|pin| ("pin", pin.as_str())).collect();
+            let response = self
+                .post_json(device, PREPARE_UPLOAD_PATH, &query, &prepare)
+                .instrument(info_span!("prepare_upload", session_id = %session.id))
+                .await?;
+            // 与官方 LocalSend 一致，PIN 缺失或不正确时返回 401
+            if response.status != 401 {
+                break response.error_for_status()?;
+            }
+            attempt += 1;
+            let prompt = match &self.pin_prompt {
+                Some(prompt) if attempt <= MAX_PIN_ATTEMPTS => prompt,
+                _ => return Err(ProtocolError::PinRequired),
+            };
+            debug!(device = %device.id, attempt, "对方要求 PIN");
+            match (prompt.0)(device, attempt).await {
+                Some(pin) => self.set_pin(&device.id, Some(pin)),
+                None => return Err(ProtocolError::PinRequired),
+            }
+        };
+        let response = if response.status == 204 {
+            PrepareUploadResponseDto {
+                session_id: String::new(),
+                files: HashMap::new(),
+            }
+        } else {
+            response.json()?
+        };
+        debug!(
+            session_id = %session.id,
+            remote_session_id = %response.session_id,
+            accepted = response.files.len(),
+            "对方已接受"
+        );
+
+        self.sessions
+            .lock()
+            .await
+            .set_state(&session.id, SessionState::Transferring)
+            .await;
+        Ok(response)
+    }
+
+    /// 上传失败或取消后通知接收方取消会话，接收方据此作废未使用的令牌
+    ///
+    /// 只是尽力通知，失败时仅记录日志
+    async fn cancel_upload(&self, device: &DeviceInfo, session_id: &str) {
+        if session_id.is_empty() {
+            return;
+        }
+        let scheme = self.device_scheme(device).unwrap_or(Scheme::Http);
+        let request = HttpRequest::post(device_url(device, scheme, CANCEL_PATH))
+            .query("sessionId", session_id)
+            .timeout(CANCEL_TIMEOUT);
+        match self.http.execute(request).await {
+            Ok(response) => debug!(session_id, status = response.status, "已通知对方取消会话"),
+            Err(e) => debug!(session_id, error = %e, "通知对方取消会话失败"),
+        }
+    }
+
+    /// 按优先顺序列出向设备上传文件的途径，排序规则见 [`routes`]
+    ///
+    /// 默认顺序为双方都支持的传输 (按添加顺序)，最后是 HTTP
+    async fn routes(&self, device: &DeviceInfo) -> crate::Result<Vec<Route>> {
+        let mut available = Vec::new();
+        for transport in &self.transports {
+            let Some((_, port)) = device
+                .transports
+                .iter()
+                .map(|entry| transport::parse_capability(entry))
+                .find(|(name, _)| *name == transport.name())
+            else {
+                continue;
+            };
+            let addrs = net::resolve(&device.ip, port.unwrap_or(device.port))
+                .await
+                .map_err(|_| ProtocolError::InvalidData(format!("无效的设备地址: {}", device.ip)))?;
+            available.push(Route::Transport(transport.clone(), addrs));
+        }
+        available.push(Route::Http(self.device_scheme(device).unwrap_or(Scheme::Http)));
+
+        let names: Vec<&str> = available.iter().map(Route::name).collect();
+        let preferred = self.config.route_preferences.get(&device.id).map(Vec::as_slice);
+        let order = routes::order(&names, preferred, &self.route_stats.speeds(&device.id));
+        let mut available: Vec<Option<Route>> = available.into_iter().map(Some).collect();
+        let routes: Vec<Route> = order.into_iter().filter_map(|i| available[i].take()).collect();
+        debug!(
+            device_id = %device.id,
+            routes = ?routes.iter().map(Route::name).collect::<Vec<_>>(),
+            "上传途径"
+        );
+        Ok(routes)
+    }
+
+    /// 通过 HTTP 逐个上传对方接受的文件
+    async fn upload_http(
+        &self,
+        device: &DeviceInfo,
+        scheme: Scheme,
+        session: &FileSession,
+        response: &PrepareUploadResponseDto,
+        local_paths: &[LocalFile],
+    ) -> crate::Result<()> {
+        for (file, token) in accepted(session, response) {
+            if self.cancel.is_cancelled() {
+                return Err(ProtocolError::Cancelled);
+            }
+
+            let Some(local) = local_paths.iter().find(|f| f.id == file.id) else {
+                continue;
+            };
+            self.check_source(session, local).await?;
+
+            debug!(session_id = %session.id, file_id = %file.id, size = file.size, "上传文件");
+            let url = device_url(device, scheme, UPLOAD_PATH);
+            let query = [
+                ("sessionId", response.session_id.as_str()),
+                ("fileId", file.id.as_str()),
+                ("token", token),
+            ];
+            let mut offset = 0;
+            let mut attempt = 0;
+            loop {
+                let upload = self
+                    .upload_from(&url, &query, &local.path, file.size, offset)
+                    .instrument(info_span!(
+                        "upload_file",
+                        session_id = %session.id,
+                        file_id = %file.id,
+                        size = file.size,
+                        offset
+                    ));
+                let uploaded = tokio::select! {
+                    _ = self.cancel.cancelled() => return Err(ProtocolError::Cancelled),
+                    uploaded = upload => uploaded,
+                };
+                let Err(e) = uploaded else {
+                    break;
+                };
+                // 中断时对方保留已收到的内容，查询长度后从该处续传
+                let resumed = if attempt < MAX_RESUME_ATTEMPTS && is_interrupted(&e) {
+                    self.received_offset(&url, &query).await.ok()
+                } else {
+                    None
+                };
+                let Some(received) = resumed else {
+                    session.record_failure(&file.id, &e);
+                    return Err(e);
+                };
+                attempt += 1;
+                warn!(file_id = %file.id, received, error = %e, "上传中断，续传其余内容");
+                offset = received;
+            }
+
+            self.sessions
+                .lock()
+                .await
+                .report_progress(&session.id, &file.id, file.size)
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// 把本地文件从 offset 起的内容上传到 url，query 为会话、文件和令牌参数
+    async fn upload_from(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+        path: &Path,
+        size: u64,
+        offset: u64,
+    ) -> crate::Result<()> {
+        let reader = self.fs.open_read_at(path, offset).await?;
+        let reader = Throttled::new(reader, self.throttle.clone());
+        let mut request = HttpRequest::post(url);
+        for (key, value) in query {
+            request = request.query(key, value);
+        }
+        if offset > 0 {
+            request = request.query("offset", &offset.to_string());
+        }
+        let request = request.stream(Box::pin(reader), size - offset);
+        self.http.execute(request).await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// 查询对方已接收的字节数，对方不支持续传时返回错误
+    async fn received_offset(&self, url: &str, query: &[(&str, &str)]) -> crate::Result<u64> {
+        let mut request = HttpRequest::get(url);
+        for (key, value) in query {
+            request = request.query(key, value);
+        }
+        let response = self.http.execute(request).await?.error_for_status()?;
+        Ok(response.json::<UploadOffsetDto>()?.offset)
+    }
+
+    /// 通过传输并发上传对方接受的文件，每个文件一条流
+    ///
+    /// addrs 为对方的各个地址，连接失败时换用下一个，连接成功的地址优先用于之后的文件
+    async fn upload_over(
+        &self,
+        transport: &TransportRef,
+        addrs: &[SocketAddr],
+        session: &FileSession,
+        response: &PrepareUploadResponseDto,
+        local_paths: &[LocalFile],
+    ) -> crate::Result<()> {
+        let preferred = AtomicUsize::new(0);
+        let uploads = accepted(session, response).filter_map(|(file, token)| {
+            let local = local_paths.iter().find(|f| f.id == file.id)?;
+            let path = &local.path;
+            let preferred = &preferred;
+            let upload = async move {
+                self.check_source(session, local).await?;
+                let header = StreamHeader {
+                    session_id: response.session_id.clone(),
+                    file_id: file.id.clone(),
+                    token: token.to_string(),
+                    size: file.size,
+                    offset: 0,
+                    sparse: self.sparse_extents(path, file.size).await,
+                };
+
+                debug!(session_id = %session.id, file_id = %file.id, size = file.size, "上传文件");
+                let start = preferred.load(Ordering::Relaxed);
+                let mut attempt = 0;
+                let (mut stream, mut liveness) = loop {
+                    let index = (start + attempt) % addrs.len();
+                    match self.send_stream(transport, addrs[index], &header, path).await {
+                        Ok(sent) => {
+                            preferred.store(index, Ordering::Relaxed);
+                            break sent;
+                        }
+                        Err(ProtocolError::Io(e))
+                            if net::is_unreachable(&e) && attempt + 1 < addrs.len() =>
+                        {
+                            debug!(peer = %addrs[index], error = %e, "地址不可达，尝试下一个地址");
+                            attempt += 1;
+                        }
+                        Err(e) => return Err(e),
+                    }
+                };
+                stream.shutdown().await?;
+
+                // 接收端写完文件后关闭流，以此确认文件已落盘
+                liveness.wait().await?;
+
+                self.sessions
+                    .lock()
+                    .await
+                    .report_progress(&session.id, &file.id, file.size)
+                    .await;
+                Ok::<_, ProtocolError>(())
+            };
+            Some(
+                async move {
+                    let uploaded = upload.await;
+                    if let Err(e) = &uploaded {
+                        session.record_failure(&file.id, e);
+                    }
+                    uploaded
+                }
+                .instrument(info_span!(
+                    "upload_file",
+                    session_id = %session.id,
+                    file_id = %file.id,
+                    size = file.size
+                )),
+            )
+        });
+
+        tokio::select! {
+            _ = self.cancel.cancelled() => Err(ProtocolError::Cancelled),
+            result = futures::future::try_join_all(uploads) => result.map(|_| ()),
+        }
+    }
+
+    /// 把广播中的一个读取端上传给设备，header 描述上传的文件
+    async fn upload_reader(
+        &self,
+        device: &DeviceInfo,
+        route: &Route,
+        header: StreamHeader,
+        reader: BroadcastReader,
+    ) -> crate::Result<()> {
+        let mut reader = Throttled::new(reader, self.throttle.clone());
+        match route {
+            Route::Transport(transport, addrs) => {
+                let stream = connect_any(transport, addrs).await?;
+                let (reader_half, mut stream) = tokio::io::split(stream);
+                let mut liveness = Liveness::new(reader_half);
+                let send = transport::send_reader(&mut stream, &header, &mut reader);
+                watch_send(send, &mut liveness).await?;
+                stream.shutdown().await?;
+                // 接收端写完文件后关闭流，以此确认文件已落盘
+                liveness.wait().await?;
+            }
+            Route::Http(scheme) => {
+                self.http
+                    .execute(
+                        HttpRequest::post(device_url(device, *scheme, UPLOAD_PATH))
+                            .query("sessionId", &header.session_id)
+                            .query("fileId", &header.file_id)
+                            .query("token", &header.token)
+                            .stream(Box::pin(reader), header.size),
+                    )
+                    .await?
+                    .error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 连接 addr 并发送文件内容，返回流的写端和等待确认的存活检测
+    ///
+    /// 零拷贝路径发送期间无法读取保活字节，依靠传输自身的保活发现对方掉线
+    async fn send_stream(
+        &self,
+        transport: &TransportRef,
+        addr: SocketAddr,
+        header: &StreamHeader,
+        path: &Path,
+    ) -> crate::Result<(WriteHalf<BoxedStream>, Liveness<ReadHalf<BoxedStream>>)> {
+        // sendfile 等零拷贝路径无法限速
+        if self.fs.is_local() && !self.throttle.is_limited() {
+            let stream = transport.send_local_file(addr, header, path).await?;
+            let (reader, writer) = tokio::io::split(stream);
+            return Ok((writer, Liveness::new(reader)));
+        }
+        let (reader, mut stream) = tokio::io::split(transport.connect(addr).await?);
+        let mut liveness = Liveness::new(reader);
+        watch_send(self.send_contents(&mut stream, header, path), &mut liveness).await?;
+        Ok((stream, liveness))
+    }
+
+    /// 经 fs 读取文件并写入流，稀疏文件只发送各数据区段
+    async fn send_contents<S>(
+        &self,
+        stream: &mut S,
+        header: &StreamHeader,
+        path: &Path,
+    ) -> crate::Result<u64>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        let Some(extents) = &header.sparse else {
+            let file = self.fs.open_read(path).await?;
+            let mut file = Throttled::new(file, self.throttle.clone());
+            return transport::send_reader(stream, header, &mut file).await;
+        };
+
+        transport::write_header(stream, header).await?;
+        let mut sent = 0;
+        for &(offset, len) in extents {
+            let file = self.fs.open_read_at(path, offset).await?;
+            let mut file = Throttled::new(file, self.throttle.clone());
+            sent += transport::send_body(stream, &mut file, len).await?;
+        }
+        stream.flush().await?;
+        Ok(sent)
+    }
+
+    /// 本地稀疏文件的数据区段，不是稀疏文件或无法检测时返回 None
+    async fn sparse_extents(&self, path: &Path, size: u64) -> Option<Vec<sparse::Extent>> {
+        if !self.fs.is_local() {
+            return None;
+        }
+        match sparse::data_extents(path, size).await {
+            Ok(Some(extents)) => {
+                let data = sparse::data_len(&extents);
+                debug!(path = %path.display(), size, data, "按稀疏文件发送");
+                Some(extents)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                debug!(path = %path.display(), error = %e, "检测空洞失败，按普通文件发送");
+                None
+            }
+        }
+    }
+
+    /// 经 WebRTC 数据通道向浏览器对端发送文件
+    #[cfg(feature = "pairdrop")]
+    async fn upload_pairdrop(
+        &self,
+        pairdrop: &PairDrop,
+        device: &DeviceInfo,
+        session: &FileSession,
+        local_paths: &[LocalFile],
+    ) -> crate::Result<()> {
+        let mut files = Vec::with_capacity(session.files.len());
+        for (file, local) in session.files.iter().zip(local_paths) {
+            files.push(pairdrop::channel::OutgoingFile {
+                header: pairdrop::channel::FileHeader {
+                    name: file.name.clone(),
+                    mime: file.file_type.clone(),
+                    size: file.size,
+                },
+                reader: self.fs.open_read(&local.path).await?,
+            });
+        }
+
+        self.sessions
+            .lock()
+            .await
+            .set_state(&session.id, SessionState::Transferring)
+            .await;
+        // 回调在发送结束时随 send 一起释放，report 随之结束
+        let (sent_tx, mut sent_rx) = mpsc::unbounded_channel();
+        let send = pairdrop.send_files(device, files, move |index| {
+            let _ = sent_tx.send(index);
+        });
+        let report = async {
+            while let Some(index) = sent_rx.recv().await {
+                let file = &session.files[index];
+                self.sessions
+                    .lock()
+                    .await
+                    .report_progress(&session.id, &file.id, file.size)
+                    .await;
+            }
+        };
+
+        let (result, ()) = tokio::join!(send, report);
+        result
+    }
+
+    /// 向设备发送文本消息
+    #[instrument(skip(self, device, text), fields(peer = %device.ip, device_id = %device.id))]
+    pub async fn send_text(&self, device: &DeviceInfo, text: &str) -> crate::Result<()> {
+        #[cfg(feature = "pairdrop")]
+        if let Some(pairdrop) = self.pairdrop.as_ref().filter(|_| pairdrop::is_pairdrop_device(device)) {
+            return pairdrop.send_text(device, text).await;
+        }
+
+        let device = &self.reachable(device).await;
+        let request = FileRequest {
+            id: self.config.device_id.clone(),
+            sender: self.config.device_name.clone(),
+            sender_type: self.config.device_type.clone(),
+            files: Vec::new(),
+            session_id: uuid::Uuid::new_v4().to_string(),
+            token: String::new(),
+            message: text.to_string(),
+        };
+
+        let response: FileResponse = self
+            .post_json(device, "/api/v1/localsend/request", &[], &request)
+            .await?
+            .error_for_status()?
+            .json()?;
+
+        if !response.accepted {
+            return Err(ProtocolError::Rejected("对方拒绝了文本消息".to_string()));
+        }
+        Ok(())
+    }
+
+    /// 启动接收服务，返回可获取接收事件的句柄
+    pub async fn receive(&self) -> crate::Result<ReceiveHandle> {
+        let addr = SocketAddr::new(self.config.ip_mode.unspecified(), self.config.port);
+        let (tx, events) = mpsc::unbounded_channel();
+
+        let mut server = LocalSendServer::new(
+            addr,
+            self.config.clone(),
+            self.sessions.clone(),
+            self.discovery.clone(),
+        )
+        .with_event_sender(tx)
+        .with_throttle(self.throttle.clone())
+        .with_cancellation(self.cancel.child_token());
+
+        // 为公告的每种传输启动监听
+        for entry in &self.config.transports {
+            match transport::parse_capability(entry) {
+                // QUIC 使用同一端口号的 UDP
+                #[cfg(feature = "quic")]
+                (transport::quic::QUIC_TRANSPORT, port) => {
+                    let addr = SocketAddr::new(addr.ip(), port.unwrap_or(addr.port()));
+                    let listener = transport::quic::QuicTransport::new()?
+                        .with_ip_mode(self.config.ip_mode)
+                        .listen(addr)
+                        .await?;
+                    server = server.with_transport_listener(Box::new(listener));
+                }
+                (transport::tcp::TCP_TRANSPORT, Some(port)) => {
+                    let addr = SocketAddr::new(addr.ip(), port);
+                    let listener = transport::tcp::TcpTransport::new()
+                        .with_ip_mode(self.config.ip_mode)
+                        .listen(addr)
+                        .await?;
+                    server = server.with_transport_listener(Box::new(listener));
+                }
+                (transport::tcp::TCP_TRANSPORT, None) => {
+                    return Err(ProtocolError::InvalidConfig(
+                        "裸 TCP 传输需要独立端口，例如 tcp:53318".to_string(),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        server.start().await?;
+
+        Ok(ReceiveHandle { server, events })
+    }
+}
+
+fn device_key(device: &DeviceInfo) -> String {
+    net::host_port(&device.ip, device.port)
+}
+
+fn device_url(device: &DeviceInfo, scheme: Scheme, path: &str) -> String {
+    format!("{}://{}{}", scheme.as_str(), device_key(device), path)
+}
@@ -0,0 +1,149 @@
+//! 剪贴板同步
+//!
+//! 定期读取本机剪贴板，文本变化时作为文本消息推送给一台已配对的设备；
+//! 收到该设备的文本消息时写入本机剪贴板。两端都开启即为简易的通用剪贴板。
+//!
+//! 两端记录最近一次同步的文本，写入收到的文本后读回的相同内容不会再推送回去，
+//! 避免来回回传。超过长度上限的文本既不发送也不接受。
+//! 访问剪贴板的方式因平台而异，由前端实现 [`Clipboard`]
+
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::{ClipboardSyncConfig, DeviceInfo, LocalSendClient, ProtocolError, ProtocolEvent};
+
+/// 默认的剪贴板轮询间隔
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 本机剪贴板
+pub trait Clipboard: Send {
+    /// 读取剪贴板中的文本，没有文本或读取失败时返回 None
+    fn get_text(&mut self) -> Option<String>;
+
+    /// 写入文本
+    fn set_text(&mut self, text: &str) -> crate::Result<()>;
+}
+
+/// 剪贴板同步
+#[derive(Debug, Clone)]
+pub struct ClipboardSync {
+    client: LocalSendClient,
+    /// 同步的设备的 ID、名称、IP 或主机名
+    target: String,
+    max_bytes: usize,
+    interval: Duration,
+}
+
+impl ClipboardSync {
+    /// 创建剪贴板同步，target 为同步的设备的 ID、名称、IP 或主机名
+    pub fn new(client: LocalSendClient, target: impl Into<String>, max_bytes: usize) -> Self {
+        Self {
+            client,
+            target: target.into(),
+            max_bytes,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// 按配置创建剪贴板同步
+    pub fn from_config(client: LocalSendClient, config: &ClipboardSyncConfig) -> Self {
+        Self::new(client, &config.device, config.max_bytes)
+    }
+
+    /// 设置剪贴板轮询间隔
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// 查找同步的设备并同步，直到 cancel 取消
+    ///
+    /// 设备未配对时返回 [`ProtocolError::Rejected`]。
+    /// events 为接收服务的事件总线，从中取得对方发来的文本
+    pub async fn run(
+        &self,
+        clipboard: impl Clipboard,
+        events: broadcast::Receiver<ProtocolEvent>,
+        cancel: CancellationToken,
+    ) -> crate::Result<()> {
+        let device = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            device = crate::outbox::resolve_device(&self.client, &self.target) => device?,
+        };
+        self.sync_with(&device, clipboard, events, cancel).await
+    }
+
+    /// 与已知设备同步，直到 cancel 取消
+    ///
+    /// 启动时剪贴板中已有的文本不推送，只同步之后的变化
+    pub async fn sync_with(
+        &self,
+        device: &DeviceInfo,
+        mut clipboard: impl Clipboard,
+        mut events: broadcast::Receiver<ProtocolEvent>,
+        cancel: CancellationToken,
+    ) -> crate::Result<()> {
+        if !self.client.config().is_trusted(&device.id) {
+            return Err(ProtocolError::Rejected(format!(
+                "{} 未配对，不同步剪贴板",
+                device.name
+            )));
+        }
+        info!(device = %device.name, "开始同步剪贴板");
+
+        let mut last = clipboard.get_text().map(|text| digest(&text));
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return Ok(()),
+                _ = ticker.tick() => {
+                    let Some(text) = clipboard.get_text() else {
+                        continue;
+                    };
+                    let current = digest(&text);
+                    if last == Some(current) {
+                        continue;
+                    }
+                    // 无论是否发送都记下，超长的文本不会每次轮询都提示
+                    last = Some(current);
+                    if text.len() > self.max_bytes {
+                        debug!(len = text.len(), max = self.max_bytes, "剪贴板文本过长，不同步");
+                        continue;
+                    }
+                    if let Err(e) = self.client.send_text(device, &text).await {
+                        warn!(device = %device.name, error = %e, "推送剪贴板失败");
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(ProtocolEvent::TextReceived { sender_id, text, .. })
+                        if sender_id == device.id =>
+                    {
+                        if text.len() > self.max_bytes {
+                            warn!(len = text.len(), max = self.max_bytes, "收到的剪贴板文本过长，忽略");
+                            continue;
+                        }
+                        let current = digest(&text);
+                        if last == Some(current) {
+                            continue;
+                        }
+                        last = Some(current);
+                        match clipboard.set_text(&text) {
+                            Ok(()) => debug!(len = text.len(), "已写入剪贴板"),
+                            Err(e) => warn!(error = %e, "写入剪贴板失败"),
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+            }
+        }
+    }
+}
+
+fn digest(text: &str) -> [u8; 32] {
+    Sha256::digest(text.as_bytes()).into()
+}
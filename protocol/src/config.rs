@@ -0,0 +1,562 @@
+//! 配置构建、校验与持久化
+//!
+//! 各前端共享同一个配置文件，保证设备 ID 和密钥在重启后保持不变
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{BandwidthWindow, LocalSendConfig, PinPolicy, ProtocolError, QuickSave};
+
+pub use crate::LocalSendConfigBuilder;
+
+/// 设备名称最大长度（字符数）
+pub const MAX_DEVICE_NAME_LEN: usize = 64;
+
+/// 协议规定的设备类型
+pub const DEVICE_TYPES: &[&str] = &["mobile", "desktop", "web", "headless", "server"];
+
+/// 可在 `route_preferences` 中指定的上传途径: 传输名称和 LocalSend 的 HTTPS、HTTP 上传
+pub const ROUTE_NAMES: &[&str] = &["quic", "tcp", "easytier", "https", "http"];
+
+/// 发送目标中设备组的前缀，如 `@family`
+pub const GROUP_PREFIX: char = '@';
+
+/// 配置文件名
+const CONFIG_FILE_NAME: &str = "localsend.json";
+
+impl LocalSendConfigBuilder {
+    /// 构建并校验配置
+    pub fn build(&self) -> crate::Result<LocalSendConfig> {
+        let config = self
+            .build_unchecked()
+            .map_err(|e| ProtocolError::InvalidConfig(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl LocalSendConfig {
+    /// 创建配置构建器，未设置的字段使用默认值
+    pub fn builder() -> LocalSendConfigBuilder {
+        LocalSendConfigBuilder::default()
+    }
+
+    /// 校验配置
+    ///
+    /// 下载目录不存在时会尝试创建，并确认可以写入
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.port == 0 {
+            return Err(ProtocolError::InvalidConfig("端口必须在 1-65535 之间".to_string()));
+        }
+
+        let name_len = self.device_name.trim().chars().count();
+        if name_len == 0 || name_len > MAX_DEVICE_NAME_LEN {
+            return Err(ProtocolError::InvalidConfig(format!(
+                "设备名称长度必须在 1-{} 个字符之间",
+                MAX_DEVICE_NAME_LEN
+            )));
+        }
+
+        if !DEVICE_TYPES.contains(&self.device_type.as_str()) {
+            return Err(ProtocolError::InvalidConfig(format!(
+                "无效的设备类型: {}",
+                self.device_type
+            )));
+        }
+
+        if let Some(address) = self.addresses.iter().find(|a| crate::net::parse_ip(a).is_none()) {
+            return Err(ProtocolError::InvalidConfig(format!("无效的候选地址: {}", address)));
+        }
+
+        for (device, routes) in &self.route_preferences {
+            if device.trim().is_empty() || routes.is_empty() {
+                return Err(ProtocolError::InvalidConfig(format!(
+                    "设备 {:?} 的上传途径偏好无效",
+                    device
+                )));
+            }
+            if let Some(route) = routes.iter().find(|r| !ROUTE_NAMES.contains(&r.as_str())) {
+                return Err(ProtocolError::InvalidConfig(format!("未知的上传途径: {}", route)));
+            }
+        }
+
+        if let Some(port) = self.browse_port {
+            if port == 0 || port == self.port {
+                return Err(ProtocolError::InvalidConfig(
+                    "浏览页面端口必须在 1-65535 之间且不能与接收端口相同".to_string(),
+                ));
+            }
+            if self.browse_password.as_deref().is_none_or(|p| p.is_empty()) {
+                return Err(ProtocolError::InvalidConfig(
+                    "启用浏览页面时必须设置密码".to_string(),
+                ));
+            }
+        }
+
+        if let Some(port) = self.share_port {
+            if port == 0 || port == self.port || Some(port) == self.browse_port {
+                return Err(ProtocolError::InvalidConfig(
+                    "分享链接端口必须在 1-65535 之间且不能与其他端口相同".to_string(),
+                ));
+            }
+        }
+
+        if let Some(port) = self.metrics_port {
+            if port == 0
+                || port == self.port
+                || Some(port) == self.browse_port
+                || Some(port) == self.share_port
+            {
+                return Err(ProtocolError::InvalidConfig(
+                    "指标端口必须在 1-65535 之间且不能与其他端口相同".to_string(),
+                ));
+            }
+        }
+
+        if let Some(port) = self.api_port {
+            if port == 0
+                || port == self.port
+                || Some(port) == self.browse_port
+                || Some(port) == self.share_port
+                || Some(port) == self.metrics_port
+            {
+                return Err(ProtocolError::InvalidConfig(
+                    "管理 API 端口必须在 1-65535 之间且不能与其他端口相同".to_string(),
+                ));
+            }
+            if self.api_token.as_deref().is_none_or(|t| t.is_empty()) {
+                return Err(ProtocolError::InvalidConfig(
+                    "启用管理 API 时必须设置访问令牌".to_string(),
+                ));
+            }
+        }
+
+        for rule in self.allowlist.iter().chain(&self.blocklist).chain(&self.pin_devices) {
+            if rule.trim().is_empty() || (rule.contains('/') && parse_ip_range(rule).is_none()) {
+                return Err(ProtocolError::InvalidConfig(format!(
+                    "无效的设备规则: {:?}",
+                    rule
+                )));
+            }
+        }
+
+        for (name, devices) in &self.groups {
+            let invalid = name.starts_with(GROUP_PREFIX) || name.contains(char::is_whitespace);
+            if name.is_empty() || invalid {
+                return Err(ProtocolError::InvalidConfig(format!(
+                    "无效的设备组名称: {:?}",
+                    name
+                )));
+            }
+            if devices.is_empty() {
+                return Err(ProtocolError::InvalidConfig(format!("设备组 {} 为空", name)));
+            }
+            // 不支持嵌套的设备组
+            if let Some(device) = devices
+                .iter()
+                .find(|d| d.trim().is_empty() || d.starts_with(GROUP_PREFIX))
+            {
+                return Err(ProtocolError::InvalidConfig(format!(
+                    "设备组 {} 中的设备无效: {:?}",
+                    name, device
+                )));
+            }
+        }
+
+        if self.max_file_size == Some(0)
+            || self.max_session_size == Some(0)
+            || self.max_files == Some(0)
+        {
+            return Err(ProtocolError::InvalidConfig(
+                "文件大小和数量上限必须大于 0".to_string(),
+            ));
+        }
+
+        if self.bandwidth_limit == Some(0)
+            || self.bandwidth_schedule.iter().any(|w| w.limit == Some(0))
+        {
+            return Err(ProtocolError::InvalidConfig("带宽上限必须大于 0".to_string()));
+        }
+        for window in &self.bandwidth_schedule {
+            match (parse_time_of_day(&window.start), parse_time_of_day(&window.end)) {
+                (Some(start), Some(end)) if start != end => {}
+                _ => {
+                    return Err(ProtocolError::InvalidConfig(format!(
+                        "无效的带宽时段: {}-{}",
+                        window.start, window.end
+                    )))
+                }
+            }
+        }
+
+        if let Some(metered) = &self.metered {
+            if metered.bandwidth_limit == Some(0) || metered.max_file_size == Some(0) {
+                return Err(ProtocolError::InvalidConfig(
+                    "省流模式的带宽上限和文件大小上限必须大于 0".to_string(),
+                ));
+            }
+            if metered.announce_interval_secs == 0 {
+                return Err(ProtocolError::InvalidConfig(
+                    "省流模式的公告间隔必须大于 0".to_string(),
+                ));
+            }
+        }
+
+        if let Some(retention) = &self.retention {
+            if retention.max_age_days.is_none() && retention.max_size.is_none() {
+                return Err(ProtocolError::InvalidConfig(
+                    "清理策略须设置保留天数或大小上限".to_string(),
+                ));
+            }
+            if retention.max_age_days == Some(0) || retention.max_size == Some(0) {
+                return Err(ProtocolError::InvalidConfig(
+                    "保留天数和大小上限必须大于 0".to_string(),
+                ));
+            }
+        }
+
+        if let Some(sync) = &self.clipboard_sync {
+            if sync.device.trim().is_empty() {
+                return Err(ProtocolError::InvalidConfig(
+                    "剪贴板同步须指定设备".to_string(),
+                ));
+            }
+            if sync.max_bytes == 0 {
+                return Err(ProtocolError::InvalidConfig(
+                    "剪贴板文本长度上限必须大于 0".to_string(),
+                ));
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        for hook in &self.hooks {
+            crate::hooks::split_command(&hook.command)?;
+            if hook.timeout_secs == 0 {
+                return Err(ProtocolError::InvalidConfig(format!(
+                    "命令超时必须大于 0: {}",
+                    hook.command
+                )));
+            }
+        }
+
+        if let Some(extract) = &self.extract {
+            if extract.max_size == 0 || extract.max_entries == 0 {
+                return Err(ProtocolError::InvalidConfig(
+                    "解压大小上限和条目数量上限必须大于 0".to_string(),
+                ));
+            }
+        }
+
+        if let Some(proxy) = &self.proxy {
+            let valid = url::Url::parse(proxy)
+                .is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.has_host());
+            if !valid {
+                return Err(ProtocolError::InvalidConfig(format!("无效的代理地址: {}", proxy)));
+            }
+        }
+
+        if !self.pin_devices.is_empty() && self.pin.as_deref().is_none_or(|p| p.is_empty()) {
+            return Err(ProtocolError::InvalidConfig(
+                "指定需要 PIN 的设备时必须设置 PIN".to_string(),
+            ));
+        }
+
+        let download_dir = Path::new(&self.download_dir);
+        std::fs::create_dir_all(download_dir)?;
+        let probe = download_dir.join(format!(".peersend-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&probe, b"").map_err(|e| {
+            ProtocolError::InvalidConfig(format!(
+                "下载目录不可写: {}: {}",
+                download_dir.display(),
+                e
+            ))
+        })?;
+        let _ = std::fs::remove_file(&probe);
+
+        Ok(())
+    }
+
+    /// 是否自动接受来自 device_id 的请求
+    pub fn quick_saves_from(&self, device_id: &str) -> bool {
+        match self.quick_save {
+            QuickSave::Off => false,
+            QuickSave::Favorites => self.is_trusted(device_id),
+            QuickSave::On => true,
+        }
+    }
+
+    /// 设备是否受信任，即在收藏中
+    pub fn is_trusted(&self, device_id: &str) -> bool {
+        self.favorites.iter().any(|id| id == device_id)
+    }
+
+    /// 展开发送目标，`@组名` 展开为组内的设备，其他目标原样返回
+    ///
+    /// 设备组不存在时返回 [`ProtocolError::InvalidConfig`]
+    pub fn expand_target(&self, target: &str) -> crate::Result<Vec<String>> {
+        let Some(name) = group_name(target) else {
+            return Ok(vec![target.to_string()]);
+        };
+        self.groups
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ProtocolError::InvalidConfig(format!("未找到设备组: {}", name)))
+    }
+
+    /// 是否与设备交互，接收请求和发现结果都据此过滤
+    ///
+    /// 列表项为设备 ID (即证书指纹)、IP 地址或 CIDR 网段，如 `192.168.1.0/24`。
+    /// 屏蔽列表优先，允许列表非空时只允许其中的设备
+    pub fn allows_device(&self, device_id: &str, ip: &str) -> bool {
+        let matches = |rule: &String| device_rule_matches(rule, device_id, ip);
+        if self.blocklist.iter().any(matches) {
+            return false;
+        }
+        self.allowlist.is_empty() || self.allowlist.iter().any(matches)
+    }
+
+    /// 来自设备的请求是否需要提供 PIN
+    ///
+    /// 未设置 PIN 时总是不需要；`pin_devices` 中的设备总是需要，
+    /// 其余设备按 [`PinPolicy`] 判断
+    pub fn requires_pin(&self, device_id: &str, ip: &str) -> bool {
+        if self.pin.as_deref().is_none_or(|p| p.is_empty()) {
+            return false;
+        }
+        if self.pin_devices.iter().any(|rule| device_rule_matches(rule, device_id, ip)) {
+            return true;
+        }
+        match self.pin_policy {
+            PinPolicy::All => true,
+            PinPolicy::Unknown => !self.is_trusted(device_id),
+            PinPolicy::Listed => false,
+        }
+    }
+
+    /// 是否有设备需要提供 PIN，用于公告中的 `usesPassword`
+    pub fn uses_pin(&self) -> bool {
+        if self.pin.as_deref().is_none_or(|p| p.is_empty()) {
+            return false;
+        }
+        self.pin_policy != PinPolicy::Listed || !self.pin_devices.is_empty()
+    }
+
+    /// 共享配置文件路径
+    ///
+    /// - Linux: `$XDG_CONFIG_HOME/peersend/localsend.json`
+    /// - macOS: `~/Library/Application Support/peersend/localsend.json`
+    /// - Windows: `%APPDATA%\peersend\localsend.json`
+    pub fn default_path() -> Option<PathBuf> {
+        let base = if cfg!(target_os = "windows") {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join("Library").join("Application Support"))
+        } else {
+            std::env::var_os("XDG_CONFIG_HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        }?;
+        Some(base.join("peersend").join(CONFIG_FILE_NAME))
+    }
+
+    /// 从文件加载配置，缺失的字段使用默认值
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        let data = std::fs::read(path)?;
+        let config: Self = serde_json::from_slice(&data)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 保存配置到文件
+    pub fn save(&self, path: &Path) -> crate::Result<()> {
+        // 配置包含 API 密钥，仅允许当前用户读取
+        write_private(path, &serde_json::to_vec_pretty(self)?)
+    }
+
+    /// 加载共享配置文件，不存在时生成默认配置并保存
+    pub fn load_or_init() -> crate::Result<Self> {
+        let path = Self::default_path()
+            .ok_or_else(|| ProtocolError::InvalidConfig("无法确定配置目录".to_string()))?;
+
+        if path.exists() {
+            return Self::load(&path);
+        }
+
+        let config = Self::default();
+        config.validate()?;
+        config.save(&path)?;
+        Ok(config)
+    }
+}
+
+/// 以 [`GROUP_PREFIX`] 开头的发送目标所指的设备组名，其他目标返回 None
+pub fn group_name(target: &str) -> Option<&str> {
+    target.strip_prefix(GROUP_PREFIX)
+}
+
+/// 可移植的配置包，用于把设置迁移到新机器
+///
+/// 包含完整的配置，受信任的设备即其中的收藏 (`favorites`)，允许和屏蔽列表等
+/// 规则也一并带走。导出时可去掉密钥，导入时去掉的密钥沿用本机原有的值
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ConfigBundle {
+    /// 配置包格式版本，见 [`ConfigBundle::VERSION`]
+    pub version: u32,
+    /// 导出时间 (Unix 时间戳，秒)
+    pub exported_at: u64,
+    /// 是否已去掉密钥
+    pub redacted: bool,
+    pub config: LocalSendConfig,
+}
+
+impl ConfigBundle {
+    /// 当前的配置包格式版本，更新版本的配置包无法导入
+    pub const VERSION: u32 = 1;
+
+    /// 打包配置
+    pub fn new(config: &LocalSendConfig) -> Self {
+        Self {
+            version: Self::VERSION,
+            exported_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            redacted: false,
+            config: config.clone(),
+        }
+    }
+
+    /// 去掉 API 密钥、PIN 和各项密码，便于分享或存放在不受信任的位置
+    pub fn redact_secrets(mut self) -> Self {
+        let config = &mut self.config;
+        config.api_key.clear();
+        config.pin = None;
+        config.browse_password = None;
+        config.api_token = None;
+        if let Some(mqtt) = &mut config.mqtt {
+            mqtt.password = None;
+        }
+        self.redacted = true;
+        self
+    }
+
+    /// 由配置包得到要保存的配置
+    ///
+    /// 配置包已去掉密钥时，缺少的密钥沿用 local 中的值。结果经过校验，
+    /// 例如配置包启用了浏览页面而本机没有设置密码时返回错误
+    pub fn into_config(self, local: &LocalSendConfig) -> crate::Result<LocalSendConfig> {
+        if self.version > Self::VERSION {
+            return Err(ProtocolError::InvalidConfig(format!(
+                "不支持的配置包版本 {}，请升级 PeerSend",
+                self.version
+            )));
+        }
+        let mut config = self.config;
+        if self.redacted {
+            if config.api_key.is_empty() {
+                config.api_key = local.api_key.clone();
+            }
+            config.pin = config.pin.or_else(|| local.pin.clone());
+            config.browse_password =
+                config.browse_password.or_else(|| local.browse_password.clone());
+            config.api_token = config.api_token.or_else(|| local.api_token.clone());
+            if let (Some(mqtt), Some(local_mqtt)) = (&mut config.mqtt, &local.mqtt) {
+                if mqtt.password.is_none() && mqtt.broker == local_mqtt.broker {
+                    mqtt.password = local_mqtt.password.clone();
+                }
+            }
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 从文件读取配置包
+    pub fn load(path: &Path) -> crate::Result<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    /// 保存配置包，未去掉密钥的配置包仅允许当前用户读取
+    pub fn save(&self, path: &Path) -> crate::Result<()> {
+        write_private(path, &serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// 写入文件并仅允许当前用户读取，需要时创建上级目录
+pub(crate) fn write_private(path: &Path, data: &[u8]) -> crate::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, data)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    Ok(())
+}
+
+fn device_rule_matches(rule: &str, device_id: &str, ip: &str) -> bool {
+    let rule = rule.trim();
+    if rule == device_id {
+        return true;
+    }
+    let (Ok(ip), Some((net, prefix))) = (ip.parse::<IpAddr>(), parse_ip_range(rule)) else {
+        return false;
+    };
+    // IPv4 映射的 IPv6 地址按 IPv4 比较
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    };
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+impl BandwidthWindow {
+    /// 时段是否包含一天中的第 minute 分钟，时间格式无效时返回 false
+    pub fn contains(&self, minute: u32) -> bool {
+        let (Some(start), Some(end)) = (parse_time_of_day(&self.start), parse_time_of_day(&self.end))
+        else {
+            return false;
+        };
+        if start <= end {
+            (start..end).contains(&minute)
+        } else {
+            minute >= start || minute < end
+        }
+    }
+}
+
+/// 解析 `HH:MM`，返回一天中的第几分钟
+fn parse_time_of_day(time: &str) -> Option<u32> {
+    let (hour, minute) = time.trim().split_once(':')?;
+    let hour: u32 = hour.parse().ok()?;
+    let minute: u32 = minute.parse().ok()?;
+    (hour < 24 && minute < 60).then_some(hour * 60 + minute)
+}
+
+/// 解析 IP 地址或 CIDR 网段，返回网络地址和前缀长度
+fn parse_ip_range(rule: &str) -> Option<(IpAddr, u8)> {
+    let (addr, prefix) = match rule.trim().split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix.parse::<u8>().ok()?)),
+        None => (rule.trim(), None),
+    };
+    let addr: IpAddr = addr.parse().ok()?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max);
+    (prefix <= max).then_some((addr, prefix))
+}
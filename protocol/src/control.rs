@@ -0,0 +1,661 @@
+//! 本地控制通道
+//!
+//! 接收守护进程在 Unix 域套接字 (Windows 上为命名管道) 上提供控制接口，
+//! 命令行和图形界面经此查询设备与会话、答复请求和发起发送，
+//! 无需在 127.0.0.1 上开放同一台机器上其他用户也能访问的端口。
+//!
+//! 协议为逐行 JSON：客户端每行发送一个 [`ControlRequest`]，守护进程按顺序每行回复一个
+//! [`ControlResponse`]。Unix 上套接字所在目录仅当前用户可访问；
+//! Windows 命名管道使用默认安全描述符，拒绝远程客户端
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufStream,
+};
+use tokio::sync::{broadcast, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use crate::audit::{self, AuditRecord};
+use crate::config::group_name;
+use crate::server::accept::AcceptGate;
+use crate::{
+    DeviceInfo, DiscoveryManager, FileInfo, FileSession, FileWarning, LocalSendClient,
+    LocalSendConfig, ProtocolError, ProtocolEvent, SessionManager, SessionState, SessionSummary,
+};
+
+/// 单行请求的长度上限
+const MAX_LINE_LEN: u64 = 64 * 1024;
+
+/// 临时开放接收的时长上限
+pub const MAX_OPEN_DURATION: Duration = Duration::from_secs(24 * 3600);
+
+/// 会话的 JSON 表示
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionView {
+    pub id: String,
+    pub sender_id: String,
+    pub receiver_id: String,
+    /// `waiting`、`transferring`、`finished`、`cancelled` 或 `error`
+    pub state: String,
+    /// 会话失败的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 本机是否为接收方
+    pub incoming: bool,
+    /// 是否在等待接受或拒绝
+    pub pending: bool,
+    pub files: Vec<FileInfo>,
+    pub bytes_transferred: u64,
+    pub total_bytes: u64,
+    /// 被跳过或未能按原样传输的文件
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<FileWarning>,
+    /// 会话结束时的摘要
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<Box<SessionSummary>>,
+}
+
+impl SessionView {
+    async fn new(session: &FileSession, device_id: &str, pending: bool) -> Self {
+        let (state, error) = match &*session.state.lock().await {
+            SessionState::Waiting => ("waiting", None),
+            SessionState::Transferring => ("transferring", None),
+            SessionState::Finished => ("finished", None),
+            SessionState::Cancelled => ("cancelled", None),
+            SessionState::Error(message) => ("error", Some(message.clone())),
+        };
+        Self {
+            id: session.id.clone(),
+            sender_id: session.sender_id.clone(),
+            receiver_id: session.receiver_id.clone(),
+            state: state.to_string(),
+            error,
+            incoming: session.receiver_id == device_id,
+            pending,
+            files: session.files.clone(),
+            bytes_transferred: session.progress.bytes_transferred(),
+            total_bytes: session.progress.total_bytes(),
+            warnings: session.warnings.lock().await.clone(),
+            summary: session.summary.get().cloned().map(Box::new),
+        }
+    }
+}
+
+/// 发送文件的请求
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SendRequest {
+    /// 设备 ID、名称、IP 或主机名，`@组名` 表示向设备组中的所有设备发送
+    pub device: String,
+    /// 本机上待发送的文件
+    pub paths: Vec<PathBuf>,
+    /// 对方要求的 PIN，向设备组发送时不使用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
+}
+
+/// 向设备组中一台设备发送的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipientResult {
+    /// 组中配置的设备 ID、名称、IP 或主机名
+    pub device: String,
+    /// 发送的会话，未找到设备时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionView>,
+    /// 发送失败的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 发送失败的原因
+#[derive(Debug, thiserror::Error)]
+pub enum SendError {
+    #[error("没有要发送的文件")]
+    NoFiles,
+    #[error("未找到设备: {0}")]
+    DeviceNotFound(String),
+    #[error("未找到设备组: {0}")]
+    GroupNotFound(String),
+    #[error(transparent)]
+    Failed(#[from] ProtocolError),
+}
+
+/// 控制请求
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ControlRequest {
+    /// 确认守护进程在运行
+    Ping,
+    /// 已发现的设备
+    Devices,
+    /// 所有会话
+    Sessions,
+    /// 单个会话
+    Session { id: String },
+    /// 答复等待确认的请求
+    Respond { session_id: String, accept: bool },
+    /// 发送文件，传输结束后才回复
+    Send(SendRequest),
+    /// 审计日志中最近的 limit 条记录
+    History { limit: usize },
+    /// 在 secs 秒内自动接受所有请求
+    OpenGate { secs: u64 },
+    /// 提前结束临时开放接收
+    CloseGate,
+    /// 查询临时开放接收的状态
+    Gate,
+}
+
+/// 控制请求的回复
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ControlResponse {
+    Pong,
+    Devices {
+        devices: Vec<DeviceInfo>,
+    },
+    Sessions {
+        sessions: Vec<SessionView>,
+    },
+    Session {
+        session: SessionView,
+    },
+    /// 向设备组发送的结果，按组中设备的顺序
+    Recipients {
+        results: Vec<RecipientResult>,
+    },
+    /// 请求已完成，没有其他内容
+    Done,
+    History {
+        records: Vec<AuditRecord>,
+    },
+    /// 临时开放接收的剩余秒数，未开放时为空
+    Gate {
+        remaining: Option<u64>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// 守护进程的控制操作，控制通道和管理 API 共用
+///
+/// 克隆后操作同一组会话和设备
+#[derive(Debug, Clone)]
+pub struct Controller {
+    client: LocalSendClient,
+    sessions: Arc<Mutex<SessionManager>>,
+    discovery: Arc<Mutex<DiscoveryManager>>,
+    accept: AcceptGate,
+    audit_path: Option<PathBuf>,
+}
+
+impl Controller {
+    /// client 用于发送文件，应通过
+    /// [`with_session_manager`](LocalSendClient::with_session_manager) 与接收服务共用会话管理器
+    pub fn new(
+        client: LocalSendClient,
+        sessions: Arc<Mutex<SessionManager>>,
+        discovery: Arc<Mutex<DiscoveryManager>>,
+        accept: AcceptGate,
+    ) -> Self {
+        Self {
+            client,
+            sessions,
+            discovery,
+            accept,
+            audit_path: None,
+        }
+    }
+
+    /// 从该审计日志查询历史记录
+    pub fn with_audit_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.audit_path = Some(path.into());
+        self
+    }
+
+    /// 本机设备 ID
+    fn device_id(&self) -> &str {
+        &self.client.config().device_id
+    }
+
+    /// 已发现的设备
+    pub async fn devices(&self) -> Vec<DeviceInfo> {
+        let devices = self.discovery.lock().await.get_devices().await;
+        devices.iter().cloned().collect()
+    }
+
+    /// 所有会话
+    pub async fn sessions(&self) -> Vec<SessionView> {
+        let sessions = self.sessions.lock().await.get_all_sessions().await;
+        let pending = self.accept.pending();
+        let mut views = Vec::with_capacity(sessions.len());
+        for session in sessions.iter() {
+            let pending = pending.contains(&session.id);
+            views.push(SessionView::new(session, self.device_id(), pending).await);
+        }
+        views
+    }
+
+    /// 单个会话，不存在时返回 None
+    pub async fn session(&self, id: &str) -> Option<SessionView> {
+        let session = self.sessions.lock().await.get_session(id).await?;
+        let pending = self.accept.pending().iter().any(|p| p == id);
+        Some(SessionView::new(&session, self.device_id(), pending).await)
+    }
+
+    /// 订阅会话管理器所在事件总线上的事件
+    pub async fn subscribe(&self) -> broadcast::Receiver<ProtocolEvent> {
+        self.sessions.lock().await.events().subscribe()
+    }
+
+    /// 答复等待确认的请求，会话不在等待中时返回 false
+    pub fn respond(&self, session_id: &str, accept: bool) -> bool {
+        self.accept.respond(session_id, accept)
+    }
+
+    /// 发送文件，传输结束后返回会话
+    pub async fn send(&self, request: SendRequest) -> Result<SessionView, SendError> {
+        if request.paths.is_empty() {
+            return Err(SendError::NoFiles);
+        }
+        let device = self
+            .resolve_device(&request.device)
+            .await
+            .ok_or_else(|| SendError::DeviceNotFound(request.device.clone()))?;
+        if request.pin.is_some() {
+            self.client.set_pin(&device.id, request.pin);
+        }
+        let session = self.client.send_files(&device, &request.paths).await?;
+        Ok(SessionView::new(&session, self.device_id(), false).await)
+    }
+
+    /// 向设备组中的所有设备发送相同的文件，每个文件只读取一次，传输结束后返回各设备的结果
+    ///
+    /// 见 [`LocalSendClient::broadcast_files`]，单台设备未找到或失败不影响其他设备
+    pub async fn send_group(
+        &self,
+        group: &str,
+        paths: &[PathBuf],
+    ) -> Result<Vec<RecipientResult>, SendError> {
+        if paths.is_empty() {
+            return Err(SendError::NoFiles);
+        }
+        let members = self
+            .client
+            .config()
+            .groups
+            .get(group)
+            .cloned()
+            .ok_or_else(|| SendError::GroupNotFound(group.to_string()))?;
+
+        let mut results = Vec::with_capacity(members.len());
+        // 找到的设备及其在 results 中的位置
+        let mut found = Vec::new();
+        let mut devices = Vec::new();
+        for member in members {
+            let error = match self.resolve_device(&member).await {
+                Some(device) => {
+                    found.push(results.len());
+                    devices.push(device);
+                    None
+                }
+                None => Some(SendError::DeviceNotFound(member.clone()).to_string()),
+            };
+            results.push(RecipientResult {
+                device: member,
+                session: None,
+                error,
+            });
+        }
+        if devices.is_empty() {
+            return Ok(results);
+        }
+
+        let sent = self.client.broadcast_files(&devices, paths).await?;
+        for (i, sent) in found.into_iter().zip(sent) {
+            match sent {
+                Ok(session) => {
+                    let view = SessionView::new(&session, self.device_id(), false).await;
+                    results[i].session = Some(view);
+                }
+                Err(e) => results[i].error = Some(e.to_string()),
+            }
+        }
+        Ok(results)
+    }
+
+    /// 按设备 ID、名称、IP 或主机名查找已发现的设备，未被发现的地址直接探测
+    async fn resolve_device(&self, target: &str) -> Option<DeviceInfo> {
+        let devices = self.discovery.lock().await.get_devices().await;
+        if let Some(device) = devices
+            .iter()
+            .find(|d| d.id == target || d.name == target || d.ip == target)
+        {
+            return Some(device.clone());
+        }
+        if crate::net::is_host(target) {
+            return self.client.check_device(target).await;
+        }
+        None
+    }
+
+    /// 在 duration 内自动接受所有请求，仍会检查允许列表、PIN 和大小限制
+    ///
+    /// 时长为 0 或超过 [`MAX_OPEN_DURATION`] 时返回 [`ProtocolError::InvalidConfig`]
+    pub fn open_gate(&self, duration: Duration) -> crate::Result<()> {
+        if duration.is_zero() || duration > MAX_OPEN_DURATION {
+            return Err(ProtocolError::InvalidConfig(format!(
+                "开放接收的时长应在 1 秒到 {} 小时之间",
+                MAX_OPEN_DURATION.as_secs() / 3600
+            )));
+        }
+        info!(secs = duration.as_secs(), "临时开放接收，自动接受所有请求");
+        self.accept.open_for(duration);
+        Ok(())
+    }
+
+    /// 提前结束临时开放接收
+    pub fn close_gate(&self) {
+        if self.accept.is_open() {
+            info!("已结束临时开放接收");
+        }
+        self.accept.close();
+    }
+
+    /// 临时开放接收的剩余秒数，不足一秒按一秒计，未开放时返回 None
+    pub fn gate_remaining(&self) -> Option<u64> {
+        let remaining = self.accept.open_remaining()?;
+        Some(remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0))
+    }
+
+    /// 审计日志中最近的 limit 条记录，未启用审计日志时返回 None
+    pub fn history(&self, limit: usize) -> crate::Result<Option<Vec<AuditRecord>>> {
+        let Some(path) = &self.audit_path else {
+            return Ok(None);
+        };
+        let mut records = audit::records(path)?;
+        let skip = records.len().saturating_sub(limit);
+        records.drain(..skip);
+        Ok(Some(records))
+    }
+
+    /// 执行控制请求
+    pub async fn handle(&self, request: ControlRequest) -> ControlResponse {
+        let error = |message: String| ControlResponse::Error { message };
+        match request {
+            ControlRequest::Ping => ControlResponse::Pong,
+            ControlRequest::Devices => ControlResponse::Devices {
+                devices: self.devices().await,
+            },
+            ControlRequest::Sessions => ControlResponse::Sessions {
+                sessions: self.sessions().await,
+            },
+            ControlRequest::Session { id } => match self.session(&id).await {
+                Some(session) => ControlResponse::Session { session },
+                None => error(format!("会话不存在: {}", id)),
+            },
+            ControlRequest::Respond { session_id, accept } => {
+                if self.respond(&session_id, accept) {
+                    ControlResponse::Done
+                } else {
+                    error(format!("会话不在等待确认: {}", session_id))
+                }
+            }
+            ControlRequest::Send(request) => match group_name(&request.device) {
+                Some(group) => match self.send_group(group, &request.paths).await {
+                    Ok(results) => ControlResponse::Recipients { results },
+                    Err(e) => error(e.to_string()),
+                },
+                None => match self.send(request).await {
+                    Ok(session) => ControlResponse::Session { session },
+                    Err(e) => error(e.to_string()),
+                },
+            },
+            ControlRequest::History { limit } => match self.history(limit) {
+                Ok(Some(records)) => ControlResponse::History { records },
+                Ok(None) => error("未启用审计日志".to_string()),
+                Err(e) => error(e.to_string()),
+            },
+            ControlRequest::OpenGate { secs } => match self.open_gate(Duration::from_secs(secs)) {
+                Ok(()) => self.gate_status(),
+                Err(e) => error(e.to_string()),
+            },
+            ControlRequest::CloseGate => {
+                self.close_gate();
+                self.gate_status()
+            }
+            ControlRequest::Gate => self.gate_status(),
+        }
+    }
+
+    fn gate_status(&self) -> ControlResponse {
+        ControlResponse::Gate {
+            remaining: self.gate_remaining(),
+        }
+    }
+}
+
+/// 默认的控制通道地址
+///
+/// Unix 上优先使用 `$XDG_RUNTIME_DIR/peersend/control.sock`，否则为配置目录下的
+/// `run/control.sock`；Windows 上为按用户区分的命名管道
+pub fn default_path() -> Option<PathBuf> {
+    if cfg!(windows) {
+        let user = std::env::var("USERNAME").unwrap_or_default();
+        return Some(PathBuf::from(format!(
+            r"\\.\pipe\peersend-control-{}",
+            user
+        )));
+    }
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join("peersend"))
+        .or_else(|| Some(LocalSendConfig::default_path()?.with_file_name("run")))?;
+    Some(dir.join("control.sock"))
+}
+
+/// 在 path 上提供控制通道，直到 cancel 取消
+///
+/// path 上已有其他守护进程在监听时返回错误，残留的套接字文件会被替换，退出时删除套接字文件
+pub async fn serve(
+    controller: Controller,
+    path: &Path,
+    cancel: CancellationToken,
+) -> crate::Result<()> {
+    listen(&Arc::new(controller), path, &cancel).await
+}
+
+#[cfg(unix)]
+async fn listen(
+    controller: &Arc<Controller>,
+    path: &Path,
+    cancel: &CancellationToken,
+) -> crate::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+        // 目录仅当前用户可访问，其他用户无法连接其中的套接字
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    if UnixStream::connect(path).await.is_ok() {
+        return Err(ProtocolError::InvalidConfig(format!(
+            "控制通道已被其他进程占用: {}",
+            path.display()
+        )));
+    }
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    let listener = UnixListener::bind(path)?;
+    let _socket = SocketFile(path);
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    info!(path = %path.display(), "控制通道已启动");
+
+    loop {
+        let stream = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!(error = %e, "控制通道接受连接失败");
+                    continue;
+                }
+            },
+        };
+        tokio::spawn(serve_connection(stream, controller.clone(), cancel.clone()));
+    }
+}
+
+/// 退出时删除监听的套接字文件
+#[cfg(unix)]
+struct SocketFile<'a>(&'a Path);
+
+#[cfg(unix)]
+impl Drop for SocketFile<'_> {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(self.0);
+    }
+}
+
+#[cfg(windows)]
+async fn listen(
+    controller: &Arc<Controller>,
+    path: &Path,
+    cancel: &CancellationToken,
+) -> crate::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    // 管道名已存在时创建失败，说明有其他守护进程在监听
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(path)
+        .map_err(|e| {
+            ProtocolError::InvalidConfig(format!(
+                "控制通道已被其他进程占用: {} ({})",
+                path.display(),
+                e
+            ))
+        })?;
+    info!(path = %path.display(), "控制通道已启动");
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            connected = server.connect() => connected?,
+        }
+        // 先创建下一个实例再处理当前连接，避免客户端连接时管道不存在
+        let connected = std::mem::replace(&mut server, ServerOptions::new().create(path)?);
+        tokio::spawn(serve_connection(
+            connected,
+            controller.clone(),
+            cancel.clone(),
+        ));
+    }
+}
+
+/// 逐行处理一个连接上的请求，直到对方关闭或 cancel 取消
+async fn serve_connection<S>(stream: S, controller: Arc<Controller>, cancel: CancellationToken)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut line = String::new();
+        let mut limited = (&mut reader).take(MAX_LINE_LEN);
+        let read = tokio::select! {
+            _ = cancel.cancelled() => return,
+            read = limited.read_line(&mut line) => read,
+        };
+        match read {
+            Ok(0) => return,
+            Ok(_) if !line.ends_with('\n') => {
+                debug!("控制请求过长或不完整，关闭连接");
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                debug!(error = %e, "读取控制请求失败");
+                return;
+            }
+        }
+
+        let response = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => controller.handle(request).await,
+            Err(e) => ControlResponse::Error {
+                message: format!("无效的请求: {}", e),
+            },
+        };
+        let Ok(mut data) = serde_json::to_vec(&response) else {
+            return;
+        };
+        data.push(b'\n');
+        if writer.write_all(&data).await.is_err() || writer.flush().await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(unix)]
+type ClientStream = tokio::net::UnixStream;
+
+#[cfg(windows)]
+type ClientStream = tokio::net::windows::named_pipe::NamedPipeClient;
+
+/// 控制通道客户端
+#[derive(Debug)]
+pub struct ControlClient {
+    stream: BufStream<ClientStream>,
+}
+
+impl ControlClient {
+    /// 连接 path 上的控制通道
+    pub async fn connect(path: &Path) -> crate::Result<Self> {
+        #[cfg(unix)]
+        let stream = tokio::net::UnixStream::connect(path).await?;
+        #[cfg(windows)]
+        let stream = tokio::net::windows::named_pipe::ClientOptions::new().open(path)?;
+        Ok(Self {
+            stream: BufStream::new(stream),
+        })
+    }
+
+    /// 连接默认位置的控制通道
+    pub async fn connect_default() -> crate::Result<Self> {
+        let path = default_path()
+            .ok_or_else(|| ProtocolError::InvalidConfig("无法确定控制通道地址".to_string()))?;
+        Self::connect(&path).await
+    }
+
+    /// 发送请求并等待回复
+    pub async fn request(&mut self, request: &ControlRequest) -> crate::Result<ControlResponse> {
+        let mut data = serde_json::to_vec(request)?;
+        data.push(b'\n');
+        self.stream.write_all(&data).await?;
+        self.stream.flush().await?;
+
+        let mut line = String::new();
+        if self.stream.read_line(&mut line).await? == 0 {
+            return Err(ProtocolError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "控制通道已关闭",
+            )));
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
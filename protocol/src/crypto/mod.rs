@@ -9,6 +9,10 @@ use rand::Rng;
 use sha2::{Sha256, Digest};
 use base64::{Engine as _, engine::general_purpose::STANDARD};
 use zeroize::Zeroize;
+use crate::ProtocolError;
+
+#[cfg(feature = "tls")]
+pub mod tls;
 
 /// 生成随机密钥
 pub fn generate_key() -> [u8; 32] {
@@ -33,15 +37,15 @@ pub fn compute_fingerprint(key: &[u8]) -> String {
 }
 
 /// 加密数据
-pub fn encrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>, String> {
+pub fn encrypt(data: &[u8], key: &[u8]) -> crate::Result<Vec<u8>> {
     let cipher = Aes256Gcm::new_from_slice(key)
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| ProtocolError::Crypto(e.to_string()))?;
 
     let iv = generate_iv();
     let nonce = Nonce::from_slice(&iv);
 
     let ciphertext = cipher.encrypt(nonce, data)
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| ProtocolError::Crypto(e.to_string()))?;
 
     let mut result = iv.to_vec();
     result.extend_from_slice(&ciphertext);
@@ -50,13 +54,13 @@ pub fn encrypt(data: &[u8], key: &[u8]) -> Result<Vec<u8>, String> {
 }
 
 /// 解密数据
-pub fn decrypt(encrypted: &[u8], key: &[u8]) -> Result<Vec<u8>, String> {
+pub fn decrypt(encrypted: &[u8], key: &[u8]) -> crate::Result<Vec<u8>> {
     if encrypted.len() < 12 {
-        return Err("加密数据太短".to_string());
+        return Err(ProtocolError::Crypto("加密数据太短".to_string()));
     }
 
     let cipher = Aes256Gcm::new_from_slice(key)
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| ProtocolError::Crypto(e.to_string()))?;
 
     let iv = &encrypted[..12];
     let ciphertext = &encrypted[12..];
@@ -64,7 +68,7 @@ pub fn decrypt(encrypted: &[u8], key: &[u8]) -> Result<Vec<u8>, String> {
     let nonce = Nonce::from_slice(iv);
 
     cipher.decrypt(nonce, ciphertext)
-        .map_err(|e| e.to_string())
+        .map_err(|e| ProtocolError::Crypto(e.to_string()))
 }
 
 /// HMAC 签名
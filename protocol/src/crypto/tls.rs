@@ -0,0 +1,118 @@
+//! 接收服务的 TLS 证书
+//!
+//! 与官方 LocalSend 一样使用自签名证书，对方通过公告中的指纹 (证书 DER 的 SHA-256)
+//! 校验证书。证书和私钥以 PEM 保存在配置目录，重启后指纹保持不变
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::{LocalSendConfig, ProtocolError};
+
+/// 证书文件名
+pub const CERTIFICATE_FILE_NAME: &str = "tls-cert.pem";
+/// 私钥文件名
+pub const PRIVATE_KEY_FILE_NAME: &str = "tls-key.pem";
+/// 生成证书时使用的名称，对方不校验主机名
+const SUBJECT_NAME: &str = "localsend";
+
+/// 自签名证书和私钥
+#[derive(Clone)]
+pub struct TlsIdentity {
+    certificate: String,
+    private_key: String,
+    fingerprint: String,
+}
+
+impl TlsIdentity {
+    /// 生成新的自签名证书
+    pub fn generate() -> crate::Result<Self> {
+        let cert = rcgen::generate_simple_self_signed(vec![SUBJECT_NAME.to_string()])
+            .map_err(|e| ProtocolError::Crypto(e.to_string()))?;
+        Ok(Self {
+            certificate: cert.cert.pem(),
+            private_key: cert.key_pair.serialize_pem(),
+            fingerprint: crate::hash::sha256_hex(cert.cert.der()),
+        })
+    }
+
+    /// 从 PEM 格式的证书和 PKCS#8 私钥创建
+    pub fn from_pem(certificate: &str, private_key: &str) -> crate::Result<Self> {
+        let der = native_tls::Certificate::from_pem(certificate.as_bytes())
+            .and_then(|cert| cert.to_der())
+            .map_err(tls_error)?;
+        let identity = Self {
+            certificate: certificate.to_string(),
+            private_key: private_key.to_string(),
+            fingerprint: crate::hash::sha256_hex(&der),
+        };
+        // 提前检查证书和私钥能否用于 TLS
+        identity.acceptor()?;
+        Ok(identity)
+    }
+
+    /// 从 dir 加载证书和私钥
+    pub fn load(dir: &Path) -> crate::Result<Self> {
+        let certificate = std::fs::read_to_string(dir.join(CERTIFICATE_FILE_NAME))?;
+        let private_key = std::fs::read_to_string(dir.join(PRIVATE_KEY_FILE_NAME))?;
+        Self::from_pem(&certificate, &private_key)
+    }
+
+    /// 保存到 dir，私钥仅允许当前用户读取
+    pub fn save(&self, dir: &Path) -> crate::Result<()> {
+        crate::config::write_private(
+            &dir.join(PRIVATE_KEY_FILE_NAME),
+            self.private_key.as_bytes(),
+        )?;
+        std::fs::write(dir.join(CERTIFICATE_FILE_NAME), &self.certificate)?;
+        Ok(())
+    }
+
+    /// 加载 dir 中的证书，不存在时生成并保存
+    pub fn load_or_generate(dir: &Path) -> crate::Result<Self> {
+        if dir.join(CERTIFICATE_FILE_NAME).exists() && dir.join(PRIVATE_KEY_FILE_NAME).exists() {
+            return Self::load(dir);
+        }
+        let identity = Self::generate()?;
+        identity.save(dir)?;
+        tracing::info!(fingerprint = %identity.fingerprint, "已生成 TLS 证书");
+        Ok(identity)
+    }
+
+    /// PEM 格式的证书
+    pub fn certificate_pem(&self) -> &str {
+        &self.certificate
+    }
+
+    /// 证书指纹，十六进制小写的 SHA-256，与 LocalSend 公告中的 `fingerprint` 相同
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// 用于接收服务的 TLS 接受器
+    pub fn acceptor(&self) -> crate::Result<tokio_native_tls::TlsAcceptor> {
+        let identity = native_tls::Identity::from_pkcs8(
+            self.certificate.as_bytes(),
+            self.private_key.as_bytes(),
+        )
+        .map_err(tls_error)?;
+        let acceptor = native_tls::TlsAcceptor::new(identity).map_err(tls_error)?;
+        Ok(acceptor.into())
+    }
+}
+
+impl fmt::Debug for TlsIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsIdentity")
+            .field("fingerprint", &self.fingerprint)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 证书的默认保存目录，即共享配置文件所在目录
+pub fn default_dir() -> Option<PathBuf> {
+    Some(LocalSendConfig::default_path()?.parent()?.to_path_buf())
+}
+
+fn tls_error(e: native_tls::Error) -> ProtocolError {
+    ProtocolError::Crypto(e.to_string())
+}
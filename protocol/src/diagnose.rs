@@ -0,0 +1,328 @@
+//! 设备连接诊断
+//!
+//! [`diagnose`] 对单个设备依次检查:
+//!
+//! 1. 设备信息: 先请求 LocalSend v2 的 `/info`，对方不支持时改用 v1 的注册接口，
+//!    首次联系的设备先尝试 HTTPS 再尝试 HTTP
+//! 2. 延迟: 重复请求设备信息 [`PROBE_COUNT`] 次，记录最短、平均和最长的往返时间
+//! 3. 证书: 使用 HTTPS 时重新握手取得证书的 SHA-256，与设备公告的指纹比较
+//! 4. 路径: 查询发往设备时系统选用的本机地址，由 [`classify`] 判断经局域网、
+//!    虚拟网络隧道还是经其他节点中转
+//!
+//! 各项检查互不影响，失败的原因记入报告的 `errors`，结果可直接序列化给界面显示
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dto::v2::{InfoDto, INFO_PATH};
+use crate::dto::RegisterResponse;
+use crate::http::{HttpClient, HttpClientConfig, HttpRequest, ReqwestClient, Scheme};
+use crate::{net, DeviceInfo, ProtocolError};
+
+/// 测量延迟的请求次数，包括取得设备信息的那一次
+pub const PROBE_COUNT: usize = 3;
+
+/// 单次请求的超时
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// v1 注册接口，GET 请求返回设备信息
+const REGISTER_V1_PATH: &str = "/api/v1/localsend/register";
+
+/// 到设备的网络路径
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NetworkPath {
+    /// 直接经本机网卡到达 (局域网或同一主机)
+    Lan,
+    /// 经虚拟网络隧道直接连接对方节点
+    Tunnel,
+    /// 经虚拟网络中的其他节点中转
+    Relay,
+    /// 系统没有到设备的路由
+    Unknown,
+}
+
+/// 证书检查的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TlsStatus {
+    /// 证书指纹与设备公告的指纹一致
+    Verified,
+    /// 证书指纹与设备公告的指纹不一致
+    Mismatch,
+    /// 使用 HTTPS，但设备没有公告可比较的指纹 (如 v1 设备)
+    Unverified,
+    /// 设备只提供 HTTP
+    Plain,
+    /// HTTPS 握手失败
+    Failed,
+}
+
+/// 证书检查
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsCheck {
+    pub status: TlsStatus,
+    /// 对方出示的证书的 SHA-256
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<String>,
+    /// 设备公告的指纹
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub announced: Option<String>,
+}
+
+/// 往返时间，毫秒
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Latency {
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    /// 成功的请求数
+    pub samples: usize,
+}
+
+/// 设备信息接口的应答
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbedInfo {
+    /// 应答的接口版本，`v2` 或 `v1`
+    pub api: String,
+    pub alias: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_type: Option<String>,
+    /// v2 设备公告的证书指纹
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    pub download: bool,
+}
+
+impl From<InfoDto> for ProbedInfo {
+    fn from(info: InfoDto) -> Self {
+        Self {
+            api: "v2".to_string(),
+            alias: info.alias,
+            version: info.version,
+            device_model: info.device_model,
+            device_type: info.device_type,
+            fingerprint: Some(info.fingerprint).filter(|f| !f.is_empty()),
+            download: info.download,
+        }
+    }
+}
+
+impl From<RegisterResponse> for ProbedInfo {
+    fn from(info: RegisterResponse) -> Self {
+        Self {
+            api: "v1".to_string(),
+            alias: info.name,
+            version: info.protocol_version,
+            device_model: None,
+            device_type: Some(info.device_type),
+            fingerprint: None,
+            download: info.download,
+        }
+    }
+}
+
+/// 设备诊断报告
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceReport {
+    pub device_id: String,
+    pub name: String,
+    /// 检查的地址，`主机:端口`
+    pub address: String,
+    /// 设备信息，设备无应答时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<ProbedInfo>,
+    /// 应答所用的协议，`https` 或 `http`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsCheck>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<Latency>,
+    /// 发往设备时使用的本机地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_address: Option<String>,
+    pub path: NetworkPath,
+    /// 检查中发现的问题
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+impl DeviceReport {
+    /// 设备是否应答了设备信息请求
+    pub fn reachable(&self) -> bool {
+        self.info.is_some()
+    }
+}
+
+/// 按发往设备时使用的本机地址判断路径
+///
+/// tunnel_ips 为本机在 EasyTier 等虚拟网络中的地址；relayed 为虚拟网络中到对方节点
+/// 是否需经其他节点中转，未知时视为直接连接
+pub fn classify(
+    local: Option<IpAddr>,
+    tunnel_ips: &[IpAddr],
+    relayed: Option<bool>,
+) -> NetworkPath {
+    match local {
+        None => NetworkPath::Unknown,
+        Some(ip) if tunnel_ips.contains(&ip) => match relayed {
+            Some(true) => NetworkPath::Relay,
+            _ => NetworkPath::Tunnel,
+        },
+        Some(_) => NetworkPath::Lan,
+    }
+}
+
+/// 诊断设备
+///
+/// scheme 为此前与设备通信成功的协议，为空时先尝试 HTTPS 再尝试 HTTP。
+/// 报告的路径只区分局域网和无路由，虚拟网络的判断需由调用方按 [`classify`] 补充
+pub async fn diagnose(
+    http: &dyn HttpClient,
+    device: &DeviceInfo,
+    scheme: Option<Scheme>,
+) -> DeviceReport {
+    let address = net::host_port(&device.ip, device.port);
+    let local = net::parse_ip(&device.ip).and_then(net::route_source);
+    let mut report = DeviceReport {
+        device_id: device.id.clone(),
+        name: device.name.clone(),
+        address: address.clone(),
+        info: None,
+        scheme: None,
+        tls: None,
+        latency: None,
+        local_address: local.map(|ip| ip.to_string()),
+        path: classify(local, &[], None),
+        errors: Vec::new(),
+    };
+    if report.path == NetworkPath::Unknown {
+        report.errors.push("没有到设备的路由".to_string());
+    }
+
+    let schemes = match scheme {
+        Some(scheme) => vec![scheme],
+        None => vec![Scheme::Https, Scheme::Http],
+    };
+    let mut found = None;
+    let mut failures = Vec::new();
+    for scheme in schemes {
+        match probe_info(http, &address, scheme).await {
+            Ok((info, path, elapsed)) => {
+                found = Some((scheme, info, path, elapsed));
+                break;
+            }
+            Err(e) => failures.push(format!("{} 请求设备信息失败: {}", scheme.as_str(), e)),
+        }
+    }
+    // 有应答时之前的失败只说明不支持该协议
+    let Some((scheme, info, path, first)) = found else {
+        report.errors.extend(failures);
+        return report;
+    };
+
+    let url = format!("{}://{}{}", scheme.as_str(), address, path);
+    let mut samples = vec![first];
+    for _ in 1..PROBE_COUNT {
+        let started = Instant::now();
+        match http.execute(HttpRequest::get(&url).timeout(PROBE_TIMEOUT)).await {
+            Ok(response) if response.status == 200 => samples.push(started.elapsed()),
+            Ok(response) => {
+                report.errors.push(format!("测量延迟时设备返回状态码 {}", response.status))
+            }
+            Err(e) => report.errors.push(format!("测量延迟失败: {}", e)),
+        }
+    }
+    report.latency = Some(latency(&samples));
+
+    let announced = info.fingerprint.clone();
+    report.tls = Some(match scheme {
+        Scheme::Http => TlsCheck {
+            status: TlsStatus::Plain,
+            certificate: None,
+            announced,
+        },
+        Scheme::Https => match certificate(&address, path).await {
+            Ok(certificate) => {
+                let status = match &announced {
+                    Some(a) if a.eq_ignore_ascii_case(&certificate) => TlsStatus::Verified,
+                    Some(_) => TlsStatus::Mismatch,
+                    None => TlsStatus::Unverified,
+                };
+                if status == TlsStatus::Mismatch {
+                    report.errors.push("证书与设备公告的指纹不一致".to_string());
+                }
+                TlsCheck {
+                    status,
+                    certificate: Some(certificate),
+                    announced,
+                }
+            }
+            Err(e) => {
+                report.errors.push(format!("检查证书失败: {}", e));
+                TlsCheck {
+                    status: TlsStatus::Failed,
+                    certificate: None,
+                    announced,
+                }
+            }
+        },
+    });
+    report.scheme = Some(scheme.as_str().to_string());
+    report.info = Some(info);
+    report
+}
+
+/// 请求设备信息，v2 接口不存在时改用 v1，返回应答、所用路径和往返时间
+async fn probe_info(
+    http: &dyn HttpClient,
+    address: &str,
+    scheme: Scheme,
+) -> crate::Result<(ProbedInfo, &'static str, Duration)> {
+    let get = |path: &str| {
+        let url = format!("{}://{}{}", scheme.as_str(), address, path);
+        HttpRequest::get(url).timeout(PROBE_TIMEOUT)
+    };
+    let started = Instant::now();
+    let response = http.execute(get(INFO_PATH)).await?;
+    if response.status != 404 {
+        let info: InfoDto = response.error_for_status()?.json()?;
+        return Ok((info.into(), INFO_PATH, started.elapsed()));
+    }
+    let started = Instant::now();
+    let response = http.execute(get(REGISTER_V1_PATH)).await?;
+    let info: RegisterResponse = response.error_for_status()?.json()?;
+    Ok((info.into(), REGISTER_V1_PATH, started.elapsed()))
+}
+
+/// 经 HTTPS 重新握手，返回对方证书的 SHA-256
+async fn certificate(address: &str, path: &str) -> crate::Result<String> {
+    let config = HttpClientConfig::default()
+        .with_connect_timeout(PROBE_TIMEOUT)
+        .with_timeout(PROBE_TIMEOUT);
+    let client = ReqwestClient::with_config(&config)?;
+    client.execute(HttpRequest::get(format!("https://{}{}", address, path))).await?;
+    client
+        .pinned(address)
+        .ok_or_else(|| ProtocolError::Crypto("对方没有出示证书".to_string()))
+}
+
+fn latency(samples: &[Duration]) -> Latency {
+    let ms: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+    Latency {
+        min_ms: ms.iter().copied().fold(f64::INFINITY, f64::min),
+        avg_ms: ms.iter().sum::<f64>() / ms.len() as f64,
+        max_ms: ms.iter().copied().fold(0.0, f64::max),
+        samples: ms.len(),
+    }
+}
@@ -2,13 +2,22 @@
 //!
 //! 实现 LocalSend 协议的设备发现功能
 //! 包括 UDP 多播发现和 HTTP 扫描发现
+//!
+//! 经 EasyTier TUN 等虚拟网卡收到的公告，来源地址不一定是对方可达的地址。
+//! PeerSend 在公告中附上候选地址 (见 [`candidate_addresses`])，
+//! 发送方按顺序探测后选用可达的地址
 
 use std::net::{UdpSocket, SocketAddr, Ipv4Addr};
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tokio::time::{interval, Duration};
+use tokio::time::{interval, Duration, Instant};
+use tokio_util::sync::CancellationToken;
 use serde_json;
-use crate::{DeviceInfo, LocalSendConfig, DiscoveryManager, AnnouncementMessage, PROTOCOL_VERSION};
+use tracing::{debug, error, instrument, warn};
+use crate::http::{HttpClientRef, HttpRequest, ReqwestClient};
+use crate::metered::Metered;
+use crate::{DeviceInfo, DeviceList, LocalSendConfig, DiscoveryManager, AnnouncementMessage, PROTOCOL_VERSION};
+use crate::ProtocolError;
 
 /// 发现管理器引用类型
 pub type DiscoveryManagerRef = Arc<Mutex<DiscoveryManager>>;
@@ -17,12 +26,48 @@ pub type DiscoveryManagerRef = Arc<Mutex<DiscoveryManager>>;
 const MULTICAST_ADDR: &str = "224.0.0.115";
 const MULTICAST_PORT: u16 = 53317;
 
+/// 公告间隔，省流模式下按配置延长
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 本地网络访问权限的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalNetworkAccess {
+    /// 已授权，或所在平台不需要授权
+    Granted,
+    /// macOS 拒绝了本地网络访问
+    Denied,
+    /// 探测失败，无法判断，例如没有可用的网络
+    Unknown,
+}
+
+/// 探测本地网络访问权限
+///
+/// 向多播组发送一个空数据包：macOS 上尚未决定时会弹出授权提示，已拒绝时发送失败。
+/// 空数据包不是合法公告，其他设备会忽略。其他平台不需要授权，直接返回
+/// [`LocalNetworkAccess::Granted`]
+pub fn local_network_access() -> LocalNetworkAccess {
+    if !cfg!(target_os = "macos") {
+        return LocalNetworkAccess::Granted;
+    }
+    let addr: SocketAddr = format!("{}:{}", MULTICAST_ADDR, MULTICAST_PORT).parse().unwrap();
+    let sent = UdpSocket::bind("0.0.0.0:0").and_then(|socket| socket.send_to(&[], addr));
+    match sent {
+        Ok(_) => LocalNetworkAccess::Granted,
+        Err(e) if crate::net::is_local_network_denied(&e) => LocalNetworkAccess::Denied,
+        Err(e) => {
+            debug!(error = %e, "探测本地网络权限失败");
+            LocalNetworkAccess::Unknown
+        }
+    }
+}
+
 /// UDP 发现器
 #[derive(Debug)]
 pub struct UdpDiscoverer {
     config: LocalSendConfig,
     manager: DiscoveryManagerRef,
     socket: Arc<UdpSocket>,
+    metered: Metered,
 }
 
 impl UdpDiscoverer {
@@ -35,11 +80,18 @@ impl UdpDiscoverer {
             config,
             manager,
             socket,
+            metered: Metered::default(),
         }
     }
 
+    /// 省流状态下按 metered 的间隔发送公告
+    pub fn with_metered(mut self, metered: Metered) -> Self {
+        self.metered = metered;
+        self
+    }
+
     /// 发送公告
-    pub async fn send_announcement(&self) -> Result<(), std::io::Error> {
+    pub async fn send_announcement(&self) -> crate::Result<()> {
         let announcement = AnnouncementMessage {
             msg_type: "announce".to_string(),
             id: self.config.device_id.clone(),
@@ -50,31 +102,46 @@ impl UdpDiscoverer {
             download: true,
             port: Some(self.config.port),
             announcement_id: None,
-            uses_password: false,
+            uses_password: self.config.uses_pin(),
+            transports: self.config.transports.clone(),
+            addresses: candidate_addresses(&self.config),
         };
 
         let msg = serde_json::to_string(&announcement)?;
         let addr: SocketAddr = format!("{}:{}", MULTICAST_ADDR, MULTICAST_PORT).parse().unwrap();
 
-        let written = self.socket.send_to(msg.as_bytes(), addr)?;
+        let written = self
+            .socket
+            .send_to(msg.as_bytes(), addr)
+            .map_err(crate::net::local_network_error)?;
         if written != msg.len() {
-            eprintln!("警告: 公告未完全发送");
+            warn!(written, total = msg.len(), "公告未完全发送");
         }
 
         Ok(())
     }
 
     /// 开始发现 (发送和接收)
-    pub async fn start_discovery(&self) -> Result<(), std::io::Error> {
+    ///
+    /// 持续运行直到 cancel 被取消
+    #[instrument(skip(self, cancel), fields(device_id = %self.config.device_id))]
+    pub async fn start_discovery(&self, cancel: CancellationToken) -> crate::Result<()> {
         // 创建一个任务来接收公告
-        let socket = self.socket.clone();
+        let std_socket = self.socket.try_clone()?;
+        std_socket.set_nonblocking(true)?;
+        let socket = tokio::net::UdpSocket::from_std(std_socket)?;
         let manager = self.manager.clone();
         let config = self.config.clone();
+        let recv_cancel = cancel.clone();
 
-        let _ = tokio::spawn(async move {
+        tokio::spawn(async move {
             let mut buf = [0u8; 2048];
             loop {
-                match socket.recv_from(&mut buf) {
+                let received = tokio::select! {
+                    _ = recv_cancel.cancelled() => break,
+                    received = socket.recv_from(&mut buf) => received,
+                };
+                match received {
                     Ok((len, addr)) => {
                         if let Ok(data) = std::str::from_utf8(&buf[..len]) {
                             if let Ok(msg) = serde_json::from_str::<AnnouncementMessage>(data) {
@@ -89,81 +156,108 @@ impl UdpDiscoverer {
                                         protocol_version: msg.protocol_version,
                                         announcement_id: msg.announcement_id.unwrap_or_default(),
                                         uses_password: msg.uses_password,
+                                        transports: msg.transports,
+                                        addresses: msg.addresses,
                                     };
 
+                                    if !config.allows_device(&device.id, &device.ip) {
+                                        debug!(peer = %addr, device_id = %device.id, "忽略被屏蔽设备的公告");
+                                        continue;
+                                    }
+
                                     let m = manager.lock().await;
+                                    if !m.admit(&device.ip, &device.id).await {
+                                        continue;
+                                    }
+                                    debug!(peer = %addr, device_id = %device.id, name = %device.name, "收到设备公告");
                                     m.add_device(device).await;
                                 }
                             }
                         }
                     }
                     Err(e) => {
-                        eprintln!("接收公告失败: {}", e);
+                        warn!(error = %e, "接收公告失败");
                     }
                 }
             }
         });
 
         // 定期发送公告
-        let mut interval = interval(Duration::from_millis(5000));
+        let mut interval = interval(ANNOUNCE_INTERVAL);
+        let mut denied = false;
+        let mut last: Option<Instant> = None;
         loop {
-            interval.tick().await;
-            if let Err(e) = self.send_announcement().await {
-                eprintln!("发送公告失败: {}", e);
+            let tick = tokio::select! {
+                _ = cancel.cancelled() => break,
+                tick = interval.tick() => tick,
+            };
+            // 省流模式下跳过间隔未到的公告，退出后立即恢复正常间隔
+            let wait = self.metered.announce_interval(ANNOUNCE_INTERVAL);
+            if last.is_some_and(|last| tick.duration_since(last) < wait) {
+                continue;
+            }
+            last = Some(tick);
+            match self.send_announcement().await {
+                Ok(()) => denied = false,
+                // 权限被拒绝时每次都会失败，只提示一次
+                Err(ProtocolError::LocalNetworkDenied) if denied => {}
+                Err(e) => {
+                    denied = matches!(e, ProtocolError::LocalNetworkDenied);
+                    warn!(error = %e, "发送公告失败");
+                }
             }
         }
+
+        debug!("设备发现已停止");
+        Ok(())
     }
 }
 
+/// 单个地址探测的超时
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// HTTP 发现器
 #[derive(Debug)]
 pub struct HttpDiscoverer {
     config: LocalSendConfig,
     manager: DiscoveryManagerRef,
+    http: HttpClientRef,
 }
 
 impl HttpDiscoverer {
-    /// 创建新的 HTTP 发现器
-    pub fn new(config: LocalSendConfig, manager: DiscoveryManagerRef) -> Self {
-        Self { config, manager }
+    /// 创建新的 HTTP 发现器，所有探测共用 http 的连接池
+    pub fn new(config: LocalSendConfig, manager: DiscoveryManagerRef, http: HttpClientRef) -> Self {
+        Self { config, manager, http }
     }
 
     /// 扫描 IP 范围
-    pub async fn scan_range(&self, base_ip: &str, range: u8) -> Result<(), std::io::Error> {
+    ///
+    /// 未发现任何设备且 macOS 拒绝了本地网络访问时返回
+    /// [`ProtocolError::LocalNetworkDenied`]，此时探测会全部静默失败
+    #[instrument(skip(self))]
+    pub async fn scan_range(&self, base_ip: &str, range: u8) -> crate::Result<()> {
         let parts: Vec<u8> = base_ip.split('.').map(|s| s.parse().unwrap_or(0)).collect();
         if parts.len() != 4 {
             return Ok(());
         }
 
         let mut handles = Vec::new();
+        let found = Arc::new(std::sync::atomic::AtomicBool::new(false));
 
         for i in 1..=range {
             let ip = format!("{}.{}.{}.{}", parts[0], parts[1], parts[2], parts[3] + i);
-            let port = self.config.port;
+            let config = self.config.clone();
             let manager = self.manager.clone();
+            let http = self.http.clone();
+            let found = found.clone();
 
             let handle = tokio::spawn(async move {
-                let addr = format!("http://{}:{}/api/v1/localsend/register", ip, port);
-
-                let client = reqwest::Client::new();
-                if let Ok(response) = client.get(&addr).send().await {
-                    if let Ok(text) = response.text().await {
-                        if let Ok(device) = serde_json::from_str::<crate::dto::RegisterResponse>(&text) {
-                            let info = DeviceInfo {
-                                id: device.id,
-                                name: device.name,
-                                device_type: device.device_type,
-                                ip,
-                                port: device.port.unwrap_or(port),
-                                version: device.version,
-                                protocol_version: device.protocol_version,
-                                announcement_id: device.announcement_id.unwrap_or_default(),
-                                uses_password: device.uses_password,
-                            };
-
-                            let m = manager.lock().await;
-                            m.add_device(info).await;
-                        }
+                if let Some(info) = probe(&config, &http, ip).await {
+                    found.store(true, std::sync::atomic::Ordering::Relaxed);
+                    let m = manager.lock().await;
+                    if m.admit(&info.ip, &info.id).await {
+                        debug!(peer = %info.ip, device_id = %info.id, "HTTP 扫描发现设备");
+                        m.add_device(info).await;
                     }
                 }
             });
@@ -176,36 +270,79 @@ impl HttpDiscoverer {
             let _ = handle.await;
         }
 
+        if !found.load(std::sync::atomic::Ordering::Relaxed)
+            && local_network_access() == LocalNetworkAccess::Denied
+        {
+            return Err(ProtocolError::LocalNetworkDenied);
+        }
         Ok(())
     }
 
-    /// 检查特定 IP 是否运行 LocalSend
-    pub async fn check_device(&self, ip: &str) -> Option<DeviceInfo> {
-        let addr = format!("http://{}:{}/api/v1/localsend/register", ip, self.config.port);
-        let client = reqwest::Client::new();
-
-        if let Ok(response) = client.get(&addr).send().await {
-            if let Ok(text) = response.text().await {
-                if let Ok(device) = serde_json::from_str::<crate::dto::RegisterResponse>(&text) {
-                    return Some(DeviceInfo {
-                        id: device.id,
-                        name: device.name,
-                        device_type: device.device_type,
-                        ip: ip.to_string(),
-                        port: device.port.unwrap_or(self.config.port),
-                        version: device.version,
-                        protocol_version: device.protocol_version,
-                        announcement_id: device.announcement_id.unwrap_or_default(),
-                        uses_password: device.uses_password,
-                    });
-                }
+    /// 检查地址是否运行 LocalSend
+    ///
+    /// 主机名 (DNS 或 mDNS 的 `.local`) 先解析为 IP，再依次探测，
+    /// 返回的设备记录探测成功的 IP
+    pub async fn check_device(&self, host: &str) -> Option<DeviceInfo> {
+        let addrs = match crate::net::resolve(host, self.config.port).await {
+            Ok(addrs) => addrs,
+            Err(e) => {
+                debug!(%host, "解析主机名失败: {}", e);
+                return None;
+            }
+        };
+        for addr in addrs {
+            if let Some(device) = probe(&self.config, &self.http, addr.ip().to_string()).await {
+                return Some(device);
             }
         }
-
         None
     }
 }
 
+/// 请求对方的注册接口，返回对方的设备信息，被屏蔽的设备返回 None
+#[instrument(skip(config, http))]
+async fn probe(config: &LocalSendConfig, http: &HttpClientRef, ip: String) -> Option<DeviceInfo> {
+    let port = config.port;
+    let ip = crate::net::normalize_host(&ip);
+    let addr = format!("http://{}/api/v1/localsend/register", crate::net::host_port(&ip, port));
+    let response = http
+        .execute(HttpRequest::get(addr).timeout(PROBE_TIMEOUT))
+        .await
+        .ok()?;
+    let device: crate::dto::RegisterResponse = response.json().ok()?;
+    if !config.allows_device(&device.id, &ip) {
+        debug!(peer = %ip, device_id = %device.id, "忽略被屏蔽的设备");
+        return None;
+    }
+
+    Some(DeviceInfo {
+        id: device.id,
+        name: device.name,
+        device_type: device.device_type,
+        ip,
+        port: device.port.unwrap_or(port),
+        version: device.version,
+        protocol_version: device.protocol_version,
+        announcement_id: device.announcement_id.unwrap_or_default(),
+        uses_password: device.uses_password,
+        transports: device.transports,
+        addresses: device.addresses,
+    })
+}
+
+/// 公告中的候选地址: 配置的地址在前，随后是发往多播组所用网卡的地址，去掉重复项
+pub fn candidate_addresses(config: &LocalSendConfig) -> Vec<String> {
+    let mut addresses: Vec<String> =
+        config.addresses.iter().map(|a| crate::net::normalize_host(a)).collect();
+    let multicast = MULTICAST_ADDR.parse::<Ipv4Addr>().unwrap().into();
+    if let Some(ip) = crate::net::route_source(multicast) {
+        addresses.push(ip.to_string());
+    }
+    let mut seen = std::collections::HashSet::new();
+    addresses.retain(|a| seen.insert(a.clone()));
+    addresses
+}
+
 /// 设备发现服务
 #[derive(Debug)]
 pub struct DiscoveryService {
@@ -217,22 +354,34 @@ pub struct DiscoveryService {
 impl DiscoveryService {
     /// 创建设备发现服务
     pub fn new(config: LocalSendConfig) -> Self {
+        let http = ReqwestClient::for_config(&config);
+        Self::with_http(config, http)
+    }
+
+    /// 创建设备发现服务，HTTP 扫描使用指定的客户端
+    pub fn with_http(config: LocalSendConfig, http: HttpClientRef) -> Self {
         let manager = Arc::new(Mutex::new(DiscoveryManager::new()));
 
         Self {
             udp_discoverer: Some(UdpDiscoverer::new(config.clone(), manager.clone())),
-            http_discoverer: Some(HttpDiscoverer::new(config, manager.clone())),
+            http_discoverer: Some(HttpDiscoverer::new(config, manager.clone(), http)),
             manager,
         }
     }
 
+    /// 省流状态下降低 UDP 公告的频率
+    pub fn with_metered(mut self, metered: Metered) -> Self {
+        self.udp_discoverer = self.udp_discoverer.map(|udp| udp.with_metered(metered));
+        self
+    }
+
     /// 获取发现管理器
     pub fn get_manager(&self) -> DiscoveryManagerRef {
         self.manager.clone()
     }
 
     /// 获取发现的设备
-    pub async fn get_devices(&self) -> Vec<DeviceInfo> {
+    pub async fn get_devices(&self) -> DeviceList {
         self.manager.lock().await.get_devices().await
     }
 
@@ -241,11 +390,11 @@ impl DiscoveryService {
         self.manager.lock().await.clear().await;
     }
 
-    /// 开始发现
-    pub async fn start(&self) {
+    /// 开始发现，直到 cancel 被取消
+    pub async fn start(&self, cancel: CancellationToken) {
         if let Some(udp) = &self.udp_discoverer {
-            if let Err(e) = udp.start_discovery().await {
-                eprintln!("UDP 发现失败: {}", e);
+            if let Err(e) = udp.start_discovery(cancel).await {
+                error!(error = %e, "UDP 发现失败");
             }
         }
     }
@@ -2,6 +2,8 @@
 //!
 //! 定义与 LocalSend 协议通信使用的数据结构
 
+pub mod v2;
+
 use serde::{Deserialize, Serialize};
 
 /// 设备注册请求
@@ -22,6 +24,12 @@ pub struct RegisterRequest {
     pub announcement_id: Option<String>,
     #[serde(default)]
     pub uses_password: bool,
+    /// PeerSend 扩展: 额外支持的文件传输，LocalSend 对端不发送此字段
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transports: Vec<String>,
+    /// PeerSend 扩展: 可连接本机的候选地址，按优先顺序
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<String>,
 }
 
 /// 设备注册响应
@@ -42,6 +50,12 @@ pub struct RegisterResponse {
     pub announcement_id: Option<String>,
     #[serde(default)]
     pub uses_password: bool,
+    /// PeerSend 扩展: 额外支持的文件传输，LocalSend 对端不发送此字段
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transports: Vec<String>,
+    /// PeerSend 扩展: 可连接本机的候选地址，按优先顺序
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<String>,
 }
 
 /// 文件请求
@@ -115,6 +129,12 @@ pub struct IncomingFileMetadata {
     #[serde(rename = "saveAs")]
     #[serde(default)]
     pub save_as: Option<String>,
+    /// 文件内容的 SHA-256，十六进制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// 文件时间等元数据，PeerSend 发送的符号链接在其中带有链接目标
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
 }
 
 /// 传输块请求
@@ -212,6 +232,12 @@ pub struct AnnouncementMessage {
     pub announcement_id: Option<String>,
     #[serde(default)]
     pub uses_password: bool,
+    /// PeerSend 扩展: 额外支持的文件传输，LocalSend 对端不发送此字段
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transports: Vec<String>,
+    /// PeerSend 扩展: 可连接本机的候选地址，按优先顺序
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<String>,
 }
 
 impl AnnouncementMessage {
@@ -227,6 +253,8 @@ impl AnnouncementMessage {
             port: req.port.or(Some(port)),
             announcement_id: req.announcement_id.clone(),
             uses_password: req.uses_password,
+            transports: req.transports.clone(),
+            addresses: req.addresses.clone(),
         }
     }
 }
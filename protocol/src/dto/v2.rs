@@ -0,0 +1,162 @@
+//! LocalSend 协议 v2 线上格式
+//!
+//! 与官方 LocalSend 客户端互通时使用的数据结构，字段名与官方实现一致。
+//! 未知字段会被忽略，可选字段为空时不序列化，保证往返后内容不变
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DeviceInfo, LocalSendConfig};
+
+/// 协议版本
+pub const PROTOCOL_VERSION: &str = "2.0";
+
+/// 设备信息查询
+pub const INFO_PATH: &str = "/api/localsend/v2/info";
+/// 设备注册
+pub const REGISTER_PATH: &str = "/api/localsend/v2/register";
+/// 准备接收
+pub const PREPARE_UPLOAD_PATH: &str = "/api/localsend/v2/prepare-upload";
+/// 上传文件
+pub const UPLOAD_PATH: &str = "/api/localsend/v2/upload";
+/// 取消会话
+pub const CANCEL_PATH: &str = "/api/localsend/v2/cancel";
+
+/// `info` 接口和 `register` 响应中的设备信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InfoDto {
+    pub alias: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_type: Option<String>,
+    pub fingerprint: String,
+    #[serde(default)]
+    pub download: bool,
+}
+
+/// `register` 请求体，也用作 `prepare-upload` 中的发送方信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterDto {
+    pub alias: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_type: Option<String>,
+    pub fingerprint: String,
+    pub port: u16,
+    pub protocol: String,
+    #[serde(default)]
+    pub download: bool,
+}
+
+impl RegisterDto {
+    /// 本机的注册信息，发送文件时随 prepare-upload 提交，指纹为设备 ID
+    pub fn from_config(config: &LocalSendConfig) -> Self {
+        Self {
+            alias: config.device_name.clone(),
+            version: PROTOCOL_VERSION.to_string(),
+            device_model: None,
+            device_type: Some(config.device_type.clone()),
+            fingerprint: config.device_id.clone(),
+            port: config.port,
+            protocol: if config.use_tls { "https" } else { "http" }.to_string(),
+            download: false,
+        }
+    }
+
+    /// 转换为发现到的设备，ip 为请求来源地址
+    pub fn to_device(&self, ip: &str) -> DeviceInfo {
+        DeviceInfo {
+            id: self.fingerprint.clone(),
+            name: self.alias.clone(),
+            device_type: self.device_type.clone().unwrap_or_else(|| "desktop".to_string()),
+            ip: ip.to_string(),
+            port: self.port,
+            version: String::new(),
+            protocol_version: self.version.clone(),
+            announcement_id: String::new(),
+            uses_password: false,
+            transports: Vec::new(),
+            addresses: Vec::new(),
+        }
+    }
+}
+
+/// UDP 多播公告
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MulticastDto {
+    pub alias: String,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_type: Option<String>,
+    pub fingerprint: String,
+    pub port: u16,
+    pub protocol: String,
+    #[serde(default)]
+    pub download: bool,
+    /// v1 客户端发送 `announcement`
+    #[serde(default, alias = "announcement", skip_serializing_if = "Option::is_none")]
+    pub announce: Option<bool>,
+}
+
+/// 文件时间信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileTimesDto {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accessed: Option<String>,
+    /// PeerSend 扩展: 符号链接的目标，见 [`storage::links`](crate::storage::links)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symlink: Option<String>,
+}
+
+/// 待发送文件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDto {
+    pub id: String,
+    pub file_name: String,
+    pub size: u64,
+    pub file_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<FileTimesDto>,
+}
+
+/// `prepare-upload` 请求体
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrepareUploadRequestDto {
+    pub info: RegisterDto,
+    /// 文件 ID 到文件信息
+    pub files: HashMap<String, FileDto>,
+}
+
+/// `prepare-upload` 响应体
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrepareUploadResponseDto {
+    pub session_id: String,
+    /// 接收方接受的文件 ID 到上传令牌
+    pub files: HashMap<String, String>,
+}
+
+/// `GET upload` 的响应，PeerSend 扩展
+///
+/// 文件此前中断时接收方已写入的字节数，发送方带上 `offset` 参数从此处续传
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadOffsetDto {
+    pub offset: u64,
+}
@@ -0,0 +1,86 @@
+//! 协议错误类型
+//!
+//! 各模块统一使用 [`ProtocolError`] 报告失败原因
+
+use thiserror::Error;
+
+/// 协议错误
+#[derive(Debug, Error)]
+pub enum ProtocolError {
+    /// 文件或网络 IO 错误
+    #[error("IO 错误: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// HTTP 请求失败
+    #[cfg(feature = "http")]
+    #[error("HTTP 错误: {0}")]
+    Http(reqwest::Error),
+
+    /// 对方返回了非 2xx 状态码
+    #[error("HTTP 状态码 {0}")]
+    Status(u16),
+
+    /// 消息序列化或反序列化失败
+    #[error("序列化错误: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    /// 对方拒绝了请求
+    #[error("请求被拒绝: {0}")]
+    Rejected(String),
+
+    /// 对方要求输入 PIN
+    #[error("需要 PIN")]
+    PinRequired,
+
+    /// 文件内容与声明的哈希不一致
+    #[error("文件校验失败: {file_id}")]
+    HashMismatch { file_id: String },
+
+    /// 操作超时
+    #[error("操作超时")]
+    Timeout,
+
+    /// 传输已取消
+    #[error("传输已取消")]
+    Cancelled,
+
+    /// 会话不存在或已过期
+    #[error("会话不存在: {0}")]
+    SessionNotFound(String),
+
+    /// 配置无效
+    #[error("配置无效: {0}")]
+    InvalidConfig(String),
+
+    /// 加密或解密失败
+    #[error("加密错误: {0}")]
+    Crypto(String),
+
+    /// 收到的数据不符合协议
+    #[error("无效数据: {0}")]
+    InvalidData(String),
+
+    /// macOS 未授予本地网络权限，多播和局域网连接都会被系统拦截
+    #[error("没有本地网络访问权限，请在“系统设置 > 隐私与安全性 > 本地网络”中允许 PeerSend")]
+    LocalNetworkDenied,
+}
+
+#[cfg(feature = "http")]
+impl From<reqwest::Error> for ProtocolError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            ProtocolError::Timeout
+        } else {
+            ProtocolError::Http(e)
+        }
+    }
+}
+
+impl From<tokio::time::error::Elapsed> for ProtocolError {
+    fn from(_: tokio::time::error::Elapsed) -> Self {
+        ProtocolError::Timeout
+    }
+}
+
+/// 协议操作结果
+pub type Result<T, E = ProtocolError> = std::result::Result<T, E>;
@@ -0,0 +1,135 @@
+//! 协议事件总线
+//!
+//! 发现、会话和传输等子系统通过同一个广播通道发布事件，
+//! 前端只需订阅一次即可获得全部状态变化
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::{DeviceInfo, FileInfo, SessionState, SessionSummary};
+
+/// 事件通道默认容量
+const DEFAULT_CAPACITY: usize = 256;
+
+/// 协议事件
+///
+/// 序列化为带 `event` 字段的 JSON 对象，如
+/// `{"event": "fileReceived", "sessionId": "...", "path": "...", "sha256": null}`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ProtocolEvent {
+    /// 发现新设备
+    DeviceDiscovered(DeviceInfo),
+    /// 收到文件发送请求
+    SessionRequested {
+        session_id: String,
+        sender: String,
+        files: Vec<FileInfo>,
+    },
+    /// 会话状态变化
+    SessionStateChanged {
+        session_id: String,
+        #[serde(flatten)]
+        state: SessionState,
+    },
+    /// 文件传输进度
+    FileProgress {
+        session_id: String,
+        file_id: String,
+        bytes_transferred: u64,
+        total_bytes: u64,
+    },
+    /// 文件接收完成，sha256 为发送方声明的哈希
+    FileReceived {
+        session_id: String,
+        path: PathBuf,
+        sha256: Option<String>,
+    },
+    /// 收到的文件扩展名有风险，已放入隔离目录
+    FileQuarantined { session_id: String, path: PathBuf },
+    /// 已解压收到的压缩包中的一个条目，extracted 为已处理的条目数
+    ArchiveEntryExtracted {
+        session_id: String,
+        archive: PathBuf,
+        entry: String,
+        extracted: usize,
+    },
+    /// 收到的压缩包已解压到 dir
+    ArchiveExtracted {
+        session_id: String,
+        archive: PathBuf,
+        dir: PathBuf,
+    },
+    /// 待发送的源文件在读取文件信息后被修改，reason 说明改变及处理方式
+    SourceChanged {
+        session_id: String,
+        file_id: String,
+        path: PathBuf,
+        reason: String,
+    },
+    /// 会话结束，附带传输结果的摘要，紧随结束时的 `sessionStateChanged` 发布
+    SessionSummary(Box<SessionSummary>),
+    /// 收到文本消息，sender_id 为对方的设备 ID
+    TextReceived {
+        sender_id: String,
+        sender: String,
+        text: String,
+    },
+    /// 子系统出错
+    Error {
+        session_id: Option<String>,
+        message: String,
+    },
+}
+
+/// 事件总线
+///
+/// 克隆后共享同一个通道
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<ProtocolEvent>,
+}
+
+impl EventBus {
+    /// 创建指定容量的事件总线
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// 订阅事件
+    ///
+    /// 订阅者处理过慢时会丢失最早的事件，接收端将收到 `RecvError::Lagged`
+    pub fn subscribe(&self) -> broadcast::Receiver<ProtocolEvent> {
+        self.sender.subscribe()
+    }
+
+    /// 发布事件，没有订阅者时直接丢弃
+    pub fn emit(&self, event: ProtocolEvent) {
+        let _ = self.sender.send(event);
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+/// 接收事件
+#[derive(Debug, Clone)]
+pub enum ReceiveEvent {
+    /// 接收服务已启动
+    Started { addr: SocketAddr },
+    /// 收到文件发送请求
+    SessionRequested { session_id: String, sender: String, files: Vec<FileInfo> },
+    /// 单个文件接收完成
+    FileReceived { session_id: String, path: PathBuf },
+    /// 收到文本消息，sender_id 为对方的设备 ID
+    TextReceived { sender_id: String, sender: String, text: String },
+    /// 会话结束
+    SessionFinished { session_id: String },
+}
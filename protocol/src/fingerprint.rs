@@ -0,0 +1,71 @@
+//! 待发送源文件的指纹
+//!
+//! 从协商会话到上传文件之间可能相隔很久 (等待对方确认、传输中断后换用途径重新上传)，
+//! 期间源文件可能被修改。发送端在读取文件信息时记录 [`SourceFingerprint`]，
+//! 上传每个文件前重新读取并比较，发现改变时从头发送该文件，
+//! 与声明的长度或哈希不再相符时不发送，避免对方收到新旧内容混杂的文件。
+//!
+//! 指纹包括长度、修改时间和开头 [`PREFIX_LEN`] 字节的 SHA-256。
+//! 文件系统不提供修改时间时 (如测试用的内存实现) 只比较长度和开头内容
+
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+
+use crate::fs::FileSystem;
+use crate::hash;
+
+/// 计入指纹的开头字节数 (64KB)
+pub const PREFIX_LEN: u64 = 64 * 1024;
+
+/// 源文件的指纹
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceFingerprint {
+    pub size: u64,
+    /// 修改时间，Unix 纳秒
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u128>,
+    /// 开头 [`PREFIX_LEN`] 字节的 SHA-256
+    pub prefix_sha256: String,
+}
+
+impl SourceFingerprint {
+    /// 读取文件当前的指纹
+    pub async fn read(fs: &dyn FileSystem, path: &Path) -> crate::Result<Self> {
+        let size = fs.file_len(path).await?;
+        let modified = fs
+            .modified(path)
+            .await?
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_nanos());
+        let mut prefix = Vec::new();
+        fs.open_read(path)
+            .await?
+            .take(PREFIX_LEN)
+            .read_to_end(&mut prefix)
+            .await?;
+        Ok(Self {
+            size,
+            modified,
+            prefix_sha256: hash::sha256_hex(&prefix),
+        })
+    }
+
+    /// 与 current 比较，返回改变的各项，未改变时为空
+    pub fn changes(&self, current: &Self) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        if self.size != current.size {
+            changes.push("长度");
+        }
+        if self.modified != current.modified {
+            changes.push("修改时间");
+        }
+        if self.prefix_sha256 != current.prefix_sha256 {
+            changes.push("开头内容");
+        }
+        changes
+    }
+}
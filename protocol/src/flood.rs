@@ -0,0 +1,189 @@
+//! 公告与请求洪泛防护
+//!
+//! 按来源 IP 统计公告、扫描响应和文件发送请求的频率，以及同一地址声称的设备数。
+//! 超出限制的地址在一段时间内被忽略，避免出错或恶意的设备用成千上万的条目
+//! 填满 [`DiscoveryManager`](crate::DiscoveryManager) 或不断创建会话
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+/// 跟踪的地址超过此数量时清理已过期的记录
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// 洪泛防护的阈值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FloodLimits {
+    /// 统计窗口
+    pub window: Duration,
+    /// 每个地址在一个窗口内允许的公告和请求数
+    pub max_requests: u32,
+    /// 每个地址允许声称的不同设备 ID 数
+    pub max_devices: usize,
+    /// 超出限制后忽略该地址的时长
+    pub block_duration: Duration,
+}
+
+impl Default for FloodLimits {
+    fn default() -> Self {
+        // LocalSend 每 5 秒左右公告一次，正常设备远达不到这些阈值
+        Self {
+            window: Duration::from_secs(10),
+            max_requests: 20,
+            max_devices: 4,
+            block_duration: Duration::from_secs(60),
+        }
+    }
+}
+
+/// 防护的统计数据
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FloodStats {
+    /// 当前跟踪的地址数
+    pub tracked: usize,
+    /// 当前被忽略的地址数
+    pub blocked: usize,
+    /// 累计封禁次数
+    pub blocks: u64,
+    /// 累计丢弃的公告和请求数
+    pub dropped: u64,
+}
+
+/// 单个地址的记录
+#[derive(Debug)]
+struct Peer {
+    window_start: Instant,
+    requests: u32,
+    devices: HashSet<String>,
+    blocked_until: Option<Instant>,
+    last_seen: Instant,
+}
+
+/// 洪泛防护
+///
+/// 克隆后共享同一份记录
+#[derive(Debug, Clone, Default)]
+pub struct FloodGuard {
+    limits: FloodLimits,
+    peers: Arc<Mutex<HashMap<String, Peer>>>,
+    blocks: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl FloodGuard {
+    /// 使用指定阈值创建
+    pub fn new(limits: FloodLimits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    /// 当前阈值
+    pub fn limits(&self) -> FloodLimits {
+        self.limits
+    }
+
+    /// 记录来自 ip、声称为 device_id 的一次公告或请求，返回是否应当处理
+    ///
+    /// 超出限制时封禁该地址并返回 false，封禁期间的调用均返回 false
+    pub fn check(&self, ip: &str, device_id: &str) -> bool {
+        let now = Instant::now();
+        let limits = self.limits;
+        let mut peers = self.peers.lock().unwrap();
+        if peers.len() > PRUNE_THRESHOLD {
+            peers.retain(|_, peer| !peer.expired(now, &limits));
+        }
+
+        let peer = peers.entry(ip.to_string()).or_insert_with(|| Peer {
+            window_start: now,
+            requests: 0,
+            devices: HashSet::new(),
+            blocked_until: None,
+            last_seen: now,
+        });
+        peer.last_seen = now;
+
+        match peer.blocked_until {
+            Some(until) if until > now => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+            Some(_) => {
+                // 封禁到期，重新计数
+                peer.blocked_until = None;
+                peer.window_start = now;
+                peer.requests = 0;
+                peer.devices.clear();
+            }
+            None => {}
+        }
+
+        if now.duration_since(peer.window_start) >= limits.window {
+            peer.window_start = now;
+            peer.requests = 0;
+        }
+        peer.requests += 1;
+        if !peer.devices.contains(device_id) && peer.devices.len() < limits.max_devices {
+            peer.devices.insert(device_id.to_string());
+        }
+
+        let reason = if peer.requests > limits.max_requests {
+            "公告或请求过于频繁"
+        } else if !peer.devices.contains(device_id) {
+            "同一地址声称的设备过多"
+        } else {
+            return true;
+        };
+        peer.blocked_until = Some(now + limits.block_duration);
+        self.blocks.fetch_add(1, Ordering::Relaxed);
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            peer = %ip,
+            device_id,
+            requests = peer.requests,
+            devices = peer.devices.len(),
+            block_secs = limits.block_duration.as_secs(),
+            "{}，暂时忽略该地址",
+            reason
+        );
+        false
+    }
+
+    /// 地址当前是否被忽略
+    pub fn is_blocked(&self, ip: &str) -> bool {
+        let now = Instant::now();
+        self.peers
+            .lock()
+            .unwrap()
+            .get(ip)
+            .and_then(|peer| peer.blocked_until)
+            .is_some_and(|until| until > now)
+    }
+
+    /// 统计数据快照
+    pub fn stats(&self) -> FloodStats {
+        let now = Instant::now();
+        let peers = self.peers.lock().unwrap();
+        FloodStats {
+            tracked: peers.len(),
+            blocked: peers
+                .values()
+                .filter(|peer| peer.blocked_until.is_some_and(|until| until > now))
+                .count(),
+            blocks: self.blocks.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Peer {
+    /// 未被封禁且一段时间内没有活动的记录可以丢弃
+    fn expired(&self, now: Instant, limits: &FloodLimits) -> bool {
+        let blocked = self.blocked_until.is_some_and(|until| until > now);
+        !blocked && now.duration_since(self.last_seen) >= limits.window.max(limits.block_duration)
+    }
+}
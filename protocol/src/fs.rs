@@ -0,0 +1,157 @@
+//! 文件系统抽象
+//!
+//! 发送器、接收器和客户端通过 [`FileSystem`] 访问文件，
+//! 测试中可替换为内存实现
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub mod uring;
+
+use std::fmt::Debug;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite};
+
+/// 装箱的读取端
+pub type BoxedReader = Pin<Box<dyn AsyncRead + Send>>;
+
+/// 装箱的写入端
+pub type BoxedWriter = Pin<Box<dyn AsyncWrite + Send>>;
+
+/// 共享的文件系统实现
+pub type FileSystemRef = Arc<dyn FileSystem>;
+
+/// 文件系统接口
+#[async_trait]
+pub trait FileSystem: Debug + Send + Sync {
+    /// 获取普通文件的长度，路径不是文件时返回 `InvalidInput`
+    async fn file_len(&self, path: &Path) -> crate::Result<u64>;
+
+    /// 获取文件的修改时间，不支持时返回 None
+    async fn modified(&self, _path: &Path) -> crate::Result<Option<SystemTime>> {
+        Ok(None)
+    }
+
+    /// 打开文件读取
+    async fn open_read(&self, path: &Path) -> crate::Result<BoxedReader>;
+
+    /// 打开文件并从 offset 处开始读取
+    ///
+    /// 默认实现读取并丢弃前 offset 个字节，支持定位的实现应覆盖
+    async fn open_read_at(&self, path: &Path, offset: u64) -> crate::Result<BoxedReader> {
+        let mut reader = self.open_read(path).await?;
+        let skipped = tokio::io::copy(&mut (&mut reader).take(offset), &mut tokio::io::sink()).await?;
+        if skipped != offset {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("偏移超出文件长度: {}", offset),
+            )
+            .into());
+        }
+        Ok(reader)
+    }
+
+    /// 打开文件写入，append 为 false 时截断已有内容
+    async fn open_write(&self, path: &Path, append: bool) -> crate::Result<BoxedWriter>;
+
+    /// 递归创建目录
+    async fn create_dir_all(&self, path: &Path) -> crate::Result<()>;
+
+    /// 删除文件，文件不存在时视为成功
+    async fn remove_file(&self, path: &Path) -> crate::Result<()>;
+
+    /// 将文件从 from 改名为 to，已存在的 to 被替换，同一文件系统内为原子操作
+    async fn rename(&self, from: &Path, to: &Path) -> crate::Result<()>;
+
+    /// 将文件内容同步到持久存储，默认不做任何事
+    async fn sync_file(&self, _path: &Path) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// 路径是否直接对应本地文件，为 true 时调用方可绕过本接口使用零拷贝发送
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+/// 本地文件系统
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LocalFs;
+
+impl LocalFs {
+    /// 共享的本地文件系统实例
+    pub fn shared() -> FileSystemRef {
+        Arc::new(LocalFs)
+    }
+}
+
+#[async_trait]
+impl FileSystem for LocalFs {
+    async fn file_len(&self, path: &Path) -> crate::Result<u64> {
+        let metadata = tokio::fs::metadata(path).await?;
+        if !metadata.is_file() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("不是文件: {}", path.display()),
+            )
+            .into());
+        }
+        Ok(metadata.len())
+    }
+
+    async fn modified(&self, path: &Path) -> crate::Result<Option<SystemTime>> {
+        Ok(tokio::fs::metadata(path).await?.modified().ok())
+    }
+
+    async fn open_read(&self, path: &Path) -> crate::Result<BoxedReader> {
+        Ok(Box::pin(tokio::fs::File::open(path).await?))
+    }
+
+    async fn open_read_at(&self, path: &Path, offset: u64) -> crate::Result<BoxedReader> {
+        let mut file = tokio::fs::File::open(path).await?;
+        file.seek(std::io::SeekFrom::Start(offset)).await?;
+        Ok(Box::pin(file))
+    }
+
+    async fn open_write(&self, path: &Path, append: bool) -> crate::Result<BoxedWriter> {
+        let file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .await?;
+        Ok(Box::pin(file))
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> crate::Result<()> {
+        tokio::fs::create_dir_all(path).await?;
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> crate::Result<()> {
+        match tokio::fs::remove_file(path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> crate::Result<()> {
+        tokio::fs::rename(from, to).await?;
+        Ok(())
+    }
+
+    async fn sync_file(&self, path: &Path) -> crate::Result<()> {
+        // fsync 作用于文件本身，任意可写句柄都能同步此前写入的数据
+        let file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+        file.sync_all().await?;
+        Ok(())
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
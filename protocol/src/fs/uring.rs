@@ -0,0 +1,323 @@
+//! 基于 io_uring 的文件系统
+//!
+//! tokio-uring 需要独立的运行时，[`UringFs`] 在专用线程上运行该运行时，
+//! 读写请求通过通道提交，结果经 oneshot 返回。每个打开的文件由线程上的一个任务持有，
+//! 同一文件的请求按顺序执行，读取端和写入端在等待完成时不占用调用方的运行时线程
+//!
+//! 目录操作和元数据查询不是瓶颈，仍交给 [`LocalFs`]
+
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{mpsc, oneshot};
+
+use super::{BoxedReader, BoxedWriter, FileSystem, FileSystemRef, LocalFs};
+
+/// 单次读取请求的最大字节数 (1MB)
+const MAX_READ_SIZE: usize = 1024 * 1024;
+
+type Reply<T> = oneshot::Sender<io::Result<T>>;
+
+/// 提交给 io_uring 线程的打开请求
+struct OpenRequest {
+    path: PathBuf,
+    write: bool,
+    append: bool,
+    reply: Reply<FileHandle>,
+}
+
+/// 已打开文件上的操作
+enum FileOp {
+    Read { pos: u64, len: usize, reply: Reply<Vec<u8>> },
+    Write { pos: u64, data: Vec<u8>, reply: Reply<()> },
+}
+
+/// io_uring 线程上打开的文件
+struct FileHandle {
+    ops: mpsc::UnboundedSender<FileOp>,
+    /// 文件打开时的长度，追加写入从这里开始
+    len: u64,
+}
+
+/// io_uring 文件系统
+///
+/// 克隆后共享同一个 io_uring 线程，所有句柄释放后线程退出
+#[derive(Debug, Clone)]
+pub struct UringFs {
+    requests: mpsc::UnboundedSender<OpenRequest>,
+}
+
+impl UringFs {
+    /// 启动 io_uring 线程
+    ///
+    /// 内核不支持 io_uring 时线程会立即退出，之后的打开操作返回错误
+    pub fn new() -> io::Result<Self> {
+        let (requests, rx) = mpsc::unbounded_channel();
+        std::thread::Builder::new()
+            .name("peersend-uring".to_string())
+            .spawn(move || tokio_uring::start(serve(rx)))?;
+        Ok(Self { requests })
+    }
+
+    /// 共享的 io_uring 文件系统实例
+    pub fn shared() -> io::Result<FileSystemRef> {
+        Ok(Arc::new(Self::new()?))
+    }
+
+    async fn open(&self, path: &Path, write: bool, append: bool) -> crate::Result<FileHandle> {
+        let (reply, rx) = oneshot::channel();
+        self.requests
+            .send(OpenRequest {
+                path: path.to_path_buf(),
+                write,
+                append,
+                reply,
+            })
+            .map_err(|_| closed())?;
+        Ok(rx.await.map_err(|_| closed())??)
+    }
+}
+
+#[async_trait]
+impl FileSystem for UringFs {
+    async fn file_len(&self, path: &Path) -> crate::Result<u64> {
+        LocalFs.file_len(path).await
+    }
+
+    async fn modified(&self, path: &Path) -> crate::Result<Option<SystemTime>> {
+        LocalFs.modified(path).await
+    }
+
+    async fn open_read(&self, path: &Path) -> crate::Result<BoxedReader> {
+        self.open_read_at(path, 0).await
+    }
+
+    async fn open_read_at(&self, path: &Path, offset: u64) -> crate::Result<BoxedReader> {
+        let handle = self.open(path, false, false).await?;
+        Ok(Box::pin(UringReader {
+            handle,
+            _runtime: self.requests.clone(),
+            pos: offset,
+            pending: None,
+            buffered: Vec::new(),
+            consumed: 0,
+        }))
+    }
+
+    async fn open_write(&self, path: &Path, append: bool) -> crate::Result<BoxedWriter> {
+        let handle = self.open(path, true, append).await?;
+        let pos = if append { handle.len } else { 0 };
+        Ok(Box::pin(UringWriter {
+            handle,
+            _runtime: self.requests.clone(),
+            pos,
+            pending: None,
+        }))
+    }
+
+    async fn create_dir_all(&self, path: &Path) -> crate::Result<()> {
+        LocalFs.create_dir_all(path).await
+    }
+
+    async fn remove_file(&self, path: &Path) -> crate::Result<()> {
+        LocalFs.remove_file(path).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> crate::Result<()> {
+        LocalFs.rename(from, to).await
+    }
+
+    async fn sync_file(&self, path: &Path) -> crate::Result<()> {
+        LocalFs.sync_file(path).await
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+/// io_uring 线程主循环，每个打开的文件交给一个任务
+async fn serve(mut requests: mpsc::UnboundedReceiver<OpenRequest>) {
+    while let Some(request) = requests.recv().await {
+        tokio_uring::spawn(async move {
+            let (ops, rx) = mpsc::unbounded_channel();
+            match open_file(&request).await {
+                Ok((file, len)) => {
+                    if request.reply.send(Ok(FileHandle { ops, len })).is_ok() {
+                        serve_file(file, rx).await;
+                    } else {
+                        let _ = file.close().await;
+                    }
+                }
+                Err(e) => {
+                    let _ = request.reply.send(Err(e));
+                }
+            }
+        });
+    }
+}
+
+async fn open_file(request: &OpenRequest) -> io::Result<(tokio_uring::fs::File, u64)> {
+    let file = tokio_uring::fs::OpenOptions::new()
+        .read(!request.write)
+        .write(request.write)
+        .create(request.write)
+        .truncate(request.write && !request.append)
+        .open(&request.path)
+        .await?;
+    let len = if request.append {
+        std::fs::metadata(&request.path)?.len()
+    } else {
+        0
+    };
+    Ok((file, len))
+}
+
+/// 顺序执行文件上的操作，所有句柄释放后关闭文件
+async fn serve_file(file: tokio_uring::fs::File, mut ops: mpsc::UnboundedReceiver<FileOp>) {
+    while let Some(op) = ops.recv().await {
+        match op {
+            FileOp::Read { pos, len, reply } => {
+                let (result, buf) = file.read_at(Vec::with_capacity(len), pos).await;
+                let _ = reply.send(result.map(|_| buf));
+            }
+            FileOp::Write { pos, data, reply } => {
+                let _ = reply.send(write_all_at(&file, data, pos).await);
+            }
+        }
+    }
+    let _ = file.close().await;
+}
+
+async fn write_all_at(file: &tokio_uring::fs::File, mut data: Vec<u8>, mut pos: u64) -> io::Result<()> {
+    while !data.is_empty() {
+        let (result, buf) = file.write_at(data, pos).await;
+        let n = result?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        pos += n as u64;
+        data = buf;
+        data.drain(..n);
+    }
+    Ok(())
+}
+
+/// 等待 io_uring 线程返回结果
+fn poll_reply<T>(
+    pending: &mut Option<oneshot::Receiver<io::Result<T>>>,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<T>> {
+    let rx = pending.as_mut().expect("没有进行中的请求");
+    let result = ready!(Pin::new(rx).poll(cx));
+    *pending = None;
+    Poll::Ready(result.unwrap_or_else(|_| Err(closed())))
+}
+
+/// io_uring 读取端，一次只有一个读取请求在途
+struct UringReader {
+    handle: FileHandle,
+    /// 保持 io_uring 线程存活
+    _runtime: mpsc::UnboundedSender<OpenRequest>,
+    pos: u64,
+    pending: Option<oneshot::Receiver<io::Result<Vec<u8>>>>,
+    /// 调用方缓冲区放不下的已读数据
+    buffered: Vec<u8>,
+    consumed: usize,
+}
+
+impl AsyncRead for UringReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if this.consumed < this.buffered.len() {
+            let n = (this.buffered.len() - this.consumed).min(buf.remaining());
+            buf.put_slice(&this.buffered[this.consumed..this.consumed + n]);
+            this.consumed += n;
+            return Poll::Ready(Ok(()));
+        }
+
+        if this.pending.is_none() {
+            let (reply, rx) = oneshot::channel();
+            let op = FileOp::Read {
+                pos: this.pos,
+                len: buf.remaining().min(MAX_READ_SIZE),
+                reply,
+            };
+            this.handle.ops.send(op).map_err(|_| closed())?;
+            this.pending = Some(rx);
+        }
+
+        let data = ready!(poll_reply(&mut this.pending, cx))?;
+        this.pos += data.len() as u64;
+        let n = data.len().min(buf.remaining());
+        buf.put_slice(&data[..n]);
+        this.buffered = data;
+        this.consumed = n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// io_uring 写入端
+///
+/// 与 `tokio::fs::File` 相同，数据提交后立即返回，写入错误在下一次操作或刷新时报告
+struct UringWriter {
+    handle: FileHandle,
+    /// 保持 io_uring 线程存活
+    _runtime: mpsc::UnboundedSender<OpenRequest>,
+    pos: u64,
+    pending: Option<oneshot::Receiver<io::Result<()>>>,
+}
+
+impl UringWriter {
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.pending.is_none() {
+            return Poll::Ready(Ok(()));
+        }
+        poll_reply(&mut self.pending, cx)
+    }
+}
+
+impl AsyncWrite for UringWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        ready!(this.poll_pending(cx))?;
+
+        let (reply, rx) = oneshot::channel();
+        let op = FileOp::Write {
+            pos: this.pos,
+            data: buf.to_vec(),
+            reply,
+        };
+        this.handle.ops.send(op).map_err(|_| closed())?;
+        this.pending = Some(rx);
+        this.pos += buf.len() as u64;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_pending(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_pending(cx)
+    }
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "io_uring 线程已退出")
+}
@@ -0,0 +1,306 @@
+//! 接收完成后执行的命令
+//!
+//! 每个文件或每个会话接收完成后按配置执行命令，便于接入导入脚本而无需重新编译。
+//! 命令模板按空白分隔为程序和参数，引号内的空白不分隔；模板中可使用以下占位符：
+//! `{{path}}` (文件路径，会话完成时为下载目录)、`{{sender}}`、`{{hash}}`
+//! (文件的 SHA-256，会话完成时为空) 和 `{{session_id}}`。
+//!
+//! 命令不经过 shell 执行，占位符替换在分隔参数之后进行，文件名中的空格和特殊字符
+//! 不会被解释。超时的命令被结束；退出状态和截断后的输出写入审计日志
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::process::Command;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::audit::{AuditAction, AuditEntry, AuditLog};
+use crate::{
+    HookConfig, HookTrigger, LocalSendConfig, ProtocolError, ProtocolEvent, SessionManager,
+    SessionState,
+};
+
+/// 写入审计日志的输出长度上限 (字节)
+const MAX_OUTPUT: usize = 4096;
+
+/// 一次命令执行的参数
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookContext {
+    pub session_id: String,
+    pub sender: String,
+    pub path: PathBuf,
+    pub hash: Option<String>,
+}
+
+/// 命令执行结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookOutput {
+    /// 退出码，超时或被信号结束时为 None
+    pub code: Option<i32>,
+    /// 是否超时
+    pub timed_out: bool,
+    /// 标准输出和标准错误，超过上限的部分被截掉
+    pub output: String,
+}
+
+impl HookOutput {
+    /// 命令是否成功退出
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    fn describe(&self) -> String {
+        match self.code {
+            _ if self.timed_out => "超时".to_string(),
+            Some(code) => format!("退出码 {}", code),
+            None => "被信号结束".to_string(),
+        }
+    }
+}
+
+/// 把命令模板按空白分隔为程序和参数，单引号和双引号内的空白不分隔
+///
+/// 模板为空或引号未闭合时返回 [`ProtocolError::InvalidConfig`]
+pub fn split_command(template: &str) -> crate::Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    for c in template.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => args.extend(current.take()),
+            None => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(ProtocolError::InvalidConfig(format!(
+            "命令中的引号未闭合: {}",
+            template
+        )));
+    }
+    args.extend(current);
+    if args.is_empty() {
+        return Err(ProtocolError::InvalidConfig("命令不能为空".to_string()));
+    }
+    Ok(args)
+}
+
+/// 分隔命令模板并替换占位符
+pub fn render(template: &str, context: &HookContext) -> crate::Result<Vec<String>> {
+    let values = [
+        ("path", context.path.to_string_lossy().into_owned()),
+        ("sender", context.sender.clone()),
+        ("hash", context.hash.clone().unwrap_or_default()),
+        ("session_id", context.session_id.clone()),
+    ];
+    Ok(split_command(template)?
+        .into_iter()
+        .map(|arg| {
+            values.iter().fold(arg, |arg, (key, value)| {
+                arg.replace(&format!("{{{{{}}}}}", key), value)
+            })
+        })
+        .collect())
+}
+
+/// 执行命令并等待结束，超时时结束命令
+pub async fn execute(args: &[String], timeout: Duration) -> crate::Result<HookOutput> {
+    let (program, args) = args
+        .split_first()
+        .ok_or_else(|| ProtocolError::InvalidConfig("命令不能为空".to_string()))?;
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    // 超时时丢弃 future，kill_on_drop 结束子进程
+    let Ok(output) = tokio::time::timeout(timeout, child.wait_with_output()).await else {
+        return Ok(HookOutput {
+            code: None,
+            timed_out: true,
+            output: String::new(),
+        });
+    };
+    let output = output?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    if text.len() > MAX_OUTPUT {
+        let mut end = MAX_OUTPUT;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    Ok(HookOutput {
+        code: output.status.code(),
+        timed_out: false,
+        output: text,
+    })
+}
+
+/// 接收完成后执行配置的命令
+#[derive(Debug, Clone)]
+pub struct PostReceiveHooks {
+    hooks: Arc<Vec<HookConfig>>,
+    download_dir: PathBuf,
+    device_id: String,
+    audit: Option<AuditLog>,
+}
+
+impl PostReceiveHooks {
+    /// 按配置创建，未配置命令时返回 None
+    pub fn from_config(config: &LocalSendConfig) -> Option<Self> {
+        if config.hooks.is_empty() {
+            return None;
+        }
+        Some(Self {
+            hooks: Arc::new(config.hooks.clone()),
+            download_dir: PathBuf::from(&config.download_dir),
+            device_id: config.device_id.clone(),
+            audit: None,
+        })
+    }
+
+    /// 把执行结果写入审计日志
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// 依次执行在 trigger 时触发的命令，失败只记录日志
+    pub async fn fire(&self, trigger: HookTrigger, mut context: HookContext) {
+        for hook in self.hooks.iter().filter(|hook| hook.on == trigger) {
+            // 发送方未声明哈希时按需计算
+            if context.hash.is_none()
+                && trigger == HookTrigger::File
+                && hook.command.contains("{{hash}}")
+            {
+                context.hash = file_hash(&context.path).await;
+            }
+            let result = match render(&hook.command, &context) {
+                Ok(args) => execute(&args, Duration::from_secs(hook.timeout_secs)).await,
+                Err(e) => Err(e),
+            };
+            let mut entry = AuditEntry {
+                session_id: Some(context.session_id.clone()),
+                peer: Some(context.sender.clone()),
+                command: Some(hook.command.clone()),
+                ..AuditEntry::new(AuditAction::Hook)
+            };
+            match result {
+                Ok(output) => {
+                    if output.success() {
+                        debug!(command = %hook.command, "接收后命令已执行");
+                    } else {
+                        warn!(command = %hook.command, status = %output.describe(), "接收后命令失败");
+                    }
+                    debug!(command = %hook.command, output = %output.output, "接收后命令输出");
+                    entry.reason = Some(output.describe());
+                    entry.output = (!output.output.is_empty()).then_some(output.output);
+                }
+                Err(e) => {
+                    warn!(command = %hook.command, error = %e, "无法执行接收后命令");
+                    entry.reason = Some(e.to_string());
+                }
+            }
+            if let Some(audit) = &self.audit {
+                audit.record_or_warn(entry);
+            }
+        }
+    }
+
+    /// 在文件和会话接收完成时执行命令，直到事件总线关闭或 cancel 取消
+    ///
+    /// sessions 用于查询会话的发送方，本机发起的会话完成时不执行
+    pub async fn run(
+        self,
+        mut events: broadcast::Receiver<ProtocolEvent>,
+        sessions: SessionManager,
+        cancel: CancellationToken,
+    ) {
+        // 会话 ID 到发送方名称，来自请求事件
+        let mut senders: HashMap<String, String> = HashMap::new();
+
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = events.recv() => event,
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "接收后命令处理过慢，丢失部分事件");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            let (trigger, session_id, sender, path, hash) = match event {
+                ProtocolEvent::SessionRequested {
+                    session_id, sender, ..
+                } => {
+                    senders.insert(session_id, sender);
+                    continue;
+                }
+                ProtocolEvent::FileReceived {
+                    session_id,
+                    path,
+                    sha256,
+                } => {
+                    let sender = senders.get(&session_id).cloned();
+                    (HookTrigger::File, session_id, sender, path, sha256)
+                }
+                ProtocolEvent::SessionStateChanged { session_id, state } => match state {
+                    SessionState::Finished => {
+                        let sender = senders.remove(&session_id);
+                        let dir = self.download_dir.clone();
+                        (HookTrigger::Session, session_id, sender, dir, None)
+                    }
+                    SessionState::Cancelled | SessionState::Error(_) => {
+                        senders.remove(&session_id);
+                        continue;
+                    }
+                    SessionState::Waiting | SessionState::Transferring => continue,
+                },
+                _ => continue,
+            };
+            if !self.hooks.iter().any(|hook| hook.on == trigger) {
+                continue;
+            }
+            let session = sessions.get_session(&session_id).await;
+            if session.as_ref().is_some_and(|s| s.sender_id == self.device_id) {
+                continue;
+            }
+            let sender = sender
+                .or_else(|| session.map(|s| s.sender_id.clone()))
+                .unwrap_or_default();
+            let context = HookContext {
+                session_id,
+                sender,
+                path,
+                hash,
+            };
+
+            // 不阻塞事件接收
+            let hooks = self.clone();
+            tokio::spawn(async move { hooks.fire(trigger, context).await });
+        }
+    }
+}
+
+async fn file_hash(path: &Path) -> Option<String> {
+    let file = tokio::fs::File::open(path).await.ok()?;
+    crate::hash::sha256_reader(file).await.ok()
+}
@@ -0,0 +1,353 @@
+//! HTTP 客户端抽象
+//!
+//! 客户端通过 [`HttpClient`] 发起请求，默认实现基于 reqwest，
+//! 测试中可替换为不经过网络的实现
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::fs::BoxedReader;
+use crate::{LocalSendConfig, ProtocolError};
+
+/// 共享的 HTTP 客户端实现
+pub type HttpClientRef = Arc<dyn HttpClient>;
+
+/// URL 协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Http,
+    Https,
+}
+
+impl Scheme {
+    /// URL 中使用的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+        }
+    }
+}
+
+/// 请求方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpMethod {
+    Get,
+    Post,
+}
+
+/// 请求体
+pub enum HttpBody {
+    /// 无请求体
+    Empty,
+    /// JSON 请求体
+    Json(Vec<u8>),
+    /// 流式请求体及其长度
+    Stream(BoxedReader, u64),
+}
+
+impl Debug for HttpBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpBody::Empty => f.write_str("Empty"),
+            HttpBody::Json(data) => write!(f, "Json({} bytes)", data.len()),
+            HttpBody::Stream(_, len) => write!(f, "Stream({} bytes)", len),
+        }
+    }
+}
+
+/// HTTP 请求
+#[derive(Debug)]
+pub struct HttpRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    pub query: Vec<(String, String)>,
+    pub body: HttpBody,
+    /// 覆盖客户端的请求超时
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    /// 创建 GET 请求
+    pub fn get(url: impl Into<String>) -> Self {
+        Self {
+            method: HttpMethod::Get,
+            url: url.into(),
+            query: Vec::new(),
+            body: HttpBody::Empty,
+            timeout: None,
+        }
+    }
+
+    /// 创建 POST 请求
+    pub fn post(url: impl Into<String>) -> Self {
+        Self {
+            method: HttpMethod::Post,
+            url: url.into(),
+            query: Vec::new(),
+            body: HttpBody::Empty,
+            timeout: None,
+        }
+    }
+
+    /// 添加查询参数
+    pub fn query(mut self, key: &str, value: &str) -> Self {
+        self.query.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// 设置 JSON 请求体
+    pub fn json<T: Serialize>(mut self, value: &T) -> crate::Result<Self> {
+        self.body = HttpBody::Json(serde_json::to_vec(value)?);
+        Ok(self)
+    }
+
+    /// 设置流式请求体
+    pub fn stream(mut self, reader: BoxedReader, len: u64) -> Self {
+        self.body = HttpBody::Stream(reader, len);
+        self
+    }
+
+    /// 设置本次请求的超时
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// HTTP 响应
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// 状态码不是 2xx 时返回错误
+    pub fn error_for_status(self) -> crate::Result<Self> {
+        if (200..300).contains(&self.status) {
+            Ok(self)
+        } else {
+            Err(ProtocolError::Status(self.status))
+        }
+    }
+
+    /// 解析 JSON 响应体
+    pub fn json<T: DeserializeOwned>(&self) -> crate::Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// HTTP 客户端接口
+#[async_trait]
+pub trait HttpClient: Debug + Send + Sync {
+    /// 发送请求并读取完整响应
+    async fn execute(&self, request: HttpRequest) -> crate::Result<HttpResponse>;
+}
+
+/// HTTP 客户端设置
+#[derive(Debug, Clone)]
+pub struct HttpClientConfig {
+    /// 建立连接的超时
+    pub connect_timeout: Duration,
+    /// 整个请求的超时，上传大文件时应保持为空
+    pub timeout: Option<Duration>,
+    /// 接受自签名证书，LocalSend 设备的 HTTPS 证书均为自签名
+    pub accept_invalid_certs: bool,
+    /// 代理地址，例如 `http://127.0.0.1:8080`，`NO_PROXY` 中的主机不经代理。
+    /// 为空时按 `HTTP_PROXY` / `HTTPS_PROXY` / `NO_PROXY` 环境变量选择代理
+    pub proxy: Option<String>,
+    /// 记录每个地址首次出现的证书指纹，之后证书变化时拒绝响应
+    pub pin_certificates: bool,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            timeout: None,
+            accept_invalid_certs: true,
+            proxy: None,
+            pin_certificates: true,
+        }
+    }
+}
+
+impl HttpClientConfig {
+    /// 设置连接超时
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// 设置请求超时
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 设置是否接受自签名证书
+    pub fn with_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// 通过代理发送请求
+    pub fn with_proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// 设置是否固定证书指纹
+    pub fn with_certificate_pinning(mut self, pin: bool) -> Self {
+        self.pin_certificates = pin;
+        self
+    }
+}
+
+/// 基于 reqwest 的 HTTP 客户端
+///
+/// reqwest 客户端内部维护连接池，克隆后共享连接，应在服务内复用同一个实例。
+/// LocalSend 设备使用自签名证书，无法通过 CA 校验，启用指纹固定后
+/// 首次连接某地址时记录证书的 SHA-256，此后该地址出示其他证书时返回 [`ProtocolError::Crypto`]
+#[derive(Debug, Clone)]
+pub struct ReqwestClient {
+    inner: reqwest::Client,
+    /// `主机:端口` 到证书指纹
+    pins: Option<Arc<Mutex<HashMap<String, String>>>>,
+}
+
+impl Default for ReqwestClient {
+    fn default() -> Self {
+        Self::with_config(&HttpClientConfig::default()).expect("创建 HTTP 客户端失败")
+    }
+}
+
+impl ReqwestClient {
+    /// 使用已配置的 reqwest 客户端，不固定证书
+    pub fn new(inner: reqwest::Client) -> Self {
+        Self { inner, pins: None }
+    }
+
+    /// 按设置创建客户端
+    pub fn with_config(config: &HttpClientConfig) -> crate::Result<Self> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .danger_accept_invalid_certs(config.accept_invalid_certs);
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(proxy) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy)?.no_proxy(reqwest::NoProxy::from_env());
+            builder = builder.proxy(proxy);
+        }
+        Ok(Self {
+            inner: builder.tls_info(config.pin_certificates).build()?,
+            pins: config.pin_certificates.then(Default::default),
+        })
+    }
+
+    /// 预先固定地址的证书指纹，例如 LocalSend v2 公告中的 `fingerprint`
+    pub fn pin(&self, authority: &str, fingerprint: &str) {
+        if let Some(pins) = &self.pins {
+            pins.lock()
+                .unwrap()
+                .insert(authority.to_string(), fingerprint.to_ascii_lowercase());
+        }
+    }
+
+    /// 地址已固定的证书指纹
+    pub fn pinned(&self, authority: &str) -> Option<String> {
+        self.pins.as_ref()?.lock().unwrap().get(authority).cloned()
+    }
+
+    /// 校验 HTTPS 响应的证书指纹，首次出现的地址直接记录
+    fn check_pin(&self, response: &reqwest::Response) -> crate::Result<()> {
+        let Some(pins) = &self.pins else {
+            return Ok(());
+        };
+        let Some(certificate) = response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .and_then(|info| info.peer_certificate())
+        else {
+            return Ok(());
+        };
+
+        let url = response.url();
+        let authority = format!(
+            "{}:{}",
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or_default()
+        );
+        let fingerprint = crate::hash::sha256_hex(certificate);
+        let mut pins = pins.lock().unwrap();
+        match pins.get(&authority) {
+            Some(pinned) if *pinned != fingerprint => Err(ProtocolError::Crypto(format!(
+                "{} 的证书与此前记录的指纹不一致",
+                authority
+            ))),
+            Some(_) => Ok(()),
+            None => {
+                pins.insert(authority, fingerprint);
+                Ok(())
+            }
+        }
+    }
+
+    /// 使用默认设置的客户端
+    pub fn shared() -> HttpClientRef {
+        Arc::new(Self::default())
+    }
+
+    /// 按配置中的代理创建客户端，未设置代理时同 [`ReqwestClient::shared`]
+    pub fn for_config(config: &LocalSendConfig) -> HttpClientRef {
+        let Some(proxy) = &config.proxy else {
+            return Self::shared();
+        };
+        match Self::with_config(&HttpClientConfig::default().with_proxy(proxy)) {
+            Ok(client) => Arc::new(client),
+            Err(e) => {
+                tracing::warn!("代理 {} 无效，改为直接连接: {}", proxy, e);
+                Self::shared()
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl HttpClient for ReqwestClient {
+    async fn execute(&self, request: HttpRequest) -> crate::Result<HttpResponse> {
+        let mut builder = match request.method {
+            HttpMethod::Get => self.inner.get(&request.url),
+            HttpMethod::Post => self.inner.post(&request.url),
+        };
+        if !request.query.is_empty() {
+            builder = builder.query(&request.query);
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        builder = match request.body {
+            HttpBody::Empty => builder,
+            HttpBody::Json(data) => builder
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(data),
+            HttpBody::Stream(reader, len) => builder
+                .header(reqwest::header::CONTENT_LENGTH, len)
+                .body(reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(reader))),
+        };
+
+        let response = builder.send().await?;
+        self.check_pin(&response)?;
+        let status = response.status().as_u16();
+        let body = response.bytes().await?.to_vec();
+        Ok(HttpResponse { status, body })
+    }
+}
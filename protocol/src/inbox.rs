@@ -0,0 +1,198 @@
+//! 收到的文本收件箱
+//!
+//! 经消息接口收到的文本和剪贴板片段只发布为 `TextReceived` 事件，
+//! 没有界面订阅事件时随即丢失。[`Inbox::run`] 订阅事件总线，
+//! 把收到的文本与审计日志等历史记录一起保存在配置目录的 `inbox.json` 中，
+//! 供命令行和图形界面稍后查看、复制或删除。
+//!
+//! 最多保留 [`INBOX_CAPACITY`] 条，超出时丢弃最早的文本。
+//! 每次操作都重新读取文件，守护进程收到的新文本无需重启即可看到
+
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::{LocalSendConfig, ProtocolError, ProtocolEvent};
+
+/// 收件箱文件名，与共享配置文件位于同一目录
+const INBOX_FILE_NAME: &str = "inbox.json";
+
+/// 保留的文本条数上限
+pub const INBOX_CAPACITY: usize = 200;
+
+/// 收到的一条文本
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboxMessage {
+    /// 序号，从 1 开始，删除后不再复用
+    pub id: u64,
+    /// 收到的时间，Unix 秒
+    pub received_at: u64,
+    /// 对方的设备 ID
+    pub sender_id: String,
+    /// 对方的设备名称
+    pub sender: String,
+    pub text: String,
+}
+
+impl InboxMessage {
+    /// 文本的第一行，过长时截断，用于列表显示
+    pub fn preview(&self, max_chars: usize) -> String {
+        let line = self.text.lines().next().unwrap_or_default();
+        let mut preview: String = line.chars().take(max_chars).collect();
+        if preview.len() < self.text.len() {
+            preview.push('…');
+        }
+        preview
+    }
+}
+
+/// 收件箱文件的内容
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InboxFile {
+    /// 最近分配的序号
+    last_id: u64,
+    /// 按收到的顺序排列
+    messages: Vec<InboxMessage>,
+}
+
+/// 文本收件箱
+///
+/// 每次操作都重新读取文件，进程间共享同一份收件箱
+#[derive(Debug)]
+pub struct Inbox {
+    path: PathBuf,
+    lock: std::sync::Mutex<()>,
+}
+
+impl Inbox {
+    /// 使用指定的收件箱文件
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: std::sync::Mutex::new(()),
+        }
+    }
+
+    /// 与共享配置文件位于同一目录的收件箱文件
+    pub fn default_path() -> Option<PathBuf> {
+        Some(LocalSendConfig::default_path()?.with_file_name(INBOX_FILE_NAME))
+    }
+
+    /// 打开默认位置的收件箱
+    pub fn open_default() -> crate::Result<Self> {
+        let path = Self::default_path()
+            .ok_or_else(|| ProtocolError::InvalidConfig("无法确定配置目录".to_string()))?;
+        Ok(Self::new(path))
+    }
+
+    /// 保存收到的文本，超出上限时丢弃最早的文本
+    pub fn push(&self, sender_id: &str, sender: &str, text: &str) -> crate::Result<InboxMessage> {
+        self.update(|inbox| {
+            inbox.last_id += 1;
+            let message = InboxMessage {
+                id: inbox.last_id,
+                received_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                sender_id: sender_id.to_string(),
+                sender: sender.to_string(),
+                text: text.to_string(),
+            };
+            inbox.messages.push(message.clone());
+            let excess = inbox.messages.len().saturating_sub(INBOX_CAPACITY);
+            inbox.messages.drain(..excess);
+            message
+        })
+    }
+
+    /// 所有文本，按收到的顺序排列
+    pub fn list(&self) -> crate::Result<Vec<InboxMessage>> {
+        Ok(self.load()?.messages)
+    }
+
+    /// 按序号查找文本
+    pub fn get(&self, id: u64) -> crate::Result<Option<InboxMessage>> {
+        Ok(self.load()?.messages.into_iter().find(|m| m.id == id))
+    }
+
+    /// 删除文本，序号不存在时返回 false
+    pub fn delete(&self, id: u64) -> crate::Result<bool> {
+        self.update(|inbox| {
+            let before = inbox.messages.len();
+            inbox.messages.retain(|m| m.id != id);
+            inbox.messages.len() != before
+        })
+    }
+
+    /// 保存事件总线上收到的文本，直到事件总线关闭或 cancel 取消
+    pub async fn run(
+        self,
+        mut events: broadcast::Receiver<ProtocolEvent>,
+        cancel: CancellationToken,
+    ) {
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = events.recv() => event,
+            };
+            match event {
+                Ok(ProtocolEvent::TextReceived {
+                    sender_id,
+                    sender,
+                    text,
+                }) => {
+                    if let Err(e) = self.push(&sender_id, &sender, &text) {
+                        warn!(error = %e, "保存收到的文本失败");
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "收件箱处理过慢，丢失部分事件");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// 在锁内读取、修改并保存收件箱
+    fn update<T>(&self, f: impl FnOnce(&mut InboxFile) -> T) -> crate::Result<T> {
+        let _guard = self.lock.lock().unwrap();
+        let mut inbox = self.load()?;
+        let result = f(&mut inbox);
+        self.save(&inbox)?;
+        Ok(result)
+    }
+
+    fn load(&self) -> crate::Result<InboxFile> {
+        match std::fs::read(&self.path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(InboxFile::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 先写临时文件再替换，避免其他进程读到写了一半的文件
+    fn save(&self, inbox: &InboxFile) -> crate::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::write(&tmp, serde_json::to_vec_pretty(inbox)?)?;
+
+        // 文本可能包含密码等敏感内容，仅允许当前用户读取
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
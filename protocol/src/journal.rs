@@ -0,0 +1,392 @@
+//! 进行中会话的预写日志
+//!
+//! 接收服务接受请求后，在写入任何数据之前先记录会话描述 (对方及每个文件的保存路径)；
+//! 每个文件完成或中断时记录已写入的偏移，所有文件完成后记录会话结束。每条记录立即落盘。
+//!
+//! 文件先写入同目录的 `.part` 文件 (见 [`storage::part_path`])，完成后才改为正式文件名。
+//! 进程崩溃或断电后，[`SessionJournal::incomplete`] 重放日志，列出未结束的会话和
+//! 各文件已写入的字节数，可保留 `.part` 文件以便续传，或用 [`SessionJournal::discard`] 清理。
+//!
+//! 日志是只追加的 JSONL 文件，打开时改写为只含未结束的会话，不会无限增长
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::storage;
+use crate::{LocalSendConfig, ProtocolError};
+
+/// 日志文件名，与共享配置文件位于同一目录
+const JOURNAL_FILE_NAME: &str = "journal.jsonl";
+
+/// 日志中的文件描述
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalFile {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// 完成后的保存路径
+    pub path: PathBuf,
+}
+
+/// 日志中的一行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+enum JournalRecord {
+    /// 接受请求，开始接收
+    Begin {
+        session_id: String,
+        peer: String,
+        peer_name: String,
+        files: Vec<JournalFile>,
+    },
+    /// 文件已写入 offset 字节，等于文件大小时文件已完成
+    Offset {
+        session_id: String,
+        file_id: String,
+        offset: u64,
+    },
+    /// 会话结束，包括完成和放弃
+    End { session_id: String },
+}
+
+impl JournalRecord {
+    fn session_id(&self) -> &str {
+        match self {
+            JournalRecord::Begin { session_id, .. }
+            | JournalRecord::Offset { session_id, .. }
+            | JournalRecord::End { session_id } => session_id,
+        }
+    }
+}
+
+/// 未结束的会话
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncompleteSession {
+    pub session_id: String,
+    /// 对方的设备 ID
+    pub peer: String,
+    pub peer_name: String,
+    pub files: Vec<IncompleteFile>,
+}
+
+impl IncompleteSession {
+    /// 已写入的字节数
+    pub fn received_bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.offset).sum()
+    }
+
+    /// 总字节数
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.file.size).sum()
+    }
+
+    /// 会话中的文件未完成时写入的 `.part` 文件
+    pub fn part_path(&self, file: &IncompleteFile) -> PathBuf {
+        storage::part_path(&file.file.path, &self.session_id, &file.file.id)
+    }
+}
+
+/// 未结束会话中的文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncompleteFile {
+    pub file: JournalFile,
+    /// 已写入的字节数，以 `.part` 文件的实际长度为准，文件不存在时取日志中的偏移
+    pub offset: u64,
+}
+
+impl IncompleteFile {
+    /// 是否已完成
+    pub fn is_complete(&self) -> bool {
+        self.offset >= self.file.size
+    }
+}
+
+/// 会话日志
+///
+/// 克隆后写入同一个文件
+#[derive(Debug, Clone)]
+pub struct SessionJournal {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    path: PathBuf,
+    /// 未结束的会话中尚未完成的文件，用于判断何时记录会话结束
+    pending: HashMap<String, HashMap<String, u64>>,
+}
+
+impl SessionJournal {
+    /// 打开日志，丢弃已结束的会话
+    pub fn open(path: impl Into<PathBuf>) -> crate::Result<Self> {
+        let path = path.into();
+        let records = live_records(&read_records(&path)?);
+        let mut pending: HashMap<String, HashMap<String, u64>> = HashMap::new();
+        for record in &records {
+            match record {
+                JournalRecord::Begin {
+                    session_id, files, ..
+                } => {
+                    let files = files.iter().map(|f| (f.id.clone(), f.size)).collect();
+                    pending.insert(session_id.clone(), files);
+                }
+                JournalRecord::Offset {
+                    session_id,
+                    file_id,
+                    offset,
+                } => {
+                    if let Some(files) = pending.get_mut(session_id) {
+                        if files.get(file_id).is_some_and(|size| offset >= size) {
+                            files.remove(file_id);
+                        }
+                    }
+                }
+                JournalRecord::End { .. } => {}
+            }
+        }
+        if path.exists() {
+            rewrite(&path, &records)?;
+        }
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Inner { path, pending })),
+        })
+    }
+
+    /// 与共享配置文件位于同一目录的日志文件
+    pub fn default_path() -> Option<PathBuf> {
+        Some(LocalSendConfig::default_path()?.with_file_name(JOURNAL_FILE_NAME))
+    }
+
+    /// 打开默认位置的日志
+    pub fn open_default() -> crate::Result<Self> {
+        let path = Self::default_path()
+            .ok_or_else(|| ProtocolError::InvalidConfig("无法确定配置目录".to_string()))?;
+        Self::open(path)
+    }
+
+    /// 日志文件路径
+    pub fn path(&self) -> PathBuf {
+        self.inner.lock().unwrap().path.clone()
+    }
+
+    /// 记录开始接收的会话，files 为需要传输的文件
+    pub fn begin(
+        &self,
+        session_id: &str,
+        peer: &str,
+        peer_name: &str,
+        files: Vec<JournalFile>,
+    ) -> crate::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        let sizes = files.iter().map(|f| (f.id.clone(), f.size)).collect();
+        append(
+            &inner.path,
+            &JournalRecord::Begin {
+                session_id: session_id.to_string(),
+                peer: peer.to_string(),
+                peer_name: peer_name.to_string(),
+                files,
+            },
+        )?;
+        inner.pending.insert(session_id.to_string(), sizes);
+        Ok(())
+    }
+
+    /// 记录文件已写入的字节数，会话的所有文件都完成时记录会话结束
+    pub fn offset(&self, session_id: &str, file_id: &str, offset: u64) -> crate::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        append(
+            &inner.path,
+            &JournalRecord::Offset {
+                session_id: session_id.to_string(),
+                file_id: file_id.to_string(),
+                offset,
+            },
+        )?;
+        let Some(files) = inner.pending.get_mut(session_id) else {
+            return Ok(());
+        };
+        if files.get(file_id).is_some_and(|size| offset >= *size) {
+            files.remove(file_id);
+        }
+        if files.is_empty() {
+            inner.pending.remove(session_id);
+            append(
+                &inner.path,
+                &JournalRecord::End {
+                    session_id: session_id.to_string(),
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// 写入记录，失败只记录日志，不影响传输
+    pub fn offset_or_warn(&self, session_id: &str, file_id: &str, offset: u64) {
+        if let Err(e) = self.offset(session_id, file_id, offset) {
+            warn!(error = %e, "写入会话日志失败");
+        }
+    }
+
+    /// 重放日志，列出未结束的会话
+    pub fn incomplete(&self) -> crate::Result<Vec<IncompleteSession>> {
+        let path = self.path();
+        let mut sessions: Vec<IncompleteSession> = Vec::new();
+        for record in live_records(&read_records(&path)?) {
+            match record {
+                JournalRecord::Begin {
+                    session_id,
+                    peer,
+                    peer_name,
+                    files,
+                } => sessions.push(IncompleteSession {
+                    session_id,
+                    peer,
+                    peer_name,
+                    files: files
+                        .into_iter()
+                        .map(|file| IncompleteFile { file, offset: 0 })
+                        .collect(),
+                }),
+                JournalRecord::Offset {
+                    session_id,
+                    file_id,
+                    offset,
+                } => {
+                    let file = sessions
+                        .iter_mut()
+                        .filter(|s| s.session_id == session_id)
+                        .flat_map(|s| s.files.iter_mut())
+                        .find(|f| f.file.id == file_id);
+                    if let Some(file) = file {
+                        file.offset = offset;
+                    }
+                }
+                JournalRecord::End { .. } => {}
+            }
+        }
+        for session in &mut sessions {
+            for file in session.files.iter_mut().filter(|f| !f.is_complete()) {
+                let part = storage::part_path(&file.file.path, &session.session_id, &file.file.id);
+                if let Ok(metadata) = std::fs::metadata(part) {
+                    file.offset = metadata.len().min(file.file.size);
+                }
+            }
+        }
+        Ok(sessions)
+    }
+
+    /// 放弃未结束的会话：删除其未完成文件的 `.part` 文件并记录会话结束，
+    /// 返回删除的文件数，会话不存在或已结束时返回 None
+    pub fn discard(&self, session_id: &str) -> crate::Result<Option<usize>> {
+        let Some(session) = self
+            .incomplete()?
+            .into_iter()
+            .find(|s| s.session_id == session_id)
+        else {
+            return Ok(None);
+        };
+        let mut removed = 0;
+        for file in session.files.iter().filter(|f| !f.is_complete()) {
+            match std::fs::remove_file(session.part_path(file)) {
+                Ok(()) => removed += 1,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let mut inner = self.inner.lock().unwrap();
+        append(
+            &inner.path,
+            &JournalRecord::End {
+                session_id: session_id.to_string(),
+            },
+        )?;
+        inner.pending.remove(session_id);
+        Ok(Some(removed))
+    }
+}
+
+fn read_records(path: &Path) -> crate::Result<Vec<JournalRecord>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            // 断电时最后一行可能只写了一半
+            Err(e) => warn!(error = %e, "忽略会话日志中无法解析的行"),
+        }
+    }
+    Ok(records)
+}
+
+/// 只保留未结束会话的记录
+fn live_records(records: &[JournalRecord]) -> Vec<JournalRecord> {
+    let ended: std::collections::HashSet<&str> = records
+        .iter()
+        .filter_map(|r| match r {
+            JournalRecord::End { session_id } => Some(session_id.as_str()),
+            _ => None,
+        })
+        .collect();
+    records
+        .iter()
+        .filter(|r| !ended.contains(r.session_id()))
+        .cloned()
+        .collect()
+}
+
+fn open_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    // 日志包含对方设备和文件名，仅允许当前用户读取
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+fn append(path: &Path, record: &JournalRecord) -> crate::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = open_options().create(true).append(true).open(path)?;
+    file.write_all(format!("{}\n", serde_json::to_string(record)?).as_bytes())?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// 原子地改写日志：先写临时文件再替换
+fn rewrite(path: &Path, records: &[JournalRecord]) -> crate::Result<()> {
+    let tmp = path.with_extension("jsonl.tmp");
+    {
+        let mut file = open_options().create(true).write(true).truncate(true).open(&tmp)?;
+        for record in records {
+            file.write_all(format!("{}\n", serde_json::to_string(record)?).as_bytes())?;
+        }
+        file.sync_data()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
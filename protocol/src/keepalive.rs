@@ -0,0 +1,150 @@
+//! 文件流保活
+//!
+//! 长时间的传输中对端可能悄然掉线 (笔记本休眠、手机切换网络)，连接不会报错，
+//! 发送和接收都会一直挂起，直到很久之后才超时。
+//!
+//! 经传输流接收文件时，接收端从读到头部起每隔 [`KEEPALIVE_INTERVAL`] 向发送端回写一个
+//! 保活字节，直到处理完文件、关闭流作为确认。发送端经 [`Liveness`] 读取保活字节，
+//! 接收端经 [`IdleTimeout`] 读取文件内容，超过 [`LIVENESS_TIMEOUT`] 没有收到对方的数据
+//! 即认为对方已掉线，中止该文件流并返回 [`io::ErrorKind::TimedOut`]，会话转为错误状态，
+//! 已写入的 `.part` 文件保留用于续传。
+//!
+//! 旧版本接收端不回写保活字节，发送端在收到第一个保活字节之前不做检测。
+//! 裸 TCP 连接另外经 [`set_tcp_keepalive`] 启用系统的保活探测，
+//! sendfile 零拷贝发送卡住时由内核中止连接
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::time::Sleep;
+
+/// 接收端回写保活字节的间隔
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 超过此时长没有收到对方的数据即认为对方已掉线
+pub const LIVENESS_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 保活字节
+const PING: u8 = 0;
+
+/// 对方掉线时返回的错误
+pub fn timed_out() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("对方 {} 秒无响应，可能已掉线", LIVENESS_TIMEOUT.as_secs()),
+    )
+}
+
+/// 是否为对方掉线导致的错误
+pub fn is_timed_out(error: &crate::ProtocolError) -> bool {
+    matches!(error, crate::ProtocolError::Io(e) if e.kind() == io::ErrorKind::TimedOut)
+}
+
+/// 每隔 [`KEEPALIVE_INTERVAL`] 写入一个保活字节，第一个立即写入
+///
+/// 不会返回，写入失败后停止写入，掉线由读取端的超时发现
+pub async fn ping<W>(writer: &mut W)
+where
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut interval = tokio::time::interval(KEEPALIVE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if writer.write_all(&[PING]).await.is_err() || writer.flush().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// 发送端对接收端的存活检测，读取接收端回写的保活字节
+#[derive(Debug)]
+pub struct Liveness<R> {
+    reader: R,
+    alive: bool,
+}
+
+impl<R: AsyncRead + Unpin> Liveness<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, alive: false }
+    }
+
+    /// 读取保活字节，直到接收端关闭流
+    ///
+    /// 收到第一个保活字节后，超过 [`LIVENESS_TIMEOUT`] 没有新数据即返回 [`timed_out`]。
+    /// 可安全地取消后再次调用
+    pub async fn wait(&mut self) -> io::Result<()> {
+        let mut buf = [0u8; 64];
+        loop {
+            let read = if self.alive {
+                tokio::time::timeout(LIVENESS_TIMEOUT, self.reader.read(&mut buf))
+                    .await
+                    .map_err(|_| timed_out())??
+            } else {
+                self.reader.read(&mut buf).await?
+            };
+            if read == 0 {
+                return Ok(());
+            }
+            self.alive = true;
+        }
+    }
+}
+
+/// 等待数据超过 [`LIVENESS_TIMEOUT`] 时返回 [`timed_out`] 的读取端
+///
+/// 只计算读取端实际等待数据的时间，调用方 (例如限速) 推迟读取的时间不计在内
+#[derive(Debug)]
+pub struct IdleTimeout<R> {
+    inner: R,
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<R> IdleTimeout<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, deadline: None }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for IdleTimeout<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                this.deadline = None;
+                Poll::Ready(result)
+            }
+            Poll::Pending => {
+                let deadline = this
+                    .deadline
+                    .get_or_insert_with(|| Box::pin(tokio::time::sleep(LIVENESS_TIMEOUT)));
+                match deadline.as_mut().poll(cx) {
+                    Poll::Ready(()) => Poll::Ready(Err(timed_out())),
+                    Poll::Pending => Poll::Pending,
+                }
+            }
+        }
+    }
+}
+
+/// 为 TCP 连接启用系统的保活探测
+///
+/// 连接空闲 [`KEEPALIVE_INTERVAL`] 后开始探测；Linux 上已发送的数据超过
+/// [`LIVENESS_TIMEOUT`] 未被确认时由内核中止连接
+pub fn set_tcp_keepalive(stream: &tokio::net::TcpStream) -> io::Result<()> {
+    let socket = socket2::SockRef::from(stream);
+    let keepalive = socket2::TcpKeepalive::new().with_time(KEEPALIVE_INTERVAL);
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    let keepalive = keepalive.with_interval(KEEPALIVE_INTERVAL);
+    socket.set_tcp_keepalive(&keepalive)?;
+    #[cfg(target_os = "linux")]
+    socket.set_tcp_user_timeout(Some(LIVENESS_TIMEOUT))?;
+    Ok(())
+}
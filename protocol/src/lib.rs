@@ -3,15 +3,92 @@
 //! 此模块实现了与 LocalSend 客户端互通的文件传输协议
 //! 包括：设备发现、文件传输、会话管理等功能
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod audit;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod config;
+#[cfg(feature = "client")]
+pub mod clipboard;
+#[cfg(feature = "client")]
+pub mod control;
+#[cfg(all(feature = "http", not(target_arch = "wasm32")))]
+pub mod diagnose;
 pub mod dto;
+pub mod error;
+pub mod event;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fingerprint;
+pub mod flood;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod fs;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hash;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hooks;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod inbox;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod journal;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod keepalive;
+#[cfg(feature = "logging")]
+pub mod logging;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod metered;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod metrics;
+pub mod mime;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod net;
+#[cfg(feature = "client")]
+pub mod outbox;
+#[cfg(feature = "pairdrop")]
+pub mod pairdrop;
+pub mod pairing;
+#[cfg(feature = "crypto")]
 pub mod crypto;
+pub mod progress;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod retention;
+#[cfg(feature = "client")]
+pub mod routes;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod screenshot;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod session;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod storage;
+pub mod summary;
+#[cfg(feature = "test-util")]
+pub mod testing;
+#[cfg(feature = "discovery")]
 pub mod discovery;
+#[cfg(feature = "server")]
 pub mod server;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod throttle;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod transport;
+#[cfg(all(feature = "http", not(target_arch = "wasm32")))]
+pub mod webhook;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+#[cfg(feature = "client")]
+pub use client::{LocalSendClient, ReceiveHandle};
 pub use dto::AnnouncementMessage;
+pub use error::{ProtocolError, Result};
+pub use event::{EventBus, ProtocolEvent, ReceiveEvent};
+pub use flood::{FloodGuard, FloodLimits, FloodStats};
+pub use progress::{ProgressTracker, ThroughputSample};
+pub use summary::SessionSummary;
 
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
 use tokio::sync::Mutex;
 
 /// 块大小 (1MB)
@@ -23,34 +100,494 @@ pub const DEFAULT_PORT: u16 = 53317;
 pub const ANNOUNCEMENT_INTERVAL_MS: u64 = 5000;
 pub const SESSION_TIMEOUT_SECS: u64 = 300;
 
+/// 默认隔离的扩展名，打开即可执行代码的文件类型
+pub const DEFAULT_QUARANTINE_EXTENSIONS: &[&str] = &[
+    "exe", "msi", "com", "scr", "pif", "bat", "cmd", "ps1", "vbs", "vbe", "js", "jse", "wsf",
+    "hta", "cpl", "jar", "lnk", "reg",
+];
+
 /// LocalSend 客户端配置
-#[derive(Clone, Debug)]
+///
+/// 通过 [`LocalSendConfig::builder`] 构造可获得校验
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, derive_builder::Builder)]
+#[serde(default)]
+#[builder(default, setter(into), build_fn(private, name = "build_unchecked"))]
 pub struct LocalSendConfig {
     pub device_id: String,
     pub device_name: String,
     pub device_type: String,
     pub api_key: String,
     pub port: u16,
+    /// 接收服务监听的地址族，默认双栈
+    pub ip_mode: IpMode,
+    /// 接收服务以 HTTPS 提供 LocalSend 接口，需启用 `tls` 特性，自签名证书保存在配置目录
     pub use_tls: bool,
     pub download_dir: String,
+    /// 随公告广播的额外传输，例如 `"quic"`
+    pub transports: Vec<String>,
+    /// 随公告广播的候选地址，按优先顺序，例如局域网地址和 EasyTier 虚拟 IP。
+    /// UDP 公告中还会附上发往多播组所用网卡的地址
+    pub addresses: Vec<String>,
+    /// 按设备指定的上传途径优先顺序，设备 ID 到途径列表，途径见 [`config::ROUTE_NAMES`]。
+    /// 发送时依次尝试，无法连接时回退到下一项；未指定的设备按实测速度排序，见 [`routes`]
+    pub route_preferences: BTreeMap<String, Vec<String>>,
+    /// 快速保存: 收到请求时不询问用户，直接接受
+    pub quick_save: QuickSave,
+    /// 收藏的设备 ID，视为受信任的设备，快速保存为 [`QuickSave::Favorites`] 时自动接受。
+    /// 可经 [`pairing`] 确认指纹后加入
+    pub favorites: Vec<String>,
+    /// 允许交互的设备，为空时允许所有未屏蔽的设备，见 [`LocalSendConfig::allows_device`]
+    pub allowlist: Vec<String>,
+    /// 屏蔽的设备，既不接收其请求，也不在发现结果中列出
+    pub blocklist: Vec<String>,
+    /// 命名的设备组，组名到设备的 ID、名称、IP 或主机名。
+    /// 发送时以 `@组名` 指定，向组内所有设备发送，见 [`LocalSendConfig::expand_target`]
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// 接收 PIN，发送方需在请求中提供，何时要求见 [`LocalSendConfig::requires_pin`]
+    pub pin: Option<String>,
+    /// 哪些设备需要提供 PIN
+    pub pin_policy: PinPolicy,
+    /// 无论策略如何都需要 PIN 的设备，格式同允许列表
+    pub pin_devices: Vec<String>,
+    /// 记录传输审计日志，保存在配置目录的 `audit.jsonl`
+    pub audit_log: bool,
+    /// 隔离的扩展名，这类文件保存到下载目录的 `quarantine` 子目录并去掉执行权限，
+    /// 为空时不隔离
+    pub quarantine_extensions: Vec<String>,
+    /// 单个文件的大小上限 (字节)，为空时不限
+    pub max_file_size: Option<u64>,
+    /// 单次接收的总大小上限 (字节)，为空时不限
+    pub max_session_size: Option<u64>,
+    /// 单次接收的文件数上限，为空时不限
+    pub max_files: Option<usize>,
+    /// 下载目录清理策略，为空时不清理
+    pub retention: Option<RetentionConfig>,
+    /// 对方声明的 SHA-256 与已接收的文件相同时的处理方式，
+    /// 已接收文件的哈希记录在配置目录的 `hashes.jsonl`
+    pub duplicate_policy: DuplicatePolicy,
+    /// 下载目录中已有同名文件时的处理方式，默认追加序号另存
+    pub collision_policy: CollisionPolicy,
+    /// 发送符号链接时的处理方式，也决定是否保留收到的链接
+    pub symlink_policy: SymlinkPolicy,
+    /// 已接收文件浏览页面的端口，为空时不提供，需要启用 `browse` 特性
+    pub browse_port: Option<u16>,
+    /// 浏览页面的 Basic 认证密码，启用浏览页面时必须设置
+    pub browse_password: Option<String>,
+    /// 分享链接服务的端口，为空时不提供，需要启用 `share` 特性
+    pub share_port: Option<u16>,
+    /// 接收守护进程提供 Prometheus `/metrics` 的端口，为空时不提供
+    pub metrics_port: Option<u16>,
+    /// 管理 API 的端口，为空时不提供，需要启用 `api` 特性
+    pub api_port: Option<u16>,
+    /// 管理 API 的访问令牌，启用管理 API 时必须设置
+    pub api_token: Option<String>,
+    /// 发件箱目录，放入的文件自动发送给对应设备
+    pub outboxes: Vec<OutboxConfig>,
+    /// 与一台已配对设备同步剪贴板文本，为空时不同步
+    pub clipboard_sync: Option<ClipboardSyncConfig>,
+    /// 自动解压收到的 zip、tar.gz 压缩包，为空时不解压，需要启用 `extract` 特性
+    pub extract: Option<ExtractConfig>,
+    /// 每个文件或会话接收完成后执行的命令
+    pub hooks: Vec<HookConfig>,
+    /// 接收的会话完成后在下载目录写入 `sha256sum` 格式的哈希清单
+    pub hash_manifest: bool,
+    /// 接收服务在传输生命周期中调用的 Webhook
+    pub webhooks: Vec<WebhookConfig>,
+    /// 发布发现和传输事件的 MQTT 代理，需要启用 `mqtt` 特性
+    pub mqtt: Option<MqttConfig>,
+    /// 发送和接收合计的带宽上限 (字节/秒)，为空时不限速
+    pub bandwidth_limit: Option<u64>,
+    /// 按本地时间生效的带宽时段，第一条包含当前时间的时段优先于 `bandwidth_limit`
+    pub bandwidth_schedule: Vec<BandwidthWindow>,
+    /// 按流量计费或使用电池时的省流模式，为空时不启用
+    pub metered: Option<MeteredConfig>,
+    /// 发现扫描和发送使用的 HTTP 代理，如 `http://proxy.example:3128`。
+    /// 为空时按 `HTTP_PROXY` / `HTTPS_PROXY` / `NO_PROXY` 环境变量选择
+    pub proxy: Option<String>,
+}
+
+/// 带宽时段，例如工作时间限速、夜间不限速
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BandwidthWindow {
+    /// 开始时间，本地时间 `HH:MM`
+    pub start: String,
+    /// 结束时间 (不含)，早于开始时间时跨越午夜
+    pub end: String,
+    /// 时段内的带宽上限 (字节/秒)，为空时不限速
+    #[serde(default)]
+    pub limit: Option<u64>,
+}
+
+/// 省流模式配置
+///
+/// 省流模式下暂缓发送发件箱中的大文件、降低公告频率并限制带宽，退出后自动恢复
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MeteredConfig {
+    /// 何时进入省流模式
+    pub mode: MeteredMode,
+    /// 省流模式下的带宽上限 (字节/秒)，与其他上限同时生效时取较小者，为空时不额外限速
+    pub bandwidth_limit: Option<u64>,
+    /// 省流模式下暂缓发送大于此大小 (字节) 的发件箱文件，为空时不暂缓
+    pub max_file_size: Option<u64>,
+    /// 省流模式下的公告间隔 (秒)
+    pub announce_interval_secs: u64,
+}
+
+impl Default for MeteredConfig {
+    fn default() -> Self {
+        Self {
+            mode: MeteredMode::Auto,
+            bandwidth_limit: Some(1024 * 1024),
+            max_file_size: Some(50 * 1024 * 1024),
+            announce_interval_secs: 30,
+        }
+    }
+}
+
+/// 进入省流模式的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MeteredMode {
+    /// 按系统状态自动检测，支持 Windows 的按流量计费网络和电池供电、macOS 的电池供电，
+    /// 其他平台上不会自动进入
+    #[default]
+    Auto,
+    /// 始终处于省流模式
+    On,
+    /// 不进入省流模式
+    Off,
+}
+
+/// MQTT 配置
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MqttConfig {
+    /// 代理地址，例如 `mqtt://homeassistant.local:1883`
+    pub broker: String,
+    /// 主题前缀
+    #[serde(default = "default_mqtt_topic")]
+    pub topic: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+fn default_mqtt_topic() -> String {
+    "peersend".to_string()
+}
+
+/// Webhook 配置
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct WebhookConfig {
+    /// 接收 POST 请求的地址
+    pub url: String,
+    /// 订阅的事件，为空时订阅全部
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    /// 请求体模板，为空时发送默认 JSON
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// 接收完成后执行的命令
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HookConfig {
+    /// 命令模板，例如 `import.sh {{path}} {{sender}}`，占位符见 [`hooks`] 模块
+    pub command: String,
+    /// 执行时机
+    #[serde(default)]
+    pub on: HookTrigger,
+    /// 超时 (秒)，超时后结束命令
+    #[serde(default = "default_hook_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_hook_timeout_secs() -> u64 {
+    60
+}
+
+/// 命令的执行时机
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookTrigger {
+    /// 每个文件接收完成后
+    #[default]
+    File,
+    /// 会话中的全部文件接收完成后
+    Session,
+}
+
+/// Webhook 事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEvent {
+    /// 收到文件发送请求
+    Requested,
+    /// 会话完成
+    Completed,
+    /// 会话被取消或出错
+    Failed,
+}
+
+impl WebhookEvent {
+    /// 配置和模板中使用的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::Requested => "requested",
+            WebhookEvent::Completed => "completed",
+            WebhookEvent::Failed => "failed",
+        }
+    }
+}
+
+/// 下载目录清理策略，由接收守护进程定期执行
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// 清理修改时间早于此天数的文件，为空时不按时间清理
+    pub max_age_days: Option<u32>,
+    /// 下载目录的总大小上限 (字节)，超出时从最旧的文件开始清理，为空时不限
+    pub max_size: Option<u64>,
+    /// 删除还是归档
+    pub action: RetentionAction,
+    /// 归档目录，为空时使用下载目录中的 `archive` 子目录
+    pub archive_dir: Option<String>,
+    /// 试运行，只记录将清理的文件
+    pub dry_run: bool,
+}
+
+/// 清理文件的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    /// 删除文件
+    #[default]
+    Delete,
+    /// 移入归档目录
+    Archive,
+}
+
+/// 发件箱配置
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct OutboxConfig {
+    /// 监视的目录
+    pub dir: String,
+    /// 目标设备的 ID、名称、IP 或主机名
+    pub device: String,
+}
+
+/// 剪贴板同步配置
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ClipboardSyncConfig {
+    /// 同步的设备的 ID、名称、IP 或主机名，须已配对 (在收藏中)
+    pub device: String,
+    /// 同步的文本长度上限 (字节)，更长的文本既不发送也不接受
+    #[serde(default = "default_clipboard_max_bytes")]
+    pub max_bytes: usize,
+}
+
+fn default_clipboard_max_bytes() -> usize {
+    64 * 1024
+}
+
+/// 自动解压配置
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ExtractConfig {
+    /// 解压出的文件总大小上限 (字节)，超出时放弃解压
+    #[serde(default = "default_extract_max_size")]
+    pub max_size: u64,
+    /// 压缩包条目数量上限，超出时放弃解压
+    #[serde(default = "default_extract_max_entries")]
+    pub max_entries: usize,
+    /// 解压成功后删除压缩包
+    #[serde(default)]
+    pub delete_archive: bool,
+}
+
+impl Default for ExtractConfig {
+    fn default() -> Self {
+        Self {
+            max_size: default_extract_max_size(),
+            max_entries: default_extract_max_entries(),
+            delete_archive: false,
+        }
+    }
+}
+
+fn default_extract_max_size() -> u64 {
+    4 * 1024 * 1024 * 1024
+}
+
+fn default_extract_max_entries() -> usize {
+    10_000
+}
+
+/// 监听的地址族
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IpMode {
+    /// 同时接受 IPv4 和 IPv6，本机不支持 IPv6 时只用 IPv4
+    #[default]
+    Dual,
+    /// 仅 IPv4
+    V4Only,
+    /// 仅 IPv6
+    V6Only,
+}
+
+/// 快速保存模式，与官方 LocalSend 的同名设置一致
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuickSave {
+    /// 每次请求都需要确认
+    #[default]
+    Off,
+    /// 仅自动接受收藏的设备
+    Favorites,
+    /// 自动接受所有设备
+    On,
+}
+
+/// 收到与已接收文件相同 (SHA-256 一致) 的文件时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+    /// 照常接收，另存一份
+    #[default]
+    Duplicate,
+    /// 不接收，保留已有的文件
+    Skip,
+    /// 不接收，在下载目录中创建指向已有文件的硬链接
+    Link,
+}
+
+/// 下载目录中已有同名文件时的处理方式
+///
+/// 服务器按 [`LocalSendConfig::collision_policy`] 处理，
+/// [`FileReceiver`](session::FileReceiver) 另行设置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// 在文件名后追加序号另存，如 `photo (1).jpg`
+    #[default]
+    Rename,
+    /// 覆盖已有的文件
+    Overwrite,
+    /// 不保存，保留已有的文件；服务器在 prepare-upload 时不为该文件发放令牌
+    Skip,
+}
+
+/// 符号链接的处理方式
+///
+/// 管道、套接字和设备文件无论如何都不发送，在会话的 `warnings` 中列出
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkPolicy {
+    /// 发送链接指向的文件，指向不存在的文件或非普通文件时跳过
+    #[default]
+    Follow,
+    /// 不发送链接；接收时也不保留对方发来的链接
+    Skip,
+    /// 对方是 PeerSend 时只发送链接本身，对方在下载目录中重建链接；
+    /// 对方不是 PeerSend 时与 `follow` 相同
+    Preserve,
+}
+
+/// PIN 策略，设置了 PIN 时生效
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PinPolicy {
+    /// 所有设备都需要 PIN，与官方 LocalSend 一致
+    #[default]
+    All,
+    /// 收藏的设备视为受信任，无需 PIN，其余设备需要
+    Unknown,
+    /// 仅 `pin_devices` 中的设备需要 PIN
+    Listed,
 }
 
 impl Default for LocalSendConfig {
     fn default() -> Self {
         Self {
             device_id: uuid::Uuid::new_v4().to_string(),
-            device_name: hostname::get().unwrap_or_default().to_string_lossy().into_owned(),
+            device_name: default_device_name(),
             device_type: "desktop".to_string(),
             api_key: uuid::Uuid::new_v4().to_string(),
             port: DEFAULT_PORT,
+            ip_mode: IpMode::Dual,
             use_tls: false,
-            download_dir: std::env::temp_dir().to_string_lossy().into_owned(),
+            download_dir: default_download_dir(),
+            transports: Vec::new(),
+            addresses: Vec::new(),
+            route_preferences: BTreeMap::new(),
+            quick_save: QuickSave::Off,
+            favorites: Vec::new(),
+            allowlist: Vec::new(),
+            blocklist: Vec::new(),
+            groups: BTreeMap::new(),
+            pin: None,
+            pin_policy: PinPolicy::All,
+            pin_devices: Vec::new(),
+            audit_log: false,
+            quarantine_extensions: DEFAULT_QUARANTINE_EXTENSIONS
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+            max_file_size: None,
+            max_session_size: None,
+            max_files: None,
+            retention: None,
+            duplicate_policy: DuplicatePolicy::Duplicate,
+            collision_policy: CollisionPolicy::Rename,
+            symlink_policy: SymlinkPolicy::Follow,
+            browse_port: None,
+            browse_password: None,
+            share_port: None,
+            metrics_port: None,
+            api_port: None,
+            api_token: None,
+            outboxes: Vec::new(),
+            clipboard_sync: None,
+            extract: None,
+            hooks: Vec::new(),
+            hash_manifest: false,
+            webhooks: Vec::new(),
+            mqtt: None,
+            bandwidth_limit: None,
+            bandwidth_schedule: Vec::new(),
+            metered: None,
+            proxy: None,
         }
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+fn default_device_name() -> String {
+    hostname::get().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn default_device_name() -> String {
+    "PeerSend Web".to_string()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_download_dir() -> String {
+    std::env::temp_dir().to_string_lossy().into_owned()
+}
+
+/// 浏览器中没有文件系统，不接收文件
+#[cfg(target_arch = "wasm32")]
+fn default_download_dir() -> String {
+    String::new()
+}
+
 /// 会话状态
-#[derive(Debug, Clone, PartialEq)]
+///
+/// 序列化为 `{"state": "waiting"}`，出错时为 `{"state": "error", "error": "..."}`
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "state", content = "error", rename_all = "lowercase")]
 pub enum SessionState {
     Waiting,
     Transferring,
@@ -67,18 +604,73 @@ pub struct FileSession {
     pub receiver_id: String,
     pub files: Vec<FileInfo>,
     pub state: Arc<Mutex<SessionState>>,
-    pub progress: Arc<Mutex<TransferProgress>>,
+    pub progress: Arc<ProgressTracker>,
+    /// 被跳过或未能按原样传输的文件
+    pub warnings: Arc<Mutex<Vec<FileWarning>>>,
+    /// 文件 ID 到各文件的传输结果
+    pub transfers: Arc<std::sync::Mutex<HashMap<String, FileTransfer>>>,
+    /// 会话结束时生成的摘要，见 [`summary`]
+    pub summary: Arc<OnceLock<SessionSummary>>,
 }
 
 impl FileSession {
     pub fn new(id: String, sender_id: String, receiver_id: String, files: Vec<FileInfo>) -> Self {
+        let total = files.iter().map(|f| f.size).sum();
         Self {
             id,
             sender_id,
             receiver_id,
             files,
             state: Arc::new(Mutex::new(SessionState::Waiting)),
-            progress: Arc::new(Mutex::new(TransferProgress::default())),
+            progress: Arc::new(ProgressTracker::new(total)),
+            warnings: Arc::default(),
+            transfers: Arc::default(),
+            summary: Arc::default(),
+        }
+    }
+
+    /// 记录文件传输失败，err 为文件内容与声明的哈希不一致时同时记录校验失败
+    pub fn record_failure(&self, file_id: &str, err: &ProtocolError) {
+        let mut transfers = self.transfers.lock().unwrap();
+        let transfer = transfers.entry(file_id.to_string()).or_default();
+        transfer.error = Some(err.to_string());
+        transfer.hash_mismatch |= matches!(err, ProtocolError::HashMismatch { .. });
+    }
+}
+
+/// 会话中单个文件的传输结果
+#[derive(Debug, Clone, Default)]
+pub struct FileTransfer {
+    /// 已传输的字节数
+    pub bytes: u64,
+    /// 是否已传输完成
+    pub completed: bool,
+    /// 最近一次失败的原因，换用途径重传成功后仍保留
+    pub error: Option<String>,
+    /// 内容与声明的哈希不一致
+    pub hash_mismatch: bool,
+    /// 接收端保存时使用的文件名，按 [`CollisionPolicy`] 重命名后与原文件名不同
+    pub saved_name: Option<String>,
+    /// 已有同名文件，按 [`CollisionPolicy::Skip`] 未保存
+    pub skipped: bool,
+    /// 正在接收，同一文件不能同时上传两次
+    pub receiving: bool,
+}
+
+/// 会话中单个文件的警告
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FileWarning {
+    /// 文件名或本地路径
+    pub name: String,
+    /// 原因
+    pub reason: String,
+}
+
+impl FileWarning {
+    pub fn new(name: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            reason: reason.into(),
         }
     }
 }
@@ -91,6 +683,9 @@ pub struct FileInfo {
     pub size: u64,
     pub file_type: String,
     pub metadata: Option<serde_json::Value>,
+    /// 文件内容的 SHA-256，十六进制，设置后接收端会校验
+    #[serde(default)]
+    pub sha256: Option<String>,
 }
 
 /// 传输进度
@@ -127,21 +722,153 @@ pub struct DeviceInfo {
     pub announcement_id: String,
     #[serde(default)]
     pub uses_password: bool,
+    /// 对方支持的额外传输，为空时只能使用 HTTP
+    #[serde(default)]
+    pub transports: Vec<String>,
+    /// 对方公告的候选地址，按对方的优先顺序，发送前依次探测，见 [`LocalSendClient::reachable`]
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+impl DeviceInfo {
+    /// 对方是否为 PeerSend，PeerSend 公告的版本号以 `-peersend` 结尾
+    pub fn is_peersend(&self) -> bool {
+        self.version.ends_with("-peersend")
+    }
+}
+
+/// 会话列表快照
+///
+/// 查询返回共享的快照，修改时仅在仍有快照未释放时复制列表本身
+pub type SessionList = Arc<Vec<Arc<FileSession>>>;
+
+/// 设备列表快照
+pub type DeviceList = Arc<Vec<DeviceInfo>>;
+
+/// 逐字节比较，耗时与内容无关
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 会话发放的上传令牌
+#[derive(Debug, Clone)]
+struct UploadTokens {
+    /// 发起会话的地址，只接受来自该地址的上传和取消
+    sender_ip: String,
+    /// 尚未收到的文件 ID 到令牌
+    files: HashMap<String, String>,
 }
 
 /// 会话管理器
 #[derive(Debug, Clone)]
 pub struct SessionManager {
-    sessions: Arc<Mutex<Vec<FileSession>>>,
+    sessions: Arc<Mutex<SessionList>>,
+    /// 会话 ID 到上传令牌，文件收到后移除对应令牌
+    tokens: Arc<std::sync::Mutex<HashMap<String, UploadTokens>>>,
+    events: EventBus,
 }
 
 impl SessionManager {
     pub fn new() -> Self {
+        Self::with_event_bus(EventBus::default())
+    }
+
+    /// 使用共享的事件总线创建会话管理器
+    pub fn with_event_bus(events: EventBus) -> Self {
         Self {
-            sessions: Arc::new(Mutex::new(Vec::new())),
+            sessions: Arc::new(Mutex::new(Arc::new(Vec::new()))),
+            tokens: Arc::default(),
+            events,
         }
     }
 
+    /// 获取事件总线
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// 为会话中需要传输的文件发放上传令牌，返回文件 ID 到令牌
+    ///
+    /// 令牌只接受来自 sender_ip 的请求，每个文件的令牌收到文件后即失效。
+    /// 没有文件时不记录，会话视为已无需上传
+    pub fn issue_tokens<'a>(
+        &self,
+        session_id: &str,
+        sender_ip: &str,
+        file_ids: impl IntoIterator<Item = &'a str>,
+    ) -> HashMap<String, String> {
+        let files: HashMap<String, String> = file_ids
+            .into_iter()
+            .map(|id| (id.to_string(), uuid::Uuid::new_v4().to_string()))
+            .collect();
+        if !files.is_empty() {
+            let tokens = UploadTokens {
+                sender_ip: sender_ip.to_string(),
+                files: files.clone(),
+            };
+            self.tokens.lock().unwrap().insert(session_id.to_string(), tokens);
+        }
+        files
+    }
+
+    /// 校验来自 ip 的上传请求
+    ///
+    /// 会话没有未使用的令牌时返回 [`ProtocolError::SessionNotFound`]，
+    /// 来源地址与发起会话的地址不一致、文件未被接受或令牌不正确时返回
+    /// [`ProtocolError::Rejected`]
+    pub fn check_token(
+        &self,
+        session_id: &str,
+        file_id: &str,
+        token: &str,
+        ip: &str,
+    ) -> crate::Result<()> {
+        let tokens = self.tokens.lock().unwrap();
+        let issued = tokens
+            .get(session_id)
+            .ok_or_else(|| ProtocolError::SessionNotFound(session_id.to_string()))?;
+        if issued.sender_ip != ip {
+            return Err(ProtocolError::Rejected("来源地址与发起会话的设备不一致".to_string()));
+        }
+        let valid = issued
+            .files
+            .get(file_id)
+            .is_some_and(|issued| constant_time_eq(issued.as_bytes(), token.as_bytes()));
+        if !valid {
+            return Err(ProtocolError::Rejected("上传令牌无效".to_string()));
+        }
+        Ok(())
+    }
+
+    /// 文件已收到，作废其令牌，返回会话的令牌是否已全部用完
+    pub fn consume_token(&self, session_id: &str, file_id: &str) -> bool {
+        let mut tokens = self.tokens.lock().unwrap();
+        let Some(issued) = tokens.get_mut(session_id) else {
+            return false;
+        };
+        issued.files.remove(file_id);
+        if !issued.files.is_empty() {
+            return false;
+        }
+        tokens.remove(session_id);
+        true
+    }
+
+    /// 作废会话的所有令牌，用于来自 ip 的取消请求；ip 为 None 时不检查来源
+    ///
+    /// 错误与 [`check_token`](Self::check_token) 相同
+    pub fn revoke_tokens(&self, session_id: &str, ip: Option<&str>) -> crate::Result<()> {
+        let mut tokens = self.tokens.lock().unwrap();
+        let issued = tokens
+            .get(session_id)
+            .ok_or_else(|| ProtocolError::SessionNotFound(session_id.to_string()))?;
+        if ip.is_some_and(|ip| issued.sender_ip != ip) {
+            return Err(ProtocolError::Rejected("来源地址与发起会话的设备不一致".to_string()));
+        }
+        tokens.remove(session_id);
+        Ok(())
+    }
+
     pub async fn create_session(
         &self,
         sender_id: String,
@@ -157,56 +884,175 @@ impl SessionManager {
         );
 
         let mut sessions = self.sessions.lock().await;
-        sessions.push(session.clone());
+        Arc::make_mut(&mut sessions).push(Arc::new(session.clone()));
+
+        self.events.emit(ProtocolEvent::SessionStateChanged {
+            session_id: session.id.clone(),
+            state: SessionState::Waiting,
+        });
 
         session
     }
 
-    pub async fn get_session(&self, session_id: &str) -> Option<FileSession> {
+    /// 更新会话状态并发布事件
+    ///
+    /// 会话结束时先生成摘要保存在会话中，再依次发布状态变化和
+    /// [`ProtocolEvent::SessionSummary`]，处理状态变化时即可读取摘要
+    pub async fn set_state(&self, session_id: &str, state: SessionState) {
+        let Some(session) = self.get_session(session_id).await else {
+            return;
+        };
+        let previous = std::mem::replace(&mut *session.state.lock().await, state.clone());
+        let summary = if SessionSummary::applies(&previous, &state) {
+            let summary = SessionSummary::new(&session, &state).await;
+            let _ = session.summary.set(summary.clone());
+            Some(summary)
+        } else {
+            None
+        };
+        self.events.emit(ProtocolEvent::SessionStateChanged {
+            session_id: session_id.to_string(),
+            state,
+        });
+        if let Some(summary) = summary {
+            self.events.emit(ProtocolEvent::SessionSummary(Box::new(summary)));
+        }
+    }
+
+    /// 累加会话进度并发布文件进度事件
+    pub async fn report_progress(&self, session_id: &str, file_id: &str, bytes: u64) {
+        let Some(session) = self.get_session(session_id).await else {
+            return;
+        };
+        let total_bytes = session
+            .files
+            .iter()
+            .find(|f| f.id == file_id)
+            .map_or(0, |f| f.size);
+        {
+            let mut transfers = session.transfers.lock().unwrap();
+            let transfer = transfers.entry(file_id.to_string()).or_default();
+            transfer.bytes += bytes;
+            transfer.completed = transfer.bytes >= total_bytes;
+        }
+
+        let bytes_transferred = session.progress.add(bytes);
+        self.events.emit(ProtocolEvent::FileProgress {
+            session_id: session_id.to_string(),
+            file_id: file_id.to_string(),
+            bytes_transferred,
+            total_bytes,
+        });
+    }
+
+    /// 会话最近每秒的吞吐，用于绘制速度曲线，会话不存在时返回 None
+    pub async fn get_transfer_timeseries(&self, session_id: &str) -> Option<Vec<ThroughputSample>> {
+        let session = self.get_session(session_id).await?;
+        Some(session.progress.timeseries())
+    }
+
+    pub async fn get_session(&self, session_id: &str) -> Option<Arc<FileSession>> {
         let sessions = self.sessions.lock().await;
         sessions.iter().find(|s| s.id == session_id).cloned()
     }
 
     pub async fn remove_session(&self, session_id: &str) {
+        self.tokens.lock().unwrap().remove(session_id);
         let mut sessions = self.sessions.lock().await;
-        sessions.retain(|s| s.id != session_id);
+        if sessions.iter().any(|s| s.id == session_id) {
+            Arc::make_mut(&mut sessions).retain(|s| s.id != session_id);
+        }
     }
 
-    pub async fn get_all_sessions(&self) -> Vec<FileSession> {
+    /// 所有会话的快照
+    pub async fn get_all_sessions(&self) -> SessionList {
         self.sessions.lock().await.clone()
     }
+
+    /// 会话数量
+    pub async fn session_count(&self) -> usize {
+        self.sessions.lock().await.len()
+    }
 }
 
 /// 设备发现管理器
 #[derive(Debug, Clone)]
 pub struct DiscoveryManager {
-    discovered_devices: Arc<Mutex<Vec<DeviceInfo>>>,
+    discovered_devices: Arc<Mutex<DeviceList>>,
+    events: EventBus,
+    flood: FloodGuard,
 }
 
 impl DiscoveryManager {
     pub fn new() -> Self {
+        Self::with_event_bus(EventBus::default())
+    }
+
+    /// 使用共享的事件总线创建发现管理器
+    pub fn with_event_bus(events: EventBus) -> Self {
         Self {
-            discovered_devices: Arc::new(Mutex::new(Vec::new())),
+            discovered_devices: Arc::new(Mutex::new(Arc::new(Vec::new()))),
+            events,
+            flood: FloodGuard::default(),
         }
     }
 
+    /// 使用指定的洪泛防护阈值
+    pub fn with_flood_limits(mut self, limits: FloodLimits) -> Self {
+        self.flood = FloodGuard::new(limits);
+        self
+    }
+
+    /// 洪泛防护，公告、扫描结果和文件发送请求共用
+    pub fn flood_guard(&self) -> &FloodGuard {
+        &self.flood
+    }
+
+    /// 记录来自 ip 的一次公告或请求，返回是否应当处理
+    ///
+    /// 地址因洪泛被忽略时一并移除该地址已发现的设备
+    pub async fn admit(&self, ip: &str, device_id: &str) -> bool {
+        if self.flood.check(ip, device_id) {
+            return true;
+        }
+        let mut devices = self.discovered_devices.lock().await;
+        if devices.iter().any(|d| d.ip == ip) {
+            Arc::make_mut(&mut devices).retain(|d| d.ip != ip);
+        }
+        false
+    }
+
+    /// 获取事件总线
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
     pub async fn add_device(&self, device: DeviceInfo) {
         let mut devices = self.discovered_devices.lock().await;
         if !devices.iter().any(|d| d.id == device.id) {
-            devices.push(device);
+            Arc::make_mut(&mut devices).push(device.clone());
+            self.events.emit(ProtocolEvent::DeviceDiscovered(device));
         }
     }
 
     pub async fn remove_device(&self, id: &str) {
         let mut devices = self.discovered_devices.lock().await;
-        devices.retain(|d| d.id != id);
+        if devices.iter().any(|d| d.id == id) {
+            Arc::make_mut(&mut devices).retain(|d| d.id != id);
+        }
     }
 
-    pub async fn get_devices(&self) -> Vec<DeviceInfo> {
+    /// 已发现设备的快照
+    pub async fn get_devices(&self) -> DeviceList {
         self.discovered_devices.lock().await.clone()
     }
 
+    /// 已发现设备数量
+    pub async fn device_count(&self) -> usize {
+        self.discovered_devices.lock().await.len()
+    }
+
     pub async fn clear(&self) {
-        self.discovered_devices.lock().await.clear();
+        *self.discovered_devices.lock().await = Arc::new(Vec::new());
     }
 }
@@ -0,0 +1,268 @@
+//! 日志初始化
+//!
+//! 协议库本身只产生 tracing 事件，由前端按需调用 [`init_tracing`] 输出。
+//! 除面向人阅读的文本格式外，还可以每个事件输出一行 JSON，便于送入 Loki、
+//! Elasticsearch 等日志系统，所在 span 的字段 (如 `session_id`、`peer`) 一并展开。
+//!
+//! 启用 `otel` 特性后，[`init_tracing_with_otlp`] 同时把 span 经 OTLP 导出到
+//! Jaeger、Tempo 等，可以按 `session_id` 查看一次传输的发现、协商、上传和落盘各阶段耗时。
+//! 协议不携带 trace 上下文，发送端与接收端各自产生独立的 trace，可通过 `session_id` 关联
+
+use std::io::Write;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+
+/// 日志过滤规则的环境变量，格式同 `peersend_protocol=debug,info`
+pub const LOG_ENV: &str = "PEERSEND_LOG";
+
+/// 日志格式的环境变量，取值为 `text` 或 `json`
+pub const LOG_FORMAT_ENV: &str = "PEERSEND_LOG_FORMAT";
+
+/// 日志输出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// 面向人阅读的单行文本
+    #[default]
+    Text,
+    /// 每个事件一行 JSON
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("未知的日志格式: {}", other)),
+        }
+    }
+}
+
+/// 以文本格式初始化全局日志订阅者
+///
+/// 优先使用 [`LOG_ENV`] 中的过滤规则，未设置或无法解析时使用 default_filter
+/// 日志写入标准错误，不影响命令行的标准输出
+pub fn init_tracing(default_filter: &str) -> Result<(), TryInitError> {
+    init_tracing_with_format(default_filter, None)
+}
+
+/// 以指定格式初始化全局日志订阅者
+///
+/// format 为空时读取 [`LOG_FORMAT_ENV`]，仍未指定时使用文本格式。
+/// 过滤规则同 [`init_tracing`]
+pub fn init_tracing_with_format(
+    default_filter: &str,
+    format: Option<LogFormat>,
+) -> Result<(), TryInitError> {
+    init(
+        default_filter,
+        format,
+        #[cfg(feature = "otel")]
+        None,
+    )
+}
+
+/// 初始化全局日志订阅者，并把 span 经 OTLP (gRPC) 导出
+///
+/// endpoint 为空时使用 `OTEL_EXPORTER_OTLP_ENDPOINT`，仍未设置时为 `http://localhost:4317`。
+/// 返回的 [`OtlpGuard`] 释放时导出尚未发送的 span，应保留到程序退出
+#[cfg(feature = "otel")]
+pub fn init_tracing_with_otlp(
+    default_filter: &str,
+    format: Option<LogFormat>,
+    endpoint: Option<&str>,
+) -> crate::Result<OtlpGuard> {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let mut exporter = opentelemetry_otlp::SpanExporter::builder().with_tonic();
+    if let Some(endpoint) = endpoint {
+        exporter = exporter.with_endpoint(endpoint);
+    }
+    let exporter = exporter
+        .build()
+        .map_err(|e| crate::ProtocolError::InvalidConfig(format!("创建 OTLP 导出器失败: {}", e)))?;
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(opentelemetry_sdk::Resource::new([opentelemetry::KeyValue::new(
+            "service.name",
+            "peersend",
+        )]))
+        .build();
+
+    init(default_filter, format, Some(provider.tracer("peersend")))
+        .map_err(|e| crate::ProtocolError::InvalidConfig(format!("初始化日志失败: {}", e)))?;
+    Ok(OtlpGuard { provider })
+}
+
+/// 保持 OTLP 导出，释放时发送剩余的 span
+#[cfg(feature = "otel")]
+#[derive(Debug)]
+pub struct OtlpGuard {
+    provider: opentelemetry_sdk::trace::TracerProvider,
+}
+
+#[cfg(feature = "otel")]
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("导出剩余的 span 失败: {}", e);
+        }
+    }
+}
+
+fn init(
+    default_filter: &str,
+    format: Option<LogFormat>,
+    #[cfg(feature = "otel")] tracer: Option<opentelemetry_sdk::trace::Tracer>,
+) -> Result<(), TryInitError> {
+    let filter = std::env::var(LOG_ENV)
+        .ok()
+        .and_then(|rule| rule.parse::<Targets>().ok())
+        .or_else(|| default_filter.parse().ok())
+        .unwrap_or_else(|| Targets::new().with_default(tracing::Level::INFO));
+    let format = format
+        .or_else(|| std::env::var(LOG_FORMAT_ENV).ok()?.parse().ok())
+        .unwrap_or_default();
+
+    let (text, json) = match format {
+        LogFormat::Text => (
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_target(false)
+                    .with_writer(std::io::stderr),
+            ),
+            None,
+        ),
+        LogFormat::Json => (None, Some(JsonLayer::new(std::io::stderr))),
+    };
+    let registry = tracing_subscriber::registry().with(text).with(json);
+    #[cfg(feature = "otel")]
+    let registry =
+        registry.with(tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer)));
+    registry.with(filter).try_init()
+}
+
+/// 每个事件输出一行 JSON 的日志层
+///
+/// 对象包含 `timestamp` (Unix 毫秒)、`level`、`target`、`message`、事件的字段
+/// 以及所在各层 span 的字段，内层 span 和事件的同名字段覆盖外层
+#[derive(Debug)]
+pub struct JsonLayer<W> {
+    writer: W,
+}
+
+impl<W> JsonLayer<W>
+where
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    /// 写入 writer，例如 `std::io::stderr`
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+/// 记录在 span 扩展中的字段
+struct SpanFields(Map<String, Value>);
+
+/// 把字段收集为 JSON 值
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.0
+            .insert(field.name().to_string(), Value::from(value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            Value::from(format!("{:?}", value)),
+        );
+    }
+}
+
+impl<S, W> Layer<S> for JsonLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(&mut fields.0));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let mut object = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(fields) = span.extensions().get::<SpanFields>() {
+                    object.extend(fields.0.iter().map(|(k, v)| (k.clone(), v.clone())));
+                }
+                object.insert("span".to_string(), Value::from(span.name()));
+            }
+        }
+        event.record(&mut JsonVisitor(&mut object));
+        object.insert("timestamp".to_string(), Value::from(timestamp));
+        object.insert("level".to_string(), Value::from(metadata.level().as_str()));
+        object.insert("target".to_string(), Value::from(metadata.target()));
+
+        let Ok(mut line) = serde_json::to_vec(&object) else {
+            return;
+        };
+        line.push(b'\n');
+        let _ = self.writer.make_writer_for(metadata).write_all(&line);
+    }
+}
@@ -0,0 +1,204 @@
+//! 省流模式
+//!
+//! 连接按流量计费或设备使用电池时，[`Metered`] 处于省流状态：
+//! 共用的 [`Throttle`](crate::throttle::Throttle) 按 [`MeteredConfig::bandwidth_limit`] 限速，
+//! UDP 公告改为按 [`MeteredConfig::announce_interval_secs`] 发送，
+//! 发件箱暂缓发送大于 [`MeteredConfig::max_file_size`] 的文件。
+//! 退出省流状态后限速和公告间隔立即恢复，发件箱随即发送暂缓的文件。
+//!
+//! `auto` 模式由 [`Metered::run`] 定期检测系统状态：Windows 上检测按流量计费的网络和电池供电，
+//! macOS 上检测电池供电，其他平台无法检测，可使用 `on` 手动开启
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use crate::{LocalSendConfig, MeteredConfig, MeteredMode};
+
+/// 自动检测的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 省流状态
+///
+/// 克隆后共享同一状态；未配置省流模式时始终不处于省流状态
+#[derive(Debug, Clone)]
+pub struct Metered {
+    config: Option<Arc<MeteredConfig>>,
+    active: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Metered {
+    fn default() -> Self {
+        Self {
+            config: None,
+            active: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl Metered {
+    /// 按省流模式配置创建，`on` 模式创建后即处于省流状态
+    pub fn new(config: MeteredConfig) -> Self {
+        let active = config.mode == MeteredMode::On;
+        Self {
+            config: Some(Arc::new(config)),
+            active: Arc::new(watch::Sender::new(active)),
+        }
+    }
+
+    /// 使用配置中的 `metered`
+    pub fn from_config(config: &LocalSendConfig) -> Self {
+        config.metered.clone().map(Self::new).unwrap_or_default()
+    }
+
+    /// 是否处于省流状态
+    pub fn is_active(&self) -> bool {
+        *self.active.borrow()
+    }
+
+    /// 进入或退出省流状态，状态改变时通知 [`subscribe`](Self::subscribe) 的接收端
+    ///
+    /// 未配置省流模式或模式为 `off` 时忽略
+    pub fn set_active(&self, active: bool) {
+        if self.config.as_ref().is_none_or(|c| c.mode == MeteredMode::Off) {
+            return;
+        }
+        let changed = self.active.send_if_modified(|current| {
+            std::mem::replace(current, active) != active
+        });
+        if changed {
+            if active {
+                info!("进入省流模式，暂缓大文件并降低带宽和公告频率");
+            } else {
+                info!("退出省流模式，恢复正常传输");
+            }
+        }
+    }
+
+    /// 订阅省流状态的变化
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.active.subscribe()
+    }
+
+    /// 是否可能限速，用于判断能否使用无法限速的快速路径
+    pub fn may_limit(&self) -> bool {
+        self.config
+            .as_ref()
+            .is_some_and(|c| c.mode != MeteredMode::Off && c.bandwidth_limit.is_some())
+    }
+
+    /// 当前的带宽上限，不处于省流状态时为空
+    pub fn limit(&self) -> Option<u64> {
+        self.active_config()?.bandwidth_limit
+    }
+
+    /// 大小为 size 的文件当前是否应暂缓发送
+    pub fn holds(&self, size: u64) -> bool {
+        self.active_config()
+            .and_then(|c| c.max_file_size)
+            .is_some_and(|max| size > max)
+    }
+
+    /// 当前的公告间隔，不处于省流状态时为 normal
+    pub fn announce_interval(&self, normal: Duration) -> Duration {
+        match self.active_config() {
+            Some(config) => normal.max(Duration::from_secs(config.announce_interval_secs)),
+            None => normal,
+        }
+    }
+
+    fn active_config(&self) -> Option<&MeteredConfig> {
+        self.config.as_deref().filter(|_| self.is_active())
+    }
+
+    /// `auto` 模式下定期检测系统状态并更新省流状态，直到 cancel 取消
+    ///
+    /// 其他模式或无法检测的平台上直接返回
+    pub async fn run(self, cancel: CancellationToken) {
+        if self.config.as_ref().is_none_or(|c| c.mode != MeteredMode::Auto) {
+            return;
+        }
+        loop {
+            match detect().await {
+                Some(active) => self.set_active(active),
+                None if cfg!(any(target_os = "windows", target_os = "macos")) => {
+                    debug!("检测网络和电源状态失败，保持当前状态");
+                }
+                None => {
+                    debug!("当前平台无法自动检测省流状态");
+                    return;
+                }
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+            }
+        }
+    }
+}
+
+/// 检测是否应处于省流状态，无法检测时返回 None
+#[cfg(target_os = "macos")]
+async fn detect() -> Option<bool> {
+    let output = tokio::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_pmset(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// 检测是否应处于省流状态，无法检测时返回 None
+#[cfg(target_os = "windows")]
+async fn detect() -> Option<bool> {
+    /// 不弹出控制台窗口
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    /// 网络费用为 Fixed 或 Variable 即按流量计费，BatteryStatus 为 1 即正在放电
+    const SCRIPT: &str = "$p = [Windows.Networking.Connectivity.NetworkInformation,\
+        Windows.Networking.Connectivity,ContentType=WindowsRuntime]::\
+        GetInternetConnectionProfile(); \
+        $m = $p -and $p.GetConnectionCost().NetworkCostType -in 'Fixed','Variable'; \
+        $b = [bool](Get-CimInstance Win32_Battery | Where-Object BatteryStatus -eq 1); \
+        $m -or $b";
+
+    let output = tokio::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "True" => Some(true),
+        "False" => Some(false),
+        _ => None,
+    }
+}
+
+/// 检测是否应处于省流状态，无法检测时返回 None
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+async fn detect() -> Option<bool> {
+    None
+}
+
+/// 解析 `pmset -g batt` 的输出，使用电池时返回 true
+///
+/// 输出的第一行为 `Now drawing from 'Battery Power'` 或 `Now drawing from 'AC Power'`
+pub fn parse_pmset(output: &str) -> Option<bool> {
+    let first = output.lines().next()?;
+    if first.contains("'Battery Power'") {
+        Some(true)
+    } else if first.contains("'AC Power'") {
+        Some(false)
+    } else {
+        None
+    }
+}
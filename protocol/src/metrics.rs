@@ -0,0 +1,287 @@
+//! 运行指标
+//!
+//! [`Metrics`] 从事件总线统计会话、发现和隔离等计数，[`TextEncoder`] 按
+//! Prometheus 文本格式输出，供接收守护进程的 `/metrics` 端点使用。
+//! 前端可以在同一份输出后追加自己的指标，例如守护进程的运行时长
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::{DiscoveryManager, ProtocolEvent, SessionManager, SessionState};
+
+/// Prometheus 文本格式的 Content-Type
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Prometheus 文本格式编码器
+#[derive(Debug, Default)]
+pub struct TextEncoder {
+    out: String,
+}
+
+impl TextEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 写入计数器
+    pub fn counter(&mut self, name: &str, help: &str, value: u64) {
+        self.family(name, "counter", help, None, &[("", value as f64)]);
+    }
+
+    /// 写入按标签区分的一组计数器
+    pub fn counter_by(&mut self, name: &str, help: &str, label: &str, samples: &[(&str, u64)]) {
+        let samples: Vec<(&str, f64)> = samples.iter().map(|&(l, v)| (l, v as f64)).collect();
+        self.family(name, "counter", help, Some(label), &samples);
+    }
+
+    /// 写入仪表
+    pub fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.family(name, "gauge", help, None, &[("", value)]);
+    }
+
+    /// 写入按标签区分的一组仪表
+    pub fn gauge_by(&mut self, name: &str, help: &str, label: &str, samples: &[(&str, f64)]) {
+        self.family(name, "gauge", help, Some(label), samples);
+    }
+
+    /// 编码结果
+    pub fn finish(self) -> String {
+        self.out
+    }
+
+    fn family(
+        &mut self,
+        name: &str,
+        kind: &str,
+        help: &str,
+        label: Option<&str>,
+        samples: &[(&str, f64)],
+    ) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, escape(help, false));
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+        for (value_label, value) in samples {
+            match label {
+                Some(label) => {
+                    let _ = writeln!(
+                        self.out,
+                        "{}{{{}=\"{}\"}} {}",
+                        name,
+                        label,
+                        escape(value_label, true),
+                        value
+                    );
+                }
+                None => {
+                    let _ = writeln!(self.out, "{} {}", name, value);
+                }
+            }
+        }
+    }
+}
+
+/// 转义 HELP 文本或标签值
+fn escape(text: &str, quote: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if quote => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 计数器快照
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// 创建的会话数，收发均计入
+    pub sessions_created: u64,
+    pub sessions_finished: u64,
+    pub sessions_cancelled: u64,
+    pub sessions_failed: u64,
+    /// 已完成会话传输的字节数
+    pub bytes_transferred: u64,
+    pub files_quarantined: u64,
+    pub devices_discovered: u64,
+    pub texts_received: u64,
+    pub errors: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    sessions_created: AtomicU64,
+    sessions_finished: AtomicU64,
+    sessions_cancelled: AtomicU64,
+    sessions_failed: AtomicU64,
+    bytes_transferred: AtomicU64,
+    files_quarantined: AtomicU64,
+    devices_discovered: AtomicU64,
+    texts_received: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// 协议运行指标
+///
+/// 克隆后共享同一组计数器
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    counters: Arc<Counters>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从事件总线统计，直到事件总线关闭或 cancel 取消
+    ///
+    /// 会话完成时从 sessions 读取已传输的字节数
+    pub async fn run(
+        self,
+        mut events: broadcast::Receiver<ProtocolEvent>,
+        sessions: SessionManager,
+        cancel: CancellationToken,
+    ) {
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = events.recv() => event,
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "指标统计处理过慢，丢失部分事件");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            let counters = &self.counters;
+            let counter = match event {
+                ProtocolEvent::DeviceDiscovered(_) => &counters.devices_discovered,
+                ProtocolEvent::SessionStateChanged { session_id, state } => match state {
+                    SessionState::Waiting => &counters.sessions_created,
+                    SessionState::Transferring => continue,
+                    SessionState::Finished => {
+                        if let Some(session) = sessions.get_session(&session_id).await {
+                            counters
+                                .bytes_transferred
+                                .fetch_add(session.progress.bytes_transferred(), Ordering::Relaxed);
+                        }
+                        &counters.sessions_finished
+                    }
+                    SessionState::Cancelled => &counters.sessions_cancelled,
+                    SessionState::Error(_) => &counters.sessions_failed,
+                },
+                ProtocolEvent::FileQuarantined { .. } => &counters.files_quarantined,
+                ProtocolEvent::TextReceived { .. } => &counters.texts_received,
+                ProtocolEvent::Error { .. } => &counters.errors,
+                ProtocolEvent::SessionRequested { .. }
+                | ProtocolEvent::FileProgress { .. }
+                | ProtocolEvent::FileReceived { .. }
+                | ProtocolEvent::ArchiveEntryExtracted { .. }
+                | ProtocolEvent::ArchiveExtracted { .. }
+                | ProtocolEvent::SourceChanged { .. }
+                | ProtocolEvent::SessionSummary(_) => continue,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 计数器快照
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let c = &self.counters;
+        MetricsSnapshot {
+            sessions_created: c.sessions_created.load(Ordering::Relaxed),
+            sessions_finished: c.sessions_finished.load(Ordering::Relaxed),
+            sessions_cancelled: c.sessions_cancelled.load(Ordering::Relaxed),
+            sessions_failed: c.sessions_failed.load(Ordering::Relaxed),
+            bytes_transferred: c.bytes_transferred.load(Ordering::Relaxed),
+            files_quarantined: c.files_quarantined.load(Ordering::Relaxed),
+            devices_discovered: c.devices_discovered.load(Ordering::Relaxed),
+            texts_received: c.texts_received.load(Ordering::Relaxed),
+            errors: c.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 写入计数器，以及当前会话、已发现设备和洪泛防护的状态
+    pub async fn encode(
+        &self,
+        encoder: &mut TextEncoder,
+        sessions: &SessionManager,
+        discovery: &DiscoveryManager,
+    ) {
+        let snapshot = self.snapshot();
+        encoder.counter_by(
+            "peersend_sessions_total",
+            "按结果统计的会话数，created 为创建的会话",
+            "state",
+            &[
+                ("created", snapshot.sessions_created),
+                ("finished", snapshot.sessions_finished),
+                ("cancelled", snapshot.sessions_cancelled),
+                ("error", snapshot.sessions_failed),
+            ],
+        );
+        encoder.counter(
+            "peersend_transferred_bytes_total",
+            "已完成会话传输的字节数",
+            snapshot.bytes_transferred,
+        );
+        encoder.counter(
+            "peersend_files_quarantined_total",
+            "放入隔离目录的文件数",
+            snapshot.files_quarantined,
+        );
+        encoder.counter(
+            "peersend_devices_discovered_total",
+            "发现的新设备数",
+            snapshot.devices_discovered,
+        );
+        encoder.counter(
+            "peersend_texts_received_total",
+            "收到的文本消息数",
+            snapshot.texts_received,
+        );
+        encoder.counter(
+            "peersend_errors_total",
+            "子系统报告的错误数",
+            snapshot.errors,
+        );
+
+        let mut states = [("waiting", 0.0), ("transferring", 0.0)];
+        for session in sessions.get_all_sessions().await.iter() {
+            match *session.state.lock().await {
+                SessionState::Waiting => states[0].1 += 1.0,
+                SessionState::Transferring => states[1].1 += 1.0,
+                _ => {}
+            }
+        }
+        encoder.gauge_by("peersend_sessions", "进行中的会话数", "state", &states);
+        encoder.gauge(
+            "peersend_devices",
+            "当前已发现的设备数",
+            discovery.device_count().await as f64,
+        );
+
+        let flood = discovery.flood_guard().stats();
+        encoder.gauge(
+            "peersend_flood_blocked_addresses",
+            "因洪泛被暂时忽略的地址数",
+            flood.blocked as f64,
+        );
+        encoder.counter("peersend_flood_blocks_total", "洪泛封禁次数", flood.blocks);
+        encoder.counter(
+            "peersend_flood_dropped_total",
+            "因洪泛丢弃的公告和请求数",
+            flood.dropped,
+        );
+    }
+}
@@ -0,0 +1,26 @@
+//! 文件类型检测
+//!
+//! 发送时按文件头和扩展名推断 MIME 类型，接收方 (包括官方 LocalSend) 据此对文件分类
+
+/// 推断类型时读取的文件头长度，足以识别常见的 Office 文档
+pub const SNIFF_LEN: usize = 8192;
+
+/// 无法识别时的类型
+pub const OCTET_STREAM: &str = "application/octet-stream";
+
+/// 只说明容器格式的类型，扩展名能给出更具体的类型时以扩展名为准
+const CONTAINERS: &[&str] = &["application/zip", "application/x-ole-storage"];
+
+/// 按文件头和文件名推断 MIME 类型
+///
+/// 文件头能识别时以其为准，扩展名与内容不符 (例如改过扩展名的图片) 时也能得到正确类型；
+/// 纯文本等没有特征的格式按扩展名判断，都无法识别时为 [`OCTET_STREAM`]
+pub fn detect(name: &str, head: &[u8]) -> String {
+    let by_name = mime_guess::from_path(name).first_raw();
+    match infer::get(head).map(|kind| kind.mime_type()) {
+        Some(sniffed) if !CONTAINERS.contains(&sniffed) => sniffed,
+        Some(sniffed) => by_name.unwrap_or(sniffed),
+        None => by_name.unwrap_or(OCTET_STREAM),
+    }
+    .to_string()
+}
@@ -0,0 +1,268 @@
+//! MQTT 事件发布
+//!
+//! 把发现和传输事件发布到 MQTT 代理，Home Assistant 等系统可据此在指定设备上线
+//! 或收到文件时触发自动化。主题以配置的前缀开头：
+//!
+//! - `<前缀>/status`: 本机在线状态 `online` / `offline`，保留消息，断线时由遗嘱消息置为 `offline`
+//! - `<前缀>/devices/<设备 ID>`: 发现的设备信息，保留消息
+//! - `<前缀>/sessions/requested`: 收到文件发送请求
+//! - `<前缀>/sessions/state`: 会话状态变化
+//! - `<前缀>/sessions/quarantined`: 收到的文件被放入隔离目录
+//! - `<前缀>/sessions/summary`: 会话结束时的摘要
+//! - `<前缀>/text`: 收到文本消息
+//! - `<前缀>/error`: 子系统出错
+//!
+//! 消息内容均为 JSON
+
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, EventLoop, LastWill, MqttOptions, QoS};
+use serde_json::json;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::{MqttConfig, ProtocolError, ProtocolEvent, SessionState};
+
+/// 默认端口
+const DEFAULT_MQTT_PORT: u16 = 1883;
+
+/// 心跳间隔
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// 连接断开后重连前的等待时长
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// 待发布的消息
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub retain: bool,
+}
+
+impl MqttMessage {
+    /// 把协议事件转换为消息，不发布的事件返回 None
+    ///
+    /// 文件进度事件过于频繁，不发布
+    pub fn from_event(prefix: &str, event: &ProtocolEvent) -> Option<Self> {
+        let (topic, payload, retain) = match event {
+            ProtocolEvent::DeviceDiscovered(device) => (
+                format!("devices/{}", device.id),
+                serde_json::to_value(device).ok()?,
+                true,
+            ),
+            ProtocolEvent::SessionRequested {
+                session_id,
+                sender,
+                files,
+            } => (
+                "sessions/requested".to_string(),
+                json!({
+                    "sessionId": session_id,
+                    "sender": sender,
+                    "files": files
+                        .iter()
+                        .map(|f| json!({"name": f.name, "size": f.size}))
+                        .collect::<Vec<_>>(),
+                }),
+                false,
+            ),
+            ProtocolEvent::SessionStateChanged { session_id, state } => {
+                let (state, error) = match state {
+                    SessionState::Waiting => ("waiting", None),
+                    SessionState::Transferring => ("transferring", None),
+                    SessionState::Finished => ("finished", None),
+                    SessionState::Cancelled => ("cancelled", None),
+                    SessionState::Error(message) => ("error", Some(message)),
+                };
+                (
+                    "sessions/state".to_string(),
+                    json!({"sessionId": session_id, "state": state, "error": error}),
+                    false,
+                )
+            }
+            ProtocolEvent::FileQuarantined { session_id, path } => (
+                "sessions/quarantined".to_string(),
+                json!({"sessionId": session_id, "path": path}),
+                false,
+            ),
+            ProtocolEvent::ArchiveExtracted {
+                session_id,
+                archive,
+                dir,
+            } => (
+                "sessions/extracted".to_string(),
+                json!({"sessionId": session_id, "archive": archive, "dir": dir}),
+                false,
+            ),
+            ProtocolEvent::SessionSummary(summary) => (
+                "sessions/summary".to_string(),
+                serde_json::to_value(summary).ok()?,
+                false,
+            ),
+            ProtocolEvent::TextReceived {
+                sender_id,
+                sender,
+                text,
+            } => (
+                "text".to_string(),
+                json!({"senderId": sender_id, "sender": sender, "text": text}),
+                false,
+            ),
+            ProtocolEvent::Error {
+                session_id,
+                message,
+            } => (
+                "error".to_string(),
+                json!({"sessionId": session_id, "message": message}),
+                false,
+            ),
+            ProtocolEvent::FileProgress { .. }
+            | ProtocolEvent::FileReceived { .. }
+            | ProtocolEvent::ArchiveEntryExtracted { .. }
+            | ProtocolEvent::SourceChanged { .. } => return None,
+        };
+        Some(Self {
+            topic: topic_for(prefix, &topic),
+            payload: serde_json::to_vec(&payload).ok()?,
+            retain,
+        })
+    }
+}
+
+/// MQTT 事件发布器
+pub struct MqttPublisher {
+    client: AsyncClient,
+    event_loop: EventLoop,
+    prefix: String,
+}
+
+impl MqttPublisher {
+    /// 按配置创建发布器，client_id 通常为本机设备 ID
+    ///
+    /// 只支持 `mqtt://主机[:端口]` 形式的代理地址，连接在 [`run`](Self::run) 中建立
+    pub fn new(config: &MqttConfig, client_id: &str) -> crate::Result<Self> {
+        let url = url::Url::parse(&config.broker)
+            .map_err(|e| ProtocolError::InvalidConfig(format!("无效的 MQTT 地址: {}", e)))?;
+        if url.scheme() != "mqtt" {
+            return Err(ProtocolError::InvalidConfig(format!(
+                "不支持的 MQTT 协议: {}",
+                url.scheme()
+            )));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| ProtocolError::InvalidConfig("MQTT 地址缺少主机".to_string()))?;
+        let prefix = config.topic.clone();
+
+        let mut options = MqttOptions::new(
+            format!("peersend-{}", client_id),
+            host,
+            url.port().unwrap_or(DEFAULT_MQTT_PORT),
+        );
+        options.set_keep_alive(KEEP_ALIVE);
+        options.set_last_will(LastWill::new(
+            topic_for(&prefix, "status"),
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+
+        let (client, event_loop) = AsyncClient::new(options, 64);
+        Ok(Self {
+            client,
+            event_loop,
+            prefix,
+        })
+    }
+
+    /// 发布事件，直到事件总线关闭或 cancel 取消
+    ///
+    /// 代理不可达时定期重连，期间的事件在客户端队列满后丢弃
+    pub async fn run(
+        self,
+        mut events: broadcast::Receiver<ProtocolEvent>,
+        cancel: CancellationToken,
+    ) {
+        let Self {
+            client,
+            mut event_loop,
+            prefix,
+        } = self;
+        let status = topic_for(&prefix, "status");
+
+        // 驱动连接，收发报文都在事件循环中完成
+        let stop = cancel.child_token();
+        let connection = tokio::spawn({
+            let client = client.clone();
+            let status = status.clone();
+            let stop = stop.clone();
+            async move {
+                loop {
+                    tokio::select! {
+                        _ = stop.cancelled() => break,
+                        polled = event_loop.poll() => match polled {
+                            Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_))) => {
+                                // 每次连接 (包括重连) 后声明在线，覆盖遗嘱消息
+                                debug!("已连接 MQTT 代理");
+                                let _ = client.try_publish(&status, QoS::AtLeastOnce, true, "online");
+                            }
+                            Ok(_) => {}
+                            Err(e) => {
+                                warn!(error = %e, "MQTT 连接断开，稍后重连");
+                                tokio::time::sleep(RECONNECT_DELAY).await;
+                            }
+                        }
+                    }
+                }
+                // 把离线状态和断开报文发出，代理不可达时不等待
+                let drain = async { while event_loop.poll().await.is_ok() {} };
+                let _ = tokio::time::timeout(Duration::from_secs(1), drain).await;
+            }
+        });
+
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = events.recv() => event,
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "MQTT 发布过慢，丢失部分事件");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let Some(message) = MqttMessage::from_event(&prefix, &event) else {
+                continue;
+            };
+            if let Err(e) = client.try_publish(
+                &message.topic,
+                QoS::AtLeastOnce,
+                message.retain,
+                message.payload,
+            ) {
+                warn!(topic = %message.topic, error = %e, "发布 MQTT 消息失败");
+            }
+        }
+
+        let _ = client.try_publish(&status, QoS::AtLeastOnce, true, "offline");
+        let _ = client.try_disconnect();
+        stop.cancel();
+        let _ = connection.await;
+    }
+}
+
+fn topic_for(prefix: &str, topic: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        topic.to_string()
+    } else {
+        format!("{}/{}", prefix, topic)
+    }
+}
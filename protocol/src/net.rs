@@ -0,0 +1,228 @@
+//! IPv4 / IPv6 地址处理
+//!
+//! 设备地址以字符串保存，可以是 IPv4、IPv6 字面量 (可带方括号) 或主机名。
+//! 拼接 URL 时 IPv6 字面量须加方括号；双栈套接字上的 IPv4 对端显示为
+//! `::ffff:a.b.c.d`，比较和匹配规则前统一还原为 IPv4
+
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::IpMode;
+
+impl IpMode {
+    /// 监听所有网卡时使用的通配地址
+    pub fn unspecified(self) -> IpAddr {
+        match self {
+            IpMode::V4Only => Ipv4Addr::UNSPECIFIED.into(),
+            IpMode::Dual | IpMode::V6Only => Ipv6Addr::UNSPECIFIED.into(),
+        }
+    }
+}
+
+/// 解析 IP 字面量，接受带方括号的 IPv6，IPv4 映射地址还原为 IPv4
+pub fn parse_ip(host: &str) -> Option<IpAddr> {
+    let host = host.trim();
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    host.parse::<IpAddr>().ok().map(|ip| ip.to_canonical())
+}
+
+/// 是否为合法的主机名，例如 `nas.lan`、`laptop.local`
+pub fn is_hostname(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+}
+
+/// 是否可作为网络地址直接探测，即 IP 字面量或主机名
+pub fn is_host(target: &str) -> bool {
+    parse_ip(target).is_some() || is_hostname(target.trim())
+}
+
+/// 规范化地址字符串：IP 字面量去掉方括号并还原 IPv4 映射地址，主机名原样返回
+pub fn normalize_host(host: &str) -> String {
+    match parse_ip(host) {
+        Some(ip) => ip.to_string(),
+        None => host.trim().to_string(),
+    }
+}
+
+/// 还原双栈套接字上的 IPv4 映射地址
+pub fn canonical(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(addr.ip().to_canonical(), addr.port())
+}
+
+/// URL 中的主机部分，IPv6 字面量加方括号
+pub fn url_host(host: &str) -> Cow<'_, str> {
+    match parse_ip(host) {
+        Some(IpAddr::V6(ip)) => Cow::Owned(format!("[{}]", ip)),
+        Some(IpAddr::V4(_)) | None => Cow::Borrowed(host.trim()),
+    }
+}
+
+/// `主机:端口`，IPv6 字面量加方括号
+pub fn host_port(host: &str, port: u16) -> String {
+    format!("{}:{}", url_host(host), port)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::time::Duration;
+
+    use socket2::{Domain, Protocol, Socket, Type};
+    use tracing::{debug, warn};
+
+    use super::parse_ip;
+    use crate::IpMode;
+
+    /// 在 addr 上绑定 TCP 监听套接字
+    ///
+    /// addr 为 IPv6 地址时按 mode 决定是否同时接受 IPv4 连接。
+    /// 双栈模式下在 IPv6 通配地址上绑定失败 (例如本机禁用了 IPv6) 时退回 IPv4 通配地址
+    pub fn bind_tcp(addr: SocketAddr, mode: IpMode) -> io::Result<std::net::TcpListener> {
+        with_fallback(addr, mode, |addr| {
+            let socket = socket(addr, mode, Type::STREAM, Protocol::TCP)?;
+            #[cfg(not(windows))]
+            socket.set_reuse_address(true)?;
+            socket.bind(&addr.into())?;
+            socket.listen(1024)?;
+            Ok(socket.into())
+        })
+    }
+
+    /// 在 addr 上绑定 UDP 套接字，地址族的处理同 [`bind_tcp`]
+    pub fn bind_udp(addr: SocketAddr, mode: IpMode) -> io::Result<std::net::UdpSocket> {
+        with_fallback(addr, mode, |addr| {
+            let socket = socket(addr, mode, Type::DGRAM, Protocol::UDP)?;
+            socket.bind(&addr.into())?;
+            Ok(socket.into())
+        })
+    }
+
+    fn socket(addr: SocketAddr, mode: IpMode, ty: Type, protocol: Protocol) -> io::Result<Socket> {
+        let socket = Socket::new(Domain::for_address(addr), ty, Some(protocol))?;
+        if addr.is_ipv6() {
+            // 各平台默认值不同，Windows 默认只接受 IPv6
+            socket.set_only_v6(mode == IpMode::V6Only)?;
+        }
+        Ok(socket)
+    }
+
+    fn with_fallback<T>(
+        addr: SocketAddr,
+        mode: IpMode,
+        bind: impl Fn(SocketAddr) -> io::Result<T>,
+    ) -> io::Result<T> {
+        match bind(addr) {
+            Err(e)
+                if mode == IpMode::Dual
+                    && addr.ip() == IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+                    && !matches!(
+                        e.kind(),
+                        io::ErrorKind::AddrInUse | io::ErrorKind::PermissionDenied
+                    ) =>
+            {
+                warn!(error = %e, port = addr.port(), "IPv6 不可用，只监听 IPv4");
+                bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), addr.port()))
+            }
+            result => result,
+        }
+    }
+
+    /// 解析主机的所有地址，IPv6 与 IPv4 交替排列且 IPv6 在前 (RFC 8305)，
+    /// 逐个尝试即可优先使用可达的地址族
+    pub async fn resolve(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        if let Some(ip) = parse_ip(host) {
+            return Ok(vec![SocketAddr::new(ip, port)]);
+        }
+        let (mut v6, mut v4): (Vec<_>, Vec<_>) = tokio::net::lookup_host((host.trim(), port))
+            .await?
+            .partition(SocketAddr::is_ipv6);
+        v6.reverse();
+        v4.reverse();
+        let mut addrs = Vec::with_capacity(v6.len() + v4.len());
+        while let Some(addr) = v6.pop().or_else(|| v4.pop()) {
+            addrs.push(addr);
+            if let Some(addr) = v4.pop() {
+                addrs.push(addr);
+            }
+        }
+        if addrs.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("无法解析 {}", host)));
+        }
+        Ok(addrs)
+    }
+
+    /// 发往 dest 时系统选用的本机地址，只查询路由，不发送数据
+    pub fn route_source(dest: IpAddr) -> Option<IpAddr> {
+        let unspecified: IpAddr = match dest {
+            IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        };
+        let socket = std::net::UdpSocket::bind(SocketAddr::new(unspecified, 0)).ok()?;
+        socket.connect(SocketAddr::new(dest, 9)).ok()?;
+        let ip = socket.local_addr().ok()?.ip();
+        (!ip.is_unspecified()).then_some(ip)
+    }
+
+    /// 按顺序探测各地址上的 port，返回第一个能在 timeout 内建立 TCP 连接的地址
+    pub async fn first_reachable(
+        candidates: &[IpAddr],
+        port: u16,
+        timeout: Duration,
+    ) -> Option<IpAddr> {
+        for &ip in candidates {
+            let connect = tokio::net::TcpStream::connect(SocketAddr::new(ip, port));
+            match tokio::time::timeout(timeout, connect).await {
+                Ok(Ok(_)) => return Some(ip),
+                Ok(Err(e)) => debug!(%ip, port, error = %e, "候选地址不可达"),
+                Err(_) => debug!(%ip, port, "连接候选地址超时"),
+            }
+        }
+        None
+    }
+
+    /// 是否为 macOS 拒绝本地网络访问导致的错误
+    ///
+    /// 未授予本地网络权限时，发往多播组和局域网地址的数据包被系统丢弃，
+    /// 发送或连接返回 EHOSTUNREACH，即使路由正常。其他平台没有此权限，总是返回 false
+    pub fn is_local_network_denied(error: &io::Error) -> bool {
+        // EHOSTUNREACH 在 macOS 上为 65
+        cfg!(target_os = "macos") && error.raw_os_error() == Some(65)
+    }
+
+    /// 把发往局域网的 IO 错误转为协议错误，本地网络权限被拒绝时给出处理办法
+    pub fn local_network_error(error: io::Error) -> crate::ProtocolError {
+        if is_local_network_denied(&error) {
+            crate::ProtocolError::LocalNetworkDenied
+        } else {
+            error.into()
+        }
+    }
+
+    /// 连接阶段的错误，说明该地址不可达，可改用对方的其他地址
+    pub fn is_unreachable(error: &io::Error) -> bool {
+        matches!(
+            error.kind(),
+            io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::HostUnreachable
+                | io::ErrorKind::NetworkUnreachable
+                | io::ErrorKind::AddrNotAvailable
+                | io::ErrorKind::TimedOut
+        )
+    }
+}
@@ -0,0 +1,252 @@
+//! 发件箱目录
+//!
+//! 每个发件箱目录对应一台目标设备，放入目录的文件会自动发送，
+//! 发送成功后移入目录下的 `sent/` 归档。扫描仪、构建产物等场景无需任何交互。
+//!
+//! [`Outbox::flush`] 发送当前已写完的文件；启用 `outbox` 特性后
+//! [`Outbox::watch`] 通过 notify 监视目录变化并自动调用。
+//! 设置了 [`Metered`] 时，省流状态下的大文件暂缓发送，退出省流状态后自动发送
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "outbox")]
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use crate::metered::Metered;
+use crate::{DeviceInfo, LocalSendClient, OutboxConfig, ProtocolError};
+
+/// 归档目录名
+pub const SENT_DIR: &str = "sent";
+
+/// 文件最后一次修改后等待的时长，期间视为仍在写入
+const DEFAULT_SETTLE: Duration = Duration::from_secs(2);
+
+/// 查找目标设备时的发现时长
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 发送失败后重新扫描的间隔
+#[cfg(feature = "outbox")]
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 单个发件箱
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    client: LocalSendClient,
+    dir: PathBuf,
+    /// 目标设备的 ID、名称、IP 或主机名
+    target: String,
+    settle: Duration,
+    metered: Metered,
+}
+
+impl Outbox {
+    /// 创建发件箱，target 为目标设备的 ID、名称、IP 或主机名
+    pub fn new(client: LocalSendClient, dir: impl Into<PathBuf>, target: impl Into<String>) -> Self {
+        Self {
+            client,
+            dir: dir.into(),
+            target: target.into(),
+            settle: DEFAULT_SETTLE,
+            metered: Metered::default(),
+        }
+    }
+
+    /// 按配置创建发件箱
+    pub fn from_config(client: LocalSendClient, config: &OutboxConfig) -> Self {
+        Self::new(client, &config.dir, &config.device)
+    }
+
+    /// 设置文件写完的判定时长，文件在此时长内被修改过时暂不发送
+    pub fn with_settle(mut self, settle: Duration) -> Self {
+        self.settle = settle;
+        self
+    }
+
+    /// 省流状态下暂缓发送超过 metered 大小上限的文件
+    pub fn with_metered(mut self, metered: Metered) -> Self {
+        self.metered = metered;
+        self
+    }
+
+    /// 发件箱目录
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 已写完、等待发送的文件，按文件名排序
+    ///
+    /// 只包含目录下的普通文件，忽略子目录和隐藏文件；省流状态下暂缓的大文件不包含在内
+    pub async fn pending(&self) -> crate::Result<Vec<PathBuf>> {
+        let now = SystemTime::now();
+        let mut files = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let settled = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_none_or(|age| age >= self.settle);
+            if settled && self.metered.holds(metadata.len()) {
+                debug!(file = %entry.path().display(), size = metadata.len(), "省流模式，暂缓发送");
+            } else if settled {
+                files.push(entry.path());
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// 查找目标设备并发送待发送的文件，返回归档后的路径
+    pub async fn flush(&self) -> crate::Result<Vec<PathBuf>> {
+        if self.pending().await?.is_empty() {
+            return Ok(Vec::new());
+        }
+        let device = self.resolve().await?;
+        self.flush_to(&device).await
+    }
+
+    /// 向已知设备发送待发送的文件，返回归档后的路径
+    ///
+    /// 所有文件在同一会话中发送，失败时文件留在原处等待下次发送
+    pub async fn flush_to(&self, device: &DeviceInfo) -> crate::Result<Vec<PathBuf>> {
+        let files = self.pending().await?;
+        if files.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        info!(dir = %self.dir.display(), device = %device.name, count = files.len(), "发送发件箱文件");
+        self.client.send_files(device, &files).await?;
+
+        let sent_dir = self.dir.join(SENT_DIR);
+        tokio::fs::create_dir_all(&sent_dir).await?;
+        let mut archived = Vec::with_capacity(files.len());
+        for file in files {
+            let target = unique_path(&sent_dir, &file).await;
+            tokio::fs::rename(&file, &target).await?;
+            debug!(file = %target.display(), "已归档");
+            archived.push(target);
+        }
+        Ok(archived)
+    }
+
+    /// 监视发件箱目录，有文件写入时自动发送，直到 cancel 取消
+    ///
+    /// 目录不存在时会创建。启动时先发送目录中已有的文件；
+    /// 发送失败的文件在下次目录变化或定期重试时再次发送，省流状态改变时也立即重新扫描
+    #[cfg(feature = "outbox")]
+    pub async fn watch(&self, cancel: CancellationToken) -> crate::Result<()> {
+        use notify::{RecursiveMode, Watcher};
+        use tokio::sync::mpsc;
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let (changes_tx, mut changes) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if event.is_ok_and(|e| !e.kind.is_access()) {
+                let _ = changes_tx.send(());
+            }
+        })
+        .map_err(watch_error)?;
+        watcher
+            .watch(&self.dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+        info!(dir = %self.dir.display(), target = %self.target, "开始监视发件箱");
+        let mut metered = self.metered.subscribe();
+
+        loop {
+            self.flush_logged(&cancel).await;
+
+            tokio::select! {
+                _ = cancel.cancelled() => return Ok(()),
+                _ = changes.recv() => {}
+                Ok(()) = metered.changed() => {}
+                _ = tokio::time::sleep(RETRY_INTERVAL) => {}
+            }
+            // 等待写入停止
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => return Ok(()),
+                    _ = changes.recv() => {}
+                    _ = tokio::time::sleep(self.settle) => break,
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "outbox")]
+    async fn flush_logged(&self, cancel: &CancellationToken) {
+        tokio::select! {
+            _ = cancel.cancelled() => {}
+            result = self.flush() => match result {
+                Ok(archived) if !archived.is_empty() => {
+                    info!(dir = %self.dir.display(), count = archived.len(), "发件箱文件已发送");
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(dir = %self.dir.display(), error = %e, "发送发件箱文件失败"),
+            }
+        }
+    }
+
+    /// 查找目标设备
+    async fn resolve(&self) -> crate::Result<DeviceInfo> {
+        resolve_device(&self.client, &self.target).await
+    }
+}
+
+/// 按 ID、名称、IP 或主机名查找设备
+pub(crate) async fn resolve_device(
+    client: &LocalSendClient,
+    target: &str,
+) -> crate::Result<DeviceInfo> {
+    let devices = client.discover(DISCOVERY_TIMEOUT).await?;
+    if let Some(device) = devices
+        .into_iter()
+        .find(|d| d.id == target || d.name == target || d.ip == target)
+    {
+        return Ok(device);
+    }
+
+    // 多播不可达时直接探测地址，主机名先解析
+    if crate::net::is_host(target) {
+        if let Some(device) = client.check_device(target).await {
+            return Ok(device);
+        }
+    }
+    Err(ProtocolError::InvalidConfig(format!("未找到设备: {}", target)))
+}
+
+/// 归档目录中不与已有文件重名的路径，重名时追加序号
+async fn unique_path(dir: &Path, file: &Path) -> PathBuf {
+    let name = file.file_name().unwrap_or_default();
+    let candidate = dir.join(name);
+    if !tokio::fs::try_exists(&candidate).await.unwrap_or(false) {
+        return candidate;
+    }
+
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = file
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut n = 1;
+    loop {
+        let candidate = dir.join(format!("{} ({}){}", stem, n, extension));
+        if !tokio::fs::try_exists(&candidate).await.unwrap_or(false) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+#[cfg(feature = "outbox")]
+fn watch_error(e: notify::Error) -> ProtocolError {
+    std::io::Error::other(e).into()
+}
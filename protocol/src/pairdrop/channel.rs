@@ -0,0 +1,385 @@
+//! 数据通道上的文件传输协议
+//!
+//! 与 PairDrop 网页端的 `peer.js` 保持一致：控制消息为 JSON 文本帧，文件内容为二进制帧。
+//! 发送方先发 `request` 列出全部文件，得到 `files-transfer-response` 后逐个发送
+//! `header` 和内容块；每满一个分段发送 `partition`，等到 `partition-received` 再继续，
+//! 以此限制通道中积压的数据量。接收方收完一个文件回复 `file-transfer-complete`
+//!
+//! Snapdrop 没有 `request` 步骤，直接从 `header` 开始，接收端同样能处理
+
+use std::future::Future;
+use std::path::Path;
+
+use async_trait::async_trait;
+use base64::Engine;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+
+use crate::fs::BoxedReader;
+use crate::storage::{ReceiveStorage, StorageKey};
+use crate::ProtocolError;
+
+/// 单个二进制帧的大小，与网页端相同
+pub const CHUNK_SIZE: usize = 64_000;
+
+/// 分段大小，每个分段需要接收端确认
+pub const PARTITION_SIZE: u64 = 1_000_000;
+
+/// 数据通道上的一帧
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    Text(String),
+    Binary(Bytes),
+}
+
+/// 可靠有序的消息通道
+#[async_trait]
+pub trait DataChannel: Send {
+    /// 发送一帧
+    async fn send(&mut self, frame: Frame) -> crate::Result<()>;
+
+    /// 接收下一帧，通道关闭时返回 None
+    async fn recv(&mut self) -> crate::Result<Option<Frame>>;
+}
+
+/// 通道上的控制消息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ChannelMessage {
+    /// 发送请求，列出所有文件
+    Request {
+        header: Vec<FileHeader>,
+        #[serde(rename = "totalSize")]
+        total_size: u64,
+        #[serde(rename = "imagesOnly", default)]
+        images_only: bool,
+    },
+    FilesTransferResponse {
+        accepted: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// 随后的二进制帧属于这个文件
+    Header(FileHeader),
+    Partition { offset: u64 },
+    PartitionReceived { offset: u64 },
+    /// 接收进度，0 到 1
+    Progress { progress: f64 },
+    /// Snapdrop 使用 `transfer-complete`
+    #[serde(alias = "transfer-complete")]
+    FileTransferComplete,
+    /// UTF-8 文本的 Base64
+    Text { text: String },
+    MessageTransferComplete,
+    #[serde(other)]
+    Unknown,
+}
+
+/// 文件描述
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHeader {
+    pub name: String,
+    pub mime: String,
+    pub size: u64,
+}
+
+/// 待发送的文件
+pub struct OutgoingFile {
+    pub header: FileHeader,
+    pub reader: BoxedReader,
+}
+
+impl std::fmt::Debug for OutgoingFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutgoingFile")
+            .field("header", &self.header)
+            .finish_non_exhaustive()
+    }
+}
+
+/// 一次接收的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Received {
+    /// 收到的文件在存储中的位置
+    Files(Vec<String>),
+    Text(String),
+}
+
+async fn send_message<C: DataChannel + ?Sized>(
+    channel: &mut C,
+    message: &ChannelMessage,
+) -> crate::Result<()> {
+    channel.send(Frame::Text(serde_json::to_string(message)?)).await
+}
+
+/// 等待指定的控制消息，跳过进度等其他消息
+async fn expect<C, T>(
+    channel: &mut C,
+    mut matches: impl FnMut(ChannelMessage) -> Option<T>,
+) -> crate::Result<T>
+where
+    C: DataChannel + ?Sized,
+{
+    loop {
+        match channel.recv().await? {
+            Some(Frame::Text(text)) => {
+                if let Some(value) = matches(serde_json::from_str(&text)?) {
+                    return Ok(value);
+                }
+            }
+            Some(Frame::Binary(_)) => {
+                return Err(ProtocolError::InvalidData("意外的二进制帧".to_string()));
+            }
+            None => return Err(closed()),
+        }
+    }
+}
+
+/// 发送文件，on_sent 在每个文件被对方确认后以文件序号调用
+pub async fn send_files<C>(
+    channel: &mut C,
+    files: Vec<OutgoingFile>,
+    mut on_sent: impl FnMut(usize),
+) -> crate::Result<()>
+where
+    C: DataChannel + ?Sized,
+{
+    let request = ChannelMessage::Request {
+        header: files.iter().map(|f| f.header.clone()).collect(),
+        total_size: files.iter().map(|f| f.header.size).sum(),
+        images_only: files.iter().all(|f| f.header.mime.starts_with("image/")),
+    };
+    send_message(channel, &request).await?;
+
+    let (accepted, reason) = expect(channel, |message| match message {
+        ChannelMessage::FilesTransferResponse { accepted, reason } => Some((accepted, reason)),
+        _ => None,
+    })
+    .await?;
+    if !accepted {
+        return Err(ProtocolError::Rejected(
+            reason.unwrap_or_else(|| "对方拒绝了文件".to_string()),
+        ));
+    }
+
+    for (index, mut file) in files.into_iter().enumerate() {
+        send_message(channel, &ChannelMessage::Header(file.header.clone())).await?;
+
+        let mut offset = 0u64;
+        let mut partition_end = PARTITION_SIZE;
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        while offset < file.header.size {
+            let len = (file.header.size - offset).min(CHUNK_SIZE as u64) as usize;
+            file.reader.read_exact(&mut buf[..len]).await?;
+            channel
+                .send(Frame::Binary(Bytes::copy_from_slice(&buf[..len])))
+                .await?;
+            offset += len as u64;
+
+            if offset >= partition_end && offset < file.header.size {
+                send_message(channel, &ChannelMessage::Partition { offset }).await?;
+                expect(channel, |message| match message {
+                    ChannelMessage::PartitionReceived { offset: acked } if acked == offset => {
+                        Some(())
+                    }
+                    _ => None,
+                })
+                .await?;
+                partition_end = offset + PARTITION_SIZE;
+            }
+        }
+
+        expect(channel, |message| {
+            matches!(message, ChannelMessage::FileTransferComplete).then_some(())
+        })
+        .await?;
+        on_sent(index);
+    }
+
+    Ok(())
+}
+
+/// 发送文本消息
+pub async fn send_text<C>(channel: &mut C, text: &str) -> crate::Result<()>
+where
+    C: DataChannel + ?Sized,
+{
+    let text = base64::engine::general_purpose::STANDARD.encode(text);
+    send_message(channel, &ChannelMessage::Text { text }).await?;
+    expect(channel, |message| {
+        matches!(message, ChannelMessage::MessageTransferComplete).then_some(())
+    })
+    .await
+}
+
+/// 接收一次传输：一组文件或一条文本
+///
+/// accept 决定是否接受对方列出的文件，Snapdrop 对端没有请求步骤，每个文件单独询问。
+/// 文件以 `session_id` 和序号作为存储键写入 storage。通道在传输开始前关闭时返回 None
+pub async fn receive<C, F, Fut>(
+    channel: &mut C,
+    storage: &dyn ReceiveStorage,
+    session_id: &str,
+    accept: F,
+) -> crate::Result<Option<Received>>
+where
+    C: DataChannel + ?Sized,
+    F: FnOnce(Vec<FileHeader>) -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let mut accept = Some(accept);
+    // 对方声明的文件数，Snapdrop 对端为 None
+    let mut expected = None;
+    let mut current: Option<(StorageKey, FileHeader, u64)> = None;
+    let mut locations = Vec::new();
+
+    loop {
+        let frame = match channel.recv().await {
+            Ok(frame) => frame,
+            Err(e) => {
+                abort(storage, &mut current).await;
+                return Err(e);
+            }
+        };
+
+        match frame {
+            None if current.is_some() => {
+                abort(storage, &mut current).await;
+                return Err(closed());
+            }
+            None if expected.is_none() && locations.is_empty() => return Ok(None),
+            None => return Ok(Some(Received::Files(locations))),
+            Some(Frame::Binary(data)) => {
+                let Some((key, header, received)) = current.as_mut() else {
+                    return Err(ProtocolError::InvalidData("数据帧之前没有文件头".to_string()));
+                };
+                *received += data.len() as u64;
+                if *received > header.size {
+                    abort(storage, &mut current).await;
+                    return Err(ProtocolError::InvalidData("文件内容超过声明的大小".to_string()));
+                }
+                storage.write_chunk(key, &data).await?;
+                if *received == header.size {
+                    let (key, _, _) = current.take().expect("当前文件存在");
+                    locations.push(storage.finalize(&key).await?);
+                    send_message(channel, &ChannelMessage::Progress { progress: 1.0 }).await?;
+                    send_message(channel, &ChannelMessage::FileTransferComplete).await?;
+                    if expected.is_none_or(|n| locations.len() >= n) {
+                        return Ok(Some(Received::Files(locations)));
+                    }
+                }
+            }
+            Some(Frame::Text(text)) => match serde_json::from_str(&text)? {
+                ChannelMessage::Request { header, .. } => {
+                    let Some(accept) = accept.take() else {
+                        return Err(ProtocolError::InvalidData("重复的发送请求".to_string()));
+                    };
+                    expected = Some(header.len());
+                    let accepted = accept(header).await;
+                    send_message(
+                        channel,
+                        &ChannelMessage::FilesTransferResponse {
+                            accepted,
+                            reason: None,
+                        },
+                    )
+                    .await?;
+                    if !accepted {
+                        return Err(ProtocolError::Rejected("已拒绝对方的文件".to_string()));
+                    }
+                }
+                ChannelMessage::Header(header) => {
+                    if current.is_some() {
+                        abort(storage, &mut current).await;
+                        return Err(ProtocolError::InvalidData("上一个文件尚未收完".to_string()));
+                    }
+                    if let Some(accept) = accept.take() {
+                        if !accept(vec![header.clone()]).await {
+                            return Err(ProtocolError::Rejected("已拒绝对方的文件".to_string()));
+                        }
+                    }
+
+                    // 只保留文件名，防止路径穿越
+                    let name = Path::new(&header.name)
+                        .file_name()
+                        .ok_or_else(|| {
+                            ProtocolError::InvalidData(format!("无效的文件名: {}", header.name))
+                        })?
+                        .to_string_lossy()
+                        .into_owned();
+                    let key = StorageKey {
+                        session_id: session_id.to_string(),
+                        file_id: locations.len().to_string(),
+                        name,
+                    };
+                    storage.open(&key).await?;
+                    if header.size == 0 {
+                        locations.push(storage.finalize(&key).await?);
+                        send_message(channel, &ChannelMessage::FileTransferComplete).await?;
+                        if expected.is_none_or(|n| locations.len() >= n) {
+                            return Ok(Some(Received::Files(locations)));
+                        }
+                    } else {
+                        current = Some((key, header, 0));
+                    }
+                }
+                ChannelMessage::Partition { offset } => {
+                    send_message(channel, &ChannelMessage::PartitionReceived { offset }).await?;
+                }
+                ChannelMessage::Text { text } => {
+                    let data = base64::engine::general_purpose::STANDARD
+                        .decode(text.as_bytes())
+                        .map_err(|e| ProtocolError::InvalidData(format!("无效的文本消息: {}", e)))?;
+                    let text = String::from_utf8(data)
+                        .map_err(|e| ProtocolError::InvalidData(format!("无效的文本消息: {}", e)))?;
+                    send_message(channel, &ChannelMessage::MessageTransferComplete).await?;
+                    return Ok(Some(Received::Text(text)));
+                }
+                _ => {}
+            },
+        }
+    }
+}
+
+async fn abort(storage: &dyn ReceiveStorage, current: &mut Option<(StorageKey, FileHeader, u64)>) {
+    if let Some((key, _, _)) = current.take() {
+        let _ = storage.abort(&key).await;
+    }
+}
+
+fn closed() -> ProtocolError {
+    std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "数据通道已关闭").into()
+}
+
+/// 内存中的数据通道
+///
+/// [`pair`](Self::pair) 返回相互连接的两端，用于测试和进程内传输
+#[derive(Debug)]
+pub struct MemoryChannel {
+    tx: mpsc::UnboundedSender<Frame>,
+    rx: mpsc::UnboundedReceiver<Frame>,
+}
+
+impl MemoryChannel {
+    /// 创建相互连接的两端
+    pub fn pair() -> (Self, Self) {
+        let (a_tx, a_rx) = mpsc::unbounded_channel();
+        let (b_tx, b_rx) = mpsc::unbounded_channel();
+        (Self { tx: a_tx, rx: b_rx }, Self { tx: b_tx, rx: a_rx })
+    }
+}
+
+#[async_trait]
+impl DataChannel for MemoryChannel {
+    async fn send(&mut self, frame: Frame) -> crate::Result<()> {
+        self.tx
+            .send(frame)
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe).into())
+    }
+
+    async fn recv(&mut self) -> crate::Result<Option<Frame>> {
+        Ok(self.rx.recv().await)
+    }
+}
@@ -0,0 +1,503 @@
+//! PairDrop / Snapdrop 互通
+//!
+//! 连接 PairDrop 或 Snapdrop 的信令服务器，把同一房间（默认按公网 IP 分组）中的浏览器
+//! 登记为发现的设备，并通过 WebRTC 数据通道与其收发文件和文本。
+//! 这些设备的传输列表只有 [`PAIRDROP_TRANSPORT`]，不能经 HTTP 访问
+//!
+//! - [`signal`]：信令消息
+//! - [`channel`]：数据通道上的文件传输协议
+//! - `rtc`：基于 webrtc-rs 的数据通道
+
+pub mod channel;
+mod rtc;
+pub mod signal;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
+
+use crate::discovery::DiscoveryManagerRef;
+use crate::event::ReceiveEvent;
+use crate::server::accept::AcceptGate;
+use crate::storage::{self, FsStorage, ReceiveStorageRef};
+use crate::{
+    DeviceInfo, FileInfo, LocalSendConfig, ProtocolError, ProtocolEvent, SessionManager,
+    SessionState, SESSION_TIMEOUT_SECS,
+};
+use channel::{FileHeader, OutgoingFile, Received};
+pub use rtc::RtcChannel;
+use rtc::Outgoing;
+use signal::{ClientMessage, IceServer, Room, ServerMessage, Signal};
+
+/// 公告中使用的传输名称
+pub const PAIRDROP_TRANSPORT: &str = "pairdrop";
+
+/// 公共 PairDrop 实例的信令地址，局域网部署应指向自建实例
+pub const DEFAULT_SIGNALING_URL: &str = "wss://pairdrop.net/server?webrtc_supported=true";
+
+/// 服务器未下发配置时使用的 STUN 服务器
+const DEFAULT_STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+
+/// 设备是否只能经 PairDrop 访问
+pub fn is_pairdrop_device(device: &DeviceInfo) -> bool {
+    device.transports.iter().any(|t| t == PAIRDROP_TRANSPORT)
+}
+
+/// 信令连接上共享的状态
+#[derive(Debug)]
+struct Signaling {
+    outgoing: mpsc::UnboundedSender<ClientMessage>,
+    ice_servers: std::sync::Mutex<Vec<IceServer>>,
+    /// 对端 ID 到所在房间，转发信令时需要
+    rooms: std::sync::Mutex<HashMap<String, Room>>,
+    /// 正在建立连接的对端，后续信令转发给对应的连接
+    pending: std::sync::Mutex<HashMap<String, mpsc::UnboundedSender<Signal>>>,
+}
+
+/// PairDrop 发现与传输
+///
+/// 克隆后共享同一个信令连接
+#[derive(Debug, Clone)]
+pub struct PairDrop {
+    url: String,
+    config: LocalSendConfig,
+    sessions: Arc<Mutex<SessionManager>>,
+    discovery: DiscoveryManagerRef,
+    storage: ReceiveStorageRef,
+    events: Option<mpsc::UnboundedSender<ReceiveEvent>>,
+    accept: AcceptGate,
+    accept_timeout: Duration,
+    signaling: Arc<Signaling>,
+    outgoing_rx: Arc<std::sync::Mutex<Option<mpsc::UnboundedReceiver<ClientMessage>>>>,
+    cancel: CancellationToken,
+}
+
+impl PairDrop {
+    /// 创建连接到 url 信令服务器的实例，收到的文件保存到配置的下载目录
+    pub fn new(
+        url: impl Into<String>,
+        config: LocalSendConfig,
+        sessions: Arc<Mutex<SessionManager>>,
+        discovery: DiscoveryManagerRef,
+    ) -> Self {
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        Self {
+            url: url.into(),
+            storage: Arc::new(
+                FsStorage::new(config.download_dir.clone())
+                    .with_quarantine(config.quarantine_extensions.clone()),
+            ),
+            config,
+            sessions,
+            discovery,
+            events: None,
+            accept: AcceptGate::new(),
+            accept_timeout: Duration::from_secs(SESSION_TIMEOUT_SECS),
+            signaling: Arc::new(Signaling {
+                outgoing,
+                ice_servers: std::sync::Mutex::new(vec![IceServer {
+                    urls: vec![DEFAULT_STUN_SERVER.to_string()],
+                    username: None,
+                    credential: None,
+                }]),
+                rooms: std::sync::Mutex::new(HashMap::new()),
+                pending: std::sync::Mutex::new(HashMap::new()),
+            }),
+            outgoing_rx: Arc::new(std::sync::Mutex::new(Some(outgoing_rx))),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// 使用指定的存储后端保存收到的文件
+    pub fn with_storage(mut self, storage: ReceiveStorageRef) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// 设置接收事件的发送端
+    pub fn with_event_sender(mut self, events: mpsc::UnboundedSender<ReceiveEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// 设置等待用户确认的最长时间，超时视为拒绝
+    pub fn with_accept_timeout(mut self, timeout: Duration) -> Self {
+        self.accept_timeout = timeout;
+        self
+    }
+
+    /// 使用外部的取消令牌，令牌取消时断开信令连接
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 答复挂起的文件发送请求，会话不在等待确认时返回 false
+    pub fn respond(&self, session_id: &str, accept: bool) -> bool {
+        self.accept.respond(session_id, accept)
+    }
+
+    /// 断开信令连接
+    pub fn shutdown(&self) {
+        self.cancel.cancel();
+    }
+
+    /// 连接信令服务器并在后台处理消息，只能调用一次
+    #[instrument(skip(self), fields(url = %self.url))]
+    pub async fn start(&self) -> crate::Result<()> {
+        let mut outgoing = self
+            .outgoing_rx
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| ProtocolError::InvalidConfig("PairDrop 已启动".to_string()))?;
+
+        let (socket, _) = tokio_tungstenite::connect_async(self.url.as_str())
+            .await
+            .map_err(std::io::Error::other)?;
+        let (mut sink, mut stream) = socket.split();
+        info!("已连接 PairDrop 信令服务器");
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = this.cancel.cancelled() => {
+                        let _ = sink.send(Message::Text(serde_json::to_string(&ClientMessage::Disconnect).unwrap_or_default())).await;
+                        break;
+                    }
+                    message = outgoing.recv() => {
+                        let Some(message) = message else { break };
+                        let Ok(text) = serde_json::to_string(&message) else { continue };
+                        if let Err(e) = sink.send(Message::Text(text)).await {
+                            warn!(error = %e, "发送信令失败");
+                            break;
+                        }
+                    }
+                    message = stream.next() => {
+                        match message {
+                            Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                                Ok(message) => this.handle(message).await,
+                                Err(e) => debug!(error = %e, "无法解析的信令消息"),
+                            },
+                            Some(Ok(_)) => {}
+                            Some(Err(e)) => {
+                                warn!(error = %e, "信令连接出错");
+                                break;
+                            }
+                            None => break,
+                        }
+                    }
+                }
+            }
+            info!("PairDrop 信令连接已断开");
+            this.forget_peers().await;
+        });
+
+        Ok(())
+    }
+
+    async fn handle(&self, message: ServerMessage) {
+        match message {
+            ServerMessage::WsConfig { ws_config } => {
+                let servers = ws_config
+                    .and_then(|c| c.rtc_config)
+                    .map(|c| c.ice_servers)
+                    .unwrap_or_default();
+                if !servers.is_empty() {
+                    *self.signaling.ice_servers.lock().unwrap() = servers;
+                }
+            }
+            ServerMessage::DisplayName(name) => {
+                debug!(name = ?name.display_name(), "信令服务器分配的名称");
+            }
+            ServerMessage::Peers { peers, room } => {
+                for peer in peers {
+                    self.add_peer(&peer, &room).await;
+                }
+            }
+            ServerMessage::PeerJoined { peer, room } => self.add_peer(&peer, &room).await,
+            ServerMessage::PeerLeft { peer_id } => {
+                self.signaling.rooms.lock().unwrap().remove(&peer_id);
+                self.discovery.lock().await.remove_device(&peer_id).await;
+            }
+            ServerMessage::Signal(signal) => {
+                let peer_id = signal.sender.id().to_string();
+                if signal.room.room_type.is_some() {
+                    self.signaling
+                        .rooms
+                        .lock()
+                        .unwrap()
+                        .insert(peer_id.clone(), signal.room.clone());
+                }
+
+                let pending = self.signaling.pending.lock().unwrap().get(&peer_id).cloned();
+                match pending {
+                    Some(connection) => {
+                        let _ = connection.send(signal);
+                    }
+                    None if signal.is_offer() => {
+                        let this = self.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = this.serve_peer(&peer_id, signal).await {
+                                warn!(peer = %peer_id, error = %e, "PairDrop 接收失败");
+                            }
+                        });
+                    }
+                    None => debug!(peer = %peer_id, "忽略未知连接的信令"),
+                }
+            }
+            ServerMessage::Ping => {
+                let _ = self.signaling.outgoing.send(ClientMessage::Pong);
+            }
+            ServerMessage::Unknown => {}
+        }
+    }
+
+    async fn add_peer(&self, peer: &signal::PeerInfo, room: &Room) {
+        if !peer.rtc_supported {
+            debug!(peer = %peer.id, "对端不支持 WebRTC");
+            return;
+        }
+        self.signaling
+            .rooms
+            .lock()
+            .unwrap()
+            .insert(peer.id.clone(), room.clone());
+        self.discovery
+            .lock()
+            .await
+            .add_device(peer.to_device(room))
+            .await;
+    }
+
+    /// 信令断开后对端不可达，从发现列表中移除
+    async fn forget_peers(&self) {
+        let peers: Vec<String> = self.signaling.rooms.lock().unwrap().drain().map(|(id, _)| id).collect();
+        let discovery = self.discovery.lock().await;
+        for id in peers {
+            discovery.remove_device(&id).await;
+        }
+    }
+
+    /// 把 WebRTC 连接产生的信令经服务器转发给对端
+    fn forward(&self, peer_id: &str) -> mpsc::UnboundedSender<Outgoing> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let signaling = self.signaling.clone();
+        let peer_id = peer_id.to_string();
+        tokio::spawn(async move {
+            while let Some(outgoing) = rx.recv().await {
+                let room = signaling
+                    .rooms
+                    .lock()
+                    .unwrap()
+                    .get(&peer_id)
+                    .cloned()
+                    .unwrap_or_default();
+                let (sdp, ice) = match outgoing {
+                    Outgoing::Sdp(sdp) => (Some(sdp), None),
+                    Outgoing::Ice(ice) => (None, Some(ice)),
+                };
+                let message = ClientMessage::Signal {
+                    to: peer_id.clone(),
+                    room,
+                    sdp,
+                    ice,
+                };
+                if signaling.outgoing.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        tx
+    }
+
+    /// 向对端发起 WebRTC 连接
+    async fn connect(&self, peer_id: &str) -> crate::Result<RtcChannel> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.signaling
+            .pending
+            .lock()
+            .unwrap()
+            .insert(peer_id.to_string(), tx);
+
+        let ice_servers = self.signaling.ice_servers.lock().unwrap().clone();
+        let result = rtc::offer(&ice_servers, self.forward(peer_id), rx).await;
+        self.signaling.pending.lock().unwrap().remove(peer_id);
+        result
+    }
+
+    /// 向浏览器对端发送文件，on_sent 在每个文件被确认后以序号调用
+    #[instrument(skip(self, device, files, on_sent), fields(peer = %device.id))]
+    pub async fn send_files(
+        &self,
+        device: &DeviceInfo,
+        files: Vec<OutgoingFile>,
+        on_sent: impl FnMut(usize),
+    ) -> crate::Result<()> {
+        let mut channel = self.connect(&device.id).await?;
+        tokio::select! {
+            _ = self.cancel.cancelled() => Err(ProtocolError::Cancelled),
+            result = channel::send_files(&mut channel, files, on_sent) => result,
+        }
+    }
+
+    /// 向浏览器对端发送文本消息
+    #[instrument(skip(self, device, text), fields(peer = %device.id))]
+    pub async fn send_text(&self, device: &DeviceInfo, text: &str) -> crate::Result<()> {
+        let mut channel = self.connect(&device.id).await?;
+        channel::send_text(&mut channel, text).await
+    }
+
+    /// 应答对端的连接并持续接收，直到通道关闭
+    async fn serve_peer(&self, peer_id: &str, offer: Signal) -> crate::Result<()> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.signaling
+            .pending
+            .lock()
+            .unwrap()
+            .insert(peer_id.to_string(), tx);
+        let ice_servers = self.signaling.ice_servers.lock().unwrap().clone();
+        let result = rtc::answer(&ice_servers, offer, self.forward(peer_id), rx).await;
+        self.signaling.pending.lock().unwrap().remove(peer_id);
+        let mut channel = result?;
+        debug!(peer = %peer_id, "PairDrop 对端已连接");
+
+        let sender = self
+            .discovery
+            .lock()
+            .await
+            .get_devices()
+            .await
+            .iter()
+            .find(|d| d.id == peer_id)
+            .map(|d| d.name.clone())
+            .unwrap_or_else(|| peer_id.to_string());
+
+        loop {
+            let transfer_id = uuid::Uuid::new_v4().to_string();
+            let mut session_id = None;
+            let received = {
+                let session_id = &mut session_id;
+                let sender = sender.as_str();
+                channel::receive(&mut channel, &*self.storage, &transfer_id, move |headers| async move {
+                    let (accepted, id) = self.confirm(peer_id, sender, headers).await;
+                    *session_id = Some(id);
+                    accepted
+                })
+                .await
+            };
+            let session_id = session_id.unwrap_or(transfer_id);
+
+            match received {
+                Ok(None) => return Ok(()),
+                Ok(Some(Received::Files(locations))) => {
+                    let sessions = self.sessions.lock().await.clone();
+                    for location in &locations {
+                        if storage::is_quarantined(location, &self.config.quarantine_extensions) {
+                            warn!(session_id = %session_id, %location, "文件类型有风险，已放入隔离目录");
+                            sessions.events().emit(ProtocolEvent::FileQuarantined {
+                                session_id: session_id.clone(),
+                                path: PathBuf::from(location),
+                            });
+                        }
+                        sessions.events().emit(ProtocolEvent::FileReceived {
+                            session_id: session_id.clone(),
+                            path: PathBuf::from(location),
+                            sha256: None,
+                        });
+                    }
+                    sessions.set_state(&session_id, SessionState::Finished).await;
+                    if let Some(events) = &self.events {
+                        for location in locations {
+                            let _ = events.send(ReceiveEvent::FileReceived {
+                                session_id: session_id.clone(),
+                                path: PathBuf::from(location),
+                            });
+                        }
+                        let _ = events.send(ReceiveEvent::SessionFinished { session_id });
+                    }
+                }
+                Ok(Some(Received::Text(text))) => {
+                    self.sessions
+                        .lock()
+                        .await
+                        .events()
+                        .emit(ProtocolEvent::TextReceived {
+                            sender_id: peer_id.to_string(),
+                            sender: sender.clone(),
+                            text: text.clone(),
+                        });
+                    if let Some(events) = &self.events {
+                        let _ = events.send(ReceiveEvent::TextReceived {
+                            sender_id: peer_id.to_string(),
+                            sender: sender.clone(),
+                            text,
+                        });
+                    }
+                }
+                Err(e) => {
+                    self.sessions
+                        .lock()
+                        .await
+                        .set_state(&session_id, SessionState::Error(e.to_string()))
+                        .await;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// 为对端列出的文件创建会话并等待确认，返回是否接受和会话 ID
+    async fn confirm(&self, peer_id: &str, sender: &str, headers: Vec<FileHeader>) -> (bool, String) {
+        let files = headers
+            .into_iter()
+            .enumerate()
+            .map(|(index, header)| FileInfo {
+                id: index.to_string(),
+                name: header.name,
+                size: header.size,
+                file_type: header.mime,
+                metadata: None,
+                sha256: None,
+            })
+            .collect();
+        let sessions = self.sessions.lock().await.clone();
+        let session = sessions
+            .create_session(peer_id.to_string(), self.config.device_id.clone(), files)
+            .await;
+
+        let accepted = if self.config.quick_saves_from(peer_id) {
+            true
+        } else {
+            sessions.events().emit(ProtocolEvent::SessionRequested {
+                session_id: session.id.clone(),
+                sender: sender.to_string(),
+                files: session.files.clone(),
+            });
+            if let Some(events) = &self.events {
+                let _ = events.send(ReceiveEvent::SessionRequested {
+                    session_id: session.id.clone(),
+                    sender: sender.to_string(),
+                    files: session.files.clone(),
+                });
+            }
+            self.accept.wait(&session.id, self.accept_timeout).await
+        };
+
+        if accepted {
+            sessions.set_state(&session.id, SessionState::Transferring).await;
+        } else {
+            sessions.set_state(&session.id, SessionState::Cancelled).await;
+            sessions.remove_session(&session.id).await;
+        }
+        (accepted, session.id)
+    }
+}
@@ -0,0 +1,273 @@
+//! 基于 webrtc-rs 的数据通道
+//!
+//! 建立连接的一方创建名为 `data-channel` 的有序通道并发送 offer，
+//! 另一方回复 answer 并在 `on_data_channel` 中得到通道。SDP 与 ICE 候选
+//! 以浏览器的 JSON 格式经信令服务器转发
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+use webrtc::api::APIBuilder;
+use webrtc::data_channel::data_channel_init::RTCDataChannelInit;
+use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::RTCDataChannel;
+use webrtc::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::RTCPeerConnection;
+
+use super::channel::{DataChannel, Frame};
+use super::signal::{IceServer, Signal};
+use crate::ProtocolError;
+
+/// 与网页端一致的通道名称
+const CHANNEL_LABEL: &str = "data-channel";
+
+/// 等待通道打开的时长
+const OPEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 发往对端的信令，SDP 或 ICE 候选之一
+#[derive(Debug, Clone)]
+pub(super) enum Outgoing {
+    Sdp(Value),
+    Ice(Value),
+}
+
+/// WebRTC 数据通道
+pub struct RtcChannel {
+    peer: Arc<RTCPeerConnection>,
+    channel: Arc<RTCDataChannel>,
+    frames: mpsc::UnboundedReceiver<Frame>,
+}
+
+impl std::fmt::Debug for RtcChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RtcChannel")
+            .field("label", &self.channel.label())
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl DataChannel for RtcChannel {
+    async fn send(&mut self, frame: Frame) -> crate::Result<()> {
+        match frame {
+            Frame::Text(text) => self.channel.send_text(text).await.map_err(rtc_error)?,
+            Frame::Binary(data) => self.channel.send(&data).await.map_err(rtc_error)?,
+        };
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> crate::Result<Option<Frame>> {
+        Ok(self.frames.recv().await)
+    }
+}
+
+impl Drop for RtcChannel {
+    fn drop(&mut self) {
+        let peer = self.peer.clone();
+        tokio::spawn(async move {
+            let _ = peer.close().await;
+        });
+    }
+}
+
+/// 作为发起方建立连接
+pub(super) async fn offer(
+    ice_servers: &[IceServer],
+    outgoing: mpsc::UnboundedSender<Outgoing>,
+    mut incoming: mpsc::UnboundedReceiver<Signal>,
+) -> crate::Result<RtcChannel> {
+    let peer = new_peer(ice_servers, outgoing.clone()).await?;
+    let channel = peer
+        .create_data_channel(
+            CHANNEL_LABEL,
+            Some(RTCDataChannelInit {
+                ordered: Some(true),
+                ..Default::default()
+            }),
+        )
+        .await
+        .map_err(rtc_error)?;
+    let (frames, opened) = attach(&channel);
+
+    let offer = peer.create_offer(None).await.map_err(rtc_error)?;
+    peer.set_local_description(offer.clone())
+        .await
+        .map_err(rtc_error)?;
+    let _ = outgoing.send(Outgoing::Sdp(serde_json::to_value(&offer)?));
+
+    let signals = {
+        let peer = peer.clone();
+        async move {
+            while let Some(signal) = incoming.recv().await {
+                apply(&peer, signal).await?;
+            }
+            Err::<(), _>(closed())
+        }
+    };
+    tokio::select! {
+        result = signals => result?,
+        result = tokio::time::timeout(OPEN_TIMEOUT, opened) => {
+            result.map_err(|_| ProtocolError::Timeout)?.map_err(|_| closed())?;
+        }
+    }
+
+    Ok(RtcChannel {
+        peer,
+        channel,
+        frames,
+    })
+}
+
+/// 作为应答方建立连接，offer 为对端发来的第一条信令
+pub(super) async fn answer(
+    ice_servers: &[IceServer],
+    offer: Signal,
+    outgoing: mpsc::UnboundedSender<Outgoing>,
+    mut incoming: mpsc::UnboundedReceiver<Signal>,
+) -> crate::Result<RtcChannel> {
+    let peer = new_peer(ice_servers, outgoing.clone()).await?;
+    let (channel_tx, channel_rx) = oneshot::channel();
+    let channel_tx = std::sync::Mutex::new(Some(channel_tx));
+    peer.on_data_channel(Box::new(move |channel: Arc<RTCDataChannel>| {
+        if let Some(tx) = channel_tx.lock().unwrap().take() {
+            let _ = tx.send(channel);
+        }
+        Box::pin(async {})
+    }));
+
+    apply(&peer, offer).await?;
+    let answer = peer.create_answer(None).await.map_err(rtc_error)?;
+    peer.set_local_description(answer.clone())
+        .await
+        .map_err(rtc_error)?;
+    let _ = outgoing.send(Outgoing::Sdp(serde_json::to_value(&answer)?));
+
+    let signals = {
+        let peer = peer.clone();
+        async move {
+            while let Some(signal) = incoming.recv().await {
+                apply(&peer, signal).await?;
+            }
+            Err::<(), _>(closed())
+        }
+    };
+    let opened = async {
+        let channel = channel_rx.await.map_err(|_| closed())?;
+        let (frames, opened) = attach(&channel);
+        opened.await.map_err(|_| closed())?;
+        Ok::<_, ProtocolError>((channel, frames))
+    };
+
+    let (channel, frames) = tokio::select! {
+        result = signals => return Err(result.err().unwrap_or_else(closed)),
+        result = tokio::time::timeout(OPEN_TIMEOUT, opened) => {
+            result.map_err(|_| ProtocolError::Timeout)??
+        }
+    };
+
+    Ok(RtcChannel {
+        peer,
+        channel,
+        frames,
+    })
+}
+
+async fn new_peer(
+    ice_servers: &[IceServer],
+    outgoing: mpsc::UnboundedSender<Outgoing>,
+) -> crate::Result<Arc<RTCPeerConnection>> {
+    let config = RTCConfiguration {
+        ice_servers: ice_servers
+            .iter()
+            .map(|server| RTCIceServer {
+                urls: server.urls.clone(),
+                username: server.username.clone().unwrap_or_default(),
+                credential: server.credential.clone().unwrap_or_default(),
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let peer = Arc::new(
+        APIBuilder::new()
+            .build()
+            .new_peer_connection(config)
+            .await
+            .map_err(rtc_error)?,
+    );
+
+    peer.on_ice_candidate(Box::new(move |candidate: Option<RTCIceCandidate>| {
+        if let Some(init) = candidate.and_then(|c| c.to_json().ok()) {
+            if let Ok(value) = serde_json::to_value(init) {
+                let _ = outgoing.send(Outgoing::Ice(value));
+            }
+        }
+        Box::pin(async {})
+    }));
+    Ok(peer)
+}
+
+/// 把通道消息转发到帧队列，返回队列和通道打开的通知
+fn attach(
+    channel: &Arc<RTCDataChannel>,
+) -> (mpsc::UnboundedReceiver<Frame>, oneshot::Receiver<()>) {
+    let (frames_tx, frames) = mpsc::unbounded_channel();
+    let (open_tx, opened) = oneshot::channel();
+
+    let open_tx = std::sync::Mutex::new(Some(open_tx));
+    channel.on_open(Box::new(move || {
+        if let Some(tx) = open_tx.lock().unwrap().take() {
+            let _ = tx.send(());
+        }
+        Box::pin(async {})
+    }));
+
+    // 通道关闭时丢弃发送端，使接收方读到 None
+    let frames_tx = Arc::new(std::sync::Mutex::new(Some(frames_tx)));
+    let close_tx = frames_tx.clone();
+    channel.on_message(Box::new(move |message: DataChannelMessage| {
+        let frame = if message.is_string {
+            Frame::Text(String::from_utf8_lossy(&message.data).into_owned())
+        } else {
+            Frame::Binary(message.data)
+        };
+        if let Some(tx) = frames_tx.lock().unwrap().as_ref() {
+            let _ = tx.send(frame);
+        }
+        Box::pin(async {})
+    }));
+    channel.on_close(Box::new(move || {
+        close_tx.lock().unwrap().take();
+        Box::pin(async {})
+    }));
+
+    (frames, opened)
+}
+
+async fn apply(peer: &RTCPeerConnection, signal: Signal) -> crate::Result<()> {
+    if let Some(sdp) = signal.sdp {
+        let description: RTCSessionDescription = serde_json::from_value(sdp)?;
+        peer.set_remote_description(description)
+            .await
+            .map_err(rtc_error)?;
+    }
+    if let Some(ice) = signal.ice {
+        let candidate: RTCIceCandidateInit = serde_json::from_value(ice)?;
+        peer.add_ice_candidate(candidate).await.map_err(rtc_error)?;
+    }
+    Ok(())
+}
+
+fn rtc_error(e: webrtc::Error) -> ProtocolError {
+    std::io::Error::other(e).into()
+}
+
+fn closed() -> ProtocolError {
+    std::io::Error::new(std::io::ErrorKind::ConnectionAborted, "WebRTC 连接已关闭").into()
+}
@@ -0,0 +1,248 @@
+//! 信令消息
+//!
+//! PairDrop 与 Snapdrop 的信令服务器使用相同的 JSON 结构，以 `type` 字段区分消息。
+//! 两者的差异（名称消息的嵌套方式、信令发送方的格式）在这里统一处理，未知消息被忽略
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::PAIRDROP_TRANSPORT;
+use crate::DeviceInfo;
+
+/// 信令服务器发来的消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ServerMessage {
+    /// 服务器下发的 WebRTC 配置，仅 PairDrop
+    WsConfig {
+        #[serde(rename = "wsConfig", default)]
+        ws_config: Option<WsConfig>,
+    },
+    /// 服务器分配给本端的名称
+    DisplayName(DisplayName),
+    /// 加入房间时已在房间内的对端
+    Peers {
+        peers: Vec<PeerInfo>,
+        #[serde(flatten)]
+        room: Room,
+    },
+    /// 新对端加入
+    PeerJoined {
+        peer: PeerInfo,
+        #[serde(flatten)]
+        room: Room,
+    },
+    /// 对端离开
+    PeerLeft {
+        #[serde(rename = "peerId")]
+        peer_id: String,
+    },
+    /// 对端转发的 SDP 或 ICE 候选
+    Signal(Signal),
+    /// 心跳，需要回复 [`ClientMessage::Pong`]
+    Ping,
+    #[serde(other)]
+    Unknown,
+}
+
+/// 本端发往信令服务器的消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ClientMessage {
+    Pong,
+    /// 经服务器转发给 to 的 SDP 或 ICE 候选
+    Signal {
+        to: String,
+        #[serde(flatten)]
+        room: Room,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sdp: Option<Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ice: Option<Value>,
+    },
+    Disconnect,
+}
+
+/// WebRTC 配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WsConfig {
+    #[serde(rename = "rtcConfig", default)]
+    pub rtc_config: Option<RtcConfig>,
+}
+
+/// 浏览器 `RTCConfiguration` 的子集
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RtcConfig {
+    #[serde(rename = "iceServers", default)]
+    pub ice_servers: Vec<IceServer>,
+}
+
+/// ICE 服务器，`urls` 可以是单个字符串或数组
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IceServer {
+    #[serde(deserialize_with = "one_or_many")]
+    pub urls: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(url) => vec![url],
+        OneOrMany::Many(urls) => urls,
+    })
+}
+
+/// 名称消息
+///
+/// PairDrop 把字段放在顶层，Snapdrop 放在 `message` 中
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DisplayName {
+    #[serde(rename = "displayName", default)]
+    display_name: Option<String>,
+    #[serde(rename = "deviceName", default)]
+    device_name: Option<String>,
+    #[serde(rename = "peerId", default)]
+    pub peer_id: Option<String>,
+    #[serde(default)]
+    message: Option<Box<DisplayName>>,
+}
+
+impl DisplayName {
+    /// 分配的显示名称
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name
+            .as_deref()
+            .or_else(|| self.message.as_ref()?.display_name())
+    }
+
+    /// 服务器根据 User-Agent 推断的设备名称
+    pub fn device_name(&self) -> Option<&str> {
+        self.device_name
+            .as_deref()
+            .or_else(|| self.message.as_ref()?.device_name())
+    }
+}
+
+/// 对端所在房间，Snapdrop 不发送这些字段
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Room {
+    #[serde(rename = "roomType", default, skip_serializing_if = "Option::is_none")]
+    pub room_type: Option<String>,
+    #[serde(rename = "roomId", default, skip_serializing_if = "Option::is_none")]
+    pub room_id: Option<String>,
+}
+
+/// 信令服务器上的对端
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub id: String,
+    #[serde(default)]
+    pub name: PeerName,
+    #[serde(rename = "rtcSupported", default)]
+    pub rtc_supported: bool,
+}
+
+/// 对端名称，除 `displayName` 外均由服务器根据 User-Agent 推断
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerName {
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub os: Option<String>,
+    #[serde(default)]
+    pub browser: Option<String>,
+    #[serde(rename = "type", default)]
+    pub device_type: Option<String>,
+    #[serde(rename = "deviceName", default)]
+    pub device_name: Option<String>,
+    #[serde(rename = "displayName", default)]
+    pub display_name: Option<String>,
+}
+
+impl PeerInfo {
+    /// 转换为设备信息
+    ///
+    /// 浏览器对端没有可直连的地址，IP 为房间 ID（IP 房间即公网地址），端口为 0，
+    /// 传输列表只有 [`PAIRDROP_TRANSPORT`]
+    pub fn to_device(&self, room: &Room) -> DeviceInfo {
+        let name = self
+            .name
+            .display_name
+            .clone()
+            .or_else(|| self.name.device_name.clone())
+            .unwrap_or_else(|| self.id.clone());
+        let device_type = match self.name.device_type.as_deref() {
+            Some("mobile") | Some("tablet") => "mobile",
+            _ => "web",
+        };
+
+        DeviceInfo {
+            id: self.id.clone(),
+            name,
+            device_type: device_type.to_string(),
+            ip: room.room_id.clone().unwrap_or_default(),
+            port: 0,
+            version: self.name.browser.clone().unwrap_or_default(),
+            protocol_version: PAIRDROP_TRANSPORT.to_string(),
+            announcement_id: String::new(),
+            uses_password: false,
+            transports: vec![PAIRDROP_TRANSPORT.to_string()],
+            addresses: Vec::new(),
+        }
+    }
+}
+
+/// 转发的信令
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Signal {
+    pub sender: SignalSender,
+    #[serde(flatten)]
+    pub room: Room,
+    /// `RTCSessionDescriptionInit`
+    #[serde(default)]
+    pub sdp: Option<Value>,
+    /// `RTCIceCandidateInit`
+    #[serde(default)]
+    pub ice: Option<Value>,
+}
+
+impl Signal {
+    /// 是否为对端发起连接的 offer
+    pub fn is_offer(&self) -> bool {
+        self.sdp
+            .as_ref()
+            .and_then(|sdp| sdp.get("type"))
+            .and_then(Value::as_str)
+            == Some("offer")
+    }
+}
+
+/// 信令发送方，PairDrop 为对象，Snapdrop 为对端 ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SignalSender {
+    Peer { id: String },
+    Id(String),
+}
+
+impl SignalSender {
+    /// 发送方 ID
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Peer { id } | Self::Id(id) => id,
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! 指纹确认配对
+//!
+//! 设备 ID 即证书指纹，但在不可信的局域网中，发现到的设备可能是中间人伪造的。
+//! 配对时两端各自由双方的指纹计算出同一个短认证串 (SAS)，用户比对两台设备上
+//! 显示的数字一致后才把对方标记为受信任 (加入收藏)。中间人无法让两端的指纹
+//! 同时与真实设备一致，两端显示的数字会不同
+
+use sha2::{Digest, Sha256};
+
+use crate::{DeviceInfo, LocalSendConfig};
+
+/// 计算认证串时附加的域分隔，避免与其他用途的指纹哈希混淆
+const SAS_CONTEXT: &[u8] = b"peersend-pairing-sas-v1";
+
+/// 认证串的十进制位数
+const SAS_DIGITS: u32 = 6;
+
+/// 由双方指纹计算短认证串，形如 `123 456`
+///
+/// 与参数顺序无关，两端调用时各自把本机指纹放在前面即可得到相同的结果。
+/// 指纹比较时忽略大小写和首尾空白
+pub fn short_auth_string(local_fingerprint: &str, remote_fingerprint: &str) -> String {
+    let mut fingerprints = [
+        local_fingerprint.trim().to_ascii_lowercase(),
+        remote_fingerprint.trim().to_ascii_lowercase(),
+    ];
+    fingerprints.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(SAS_CONTEXT);
+    for fingerprint in &fingerprints {
+        hasher.update((fingerprint.len() as u32).to_be_bytes());
+        hasher.update(fingerprint.as_bytes());
+    }
+    let digest = hasher.finalize();
+
+    let value = u64::from_be_bytes(digest[..8].try_into().unwrap()) % 10u64.pow(SAS_DIGITS);
+    let digits = format!("{:0width$}", value, width = SAS_DIGITS as usize);
+    let (head, tail) = digits.split_at(digits.len() / 2);
+    format!("{} {}", head, tail)
+}
+
+/// 一次配对
+#[derive(Debug, Clone)]
+pub struct Pairing {
+    /// 本机指纹
+    pub local: String,
+    /// 待确认的设备
+    pub remote: DeviceInfo,
+    /// 两端应显示的认证串
+    pub code: String,
+}
+
+impl Pairing {
+    /// 以本机配置中的设备 ID 作为本机指纹开始配对
+    pub fn new(config: &LocalSendConfig, remote: DeviceInfo) -> Self {
+        let local = config.device_id.clone();
+        let code = short_auth_string(&local, &remote.id);
+        Self {
+            local,
+            remote,
+            code,
+        }
+    }
+
+    /// 用户确认两端认证串一致后调用，把对方加入收藏
+    ///
+    /// 对方已在收藏中时返回 false
+    pub fn confirm(&self, config: &mut LocalSendConfig) -> bool {
+        if config.is_trusted(&self.remote.id) {
+            return false;
+        }
+        config.favorites.push(self.remote.id.clone());
+        true
+    }
+}
@@ -0,0 +1,194 @@
+//! 传输进度计数
+//!
+//! 数据路径每个块都会累加进度，界面则定期读取。计数使用原子变量，
+//! 速度在读取快照时计算，写入端不需要加锁。
+//! 每秒的吞吐另外记录在环形缓冲中，供界面绘制速度曲线
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+
+use crate::TransferProgress;
+
+/// 两次快照推送之间的最小间隔
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 吞吐采样保留的秒数
+pub const TIMESERIES_SECS: usize = 300;
+
+/// 采样槽中字节数占用的低位，高位记录所属的秒
+const SAMPLE_BYTES_BITS: u32 = 44;
+const SAMPLE_BYTES_MASK: u64 = (1 << SAMPLE_BYTES_BITS) - 1;
+
+/// 一秒内的吞吐
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct ThroughputSample {
+    /// 距开始传输的秒数
+    pub offset_secs: u64,
+    /// 这一秒内传输的字节数
+    pub bytes: u64,
+}
+
+/// 会话进度计数器
+///
+/// 通过 [`subscribe`](Self::subscribe) 获得的接收端会收到节流后的进度快照，
+/// 传输完成时总会推送最终快照
+#[derive(Debug)]
+pub struct ProgressTracker {
+    bytes: AtomicU64,
+    total: AtomicU64,
+    /// 第一次累加的时间，浏览器端不会累加，因此不会调用 `Instant::now`
+    started: OnceLock<Instant>,
+    /// 上次推送时距开始的毫秒数
+    last_publish_ms: AtomicU64,
+    snapshots: watch::Sender<TransferProgress>,
+    /// 按秒取模的采样槽，每个槽把秒序号与字节数打包在一个原子变量中
+    samples: Box<[AtomicU64]>,
+    /// 最近一次累加所在的秒
+    last_second: AtomicU64,
+}
+
+impl ProgressTracker {
+    /// 创建总字节数为 total 的计数器
+    pub fn new(total: u64) -> Self {
+        let (snapshots, _) = watch::channel(TransferProgress {
+            bytes_transferred: 0,
+            total_bytes: total,
+            speed_bytes_per_sec: 0.0,
+        });
+        Self {
+            bytes: AtomicU64::new(0),
+            total: AtomicU64::new(total),
+            started: OnceLock::new(),
+            last_publish_ms: AtomicU64::new(0),
+            snapshots,
+            samples: (0..TIMESERIES_SECS).map(|_| AtomicU64::new(0)).collect(),
+            last_second: AtomicU64::new(0),
+        }
+    }
+
+    /// 累加已传输字节数，返回累加后的值
+    pub fn add(&self, bytes: u64) -> u64 {
+        let started = *self.started.get_or_init(Instant::now);
+        let transferred = self.bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        self.record_sample(elapsed_ms / 1000, bytes);
+        let last = self.last_publish_ms.load(Ordering::Relaxed);
+        let finished = transferred >= self.total.load(Ordering::Relaxed);
+        if (finished || elapsed_ms >= last + PUBLISH_INTERVAL.as_millis() as u64)
+            && self
+                .last_publish_ms
+                .compare_exchange(last, elapsed_ms, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.publish();
+        }
+
+        transferred
+    }
+
+    /// 修改总字节数
+    pub fn set_total(&self, total: u64) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// 已传输字节数
+    pub fn bytes_transferred(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// 总字节数
+    pub fn total_bytes(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// 开始传输以来的时长，尚未开始时为 0
+    pub fn elapsed(&self) -> Duration {
+        self.started.get().map_or(Duration::ZERO, Instant::elapsed)
+    }
+
+    /// 当前进度快照，速度为开始传输以来的平均值
+    pub fn snapshot(&self) -> TransferProgress {
+        let bytes_transferred = self.bytes_transferred();
+        let speed_bytes_per_sec = match self.started.get() {
+            Some(started) => {
+                let secs = started.elapsed().as_secs_f64();
+                if secs > 0.0 {
+                    bytes_transferred as f64 / secs
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        };
+
+        TransferProgress {
+            bytes_transferred,
+            total_bytes: self.total_bytes(),
+            speed_bytes_per_sec,
+        }
+    }
+
+    /// 最近 [`TIMESERIES_SECS`] 秒内每秒的吞吐，按时间先后排列
+    ///
+    /// 传输未完成时截至当前这一秒，没有数据的秒记为 0；完成后截至最后一次累加。
+    /// 尚未开始传输时为空
+    pub fn timeseries(&self) -> Vec<ThroughputSample> {
+        let Some(started) = self.started.get() else {
+            return Vec::new();
+        };
+        let end = if self.bytes_transferred() >= self.total_bytes() {
+            self.last_second.load(Ordering::Relaxed)
+        } else {
+            started.elapsed().as_secs()
+        };
+        let start = end.saturating_sub(TIMESERIES_SECS as u64 - 1);
+        (start..=end)
+            .map(|second| {
+                let slot = self.samples[second as usize % TIMESERIES_SECS].load(Ordering::Relaxed);
+                let bytes = if slot >> SAMPLE_BYTES_BITS == sample_tag(second) {
+                    slot & SAMPLE_BYTES_MASK
+                } else {
+                    0
+                };
+                ThroughputSample {
+                    offset_secs: second,
+                    bytes,
+                }
+            })
+            .collect()
+    }
+
+    /// 把 bytes 计入第 second 秒的采样槽，槽中是更早的秒时先清零
+    fn record_sample(&self, second: u64, bytes: u64) {
+        let tag = sample_tag(second);
+        let slot = &self.samples[second as usize % TIMESERIES_SECS];
+        let _ = slot.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+            let count = if current >> SAMPLE_BYTES_BITS == tag {
+                current & SAMPLE_BYTES_MASK
+            } else {
+                0
+            };
+            Some(tag << SAMPLE_BYTES_BITS | count.saturating_add(bytes).min(SAMPLE_BYTES_MASK))
+        });
+        self.last_second.fetch_max(second, Ordering::Relaxed);
+    }
+
+    /// 订阅进度快照
+    pub fn subscribe(&self) -> watch::Receiver<TransferProgress> {
+        self.snapshots.subscribe()
+    }
+
+    /// 立即推送当前快照
+    pub fn publish(&self) {
+        self.snapshots.send_replace(self.snapshot());
+    }
+}
+
+/// 采样槽中的秒序号，加 1 使空槽 (0) 不会被当作第 0 秒
+fn sample_tag(second: u64) -> u64 {
+    (second + 1) & (u64::MAX >> SAMPLE_BYTES_BITS)
+}
@@ -0,0 +1,255 @@
+//! 下载目录清理
+//!
+//! 按 [`RetentionConfig`] 删除或归档超过保留天数的已接收文件，下载目录超过大小上限时
+//! 再从最旧的文件开始清理，适合长期无人值守的接收端。
+//!
+//! 接收守护进程通过 [`Retention::run`] 定期执行。试运行只输出将清理的文件；
+//! 实际清理后在审计日志中追加 `expired` 记录，传输历史据此可知文件已不在下载目录
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::audit::{AuditAction, AuditEntry, AuditFile, AuditLog};
+use crate::{LocalSendConfig, RetentionAction, RetentionConfig};
+
+/// 默认的归档目录名，位于下载目录中
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// 守护进程执行清理的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 需清理的文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expired {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// 一次清理的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cleanup {
+    /// 已清理 (试运行时为将清理) 的文件
+    pub files: Vec<Expired>,
+    /// 是否为试运行
+    pub dry_run: bool,
+}
+
+/// 下载目录清理器
+#[derive(Debug, Clone)]
+pub struct Retention {
+    config: RetentionConfig,
+    download_dir: PathBuf,
+    audit: Option<AuditLog>,
+}
+
+impl Retention {
+    /// 按策略清理 download_dir
+    pub fn new(config: RetentionConfig, download_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            config,
+            download_dir: download_dir.into(),
+            audit: None,
+        }
+    }
+
+    /// 按配置创建，未设置清理策略时返回 None
+    pub fn from_config(config: &LocalSendConfig) -> Option<Self> {
+        let retention = config.retention.clone()?;
+        Some(Self::new(retention, &config.download_dir))
+    }
+
+    /// 清理后在审计日志中记录
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// 覆盖配置中的试运行设置
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.config.dry_run = dry_run;
+        self
+    }
+
+    /// 归档目录
+    pub fn archive_dir(&self) -> PathBuf {
+        match &self.config.archive_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => self.download_dir.join(ARCHIVE_DIR),
+        }
+    }
+
+    /// 以 now 为当前时间，列出需清理的文件，最旧的在前
+    ///
+    /// 隐藏文件和归档目录中的文件不计入也不清理
+    pub fn plan(&self, now: SystemTime) -> io::Result<Vec<Expired>> {
+        let mut files = Vec::new();
+        collect_files(&self.download_dir, &self.archive_dir(), &mut files)?;
+        files.sort_by(|a, b| a.modified.cmp(&b.modified).then_with(|| a.path.cmp(&b.path)));
+
+        let cutoff = self
+            .config
+            .max_age_days
+            .and_then(|days| now.checked_sub(DAY * days));
+        let split = cutoff.map_or(0, |cutoff| files.partition_point(|f| f.modified < cutoff));
+        let mut remaining: u64 = files[split..].iter().map(|f| f.size).sum();
+        let mut end = split;
+        if let Some(max) = self.config.max_size {
+            while remaining > max && end < files.len() {
+                remaining -= files[end].size;
+                end += 1;
+            }
+        }
+        files.truncate(end);
+        Ok(files)
+    }
+
+    /// 执行一次清理，试运行时只返回将清理的文件
+    ///
+    /// 单个文件清理失败时记录警告并继续，结果中只包含成功清理的文件
+    pub fn apply(&self, now: SystemTime) -> crate::Result<Cleanup> {
+        let planned = self.plan(now)?;
+        if self.config.dry_run {
+            for file in &planned {
+                info!(path = %file.path.display(), size = file.size, "试运行: 将清理文件");
+            }
+            return Ok(Cleanup {
+                files: planned,
+                dry_run: true,
+            });
+        }
+
+        let archive_dir = self.archive_dir();
+        let mut cleaned = Vec::with_capacity(planned.len());
+        for file in planned {
+            let result = match self.config.action {
+                RetentionAction::Delete => std::fs::remove_file(&file.path),
+                RetentionAction::Archive => {
+                    let relative = file.path.strip_prefix(&self.download_dir).unwrap_or(&file.path);
+                    archive(&file.path, &archive_dir.join(relative))
+                }
+            };
+            match result {
+                Ok(()) => cleaned.push(file),
+                Err(e) => warn!(path = %file.path.display(), error = %e, "清理文件失败"),
+            }
+        }
+        if !cleaned.is_empty() {
+            info!(count = cleaned.len(), action = ?self.config.action, "已清理下载目录");
+            self.record(&cleaned);
+        }
+        Ok(Cleanup {
+            files: cleaned,
+            dry_run: false,
+        })
+    }
+
+    /// 在审计日志中注明清理的文件
+    fn record(&self, files: &[Expired]) {
+        let Some(audit) = &self.audit else {
+            return;
+        };
+        let reason = match self.config.action {
+            RetentionAction::Delete => "按清理策略删除".to_string(),
+            RetentionAction::Archive => format!("按清理策略归档至 {}", self.archive_dir().display()),
+        };
+        audit.record_or_warn(AuditEntry {
+            files: files
+                .iter()
+                .map(|f| AuditFile {
+                    name: f
+                        .path
+                        .strip_prefix(&self.download_dir)
+                        .unwrap_or(&f.path)
+                        .to_string_lossy()
+                        .into_owned(),
+                    size: f.size,
+                    sha256: None,
+                })
+                .collect(),
+            reason: Some(reason),
+            ..AuditEntry::new(AuditAction::Expired)
+        });
+    }
+
+    /// 立即清理一次，之后每小时清理，直到 cancel 取消
+    pub async fn run(self, cancel: CancellationToken) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+            let retention = self.clone();
+            let result =
+                tokio::task::spawn_blocking(move || retention.apply(SystemTime::now())).await;
+            match result {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => warn!(error = %e, "清理下载目录失败"),
+                Err(e) => warn!(error = %e, "清理任务异常退出"),
+            }
+        }
+    }
+}
+
+/// 递归收集 dir 下的普通文件，跳过隐藏文件和 archive_dir
+fn collect_files(dir: &Path, archive_dir: &Path, files: &mut Vec<Expired>) -> io::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') || path == archive_dir {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect_files(&path, archive_dir, files)?;
+        } else if metadata.is_file() {
+            files.push(Expired {
+                path,
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// 把文件移到 target，目标已存在时追加序号，跨文件系统时复制后删除
+fn archive(path: &Path, target: &Path) -> io::Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let target = unique_path(target);
+    if std::fs::rename(path, &target).is_err() {
+        std::fs::copy(path, &target)?;
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// 不与已有文件重名的路径
+fn unique_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
@@ -0,0 +1,156 @@
+//! 上传途径的选择
+//!
+//! 向设备上传文件可经双方都支持的传输 (如 `quic`、`tcp`、EasyTier 隧道 `easytier`)，
+//! 也可经 LocalSend 的 HTTPS 或 HTTP 上传接口。配置的 `route_preferences` 按设备指定途径的
+//! 优先顺序，未列出的可用途径排在其后。未指定的设备按 [`RouteStats`] 中各途径的实测速度
+//! 从快到慢排列，尚未测过的途径排在最前以便测量，都未测过时传输优先于 HTTP。
+//! 发送时依次尝试，途径无法连接时回退到下一项。
+//!
+//! 实测速度取自实际的上传，不少于 [`MIN_SAMPLE_BYTES`] 的上传才计入，与此前的结果取平均。
+//! [`RouteStats::open_default`] 把结果保存在配置目录的 `routes.json`，供之后的发送使用
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::{LocalSendConfig, ProtocolError};
+
+/// 实测结果文件名，与共享配置文件位于同一目录
+const ROUTES_FILE_NAME: &str = "routes.json";
+
+/// 计入实测速度的最小上传量 (1MB)，更小的上传主要受连接延迟影响
+pub const MIN_SAMPLE_BYTES: u64 = 1024 * 1024;
+
+/// 设备 ID 到各途径的实测速度 (字节/秒)
+type Speeds = BTreeMap<String, BTreeMap<String, u64>>;
+
+/// 按优先顺序排列途径，返回 available 中的下标
+///
+/// available 为按默认顺序排列的可用途径，preferred 为配置的该设备的顺序，
+/// speeds 为该设备各途径的实测速度，配置了顺序时不使用
+pub fn order(
+    available: &[&str],
+    preferred: Option<&[String]>,
+    speeds: &BTreeMap<String, u64>,
+) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..available.len()).collect();
+    match preferred {
+        Some(preferred) => order.sort_by_key(|&i| {
+            preferred
+                .iter()
+                .position(|name| name == available[i])
+                .unwrap_or(preferred.len())
+        }),
+        // 未测过的途径视为最快
+        None => order.sort_by_key(|&i| {
+            std::cmp::Reverse(speeds.get(available[i]).copied().unwrap_or(u64::MAX))
+        }),
+    }
+    order
+}
+
+/// 各设备上传途径的实测速度
+///
+/// 克隆后共享同一份结果。使用文件时每次读写都重新读取文件，进程间共享同一份结果
+#[derive(Debug, Clone, Default)]
+pub struct RouteStats {
+    path: Option<PathBuf>,
+    speeds: Arc<Mutex<Speeds>>,
+}
+
+impl RouteStats {
+    /// 只在内存中记录的实测结果
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 使用指定的结果文件，文件不存在时在首次记录时创建
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Some(path.into()),
+            speeds: Arc::default(),
+        }
+    }
+
+    /// 与共享配置文件位于同一目录的结果文件
+    pub fn default_path() -> Option<PathBuf> {
+        Some(LocalSendConfig::default_path()?.with_file_name(ROUTES_FILE_NAME))
+    }
+
+    /// 使用默认位置的结果文件
+    pub fn open_default() -> crate::Result<Self> {
+        let path = Self::default_path()
+            .ok_or_else(|| ProtocolError::InvalidConfig("无法确定配置目录".to_string()))?;
+        Ok(Self::open(path))
+    }
+
+    /// 设备各途径的实测速度 (字节/秒)，读取文件失败时使用上次读到的结果
+    pub fn speeds(&self, device_id: &str) -> BTreeMap<String, u64> {
+        let mut speeds = self.speeds.lock().unwrap();
+        if let Err(e) = self.reload(&mut speeds) {
+            warn!(error = %e, "读取上传途径的实测速度失败");
+        }
+        speeds.get(device_id).cloned().unwrap_or_default()
+    }
+
+    /// 记录一次经 route 的上传，不足 [`MIN_SAMPLE_BYTES`] 时忽略
+    pub fn record(
+        &self,
+        device_id: &str,
+        route: &str,
+        bytes: u64,
+        elapsed: Duration,
+    ) -> crate::Result<()> {
+        if bytes < MIN_SAMPLE_BYTES || elapsed.is_zero() {
+            return Ok(());
+        }
+        let sample = (bytes as f64 / elapsed.as_secs_f64()) as u64;
+
+        let mut speeds = self.speeds.lock().unwrap();
+        self.reload(&mut speeds)?;
+        let speed = speeds
+            .entry(device_id.to_string())
+            .or_default()
+            .entry(route.to_string())
+            .or_insert(sample);
+        *speed = *speed / 2 + sample / 2;
+        self.save(&speeds)
+    }
+
+    /// 记录上传，失败只记录日志，不影响传输
+    pub fn record_or_warn(&self, device_id: &str, route: &str, bytes: u64, elapsed: Duration) {
+        if let Err(e) = self.record(device_id, route, bytes, elapsed) {
+            warn!(error = %e, "保存上传途径的实测速度失败");
+        }
+    }
+
+    /// 从文件重新读取结果，不使用文件时保持不变
+    fn reload(&self, speeds: &mut Speeds) -> crate::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        *speeds = match std::fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Speeds::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(())
+    }
+
+    /// 先写临时文件再替换，避免其他进程读到写了一半的文件
+    fn save(&self, speeds: &Speeds) -> crate::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::write(&tmp, serde_json::to_vec_pretty(speeds)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
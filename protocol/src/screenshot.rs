@@ -0,0 +1,94 @@
+//! 截屏
+//!
+//! 截取整个屏幕或其中的区域，保存为临时 PNG 后即可像普通文件一样发送，
+//! 一步把屏幕内容发给手机等设备。截屏需要启用 `screenshot` 特性 (基于 xcap)，
+//! Linux 上需要 X11 或支持截屏门户的 Wayland 桌面
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::ProtocolError;
+
+/// 屏幕区域，坐标为全局桌面坐标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Region {
+    type Err = ProtocolError;
+
+    /// 解析 `x,y,宽,高`，例如 `0,0,1280,720`
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let invalid = || {
+            ProtocolError::InvalidData(format!("无效的截屏区域: {}，格式为 x,y,宽,高", s))
+        };
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        let [x, y, width, height] = parts[..] else {
+            return Err(invalid());
+        };
+        let region = Region {
+            x: x.parse().map_err(|_| invalid())?,
+            y: y.parse().map_err(|_| invalid())?,
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+        };
+        if region.width == 0 || region.height == 0 {
+            return Err(invalid());
+        }
+        Ok(region)
+    }
+}
+
+/// 截屏文件在 dir 中的路径，文件名带本地时间，例如 `screenshot-20240501-093012.png`
+pub fn screenshot_path(dir: &Path) -> PathBuf {
+    let now = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    dir.join(format!("screenshot-{}.png", now))
+}
+
+/// 截屏并保存到临时目录，返回 PNG 文件路径
+///
+/// 未指定区域时截取主显示器；指定区域时截取区域左上角所在的显示器，
+/// 超出该显示器的部分被裁掉
+#[cfg(feature = "screenshot")]
+pub fn capture(region: Option<Region>) -> crate::Result<PathBuf> {
+    use xcap::Monitor;
+
+    let image = match region {
+        Some(region) => {
+            let monitor = Monitor::from_point(region.x, region.y).map_err(capture_error)?;
+            let x = region.x - monitor.x().map_err(capture_error)?;
+            let y = region.y - monitor.y().map_err(capture_error)?;
+            let width = region.width.min(monitor.width().map_err(capture_error)? - x as u32);
+            let height = region.height.min(monitor.height().map_err(capture_error)? - y as u32);
+            monitor
+                .capture_region(x as u32, y as u32, width, height)
+                .map_err(capture_error)?
+        }
+        None => {
+            let monitors = Monitor::all().map_err(capture_error)?;
+            let monitor = monitors
+                .iter()
+                .find(|m| m.is_primary().unwrap_or(false))
+                .or(monitors.first())
+                .ok_or_else(|| ProtocolError::InvalidData("没有可截取的显示器".to_string()))?;
+            monitor.capture_image().map_err(capture_error)?
+        }
+    };
+
+    let dir = std::env::temp_dir().join("peersend");
+    std::fs::create_dir_all(&dir)?;
+    let path = screenshot_path(&dir);
+    image
+        .save_with_format(&path, xcap::image::ImageFormat::Png)
+        .map_err(|e| ProtocolError::Io(std::io::Error::other(e)))?;
+    Ok(path)
+}
+
+#[cfg(feature = "screenshot")]
+fn capture_error(e: xcap::XCapError) -> ProtocolError {
+    std::io::Error::other(format!("截屏失败: {}", e)).into()
+}
@@ -0,0 +1,81 @@
+//! 接收确认
+//!
+//! prepare-upload 请求在用户确认前挂起，前端通过 [`AcceptGate::respond`] 给出答复。
+//! 快速保存生效时服务器不经过此处，直接接受。
+//!
+//! [`AcceptGate::open_for`] 临时开放接收：期间所有通过允许列表、PIN 和大小限制检查的请求
+//! 都自动接受，到期后恢复按快速保存设置确认，适合当面收集多人的文件
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+
+/// 等待用户确认的请求
+#[derive(Debug, Clone, Default)]
+pub struct AcceptGate {
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<bool>>>>,
+    /// 临时开放接收的截止时间
+    open_until: Arc<Mutex<Option<Instant>>>,
+}
+
+impl AcceptGate {
+    /// 创建空的确认队列
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 等待对会话的答复，超时视为拒绝
+    pub async fn wait(&self, session_id: &str, timeout: Duration) -> bool {
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), tx);
+
+        let accepted = matches!(tokio::time::timeout(timeout, rx).await, Ok(Ok(true)));
+        self.pending.lock().unwrap().remove(session_id);
+        accepted
+    }
+
+    /// 答复挂起的请求，会话不在等待中时返回 false
+    pub fn respond(&self, session_id: &str, accept: bool) -> bool {
+        match self.pending.lock().unwrap().remove(session_id) {
+            Some(tx) => tx.send(accept).is_ok(),
+            None => false,
+        }
+    }
+
+    /// 正在等待答复的会话 ID
+    pub fn pending(&self) -> Vec<String> {
+        self.pending.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// 在 duration 内自动接受所有请求，已开放时重新计时
+    pub fn open_for(&self, duration: Duration) {
+        *self.open_until.lock().unwrap() = Some(Instant::now() + duration);
+    }
+
+    /// 提前结束临时开放接收
+    pub fn close(&self) {
+        *self.open_until.lock().unwrap() = None;
+    }
+
+    /// 临时开放接收的剩余时间，未开放或已到期时返回 None
+    pub fn open_remaining(&self) -> Option<Duration> {
+        let mut open_until = self.open_until.lock().unwrap();
+        match open_until.and_then(|until| until.checked_duration_since(Instant::now())) {
+            Some(remaining) if !remaining.is_zero() => Some(remaining),
+            _ => {
+                *open_until = None;
+                None
+            }
+        }
+    }
+
+    /// 是否正在临时开放接收
+    pub fn is_open(&self) -> bool {
+        self.open_remaining().is_some()
+    }
+}
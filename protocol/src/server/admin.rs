@@ -0,0 +1,33 @@
+//! 管理 API 附带的网页界面
+//!
+//! 静态文件编译进程序，由管理 API 在 `/` 下提供。页面本身无需认证，
+//! 登录时输入的访问令牌保存在浏览器中，之后随每个 `/api/` 请求发送
+
+use hyper::header;
+use hyper::{Body, Response};
+
+/// 路径、Content-Type 和内容
+const ASSETS: &[(&str, &str, &str)] = &[
+    ("/", "text/html; charset=utf-8", include_str!("admin/index.html")),
+    ("/app.js", "text/javascript; charset=utf-8", include_str!("admin/app.js")),
+    ("/style.css", "text/css; charset=utf-8", include_str!("admin/style.css")),
+];
+
+/// 只允许加载同源的脚本和样式，禁止被嵌入其他页面
+const CONTENT_SECURITY_POLICY: &str =
+    "default-src 'self'; object-src 'none'; base-uri 'none'; frame-ancestors 'none'";
+
+/// path 对应的静态文件，不存在时返回 None
+pub(crate) fn asset(path: &str) -> Option<Response<Body>> {
+    let path = if path == "/index.html" { "/" } else { path };
+    let (_, content_type, content) = ASSETS.iter().find(|(p, _, _)| *p == path)?;
+    Some(
+        Response::builder()
+            .header(header::CONTENT_TYPE, *content_type)
+            .header(header::CONTENT_SECURITY_POLICY, CONTENT_SECURITY_POLICY)
+            .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+            .header(header::CACHE_CONTROL, "no-cache")
+            .body(Body::from(*content))
+            .unwrap(),
+    )
+}
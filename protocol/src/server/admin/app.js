@@ -0,0 +1,287 @@
+"use strict";
+
+// 管理页面：令牌保存在 localStorage，订阅事件流，收到事件时刷新各列表；
+// 事件流断开期间每隔几秒刷新一次
+
+const TOKEN_KEY = "peersend.apiToken";
+const REFRESH_MS = 2000;
+const STREAMING_REFRESH_MS = 30000;
+const EVENT_DELAY_MS = 200;
+const STREAM_RETRY_MS = 3000;
+const HISTORY_LIMIT = 50;
+
+const STATES = {
+  waiting: "等待中",
+  transferring: "传输中",
+  finished: "已完成",
+  cancelled: "已取消",
+  error: "失败",
+};
+
+const ACTIONS = {
+  requested: "请求",
+  accepted: "接受",
+  rejected: "拒绝",
+  completed: "完成",
+  failed: "失败",
+  expired: "清理",
+};
+
+const $ = (id) => document.getElementById(id);
+let timer = null;
+let stream = null;
+let streaming = false;
+
+class Unauthorized extends Error {}
+
+function authorization() {
+  return { Authorization: "Bearer " + localStorage.getItem(TOKEN_KEY) };
+}
+
+async function api(method, path) {
+  const response = await fetch(path, {
+    method,
+    headers: authorization(),
+    cache: "no-store",
+  });
+  if (response.status === 401) {
+    throw new Unauthorized();
+  }
+  if (!response.ok && response.status !== 404) {
+    const body = await response.json().catch(() => ({}));
+    throw new Error(body.error || response.statusText);
+  }
+  return response.status === 200 ? response.json() : null;
+}
+
+function cell(row, text, className) {
+  const td = row.insertCell();
+  td.textContent = text;
+  if (className) {
+    td.className = className;
+  }
+  return td;
+}
+
+function formatSize(bytes) {
+  const units = ["B", "KB", "MB", "GB", "TB"];
+  let value = bytes;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit += 1;
+  }
+  return unit === 0 ? bytes + " B" : value.toFixed(1) + " " + units[unit];
+}
+
+function fileSummary(files) {
+  if (files.length === 0) {
+    return "";
+  }
+  const first = files[0].name;
+  return files.length === 1 ? first : first + " 等 " + files.length + " 个文件";
+}
+
+function fillTable(id, items, render) {
+  const table = $(id);
+  const body = table.tBodies[0];
+  body.replaceChildren();
+  for (const item of items) {
+    render(body.insertRow(), item);
+  }
+  table.hidden = items.length === 0;
+  $(id + "-empty").hidden = items.length !== 0;
+}
+
+async function respond(sessionId, action) {
+  try {
+    await api("POST", "/api/sessions/" + encodeURIComponent(sessionId) + "/" + action);
+  } catch (e) {
+    if (e instanceof Unauthorized) {
+      return showLogin();
+    }
+    alert(e.message);
+  }
+  refresh();
+}
+
+function renderPending(sessions, devices) {
+  const list = $("pending");
+  list.replaceChildren();
+  const pending = sessions.filter((s) => s.pending);
+  for (const session of pending) {
+    const item = document.createElement("li");
+    const info = document.createElement("div");
+    const title = document.createElement("strong");
+    title.textContent = fileSummary(session.files);
+    const detail = document.createElement("div");
+    detail.className = "muted";
+    const size = session.files.reduce((sum, f) => sum + f.size, 0);
+    detail.textContent = peerName(session.senderId, devices) + " · " + formatSize(size);
+    info.append(title, detail);
+
+    const actions = document.createElement("div");
+    actions.className = "actions";
+    const accept = document.createElement("button");
+    accept.className = "primary";
+    accept.textContent = "接受";
+    accept.onclick = () => respond(session.id, "accept");
+    const reject = document.createElement("button");
+    reject.textContent = "拒绝";
+    reject.onclick = () => respond(session.id, "reject");
+    actions.append(accept, reject);
+
+    item.append(info, actions);
+    list.append(item);
+  }
+  $("pending-empty").hidden = pending.length !== 0;
+}
+
+function peerName(id, devices) {
+  const device = devices.find((d) => d.id === id);
+  return device ? device.name : id.slice(0, 12);
+}
+
+function renderSessions(sessions, devices) {
+  const shown = sessions.filter((s) => !s.pending).reverse();
+  fillTable("sessions", shown, (row, session) => {
+    cell(row, fileSummary(session.files));
+    const peer = session.incoming ? session.senderId : session.receiverId;
+    cell(row, (session.incoming ? "来自 " : "发往 ") + peerName(peer, devices));
+    cell(row, STATES[session.state] || session.state).title = session.error || "";
+    const progress = document.createElement("progress");
+    progress.max = session.totalBytes || 1;
+    progress.value = session.bytesTransferred;
+    row.insertCell().append(progress);
+  });
+}
+
+function renderDevices(devices) {
+  fillTable("devices", devices, (row, device) => {
+    cell(row, device.name);
+    cell(row, device.type);
+    cell(row, device.ip + ":" + device.port);
+    cell(row, device.id.slice(0, 16), "muted").title = device.id;
+  });
+}
+
+function renderHistory(records) {
+  fillTable("history", (records || []).reverse(), (row, record) => {
+    cell(row, new Date(record.timestamp * 1000).toLocaleString());
+    cell(row, ACTIONS[record.action] || record.action);
+    cell(row, record.peerName || (record.peer || "").slice(0, 12));
+    cell(row, (record.files || []).map((f) => f.name).join(", "));
+    cell(row, record.reason || "", "muted");
+  });
+}
+
+async function refresh() {
+  clearTimeout(timer);
+  try {
+    const [devices, sessions, history] = await Promise.all([
+      api("GET", "/api/devices"),
+      api("GET", "/api/sessions"),
+      api("GET", "/api/history?limit=" + HISTORY_LIMIT),
+    ]);
+    renderPending(sessions, devices);
+    renderSessions(sessions, devices);
+    renderDevices(devices);
+    renderHistory(history);
+    showDashboard();
+  } catch (e) {
+    if (e instanceof Unauthorized) {
+      return showLogin();
+    }
+    console.error(e);
+  }
+  timer = setTimeout(refresh, streaming ? STREAMING_REFRESH_MS : REFRESH_MS);
+}
+
+// 短时间内的多个事件只刷新一次
+function scheduleRefresh() {
+  clearTimeout(timer);
+  timer = setTimeout(refresh, EVENT_DELAY_MS);
+}
+
+// EventSource 无法携带 Authorization 头，改用 fetch 读取事件流
+async function followEvents() {
+  const controller = new AbortController();
+  stream = controller;
+  try {
+    const response = await fetch("/api/events", {
+      headers: authorization(),
+      cache: "no-store",
+      signal: controller.signal,
+    });
+    if (!response.ok) {
+      throw new Error(response.statusText);
+    }
+    streaming = true;
+    const reader = response.body.pipeThrough(new TextDecoderStream()).getReader();
+    let buffer = "";
+    for (;;) {
+      const { value, done } = await reader.read();
+      if (done) {
+        break;
+      }
+      buffer += value;
+      const messages = buffer.split("\n\n");
+      buffer = messages.pop();
+      if (messages.some((m) => m.split("\n").some((line) => line.startsWith("data:")))) {
+        scheduleRefresh();
+      }
+    }
+  } catch (e) {
+    if (e.name !== "AbortError") {
+      console.error(e);
+    }
+  }
+  streaming = false;
+  // 退出登录后 stream 被清空，不再重连
+  setTimeout(() => stream === controller && followEvents(), STREAM_RETRY_MS);
+}
+
+function stopEvents() {
+  if (stream) {
+    stream.abort();
+    stream = null;
+  }
+}
+
+// 保存的令牌被拒绝时提示错误
+function showLogin() {
+  clearTimeout(timer);
+  stopEvents();
+  $("dashboard").hidden = true;
+  $("logout").hidden = true;
+  $("login").hidden = false;
+  $("login-error").hidden = !localStorage.getItem(TOKEN_KEY);
+  localStorage.removeItem(TOKEN_KEY);
+  $("token").focus();
+}
+
+function showDashboard() {
+  $("login").hidden = true;
+  $("dashboard").hidden = false;
+  $("logout").hidden = false;
+}
+
+$("login").addEventListener("submit", (event) => {
+  event.preventDefault();
+  localStorage.setItem(TOKEN_KEY, $("token").value.trim());
+  $("token").value = "";
+  refresh();
+  followEvents();
+});
+
+$("logout").addEventListener("click", () => {
+  localStorage.removeItem(TOKEN_KEY);
+  showLogin();
+});
+
+if (localStorage.getItem(TOKEN_KEY)) {
+  refresh();
+  followEvents();
+} else {
+  showLogin();
+}
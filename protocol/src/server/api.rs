@@ -0,0 +1,449 @@
+//! 管理 API
+//!
+//! 在单独端口上提供 JSON 接口，无界面部署时可由脚本或远程面板控制接收守护进程：
+//!
+//! | 方法 | 路径 | 说明 |
+//! | --- | --- | --- |
+//! | GET | `/api/devices` | 已发现的设备 |
+//! | GET | `/api/sessions` | 所有会话 |
+//! | GET | `/api/sessions/<ID>` | 单个会话 |
+//! | POST | `/api/sessions/<ID>/accept` | 接受等待确认的请求 |
+//! | POST | `/api/sessions/<ID>/reject` | 拒绝等待确认的请求 |
+//! | POST | `/api/send` | 发送文件，请求体为 `{"device": "...", "paths": [...]}` |
+//! | GET | `/api/history?limit=N` | 审计日志中最近的 N 条记录，默认 100 |
+//! | GET | `/api/events` | 实时事件流 (Server-Sent Events) |
+//! | GET | `/api/events/recent` | 最近的事件 |
+//! | GET | `/api/gate` | 临时开放接收的剩余秒数 `{"remaining": N}`，未开放时为 null |
+//! | POST | `/api/gate` | 临时开放接收，请求体为 `{"secs": N}` |
+//! | DELETE | `/api/gate` | 提前结束临时开放接收 |
+//!
+//! 所有请求都需携带 `Authorization: Bearer <api_token>`。
+//! `/api/send` 的 device 可以是设备 ID、名称、IP 或主机名，传输结束后才返回会话；
+//! 为 `@组名` 时向设备组中的所有设备发送，返回各设备的结果。
+//! `/api/events` 的每条消息带有递增的 `id`，data 为 [`ProtocolEvent`] 的 JSON；
+//! 断线重连时携带 `Last-Event-ID` 可补发之后的事件，传输进度事件不补发。
+//! `/api/` 之外的路径提供基于这些接口的网页管理界面
+
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use hyper::body::HttpBody;
+use hyper::header::{self, HeaderValue};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use super::admin;
+use super::{constant_time_eq, web};
+use crate::config::group_name;
+use crate::control::{Controller, SendError, SendRequest};
+use crate::{ProtocolError, ProtocolEvent};
+
+/// 请求体大小上限
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// `/api/history` 默认返回的记录数
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// 保留供补发的最近事件数
+const RECENT_EVENTS: usize = 256;
+
+/// 事件流没有事件时发送注释的间隔，避免代理断开空闲连接
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// 管理 API 服务
+#[derive(Debug)]
+pub struct ManagementApi {
+    token: String,
+    controller: Controller,
+    feed: Arc<EventFeed>,
+}
+
+impl ManagementApi {
+    /// 创建管理 API，controller 未设置审计日志时 `/api/history` 返回 404
+    pub fn new(token: impl Into<String>, controller: Controller) -> Self {
+        Self {
+            token: token.into(),
+            controller,
+            feed: Arc::new(EventFeed::new()),
+        }
+    }
+
+    /// 在 listener 上提供管理 API，直到 cancel 取消
+    pub async fn serve(
+        self: Arc<Self>,
+        listener: std::net::TcpListener,
+        cancel: CancellationToken,
+    ) -> crate::Result<()> {
+        let events = self.controller.subscribe().await;
+        tokio::spawn(self.feed.clone().run(events, cancel.clone()));
+        web::serve("api", listener, cancel, move |request| {
+            let api = self.clone();
+            async move { api.handle(request).await }
+        })
+        .await
+    }
+
+    async fn handle(&self, request: Request<Body>) -> crate::Result<Response<Body>> {
+        let path = request.uri().path();
+        if path != "/api" && !path.starts_with("/api/") {
+            web::ensure_get(&request)?;
+            return admin::asset(path).ok_or(ProtocolError::Status(404));
+        }
+        if !self.authorized(&request) {
+            let mut response = error(StatusCode::UNAUTHORIZED, "需要有效的访问令牌");
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            return Ok(response);
+        }
+
+        let method = request.method().clone();
+        let path = request.uri().path().trim_end_matches('/').to_string();
+        let segments: Vec<&str> = path.split('/').skip(1).collect();
+        match (&method, segments.as_slice()) {
+            (&Method::GET, ["api", "devices"]) => {
+                json(StatusCode::OK, &self.controller.devices().await)
+            }
+            (&Method::GET, ["api", "sessions"]) => {
+                json(StatusCode::OK, &self.controller.sessions().await)
+            }
+            (&Method::GET, ["api", "sessions", id]) => match self.controller.session(id).await {
+                Some(session) => json(StatusCode::OK, &session),
+                None => Ok(error(StatusCode::NOT_FOUND, "会话不存在")),
+            },
+            (&Method::POST, ["api", "sessions", id, action @ ("accept" | "reject")]) => {
+                if !self.controller.respond(id, *action == "accept") {
+                    return Ok(error(StatusCode::CONFLICT, "会话不在等待确认"));
+                }
+                Ok(status_only(StatusCode::NO_CONTENT))
+            }
+            (&Method::POST, ["api", "send"]) => {
+                let body = read_body(request.into_body()).await?;
+                let Ok(send) = serde_json::from_slice::<SendRequest>(&body) else {
+                    return Ok(error(StatusCode::BAD_REQUEST, "请求体无效"));
+                };
+                // 向设备组发送时返回各设备的结果
+                let result = match group_name(&send.device) {
+                    Some(group) => match self.controller.send_group(group, &send.paths).await {
+                        Ok(results) => return json(StatusCode::OK, &results),
+                        Err(e) => e,
+                    },
+                    None => match self.controller.send(send).await {
+                        Ok(session) => return json(StatusCode::OK, &session),
+                        Err(e) => e,
+                    },
+                };
+                match result {
+                    e @ SendError::NoFiles => Ok(error(StatusCode::BAD_REQUEST, &e.to_string())),
+                    e @ (SendError::DeviceNotFound(_) | SendError::GroupNotFound(_)) => {
+                        Ok(error(StatusCode::NOT_FOUND, &e.to_string()))
+                    }
+                    SendError::Failed(e) => Ok(error(StatusCode::BAD_GATEWAY, &e.to_string())),
+                }
+            }
+            (&Method::GET, ["api", "history"]) => {
+                let limit = query_param(&request, "limit")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_HISTORY_LIMIT);
+                match self.controller.history(limit)? {
+                    Some(records) => json(StatusCode::OK, &records),
+                    None => Ok(error(StatusCode::NOT_FOUND, "未启用审计日志")),
+                }
+            }
+            (&Method::GET, ["api", "events"]) => {
+                let after = request
+                    .headers()
+                    .get("last-event-id")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse().ok())
+                    .unwrap_or(u64::MAX);
+                Ok(Response::builder()
+                    .header(header::CONTENT_TYPE, "text/event-stream")
+                    .header(header::CACHE_CONTROL, "no-store")
+                    .body(self.feed.stream(after))
+                    .unwrap())
+            }
+            (&Method::GET, ["api", "gate"]) => self.gate_status(),
+            (&Method::POST, ["api", "gate"]) => {
+                let body = read_body(request.into_body()).await?;
+                let Ok(open) = serde_json::from_slice::<OpenGate>(&body) else {
+                    return Ok(error(StatusCode::BAD_REQUEST, "请求体无效"));
+                };
+                if let Err(e) = self.controller.open_gate(Duration::from_secs(open.secs)) {
+                    return Ok(error(StatusCode::BAD_REQUEST, &e.to_string()));
+                }
+                self.gate_status()
+            }
+            (&Method::DELETE, ["api", "gate"]) => {
+                self.controller.close_gate();
+                self.gate_status()
+            }
+            (&Method::GET, ["api", "events", "recent"]) => {
+                let (recent, _) = self.feed.subscribe(0);
+                let recent: Vec<&EventRecord> = recent.iter().map(Arc::as_ref).collect();
+                json(StatusCode::OK, &recent)
+            }
+            _ => Ok(error(StatusCode::NOT_FOUND, "未知的路径")),
+        }
+    }
+
+    fn gate_status(&self) -> crate::Result<Response<Body>> {
+        let remaining = self.controller.gate_remaining();
+        json(StatusCode::OK, &serde_json::json!({ "remaining": remaining }))
+    }
+
+    /// 校验 Bearer 令牌
+    fn authorized(&self, request: &Request<Body>) -> bool {
+        request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), self.token.as_bytes()))
+    }
+}
+
+/// `POST /api/gate` 的请求体
+#[derive(Debug, Deserialize)]
+struct OpenGate {
+    secs: u64,
+}
+
+/// 带序号的事件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventRecord {
+    /// 从 1 开始递增，管理 API 重启后重新计数
+    pub id: u64,
+    /// [`ProtocolEvent`] 的 JSON
+    pub event: serde_json::Value,
+}
+
+/// 为事件总线上的事件编号并保留最近的事件，供事件流补发
+#[derive(Debug)]
+struct EventFeed {
+    state: std::sync::Mutex<FeedState>,
+    sender: broadcast::Sender<Arc<EventRecord>>,
+    /// 停止转发后取消，结束所有事件流，服务才能正常退出
+    closed: CancellationToken,
+}
+
+#[derive(Debug, Default)]
+struct FeedState {
+    last_id: u64,
+    recent: VecDeque<Arc<EventRecord>>,
+}
+
+impl EventFeed {
+    fn new() -> Self {
+        let (sender, _) = broadcast::channel(RECENT_EVENTS);
+        Self {
+            state: Default::default(),
+            sender,
+            closed: CancellationToken::new(),
+        }
+    }
+
+    /// 转发 events 中的事件，直到事件总线关闭或 cancel 取消
+    async fn run(
+        self: Arc<Self>,
+        mut events: broadcast::Receiver<ProtocolEvent>,
+        cancel: CancellationToken,
+    ) {
+        let _closed = self.closed.clone().drop_guard();
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => return,
+                event = events.recv() => event,
+            };
+            match event {
+                Ok(event) => self.publish(&event),
+                Err(RecvError::Lagged(skipped)) => warn!(skipped, "事件流转发过慢，已丢失部分事件"),
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// 为事件编号后推送给所有事件流，进度事件过于频繁，只推送不保留
+    fn publish(&self, event: &ProtocolEvent) {
+        let Ok(value) = serde_json::to_value(event) else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        state.last_id += 1;
+        let record = Arc::new(EventRecord {
+            id: state.last_id,
+            event: value,
+        });
+        if !matches!(event, ProtocolEvent::FileProgress { .. }) {
+            if state.recent.len() == RECENT_EVENTS {
+                state.recent.pop_front();
+            }
+            state.recent.push_back(record.clone());
+        }
+        let _ = self.sender.send(record);
+    }
+
+    /// 序号大于 after 的最近事件和之后事件的订阅，两者之间不遗漏也不重复
+    fn subscribe(
+        &self,
+        after: u64,
+    ) -> (Vec<Arc<EventRecord>>, broadcast::Receiver<Arc<EventRecord>>) {
+        let state = self.state.lock().unwrap();
+        let recent = state.recent.iter().filter(|r| r.id > after).cloned().collect();
+        (recent, self.sender.subscribe())
+    }
+
+    /// Server-Sent Events 格式的事件流，先补发序号大于 after 的事件
+    fn stream(&self, after: u64) -> Body {
+        let (recent, receiver) = self.subscribe(after);
+        // 先发送重连间隔，客户端随即收到响应头
+        let head = std::iter::once("retry: 3000\n\n".to_string());
+        let replay = futures::stream::iter(head.chain(recent.into_iter().map(|r| sse_message(&r))));
+        let live = futures::stream::unfold(
+            (receiver, self.closed.clone()),
+            |(mut receiver, closed)| async move {
+                let message = tokio::select! {
+                    _ = closed.cancelled() => return None,
+                    _ = tokio::time::sleep(KEEPALIVE_INTERVAL) => ": keepalive\n\n".to_string(),
+                    record = receiver.recv() => match record {
+                        Ok(record) => sse_message(&record),
+                        // 跟不上时断开，客户端携带 Last-Event-ID 重连后补发
+                        Err(_) => return None,
+                    },
+                };
+                Some((message, (receiver, closed)))
+            },
+        );
+        Body::wrap_stream(replay.chain(live).map(Ok::<_, Infallible>))
+    }
+}
+
+fn sse_message(record: &EventRecord) -> String {
+    format!("id: {}\ndata: {}\n\n", record.id, record.event)
+}
+
+/// 查询 base (如 `http://127.0.0.1:53318`) 上的管理 API 保留的最近事件
+pub async fn recent_events(base: &str, token: &str) -> crate::Result<Vec<EventRecord>> {
+    let response = reqwest::Client::new()
+        .get(format!("{}/api/events/recent", base.trim_end_matches('/')))
+        .bearer_auth(token)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(ProtocolError::Status(response.status().as_u16()));
+    }
+    Ok(response.json().await?)
+}
+
+/// 管理 API 事件流的订阅
+#[derive(Debug)]
+pub struct EventSubscription {
+    response: reqwest::Response,
+    buffer: Vec<u8>,
+}
+
+impl EventSubscription {
+    /// 订阅 base 上的管理 API 事件流，after 不为空时先补发序号大于 after 的最近事件
+    pub async fn connect(base: &str, token: &str, after: Option<u64>) -> crate::Result<Self> {
+        let mut request = reqwest::Client::new()
+            .get(format!("{}/api/events", base.trim_end_matches('/')))
+            .bearer_auth(token);
+        if let Some(after) = after {
+            request = request.header("last-event-id", after);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(ProtocolError::Status(response.status().as_u16()));
+        }
+        Ok(Self {
+            response,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// 下一个事件，服务端关闭事件流时返回 None
+    pub async fn next(&mut self) -> crate::Result<Option<EventRecord>> {
+        loop {
+            while let Some(end) = self.buffer.windows(2).position(|w| w == b"\n\n") {
+                let message: Vec<u8> = self.buffer.drain(..end + 2).collect();
+                if let Some(record) = parse_sse_message(&String::from_utf8_lossy(&message))? {
+                    return Ok(Some(record));
+                }
+            }
+            match self.response.chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// 解析一条事件流消息，注释和没有数据的消息返回 None
+fn parse_sse_message(message: &str) -> crate::Result<Option<EventRecord>> {
+    let mut id = None;
+    let mut data = None;
+    for line in message.lines() {
+        if let Some(value) = line.strip_prefix("id:") {
+            id = value.trim().parse().ok();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data = Some(value.trim_start());
+        }
+    }
+    let (Some(id), Some(data)) = (id, data) else {
+        return Ok(None);
+    };
+    Ok(Some(EventRecord {
+        id,
+        event: serde_json::from_str(data)?,
+    }))
+}
+
+/// 读取请求体，超过上限时返回 `Status(413)`
+async fn read_body(mut body: Body) -> crate::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(std::io::Error::other)?;
+        if data.len() + chunk.len() > MAX_BODY_SIZE {
+            return Err(ProtocolError::Status(413));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+/// 查询参数的值，不做百分号解码
+fn query_param<'a>(request: &'a Request<Body>, name: &str) -> Option<&'a str> {
+    request
+        .uri()
+        .query()?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn json<T: Serialize>(code: StatusCode, value: &T) -> crate::Result<Response<Body>> {
+    Ok(Response::builder()
+        .status(code)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(serde_json::to_vec(value)?))
+        .unwrap())
+}
+
+/// `{"error": message}` 形式的错误响应
+fn error(code: StatusCode, message: &str) -> Response<Body> {
+    json(code, &serde_json::json!({ "error": message })).unwrap_or_else(|_| web::status(code))
+}
+
+fn status_only(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+}
@@ -0,0 +1,145 @@
+//! 文件响应体
+//!
+//! 反向下载和分享链接直接把文件流式写入响应，不整体读入内存。
+//! [`FileBody`] 只在 HTTP 层拉取下一块时才读取文件，客户端接收变慢时读取随之暂停；
+//! 同时支持单个 `Range` 区间，用于断点续传和浏览器拖动进度
+
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::Stream;
+use tokio::io::AsyncReadExt;
+use tokio_util::io::ReaderStream;
+
+use crate::fs::{BoxedReader, FileSystemRef};
+use crate::ProtocolError;
+
+/// 每次从文件读取的块大小 (256KB)
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// 闭区间字节范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// `Content-Range` 头的值
+    pub fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+/// 解析 `Range` 请求头
+///
+/// 返回 `Ok(None)` 表示应返回完整文件，包括格式无法识别和多区间请求；
+/// 区间完全超出文件时返回 `Status(416)`
+pub fn parse_range(value: &str, len: u64) -> crate::Result<Option<ByteRange>> {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+
+    let range = match (start.trim(), end.trim()) {
+        ("", "") => return Ok(None),
+        // 后缀区间: 最后 n 个字节
+        ("", suffix) => {
+            let Ok(n) = suffix.parse::<u64>() else {
+                return Ok(None);
+            };
+            if n == 0 || len == 0 {
+                return Err(ProtocolError::Status(416));
+            }
+            ByteRange {
+                start: len.saturating_sub(n),
+                end: len - 1,
+            }
+        }
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return Ok(None);
+            };
+            let end = match end {
+                "" => u64::MAX,
+                end => match end.parse::<u64>() {
+                    Ok(end) if end >= start => end,
+                    _ => return Ok(None),
+                },
+            };
+            if start >= len {
+                return Err(ProtocolError::Status(416));
+            }
+            ByteRange {
+                start,
+                end: end.min(len - 1),
+            }
+        }
+    };
+    Ok(Some(range))
+}
+
+/// 按需读取的文件响应体
+pub struct FileBody {
+    inner: ReaderStream<tokio::io::Take<BoxedReader>>,
+    len: u64,
+    range: Option<ByteRange>,
+}
+
+impl FileBody {
+    /// 打开文件，range 为空时发送完整文件
+    pub async fn open(
+        fs: &FileSystemRef,
+        path: &Path,
+        range: Option<ByteRange>,
+    ) -> crate::Result<Self> {
+        let (reader, len) = match range {
+            Some(range) => (fs.open_read_at(path, range.start).await?, range.end - range.start + 1),
+            None => (fs.open_read(path).await?, fs.file_len(path).await?),
+        };
+        Ok(Self {
+            inner: ReaderStream::with_capacity(reader.take(len), CHUNK_SIZE),
+            len,
+            range,
+        })
+    }
+
+    /// 响应体长度，用于 `Content-Length`
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// 响应体是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 请求的区间，存在时应返回 206
+    pub fn range(&self) -> Option<ByteRange> {
+        self.range
+    }
+}
+
+impl std::fmt::Debug for FileBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileBody")
+            .field("len", &self.len)
+            .field("range", &self.range)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Stream for FileBody {
+    type Item = std::io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+}
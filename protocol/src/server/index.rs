@@ -0,0 +1,224 @@
+//! 已接收文件的浏览页面
+//!
+//! 在单独端口上列出下载目录中已完成的文件并提供直接下载链接，
+//! 无界面部署 (例如 NAS) 时可从局域网内任意浏览器取回收到的文件。
+//! 页面和下载均需通过 HTTP Basic 认证，用户名任意，密码为配置的 `browse_password`
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use base64::Engine;
+use hyper::header;
+use hyper::{Body, Request, Response, StatusCode};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use super::{constant_time_eq, format_size, web};
+use crate::fs::{FileSystemRef, LocalFs};
+use crate::{ProtocolError, SessionManager, SessionState};
+
+/// 认证域
+const REALM: &str = "PeerSend";
+
+/// 下载链接前缀
+const FILES_PREFIX: &str = "/files/";
+
+/// 浏览页面中的一个文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub name: String,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// 已接收文件索引
+#[derive(Debug)]
+pub struct FileIndex {
+    download_dir: PathBuf,
+    password: String,
+    sessions: Arc<Mutex<SessionManager>>,
+    fs: FileSystemRef,
+}
+
+impl FileIndex {
+    /// 创建索引，sessions 用于排除仍在接收中的文件
+    pub fn new(
+        download_dir: impl Into<PathBuf>,
+        password: impl Into<String>,
+        sessions: Arc<Mutex<SessionManager>>,
+    ) -> Self {
+        Self {
+            download_dir: download_dir.into(),
+            password: password.into(),
+            sessions,
+            fs: LocalFs::shared(),
+        }
+    }
+
+    /// 列出已完成的文件，按修改时间从新到旧排列
+    ///
+    /// 隐藏文件和仍在等待确认或传输中的会话的文件不会列出
+    pub async fn entries(&self) -> crate::Result<Vec<IndexEntry>> {
+        let pending = self.pending_names().await;
+        let mut entries = Vec::new();
+        let mut dir = tokio::fs::read_dir(&self.download_dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if name.starts_with('.') || pending.contains(&name) {
+                continue;
+            }
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+            entries.push(IndexEntry {
+                name,
+                size: metadata.len(),
+                modified: metadata.modified().ok(),
+            });
+        }
+        entries.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| a.name.cmp(&b.name)));
+        Ok(entries)
+    }
+
+    /// 在 listener 上提供浏览页面，直到 cancel 取消
+    pub async fn serve(
+        self: Arc<Self>,
+        listener: std::net::TcpListener,
+        cancel: CancellationToken,
+    ) -> crate::Result<()> {
+        web::serve("browse", listener, cancel, move |request| {
+            let index = self.clone();
+            async move { index.handle(request).await }
+        })
+        .await
+    }
+
+    async fn handle(&self, request: Request<Body>) -> crate::Result<Response<Body>> {
+        if !self.authorized(&request) {
+            return Ok(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .header(
+                    header::WWW_AUTHENTICATE,
+                    format!("Basic realm=\"{}\", charset=\"UTF-8\"", REALM),
+                )
+                .body(Body::from("需要认证"))
+                .unwrap());
+        }
+        web::ensure_get(&request)?;
+
+        let path = request.uri().path();
+        if path == "/" {
+            self.listing().await
+        } else if let Some(name) = path.strip_prefix(FILES_PREFIX) {
+            self.download(name, &request).await
+        } else {
+            Err(ProtocolError::Status(404))
+        }
+    }
+
+    /// 校验 Basic 认证，只比较密码
+    fn authorized(&self, request: &Request<Body>) -> bool {
+        let Some(credentials) = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Basic "))
+            .and_then(|v| base64::engine::general_purpose::STANDARD.decode(v.trim()).ok())
+        else {
+            return false;
+        };
+        let Some(split) = credentials.iter().position(|&b| b == b':') else {
+            return false;
+        };
+        constant_time_eq(&credentials[split + 1..], self.password.as_bytes())
+    }
+
+    /// 仍在等待确认或传输中的文件名
+    async fn pending_names(&self) -> HashSet<String> {
+        let sessions = self.sessions.lock().await.get_all_sessions().await;
+        let mut names = HashSet::new();
+        for session in sessions.iter() {
+            let state = session.state.lock().await.clone();
+            if matches!(state, SessionState::Waiting | SessionState::Transferring) {
+                names.extend(session.files.iter().filter_map(|f| file_name(&f.name)));
+            }
+        }
+        names
+    }
+
+    async fn listing(&self) -> crate::Result<Response<Body>> {
+        let entries = self.entries().await?;
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+             <title>PeerSend</title></head><body>\n<h1>已接收的文件</h1>\n",
+        );
+        if entries.is_empty() {
+            html.push_str("<p>暂无文件</p>\n");
+        } else {
+            html.push_str("<table>\n<tr><th>名称</th><th>大小</th></tr>\n");
+            for entry in &entries {
+                html.push_str(&format!(
+                    "<tr><td><a href=\"{}{}\">{}</a></td><td>{}</td></tr>\n",
+                    FILES_PREFIX,
+                    utf8_percent_encode(&entry.name, NON_ALPHANUMERIC),
+                    escape_html(&entry.name),
+                    format_size(entry.size)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body></html>\n");
+
+        Ok(Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(Body::from(html))
+            .unwrap())
+    }
+
+    async fn download(&self, name: &str, request: &Request<Body>) -> crate::Result<Response<Body>> {
+        let name = percent_decode_str(name)
+            .decode_utf8()
+            .map_err(|_| ProtocolError::Status(400))?;
+        // 只允许下载目录中的文件，防止路径穿越
+        if file_name(&name).as_deref() != Some(&*name) || name.starts_with('.') {
+            return Err(ProtocolError::Status(404));
+        }
+        if self.pending_names().await.contains(&*name) {
+            return Err(ProtocolError::Status(404));
+        }
+
+        let path = self.download_dir.join(&*name);
+        web::file_response(&self.fs, &path, &name, request).await
+    }
+}
+
+/// 下载目录中的文件名，与接收时的处理一致
+fn file_name(name: &str) -> Option<String> {
+    Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string)
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
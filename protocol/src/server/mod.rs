@@ -1,12 +1,49 @@
 //! HTTP 服务器模块
 //!
-//! LocalSend HTTP API 服务器
-//! 将在 Phase 4 中完整实现
+//! LocalSend HTTP API 服务器，接口见 [`v2`]。
+//! 接收服务的端口上提供 LocalSend v2 接口，`use_tls` 时以 HTTPS 提供，
+//! 另可通过传输监听器接收 QUIC 或裸 TCP 文件流。
+//! 文件流头部带有 prepare-upload 发放的令牌，与 HTTP 上传一样校验令牌和来源地址
 
+pub mod accept;
+#[cfg(feature = "api")]
+mod admin;
+#[cfg(feature = "api")]
+pub mod api;
+pub mod body;
+#[cfg(feature = "browse")]
+pub mod index;
+#[cfg(feature = "share")]
+pub mod share;
+pub mod v2;
+#[cfg(any(feature = "api", feature = "browse", feature = "share"))]
+mod web;
+
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWriteExt};
 use tokio::sync::Mutex;
 use std::net::SocketAddr;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, field, info, info_span, instrument, warn, Instrument};
+use crate::dto::v2::{PrepareUploadRequestDto, PrepareUploadResponseDto};
+use crate::dto::{FileRequest, FileResponse};
+use crate::event::ReceiveEvent;
+use crate::storage::{self, dedup::{self, HashIndex}, links};
+use crate::storage::names::{numbered_name, sanitize_file_name};
+use crate::throttle::{Throttle, Throttled};
+use crate::keepalive::{self, IdleTimeout};
+use crate::transport::{self, BoxedStream, StreamHeader, TransportListener};
+use crate::{constant_time_eq, ProtocolError, ProtocolEvent, SessionState, SESSION_TIMEOUT_SECS};
+use accept::AcceptGate;
+use crate::audit::{AuditAction, AuditEntry, AuditFile, AuditLog};
+use crate::journal::{JournalFile, SessionJournal};
 use crate::{LocalSendConfig, FileSession, FileInfo, DeviceInfo, SessionManager, DiscoveryManager};
+use crate::{CollisionPolicy, DuplicatePolicy, FileWarning, SymlinkPolicy};
+#[cfg(feature = "tls")]
+use crate::crypto::tls::TlsIdentity;
 
 /// HTTP 服务器
 #[derive(Debug)]
@@ -15,6 +52,19 @@ pub struct LocalSendServer {
     config: LocalSendConfig,
     session_manager: Arc<Mutex<SessionManager>>,
     discovery_manager: Arc<Mutex<DiscoveryManager>>,
+    listener: Option<std::net::TcpListener>,
+    transport_listeners: std::sync::Mutex<Vec<Box<dyn TransportListener>>>,
+    events: Option<mpsc::UnboundedSender<ReceiveEvent>>,
+    accept: AcceptGate,
+    accept_timeout: Duration,
+    audit: Option<AuditLog>,
+    hash_index: Option<HashIndex>,
+    journal: Option<SessionJournal>,
+    throttle: Throttle,
+    /// `use_tls` 时 HTTPS 使用的证书
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<TlsIdentity>>,
+    cancel: CancellationToken,
 }
 
 impl LocalSendServer {
@@ -25,17 +75,668 @@ impl LocalSendServer {
         session_manager: Arc<Mutex<SessionManager>>,
         discovery_manager: Arc<Mutex<DiscoveryManager>>,
     ) -> Self {
+        let throttle = Throttle::from_config(&config);
         Self {
             addr,
             config,
             session_manager,
             discovery_manager,
+            listener: None,
+            transport_listeners: std::sync::Mutex::new(Vec::new()),
+            events: None,
+            accept: AcceptGate::new(),
+            accept_timeout: Duration::from_secs(SESSION_TIMEOUT_SECS),
+            audit: None,
+            hash_index: None,
+            journal: None,
+            throttle,
+            #[cfg(feature = "tls")]
+            tls: None,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// 使用外部的取消令牌，令牌取消时服务器停止
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 停止服务器
+    pub fn shutdown(&self) {
+        self.cancel.cancel();
+    }
+
+    /// 等待服务器停止
+    pub async fn stopped(&self) {
+        self.cancel.cancelled().await;
+    }
+
+    /// 设置接收事件的发送端
+    pub fn with_event_sender(mut self, events: mpsc::UnboundedSender<ReceiveEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// 使用已绑定的监听套接字，例如由 systemd 套接字激活传入
+    ///
+    /// 设置后服务器不再自行绑定地址
+    pub fn with_listener(mut self, listener: std::net::TcpListener) -> Self {
+        if let Ok(addr) = listener.local_addr() {
+            self.addr = addr;
+        }
+        self.listener = Some(listener);
+        self
+    }
+
+    /// `use_tls` 时使用指定的证书，未设置时从配置目录加载或生成
+    #[cfg(feature = "tls")]
+    pub fn with_tls_identity(mut self, identity: TlsIdentity) -> Self {
+        self.tls = Some(Arc::new(identity));
+        self
+    }
+
+    /// 同时通过传输监听器接收文件流，例如 QUIC 或裸 TCP，可多次调用
+    ///
+    /// 流头部中的会话和文件须已通过 prepare-upload 协商
+    pub fn with_transport_listener(self, listener: Box<dyn TransportListener>) -> Self {
+        self.transport_listeners.lock().unwrap().push(listener);
+        self
+    }
+
+    /// 设置等待用户确认的最长时间，超时视为拒绝
+    pub fn with_accept_timeout(mut self, timeout: Duration) -> Self {
+        self.accept_timeout = timeout;
+        self
+    }
+
+    /// 使用外部的确认队列，服务器之外的前端可持有其克隆答复请求
+    pub fn with_accept_gate(mut self, accept: AcceptGate) -> Self {
+        self.accept = accept;
+        self
+    }
+
+    /// 在审计日志中记录收到的请求和接受或拒绝的决定
+    pub fn with_audit_log(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// 记录收到文件的哈希，并按配置的 [`DuplicatePolicy`] 处理与已有文件相同的文件
+    pub fn with_hash_index(mut self, index: HashIndex) -> Self {
+        self.hash_index = Some(index);
+        self
+    }
+
+    /// 在会话日志中记录接受的会话和每个文件的进度，崩溃后可据此清理或续传
+    pub fn with_journal(mut self, journal: SessionJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// 使用外部的限速器，与发送共用带宽上限
+    ///
+    /// 默认按配置创建独立的限速器
+    pub fn with_throttle(mut self, throttle: Throttle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// 答复挂起的文件发送请求，会话不在等待确认时返回 false
+    pub fn respond(&self, session_id: &str, accept: bool) -> bool {
+        self.accept.respond(session_id, accept)
+    }
+
+    /// 处理 prepare-upload 请求，返回会话 ID 和每个文件的上传令牌
+    ///
+    /// 配置的快速保存对发送方生效时直接接受，否则发布 `SessionRequested`
+    /// 并等待 [`respond`](Self::respond)，被拒绝或超时返回 [`ProtocolError::Rejected`]。
+    /// 被屏蔽或不在允许列表中的设备，以及超出文件大小或数量上限的请求不创建会话，直接拒绝。
+    /// 请求计入发现管理器的洪泛防护，过于频繁的地址在封禁期内的请求同样直接拒绝。
+    ///
+    /// pin 为请求附带的 PIN (`?pin=`)，按 [`LocalSendConfig::requires_pin`] 需要 PIN
+    /// 而未提供或不正确时返回 [`ProtocolError::PinRequired`]。
+    /// 设置了审计日志时记录请求及接受或拒绝的原因，设置了会话日志时记录接受的会话。
+    /// 令牌由 [`SessionManager::issue_tokens`] 发放，只接受来自 ip 的上传
+    #[instrument(skip(self, request, pin), fields(peer = %ip, device_id = %request.info.fingerprint))]
+    pub async fn prepare_upload(
+        &self,
+        ip: &str,
+        request: PrepareUploadRequestDto,
+        pin: Option<&str>,
+    ) -> crate::Result<PrepareUploadResponseDto> {
+        let sender = request.info.to_device(ip);
+        let files: Vec<FileInfo> = request
+            .files
+            .into_values()
+            .map(|f| FileInfo {
+                id: f.id,
+                name: f.file_name,
+                size: f.size,
+                file_type: f.file_type,
+                metadata: f
+                    .metadata
+                    .and_then(|m| m.symlink)
+                    .map(|target| links::link_metadata(&target)),
+                sha256: f.sha256,
+            })
+            .collect();
+
+        if !self.discovery_manager.lock().await.admit(ip, &sender.id).await {
+            // 洪泛期间的请求不写入审计日志，封禁时已输出警告
+            return Err(ProtocolError::Rejected("请求过于频繁，请稍后再试".to_string()));
+        }
+        if !self.config.allows_device(&sender.id, ip) {
+            warn!("设备已被屏蔽或不在允许列表中，拒绝请求");
+            self.audit(AuditAction::Rejected, None, &sender, &files, Some("设备被屏蔽或不在允许列表中"));
+            return Err(ProtocolError::Rejected(format!("不接受来自 {} 的请求", sender.name)));
+        }
+        if self.config.requires_pin(&sender.id, ip) {
+            let expected = self.config.pin.as_deref().unwrap_or_default();
+            if !pin.is_some_and(|pin| constant_time_eq(pin.as_bytes(), expected.as_bytes())) {
+                warn!(pin_supplied = pin.is_some(), "PIN 缺失或不正确，拒绝请求");
+                self.audit(AuditAction::Rejected, None, &sender, &files, Some("PIN 缺失或不正确"));
+                return Err(ProtocolError::PinRequired);
+            }
+        }
+        if let Err(reason) = self.check_limits(&files) {
+            warn!(%reason, "超出接收限制，拒绝请求");
+            self.audit(AuditAction::Rejected, None, &sender, &files, Some(&reason));
+            return Err(ProtocolError::Rejected(reason));
+        }
+
+        let sessions = self.session_manager.lock().await.clone();
+        let session = sessions
+            .create_session(sender.id.clone(), self.config.device_id.clone(), files)
+            .await;
+        let session_id = Some(session.id.as_str());
+        self.audit(AuditAction::Requested, session_id, &sender, &session.files, None);
+
+        let quick_save = self.config.quick_saves_from(&sender.id);
+        let open = !quick_save && self.accept.is_open();
+        let accepted = if quick_save || open {
+            debug!(session_id = %session.id, quick_save, open, "自动接受");
+            true
+        } else {
+            sessions.events().emit(ProtocolEvent::SessionRequested {
+                session_id: session.id.clone(),
+                sender: sender.name.clone(),
+                files: session.files.clone(),
+            });
+            if let Some(events) = &self.events {
+                let _ = events.send(ReceiveEvent::SessionRequested {
+                    session_id: session.id.clone(),
+                    sender: sender.name.clone(),
+                    files: session.files.clone(),
+                });
+            }
+            self.accept.wait(&session.id, self.accept_timeout).await
+        };
+
+        if !accepted {
+            self.audit(AuditAction::Rejected, session_id, &sender, &[], Some("用户拒绝或超时"));
+            sessions.set_state(&session.id, SessionState::Cancelled).await;
+            sessions.remove_session(&session.id).await;
+            return Err(ProtocolError::Rejected(format!("{} 的请求未被接受", sender.name)));
+        }
+        let reason = match (quick_save, open) {
+            (true, _) => "快速保存",
+            (_, true) => "开放接收",
+            _ => "用户接受",
+        };
+        self.audit(AuditAction::Accepted, session_id, &sender, &[], Some(reason));
+
+        let mut files: Vec<&FileInfo> = Vec::with_capacity(session.files.len());
+        for file in &session.files {
+            if links::link_target(file).is_some() {
+                self.restore_link(&session, file).await;
+            } else if !self.resolve_duplicate(&session.id, file)
+                && !self.skip_existing(&session, file).await
+            {
+                files.push(file);
+            }
+        }
+        self.journal_begin(&session.id, &sender, &files);
+        let tokens = sessions.issue_tokens(&session.id, ip, files.iter().map(|f| f.id.as_str()));
+        if tokens.is_empty() {
+            // 没有需要传输的文件，会话直接结束
+            sessions.set_state(&session.id, SessionState::Finished).await;
         }
+        Ok(PrepareUploadResponseDto {
+            session_id: session.id.clone(),
+            files: tokens,
+        })
+    }
+
+    /// 处理文本消息请求 (`/api/v1/localsend/request` 中不带文件、只带 message 的请求)
+    ///
+    /// 文本不写入磁盘，直接发布 `TextReceived`，无需用户确认。
+    /// 与 [`prepare_upload`](Self::prepare_upload) 一样计入洪泛防护，
+    /// 并拒绝被屏蔽或不在允许列表中的设备
+    #[instrument(skip(self, request), fields(peer = %ip, device_id = %request.id))]
+    pub async fn receive_text(&self, ip: &str, request: FileRequest) -> crate::Result<FileResponse> {
+        if !self.discovery_manager.lock().await.admit(ip, &request.id).await {
+            return Err(ProtocolError::Rejected("请求过于频繁，请稍后再试".to_string()));
+        }
+        if !self.config.allows_device(&request.id, ip) {
+            warn!("设备已被屏蔽或不在允许列表中，拒绝文本消息");
+            return Err(ProtocolError::Rejected(format!("不接受来自 {} 的消息", request.sender)));
+        }
+        if request.message.is_empty() || !request.files.is_empty() {
+            return Err(ProtocolError::InvalidData("不是文本消息".to_string()));
+        }
+
+        debug!(len = request.message.len(), "收到文本消息");
+        self.session_manager
+            .lock()
+            .await
+            .events()
+            .emit(ProtocolEvent::TextReceived {
+                sender_id: request.id.clone(),
+                sender: request.sender.clone(),
+                text: request.message.clone(),
+            });
+        if let Some(events) = &self.events {
+            let _ = events.send(ReceiveEvent::TextReceived {
+                sender_id: request.id.clone(),
+                sender: request.sender,
+                text: request.message,
+            });
+        }
+        Ok(FileResponse {
+            id: self.config.device_id.clone(),
+            session_id: request.session_id,
+            accepted: true,
+            token: String::new(),
+        })
+    }
+
+    /// 收到的文件的保存路径，文件名经 [`sanitize_file_name`] 清理后无效时返回 None
+    fn target_path(&self, file: &FileInfo) -> Option<PathBuf> {
+        let name = sanitize_file_name(&file.name).ok()?;
+        Some(storage::receive_path(
+            Path::new(&self.config.download_dir),
+            &name,
+            &self.config.quarantine_extensions,
+        ))
+    }
+
+    /// 在会话日志中记录需要传输的文件，未启用或没有文件时忽略
+    fn journal_begin(&self, session_id: &str, sender: &DeviceInfo, files: &[&FileInfo]) {
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        let files: Vec<JournalFile> = files
+            .iter()
+            .filter_map(|f| {
+                Some(JournalFile {
+                    id: f.id.clone(),
+                    name: f.name.clone(),
+                    size: f.size,
+                    sha256: f.sha256.clone(),
+                    path: self.target_path(f)?,
+                })
+            })
+            .collect();
+        if files.is_empty() {
+            return;
+        }
+        if let Err(e) = journal.begin(session_id, &sender.id, &sender.name, files) {
+            warn!(error = %e, "写入会话日志失败");
+        }
+    }
+
+    /// 文件与已接收的文件相同时按配置的策略处理，返回是否无需传输
+    ///
+    /// 未设置哈希索引、文件未声明 SHA-256 或策略为
+    /// [`DuplicatePolicy::Duplicate`] 时总是需要传输
+    fn resolve_duplicate(&self, session_id: &str, file: &FileInfo) -> bool {
+        let policy = self.config.duplicate_policy;
+        if policy == DuplicatePolicy::Duplicate {
+            return false;
+        }
+        let (Some(index), Some(sha256)) = (&self.hash_index, &file.sha256) else {
+            return false;
+        };
+        let Some(existing) = index.find(sha256, file.size) else {
+            return false;
+        };
+        let Some(target) = self.target_path(file) else {
+            return false;
+        };
+        match dedup::resolve_duplicate(policy, &existing, &target) {
+            Ok(false) => false,
+            Ok(true) => {
+                info!(
+                    session_id,
+                    file_id = %file.id,
+                    existing = %existing.display(),
+                    ?policy,
+                    "已有相同的文件，无需传输"
+                );
+                if policy == DuplicatePolicy::Link {
+                    if let Some(events) = &self.events {
+                        let _ = events.send(ReceiveEvent::FileReceived {
+                            session_id: session_id.to_string(),
+                            path: target,
+                        });
+                    }
+                }
+                true
+            }
+            Err(e) => {
+                warn!(file_id = %file.id, error = %e, "链接已有文件失败，重新传输");
+                false
+            }
+        }
+    }
+
+    /// 策略为 [`CollisionPolicy::Skip`] 且下载目录中已有同名文件时跳过该文件，返回是否跳过
+    ///
+    /// 跳过的文件不发放令牌，在会话中记为跳过并写入警告
+    async fn skip_existing(&self, session: &FileSession, file: &FileInfo) -> bool {
+        if self.config.collision_policy != CollisionPolicy::Skip {
+            return false;
+        }
+        let Some(target) = self.target_path(file) else {
+            return false;
+        };
+        if !tokio::fs::try_exists(&target).await.unwrap_or(false) {
+            return false;
+        }
+        info!(session_id = %session.id, file_id = %file.id, "已有同名文件，跳过");
+        session.transfers.lock().unwrap().entry(file.id.clone()).or_default().skipped = true;
+        let warning = FileWarning::new(&file.name, "已有同名文件，已跳过");
+        session.warnings.lock().await.push(warning);
+        true
+    }
+
+    /// 在下载目录中重建对方发来的符号链接，无需传输
+    ///
+    /// 策略为 [`SymlinkPolicy::Skip`]、目标可能指向下载目录之外或创建失败时不重建，
+    /// 原因记入会话的 `warnings`
+    async fn restore_link(&self, session: &FileSession, file: &FileInfo) {
+        let target = links::link_target(file).unwrap_or_default();
+        let reason = if self.config.symlink_policy == SymlinkPolicy::Skip {
+            "未保留符号链接".to_string()
+        } else if !links::is_contained(target) {
+            format!("符号链接指向下载目录之外 ({})，未重建", target)
+        } else {
+            let Some(path) = self.target_path(file) else {
+                return;
+            };
+            match links::create_link(&path, target) {
+                Ok(()) => {
+                    info!(session_id = %session.id, path = %path.display(), target, "已重建符号链接");
+                    return;
+                }
+                Err(e) => format!("重建符号链接失败: {}", e),
+            }
+        };
+        warn!(session_id = %session.id, file = %file.name, %reason, "未保留符号链接");
+        session.warnings.lock().await.push(FileWarning::new(&file.name, reason));
+    }
+
+    /// 检查配置的文件大小和数量上限，超出时返回原因
+    fn check_limits(&self, files: &[FileInfo]) -> Result<(), String> {
+        if let Some(max) = self.config.max_files {
+            if files.len() > max {
+                return Err(format!("文件数 {} 超过上限 {}", files.len(), max));
+            }
+        }
+        if let Some(max) = self.config.max_file_size {
+            if let Some(file) = files.iter().find(|f| f.size > max) {
+                return Err(format!(
+                    "{} 的大小 {} 超过单个文件上限 {}",
+                    file.name,
+                    format_size(file.size),
+                    format_size(max)
+                ));
+            }
+        }
+        if let Some(max) = self.config.max_session_size {
+            let total = files.iter().fold(0u64, |total, f| total.saturating_add(f.size));
+            if total > max {
+                return Err(format!(
+                    "总大小 {} 超过单次接收上限 {}",
+                    format_size(total),
+                    format_size(max)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// 写入审计日志，未启用时忽略
+    fn audit(
+        &self,
+        action: AuditAction,
+        session_id: Option<&str>,
+        sender: &DeviceInfo,
+        files: &[FileInfo],
+        reason: Option<&str>,
+    ) {
+        let Some(audit) = &self.audit else {
+            return;
+        };
+        audit.record_or_warn(AuditEntry {
+            session_id: session_id.map(str::to_string),
+            peer: Some(sender.id.clone()),
+            peer_name: Some(sender.name.clone()),
+            ip: Some(sender.ip.clone()),
+            files: files.iter().map(AuditFile::from).collect(),
+            reason: reason.map(str::to_string),
+            ..AuditEntry::new(action)
+        });
     }
 
     /// 启动服务器
-    pub async fn start(&self) -> Result<(), std::io::Error> {
-        println!("LocalSend HTTP 服务器已启动，监听 {}", self.addr);
+    #[instrument(skip(self), fields(addr = %self.addr))]
+    pub async fn start(&self) -> crate::Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(crate::ProtocolError::Cancelled);
+        }
+
+        let listener = match &self.listener {
+            Some(listener) => {
+                info!("LocalSend HTTP 服务器使用继承的套接字");
+                listener.try_clone()?
+            }
+            None => crate::net::bind_tcp(self.addr, self.config.ip_mode)?,
+        };
+        let addr = listener.local_addr()?;
+        let mut server = self.detached();
+        server.resolve_tls()?;
+        let server = Arc::new(server);
+        let cancel = self.cancel.clone();
+        tokio::spawn(async move {
+            if let Err(e) = v2::serve(server, listener, cancel).await {
+                warn!(error = %e, "LocalSend HTTP 服务器已停止");
+            }
+        });
+        if let Some(events) = &self.events {
+            let _ = events.send(ReceiveEvent::Started { addr });
+        }
+
+        if let Some(port) = self.config.browse_port {
+            self.start_index(port)?;
+        }
+        if let Some(port) = self.config.share_port {
+            self.start_shares(port)?;
+        }
+        if let Some(port) = self.config.api_port {
+            self.start_api(port).await?;
+        }
+
+        let transport_listeners = std::mem::take(&mut *self.transport_listeners.lock().unwrap());
+        for listener in transport_listeners {
+            info!(addr = %listener.local_addr(), "传输监听器已启动");
+            tokio::spawn(accept_streams(listener, self.stream_receiver(), self.cancel.clone()));
+        }
+        Ok(())
+    }
+
+    /// 按 `use_tls` 准备 HTTPS 使用的证书，未设置时从配置目录加载或生成
+    #[cfg(feature = "tls")]
+    fn resolve_tls(&mut self) -> crate::Result<()> {
+        if !self.config.use_tls {
+            self.tls = None;
+        } else if self.tls.is_none() {
+            let dir = crate::crypto::tls::default_dir()
+                .ok_or_else(|| ProtocolError::InvalidConfig("无法确定配置目录".to_string()))?;
+            self.tls = Some(Arc::new(TlsIdentity::load_or_generate(&dir)?));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "tls"))]
+    fn resolve_tls(&mut self) -> crate::Result<()> {
+        if self.config.use_tls {
+            warn!("未启用 tls 特性，LocalSend 接口改用 HTTP 提供");
+        }
+        Ok(())
+    }
+
+    /// 本机在 LocalSend 接口中的指纹，以 HTTPS 提供时为证书指纹，否则为设备 ID
+    fn fingerprint(&self) -> &str {
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &self.tls {
+            return tls.fingerprint();
+        }
+        &self.config.device_id
+    }
+
+    /// 写入收到的文件时使用的状态，HTTP 上传与传输流共用
+    fn stream_receiver(&self) -> StreamReceiver {
+        StreamReceiver {
+            sessions: self.session_manager.clone(),
+            download_dir: PathBuf::from(&self.config.download_dir),
+            quarantine: Arc::from(self.config.quarantine_extensions.as_slice()),
+            events: self.events.clone(),
+            throttle: self.throttle.clone(),
+            hash_index: self.hash_index.clone(),
+            journal: self.journal.clone(),
+            collision: self.config.collision_policy,
+            #[cfg(feature = "extract")]
+            extract: self.config.extract.clone(),
+        }
+    }
+
+    /// 与本服务器共享会话和确认队列的副本，供 HTTP 接口的后台任务持有
+    ///
+    /// 副本不持有监听套接字
+    fn detached(&self) -> Self {
+        Self {
+            addr: self.addr,
+            config: self.config.clone(),
+            session_manager: self.session_manager.clone(),
+            discovery_manager: self.discovery_manager.clone(),
+            listener: None,
+            transport_listeners: std::sync::Mutex::new(Vec::new()),
+            events: self.events.clone(),
+            accept: self.accept.clone(),
+            accept_timeout: self.accept_timeout,
+            audit: self.audit.clone(),
+            hash_index: self.hash_index.clone(),
+            journal: self.journal.clone(),
+            throttle: self.throttle.clone(),
+            #[cfg(feature = "tls")]
+            tls: self.tls.clone(),
+            cancel: self.cancel.clone(),
+        }
+    }
+
+    /// 在 port 上启动已接收文件的浏览页面
+    #[cfg(feature = "browse")]
+    fn start_index(&self, port: u16) -> crate::Result<()> {
+        let password = match &self.config.browse_password {
+            Some(password) if !password.is_empty() => password.clone(),
+            _ => {
+                return Err(ProtocolError::InvalidConfig(
+                    "启用浏览页面时必须设置密码".to_string(),
+                ))
+            }
+        };
+        let listener = crate::net::bind_tcp(SocketAddr::new(self.addr.ip(), port), self.config.ip_mode)?;
+        let index = Arc::new(index::FileIndex::new(
+            &self.config.download_dir,
+            password,
+            self.session_manager.clone(),
+        ));
+        let cancel = self.cancel.clone();
+        tokio::spawn(async move {
+            if let Err(e) = index.serve(listener, cancel).await {
+                warn!(error = %e, "文件浏览页面已停止");
+            }
+        });
+        Ok(())
+    }
+
+    #[cfg(not(feature = "browse"))]
+    fn start_index(&self, port: u16) -> crate::Result<()> {
+        warn!(port, "未启用 browse 特性，忽略文件浏览页面配置");
+        Ok(())
+    }
+
+    /// 在 port 上启动分享链接服务
+    #[cfg(feature = "share")]
+    fn start_shares(&self, port: u16) -> crate::Result<()> {
+        let store = Arc::new(share::ShareStore::open_default()?);
+        let listener = crate::net::bind_tcp(SocketAddr::new(self.addr.ip(), port), self.config.ip_mode)?;
+        let cancel = self.cancel.clone();
+        tokio::spawn(async move {
+            if let Err(e) = store.serve(listener, cancel).await {
+                warn!(error = %e, "分享链接服务已停止");
+            }
+        });
+        Ok(())
+    }
+
+    #[cfg(not(feature = "share"))]
+    fn start_shares(&self, port: u16) -> crate::Result<()> {
+        warn!(port, "未启用 share 特性，忽略分享链接配置");
+        Ok(())
+    }
+
+    /// 在 port 上启动管理 API
+    #[cfg(feature = "api")]
+    async fn start_api(&self, port: u16) -> crate::Result<()> {
+        let token = match &self.config.api_token {
+            Some(token) if !token.is_empty() => token.clone(),
+            _ => {
+                return Err(ProtocolError::InvalidConfig(
+                    "启用管理 API 时必须设置访问令牌".to_string(),
+                ))
+            }
+        };
+        let listener = crate::net::bind_tcp(SocketAddr::new(self.addr.ip(), port), self.config.ip_mode)?;
+        // 经 API 发起的发送与接收的会话列在一起，事件也发布到同一事件总线
+        let sessions = self.session_manager.lock().await.clone();
+        let client = crate::LocalSendClient::new(self.config.clone())
+            .with_session_manager(sessions)
+            .with_throttle(self.throttle.clone());
+        let mut controller = crate::control::Controller::new(
+            client,
+            self.session_manager.clone(),
+            self.discovery_manager.clone(),
+            self.accept.clone(),
+        );
+        if let Some(audit) = &self.audit {
+            controller = controller.with_audit_log(audit.path());
+        }
+        let api = Arc::new(api::ManagementApi::new(token, controller));
+        let cancel = self.cancel.clone();
+        tokio::spawn(async move {
+            if let Err(e) = api.serve(listener, cancel).await {
+                warn!(error = %e, "管理 API 已停止");
+            }
+        });
+        Ok(())
+    }
+
+    #[cfg(not(feature = "api"))]
+    async fn start_api(&self, port: u16) -> crate::Result<()> {
+        warn!(port, "未启用 api 特性，忽略管理 API 配置");
         Ok(())
     }
 
@@ -50,10 +751,314 @@ impl LocalSendServer {
     }
 }
 
+/// 接收传输流时共享的状态
+#[derive(Debug, Clone)]
+struct StreamReceiver {
+    sessions: Arc<Mutex<SessionManager>>,
+    download_dir: PathBuf,
+    quarantine: Arc<[String]>,
+    events: Option<mpsc::UnboundedSender<ReceiveEvent>>,
+    throttle: Throttle,
+    hash_index: Option<HashIndex>,
+    journal: Option<SessionJournal>,
+    collision: CollisionPolicy,
+    #[cfg(feature = "extract")]
+    extract: Option<crate::ExtractConfig>,
+}
+
+/// 接受传输流，直到服务器停止
+async fn accept_streams(
+    mut listener: Box<dyn TransportListener>,
+    receiver: StreamReceiver,
+    cancel: CancellationToken,
+) {
+    loop {
+        let accepted = tokio::select! {
+            _ = cancel.cancelled() => break,
+            accepted = listener.accept() => accepted,
+        };
+        let (stream, peer) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!(error = %e, "传输监听器已停止");
+                break;
+            }
+        };
+
+        let receiver = receiver.clone();
+        let ip = crate::net::canonical(peer).ip().to_string();
+        tokio::spawn(async move {
+            if let Err(e) = receive_stream(stream, &ip, &receiver).await {
+                warn!(peer = %peer, error = %e, "接收文件流失败");
+            }
+        });
+    }
+}
+
+/// 接收来自 ip 的单个文件流，完成后关闭流作为确认
+///
+/// 读到头部后直到关闭流前持续回写保活字节，见 [`keepalive`]
+#[instrument(skip_all, fields(peer = ip, session_id = field::Empty, file_id = field::Empty))]
+async fn receive_stream(
+    stream: BoxedStream,
+    ip: &str,
+    receiver: &StreamReceiver,
+) -> crate::Result<()> {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let header = transport::read_header(&mut reader).await?;
+    let span = tracing::Span::current();
+    span.record("session_id", header.session_id.as_str());
+    span.record("file_id", header.file_id.as_str());
+    tokio::select! {
+        stored = receive_upload(&mut reader, &header, ip, receiver) => stored?,
+        _ = keepalive::ping(&mut writer) => {}
+    }
+    writer.shutdown().await?;
+    Ok(())
+}
+
+/// 正在接收的文件，释放时清除 [`FileTransfer::receiving`](crate::FileTransfer::receiving)
+struct Receiving {
+    session: FileSession,
+    file_id: String,
+}
+
+impl Receiving {
+    /// 标记文件正在接收，已在接收时返回 409
+    fn start(session: &FileSession, file_id: &str) -> crate::Result<Self> {
+        let mut transfers = session.transfers.lock().unwrap();
+        let transfer = transfers.entry(file_id.to_string()).or_default();
+        if transfer.receiving {
+            warn!(session_id = %session.id, file_id, "同一文件已在接收，拒绝重复上传");
+            return Err(ProtocolError::Status(409));
+        }
+        transfer.receiving = true;
+        Ok(Self {
+            session: session.clone(),
+            file_id: file_id.to_string(),
+        })
+    }
+}
+
+impl Drop for Receiving {
+    fn drop(&mut self) {
+        if let Some(transfer) = self.session.transfers.lock().unwrap().get_mut(&self.file_id) {
+            transfer.receiving = false;
+        }
+    }
+}
+
+/// 本会话此前中断的上传已写入 `.part` 的字节数
+///
+/// `.part` 由会话记录的保存文件名确定，尚未开始接收的文件为 0
+async fn received_len(session: &FileSession, file_id: &str, receiver: &StreamReceiver) -> u64 {
+    let saved = session.transfers.lock().unwrap().get(file_id).and_then(|t| t.saved_name.clone());
+    match saved {
+        Some(name) => {
+            let path = storage::receive_path(&receiver.download_dir, &name, &receiver.quarantine);
+            storage::part_len(&path, &session.id, file_id).await
+        }
+        None => 0,
+    }
+}
+
+/// 接收来自 ip 的文件，HTTP 上传与传输流共用
+///
+/// 写入前按 [`SessionManager::check_token`] 校验头部中的令牌和来源地址，
+/// 同一文件已在接收时返回 409；
+/// 会话的第一个文件开始时进入传输状态，接受的文件全部收到后结束会话
+async fn receive_upload<R>(
+    reader: &mut R,
+    header: &StreamHeader,
+    ip: &str,
+    receiver: &StreamReceiver,
+) -> crate::Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    let sessions = receiver.sessions.lock().await.clone();
+    sessions.check_token(&header.session_id, &header.file_id, &header.token, ip)?;
+    let session = sessions
+        .get_session(&header.session_id)
+        .await
+        .ok_or_else(|| ProtocolError::SessionNotFound(header.session_id.clone()))?;
+    let _receiving = Receiving::start(&session, &header.file_id)?;
+    if *session.state.lock().await == SessionState::Waiting {
+        sessions.set_state(&session.id, SessionState::Transferring).await;
+    }
+
+    store_stream(reader, header, receiver).await?;
+    if sessions.consume_token(&session.id, &header.file_id) {
+        sessions.set_state(&session.id, SessionState::Finished).await;
+    }
+    Ok(())
+}
+
+/// 确定文件的保存路径并记录到会话的传输结果中
+///
+/// 同一文件续传或重传时沿用已记录的文件名。否则下载目录中已有同名文件，
+/// 或从头上传而有其他文件正在写入同名的 `.part` 时，按 [`CollisionPolicy`] 追加序号另存或覆盖。
+/// [`CollisionPolicy::Skip`] 在 prepare-upload 时已跳过已有的同名文件，
+/// 之后才出现的同名文件同样追加序号另存，不覆盖也不中断会话
+async fn saved_path(
+    session: &FileSession,
+    file: &FileInfo,
+    offset: u64,
+    receiver: &StreamReceiver,
+) -> crate::Result<(String, PathBuf)> {
+    let dir = &receiver.download_dir;
+    let path_of = |name: &str| storage::receive_path(dir, name, &receiver.quarantine);
+    let saved = session.transfers.lock().unwrap().get(&file.id).and_then(|t| t.saved_name.clone());
+    if let Some(name) = saved {
+        let path = path_of(&name);
+        return Ok((name, path));
+    }
+
+    // 只保留清理后的文件名，防止路径穿越
+    let name = sanitize_file_name(&file.name)?;
+    let taken = |path: PathBuf| async move {
+        let in_flight = offset == 0 && storage::has_part(&path).await?;
+        Ok::<_, ProtocolError>(in_flight || tokio::fs::try_exists(&path).await?)
+    };
+    let resolved = match receiver.collision {
+        CollisionPolicy::Overwrite => name.clone(),
+        _ if !taken(path_of(&name)).await? => name.clone(),
+        CollisionPolicy::Rename | CollisionPolicy::Skip => {
+            let mut n = 1;
+            while taken(path_of(&numbered_name(&name, n))).await? {
+                n += 1;
+            }
+            let renamed = numbered_name(&name, n);
+            let reason = format!("已有同名文件，另存为 {}", renamed);
+            debug!(file_id = %file.id, %name, %reason, "文件名冲突");
+            session.warnings.lock().await.push(FileWarning::new(&name, reason));
+            renamed
+        }
+    };
+    let path = path_of(&resolved);
+    let mut transfers = session.transfers.lock().unwrap();
+    transfers.entry(file.id.clone()).or_default().saved_name = Some(resolved.clone());
+    Ok((resolved, path))
+}
+
+/// 把文件流的内容写入下载目录
+async fn store_stream<R>(
+    reader: &mut R,
+    header: &StreamHeader,
+    receiver: &StreamReceiver,
+) -> crate::Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    let StreamReceiver {
+        sessions,
+        quarantine,
+        events,
+        throttle,
+        hash_index,
+        journal,
+        ..
+    } = receiver;
+    let session = sessions
+        .lock()
+        .await
+        .get_session(&header.session_id)
+        .await
+        .ok_or_else(|| ProtocolError::SessionNotFound(header.session_id.clone()))?;
+    let file = session
+        .files
+        .iter()
+        .find(|f| f.id == header.file_id && f.size == header.size)
+        .ok_or_else(|| ProtocolError::InvalidData(format!("未协商的文件: {}", header.file_id)))?;
+
+    let (name, path) = saved_path(&session, file, header.offset, receiver).await?;
+
+    debug!(session_id = %session.id, file_id = %file.id, "通过传输接收文件");
+    let mut body = Throttled::new(IdleTimeout::new(reader), throttle.clone());
+    let received = transport::receive_file(&mut body, header, &path, file.sha256.as_deref())
+        .instrument(info_span!("receive_file", size = file.size))
+        .await;
+    if let Some(e) = received.as_ref().err().filter(|e| keepalive::is_timed_out(e)) {
+        warn!(session_id = %session.id, "发送端无响应，会话中止，可稍后续传");
+        let state = SessionState::Error(e.to_string());
+        sessions.lock().await.set_state(&session.id, state).await;
+    }
+    if let Some(journal) = journal {
+        // 中断时记录已写入 .part 的长度，校验失败时 .part 已删除，记为 0
+        let offset = match &received {
+            Ok(_) => file.size,
+            Err(_) => storage::part_len(&path, &session.id, &file.id).await,
+        };
+        journal.offset_or_warn(&session.id, &file.id, offset);
+    }
+    if let Err(e) = &received {
+        session.record_failure(&file.id, e);
+    }
+    received?;
+    if let (Some(index), Some(sha256)) = (hash_index, &file.sha256) {
+        if let Err(e) = index.insert(sha256, &path) {
+            warn!(error = %e, "写入哈希索引失败");
+        }
+    }
+
+    let sessions = sessions.lock().await.clone();
+    if storage::is_quarantined(&name, quarantine) {
+        storage::strip_execute_bits(&path)?;
+        warn!(session_id = %session.id, path = %path.display(), "文件类型有风险，已放入隔离目录");
+        sessions.events().emit(ProtocolEvent::FileQuarantined {
+            session_id: session.id.clone(),
+            path: path.clone(),
+        });
+    } else {
+        #[cfg(feature = "extract")]
+        if let Some(config) = &receiver.extract {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if storage::extract::archive_kind(&name).is_some() {
+                tokio::spawn(storage::extract::extract_received(
+                    session.id.clone(),
+                    path.clone(),
+                    config.clone(),
+                    quarantine.clone(),
+                    sessions.events().clone(),
+                ));
+            }
+        }
+    }
+    sessions.events().emit(ProtocolEvent::FileReceived {
+        session_id: session.id.clone(),
+        path: path.clone(),
+        sha256: file.sha256.clone(),
+    });
+    sessions.report_progress(&session.id, &file.id, file.size).await;
+    if let Some(events) = events {
+        let _ = events.send(ReceiveEvent::FileReceived {
+            session_id: session.id.clone(),
+            path,
+        });
+    }
+    Ok(())
+}
+
 /// 创建设备发现服务
 pub async fn start_discovery(
     _config: LocalSendConfig,
-) -> Result<(), std::io::Error> {
-    println!("设备发现服务已启动");
+) -> crate::Result<()> {
+    info!("设备发现服务已启动");
     Ok(())
 }
+
+/// 便于阅读的文件大小
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", size)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
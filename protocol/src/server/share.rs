@@ -0,0 +1,265 @@
+//! 分享链接
+//!
+//! 为本地文件生成带随机令牌的下载链接 (`/s/<令牌>`)，持有链接即可下载，无需认证。
+//! 链接可设置有效期和下载次数上限，过期或次数用完后失效，并在下次修改列表时清理。
+//!
+//! 链接保存在配置目录的 `shares.json` 中，命令行创建或撤销链接后，
+//! 接收守护进程的分享服务无需重启即可生效
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hyper::{Body, Request, Response};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use super::web;
+use crate::fs::{FileSystemRef, LocalFs};
+use crate::{LocalSendConfig, ProtocolError};
+
+/// 链接文件名
+const SHARES_FILE_NAME: &str = "shares.json";
+
+/// 下载路径前缀
+pub const SHARE_PREFIX: &str = "/s/";
+
+/// 单个分享链接
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareLink {
+    /// 链接令牌，同时作为链接 ID
+    pub token: String,
+    /// 分享的本地文件
+    pub path: PathBuf,
+    /// 创建时间，Unix 秒
+    pub created_at: u64,
+    /// 过期时间，Unix 秒，为空时不过期
+    pub expires_at: Option<u64>,
+    /// 下载次数上限，为空时不限
+    pub max_downloads: Option<u32>,
+    /// 已开始的下载次数
+    pub downloads: u32,
+}
+
+impl ShareLink {
+    /// 在 now 时是否已过期
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|at| unix_secs(now) >= at)
+    }
+
+    /// 下载次数是否已用完
+    pub fn is_exhausted(&self) -> bool {
+        self.max_downloads.is_some_and(|max| self.downloads >= max)
+    }
+
+    /// 下载时使用的文件名
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.token.clone())
+    }
+
+    /// 链接路径，拼接在分享服务地址之后
+    pub fn url_path(&self) -> String {
+        format!("{}{}", SHARE_PREFIX, self.token)
+    }
+}
+
+/// 分享链接存储
+///
+/// 每次操作都重新读取文件，进程间共享同一份链接列表
+#[derive(Debug)]
+pub struct ShareStore {
+    path: PathBuf,
+    lock: std::sync::Mutex<()>,
+    fs: FileSystemRef,
+}
+
+impl ShareStore {
+    /// 使用指定的链接文件
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: std::sync::Mutex::new(()),
+            fs: LocalFs::shared(),
+        }
+    }
+
+    /// 与共享配置文件位于同一目录的链接文件
+    pub fn default_path() -> Option<PathBuf> {
+        Some(LocalSendConfig::default_path()?.with_file_name(SHARES_FILE_NAME))
+    }
+
+    /// 打开默认位置的链接存储
+    pub fn open_default() -> crate::Result<Self> {
+        let path = Self::default_path()
+            .ok_or_else(|| ProtocolError::InvalidConfig("无法确定配置目录".to_string()))?;
+        Ok(Self::new(path))
+    }
+
+    /// 为文件创建分享链接
+    ///
+    /// expires_in 为有效期，max_downloads 为下载次数上限，均为空时链接一直有效
+    pub fn create(
+        &self,
+        file: &Path,
+        expires_in: Option<Duration>,
+        max_downloads: Option<u32>,
+    ) -> crate::Result<ShareLink> {
+        if !file.is_file() {
+            return Err(ProtocolError::InvalidConfig(format!("不是文件: {}", file.display())));
+        }
+        if max_downloads == Some(0) {
+            return Err(ProtocolError::InvalidConfig("下载次数上限必须大于 0".to_string()));
+        }
+
+        let now = SystemTime::now();
+        let link = ShareLink {
+            token: new_token(),
+            path: std::path::absolute(file)?,
+            created_at: unix_secs(now),
+            expires_at: expires_in.map(|d| unix_secs(now + d)),
+            max_downloads,
+            downloads: 0,
+        };
+        self.update(|links| links.push(link.clone()))?;
+        Ok(link)
+    }
+
+    /// 所有仍然有效的链接，同时清理已失效的链接
+    pub fn list(&self) -> crate::Result<Vec<ShareLink>> {
+        self.update(|links| links.clone())
+    }
+
+    /// 撤销链接，令牌不存在时返回 false
+    pub fn revoke(&self, token: &str) -> crate::Result<bool> {
+        self.update(|links| {
+            let before = links.len();
+            links.retain(|link| link.token != token);
+            links.len() != before
+        })
+    }
+
+    /// 登记一次下载，返回链接
+    ///
+    /// 令牌不存在时返回 `Status(404)`，过期或次数用完时返回 `Status(410)`。
+    /// 断点续传 (resume 为 true) 不计次数，但要求此前已开始过下载
+    pub fn claim(&self, token: &str, resume: bool) -> crate::Result<ShareLink> {
+        let _guard = self.lock.lock().unwrap();
+        let mut links = self.load()?;
+        let now = SystemTime::now();
+        let Some(link) = links.iter_mut().find(|link| link.token == token) else {
+            return Err(ProtocolError::Status(404));
+        };
+        if link.is_expired(now) {
+            return Err(ProtocolError::Status(410));
+        }
+
+        if resume {
+            // 最后一次下载中断后仍允许续传
+            if link.downloads == 0 {
+                return Err(ProtocolError::Status(410));
+            }
+            return Ok(link.clone());
+        }
+        if link.is_exhausted() {
+            return Err(ProtocolError::Status(410));
+        }
+        link.downloads += 1;
+        let link = link.clone();
+        self.save(&links)?;
+        Ok(link)
+    }
+
+    /// 在 listener 上提供分享链接下载，直到 cancel 取消
+    pub async fn serve(
+        self: Arc<Self>,
+        listener: std::net::TcpListener,
+        cancel: CancellationToken,
+    ) -> crate::Result<()> {
+        web::serve("share", listener, cancel, move |request| {
+            let store = self.clone();
+            async move { store.handle(request).await }
+        })
+        .await
+    }
+
+    async fn handle(&self, request: Request<Body>) -> crate::Result<Response<Body>> {
+        web::ensure_get(&request)?;
+        let token = request
+            .uri()
+            .path()
+            .strip_prefix(SHARE_PREFIX)
+            .filter(|token| !token.is_empty() && !token.contains('/'))
+            .ok_or(ProtocolError::Status(404))?;
+
+        // 先按令牌取得文件长度，判断是否为续传请求
+        let link = self
+            .load()?
+            .into_iter()
+            .find(|link| link.token == token)
+            .ok_or(ProtocolError::Status(404))?;
+        let Ok(len) = self.fs.file_len(&link.path).await else {
+            return Err(ProtocolError::Status(410));
+        };
+        let resume = web::request_range(&request, len)
+            .ok()
+            .flatten()
+            .is_some_and(|range| range.start > 0);
+
+        let link = self.claim(token, resume)?;
+        web::file_response(&self.fs, &link.path, &link.file_name(), &request).await
+    }
+
+    /// 在锁内读取、修改并保存链接，失效的链接会被移除
+    fn update<T>(&self, f: impl FnOnce(&mut Vec<ShareLink>) -> T) -> crate::Result<T> {
+        let _guard = self.lock.lock().unwrap();
+        let mut links = self.load()?;
+        let now = SystemTime::now();
+        links.retain(|link| !link.is_expired(now) && !link.is_exhausted());
+        let result = f(&mut links);
+        self.save(&links)?;
+        Ok(result)
+    }
+
+    fn load(&self) -> crate::Result<Vec<ShareLink>> {
+        match std::fs::read(&self.path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 先写临时文件再替换，避免其他进程读到写了一半的文件
+    fn save(&self, links: &[ShareLink]) -> crate::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::write(&tmp, serde_json::to_vec_pretty(links)?)?;
+
+        // 令牌即下载凭据，仅允许当前用户读取
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// 随机令牌，两个 UUID v4 共 244 位随机数
+fn new_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
@@ -0,0 +1,431 @@
+//! LocalSend v2 HTTP 接口
+//!
+//! 在接收服务的端口上提供官方 LocalSend 客户端使用的接口：
+//!
+//! | 方法 | 路径 | 说明 |
+//! | --- | --- | --- |
+//! | GET | `/api/localsend/v2/info` | 本机设备信息 |
+//! | POST | `/api/localsend/v2/register` | 对方注册自己，返回本机设备信息 |
+//! | POST | `/api/localsend/v2/prepare-upload?pin=` | 请求发送文件，返回会话 ID 和每个文件的上传令牌 |
+//! | POST | `/api/localsend/v2/upload?sessionId=&fileId=&token=&offset=` | 上传单个文件，请求体为文件内容 |
+//! | GET | `/api/localsend/v2/upload?sessionId=&fileId=&token=` | 文件已接收的字节数 (PeerSend 扩展) |
+//! | POST | `/api/localsend/v2/cancel?sessionId=` | 发送方取消会话 |
+//!
+//! prepare-upload 交给 [`LocalSendServer::prepare_upload`] 处理，没有需要传输的文件时返回 204。
+//! 上传的文件与传输流使用相同的写入流程；会话的第一个文件开始上传时进入传输状态，
+//! 接受的文件全部收到后结束。错误按 LocalSend 的约定返回状态码：请求无效为 400，
+//! 需要 PIN 为 401，被拒绝、令牌或会话无效为 403，过于频繁为 429。
+//! upload 和 cancel 只接受来自发起会话的地址的请求，见 [`crate::SessionManager::check_token`]。
+//!
+//! 上传中断时已收到的内容保留在 `.part` 文件中，令牌仍然有效。发送方可用 GET 查询本会话已接收的
+//! 字节数，再带上 `offset` 上传其余内容，offset 超出已接收的长度时返回 416。
+//! 同一文件正在上传时再次上传返回 409
+//!
+//! 配置 `use_tls` 时以 HTTPS 提供接口，证书见 [`crate::crypto::tls`]，
+//! info 和 register 返回的指纹为证书指纹
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use hyper::body::HttpBody;
+use hyper::header;
+use hyper::server::accept::Accept;
+use hyper::server::conn::{AddrIncoming, AddrStream};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::{receive_upload, LocalSendServer};
+use crate::dto::v2::{
+    InfoDto, PrepareUploadRequestDto, RegisterDto, UploadOffsetDto, CANCEL_PATH, INFO_PATH,
+    PREPARE_UPLOAD_PATH, PROTOCOL_VERSION, REGISTER_PATH, UPLOAD_PATH,
+};
+use crate::transport::StreamHeader;
+use crate::{net, ProtocolError, SessionState};
+
+/// JSON 请求体大小上限，prepare-upload 中可能列出上千个文件
+const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+/// 在 listener 上提供接口，直到 cancel 取消；服务器带有证书时以 HTTPS 提供
+pub(super) async fn serve(
+    server: Arc<LocalSendServer>,
+    listener: std::net::TcpListener,
+    cancel: CancellationToken,
+) -> crate::Result<()> {
+    listener.set_nonblocking(true)?;
+    let addr = listener.local_addr()?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+
+    #[cfg(feature = "tls")]
+    if let Some(identity) = server.tls.clone() {
+        let incoming = tls::incoming(listener, identity.acceptor()?, cancel.clone());
+        info!(%addr, fingerprint = identity.fingerprint(), "LocalSend HTTPS 接口已启动");
+        return run(server, incoming, cancel).await;
+    }
+
+    let incoming = AddrIncoming::from_listener(listener).map_err(std::io::Error::other)?;
+    info!(%addr, "LocalSend HTTP 接口已启动");
+    run(server, incoming, cancel).await
+}
+
+/// 连接的对方地址
+trait RemoteAddr {
+    fn remote_addr(&self) -> SocketAddr;
+}
+
+impl RemoteAddr for AddrStream {
+    fn remote_addr(&self) -> SocketAddr {
+        AddrStream::remote_addr(self)
+    }
+}
+
+async fn run<I>(
+    server: Arc<LocalSendServer>,
+    incoming: I,
+    cancel: CancellationToken,
+) -> crate::Result<()>
+where
+    I: Accept<Error = std::io::Error>,
+    I::Conn: RemoteAddr + AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let make_service = make_service_fn(move |conn: &I::Conn| {
+        let server = server.clone();
+        let ip = net::canonical(conn.remote_addr()).ip().to_string();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let server = server.clone();
+                let ip = ip.clone();
+                async move { Ok::<_, Infallible>(handle(&server, &ip, request).await) }
+            }))
+        }
+    });
+
+    hyper::Server::builder(incoming)
+        .serve(make_service)
+        .with_graceful_shutdown(cancel.cancelled_owned())
+        .await
+        .map_err(std::io::Error::other)?;
+    Ok(())
+}
+
+/// 处理请求，错误转换为对应的状态码
+async fn handle(server: &LocalSendServer, ip: &str, request: Request<Body>) -> Response<Body> {
+    let path = request.uri().path().to_string();
+    match route(server, ip, request).await {
+        Ok(response) => response,
+        Err(e) => {
+            let code = status_of(&e);
+            if code == StatusCode::INTERNAL_SERVER_ERROR {
+                warn!(peer = ip, path, error = %e, "处理请求失败");
+            } else {
+                debug!(peer = ip, path, error = %e, "拒绝请求");
+            }
+            message(code, &e.to_string())
+        }
+    }
+}
+
+async fn route(
+    server: &LocalSendServer,
+    ip: &str,
+    request: Request<Body>,
+) -> crate::Result<Response<Body>> {
+    let method = request.method().clone();
+    match (&method, request.uri().path()) {
+        (&Method::GET | &Method::POST, INFO_PATH) => json(&info(server)),
+        (&Method::POST, REGISTER_PATH) => {
+            let register: RegisterDto = read_json(request.into_body()).await?;
+            let device = register.to_device(ip);
+            let discovery = server.discovery_manager.lock().await;
+            if !discovery.admit(ip, &device.id).await {
+                return Err(ProtocolError::Status(429));
+            }
+            if device.id != server.config.device_id && device.id != server.fingerprint() {
+                discovery.add_device(device).await;
+            }
+            json(&info(server))
+        }
+        (&Method::POST, PREPARE_UPLOAD_PATH) => {
+            let pin = query_param(&request, "pin");
+            let prepare: PrepareUploadRequestDto = read_json(request.into_body()).await?;
+            let response = server.prepare_upload(ip, prepare, pin.as_deref()).await?;
+            if response.files.is_empty() {
+                return Ok(status(StatusCode::NO_CONTENT));
+            }
+            json(&response)
+        }
+        (&Method::POST | &Method::GET, UPLOAD_PATH) => {
+            let (Some(session_id), Some(file_id), Some(token)) = (
+                query_param(&request, "sessionId"),
+                query_param(&request, "fileId"),
+                query_param(&request, "token"),
+            ) else {
+                return Err(ProtocolError::Status(400));
+            };
+            let sessions = server.session_manager.lock().await.clone();
+            sessions.check_token(&session_id, &file_id, &token, ip)?;
+            let received = received_len(server, &session_id, &file_id).await?;
+            if method == Method::GET {
+                return json(&UploadOffsetDto { offset: received });
+            }
+            let offset = match query_param(&request, "offset") {
+                Some(offset) => offset.parse().map_err(|_| ProtocolError::Status(400))?,
+                None => 0,
+            };
+            if offset > received {
+                return Err(ProtocolError::Status(416));
+            }
+            let body = request.into_body();
+            upload(server, ip, &session_id, &file_id, &token, offset, body).await?;
+            Ok(status(StatusCode::OK))
+        }
+        (&Method::POST, CANCEL_PATH) => {
+            let session_id =
+                query_param(&request, "sessionId").ok_or(ProtocolError::Status(400))?;
+            let sessions = server.session_manager.lock().await.clone();
+            sessions.revoke_tokens(&session_id, Some(ip))?;
+            info!(peer = ip, session_id, "发送方取消了会话");
+            sessions.set_state(&session_id, SessionState::Cancelled).await;
+            Ok(status(StatusCode::OK))
+        }
+        (_, INFO_PATH | REGISTER_PATH | PREPARE_UPLOAD_PATH | UPLOAD_PATH | CANCEL_PATH) => {
+            Err(ProtocolError::Status(405))
+        }
+        _ => Err(ProtocolError::Status(404)),
+    }
+}
+
+/// 本会话此前中断的上传已写入 `.part` 的字节数
+async fn received_len(
+    server: &LocalSendServer,
+    session_id: &str,
+    file_id: &str,
+) -> crate::Result<u64> {
+    let sessions = server.session_manager.lock().await.clone();
+    let session = sessions
+        .get_session(session_id)
+        .await
+        .ok_or(ProtocolError::Status(403))?;
+    if !session.files.iter().any(|f| f.id == file_id) {
+        return Err(ProtocolError::Status(403));
+    }
+    Ok(super::received_len(&session, file_id, &server.stream_receiver()).await)
+}
+
+/// 接收来自 ip 的文件，请求体从文件的 offset 处开始，全部文件收到后结束会话
+async fn upload(
+    server: &LocalSendServer,
+    ip: &str,
+    session_id: &str,
+    file_id: &str,
+    token: &str,
+    offset: u64,
+    body: Body,
+) -> crate::Result<()> {
+    let sessions = server.session_manager.lock().await.clone();
+    let session = sessions
+        .get_session(session_id)
+        .await
+        .ok_or(ProtocolError::Status(403))?;
+    let size = session
+        .files
+        .iter()
+        .find(|f| f.id == file_id)
+        .map(|f| f.size)
+        .ok_or(ProtocolError::Status(403))?;
+
+    let header = StreamHeader {
+        session_id: session_id.to_string(),
+        file_id: file_id.to_string(),
+        token: token.to_string(),
+        size,
+        offset,
+        sparse: None,
+    };
+    if offset > 0 {
+        debug!(session_id, file_id, offset, "续传文件");
+    }
+    let mut reader = StreamReader::new(TryStreamExt::map_err(body, std::io::Error::other));
+    receive_upload(&mut reader, &header, ip, &server.stream_receiver()).await
+}
+
+/// 会话接受的文件已全部收到
+/// 本机设备信息，以 HTTPS 提供时指纹为证书指纹，否则为设备 ID
+fn info(server: &LocalSendServer) -> InfoDto {
+    InfoDto {
+        alias: server.config.device_name.clone(),
+        version: PROTOCOL_VERSION.to_string(),
+        device_model: None,
+        device_type: Some(server.config.device_type.clone()),
+        fingerprint: server.fingerprint().to_string(),
+        download: false,
+    }
+}
+
+fn status_of(error: &ProtocolError) -> StatusCode {
+    match error {
+        ProtocolError::Status(code) => {
+            StatusCode::from_u16(*code).unwrap_or(StatusCode::BAD_REQUEST)
+        }
+        ProtocolError::PinRequired => StatusCode::UNAUTHORIZED,
+        ProtocolError::Rejected(_) | ProtocolError::SessionNotFound(_) => StatusCode::FORBIDDEN,
+        ProtocolError::Serialization(_)
+        | ProtocolError::InvalidData(_)
+        | ProtocolError::HashMismatch { .. } => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// 读取 JSON 请求体，超过上限时返回 `Status(413)`
+async fn read_json<T: serde::de::DeserializeOwned>(mut body: Body) -> crate::Result<T> {
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(std::io::Error::other)?;
+        if data.len() + chunk.len() > MAX_BODY_SIZE {
+            return Err(ProtocolError::Status(413));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// 百分号解码后的查询参数
+fn query_param(request: &Request<Body>, name: &str) -> Option<String> {
+    url::form_urlencoded::parse(request.uri().query()?.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn json<T: Serialize>(value: &T) -> crate::Result<Response<Body>> {
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(value)?))
+        .unwrap())
+}
+
+/// 纯文本说明的响应
+fn message(code: StatusCode, text: &str) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Body::from(text.to_string()))
+        .unwrap()
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use std::io;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use hyper::server::accept::{self, Accept};
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+    use tokio_native_tls::{TlsAcceptor, TlsStream};
+    use tokio_stream::wrappers::ReceiverStream;
+    use tokio_util::sync::CancellationToken;
+    use tracing::{debug, warn};
+
+    use super::RemoteAddr;
+
+    /// TLS 握手的超时
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+    /// 已完成握手、等待 hyper 取走的连接数
+    const PENDING_CONNECTIONS: usize = 64;
+
+    /// 已完成 TLS 握手的连接
+    pub(super) struct TlsConn {
+        stream: TlsStream<TcpStream>,
+        remote: SocketAddr,
+    }
+
+    impl RemoteAddr for TlsConn {
+        fn remote_addr(&self) -> SocketAddr {
+            self.remote
+        }
+    }
+
+    impl AsyncRead for TlsConn {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for TlsConn {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.stream).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_shutdown(cx)
+        }
+    }
+
+    /// 接受连接并在后台完成握手，握手慢的连接不阻塞其他连接
+    pub(super) fn incoming(
+        listener: TcpListener,
+        acceptor: TlsAcceptor,
+        cancel: CancellationToken,
+    ) -> impl Accept<Conn = TlsConn, Error = io::Error> {
+        let (tx, rx) = mpsc::channel(PENDING_CONNECTIONS);
+        let acceptor = Arc::new(acceptor);
+        tokio::spawn(async move {
+            loop {
+                let accepted = tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tx.closed() => break,
+                    accepted = listener.accept() => accepted,
+                };
+                let (stream, remote) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        // 例如文件描述符耗尽，稍后重试
+                        warn!(error = %e, "接受连接失败");
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send(Ok(TlsConn { stream, remote })).await;
+                        }
+                        Ok(Err(e)) => debug!(peer = %remote, error = %e, "TLS 握手失败"),
+                        Err(_) => debug!(peer = %remote, "TLS 握手超时"),
+                    }
+                });
+            }
+        });
+        accept::from_stream(ReceiverStream::new(rx))
+    }
+}
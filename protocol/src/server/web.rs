@@ -0,0 +1,150 @@
+//! 浏览页面与分享链接共用的 HTTP 服务
+//!
+//! 基于 hyper 的最小服务框架：处理函数返回 [`crate::Result`]，
+//! `Status` 错误转换为对应状态码，HEAD 请求自动去掉响应体
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+
+use hyper::header::{self, HeaderValue};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use super::body::{parse_range, FileBody};
+use crate::fs::FileSystemRef;
+use crate::ProtocolError;
+
+/// 在 listener 上运行服务，直到 cancel 取消
+pub(crate) async fn serve<H, F>(
+    name: &'static str,
+    listener: std::net::TcpListener,
+    cancel: CancellationToken,
+    handler: H,
+) -> crate::Result<()>
+where
+    H: Fn(Request<Body>) -> F + Send + Sync + 'static,
+    F: Future<Output = crate::Result<Response<Body>>> + Send + 'static,
+{
+    listener.set_nonblocking(true)?;
+    let addr = listener.local_addr()?;
+    let handler = Arc::new(handler);
+    let make_service = make_service_fn(move |_| {
+        let handler = handler.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let head = request.method() == Method::HEAD;
+                let path = request.uri().path().to_string();
+                let response = handler(request);
+                async move {
+                    let mut response = match response.await {
+                        Ok(response) => response,
+                        Err(ProtocolError::Status(code)) => {
+                            status(StatusCode::from_u16(code).unwrap_or(StatusCode::BAD_REQUEST))
+                        }
+                        Err(e) => {
+                            warn!(service = name, path, error = %e, "处理请求失败");
+                            status(StatusCode::INTERNAL_SERVER_ERROR)
+                        }
+                    };
+                    if head {
+                        *response.body_mut() = Body::empty();
+                    }
+                    Ok::<_, Infallible>(response)
+                }
+            }))
+        }
+    });
+
+    info!(service = name, %addr, "HTTP 服务已启动");
+    hyper::Server::from_tcp(listener)
+        .map_err(std::io::Error::other)?
+        .serve(make_service)
+        .with_graceful_shutdown(cancel.cancelled_owned())
+        .await
+        .map_err(std::io::Error::other)?;
+    Ok(())
+}
+
+/// 只含状态说明的响应
+pub(crate) fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::from(
+        code.canonical_reason().unwrap_or_default().to_string(),
+    ));
+    *response.status_mut() = code;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8"));
+    response
+}
+
+/// 只允许 GET 和 HEAD
+pub(crate) fn ensure_get(request: &Request<Body>) -> crate::Result<()> {
+    if request.method() == Method::GET || request.method() == Method::HEAD {
+        Ok(())
+    } else {
+        Err(ProtocolError::Status(405))
+    }
+}
+
+/// 以附件形式返回文件，支持单个 `Range` 区间
+///
+/// 文件不存在时返回 `Status(404)`
+pub(crate) async fn file_response(
+    fs: &FileSystemRef,
+    path: &Path,
+    name: &str,
+    request: &Request<Body>,
+) -> crate::Result<Response<Body>> {
+    let Ok(len) = fs.file_len(path).await else {
+        return Err(ProtocolError::Status(404));
+    };
+    let range = match request_range(request, len) {
+        Ok(range) => range,
+        Err(ProtocolError::Status(416)) => {
+            return Ok(Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", len))
+                .body(Body::empty())
+                .unwrap());
+        }
+        Err(e) => return Err(e),
+    };
+
+    let body = FileBody::open(fs, path, range).await?;
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, body.len())
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename*=UTF-8''{}",
+                utf8_percent_encode(name, NON_ALPHANUMERIC)
+            ),
+        );
+    if let Some(range) = body.range() {
+        response = response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_RANGE, range.content_range(len));
+    }
+    Ok(response.body(Body::wrap_stream(body)).unwrap())
+}
+
+/// 请求的字节区间，无 `Range` 头或无法识别时为空
+pub(crate) fn request_range(
+    request: &Request<Body>,
+    len: u64,
+) -> crate::Result<Option<super::body::ByteRange>> {
+    Ok(request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| parse_range(v, len))
+        .transpose()?
+        .flatten())
+}
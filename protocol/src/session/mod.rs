@@ -2,23 +2,76 @@
 //!
 //! 实现完整的文件发送和接收逻辑
 
+use std::future::Future;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tokio::fs::{File, OpenOptions};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use crate::{FileSession, FileInfo, TransferProgress, SessionState};
+use bytes::{Bytes, BytesMut};
+use tokio::sync::{mpsc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::io::StreamReader;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, instrument};
+use crate::fs::{BoxedReader, FileSystemRef, LocalFs};
+use crate::hash::HashPipeline;
+use crate::storage::names::{numbered_name, sanitize_file_name};
+use crate::storage::{FsStorage, ReceiveStorageRef, StorageKey, DEFAULT_WRITE_BUFFER_SIZE};
+use crate::{CollisionPolicy, FileSession, FileInfo, FileWarning, TransferProgress, SessionState};
 
 /// 块大小 (1MB)
 const BLOCK_SIZE: usize = 1024 * 1024;
 
+/// 广播时每个接收方最多缓冲的块数，最慢的接收方落后更多时暂停读取
+const BROADCAST_BUFFER_BLOCKS: usize = 4;
+
+/// 广播中单个接收方的读取端，见 [`TransferManager::broadcast`]
+pub type BroadcastReader = StreamReader<ReceiverStream<std::io::Result<Bytes>>, Bytes>;
+
+/// 正在读取的文件句柄
+type OpenReader = Arc<Mutex<BoxedReader>>;
+
 /// 文件发送器
-#[derive(Debug, Clone)]
+///
+/// 当前文件在第一次读取时打开，之后的读取沿用同一个句柄，直到换到下一个文件或
+/// 调整续传位置。克隆不带打开的句柄，第一次读取时从自己的位置重新打开
 pub struct FileSender {
     session: FileSession,
     file_index: usize,
+    /// 当前文件中下一次读取的位置
+    file_offset: u64,
     bytes_sent: u64,
     chunk_size: usize,
+    cancel: CancellationToken,
+    fs: FileSystemRef,
+    reader: Option<OpenReader>,
+}
+
+impl Clone for FileSender {
+    fn clone(&self) -> Self {
+        Self {
+            session: self.session.clone(),
+            file_index: self.file_index,
+            file_offset: self.file_offset,
+            bytes_sent: self.bytes_sent,
+            chunk_size: self.chunk_size,
+            cancel: self.cancel.clone(),
+            fs: self.fs.clone(),
+            reader: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for FileSender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileSender")
+            .field("session", &self.session)
+            .field("file_index", &self.file_index)
+            .field("file_offset", &self.file_offset)
+            .field("bytes_sent", &self.bytes_sent)
+            .field("chunk_size", &self.chunk_size)
+            .field("fs", &self.fs)
+            .finish_non_exhaustive()
+    }
 }
 
 impl FileSender {
@@ -27,11 +80,33 @@ impl FileSender {
         Self {
             session,
             file_index: 0,
+            file_offset: 0,
             bytes_sent: 0,
             chunk_size: BLOCK_SIZE,
+            cancel: CancellationToken::new(),
+            fs: LocalFs::shared(),
+            reader: None,
         }
     }
 
+    /// 使用指定的文件系统读取文件
+    pub fn with_fs(mut self, fs: FileSystemRef) -> Self {
+        self.fs = fs;
+        self.reader = None;
+        self
+    }
+
+    /// 使用外部的取消令牌
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 取消发送
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
     /// 获取当前文件信息
     pub fn current_file_info(&self) -> Option<&FileInfo> {
         self.session.files.get(self.file_index)
@@ -57,34 +132,74 @@ impl FileSender {
         self.file_index >= self.session.files.len()
     }
 
-    /// 跳到下一个文件
+    /// 跳到下一个文件，关闭当前文件
     pub fn next_file(&mut self) -> bool {
         self.file_index += 1;
+        self.file_offset = 0;
+        self.reader = None;
         !self.is_complete()
     }
 
-    /// 读取文件数据块
-    pub async fn read_chunk(&mut self) -> Result<Option<Vec<u8>>, std::io::Error> {
-        if let Some(file_info) = self.current_file_info() {
-            let path = PathBuf::from(&file_info.name);
+    /// 从当前文件的 offset 处继续发送，offset 通常为接收方已收到的字节数
+    ///
+    /// 跳过的部分计入已发送的字节数
+    pub fn resume_from(&mut self, offset: u64) -> crate::Result<()> {
+        let size = self.current_file_info().map_or(0, |f| f.size);
+        if offset > size {
+            return Err(crate::ProtocolError::InvalidData(format!("续传偏移超出文件长度: {}", offset)));
+        }
+        self.bytes_sent = self.bytes_sent - self.file_offset + offset;
+        self.file_offset = offset;
+        // 下一次读取时从新的位置重新打开
+        self.reader = None;
+        Ok(())
+    }
 
-            match File::open(&path).await {
-                Ok(mut file) => {
-                    let mut buffer = vec![0u8; self.chunk_size];
-                    match file.read(&mut buffer).await {
-                        Ok(n) => {
-                            buffer.truncate(n);
-                            self.bytes_sent += n as u64;
-                            Ok(Some(buffer))
-                        }
-                        Err(e) => Err(e),
-                    }
-                }
-                Err(e) => Err(e),
+    /// 读取当前文件的下一个数据块，所有文件都已发送时返回 None
+    ///
+    /// 数据块最长为块大小，不超过文件声明的大小；当前文件读完后返回空的数据块，
+    /// 需调用 [`next_file`](Self::next_file) 换到下一个文件。
+    /// 文件比声明的大小短时返回 [`std::io::ErrorKind::UnexpectedEof`]，不会发送截断的内容
+    #[instrument(skip(self), fields(session_id = %self.session.id, file_index = self.file_index))]
+    pub async fn read_chunk(&mut self) -> crate::Result<Option<Vec<u8>>> {
+        if self.cancel.is_cancelled() {
+            return Err(crate::ProtocolError::Cancelled);
+        }
+
+        let Some(file_info) = self.current_file_info() else {
+            return Ok(None);
+        };
+        let path = PathBuf::from(&file_info.name);
+        let size = file_info.size;
+        let len = size
+            .saturating_sub(self.file_offset)
+            .min(self.chunk_size as u64);
+        if len == 0 {
+            return Ok(Some(Vec::new()));
+        }
+
+        let reader = match &self.reader {
+            Some(reader) => reader.clone(),
+            None => {
+                let file = self.fs.open_read_at(&path, self.file_offset).await?;
+                self.reader.insert(Arc::new(Mutex::new(file))).clone()
             }
-        } else {
-            Ok(None)
+        };
+
+        let mut buffer = Vec::with_capacity(len as usize);
+        let read = (&mut *reader.lock().await).take(len).read_to_end(&mut buffer).await;
+        if let Err(e) = read {
+            self.reader = None;
+            return Err(e.into());
+        }
+        if (buffer.len() as u64) < len {
+            self.reader = None;
+            let message = format!("文件比声明的 {} 字节短: {}", size, path.display());
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, message).into());
         }
+        self.file_offset += buffer.len() as u64;
+        self.bytes_sent += buffer.len() as u64;
+        Ok(Some(buffer))
     }
 
     /// 获取当前进度
@@ -101,16 +216,33 @@ impl FileSender {
     pub fn get_offset(&self) -> u64 {
         self.bytes_sent
     }
+
+    /// 当前文件中已读取的字节数
+    pub fn file_offset(&self) -> u64 {
+        self.file_offset
+    }
 }
 
 /// 文件接收器
+///
+/// 文件声明了 SHA-256 时，写入的数据块同时送入 [`HashPipeline`]，完成文件时校验。
+/// 克隆与存储一样共享进行中的哈希计算。
+///
+/// 已有同名文件时按 [`CollisionPolicy`] 处理，实际保存的文件名记录在会话的
+/// `transfers` 中，重命名或跳过的文件同时记入会话的 `warnings`
 #[derive(Debug, Clone)]
 pub struct FileReceiver {
     session: FileSession,
     output_dir: PathBuf,
     file_index: usize,
     bytes_received: u64,
-    current_file: Option<PathBuf>,
+    current_file: Option<StorageKey>,
+    hasher: Option<Arc<HashPipeline>>,
+    cancel: CancellationToken,
+    fs: FileSystemRef,
+    buffer_size: usize,
+    storage: ReceiveStorageRef,
+    collision: CollisionPolicy,
 }
 
 impl FileReceiver {
@@ -118,54 +250,238 @@ impl FileReceiver {
     pub fn new(session: FileSession, output_dir: PathBuf) -> Self {
         Self {
             session,
+            storage: Arc::new(FsStorage::new(output_dir.clone())),
             output_dir,
             file_index: 0,
             bytes_received: 0,
             current_file: None,
+            hasher: None,
+            cancel: CancellationToken::new(),
+            fs: LocalFs::shared(),
+            buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            collision: CollisionPolicy::default(),
         }
     }
 
+    /// 使用指定的文件系统写入下载目录
+    pub fn with_fs(mut self, fs: FileSystemRef) -> Self {
+        self.fs = fs;
+        self.storage = self.fs_storage();
+        self
+    }
+
+    /// 设置写入下载目录时的缓冲区大小
+    pub fn with_buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size;
+        self.storage = self.fs_storage();
+        self
+    }
+
+    fn fs_storage(&self) -> ReceiveStorageRef {
+        Arc::new(
+            FsStorage::with_fs(self.output_dir.clone(), self.fs.clone())
+                .with_buffer_size(self.buffer_size),
+        )
+    }
+
+    /// 使用指定的存储后端保存文件，下载目录将被忽略
+    pub fn with_storage(mut self, storage: ReceiveStorageRef) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// 设置已有同名文件时的处理方式，默认追加序号另存
+    pub fn with_collision_policy(mut self, policy: CollisionPolicy) -> Self {
+        self.collision = policy;
+        self
+    }
+
+    /// 使用外部的取消令牌
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 取消接收
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
     /// 获取当前文件信息
     pub fn current_file_info(&self) -> Option<&FileInfo> {
         self.session.files.get(self.file_index)
     }
 
     /// 获取保存路径
-    pub fn get_save_path(&self, filename: &str) -> PathBuf {
-        self.output_dir.join(filename)
+    ///
+    /// 文件名由发送方提供，经 [`sanitize_file_name`] 清理，不会指向下载目录之外
+    pub fn get_save_path(&self, filename: &str) -> crate::Result<PathBuf> {
+        Ok(self.output_dir.join(sanitize_file_name(filename)?))
     }
 
-    /// 开始接收新文件
-    pub async fn start_file(&mut self, filename: &str) -> Result<(), std::io::Error> {
-        let save_path = self.get_save_path(filename);
+    /// 当前文件在存储中的标识，文件名已清理
+    fn storage_key(&self, filename: &str) -> crate::Result<StorageKey> {
+        Ok(StorageKey {
+            session_id: self.session.id.clone(),
+            file_id: self
+                .current_file_info()
+                .map(|f| f.id.clone())
+                .unwrap_or_default(),
+            name: sanitize_file_name(filename)?,
+        })
+    }
+
+    /// 此前为当前文件选定的存储标识，续传时沿用重命名后的文件名
+    fn saved_key(&self, filename: &str) -> crate::Result<StorageKey> {
+        let mut key = self.storage_key(filename)?;
+        let transfers = self.session.transfers.lock().unwrap();
+        if let Some(saved) = transfers.get(&key.file_id).and_then(|t| t.saved_name.clone()) {
+            key.name = saved;
+        }
+        Ok(key)
+    }
+
+    /// 按 [`CollisionPolicy`] 确定保存的文件名并记录到会话中，跳过时返回 None
+    async fn resolve_collision(&self, key: StorageKey) -> crate::Result<Option<StorageKey>> {
+        let exists =
+            self.collision != CollisionPolicy::Overwrite && self.storage.exists(&key).await?;
+        let resolved = match self.collision {
+            _ if !exists => Some(key.clone()),
+            CollisionPolicy::Skip => None,
+            _ => {
+                let mut renamed = key.clone();
+                for n in 1.. {
+                    renamed.name = numbered_name(&key.name, n);
+                    if !self.storage.exists(&renamed).await? {
+                        break;
+                    }
+                }
+                Some(renamed)
+            }
+        };
 
-        // 确保目录存在
-        if let Some(parent) = save_path.parent() {
-            tokio::fs::create_dir_all(parent).await?;
+        {
+            let mut transfers = self.session.transfers.lock().unwrap();
+            let transfer = transfers.entry(key.file_id.clone()).or_default();
+            transfer.saved_name = resolved.as_ref().map(|k| k.name.clone());
+            transfer.skipped = resolved.is_none();
         }
+        let reason = match &resolved {
+            None => "已有同名文件，已跳过".to_string(),
+            Some(saved) if saved.name != key.name => format!("已有同名文件，另存为 {}", saved.name),
+            Some(_) => return Ok(resolved),
+        };
+        debug!(file_id = %key.file_id, name = %key.name, %reason, "文件名冲突");
+        self.session.warnings.lock().await.push(FileWarning::new(&key.name, reason));
+        Ok(resolved)
+    }
 
-        let file = File::create(&save_path).await?;
-        self.current_file = Some(save_path);
+    /// 开始接收新文件
+    ///
+    /// 已有同名文件且策略为 [`CollisionPolicy::Skip`] 时不保存该文件，之后写入的数据被丢弃
+    #[instrument(skip(self), fields(session_id = %self.session.id))]
+    pub async fn start_file(&mut self, filename: &str) -> crate::Result<()> {
+        let key = self.storage_key(filename)?;
+        let Some(key) = self.resolve_collision(key).await? else {
+            self.hasher = None;
+            self.current_file = None;
+            return Ok(());
+        };
+
+        self.storage.open(&key).await?;
+        debug!(file_id = %key.file_id, name = %key.name, "开始接收文件");
+        self.hasher = self
+            .current_file_info()
+            .and_then(|f| f.sha256.as_ref())
+            .map(|_| Arc::new(HashPipeline::new()));
+        self.current_file = Some(key);
         Ok(())
     }
 
+    /// 当前文件此前中断时已写入的字节数，发送方从此处续传
+    pub async fn received_len(&self, filename: &str) -> crate::Result<u64> {
+        self.storage.received_len(&self.saved_key(filename)?).await
+    }
+
+    /// 从 offset 处继续接收此前中断的当前文件，offset 需等于 [`received_len`](Self::received_len)
+    ///
+    /// 已写入的内容无法重新送入哈希流水线，续传的文件不校验声明的 SHA-256
+    #[instrument(skip(self), fields(session_id = %self.session.id))]
+    pub async fn resume_file(&mut self, filename: &str, offset: u64) -> crate::Result<()> {
+        if offset == 0 {
+            return self.start_file(filename).await;
+        }
+        let key = self.saved_key(filename)?;
+        self.storage.resume(&key, offset).await?;
+        debug!(file_id = %key.file_id, name = %key.name, offset, "续传文件");
+        self.hasher = None;
+        self.bytes_received += offset;
+        self.current_file = Some(key);
+        Ok(())
+    }
+
+    /// 当前文件保存时使用的文件名，跳过或尚未开始时为 None
+    pub fn saved_name(&self) -> Option<&str> {
+        self.current_file.as_ref().map(|k| k.name.as_str())
+    }
+
     /// 写入数据块
-    pub async fn write_chunk(&mut self, data: &[u8]) -> Result<(), std::io::Error> {
-        if let Some(path) = &self.current_file {
-            let mut file = OpenOptions::new()
-                .append(true)
-                .open(path)
-                .await?;
-            file.write_all(data).await?;
+    pub async fn write_chunk(&mut self, data: &[u8]) -> crate::Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(crate::ProtocolError::Cancelled);
+        }
+
+        if let Some(key) = &self.current_file {
+            // 先送入哈希流水线，计算与写入同时进行
+            if let Some(hasher) = &self.hasher {
+                hasher.update(data).await?;
+            }
+            self.storage.write_chunk(key, data).await?;
             self.bytes_received += data.len() as u64;
         }
         Ok(())
     }
 
     /// 完成当前文件
-    pub async fn finish_current_file(&mut self) {
-        self.current_file = None;
+    ///
+    /// 哈希不一致时放弃该文件并返回 [`crate::ProtocolError::HashMismatch`]
+    pub async fn finish_current_file(&mut self) -> crate::Result<()> {
+        if let Some(key) = self.current_file.take() {
+            let expected = self.current_file_info().and_then(|f| f.sha256.clone());
+            if let (Some(hasher), Some(expected)) = (self.hasher.take(), expected) {
+                let hasher = Arc::try_unwrap(hasher).map_err(|_| {
+                    crate::ProtocolError::InvalidData("文件仍在其他接收器中写入".to_string())
+                })?;
+                if let Err(e) = hasher.verify(&expected, &key.file_id).await {
+                    self.storage.abort(&key).await?;
+                    return Err(e);
+                }
+            }
+            let location = self.storage.finalize(&key).await?;
+            debug!(session_id = %self.session.id, file_id = %key.file_id, %location, "文件接收完成");
+        }
         self.file_index += 1;
+        Ok(())
+    }
+
+    /// 放弃当前文件，清理已写入的数据
+    pub async fn abort_current_file(&mut self) -> crate::Result<()> {
+        self.hasher = None;
+        if let Some(key) = self.current_file.take() {
+            self.storage.abort(&key).await?;
+            debug!(session_id = %self.session.id, file_id = %key.file_id, "文件接收已放弃");
+        }
+        Ok(())
+    }
+
+    /// 中断当前文件，保留已写入的数据，之后可用 [`resume_file`](Self::resume_file) 续传
+    pub async fn suspend_current_file(&mut self) -> crate::Result<()> {
+        self.hasher = None;
+        if let Some(key) = self.current_file.take() {
+            self.storage.suspend(&key).await?;
+            debug!(session_id = %self.session.id, file_id = %key.file_id, "文件接收已中断");
+        }
+        Ok(())
     }
 
     /// 检查是否完成
@@ -271,4 +587,57 @@ impl TransferManager {
         let mut senders = self.senders.lock().await;
         senders.retain(|s| s.session.id != session_id);
     }
+
+    /// 把 reader 的内容分发给 recipients 个读取端，每块只读取一次
+    ///
+    /// 返回的 future 负责读取并分发，需与各读取端的上传同时运行，结束时返回读取的字节数。
+    /// 每个读取端最多缓冲 [`BROADCAST_BUFFER_BLOCKS`] 块，读取速度受最慢的接收方限制；
+    /// 丢弃的读取端不再接收数据，全部丢弃时提前结束。读取出错时每个读取端都会收到该错误
+    pub fn broadcast<R>(
+        reader: R,
+        recipients: usize,
+    ) -> (impl Future<Output = std::io::Result<u64>>, Vec<BroadcastReader>)
+    where
+        R: AsyncRead + Unpin,
+    {
+        let (senders, readers): (Vec<_>, Vec<_>) = (0..recipients)
+            .map(|_| {
+                let (tx, rx) = mpsc::channel(BROADCAST_BUFFER_BLOCKS);
+                (tx, StreamReader::new(ReceiverStream::new(rx)))
+            })
+            .unzip();
+
+        let pump = async move {
+            let mut reader = reader;
+            let mut senders = senders;
+            let mut total = 0u64;
+            while !senders.is_empty() {
+                let mut block = BytesMut::with_capacity(BLOCK_SIZE);
+                let read = match reader.read_buf(&mut block).await {
+                    Ok(read) => read,
+                    Err(e) => {
+                        for sender in &senders {
+                            let error = std::io::Error::new(e.kind(), e.to_string());
+                            let _ = sender.send(Err(error)).await;
+                        }
+                        return Err(e);
+                    }
+                };
+                if read == 0 {
+                    break;
+                }
+                total += read as u64;
+                let block = block.freeze();
+                let mut open = Vec::with_capacity(senders.len());
+                for sender in senders {
+                    if sender.send(Ok(block.clone())).await.is_ok() {
+                        open.push(sender);
+                    }
+                }
+                senders = open;
+            }
+            Ok(total)
+        };
+        (pump, readers)
+    }
 }
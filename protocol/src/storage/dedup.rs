@@ -0,0 +1,154 @@
+//! 已接收文件的哈希索引
+//!
+//! 声明了 SHA-256 的文件校验通过后，把哈希和保存路径追加到索引。
+//! 之后收到声明相同哈希的文件时，可按 [`DuplicatePolicy`] 跳过或链接到已有文件，
+//! 不必重新传输。
+//!
+//! 索引是只追加的 JSONL 文件，同一哈希以最后一条为准。
+//! 文件被移动、删除或修改后条目不再有效，查找时按大小核对并忽略失效的条目
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DuplicatePolicy, LocalSendConfig, ProtocolError};
+
+/// 索引文件名，与共享配置文件位于同一目录
+const INDEX_FILE_NAME: &str = "hashes.jsonl";
+
+/// 索引中的一行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct IndexEntry {
+    sha256: String,
+    path: PathBuf,
+}
+
+/// 哈希索引
+///
+/// 克隆后共享同一份内存索引和文件
+#[derive(Debug, Clone)]
+pub struct HashIndex {
+    path: PathBuf,
+    entries: Arc<Mutex<HashMap<String, PathBuf>>>,
+}
+
+impl HashIndex {
+    /// 打开索引，文件不存在时在首次写入时创建
+    pub fn open(path: impl Into<PathBuf>) -> crate::Result<Self> {
+        let path = path.into();
+        let mut entries = HashMap::new();
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let entry: IndexEntry = serde_json::from_str(&line)?;
+                    entries.insert(entry.sha256, entry.path);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(Self {
+            path,
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    /// 与共享配置文件位于同一目录的索引文件
+    pub fn default_path() -> Option<PathBuf> {
+        Some(LocalSendConfig::default_path()?.with_file_name(INDEX_FILE_NAME))
+    }
+
+    /// 打开默认位置的索引
+    pub fn open_default() -> crate::Result<Self> {
+        let path = Self::default_path()
+            .ok_or_else(|| ProtocolError::InvalidConfig("无法确定配置目录".to_string()))?;
+        Self::open(path)
+    }
+
+    /// 索引文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 查找内容为 sha256 的已接收文件，文件已不存在或大小不符时返回 None
+    pub fn find(&self, sha256: &str, size: u64) -> Option<PathBuf> {
+        let path = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(&sha256.to_ascii_lowercase())
+            .cloned()?;
+        let metadata = std::fs::metadata(&path).ok()?;
+        (metadata.is_file() && metadata.len() == size).then_some(path)
+    }
+
+    /// 记录已校验的文件
+    pub fn insert(&self, sha256: &str, path: &Path) -> crate::Result<()> {
+        let entry = IndexEntry {
+            sha256: sha256.to_ascii_lowercase(),
+            path: path.to_path_buf(),
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())?;
+        entries.insert(entry.sha256, entry.path);
+        Ok(())
+    }
+}
+
+/// 按策略处理与已有文件相同的文件，返回是否无需再传输
+///
+/// [`DuplicatePolicy::Link`] 在 target 处创建指向 existing 的硬链接，
+/// 不支持硬链接 (例如跨文件系统) 时复制。target 即 existing 时什么也不做
+pub fn resolve_duplicate(
+    policy: DuplicatePolicy,
+    existing: &Path,
+    target: &Path,
+) -> std::io::Result<bool> {
+    match policy {
+        DuplicatePolicy::Duplicate => Ok(false),
+        DuplicatePolicy::Skip => Ok(true),
+        DuplicatePolicy::Link => {
+            if same_file(existing, target) {
+                return Ok(true);
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            // 与正常接收一样覆盖同名文件
+            match std::fs::remove_file(target) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            if std::fs::hard_link(existing, target).is_err() {
+                std::fs::copy(existing, target)?;
+            }
+            Ok(true)
+        }
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
@@ -0,0 +1,267 @@
+//! 自动解压收到的压缩包
+//!
+//! 收到 zip、tar.gz 压缩包后解压到同目录下以压缩包命名的文件夹，已存在时加序号。
+//! 条目路径只保留普通路径段，绝对路径和 `..` 直接拒绝；符号链接、硬链接和
+//! 设备文件等特殊条目跳过，也不保留权限位；扩展名在隔离列表中的文件不解压。
+//! 解压出的总大小和条目数量有上限，按实际写入的字节计数，不依赖压缩包中声明的大小。
+//!
+//! 先解压到临时目录，全部成功后才改为正式目录名，失败时删除临时目录
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::{info, warn};
+
+use super::is_quarantined;
+use crate::{EventBus, ExtractConfig, ProtocolError, ProtocolEvent};
+
+/// 压缩包格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    TarGz,
+}
+
+/// 按文件名识别压缩包格式，大小写不敏感，不是支持的格式时返回 None
+pub fn archive_kind(name: &str) -> Option<ArchiveKind> {
+    let name = name.to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else {
+        None
+    }
+}
+
+/// 压缩包的解压目录：同目录下去掉扩展名的文件夹，已存在时追加 ` (1)`、` (2)` 等
+pub fn extract_dir(archive: &Path) -> PathBuf {
+    let name = archive
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let lower = name.to_ascii_lowercase();
+    let stem = [".tar.gz", ".tgz", ".zip"]
+        .iter()
+        .find(|ext| lower.ends_with(*ext) && lower.len() > ext.len())
+        .map_or(name.as_str(), |ext| &name[..name.len() - ext.len()]);
+    let parent = archive.parent().unwrap_or(Path::new(""));
+    let mut dir = parent.join(stem);
+    let mut n = 1;
+    while dir.exists() {
+        dir = parent.join(format!("{} ({})", stem, n));
+        n += 1;
+    }
+    dir
+}
+
+/// 把压缩包条目路径转为相对路径，包含绝对路径、`..` 或盘符时返回 None
+pub fn entry_path(name: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+/// 解压压缩包到 [`extract_dir`]，返回解压目录
+///
+/// 扩展名在 quarantine 中的条目不解压。
+/// 每解压一个条目调用一次 progress，参数为条目路径和已处理的条目数。
+/// 条目路径不安全或超出大小、数量上限时返回 [`ProtocolError::Rejected`]，
+/// 不会留下解压了一半的目录
+pub fn extract(
+    archive: &Path,
+    config: &ExtractConfig,
+    quarantine: &[String],
+    mut progress: impl FnMut(&Path, usize),
+) -> crate::Result<PathBuf> {
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let kind = archive_kind(&name)
+        .ok_or_else(|| ProtocolError::InvalidData(format!("不支持的压缩包格式: {}", name)))?;
+    let dir = extract_dir(archive);
+    let staging = dir.with_file_name(format!(
+        ".{}.extracting",
+        dir.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+
+    let mut extractor = Extractor {
+        root: &staging,
+        config,
+        quarantine,
+        entries: 0,
+        written: 0,
+    };
+    let result = match kind {
+        ArchiveKind::Zip => extractor.zip(archive, &mut progress),
+        ArchiveKind::TarGz => extractor.tar_gz(archive, &mut progress),
+    };
+    if let Err(e) = result {
+        if let Err(e) = fs::remove_dir_all(&staging) {
+            warn!(dir = %staging.display(), error = %e, "删除解压临时目录失败");
+        }
+        return Err(e);
+    }
+    fs::rename(&staging, &dir)?;
+    Ok(dir)
+}
+
+/// 在后台线程解压收到的压缩包，并在事件总线上发布进度
+///
+/// 失败只记录日志，压缩包本身保留；解压成功且配置了删除时删除压缩包
+pub async fn extract_received(
+    session_id: String,
+    archive: PathBuf,
+    config: ExtractConfig,
+    quarantine: Arc<[String]>,
+    events: EventBus,
+) {
+    let path = archive.clone();
+    let task = tokio::task::spawn_blocking(move || {
+        extract(&path, &config, &quarantine, |entry, extracted| {
+            events.emit(ProtocolEvent::ArchiveEntryExtracted {
+                session_id: session_id.clone(),
+                archive: path.clone(),
+                entry: entry.to_string_lossy().into_owned(),
+                extracted,
+            });
+        })
+        .map(|dir| {
+            info!(archive = %path.display(), dir = %dir.display(), "已解压收到的压缩包");
+            if config.delete_archive {
+                if let Err(e) = fs::remove_file(&path) {
+                    warn!(archive = %path.display(), error = %e, "删除已解压的压缩包失败");
+                }
+            }
+            events.emit(ProtocolEvent::ArchiveExtracted {
+                session_id,
+                archive: path.clone(),
+                dir,
+            });
+        })
+    });
+    match task.await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!(archive = %archive.display(), error = %e, "解压收到的压缩包失败"),
+        Err(e) => warn!(archive = %archive.display(), error = %e, "解压任务异常退出"),
+    }
+}
+
+/// 单次解压的状态
+struct Extractor<'a> {
+    root: &'a Path,
+    config: &'a ExtractConfig,
+    quarantine: &'a [String],
+    entries: usize,
+    written: u64,
+}
+
+impl Extractor<'_> {
+    fn zip(
+        &mut self,
+        archive: &Path,
+        progress: &mut impl FnMut(&Path, usize),
+    ) -> crate::Result<()> {
+        let mut zip = zip::ZipArchive::new(File::open(archive)?).map_err(invalid_archive)?;
+        for i in 0..zip.len() {
+            let mut entry = zip.by_index(i).map_err(invalid_archive)?;
+            let path = self.entry(Path::new(entry.name()))?;
+            if entry.is_dir() {
+                fs::create_dir_all(self.root.join(&path))?;
+            } else if entry.is_file() {
+                if !self.write(&mut entry, &path)? {
+                    continue;
+                }
+            } else {
+                warn!(entry = %path.display(), "跳过压缩包中的特殊条目");
+                continue;
+            }
+            progress(&path, self.entries);
+        }
+        Ok(())
+    }
+
+    fn tar_gz(
+        &mut self,
+        archive: &Path,
+        progress: &mut impl FnMut(&Path, usize),
+    ) -> crate::Result<()> {
+        let gz = flate2::read::GzDecoder::new(File::open(archive)?);
+        let mut tar = tar::Archive::new(gz);
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+            let kind = entry.header().entry_type();
+            if kind.is_pax_global_extensions() || kind.is_pax_local_extensions() {
+                continue;
+            }
+            let path = self.entry(&entry.path()?)?;
+            if kind.is_dir() {
+                fs::create_dir_all(self.root.join(&path))?;
+            } else if kind.is_file() {
+                if !self.write(&mut entry, &path)? {
+                    continue;
+                }
+            } else {
+                warn!(entry = %path.display(), "跳过压缩包中的特殊条目");
+                continue;
+            }
+            progress(&path, self.entries);
+        }
+        Ok(())
+    }
+
+    /// 检查条目路径和数量上限，返回相对解压目录的路径
+    fn entry(&mut self, name: &Path) -> crate::Result<PathBuf> {
+        let path = entry_path(name).ok_or_else(|| {
+            ProtocolError::Rejected(format!("压缩包中有不安全的路径: {}", name.display()))
+        })?;
+        self.entries += 1;
+        if self.entries > self.config.max_entries {
+            return Err(ProtocolError::Rejected(format!(
+                "压缩包条目超过 {} 个",
+                self.config.max_entries
+            )));
+        }
+        Ok(path)
+    }
+
+    /// 写入文件条目，按实际写入的字节检查大小上限，返回是否写入
+    ///
+    /// 扩展名在隔离列表中的条目不解压，只留在压缩包中
+    fn write(&mut self, reader: &mut impl Read, path: &Path) -> crate::Result<bool> {
+        if is_quarantined(&path.to_string_lossy(), self.quarantine) {
+            warn!(entry = %path.display(), "压缩包中的文件类型有风险，不解压");
+            return Ok(false);
+        }
+        let target = self.root.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(&target)?;
+        let remaining = self.config.max_size - self.written;
+        // 多读一个字节，用于判断是否超出上限
+        let copied = io::copy(&mut reader.take(remaining + 1), &mut file)?;
+        if copied > remaining {
+            return Err(ProtocolError::Rejected(format!(
+                "解压后的大小超过 {} 字节",
+                self.config.max_size
+            )));
+        }
+        self.written += copied;
+        Ok(true)
+    }
+}
+
+fn invalid_archive(e: zip::result::ZipError) -> ProtocolError {
+    ProtocolError::InvalidData(format!("无效的 zip 压缩包: {}", e))
+}
@@ -0,0 +1,53 @@
+//! 符号链接的传输
+//!
+//! [`SymlinkPolicy::Preserve`](crate::SymlinkPolicy::Preserve) 时，PeerSend 之间发送的
+//! 符号链接不传输内容，而是作为大小为 0 的文件，在元数据的 `symlink` 字段中带上链接目标。
+//! 接收端不为其分配上传令牌，直接在下载目录中重建链接。
+//!
+//! 只重建指向同一目录或其子目录的相对链接，绝对路径或含 `..` 的目标可能指向
+//! 下载目录之外，不予重建
+
+use std::io;
+use std::path::{Component, Path};
+
+use crate::FileInfo;
+
+/// 元数据中链接目标的字段名
+pub const SYMLINK_KEY: &str = "symlink";
+
+/// 链接条目的文件类型
+pub const SYMLINK_TYPE: &str = "inode/symlink";
+
+/// 带有链接目标的元数据
+pub fn link_metadata(target: &str) -> serde_json::Value {
+    serde_json::json!({ SYMLINK_KEY: target })
+}
+
+/// 文件条目的链接目标，不是链接时返回 None
+pub fn link_target(file: &FileInfo) -> Option<&str> {
+    file.metadata.as_ref()?.get(SYMLINK_KEY)?.as_str()
+}
+
+/// 链接目标是否停留在链接所在目录之内
+pub fn is_contained(target: &str) -> bool {
+    let path = Path::new(target);
+    !target.is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// 在 path 处创建指向 target 的符号链接，path 已存在时失败
+///
+/// Windows 上创建符号链接需要特权，不支持
+pub fn create_link(path: &Path, target: &str) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, path)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, target);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "此平台不支持重建符号链接"))
+    }
+}
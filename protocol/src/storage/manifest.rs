@@ -0,0 +1,138 @@
+//! 会话完成后写入的哈希清单
+//!
+//! 启用 `hash_manifest` 时，每个接收的会话完成后在下载目录写入
+//! `SHA256SUMS-<会话 ID 前 8 位>`，格式与 `sha256sum` 的输出相同，
+//! 在下载目录中执行 `sha256sum -c SHA256SUMS-xxxxxxxx` 即可校验收到的文件。
+//!
+//! 哈希按写入磁盘的内容计算，路径相对于下载目录；写清单时已被移走或删除的文件不列出
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::{LocalSendConfig, ProtocolEvent, SessionState};
+
+/// 清单文件名的前缀
+pub const MANIFEST_PREFIX: &str = "SHA256SUMS-";
+
+/// 会话的清单路径
+pub fn manifest_path(download_dir: &Path, session_id: &str) -> PathBuf {
+    let id: String = session_id.chars().take(8).collect();
+    download_dir.join(format!("{}{}", MANIFEST_PREFIX, id))
+}
+
+/// `sha256sum` 格式的一行
+///
+/// 路径中有换行或反斜杠时与 `sha256sum` 相同，转义后在行首加反斜杠
+pub fn manifest_line(sha256: &str, path: &str) -> String {
+    if path.contains(['\n', '\\']) {
+        let path = path.replace('\\', "\\\\").replace('\n', "\\n");
+        format!("\\{}  {}\n", sha256, path)
+    } else {
+        format!("{}  {}\n", sha256, path)
+    }
+}
+
+/// 计算 files 的哈希并写入会话的清单，返回清单路径
+///
+/// 不存在的文件跳过，没有可列出的文件时不写入并返回 None
+pub async fn write_manifest(
+    download_dir: &Path,
+    session_id: &str,
+    files: &[PathBuf],
+) -> crate::Result<Option<PathBuf>> {
+    let mut manifest = String::new();
+    for path in files {
+        let file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!(path = %path.display(), "文件已不在，不列入清单");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let sha256 = crate::hash::sha256_reader(file).await?;
+        let relative = path.strip_prefix(download_dir).unwrap_or(path);
+        // sha256sum 在 Windows 上也使用正斜杠
+        let relative = relative.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/");
+        manifest.push_str(&manifest_line(&sha256, &relative));
+    }
+    if manifest.is_empty() {
+        return Ok(None);
+    }
+    let path = manifest_path(download_dir, session_id);
+    tokio::fs::write(&path, manifest).await?;
+    Ok(Some(path))
+}
+
+/// 在接收的会话完成后写入哈希清单
+#[derive(Debug, Clone)]
+pub struct ManifestWriter {
+    download_dir: PathBuf,
+}
+
+impl ManifestWriter {
+    /// 按配置创建，未启用 `hash_manifest` 时返回 None
+    pub fn from_config(config: &LocalSendConfig) -> Option<Self> {
+        config.hash_manifest.then(|| Self {
+            download_dir: PathBuf::from(&config.download_dir),
+        })
+    }
+
+    /// 记录收到的文件，会话完成时写入清单，直到事件总线关闭或 cancel 取消
+    pub async fn run(
+        self,
+        mut events: broadcast::Receiver<ProtocolEvent>,
+        cancel: CancellationToken,
+    ) {
+        // 会话 ID 到已收到的文件，只有接收的会话才有
+        let mut received: HashMap<String, Vec<PathBuf>> = HashMap::new();
+
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = events.recv() => event,
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "哈希清单处理过慢，丢失部分事件");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            match event {
+                ProtocolEvent::FileReceived {
+                    session_id, path, ..
+                } => received.entry(session_id).or_default().push(path),
+                ProtocolEvent::SessionStateChanged { session_id, state } => match state {
+                    SessionState::Finished => {
+                        let Some(files) = received.remove(&session_id) else {
+                            continue;
+                        };
+                        // 计算哈希需要读取全部文件，不阻塞事件接收
+                        let dir = self.download_dir.clone();
+                        tokio::spawn(async move {
+                            match write_manifest(&dir, &session_id, &files).await {
+                                Ok(Some(path)) => {
+                                    info!(session_id, path = %path.display(), "已写入哈希清单")
+                                }
+                                Ok(None) => {}
+                                Err(e) => warn!(session_id, error = %e, "写入哈希清单失败"),
+                            }
+                        });
+                    }
+                    SessionState::Cancelled | SessionState::Error(_) => {
+                        received.remove(&session_id);
+                    }
+                    SessionState::Waiting | SessionState::Transferring => {}
+                },
+                _ => {}
+            }
+        }
+    }
+}
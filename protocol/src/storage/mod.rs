@@ -0,0 +1,426 @@
+//! 接收文件存储
+//!
+//! [`FileReceiver`](crate::session::FileReceiver) 通过 [`ReceiveStorage`] 落地收到的文件，
+//! 默认写入本地目录，服务端部署可替换为其他后端。
+//!
+//! 扩展名在隔离列表中的文件不直接放入下载目录，而是放入其中的 [`QUARANTINE_DIR`]
+//! 子目录并去掉执行权限，见 [`receive_path`]。
+//! 经传输流或 [`FsStorage`] 接收的文件先写入 [`part_path`]，完整写入后才改为正式文件名；
+//! [`FsStorage`] 的 `.part` 另带会话和文件的标识，见 [`FsStorage::part_path`]。
+//! 与已接收文件相同的文件可经 [`dedup`] 中的哈希索引跳过。
+//! 启用 `extract` 特性时，收到的压缩包可经 `extract` 自动解压。
+//! 会话完成后可经 [`manifest`] 在下载目录写入 `sha256sum` 格式的哈希清单。
+//! PeerSend 之间发送的符号链接经 [`links`] 在下载目录中重建。
+//! 发送方提供的文件名保存前经 [`names`] 清理
+
+pub mod dedup;
+#[cfg(feature = "extract")]
+pub mod extract;
+pub mod links;
+pub mod manifest;
+pub mod names;
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::Mutex;
+
+use crate::fs::{BoxedWriter, FileSystemRef, LocalFs};
+use crate::hash::sha256_hex;
+
+/// 默认写缓冲区大小 (256KB)
+pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 256 * 1024;
+
+/// 隔离子目录，位于下载目录中
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// `.part` 文件名中会话和文件标识的长度
+const PART_ID_LEN: usize = 8;
+
+/// 正在写入的文件句柄
+type OpenFile = Arc<Mutex<BufWriter<BoxedWriter>>>;
+
+/// 共享的存储后端
+pub type ReceiveStorageRef = Arc<dyn ReceiveStorage>;
+
+/// 存储中的文件标识
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StorageKey {
+    pub session_id: String,
+    pub file_id: String,
+    /// 保存时使用的文件名，已经过 [`names`] 清理
+    pub name: String,
+}
+
+/// 接收文件存储接口
+///
+/// 每个文件依次经过 `open`、若干次 `write_chunk`，最后 `finalize` 或 `abort`。
+/// 中断的文件可经 `suspend` 保留已写入的数据，之后用 `resume` 从中断处继续
+#[async_trait]
+pub trait ReceiveStorage: Debug + Send + Sync {
+    /// 开始接收文件，已存在的同名文件被覆盖
+    async fn open(&self, key: &StorageKey) -> crate::Result<()>;
+
+    /// 是否已有同名的文件，供 [`CollisionPolicy`](crate::CollisionPolicy) 判断，默认视为没有
+    async fn exists(&self, _key: &StorageKey) -> crate::Result<bool> {
+        Ok(false)
+    }
+
+    /// 此前中断的文件已写入的字节数，没有数据或不支持续传时返回 0
+    async fn received_len(&self, _key: &StorageKey) -> crate::Result<u64> {
+        Ok(0)
+    }
+
+    /// 从 offset 处继续接收此前中断的文件，offset 需等于 [`received_len`](Self::received_len)
+    ///
+    /// 默认只支持从头接收
+    async fn resume(&self, key: &StorageKey, offset: u64) -> crate::Result<()> {
+        if offset != 0 {
+            return Err(crate::ProtocolError::InvalidData("存储不支持续传".to_string()));
+        }
+        self.open(key).await
+    }
+
+    /// 追加数据块
+    async fn write_chunk(&self, key: &StorageKey, data: &[u8]) -> crate::Result<()>;
+
+    /// 完成文件，返回文件的最终位置
+    async fn finalize(&self, key: &StorageKey) -> crate::Result<String>;
+
+    /// 放弃文件并清理已写入的数据
+    async fn abort(&self, key: &StorageKey) -> crate::Result<()>;
+
+    /// 中断文件，保留已写入的数据以便之后 [`resume`](Self::resume)，默认与 `abort` 相同
+    async fn suspend(&self, key: &StorageKey) -> crate::Result<()> {
+        self.abort(key).await
+    }
+}
+
+/// 续传偏移与已写入的长度不一致时的错误
+fn check_resume_offset(key: &StorageKey, offset: u64, received: u64) -> crate::Result<()> {
+    if offset != received {
+        return Err(crate::ProtocolError::InvalidData(format!(
+            "{} 的续传偏移 {} 与已接收的长度 {} 不一致",
+            key.name, offset, received
+        )));
+    }
+    Ok(())
+}
+
+/// 本地目录存储
+///
+/// 文件从 `open` 到 `finalize` 期间保持同一个打开的句柄，写入经过缓冲。
+/// 写入期间文件名带 `.part` 后缀，完成时刷新并同步到磁盘后再改为正式文件名，
+/// 未接收完的文件不会以正式文件名出现；中断时保留 `.part` 以便续传
+#[derive(Clone)]
+pub struct FsStorage {
+    root: PathBuf,
+    fs: FileSystemRef,
+    buffer_size: usize,
+    quarantine: Vec<String>,
+    open_files: Arc<Mutex<HashMap<StorageKey, OpenFile>>>,
+}
+
+impl Debug for FsStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FsStorage")
+            .field("root", &self.root)
+            .field("fs", &self.fs)
+            .field("buffer_size", &self.buffer_size)
+            .field("quarantine", &self.quarantine)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FsStorage {
+    /// 将文件保存到 root 目录
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self::with_fs(root, LocalFs::shared())
+    }
+
+    /// 使用指定的文件系统
+    pub fn with_fs(root: impl Into<PathBuf>, fs: FileSystemRef) -> Self {
+        Self {
+            root: root.into(),
+            fs,
+            buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            quarantine: Vec::new(),
+            open_files: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 设置写缓冲区大小，只影响之后打开的文件
+    pub fn with_buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size;
+        self
+    }
+
+    /// 把这些扩展名的文件放入隔离目录，通常为配置的 `quarantine_extensions`
+    pub fn with_quarantine(mut self, extensions: Vec<String>) -> Self {
+        self.quarantine = extensions;
+        self
+    }
+
+    /// 文件保存路径
+    pub fn path(&self, key: &StorageKey) -> PathBuf {
+        receive_path(&self.root, &key.name, &self.quarantine)
+    }
+
+    /// 文件写入中使用的 `.part` 路径，见 [`part_path`]
+    pub fn part_path(&self, key: &StorageKey) -> PathBuf {
+        part_path(&self.path(key), &key.session_id, &key.file_id)
+    }
+
+    async fn open_writer(&self, key: &StorageKey, path: &Path, append: bool) -> crate::Result<()> {
+        let file = self.fs.open_write(path, append).await?;
+        let writer = BufWriter::with_capacity(self.buffer_size, file);
+        self.open_files
+            .lock()
+            .await
+            .insert(key.clone(), Arc::new(Mutex::new(writer)));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ReceiveStorage for FsStorage {
+    async fn open(&self, key: &StorageKey) -> crate::Result<()> {
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            self.fs.create_dir_all(parent).await?;
+        }
+        self.open_writer(key, &self.part_path(key), false).await
+    }
+
+    async fn exists(&self, key: &StorageKey) -> crate::Result<bool> {
+        match self.fs.file_len(&self.path(key)).await {
+            Err(crate::ProtocolError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(false)
+            }
+            len => len.map(|_| true),
+        }
+    }
+
+    async fn received_len(&self, key: &StorageKey) -> crate::Result<u64> {
+        match self.fs.file_len(&self.part_path(key)).await {
+            Err(crate::ProtocolError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            len => len,
+        }
+    }
+
+    async fn resume(&self, key: &StorageKey, offset: u64) -> crate::Result<()> {
+        if offset == 0 {
+            return self.open(key).await;
+        }
+        check_resume_offset(key, offset, self.received_len(key).await?)?;
+        self.open_writer(key, &self.part_path(key), true).await
+    }
+
+    async fn write_chunk(&self, key: &StorageKey, data: &[u8]) -> crate::Result<()> {
+        let writer = self
+            .open_files
+            .lock()
+            .await
+            .get(key)
+            .cloned()
+            .ok_or_else(|| crate::ProtocolError::InvalidData(format!("文件未打开: {}", key.name)))?;
+        writer.lock().await.write_all(data).await?;
+        Ok(())
+    }
+
+    async fn finalize(&self, key: &StorageKey) -> crate::Result<String> {
+        let path = self.path(key);
+        let part = self.part_path(key);
+        if let Some(writer) = self.open_files.lock().await.remove(key) {
+            writer.lock().await.shutdown().await?;
+        }
+        // 先同步内容再改名，断电后正式文件名下不会是不完整的内容
+        self.fs.sync_file(&part).await?;
+        if self.fs.is_local() && is_quarantined(&key.name, &self.quarantine) {
+            strip_execute_bits(&part)?;
+        }
+        self.fs.rename(&part, &path).await?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    async fn abort(&self, key: &StorageKey) -> crate::Result<()> {
+        // 先关闭句柄，Windows 上无法删除打开的文件
+        self.open_files.lock().await.remove(key);
+        self.fs.remove_file(&self.part_path(key)).await
+    }
+
+    async fn suspend(&self, key: &StorageKey) -> crate::Result<()> {
+        if let Some(writer) = self.open_files.lock().await.remove(key) {
+            writer.lock().await.shutdown().await?;
+        }
+        Ok(())
+    }
+}
+
+/// 文件名的扩展名是否在隔离列表中，大小写不敏感
+pub fn is_quarantined(name: &str, extensions: &[String]) -> bool {
+    let Some(extension) = Path::new(name).extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    extensions
+        .iter()
+        .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+}
+
+/// 收到的文件在 root 下的保存路径，需隔离的文件放入 [`QUARANTINE_DIR`]
+pub fn receive_path(root: &Path, name: &str, quarantine: &[String]) -> PathBuf {
+    if is_quarantined(name, quarantine) {
+        root.join(QUARANTINE_DIR).join(name)
+    } else {
+        root.join(name)
+    }
+}
+
+/// 会话中的文件接收完成前写入的路径，完成后再改为正式文件名 path
+///
+/// 文件名中带有由会话和文件 ID 得到的标识，如 `photo.jpg.1a2b3c4d.part`，
+/// 不同会话或同一会话中同名的文件各自写入自己的 `.part`，不会互相截断
+pub fn part_path(path: &Path, session_id: &str, file_id: &str) -> PathBuf {
+    let id = sha256_hex(format!("{}/{}", session_id, file_id).as_bytes());
+    let mut name = path.file_name().map(std::ffi::OsString::from).unwrap_or_default();
+    name.push(format!(".{}.part", &id[..PART_ID_LEN]));
+    path.with_file_name(name)
+}
+
+/// 会话中的文件已写入 `.part` 的字节数，不存在时为 0
+pub async fn part_len(path: &Path, session_id: &str, file_id: &str) -> u64 {
+    let part = part_path(path, session_id, file_id);
+    tokio::fs::metadata(part).await.map_or(0, |m| m.len())
+}
+
+/// 是否有任一会话的文件正在写入以 path 为正式文件名的 `.part`
+pub async fn has_part(path: &Path) -> std::io::Result<bool> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|n| n.to_str()))
+    else {
+        return Ok(false);
+    };
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        entries => entries?,
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let entry = entry.file_name();
+        let id = entry
+            .to_str()
+            .and_then(|n| n.strip_prefix(name))
+            .and_then(|n| n.strip_prefix('.'))
+            .and_then(|n| n.strip_suffix(".part"));
+        if id.is_some_and(|id| id.len() == PART_ID_LEN && id.bytes().all(|b| b.is_ascii_hexdigit()))
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// 去掉文件的执行权限，Windows 上没有执行位，不做处理
+pub fn strip_execute_bits(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = std::fs::metadata(path)?.permissions();
+        permissions.set_mode(permissions.mode() & !0o111);
+        std::fs::set_permissions(path, permissions)?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// 内存存储
+///
+/// 完成的文件按文件名保存，适合测试和不落盘的部署
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    pending: Mutex<HashMap<StorageKey, Vec<u8>>>,
+    /// 中断后等待续传的文件
+    suspended: Mutex<HashMap<StorageKey, Vec<u8>>>,
+    files: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    /// 创建空的内存存储
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 获取已完成文件的内容
+    pub async fn get(&self, name: &str) -> Option<Vec<u8>> {
+        self.files.lock().await.get(name).cloned()
+    }
+
+    /// 已完成的文件名
+    pub async fn names(&self) -> Vec<String> {
+        self.files.lock().await.keys().cloned().collect()
+    }
+}
+
+#[async_trait]
+impl ReceiveStorage for MemoryStorage {
+    async fn open(&self, key: &StorageKey) -> crate::Result<()> {
+        self.suspended.lock().await.remove(key);
+        self.pending.lock().await.insert(key.clone(), Vec::new());
+        Ok(())
+    }
+
+    async fn exists(&self, key: &StorageKey) -> crate::Result<bool> {
+        Ok(self.files.lock().await.contains_key(&key.name))
+    }
+
+    async fn received_len(&self, key: &StorageKey) -> crate::Result<u64> {
+        Ok(self.suspended.lock().await.get(key).map_or(0, |data| data.len() as u64))
+    }
+
+    async fn resume(&self, key: &StorageKey, offset: u64) -> crate::Result<()> {
+        let data = self.suspended.lock().await.remove(key).unwrap_or_default();
+        if let Err(e) = check_resume_offset(key, offset, data.len() as u64) {
+            self.suspended.lock().await.insert(key.clone(), data);
+            return Err(e);
+        }
+        self.pending.lock().await.insert(key.clone(), data);
+        Ok(())
+    }
+
+    async fn write_chunk(&self, key: &StorageKey, data: &[u8]) -> crate::Result<()> {
+        self.pending
+            .lock()
+            .await
+            .get_mut(key)
+            .ok_or_else(|| crate::ProtocolError::InvalidData(format!("文件未打开: {}", key.name)))?
+            .extend_from_slice(data);
+        Ok(())
+    }
+
+    async fn finalize(&self, key: &StorageKey) -> crate::Result<String> {
+        let data = self
+            .pending
+            .lock()
+            .await
+            .remove(key)
+            .ok_or_else(|| crate::ProtocolError::InvalidData(format!("文件未打开: {}", key.name)))?;
+        self.files.lock().await.insert(key.name.clone(), data);
+        Ok(format!("memory:{}", key.name))
+    }
+
+    async fn abort(&self, key: &StorageKey) -> crate::Result<()> {
+        self.pending.lock().await.remove(key);
+        self.suspended.lock().await.remove(key);
+        Ok(())
+    }
+
+    async fn suspend(&self, key: &StorageKey) -> crate::Result<()> {
+        if let Some(data) = self.pending.lock().await.remove(key) {
+            self.suspended.lock().await.insert(key.clone(), data);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+//! 收到的文件名的清理
+//!
+//! 文件名由发送方提供，直接拼接到下载目录可能经 `../` 写到目录之外。
+//! 保存前只保留文件名的最后一段，拒绝绝对路径和 Windows 保留的设备名，
+//! 并统一为 NFC 形式，避免同一个名字因组合方式不同而保存为两个文件
+
+use std::path::Path;
+
+use icu_normalizer::ComposingNormalizerBorrowed;
+
+use crate::ProtocolError;
+
+/// Windows 保留的设备名，带扩展名时同样无法创建
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Windows 文件名中不允许的字符，替换为 `_`
+const FORBIDDEN_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// 清理发送方提供的文件名，返回可安全放入下载目录的文件名
+///
+/// `/` 和 `\` 均视为目录分隔符，只保留最后一段；去掉控制字符以及结尾的点和空格，
+/// Windows 不允许的字符替换为 `_`。
+/// 绝对路径、清理后为空或为保留设备名时返回 [`ProtocolError::Rejected`]
+pub fn sanitize_file_name(name: &str) -> crate::Result<String> {
+    let rejected = |reason: &str| Err(ProtocolError::Rejected(format!("{}: {:?}", reason, name)));
+    if is_absolute(name) {
+        return rejected("文件名不能是绝对路径");
+    }
+
+    // 先规范化再拆分，规范化不会产生新的分隔符
+    let normalized = ComposingNormalizerBorrowed::new_nfc().normalize(name);
+    let last = normalized.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = last
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| if FORBIDDEN_CHARS.contains(&c) { '_' } else { c })
+        .collect();
+    // Windows 会去掉结尾的点和空格，`..` 也在此变为空
+    let cleaned = cleaned.trim_end_matches(['.', ' ']).trim_start();
+
+    if cleaned.is_empty() {
+        return rejected("文件名无效");
+    }
+    if is_reserved(cleaned) {
+        return rejected("文件名为系统保留的设备名");
+    }
+    Ok(cleaned.to_string())
+}
+
+/// 在文件名的扩展名前追加序号，如 `photo.jpg` 的第 1 个为 `photo (1).jpg`
+pub fn numbered_name(name: &str, n: u32) -> String {
+    let path = Path::new(name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    format!("{} ({}){}", stem, n, extension)
+}
+
+/// 以分隔符或盘符开头的路径
+fn is_absolute(name: &str) -> bool {
+    let drive = matches!(name.as_bytes(), [letter, b':', ..] if letter.is_ascii_alphabetic());
+    name.starts_with(['/', '\\']) || drive
+}
+
+/// 第一个点之前的部分是否为保留设备名，大小写不敏感
+fn is_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem))
+}
@@ -0,0 +1,172 @@
+//! 会话结束时的摘要
+//!
+//! 会话结束 (完成、出错或传输开始后取消) 时 [`SessionManager`](crate::SessionManager)
+//! 按各文件的传输结果生成 [`SessionSummary`]，保存在会话的 `summary` 中并发布
+//! [`ProtocolEvent::SessionSummary`](crate::ProtocolEvent::SessionSummary)。
+//! 审计日志把摘要附在完成或失败的记录中，作为传输历史保存。
+//!
+//! 会话完成时未传输的文件 (对方未接受、已有相同文件等) 记为跳过，
+//! 会话失败时记为失败，按 [`CollisionPolicy::Skip`](crate::CollisionPolicy::Skip)
+//! 跳过的同名文件总是记为跳过。声明了 SHA-256 的文件由接收端边写入边校验，传输完成即视为校验通过
+
+use serde::{Deserialize, Serialize};
+
+use crate::{FileSession, FileWarning, SessionState};
+
+/// 单个文件的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileOutcome {
+    Transferred,
+    Skipped,
+    Failed,
+}
+
+/// 单个文件的校验结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verification {
+    /// 内容与声明的 SHA-256 一致
+    Verified,
+    /// 内容与声明的 SHA-256 不一致
+    Mismatch,
+    /// 未声明哈希或未传输完成，没有校验
+    Unchecked,
+}
+
+/// 摘要中的文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSummary {
+    pub name: String,
+    pub size: u64,
+    pub outcome: FileOutcome,
+    pub verification: Verification,
+    /// 失败的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 保存时使用的文件名，与原文件名相同时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved_name: Option<String>,
+}
+
+/// 会话摘要
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub sender_id: String,
+    pub receiver_id: String,
+    /// `finished`、`cancelled` 或 `error`
+    pub state: String,
+    /// 会话失败的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub files: Vec<FileSummary>,
+    pub transferred: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    /// 传输完成的文件的总字节数
+    pub total_bytes: u64,
+    /// 从开始传输到会话结束的毫秒数，没有传输任何数据时为 0
+    pub duration_ms: u64,
+    /// 平均速度 (字节/秒)
+    pub average_speed: u64,
+    /// 校验通过的文件数
+    pub verified: usize,
+    /// 校验失败的文件数
+    pub mismatched: usize,
+    /// 被跳过或未能按原样传输的文件，与会话的 `warnings` 相同
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<FileWarning>,
+}
+
+impl SessionSummary {
+    /// 会话从 previous 变为 state 时是否结束，需要生成摘要
+    ///
+    /// 与审计日志一致，等待确认时被取消 (如拒绝请求) 不算结束的传输
+    pub fn applies(previous: &SessionState, state: &SessionState) -> bool {
+        let ended = |state: &SessionState| {
+            matches!(
+                state,
+                SessionState::Finished | SessionState::Cancelled | SessionState::Error(_)
+            )
+        };
+        match state {
+            SessionState::Cancelled => *previous == SessionState::Transferring,
+            state => ended(state) && !ended(previous),
+        }
+    }
+
+    /// 按会话中各文件的传输结果生成摘要，state 为会话结束时的状态
+    pub async fn new(session: &FileSession, state: &SessionState) -> Self {
+        let (state, error) = match state {
+            SessionState::Finished => ("finished", None),
+            SessionState::Error(message) => ("error", Some(message.clone())),
+            _ => ("cancelled", None),
+        };
+        let transfers = session.transfers.lock().unwrap().clone();
+        let files: Vec<FileSummary> = session
+            .files
+            .iter()
+            .map(|file| {
+                let transfer = transfers.get(&file.id).cloned().unwrap_or_default();
+                let outcome = if transfer.completed {
+                    FileOutcome::Transferred
+                } else if transfer.skipped || (state == "finished" && transfer.error.is_none()) {
+                    FileOutcome::Skipped
+                } else {
+                    FileOutcome::Failed
+                };
+                // 校验失败后换用途径重传成功的文件以最后一次为准
+                let verification = match outcome {
+                    FileOutcome::Transferred if file.sha256.is_some() => Verification::Verified,
+                    FileOutcome::Failed if transfer.hash_mismatch => Verification::Mismatch,
+                    _ => Verification::Unchecked,
+                };
+                FileSummary {
+                    name: file.name.clone(),
+                    size: file.size,
+                    outcome,
+                    verification,
+                    reason: transfer.error.filter(|_| outcome == FileOutcome::Failed),
+                    saved_name: transfer.saved_name.filter(|name| *name != file.name),
+                }
+            })
+            .collect();
+
+        let count = |outcome| files.iter().filter(|f| f.outcome == outcome).count();
+        let (transferred, skipped, failed) = (
+            count(FileOutcome::Transferred),
+            count(FileOutcome::Skipped),
+            count(FileOutcome::Failed),
+        );
+        let checked = |result| files.iter().filter(|f| f.verification == result).count();
+        let total_bytes = files
+            .iter()
+            .filter(|f| f.outcome == FileOutcome::Transferred)
+            .map(|f| f.size)
+            .sum();
+        let elapsed = session.progress.elapsed();
+        let average_speed = match elapsed.as_secs_f64() {
+            secs if secs > 0.0 => (session.progress.bytes_transferred() as f64 / secs) as u64,
+            _ => 0,
+        };
+        Self {
+            session_id: session.id.clone(),
+            sender_id: session.sender_id.clone(),
+            receiver_id: session.receiver_id.clone(),
+            state: state.to_string(),
+            error,
+            transferred,
+            skipped,
+            failed,
+            total_bytes,
+            duration_ms: elapsed.as_millis() as u64,
+            average_speed,
+            verified: checked(Verification::Verified),
+            mismatched: checked(Verification::Mismatch),
+            warnings: session.warnings.lock().await.clone(),
+            files,
+        }
+    }
+}
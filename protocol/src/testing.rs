@@ -0,0 +1,550 @@
+//! 测试工具
+//!
+//! - [`PeerPair`] 在同一进程中启动两个节点，各自拥有会话管理、发现管理、事件总线和监听回环地址上
+//!   不同端口的接收服务。节点经 HTTP 接口互相注册，由 [`LocalSendClient`] 驱动准备、上传和取消流程
+//! - [`MockHttp`] 和 [`MockFs`] 是不经过套接字和磁盘的 HTTP 客户端与文件系统，可注入断连、延迟和短读等故障
+//! - [`peer_device`] 是测试中作为发送目标的设备信息
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use crate::dto::v2::{
+    FileDto, PrepareUploadRequestDto, PrepareUploadResponseDto, RegisterDto, REGISTER_PATH,
+};
+use crate::fs::{BoxedReader, BoxedWriter, FileSystem};
+use crate::http::{HttpBody, HttpClient, HttpMethod, HttpRequest, HttpResponse, ReqwestClient};
+use crate::server::LocalSendServer;
+use crate::{
+    DeviceInfo, DiscoveryManager, EventBus, FileSession, LocalSendClient, LocalSendConfig,
+    ProtocolError, QuickSave, SessionManager, PROTOCOL_VERSION,
+};
+
+/// 单个测试节点
+#[derive(Debug)]
+pub struct TestPeer {
+    pub config: LocalSendConfig,
+    pub sessions: Arc<Mutex<SessionManager>>,
+    pub discovery: Arc<Mutex<DiscoveryManager>>,
+    pub events: EventBus,
+    pub server: LocalSendServer,
+    root: PathBuf,
+}
+
+impl TestPeer {
+    /// 启动测试节点
+    ///
+    /// 接收服务绑定 127.0.0.1 上的随机端口并自动接受收到的文件，
+    /// 下载目录位于独立的临时目录，节点释放时删除
+    pub async fn spawn(name: &str) -> crate::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let addr = listener.local_addr()?;
+
+        let root = std::env::temp_dir().join(format!("peersend-test-{}", uuid::Uuid::new_v4()));
+        let config = LocalSendConfig::builder()
+            .device_name(name)
+            .port(addr.port())
+            .download_dir(root.join("downloads").to_string_lossy().into_owned())
+            .quick_save(QuickSave::On)
+            .build()?;
+
+        let events = EventBus::default();
+        let sessions = Arc::new(Mutex::new(SessionManager::with_event_bus(events.clone())));
+        let discovery = Arc::new(Mutex::new(DiscoveryManager::with_event_bus(events.clone())));
+
+        let server = LocalSendServer::new(
+            SocketAddr::from((Ipv4Addr::LOCALHOST, addr.port())),
+            config.clone(),
+            sessions.clone(),
+            discovery.clone(),
+        )
+        .with_listener(listener);
+        server.start().await?;
+
+        Ok(Self {
+            config,
+            sessions,
+            discovery,
+            events,
+            server,
+            root,
+        })
+    }
+
+    /// 其他节点看到的设备信息
+    pub fn device_info(&self) -> DeviceInfo {
+        DeviceInfo {
+            id: self.config.device_id.clone(),
+            name: self.config.device_name.clone(),
+            device_type: self.config.device_type.clone(),
+            ip: Ipv4Addr::LOCALHOST.to_string(),
+            port: self.config.port,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: PROTOCOL_VERSION.to_string(),
+            announcement_id: String::new(),
+            uses_password: false,
+            transports: self.config.transports.clone(),
+            addresses: Vec::new(),
+        }
+    }
+
+    /// 下载目录
+    pub fn download_dir(&self) -> &Path {
+        Path::new(&self.config.download_dir)
+    }
+
+    /// 在节点的临时目录中创建待发送文件
+    pub async fn write_file(&self, name: &str, contents: &[u8]) -> crate::Result<PathBuf> {
+        let dir = self.root.join("outgoing");
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = dir.join(name);
+        tokio::fs::write(&path, contents).await?;
+        Ok(path)
+    }
+
+    /// 以本节点的身份发送的客户端，发送的会话记录在本节点的会话管理中
+    pub async fn client(&self) -> LocalSendClient {
+        let sessions = self.sessions.lock().await.clone();
+        LocalSendClient::new(self.config.clone()).with_session_manager(sessions)
+    }
+
+    /// 向对方的接收服务发送 register 请求注册自己
+    pub async fn register_with(&self, other: &TestPeer) -> crate::Result<()> {
+        let url = format!("http://{}:{}{}", Ipv4Addr::LOCALHOST, other.config.port, REGISTER_PATH);
+        let request = HttpRequest::post(url).json(&RegisterDto::from_config(&self.config))?;
+        ReqwestClient::shared().execute(request).await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+impl Drop for TestPeer {
+    fn drop(&mut self) {
+        self.server.shutdown();
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
+
+/// 双端测试环境
+///
+/// 两个节点经回环地址上的 HTTP 接口互相注册，不经过组播发现
+#[derive(Debug)]
+pub struct PeerPair {
+    pub sender: TestPeer,
+    pub receiver: TestPeer,
+}
+
+impl PeerPair {
+    /// 启动两个节点并互相注册
+    pub async fn start() -> crate::Result<Self> {
+        let sender = TestPeer::spawn("sender").await?;
+        let receiver = TestPeer::spawn("receiver").await?;
+        sender.register_with(&receiver).await?;
+        receiver.register_with(&sender).await?;
+        Ok(Self { sender, receiver })
+    }
+
+    /// 从 sender 向 receiver 发送文件，返回接收端会话
+    pub async fn send_files(&self, files: &[(&str, &[u8])]) -> crate::Result<Arc<FileSession>> {
+        self.transfer(files, CancellationToken::new()).await
+    }
+
+    /// 由 sender 的 [`LocalSendClient`] 经 prepare-upload 和 upload 向 receiver 发送文件
+    ///
+    /// cancel 被取消时取消客户端，客户端通知 receiver 取消会话
+    pub async fn transfer(
+        &self,
+        files: &[(&str, &[u8])],
+        cancel: CancellationToken,
+    ) -> crate::Result<Arc<FileSession>> {
+        let mut paths = Vec::with_capacity(files.len());
+        for (name, contents) in files {
+            paths.push(self.sender.write_file(name, contents).await?);
+        }
+
+        let receiver_sessions = self.receiver.sessions.lock().await.clone();
+        let before = receiver_sessions.get_all_sessions().await;
+        let client = self.sender.client().await;
+        let watcher = tokio::spawn({
+            let client = client.clone();
+            async move {
+                cancel.cancelled().await;
+                client.cancel();
+            }
+        });
+        let sent = client.send_files(&self.receiver.device_info(), &paths).await;
+        watcher.abort();
+        sent?;
+
+        receiver_sessions
+            .get_all_sessions()
+            .await
+            .iter()
+            .find(|s| before.iter().all(|b| b.id != s.id))
+            .cloned()
+            .ok_or_else(|| ProtocolError::InvalidData("接收端没有新的会话".to_string()))
+    }
+}
+
+/// 192.0.2.1:53317 上的 LocalSend 桌面设备，没有声明传输和候选地址
+///
+/// 测试需要的字段不同时用结构体更新语法覆盖，如 `DeviceInfo { port, ..peer_device() }`
+pub fn peer_device() -> DeviceInfo {
+    DeviceInfo {
+        id: "peer".to_string(),
+        name: "peer".to_string(),
+        device_type: "desktop".to_string(),
+        ip: "192.0.2.1".to_string(),
+        port: 53317,
+        version: "test".to_string(),
+        protocol_version: "2.0".to_string(),
+        announcement_id: String::new(),
+        uses_password: false,
+        transports: Vec::new(),
+        addresses: Vec::new(),
+    }
+}
+
+/// 注入的故障
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// 连接中断，请求返回 IO 错误
+    Drop,
+    /// 延迟指定时间后再处理请求
+    Delay(Duration),
+    /// 直接返回指定状态码
+    Status(u16),
+}
+
+/// 已收到的请求，流式请求体已读入内存
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: HttpMethod,
+    pub url: String,
+    pub query: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// 获取查询参数
+    pub fn query_param(&self, key: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 像接收端一样应答 prepare-upload 请求，接受 accept 返回 true 的文件
+    ///
+    /// 会话 ID 为 `remote`，每个文件的令牌为文件 ID
+    pub fn accept_files(&self, accept: impl Fn(&FileDto) -> bool) -> HttpResponse {
+        let prepare: PrepareUploadRequestDto = serde_json::from_slice(&self.body).unwrap();
+        let files = prepare.files.into_values().filter(|f| accept(f));
+        let response = PrepareUploadResponseDto {
+            session_id: "remote".to_string(),
+            files: files.map(|f| (f.id.clone(), f.id)).collect(),
+        };
+        HttpResponse {
+            status: 200,
+            body: serde_json::to_vec(&response).unwrap(),
+        }
+    }
+}
+
+type Handler = Box<dyn Fn(&RecordedRequest) -> HttpResponse + Send + Sync>;
+
+/// 内存中的 HTTP 客户端
+///
+/// 按 URL 路径后缀匹配路由，未匹配的请求返回 404
+#[derive(Default)]
+pub struct MockHttp {
+    routes: std::sync::Mutex<Vec<(String, Handler)>>,
+    faults: std::sync::Mutex<Vec<(String, Fault)>>,
+    requests: std::sync::Mutex<Vec<RecordedRequest>>,
+}
+
+impl fmt::Debug for MockHttp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockHttp")
+            .field("faults", &self.faults)
+            .field("requests", &self.requests)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MockHttp {
+    /// 创建没有任何路由的客户端
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// 注册路由处理函数
+    pub fn route<F>(&self, path: &str, handler: F)
+    where
+        F: Fn(&RecordedRequest) -> HttpResponse + Send + Sync + 'static,
+    {
+        self.routes
+            .lock()
+            .unwrap()
+            .push((path.to_string(), Box::new(handler)));
+    }
+
+    /// 像接收端一样接受所有文件
+    ///
+    /// prepare-upload 见 [`RecordedRequest::accept_files`]；upload 返回 200
+    pub fn accept_all(&self) {
+        self.route("/prepare-upload", |request| request.accept_files(|_| true));
+        self.respond_status("/upload", 200);
+    }
+
+    /// 注册只返回状态码的路由
+    pub fn respond_status(&self, path: &str, status: u16) {
+        self.route(path, move |_| HttpResponse {
+            status,
+            body: Vec::new(),
+        });
+    }
+
+    /// 为下一个匹配 path 的请求注入故障，按注入顺序依次生效
+    pub fn inject(&self, path: &str, fault: Fault) {
+        self.faults.lock().unwrap().push((path.to_string(), fault));
+    }
+
+    /// 已处理的请求
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn take_fault(&self, path: &str) -> Option<Fault> {
+        let mut faults = self.faults.lock().unwrap();
+        let index = faults.iter().position(|(p, _)| path.ends_with(p.as_str()))?;
+        Some(faults.remove(index).1)
+    }
+}
+
+#[async_trait]
+impl HttpClient for MockHttp {
+    async fn execute(&self, request: HttpRequest) -> crate::Result<HttpResponse> {
+        let path = url::Url::parse(&request.url)
+            .map(|u| u.path().to_string())
+            .unwrap_or_else(|_| request.url.clone());
+
+        let mut status = None;
+        if let Some(fault) = self.take_fault(&path) {
+            match fault {
+                Fault::Drop => {
+                    return Err(io::Error::new(io::ErrorKind::ConnectionReset, "注入的断连").into())
+                }
+                Fault::Delay(delay) => tokio::time::sleep(delay).await,
+                Fault::Status(code) => status = Some(code),
+            }
+        }
+
+        let body = match request.body {
+            HttpBody::Empty => Vec::new(),
+            HttpBody::Json(data) => data,
+            HttpBody::Stream(mut reader, len) => {
+                let mut data = Vec::with_capacity(len as usize);
+                reader.read_to_end(&mut data).await?;
+                data
+            }
+        };
+        let recorded = RecordedRequest {
+            method: request.method,
+            url: request.url,
+            query: request.query,
+            body,
+        };
+        self.requests.lock().unwrap().push(recorded.clone());
+
+        if let Some(status) = status {
+            return Ok(HttpResponse {
+                status,
+                body: Vec::new(),
+            });
+        }
+
+        let routes = self.routes.lock().unwrap();
+        Ok(routes
+            .iter()
+            .find(|(p, _)| path.ends_with(p.as_str()))
+            .map(|(_, handler)| handler(&recorded))
+            .unwrap_or(HttpResponse {
+                status: 404,
+                body: Vec::new(),
+            }))
+    }
+}
+
+/// 内存文件系统
+///
+/// 目录是隐式的，`create_dir_all` 总是成功
+#[derive(Debug, Default)]
+pub struct MockFs {
+    files: Arc<std::sync::Mutex<HashMap<PathBuf, Vec<u8>>>>,
+    max_read: Option<usize>,
+    fail_reads_after: Option<usize>,
+}
+
+impl MockFs {
+    /// 创建空的文件系统
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 每次读取最多返回 n 字节，模拟短读
+    pub fn with_short_reads(mut self, n: usize) -> Self {
+        self.max_read = Some(n.max(1));
+        self
+    }
+
+    /// 每个读取端在读出 n 字节后返回错误
+    pub fn fail_reads_after(mut self, n: usize) -> Self {
+        self.fail_reads_after = Some(n);
+        self
+    }
+
+    /// 写入文件内容
+    pub fn insert(&self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.into(), contents.into());
+    }
+
+    /// 读取文件内容
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().get(path.as_ref()).cloned()
+    }
+}
+
+#[async_trait]
+impl FileSystem for MockFs {
+    async fn file_len(&self, path: &Path) -> crate::Result<u64> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .map(|data| data.len() as u64)
+            .ok_or_else(|| not_found(path))
+    }
+
+    async fn open_read(&self, path: &Path) -> crate::Result<BoxedReader> {
+        let data = self.get(path).ok_or_else(|| not_found(path))?;
+        Ok(Box::pin(MockReader {
+            data,
+            pos: 0,
+            max_read: self.max_read,
+            fail_after: self.fail_reads_after,
+        }))
+    }
+
+    async fn open_write(&self, path: &Path, append: bool) -> crate::Result<BoxedWriter> {
+        {
+            let mut files = self.files.lock().unwrap();
+            let file = files.entry(path.to_path_buf()).or_default();
+            if !append {
+                file.clear();
+            }
+        }
+        Ok(Box::pin(MockWriter {
+            files: self.files.clone(),
+            path: path.to_path_buf(),
+        }))
+    }
+
+    async fn create_dir_all(&self, _path: &Path) -> crate::Result<()> {
+        Ok(())
+    }
+
+    async fn remove_file(&self, path: &Path) -> crate::Result<()> {
+        self.files.lock().unwrap().remove(path);
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> crate::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        let data = files.remove(from).ok_or_else(|| not_found(from))?;
+        files.insert(to.to_path_buf(), data);
+        Ok(())
+    }
+}
+
+fn not_found(path: &Path) -> crate::ProtocolError {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("文件不存在: {}", path.display()),
+    )
+    .into()
+}
+
+struct MockReader {
+    data: Vec<u8>,
+    pos: usize,
+    max_read: Option<usize>,
+    fail_after: Option<usize>,
+}
+
+impl AsyncRead for MockReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut end = self.data.len();
+        if let Some(limit) = self.fail_after {
+            if self.pos >= limit && self.pos < self.data.len() {
+                return Poll::Ready(Err(io::Error::other("注入的读取错误")));
+            }
+            end = end.min(limit);
+        }
+
+        let mut n = (end - self.pos).min(buf.remaining());
+        if let Some(max) = self.max_read {
+            n = n.min(max);
+        }
+        let start = self.pos;
+        buf.put_slice(&self.data[start..start + n]);
+        self.pos += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+struct MockWriter {
+    files: Arc<std::sync::Mutex<HashMap<PathBuf, Vec<u8>>>>,
+    path: PathBuf,
+}
+
+impl AsyncWrite for MockWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.files
+            .lock()
+            .unwrap()
+            .entry(self.path.clone())
+            .or_default()
+            .extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,135 @@
+//! 全局带宽限速
+//!
+//! 同一进程中的发送和接收共用一个 [`Throttle`]，合计速度不超过配置的上限。
+//! 上限按 [`BandwidthWindow`] 随本地时间变化，每读取一块数据都重新取当前时段的上限，
+//! 时段切换时进行中的传输无需中断即按新的上限继续。
+//! 设置了 [`Metered`] 时，省流状态下的上限与时段上限取较小者
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use chrono::Timelike;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+use crate::metered::Metered;
+use crate::{BandwidthWindow, LocalSendConfig};
+
+/// 带宽限速器
+///
+/// 克隆后共享同一份额度
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    limit: Option<u64>,
+    schedule: Arc<[BandwidthWindow]>,
+    metered: Metered,
+    /// 已分配额度用到的时刻
+    next: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Throttle {
+    /// limit 为时段之外的上限 (字节/秒)，为空时不限速
+    pub fn new(limit: Option<u64>, schedule: Vec<BandwidthWindow>) -> Self {
+        Self {
+            limit,
+            schedule: schedule.into(),
+            metered: Metered::default(),
+            next: Arc::default(),
+        }
+    }
+
+    /// 省流状态下按 metered 的上限限速
+    pub fn with_metered(mut self, metered: Metered) -> Self {
+        self.metered = metered;
+        self
+    }
+
+    /// 使用配置中的 `bandwidth_limit` 和 `bandwidth_schedule`
+    pub fn from_config(config: &LocalSendConfig) -> Self {
+        Self::new(config.bandwidth_limit, config.bandwidth_schedule.clone())
+    }
+
+    /// 是否配置了任何上限，未配置时可使用 sendfile 等无法限速的快速路径
+    pub fn is_limited(&self) -> bool {
+        self.limit.is_some()
+            || self.schedule.iter().any(|w| w.limit.is_some())
+            || self.metered.may_limit()
+    }
+
+    /// 一天中第 minute 分钟 (本地时间) 的上限
+    pub fn limit_at(&self, minute: u32) -> Option<u64> {
+        match self.schedule.iter().find(|w| w.contains(minute)) {
+            Some(window) => window.limit,
+            None => self.limit,
+        }
+    }
+
+    /// 当前的上限
+    pub fn current_limit(&self) -> Option<u64> {
+        if !self.is_limited() {
+            return None;
+        }
+        let now = chrono::Local::now();
+        let scheduled = self.limit_at(now.hour() * 60 + now.minute());
+        match (scheduled, self.metered.limit()) {
+            (Some(scheduled), Some(metered)) => Some(scheduled.min(metered)),
+            (scheduled, metered) => scheduled.or(metered),
+        }
+    }
+
+    /// 记录传输了 bytes 字节，返回继续传输前需等待的时长
+    pub fn consume(&self, bytes: usize) -> Option<Duration> {
+        let limit = self.current_limit()?;
+        let cost = Duration::from_secs_f64(bytes as f64 / limit as f64);
+        let now = Instant::now();
+        let mut next = self.next.lock().unwrap();
+        let end = next.map_or(now, |next| next.max(now)) + cost;
+        *next = Some(end);
+        Some(end - now).filter(|delay| !delay.is_zero())
+    }
+}
+
+/// 按 [`Throttle`] 限速的读取端
+#[derive(Debug)]
+pub struct Throttled<R> {
+    inner: R,
+    throttle: Throttle,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<R> Throttled<R> {
+    pub fn new(inner: R, throttle: Throttle) -> Self {
+        Self {
+            inner,
+            throttle,
+            delay: None,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Throttled<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        // 先读后等：上一块数据的额度用完后才读取下一块
+        if let Some(delay) = &mut self.delay {
+            std::task::ready!(delay.as_mut().poll(cx));
+            self.delay = None;
+        }
+        let filled = buf.filled().len();
+        std::task::ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = buf.filled().len() - filled;
+        if read > 0 {
+            self.delay = self
+                .throttle
+                .consume(read)
+                .map(|delay| Box::pin(tokio::time::sleep(delay)));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
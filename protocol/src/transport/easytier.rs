@@ -0,0 +1,137 @@
+//! EasyTier 隧道传输
+//!
+//! 直接通过 EasyTier 隧道连接传输文件流，不经过 TUN 网卡和 HTTP
+//! 在无 TUN 模式下同样可用
+
+use std::net::{Ipv4Addr, SocketAddr};
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+use easytier::tunnel::packet_def::ZCPacket;
+use easytier::tunnel::tcp::{TcpTunnelConnector, TcpTunnelListener};
+use easytier::tunnel::{Tunnel, TunnelConnector, TunnelError, TunnelListener};
+
+use super::{BoxedStream, Transport, TransportListener};
+
+/// 单个隧道包承载的最大数据量 (16KB)
+const MAX_PAYLOAD_SIZE: usize = 16 * 1024;
+
+/// 本地桥接缓冲区大小 (256KB)
+const BRIDGE_BUFFER_SIZE: usize = 256 * 1024;
+
+fn tunnel_error(e: TunnelError) -> std::io::Error {
+    match e {
+        TunnelError::IOError(e) => e,
+        e => std::io::Error::other(e.to_string()),
+    }
+}
+
+fn tunnel_url(addr: SocketAddr) -> url::Url {
+    easytier::tunnel::build_url_from_socket_addr(&addr.to_string(), "tcp")
+}
+
+/// 将隧道桥接为字节流
+///
+/// 隧道是面向包的，这里通过内存管道把它转换为 AsyncRead/AsyncWrite
+fn bridge(tunnel: Box<dyn Tunnel>) -> DuplexStream {
+    let (local, remote) = tokio::io::duplex(BRIDGE_BUFFER_SIZE);
+    let (mut remote_read, mut remote_write) = tokio::io::split(remote);
+    let (mut tunnel_stream, mut tunnel_sink) = tunnel.split();
+
+    // 本地写入 -> 隧道
+    tokio::spawn(async move {
+        // 持有隧道直到发送结束
+        let _tunnel = tunnel;
+        let mut buf = vec![0u8; MAX_PAYLOAD_SIZE];
+        loop {
+            match remote_read.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if tunnel_sink.send(ZCPacket::new_with_payload(&buf[..n])).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        let _ = tunnel_sink.close().await;
+    });
+
+    // 隧道 -> 本地读取
+    tokio::spawn(async move {
+        while let Some(Ok(packet)) = tunnel_stream.next().await {
+            if remote_write.write_all(packet.payload()).await.is_err() {
+                break;
+            }
+        }
+        let _ = remote_write.shutdown().await;
+    });
+
+    local
+}
+
+/// EasyTier 隧道传输
+#[derive(Debug, Clone, Default)]
+pub struct EasyTierTransport;
+
+impl EasyTierTransport {
+    /// 创建新的 EasyTier 隧道传输
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// 在指定地址上监听
+    pub async fn listen(&self, addr: SocketAddr) -> crate::Result<EasyTierListener> {
+        let mut listener = TcpTunnelListener::new(tunnel_url(addr));
+        listener.listen().await.map_err(tunnel_error)?;
+        Ok(EasyTierListener { listener, addr })
+    }
+}
+
+#[async_trait]
+impl Transport for EasyTierTransport {
+    fn name(&self) -> &str {
+        "easytier"
+    }
+
+    async fn connect(&self, addr: SocketAddr) -> crate::Result<BoxedStream> {
+        let mut connector = TcpTunnelConnector::new(tunnel_url(addr));
+        let tunnel = connector.connect().await.map_err(tunnel_error)?;
+        Ok(Box::pin(bridge(tunnel)))
+    }
+}
+
+/// EasyTier 隧道监听器
+pub struct EasyTierListener {
+    listener: TcpTunnelListener,
+    addr: SocketAddr,
+}
+
+impl std::fmt::Debug for EasyTierListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EasyTierListener")
+            .field("addr", &self.addr)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl TransportListener for EasyTierListener {
+    async fn accept(&mut self) -> crate::Result<(BoxedStream, SocketAddr)> {
+        let tunnel = self.listener.accept().await.map_err(tunnel_error)?;
+
+        let peer_addr = tunnel
+            .info()
+            .and_then(|info| info.remote_addr)
+            .and_then(|u| url::Url::parse(&u.url).ok())
+            .and_then(|u| u.socket_addrs(|| None).ok())
+            .and_then(|addrs| addrs.into_iter().next())
+            .unwrap_or_else(|| SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)));
+
+        Ok((Box::pin(bridge(tunnel)), peer_addr))
+    }
+
+    fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
@@ -0,0 +1,396 @@
+//! 传输层抽象
+//!
+//! 定义文件流的传输接口，使文件数据可以不经过 HTTP 直接在对端之间传输
+//! 具体实现见各子模块，稀疏文件只发送数据区段，见 [`sparse`]
+
+#[cfg(feature = "easytier-tunnel")]
+pub mod easytier;
+#[cfg(feature = "quic")]
+pub mod quic;
+pub mod sparse;
+pub mod tcp;
+
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::fs::File;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite,
+    AsyncWriteExt, BufReader,
+};
+use crate::hash::HashPipeline;
+use crate::storage;
+use crate::ProtocolError;
+
+/// 流头部最大长度 (64KB)
+const MAX_HEADER_LEN: usize = 64 * 1024;
+
+/// 文件内容拷贝缓冲区 (256KB)，默认的 8KB 在 2.5GbE 下系统调用开销明显
+const COPY_BUFFER_SIZE: usize = 256 * 1024;
+
+/// 传输流
+pub trait TransportStream: AsyncRead + AsyncWrite + Send {}
+
+impl<T> TransportStream for T where T: AsyncRead + AsyncWrite + Send {}
+
+/// 装箱的传输流
+pub type BoxedStream = Pin<Box<dyn TransportStream>>;
+
+/// 共享的传输实现
+pub type TransportRef = Arc<dyn Transport>;
+
+/// 传输层接口
+#[async_trait]
+pub trait Transport: Debug + Send + Sync {
+    /// 传输名称
+    fn name(&self) -> &str;
+
+    /// 连接到远端
+    async fn connect(&self, addr: SocketAddr) -> crate::Result<BoxedStream>;
+
+    /// 连接到远端并发送本地文件，返回的流可继续用于关闭写端和等待确认
+    ///
+    /// 实现可覆盖为零拷贝路径
+    async fn send_local_file(
+        &self,
+        addr: SocketAddr,
+        header: &StreamHeader,
+        path: &Path,
+    ) -> crate::Result<BoxedStream> {
+        let mut stream = self.connect(addr).await?;
+        send_file(&mut stream, header, path).await?;
+        Ok(stream)
+    }
+}
+
+/// 传输层监听器
+#[async_trait]
+pub trait TransportListener: Debug + Send {
+    /// 接受新的连接
+    async fn accept(&mut self) -> crate::Result<(BoxedStream, SocketAddr)>;
+
+    /// 本地监听地址
+    fn local_addr(&self) -> SocketAddr;
+}
+
+/// 选择双方都支持的传输
+///
+/// 按本端的优先顺序匹配对方公告的传输，没有交集时返回 None，
+/// 调用方应回退到 HTTP 上传，以兼容普通 LocalSend 对端。
+/// 公告项为 `名称` 或 `名称:端口`，返回值中的端口为 None 时使用设备的 HTTP 端口
+pub fn negotiate<'a>(
+    local: &'a [TransportRef],
+    remote: &[String],
+) -> Option<(&'a TransportRef, Option<u16>)> {
+    local.iter().find_map(|transport| {
+        remote
+            .iter()
+            .map(|entry| parse_capability(entry))
+            .find(|(name, _)| *name == transport.name())
+            .map(|(_, port)| (transport, port))
+    })
+}
+
+/// 解析公告项，端口无效时视为未指定
+pub fn parse_capability(entry: &str) -> (&str, Option<u16>) {
+    match entry.split_once(':') {
+        Some((name, port)) => (name, port.parse().ok()),
+        None => (entry, None),
+    }
+}
+
+/// 文件流头部
+///
+/// 每个文件流以 4 字节大端长度 + JSON 头部开始，随后是文件内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamHeader {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    #[serde(rename = "fileId")]
+    pub file_id: String,
+    #[serde(default)]
+    pub token: String,
+    pub size: u64,
+    /// 续传时流中内容在文件中的起始偏移，此前的内容接收端已写入 `.part` 文件
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub offset: u64,
+    /// 稀疏文件的数据区段，给出时流中只依次包含各区段的内容
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse: Option<Vec<sparse::Extent>>,
+}
+
+impl StreamHeader {
+    /// 流中文件内容的长度
+    pub fn body_len(&self) -> u64 {
+        self.sparse
+            .as_deref()
+            .map_or(self.size.saturating_sub(self.offset), sparse::data_len)
+    }
+
+    /// 检查偏移，偏移超出文件长度或与稀疏区段同时给出时返回错误
+    pub fn check_offset(&self) -> crate::Result<()> {
+        if self.offset > self.size || (self.offset > 0 && self.sparse.is_some()) {
+            return Err(ProtocolError::InvalidData(format!("无效的续传偏移: {}", self.offset)));
+        }
+        Ok(())
+    }
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+/// 写入文件流头部
+pub async fn write_header<S>(stream: &mut S, header: &StreamHeader) -> crate::Result<()>
+where
+    S: AsyncWrite + Unpin + ?Sized,
+{
+    let data = serde_json::to_vec(header)?;
+    stream.write_u32(data.len() as u32).await?;
+    stream.write_all(&data).await?;
+    Ok(())
+}
+
+/// 读取文件流头部
+pub async fn read_header<S>(stream: &mut S) -> crate::Result<StreamHeader>
+where
+    S: AsyncRead + Unpin + ?Sized,
+{
+    let len = stream.read_u32().await? as usize;
+    if len > MAX_HEADER_LEN {
+        return Err(ProtocolError::InvalidData(format!("流头部过长: {}", len)));
+    }
+
+    let mut data = vec![0u8; len];
+    stream.read_exact(&mut data).await?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// 通过传输流发送文件，头部带有稀疏区段时只发送各区段的内容，续传时从偏移处发送
+pub async fn send_file<S>(
+    stream: &mut S,
+    header: &StreamHeader,
+    path: &Path,
+) -> crate::Result<u64>
+where
+    S: AsyncWrite + Unpin + ?Sized,
+{
+    header.check_offset()?;
+    let mut file = File::open(path).await?;
+    let Some(extents) = &header.sparse else {
+        file.seek(std::io::SeekFrom::Start(header.offset)).await?;
+        return send_reader(stream, header, &mut file).await;
+    };
+
+    write_header(stream, header).await?;
+    let mut sent = 0;
+    for &(offset, len) in extents {
+        file.seek(std::io::SeekFrom::Start(offset)).await?;
+        sent += send_body(stream, &mut file, len).await?;
+    }
+    stream.flush().await?;
+    Ok(sent)
+}
+
+/// 通过传输流发送读取端中的内容
+///
+/// 最多发送 [`StreamHeader::body_len`] 字节，读取端提前结束时返回错误。
+/// 稀疏文件的读取端应依次给出各数据区段的内容
+pub async fn send_reader<S, R>(
+    stream: &mut S,
+    header: &StreamHeader,
+    reader: &mut R,
+) -> crate::Result<u64>
+where
+    S: AsyncWrite + Unpin + ?Sized,
+    R: AsyncRead + Unpin + ?Sized,
+{
+    write_header(stream, header).await?;
+    let sent = send_body(stream, reader, header.body_len()).await?;
+    stream.flush().await?;
+    Ok(sent)
+}
+
+/// 从读取端发送 len 字节内容，读取端提前结束时返回错误
+pub async fn send_body<S, R>(stream: &mut S, reader: &mut R, len: u64) -> crate::Result<u64>
+where
+    S: AsyncWrite + Unpin + ?Sized,
+    R: AsyncRead + Unpin + ?Sized,
+{
+    let mut body = BufReader::with_capacity(COPY_BUFFER_SIZE, reader.take(len));
+    let sent = tokio::io::copy_buf(&mut body, stream).await?;
+    check_len(len, sent)?;
+    Ok(sent)
+}
+
+fn check_len(expected: u64, actual: u64) -> std::io::Result<()> {
+    if actual != expected {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("文件长度不符: 期望 {}，实际 {}", expected, actual),
+        ));
+    }
+    Ok(())
+}
+
+/// 从传输流接收文件内容到指定路径
+///
+/// 头部需已通过 [`read_header`] 读取。内容先写入头部中会话和文件对应的 [`storage::part_path`]，
+/// 完整写入后才改名为 path；中途断开时保留 `.part` 文件，可据此续传。
+/// 头部带有偏移时保留 `.part` 中此前的内容，从偏移处继续写入，`.part` 短于偏移时返回错误。
+/// 给出 `sha256` 时边写入边校验，不一致则删除文件并返回 [`ProtocolError::HashMismatch`]。
+/// 头部带有稀疏区段时只写入各区段，空洞保留为文件系统中的空洞，按全 0 参与校验
+pub async fn receive_file<S>(
+    stream: &mut S,
+    header: &StreamHeader,
+    path: &Path,
+    sha256: Option<&str>,
+) -> crate::Result<u64>
+where
+    S: AsyncRead + Unpin + ?Sized,
+{
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    header.check_offset()?;
+    if let Some(extents) = &header.sparse {
+        sparse::check_extents(extents, header.size)?;
+    }
+
+    let part = storage::part_path(path, &header.session_id, &header.file_id);
+    let pipeline = sha256.map(|_| HashPipeline::new());
+    let mut file = if header.offset == 0 {
+        File::create(&part).await?
+    } else {
+        resume_part(&part, header.offset, pipeline.as_ref()).await?
+    };
+    let received = match &header.sparse {
+        Some(extents) => {
+            receive_extents(stream, &mut file, extents, header.size, pipeline.as_ref()).await?
+        }
+        None => receive_body(stream, &mut file, header.body_len(), pipeline.as_ref()).await?,
+    };
+    file.flush().await?;
+
+    if let (Some(pipeline), Some(expected)) = (pipeline, sha256) {
+        if let Err(e) = pipeline.verify(expected, &header.file_id).await {
+            drop(file);
+            let _ = tokio::fs::remove_file(&part).await;
+            return Err(e);
+        }
+    }
+
+    file.sync_all().await?;
+    drop(file);
+    tokio::fs::rename(&part, path).await?;
+    Ok(received)
+}
+
+/// 打开已有的 `.part` 文件准备从 offset 处续写，之后的内容被截去
+///
+/// 给出哈希流水线时先送入前 offset 字节
+async fn resume_part(
+    part: &Path,
+    offset: u64,
+    pipeline: Option<&HashPipeline>,
+) -> crate::Result<File> {
+    let mut file = tokio::fs::OpenOptions::new().read(true).write(true).open(part).await?;
+    let len = file.metadata().await?.len();
+    if len < offset {
+        return Err(ProtocolError::InvalidData(format!(
+            "续传偏移 {} 超出已接收的长度 {}",
+            offset, len
+        )));
+    }
+    file.set_len(offset).await?;
+    if let Some(pipeline) = pipeline {
+        let mut prefix = BufReader::with_capacity(COPY_BUFFER_SIZE, (&mut file).take(offset));
+        copy_hashed(&mut prefix, &mut tokio::io::sink(), pipeline).await?;
+    }
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+    Ok(file)
+}
+
+/// 从流中接收 len 字节写入文件，流提前结束时返回错误
+async fn receive_body<S>(
+    stream: &mut S,
+    file: &mut File,
+    len: u64,
+    pipeline: Option<&HashPipeline>,
+) -> crate::Result<u64>
+where
+    S: AsyncRead + Unpin + ?Sized,
+{
+    let mut body = BufReader::with_capacity(COPY_BUFFER_SIZE, stream.take(len));
+    let received = match pipeline {
+        Some(pipeline) => copy_hashed(&mut body, file, pipeline).await?,
+        None => tokio::io::copy_buf(&mut body, file).await?,
+    };
+    check_len(len, received)?;
+    Ok(received)
+}
+
+/// 把各数据区段写到文件中对应的偏移，再将文件截到 size，返回 size
+async fn receive_extents<S>(
+    stream: &mut S,
+    file: &mut File,
+    extents: &[sparse::Extent],
+    size: u64,
+    pipeline: Option<&HashPipeline>,
+) -> crate::Result<u64>
+where
+    S: AsyncRead + Unpin + ?Sized,
+{
+    let zeros = pipeline.map(|_| vec![0u8; COPY_BUFFER_SIZE]);
+    let mut position = 0u64;
+    for &(offset, len) in extents {
+        if let (Some(pipeline), Some(zeros)) = (pipeline, &zeros) {
+            hash_zeros(pipeline, zeros, offset - position).await?;
+        }
+        file.flush().await?;
+        file.seek(std::io::SeekFrom::Start(offset)).await?;
+        receive_body(&mut *stream, file, len, pipeline).await?;
+        position = offset + len;
+    }
+    if let (Some(pipeline), Some(zeros)) = (pipeline, &zeros) {
+        hash_zeros(pipeline, zeros, size - position).await?;
+    }
+    file.flush().await?;
+    file.set_len(size).await?;
+    Ok(size)
+}
+
+/// 将 len 字节的 0 送入哈希流水线
+async fn hash_zeros(pipeline: &HashPipeline, zeros: &[u8], mut len: u64) -> crate::Result<()> {
+    while len > 0 {
+        let n = len.min(zeros.len() as u64) as usize;
+        pipeline.update(&zeros[..n]).await?;
+        len -= n as u64;
+    }
+    Ok(())
+}
+
+/// 拷贝内容，同时将每块数据送入哈希流水线
+async fn copy_hashed<R, W>(reader: &mut R, writer: &mut W, pipeline: &HashPipeline) -> crate::Result<u64>
+where
+    R: AsyncBufRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+{
+    let mut copied = 0u64;
+    loop {
+        let buf = reader.fill_buf().await?;
+        if buf.is_empty() {
+            return Ok(copied);
+        }
+        let len = buf.len();
+        pipeline.update(buf).await?;
+        writer.write_all(buf).await?;
+        reader.consume(len);
+        copied += len as u64;
+    }
+}
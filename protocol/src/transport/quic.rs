@@ -0,0 +1,334 @@
+//! QUIC 传输
+//!
+//! 同一对端的文件流复用一条 QUIC 连接，每个文件占用一个双向流，
+//! 多文件会话不会因单个流丢包而互相阻塞
+//!
+//! 证书在启动时自签生成，客户端不校验证书，安全性与 LocalSend 的 HTTP 模式相同
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn::{
+    ClientConfig, Connection, ConnectionError, Endpoint, EndpointConfig, RecvStream, SendStream,
+    ServerConfig, TransportConfig,
+};
+use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::{BoxedStream, Transport, TransportListener};
+use crate::{net, IpMode, ProtocolError};
+
+/// 公告中使用的传输名称
+pub const QUIC_TRANSPORT: &str = "quic";
+
+/// TLS ALPN 标识
+const ALPN: &[u8] = b"peersend";
+
+/// 证书中的服务器名称，客户端不校验
+const SERVER_NAME: &str = "peersend";
+
+/// 单条连接上允许同时打开的文件流数量
+const MAX_CONCURRENT_STREAMS: u32 = 256;
+
+/// 监听器中等待被取走的流数量
+const ACCEPT_BACKLOG: usize = 64;
+
+/// QUIC 传输
+///
+/// 按对端地址缓存连接，克隆后共享同一个端点
+#[derive(Debug, Clone)]
+pub struct QuicTransport {
+    endpoint: Endpoint,
+    connections: Arc<Mutex<HashMap<SocketAddr, Connection>>>,
+    ip_mode: IpMode,
+}
+
+impl QuicTransport {
+    /// 创建客户端端点，需在 tokio 运行时中调用
+    ///
+    /// 端点为双栈，可连接 IPv4 和 IPv6 对端，本机不支持 IPv6 时只用 IPv4
+    pub fn new() -> crate::Result<Self> {
+        let socket = net::bind_udp(SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)), IpMode::Dual)?;
+        let mut endpoint = endpoint(socket, None)?;
+        endpoint.set_default_client_config(client_config()?);
+
+        Ok(Self {
+            endpoint,
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            ip_mode: IpMode::Dual,
+        })
+    }
+
+    /// 在 IPv6 通配地址上监听时是否同时接受 IPv4，默认双栈
+    pub fn with_ip_mode(mut self, ip_mode: IpMode) -> Self {
+        self.ip_mode = ip_mode;
+        self
+    }
+
+    /// 在指定 UDP 地址上监听
+    pub async fn listen(&self, addr: SocketAddr) -> crate::Result<QuicListener> {
+        let socket = net::bind_udp(addr, self.ip_mode)?;
+        let endpoint = endpoint(socket, Some(server_config()?))?;
+        let addr = endpoint.local_addr()?;
+        let (tx, streams) = mpsc::channel(ACCEPT_BACKLOG);
+
+        let task = tokio::spawn(accept_loop(endpoint.clone(), tx));
+
+        Ok(QuicListener {
+            endpoint,
+            streams,
+            addr,
+            task,
+        })
+    }
+
+    /// 获取到对端的连接，已断开的缓存连接会被替换
+    async fn connection(&self, addr: SocketAddr) -> crate::Result<Connection> {
+        let mut connections = self.connections.lock().await;
+        if let Some(connection) = connections.get(&addr) {
+            if connection.close_reason().is_none() {
+                return Ok(connection.clone());
+            }
+        }
+
+        debug!(peer = %addr, "建立 QUIC 连接");
+        let connection = self
+            .endpoint
+            .connect(addr, SERVER_NAME)
+            .map_err(quic_error)?
+            .await
+            .map_err(|e| match e {
+                // 握手超时说明地址不可达，调用方可改用对方的其他地址
+                ConnectionError::TimedOut => ProtocolError::Io(io::ErrorKind::TimedOut.into()),
+                e => quic_error(e),
+            })?;
+        connections.insert(addr, connection.clone());
+        Ok(connection)
+    }
+}
+
+#[async_trait]
+impl Transport for QuicTransport {
+    fn name(&self) -> &str {
+        QUIC_TRANSPORT
+    }
+
+    async fn connect(&self, addr: SocketAddr) -> crate::Result<BoxedStream> {
+        let connection = self.connection(addr).await?;
+        let (send, recv) = connection.open_bi().await.map_err(quic_error)?;
+        Ok(Box::pin(QuicStream { send, recv }))
+    }
+}
+
+/// QUIC 监听器
+///
+/// 每个双向流作为一条独立的传输流返回
+#[derive(Debug)]
+pub struct QuicListener {
+    endpoint: Endpoint,
+    streams: mpsc::Receiver<(QuicStream, SocketAddr)>,
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+#[async_trait]
+impl TransportListener for QuicListener {
+    async fn accept(&mut self) -> crate::Result<(BoxedStream, SocketAddr)> {
+        let (stream, addr) = self.streams.recv().await.ok_or_else(|| {
+            ProtocolError::Io(io::Error::new(io::ErrorKind::BrokenPipe, "QUIC 端点已关闭"))
+        })?;
+        Ok((Box::pin(stream), addr))
+    }
+
+    fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for QuicListener {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.endpoint.close(0u32.into(), b"");
+    }
+}
+
+/// 接受新连接，并把每条连接上的双向流转发给监听器
+async fn accept_loop(endpoint: Endpoint, tx: mpsc::Sender<(QuicStream, SocketAddr)>) {
+    while let Some(incoming) = endpoint.accept().await {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let connection = match incoming.await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    warn!(error = %e, "QUIC 握手失败");
+                    return;
+                }
+            };
+            let peer = net::canonical(connection.remote_address());
+            debug!(peer = %peer, "接受 QUIC 连接");
+
+            while let Ok((send, recv)) = connection.accept_bi().await {
+                if tx.send((QuicStream { send, recv }, peer)).await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+/// QUIC 双向流
+#[derive(Debug)]
+pub struct QuicStream {
+    send: SendStream,
+    recv: RecvStream,
+}
+
+impl AsyncRead for QuicStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.recv).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for QuicStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.send).poll_write(cx, buf).map_err(Into::into)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.send).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.send).poll_shutdown(cx)
+    }
+}
+
+fn transport_config() -> Arc<TransportConfig> {
+    let mut config = TransportConfig::default();
+    config
+        .max_concurrent_bidi_streams(MAX_CONCURRENT_STREAMS.into())
+        .max_concurrent_uni_streams(0u32.into())
+        .keep_alive_interval(Some(Duration::from_secs(5)));
+    Arc::new(config)
+}
+
+fn crypto_provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn server_config() -> crate::Result<ServerConfig> {
+    let cert = rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_string()])
+        .map_err(|e| ProtocolError::Crypto(e.to_string()))?;
+    let key = PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der());
+
+    let mut crypto = rustls::ServerConfig::builder_with_provider(crypto_provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(tls_error)?
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.cert.der().clone()], key.into())
+        .map_err(tls_error)?;
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+
+    let crypto = QuicServerConfig::try_from(crypto).map_err(tls_error)?;
+    let mut config = ServerConfig::with_crypto(Arc::new(crypto));
+    config.transport_config(transport_config());
+    Ok(config)
+}
+
+fn client_config() -> crate::Result<ClientConfig> {
+    let provider = crypto_provider();
+    let mut crypto = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(tls_error)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(SkipServerVerification(provider)))
+        .with_no_client_auth();
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+
+    let crypto = QuicClientConfig::try_from(crypto).map_err(tls_error)?;
+    let mut config = ClientConfig::new(Arc::new(crypto));
+    config.transport_config(transport_config());
+    Ok(config)
+}
+
+/// 接受任意证书，仅校验握手签名
+#[derive(Debug)]
+struct SkipServerVerification(Arc<rustls::crypto::CryptoProvider>);
+
+impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp: &[u8],
+        _now: UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// 在已绑定的套接字上创建端点
+fn endpoint(socket: std::net::UdpSocket, server: Option<ServerConfig>) -> crate::Result<Endpoint> {
+    let runtime = quinn::default_runtime()
+        .ok_or_else(|| io::Error::other("QUIC 端点需要在 tokio 运行时中创建"))?;
+    Ok(Endpoint::new(EndpointConfig::default(), server, socket, runtime)?)
+}
+
+fn quic_error(e: impl std::fmt::Display) -> ProtocolError {
+    ProtocolError::Io(io::Error::new(io::ErrorKind::ConnectionAborted, e.to_string()))
+}
+
+fn tls_error(e: impl std::fmt::Display) -> ProtocolError {
+    ProtocolError::Crypto(e.to_string())
+}
@@ -0,0 +1,101 @@
+//! 稀疏文件传输
+//!
+//! 磁盘镜像、虚拟机文件等稀疏文件中大段的空洞读出来全是 0。启用 `sparse` 特性时，
+//! Linux 上发送端经 `SEEK_DATA` / `SEEK_HOLE` 找出文件中的数据区段，
+//! 在 [`StreamHeader::sparse`](super::StreamHeader::sparse) 中带上区段列表，流中只发送各区段的内容。
+//! 接收端把各区段写到对应偏移后将文件截到原长度，空洞不写入磁盘。
+//!
+//! 接收端始终支持稀疏流；空洞过小或区段过多时发送端按普通文件发送
+
+use std::path::Path;
+
+use crate::ProtocolError;
+
+/// 空洞总长不足此值 (1MB) 时按普通文件发送
+pub const MIN_HOLE_SIZE: u64 = 1024 * 1024;
+
+/// 区段数上限，超过时按普通文件发送，避免流头部过长
+pub const MAX_EXTENTS: usize = 1024;
+
+/// 数据区段 (偏移, 长度)
+pub type Extent = (u64, u64);
+
+/// 检查区段按偏移升序、互不重叠且都在文件长度之内
+pub fn check_extents(extents: &[Extent], size: u64) -> crate::Result<()> {
+    let mut position = 0u64;
+    for &(offset, len) in extents {
+        let end = offset.checked_add(len).filter(|end| offset >= position && *end <= size);
+        position = end.ok_or_else(|| {
+            ProtocolError::InvalidData(format!("无效的稀疏区段: {} + {}", offset, len))
+        })?;
+    }
+    Ok(())
+}
+
+/// 区段内容的总长度
+pub fn data_len(extents: &[Extent]) -> u64 {
+    extents.iter().map(|(_, len)| len).sum()
+}
+
+/// 找出 path 前 size 字节中的数据区段
+///
+/// 文件不是稀疏文件、空洞不足 [`MIN_HOLE_SIZE`]、区段超过 [`MAX_EXTENTS`]
+/// 或文件系统不支持时返回 None
+#[cfg(all(feature = "sparse", target_os = "linux"))]
+pub async fn data_extents(path: &Path, size: u64) -> std::io::Result<Option<Vec<Extent>>> {
+    let file = std::fs::File::open(path)?;
+    tokio::task::spawn_blocking(move || scan(&file, size))
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+/// 找出 path 前 size 字节中的数据区段
+///
+/// 此平台无法检测空洞，始终返回 None
+#[cfg(not(all(feature = "sparse", target_os = "linux")))]
+pub async fn data_extents(path: &Path, size: u64) -> std::io::Result<Option<Vec<Extent>>> {
+    let _ = (path, size);
+    Ok(None)
+}
+
+#[cfg(all(feature = "sparse", target_os = "linux"))]
+fn scan(file: &std::fs::File, size: u64) -> std::io::Result<Option<Vec<Extent>>> {
+    let mut extents = Vec::new();
+    let mut position = 0u64;
+    while position < size {
+        let Some(start) = seek(file, position, libc::SEEK_DATA)? else {
+            break;
+        };
+        if start >= size {
+            break;
+        }
+        let end = seek(file, start, libc::SEEK_HOLE)?.unwrap_or(size).min(size);
+        if extents.len() == MAX_EXTENTS {
+            return Ok(None);
+        }
+        extents.push((start, end - start));
+        position = end;
+    }
+    if size - data_len(&extents) < MIN_HOLE_SIZE {
+        return Ok(None);
+    }
+    Ok(Some(extents))
+}
+
+/// 从 offset 起查找下一个数据或空洞的位置，offset 之后没有数据时返回 None
+#[cfg(all(feature = "sparse", target_os = "linux"))]
+fn seek(file: &std::fs::File, offset: u64, whence: libc::c_int) -> std::io::Result<Option<u64>> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: 描述符在调用期间有效，lseek 不涉及内存
+    let n = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+    if n >= 0 {
+        return Ok(Some(n as u64));
+    }
+    let error = std::io::Error::last_os_error();
+    if error.raw_os_error() == Some(libc::ENXIO) {
+        Ok(None)
+    } else {
+        Err(error)
+    }
+}
@@ -0,0 +1,153 @@
+//! 裸 TCP 传输
+//!
+//! 文件流直接写入 TCP 连接，省去 HTTP 分块编码开销，适合可信局域网中的高速传输。
+//! 连接不加密，需要单独的端口，公告形式为 `tcp:<端口>`。
+//! 启用 `sendfile` 特性后，Linux 上发送本地文件由内核直接从页缓存写入套接字。
+//! 连接启用系统的保活探测，见 [`keepalive`](crate::keepalive)
+
+use std::net::SocketAddr;
+#[cfg(all(feature = "sendfile", target_os = "linux"))]
+use std::path::Path;
+
+use async_trait::async_trait;
+use tokio::net::{TcpListener, TcpStream};
+
+use super::{BoxedStream, Transport, TransportListener};
+use crate::{keepalive, net, IpMode};
+#[cfg(all(feature = "sendfile", target_os = "linux"))]
+use super::{write_header, StreamHeader};
+
+/// 公告中使用的传输名称
+pub const TCP_TRANSPORT: &str = "tcp";
+
+/// 裸 TCP 传输
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpTransport {
+    ip_mode: IpMode,
+}
+
+impl TcpTransport {
+    /// 创建新的 TCP 传输
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 在 IPv6 通配地址上监听时是否同时接受 IPv4，默认双栈
+    pub fn with_ip_mode(mut self, ip_mode: IpMode) -> Self {
+        self.ip_mode = ip_mode;
+        self
+    }
+
+    /// 在指定地址上监听
+    pub async fn listen(&self, addr: SocketAddr) -> crate::Result<TcpTransportListener> {
+        let listener = net::bind_tcp(addr, self.ip_mode)?;
+        listener.set_nonblocking(true)?;
+        let listener = TcpListener::from_std(listener)?;
+        let addr = listener.local_addr()?;
+        Ok(TcpTransportListener { listener, addr })
+    }
+}
+
+#[async_trait]
+impl Transport for TcpTransport {
+    fn name(&self) -> &str {
+        TCP_TRANSPORT
+    }
+
+    async fn connect(&self, addr: SocketAddr) -> crate::Result<BoxedStream> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        keepalive::set_tcp_keepalive(&stream)?;
+        Ok(Box::pin(stream))
+    }
+
+    #[cfg(all(feature = "sendfile", target_os = "linux"))]
+    async fn send_local_file(
+        &self,
+        addr: SocketAddr,
+        header: &StreamHeader,
+        path: &Path,
+    ) -> crate::Result<BoxedStream> {
+        header.check_offset()?;
+        let file = tokio::fs::File::open(path).await?.into_std().await;
+        let mut stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        keepalive::set_tcp_keepalive(&stream)?;
+
+        write_header(&mut stream, header).await?;
+        let whole = [(header.offset, header.size - header.offset)];
+        for &(offset, len) in header.sparse.as_deref().unwrap_or(&whole) {
+            let sent = sendfile(&stream, &file, offset, len).await?;
+            if sent != len {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    format!("文件长度不符: 期望 {}，实际 {}", len, sent),
+                )
+                .into());
+            }
+        }
+
+        Ok(Box::pin(stream))
+    }
+}
+
+/// 单次 sendfile 的最大字节数，避免长时间占用运行时线程
+#[cfg(all(feature = "sendfile", target_os = "linux"))]
+const SENDFILE_CHUNK: u64 = 4 * 1024 * 1024;
+
+/// 从文件的 start 处起发送 len 字节，文件提前结束时返回实际发送的字节数
+#[cfg(all(feature = "sendfile", target_os = "linux"))]
+async fn sendfile(
+    stream: &TcpStream,
+    file: &std::fs::File,
+    start: u64,
+    len: u64,
+) -> std::io::Result<u64> {
+    use std::os::fd::AsRawFd;
+    use tokio::io::Interest;
+
+    let mut offset = start as libc::off_t;
+    let mut sent = 0u64;
+    while sent < len {
+        let count = (len - sent).min(SENDFILE_CHUNK) as usize;
+        let n = stream
+            .async_io(Interest::WRITABLE, || {
+                // SAFETY: 两个描述符在调用期间均有效，offset 指向本地变量
+                let n = unsafe {
+                    libc::sendfile(stream.as_raw_fd(), file.as_raw_fd(), &mut offset, count)
+                };
+                if n < 0 {
+                    Err(std::io::Error::last_os_error())
+                } else {
+                    Ok(n as u64)
+                }
+            })
+            .await?;
+        if n == 0 {
+            break;
+        }
+        sent += n;
+    }
+    Ok(sent)
+}
+
+/// 裸 TCP 监听器
+#[derive(Debug)]
+pub struct TcpTransportListener {
+    listener: TcpListener,
+    addr: SocketAddr,
+}
+
+#[async_trait]
+impl TransportListener for TcpTransportListener {
+    async fn accept(&mut self) -> crate::Result<(BoxedStream, SocketAddr)> {
+        let (stream, peer) = self.listener.accept().await?;
+        stream.set_nodelay(true)?;
+        keepalive::set_tcp_keepalive(&stream)?;
+        Ok((Box::pin(stream), net::canonical(peer)))
+    }
+
+    fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
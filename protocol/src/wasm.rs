@@ -0,0 +1,125 @@
+//! 浏览器端发送器
+//!
+//! 基于 fetch 实现 prepare-upload / upload 流程，网页可直接把用户选择的文件发送给
+//! PeerSend 接收端。编译:
+//!
+//! ```text
+//! cargo rustc -p peersend-protocol --lib --release --target wasm32-unknown-unknown \
+//!     --no-default-features --features wasm --crate-type cdylib
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/peersend_protocol.wasm
+//! ```
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{File, Request, RequestInit, Response};
+
+use crate::dto::{IncomingFileMetadata, PrepareRequest, PrepareResponse};
+
+/// 浏览器端发送器
+#[wasm_bindgen]
+pub struct WasmSender {
+    base_url: String,
+    device_id: String,
+    token: String,
+}
+
+#[wasm_bindgen]
+impl WasmSender {
+    /// 创建指向接收端的发送器
+    #[wasm_bindgen(constructor)]
+    pub fn new(ip: &str, port: u16, use_tls: bool) -> WasmSender {
+        let scheme = if use_tls { "https" } else { "http" };
+        WasmSender {
+            base_url: format!("{}://{}", scheme, crate::net::host_port(ip, port)),
+            device_id: uuid::Uuid::new_v4().to_string(),
+            token: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+
+    /// 发送文件，files 为 `File` 数组，返回会话 ID
+    #[wasm_bindgen(js_name = sendFiles)]
+    pub async fn send_files(&self, files: js_sys::Array) -> Result<String, JsValue> {
+        let files: Vec<File> = files
+            .iter()
+            .map(|f| f.dyn_into::<File>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| JsValue::from_str("只能发送 File 对象"))?;
+
+        let metadata: Vec<IncomingFileMetadata> = files
+            .iter()
+            .map(|f| IncomingFileMetadata {
+                id: uuid::Uuid::new_v4().to_string(),
+                name: f.name(),
+                file_type: f.type_(),
+                size: f.size() as u64,
+                save_as: None,
+                sha256: None,
+                metadata: None,
+            })
+            .collect();
+
+        let prepare = PrepareRequest {
+            id: self.device_id.clone(),
+            session_id: uuid::Uuid::new_v4().to_string(),
+            files: metadata.clone(),
+            token: self.token.clone(),
+        };
+        let body = serde_json::to_string(&prepare).map_err(to_js_error)?;
+        let response = self
+            .post(
+                &format!("{}/api/v1/localsend/prepare-upload", self.base_url),
+                &JsValue::from_str(&body),
+                Some("application/json"),
+            )
+            .await?;
+        let response: PrepareResponse = serde_json::from_str(&response).map_err(to_js_error)?;
+
+        for accepted in &response.files {
+            let Some(index) = metadata.iter().position(|m| m.id == accepted.id) else {
+                continue;
+            };
+            let url = format!(
+                "{}/api/v1/localsend/upload?sessionId={}&fileId={}&token={}",
+                self.base_url,
+                js_sys::encode_uri_component(&prepare.session_id),
+                js_sys::encode_uri_component(&accepted.id),
+                js_sys::encode_uri_component(&self.token),
+            );
+            self.post(&url, &files[index], None).await?;
+        }
+
+        Ok(prepare.session_id)
+    }
+
+    async fn post(
+        &self,
+        url: &str,
+        body: &JsValue,
+        content_type: Option<&str>,
+    ) -> Result<String, JsValue> {
+        let init = RequestInit::new();
+        init.set_method("POST");
+        init.set_body(body);
+
+        let request = Request::new_with_str_and_init(url, &init)?;
+        if let Some(content_type) = content_type {
+            request.headers().set("Content-Type", content_type)?;
+        }
+
+        let window = web_sys::window().ok_or_else(|| JsValue::from_str("没有可用的 window"))?;
+        let response: Response = JsFuture::from(window.fetch_with_request(&request))
+            .await?
+            .dyn_into()?;
+        if !response.ok() {
+            return Err(JsValue::from_str(&format!("HTTP 状态码 {}", response.status())));
+        }
+
+        let text = JsFuture::from(response.text()?).await?;
+        Ok(text.as_string().unwrap_or_default())
+    }
+}
+
+fn to_js_error(e: impl std::fmt::Display) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
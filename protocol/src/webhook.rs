@@ -0,0 +1,213 @@
+//! 传输生命周期的 Webhook 通知
+//!
+//! 接收服务收到请求、会话完成或失败时向配置的 URL 发送 POST 请求，
+//! 配合 Slack、Matrix、ntfy 或家庭自动化服务在文件到达时发出提醒。
+//!
+//! 未设置模板时请求体为固定格式的 JSON。模板中可使用以下占位符：
+//! `{{event}}`、`{{session_id}}`、`{{sender}}`、`{{files}}` (逗号分隔的文件名)、
+//! `{{count}}`、`{{size}}` (总字节数) 和 `{{error}}`。
+//! 模板中的占位符替换为空字符串后是合法 JSON 时，替换的值会按 JSON 字符串转义
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::http::{HttpBody, HttpClientConfig, HttpClientRef, HttpRequest, ReqwestClient};
+use crate::{FileInfo, ProtocolEvent, SessionManager, SessionState, WebhookConfig, WebhookEvent};
+
+/// 单次通知的超时
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 一次待发送的通知
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub event: WebhookEvent,
+    pub session_id: String,
+    pub sender: String,
+    pub files: Vec<NotifiedFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 通知中的文件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NotifiedFile {
+    pub name: String,
+    pub size: u64,
+}
+
+impl Notification {
+    fn new(
+        event: WebhookEvent,
+        session_id: String,
+        sender: String,
+        files: &[FileInfo],
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            event,
+            session_id,
+            sender,
+            files: files
+                .iter()
+                .map(|f| NotifiedFile {
+                    name: f.name.clone(),
+                    size: f.size,
+                })
+                .collect(),
+            error,
+        }
+    }
+
+    /// 按模板生成请求体，模板为空时使用默认 JSON
+    pub fn render(&self, template: Option<&str>) -> Vec<u8> {
+        let Some(template) = template else {
+            return serde_json::to_vec(self).unwrap_or_default();
+        };
+
+        let files = self
+            .files
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let values = [
+            ("event", self.event.as_str().to_string()),
+            ("session_id", self.session_id.clone()),
+            ("sender", self.sender.clone()),
+            ("files", files),
+            ("count", self.files.len().to_string()),
+            ("size", self.files.iter().map(|f| f.size).sum::<u64>().to_string()),
+            ("error", self.error.clone().unwrap_or_default()),
+        ];
+
+        let placeholder = |key: &str| format!("{{{{{}}}}}", key);
+        let blank = values
+            .iter()
+            .fold(template.to_string(), |t, (key, _)| t.replace(&placeholder(key), ""));
+        let json = serde_json::from_str::<serde_json::Value>(&blank).is_ok();
+
+        let mut body = template.to_string();
+        for (key, value) in values {
+            let value = if json {
+                // 去掉 JSON 字符串两端的引号，只保留转义后的内容
+                let quoted = serde_json::to_string(&value).unwrap_or_default();
+                quoted[1..quoted.len() - 1].to_string()
+            } else {
+                value
+            };
+            body = body.replace(&placeholder(key), &value);
+        }
+        body.into_bytes()
+    }
+}
+
+/// Webhook 通知器
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    hooks: Arc<Vec<WebhookConfig>>,
+    http: HttpClientRef,
+}
+
+impl WebhookNotifier {
+    /// 创建通知器，使用校验证书的 HTTP 客户端
+    pub fn new(hooks: Vec<WebhookConfig>) -> crate::Result<Self> {
+        let config = HttpClientConfig::default()
+            .with_accept_invalid_certs(false)
+            .with_certificate_pinning(false)
+            .with_timeout(WEBHOOK_TIMEOUT);
+        Ok(Self {
+            hooks: Arc::new(hooks),
+            http: Arc::new(ReqwestClient::with_config(&config)?),
+        })
+    }
+
+    /// 使用自定义 HTTP 客户端
+    pub fn with_http(mut self, http: HttpClientRef) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// 向订阅了该事件的所有 Webhook 发送通知，失败只记录日志
+    pub async fn notify(&self, notification: &Notification) {
+        let requests = self
+            .hooks
+            .iter()
+            .filter(|hook| hook.events.is_empty() || hook.events.contains(&notification.event))
+            .map(|hook| async move {
+                let mut request = HttpRequest::post(&hook.url);
+                request.body = HttpBody::Json(notification.render(hook.template.as_deref()));
+                match self.http.execute(request).await.and_then(|r| r.error_for_status()) {
+                    Ok(_) => debug!(url = %hook.url, event = notification.event.as_str(), "Webhook 已发送"),
+                    Err(e) => warn!(url = %hook.url, error = %e, "发送 Webhook 失败"),
+                }
+            });
+        futures::future::join_all(requests).await;
+    }
+
+    /// 把会话事件转换为通知并发送，直到事件总线关闭或 cancel 取消
+    ///
+    /// sessions 用于查询快速保存等未发布请求事件的会话
+    pub async fn run(
+        self,
+        mut events: broadcast::Receiver<ProtocolEvent>,
+        sessions: SessionManager,
+        cancel: CancellationToken,
+    ) {
+        // 会话 ID 到发送方名称和文件，来自请求事件
+        let mut requested: HashMap<String, (String, Vec<FileInfo>)> = HashMap::new();
+
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = events.recv() => event,
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "Webhook 处理过慢，丢失部分事件");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            let notification = match event {
+                ProtocolEvent::SessionRequested {
+                    session_id,
+                    sender,
+                    files,
+                } => {
+                    requested.insert(session_id.clone(), (sender.clone(), files.clone()));
+                    Notification::new(WebhookEvent::Requested, session_id, sender, &files, None)
+                }
+                ProtocolEvent::SessionStateChanged { session_id, state } => {
+                    let (event, error) = match state {
+                        SessionState::Finished => (WebhookEvent::Completed, None),
+                        SessionState::Cancelled => (WebhookEvent::Failed, Some("已取消".to_string())),
+                        SessionState::Error(message) => (WebhookEvent::Failed, Some(message)),
+                        SessionState::Waiting | SessionState::Transferring => continue,
+                    };
+                    let (sender, files) = match requested.remove(&session_id) {
+                        Some(known) => known,
+                        None => match sessions.get_session(&session_id).await {
+                            Some(session) => (session.sender_id.clone(), session.files.clone()),
+                            None => (String::new(), Vec::new()),
+                        },
+                    };
+                    Notification::new(event, session_id, sender, &files, error)
+                }
+                _ => continue,
+            };
+
+            // 不阻塞事件接收
+            let notifier = self.clone();
+            tokio::spawn(async move { notifier.notify(&notification).await });
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! 候选地址测试
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+use peersend_protocol::discovery::candidate_addresses;
+use peersend_protocol::testing::peer_device;
+use peersend_protocol::{net, AnnouncementMessage, DeviceInfo, LocalSendClient, LocalSendConfig};
+
+fn device(ip: &str, port: u16, addresses: &[&str]) -> DeviceInfo {
+    DeviceInfo {
+        ip: ip.to_string(),
+        port,
+        version: "0.1.0-peersend".to_string(),
+        addresses: addresses.iter().map(|a| a.to_string()).collect(),
+        ..peer_device()
+    }
+}
+
+#[test]
+fn configured_addresses_come_first() {
+    let config = LocalSendConfig {
+        addresses: vec!["10.126.126.3".to_string(), "[fd00::3]".to_string()],
+        ..LocalSendConfig::default()
+    };
+    let addresses = candidate_addresses(&config);
+    assert_eq!(addresses[..2], ["10.126.126.3", "fd00::3"]);
+    let unique: std::collections::HashSet<_> = addresses.iter().collect();
+    assert_eq!(unique.len(), addresses.len());
+
+    let invalid = LocalSendConfig {
+        addresses: vec!["tun0".to_string()],
+        ..LocalSendConfig::default()
+    };
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+fn addresses_are_optional_in_announcements() {
+    let json = r#"{"type":"announce","id":"a","deviceType":"desktop","name":"a",
+        "version":"2.1.0","protocolVersion":"2.0"}"#;
+    let message: AnnouncementMessage = serde_json::from_str(json).unwrap();
+    assert!(message.addresses.is_empty());
+    assert!(!serde_json::to_string(&message).unwrap().contains("addresses"));
+}
+
+#[test]
+fn route_source_is_a_local_address() {
+    let source = net::route_source(Ipv4Addr::LOCALHOST.into());
+    assert_eq!(source, Some(IpAddr::from(Ipv4Addr::LOCALHOST)));
+}
+
+#[tokio::test]
+async fn first_reachable_address_is_used() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let client = LocalSendClient::new(LocalSendConfig::default());
+
+    // 公告来源 127.0.0.2 上没有监听，候选地址 127.0.0.1 可连接
+    let announced = device("127.0.0.2", port, &["127.0.0.2", "127.0.0.1"]);
+    assert_eq!(client.reachable(&announced).await.ip, "127.0.0.1");
+
+    // 没有候选地址的 LocalSend 设备不探测
+    let localsend = device("127.0.0.2", port, &[]);
+    assert_eq!(client.reachable(&localsend).await.ip, "127.0.0.2");
+
+    // 全部不可达时保留来源地址
+    drop(listener);
+    let unreachable = device("127.0.0.2", port, &["127.0.0.1"]);
+    let reached = tokio::time::timeout(Duration::from_secs(5), client.reachable(&unreachable));
+    assert_eq!(reached.await.unwrap().ip, "127.0.0.2");
+}
@@ -0,0 +1,310 @@
+//! 管理 API 测试
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::audit::{AuditAction, AuditEntry, AuditLog, AuditRecord};
+use peersend_protocol::control::{Controller, SessionView};
+use peersend_protocol::server::accept::AcceptGate;
+use peersend_protocol::server::api::{self, EventRecord, EventSubscription, ManagementApi};
+use peersend_protocol::testing::peer_device;
+use peersend_protocol::{
+    DeviceInfo, DiscoveryManager, FileInfo, LocalSendClient, LocalSendConfig, ProtocolEvent,
+    SessionManager, SessionState,
+};
+use reqwest::StatusCode;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+const TOKEN: &str = "secret-token";
+
+struct Api {
+    base: String,
+    sessions: SessionManager,
+    discovery: DiscoveryManager,
+    accept: AcceptGate,
+    http: reqwest::Client,
+    _cancel: tokio_util::sync::DropGuard,
+}
+
+impl Api {
+    fn start(audit: Option<&AuditLog>) -> Self {
+        let sessions = SessionManager::new();
+        let discovery = DiscoveryManager::new();
+        let accept = AcceptGate::new();
+        let client =
+            LocalSendClient::new(LocalSendConfig::default()).with_session_manager(sessions.clone());
+        let mut controller = Controller::new(
+            client,
+            Arc::new(Mutex::new(sessions.clone())),
+            Arc::new(Mutex::new(discovery.clone())),
+            accept.clone(),
+        );
+        if let Some(audit) = audit {
+            controller = controller.with_audit_log(audit.path());
+        }
+        let api = ManagementApi::new(TOKEN, controller);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let cancel = CancellationToken::new();
+        tokio::spawn(Arc::new(api).serve(listener, cancel.clone()));
+        Self {
+            base,
+            sessions,
+            discovery,
+            accept,
+            http: reqwest::Client::new(),
+            _cancel: cancel.drop_guard(),
+        }
+    }
+
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .get(format!("{}{}", self.base, path))
+            .bearer_auth(TOKEN)
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .post(format!("{}{}", self.base, path))
+            .bearer_auth(TOKEN)
+    }
+}
+
+fn file() -> FileInfo {
+    FileInfo {
+        id: "file".to_string(),
+        name: "photo.jpg".to_string(),
+        size: 42,
+        file_type: "image/jpeg".to_string(),
+        metadata: None,
+        sha256: None,
+    }
+}
+
+#[tokio::test]
+async fn requests_require_token() {
+    let api = Api::start(None);
+    let url = format!("{}/api/sessions", api.base);
+
+    let response = api.http.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = api.http.get(&url).bearer_auth("wrong").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = api.get("/api/sessions").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = api.get("/api/unknown").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn serves_admin_page_without_token() {
+    let api = Api::start(None);
+
+    let response = api.http.get(&api.base).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-security-policy"]
+        .to_str()
+        .unwrap()
+        .contains("default-src 'self'"));
+    assert!(response.text().await.unwrap().contains("/app.js"));
+
+    let response = api
+        .http
+        .get(format!("{}/app.js", api.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/javascript"));
+
+    let response = api
+        .http
+        .get(format!("{}/missing.js", api.base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn pending_session_can_be_accepted() {
+    let api = Api::start(None);
+    let session = api
+        .sessions
+        .create_session("peer".to_string(), "me".to_string(), vec![file()])
+        .await;
+    let accept = api.accept.clone();
+    let id = session.id.clone();
+    let decision = tokio::spawn(async move { accept.wait(&id, Duration::from_secs(5)).await });
+    while api.accept.pending().is_empty() {
+        tokio::task::yield_now().await;
+    }
+
+    let sessions: Vec<SessionView> = api
+        .get("/api/sessions")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, session.id);
+    assert_eq!(sessions[0].state, "waiting");
+    assert!(sessions[0].pending);
+    assert_eq!(sessions[0].total_bytes, 42);
+
+    let response = api
+        .post(&format!("/api/sessions/{}/accept", session.id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(decision.await.unwrap());
+
+    // 已答复的会话不能再次答复
+    let response = api
+        .post(&format!("/api/sessions/{}/reject", session.id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = api.get("/api/sessions/missing").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn gate_can_be_opened_and_closed() {
+    let api = Api::start(None);
+    let remaining = |response: reqwest::Response| async move {
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        body["remaining"].as_u64()
+    };
+    assert_eq!(remaining(api.get("/api/gate").send().await.unwrap()).await, None);
+
+    let response = api.post("/api/gate").json(&serde_json::json!({"secs": 600})).send();
+    assert_eq!(remaining(response.await.unwrap()).await, Some(600));
+    assert!(api.accept.is_open());
+
+    let response = api.post("/api/gate").json(&serde_json::json!({"secs": 0})).send();
+    assert_eq!(response.await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+    let response = api.http.delete(format!("{}/api/gate", api.base)).bearer_auth(TOKEN);
+    assert_eq!(remaining(response.send().await.unwrap()).await, None);
+    assert!(!api.accept.is_open());
+}
+
+#[tokio::test]
+async fn lists_devices_and_rejects_unknown_send_target() {
+    let api = Api::start(None);
+    api.discovery
+        .add_device(DeviceInfo {
+            id: "phone".to_string(),
+            name: "Phone".to_string(),
+            device_type: "mobile".to_string(),
+            ip: "192.0.2.7".to_string(),
+            version: "2.0".to_string(),
+            ..peer_device()
+        })
+        .await;
+
+    let devices: Vec<DeviceInfo> = api
+        .get("/api/devices")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].name, "Phone");
+
+    let response = api
+        .post("/api/send")
+        .json(&serde_json::json!({ "device": "laptop", "paths": ["/tmp/a.txt"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = api.post("/api/send").body("not json").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn history_returns_latest_records() {
+    let dir = tempfile::tempdir().unwrap();
+    let audit = AuditLog::open(dir.path().join("audit.jsonl")).unwrap();
+    for action in [AuditAction::Requested, AuditAction::Accepted, AuditAction::Completed] {
+        audit.record(AuditEntry::new(action)).unwrap();
+    }
+    let api = Api::start(Some(&audit));
+
+    let records: Vec<AuditRecord> = api
+        .get("/api/history?limit=2")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let actions: Vec<_> = records.iter().map(|r| r.entry.action).collect();
+    assert_eq!(actions, vec![AuditAction::Accepted, AuditAction::Completed]);
+
+    let without_audit = Api::start(None);
+    let response = without_audit.get("/api/history").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+async fn next_event(subscription: &mut EventSubscription) -> EventRecord {
+    let next = tokio::time::timeout(Duration::from_secs(5), subscription.next());
+    next.await.unwrap().unwrap().expect("事件流未结束")
+}
+
+#[tokio::test]
+async fn events_are_streamed_and_replayed() {
+    let api = Api::start(None);
+    let response = api.http.get(format!("{}/api/events", api.base)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let mut live = EventSubscription::connect(&api.base, TOKEN, None).await.unwrap();
+    let events = api.sessions.events();
+    events.emit(ProtocolEvent::SessionStateChanged {
+        session_id: "s1".to_string(),
+        state: SessionState::Error("磁盘已满".to_string()),
+    });
+    events.emit(ProtocolEvent::FileProgress {
+        session_id: "s1".to_string(),
+        file_id: "file".to_string(),
+        bytes_transferred: 10,
+        total_bytes: 42,
+    });
+
+    let record = next_event(&mut live).await;
+    assert_eq!(record.id, 1);
+    assert_eq!(
+        record.event,
+        serde_json::json!({
+            "event": "sessionStateChanged",
+            "sessionId": "s1",
+            "state": "error",
+            "error": "磁盘已满",
+        })
+    );
+    let record = next_event(&mut live).await;
+    assert_eq!(record.id, 2);
+    assert_eq!(record.event["event"], "fileProgress");
+    assert_eq!(record.event["bytesTransferred"], 10);
+
+    // 进度事件不补发
+    let recent = api::recent_events(&api.base, TOKEN).await.unwrap();
+    assert_eq!(recent.iter().map(|r| r.id).collect::<Vec<_>>(), [1]);
+    let mut replayed = EventSubscription::connect(&api.base, TOKEN, Some(0)).await.unwrap();
+    assert_eq!(next_event(&mut replayed).await.id, 1);
+}
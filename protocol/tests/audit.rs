@@ -0,0 +1,245 @@
+//! 审计日志测试
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::audit::{self, AuditAction, AuditEntry, AuditLog, AuditRecord};
+use peersend_protocol::dto::v2::{FileDto, PrepareUploadRequestDto, RegisterDto, PROTOCOL_VERSION};
+use peersend_protocol::server::accept::AcceptGate;
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::{
+    DiscoveryManager, FileInfo, LocalSendConfig, ProtocolError, QuickSave, SessionManager,
+    SessionState,
+};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+fn records(path: &std::path::Path) -> Vec<AuditRecord> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn request(fingerprint: &str) -> PrepareUploadRequestDto {
+    let file = FileDto {
+        id: "file".to_string(),
+        file_name: "contract.pdf".to_string(),
+        size: 42,
+        file_type: "application/pdf".to_string(),
+        sha256: Some("ab".repeat(32)),
+        preview: None,
+        metadata: None,
+    };
+    PrepareUploadRequestDto {
+        info: RegisterDto {
+            alias: "Phone".to_string(),
+            version: PROTOCOL_VERSION.to_string(),
+            device_model: None,
+            device_type: Some("mobile".to_string()),
+            fingerprint: fingerprint.to_string(),
+            port: 53317,
+            protocol: "http".to_string(),
+            download: false,
+        },
+        files: HashMap::from([("file".to_string(), file)]),
+    }
+}
+
+#[test]
+fn chain_detects_tampering() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.jsonl");
+    assert_eq!(audit::verify(&path).unwrap().records, 0);
+
+    let log = AuditLog::open(&path).unwrap();
+    log.record(AuditEntry::new(AuditAction::Requested)).unwrap();
+    log.record(AuditEntry::new(AuditAction::Accepted)).unwrap();
+    // 重新打开后接续已有的链
+    let reopened = AuditLog::open(&path).unwrap();
+    reopened
+        .record(AuditEntry::new(AuditAction::Completed))
+        .unwrap();
+
+    let mut exported = Vec::new();
+    let summary = audit::export(&path, &mut exported).unwrap();
+    assert_eq!(summary.records, 3);
+    assert_eq!(exported, std::fs::read(&path).unwrap());
+    let seqs: Vec<u64> = records(&path).iter().map(|r| r.seq).collect();
+    assert_eq!(seqs, [1, 2, 3]);
+
+    let original = std::fs::read_to_string(&path).unwrap();
+    let tampered = original.replacen("accepted", "rejected", 1);
+    std::fs::write(&path, tampered).unwrap();
+    assert!(matches!(
+        audit::verify(&path),
+        Err(ProtocolError::InvalidData(_))
+    ));
+
+    let mut lines: Vec<&str> = original.lines().collect();
+    lines.remove(1);
+    std::fs::write(&path, lines.join("\n")).unwrap();
+    assert!(matches!(
+        audit::verify(&path),
+        Err(ProtocolError::InvalidData(_))
+    ));
+}
+
+#[tokio::test]
+async fn server_records_requests_and_decisions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.jsonl");
+    let config = LocalSendConfig {
+        quick_save: QuickSave::On,
+        blocklist: vec!["prankster".to_string()],
+        ..LocalSendConfig::default()
+    };
+    let server = LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        config,
+        Arc::new(Mutex::new(SessionManager::new())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    )
+    .with_accept_timeout(Duration::from_secs(5))
+    .with_audit_log(AuditLog::open(&path).unwrap());
+
+    let response = server
+        .prepare_upload("192.0.2.5", request("phone"), None)
+        .await
+        .unwrap();
+    assert!(server
+        .prepare_upload("192.0.2.6", request("prankster"), None)
+        .await
+        .is_err());
+
+    let records = records(&path);
+    let actions: Vec<AuditAction> = records.iter().map(|r| r.entry.action).collect();
+    assert_eq!(
+        actions,
+        [
+            AuditAction::Requested,
+            AuditAction::Accepted,
+            AuditAction::Rejected
+        ]
+    );
+    let requested = &records[0].entry;
+    assert_eq!(
+        requested.session_id.as_deref(),
+        Some(response.session_id.as_str())
+    );
+    assert_eq!(requested.peer.as_deref(), Some("phone"));
+    assert_eq!(requested.ip.as_deref(), Some("192.0.2.5"));
+    assert_eq!(requested.files[0].sha256, Some("ab".repeat(32)));
+    assert_eq!(records[2].entry.peer.as_deref(), Some("prankster"));
+    assert_eq!(audit::verify(&path).unwrap().records, 3);
+}
+
+#[tokio::test]
+async fn open_gate_accepts_until_closed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.jsonl");
+    let config = LocalSendConfig {
+        blocklist: vec!["prankster".to_string()],
+        ..LocalSendConfig::default()
+    };
+    let accept = AcceptGate::new();
+    let server = LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        config,
+        Arc::new(Mutex::new(SessionManager::new())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    )
+    .with_accept_timeout(Duration::from_millis(100))
+    .with_accept_gate(accept.clone())
+    .with_audit_log(AuditLog::open(&path).unwrap());
+
+    accept.open_for(Duration::from_secs(60));
+    assert!(accept.open_remaining().unwrap() > Duration::from_secs(59));
+    assert!(server.prepare_upload("192.0.2.5", request("phone"), None).await.is_ok());
+    // 开放期间仍拒绝屏蔽的设备
+    assert!(server
+        .prepare_upload("192.0.2.6", request("prankster"), None)
+        .await
+        .is_err());
+
+    accept.close();
+    assert!(!accept.is_open());
+    assert!(server.prepare_upload("192.0.2.5", request("phone"), None).await.is_err());
+
+    let reasons: Vec<(AuditAction, Option<String>)> = records(&path)
+        .into_iter()
+        .filter(|r| r.entry.action != AuditAction::Requested)
+        .map(|r| (r.entry.action, r.entry.reason))
+        .collect();
+    assert_eq!(reasons[0], (AuditAction::Accepted, Some("开放接收".to_string())));
+    assert_eq!(reasons[1].0, AuditAction::Rejected);
+    assert_eq!(
+        reasons[2],
+        (AuditAction::Rejected, Some("用户拒绝或超时".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn transfer_results_are_recorded() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.jsonl");
+    let sessions = SessionManager::new();
+    let cancel = CancellationToken::new();
+    let task = tokio::spawn(AuditLog::open(&path).unwrap().run(
+        sessions.events().subscribe(),
+        sessions.clone(),
+        "me".to_string(),
+        cancel.clone(),
+    ));
+
+    let file = FileInfo {
+        id: "f".to_string(),
+        name: "report.xlsx".to_string(),
+        size: 3,
+        file_type: "application/octet-stream".to_string(),
+        metadata: None,
+        sha256: None,
+    };
+    let sent = sessions
+        .create_session("me".to_string(), "printer".to_string(), vec![file.clone()])
+        .await;
+    sessions
+        .set_state(&sent.id, SessionState::Transferring)
+        .await;
+    sessions.set_state(&sent.id, SessionState::Finished).await;
+    // 未开始传输就取消的会话是被拒绝的请求，不记为失败
+    let rejected = sessions
+        .create_session("phone".to_string(), "me".to_string(), vec![file.clone()])
+        .await;
+    sessions
+        .set_state(&rejected.id, SessionState::Cancelled)
+        .await;
+    let failed = sessions
+        .create_session("phone".to_string(), "me".to_string(), vec![file])
+        .await;
+    sessions
+        .set_state(&failed.id, SessionState::Error("磁盘已满".to_string()))
+        .await;
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    cancel.cancel();
+    task.await.unwrap();
+
+    let records = records(&path);
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].entry.action, AuditAction::Completed);
+    assert_eq!(records[0].entry.peer.as_deref(), Some("printer"));
+    assert_eq!(records[0].entry.files[0].name, "report.xlsx");
+    assert_eq!(records[1].entry.action, AuditAction::Failed);
+    assert_eq!(records[1].entry.peer.as_deref(), Some("phone"));
+    assert_eq!(records[1].entry.reason.as_deref(), Some("磁盘已满"));
+
+    // 记录附带会话摘要，完成时未传输的文件记为跳过
+    let summary = records[0].entry.summary.as_ref().unwrap();
+    assert_eq!((summary.transferred, summary.skipped, summary.failed), (0, 1, 0));
+    let summary = records[1].entry.summary.as_ref().unwrap();
+    assert_eq!((summary.state.as_str(), summary.failed), ("error", 1));
+    assert_eq!(summary.error.as_deref(), Some("磁盘已满"));
+}
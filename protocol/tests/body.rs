@@ -0,0 +1,50 @@
+//! 文件响应体与 Range 解析测试
+
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use peersend_protocol::fs::FileSystemRef;
+use peersend_protocol::server::body::{parse_range, ByteRange, FileBody};
+use peersend_protocol::testing::MockFs;
+use peersend_protocol::ProtocolError;
+
+#[test]
+fn ranges_are_clamped_to_the_file() {
+    let range = |start, end| Some(ByteRange { start, end });
+
+    assert_eq!(parse_range("bytes=0-99", 1000).unwrap(), range(0, 99));
+    assert_eq!(parse_range("bytes=900-", 1000).unwrap(), range(900, 999));
+    assert_eq!(parse_range("bytes=-100", 1000).unwrap(), range(900, 999));
+    assert_eq!(parse_range("bytes=-5000", 1000).unwrap(), range(0, 999));
+    assert_eq!(parse_range("bytes=500-5000", 1000).unwrap(), range(500, 999));
+    assert_eq!(range(500, 999).unwrap().content_range(1000), "bytes 500-999/1000");
+}
+
+#[test]
+fn unsupported_ranges_fall_back_to_full_body() {
+    assert_eq!(parse_range("items=0-1", 1000).unwrap(), None);
+    assert_eq!(parse_range("bytes=0-1,5-6", 1000).unwrap(), None);
+    assert_eq!(parse_range("bytes=5-1", 1000).unwrap(), None);
+    assert!(matches!(parse_range("bytes=1000-", 1000), Err(ProtocolError::Status(416))));
+    assert!(matches!(parse_range("bytes=-0", 1000), Err(ProtocolError::Status(416))));
+}
+
+#[tokio::test]
+async fn body_streams_requested_range() {
+    let fs = MockFs::new().with_short_reads(3);
+    fs.insert("/share/a.txt", b"0123456789".to_vec());
+    let fs: FileSystemRef = Arc::new(fs);
+
+    let body = FileBody::open(&fs, "/share/a.txt".as_ref(), parse_range("bytes=2-6", 10).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(body.len(), 5);
+    let chunks: Vec<_> = body.try_collect().await.unwrap();
+    assert_eq!(chunks.concat(), b"23456");
+
+    let body = FileBody::open(&fs, "/share/a.txt".as_ref(), None).await.unwrap();
+    assert_eq!(body.len(), 10);
+    assert!(body.range().is_none());
+    let chunks: Vec<_> = body.try_collect().await.unwrap();
+    assert_eq!(chunks.concat(), b"0123456789");
+}
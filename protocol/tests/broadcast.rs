@@ -0,0 +1,114 @@
+//! 一次读取、多设备发送测试
+
+use std::sync::Arc;
+
+use peersend_protocol::session::TransferManager;
+use peersend_protocol::testing::{peer_device, MockFs, MockHttp};
+use peersend_protocol::transport::tcp::{TcpTransport, TCP_TRANSPORT};
+use peersend_protocol::transport::{self, TransportListener};
+use peersend_protocol::{DeviceInfo, LocalSendClient, LocalSendConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn data(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+fn device(id: &str, port: u16) -> DeviceInfo {
+    DeviceInfo {
+        id: id.to_string(),
+        name: id.to_string(),
+        ip: "127.0.0.1".to_string(),
+        transports: vec![format!("{}:{}", TCP_TRANSPORT, port)],
+        ..peer_device()
+    }
+}
+
+/// 接受一条流并读出文件内容，读完后关闭流作为确认
+async fn receive(listener: &mut impl TransportListener) -> Vec<u8> {
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let header = transport::read_header(&mut stream).await.unwrap();
+    let mut body = vec![0u8; header.size as usize];
+    stream.read_exact(&mut body).await.unwrap();
+    stream.shutdown().await.unwrap();
+    body
+}
+
+#[tokio::test]
+async fn every_reader_gets_the_whole_stream() {
+    let data = data(3 * 1024 * 1024 + 17);
+    let (pump, readers) = TransferManager::broadcast(&data[..], 3);
+    let reads = readers.into_iter().map(|mut reader| async move {
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).await.unwrap();
+        body
+    });
+
+    let (read, bodies) = tokio::join!(pump, futures::future::join_all(reads));
+    assert_eq!(read.unwrap(), data.len() as u64);
+    assert_eq!(bodies.len(), 3);
+    assert!(bodies.iter().all(|body| *body == data));
+}
+
+#[tokio::test]
+async fn dropped_reader_does_not_stall_others() {
+    // 超过每个读取端的缓冲，被丢弃的读取端若仍占位会使读取停住
+    let data = data(8 * 1024 * 1024);
+    let (pump, mut readers) = TransferManager::broadcast(&data[..], 2);
+    drop(readers.pop());
+    let mut reader = readers.pop().unwrap();
+    let read = async {
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).await.unwrap();
+        body
+    };
+
+    let (total, body) = tokio::join!(pump, read);
+    assert_eq!(total.unwrap(), data.len() as u64);
+    assert!(body == data);
+}
+
+#[tokio::test]
+async fn client_broadcasts_to_every_device() {
+    let mut first = TcpTransport::new().listen("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let mut second = TcpTransport::new().listen("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    // 绑定后立即释放的端口，连接被拒绝
+    let closed = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let devices = [
+        device("first", first.local_addr().port()),
+        device("second", second.local_addr().port()),
+        device("offline", closed),
+    ];
+
+    let http = MockHttp::new();
+    http.route("/prepare-upload", |request| request.accept_files(|_| true));
+    let contents = data(2 * 1024 * 1024 + 5);
+    let fs = MockFs::new();
+    fs.insert("/src/video.mp4", contents.clone());
+    let client = LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_fs(Arc::new(fs))
+        .with_transport(Arc::new(TcpTransport::new()));
+
+    let (sent, first_body, second_body) = tokio::join!(
+        client.broadcast_files(&devices, &["/src/video.mp4"]),
+        receive(&mut first),
+        receive(&mut second),
+    );
+
+    let sent = sent.unwrap();
+    assert_eq!(sent.len(), 3);
+    assert_eq!(sent[0].as_ref().unwrap().receiver_id, "first");
+    assert_eq!(sent[1].as_ref().unwrap().receiver_id, "second");
+    assert!(sent[2].is_err());
+    assert!(first_body == contents);
+    assert!(second_body == contents);
+    // 每台设备一次 prepare-upload，上传失败的设备随后收到取消
+    let requests = http.requests();
+    assert_eq!(requests.len(), 4);
+    assert!(requests[3].url.ends_with("/cancel"));
+    assert_eq!(requests[3].query_param("sessionId"), Some("remote"));
+}
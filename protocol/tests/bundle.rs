@@ -0,0 +1,74 @@
+//! 配置包导出与导入测试
+
+use peersend_protocol::config::ConfigBundle;
+use peersend_protocol::{LocalSendConfig, MqttConfig, ProtocolError};
+
+fn config(dir: &std::path::Path) -> LocalSendConfig {
+    LocalSendConfig {
+        download_dir: dir.to_string_lossy().into_owned(),
+        favorites: vec!["laptop".to_string()],
+        blocklist: vec!["10.0.0.0/8".to_string()],
+        pin: Some("pin-secret".to_string()),
+        browse_port: Some(8080),
+        browse_password: Some("browse-secret".to_string()),
+        mqtt: Some(MqttConfig {
+            broker: "mqtt://broker.local:1883".to_string(),
+            topic: "peersend".to_string(),
+            username: Some("user".to_string()),
+            password: Some("mqtt-secret".to_string()),
+        }),
+        ..LocalSendConfig::default()
+    }
+}
+
+#[test]
+fn bundles_round_trip_with_secrets() {
+    let dir = tempfile::tempdir().unwrap();
+    let exported = config(dir.path());
+    let path = dir.path().join("bundle.json");
+    ConfigBundle::new(&exported).save(&path).unwrap();
+
+    let bundle = ConfigBundle::load(&path).unwrap();
+    assert!(!bundle.redacted);
+    let imported = bundle.into_config(&LocalSendConfig::default()).unwrap();
+    assert_eq!(imported.device_id, exported.device_id);
+    assert_eq!(imported.api_key, exported.api_key);
+    assert_eq!(imported.favorites, ["laptop"]);
+    assert_eq!(imported.blocklist, ["10.0.0.0/8"]);
+    assert_eq!(imported.pin.as_deref(), Some("pin-secret"));
+}
+
+#[test]
+fn redacted_bundles_keep_local_secrets() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = ConfigBundle::new(&config(dir.path())).redact_secrets();
+    let text = serde_json::to_string(&bundle).unwrap();
+    for secret in ["pin-secret", "browse-secret", "mqtt-secret"] {
+        assert!(!text.contains(secret));
+    }
+    assert!(bundle.config.api_key.is_empty());
+
+    // 本机没有浏览页面密码时无法启用浏览页面
+    assert!(bundle.clone().into_config(&LocalSendConfig::default()).is_err());
+
+    let local = LocalSendConfig {
+        api_key: "local-key".to_string(),
+        ..config(dir.path())
+    };
+    let imported = bundle.into_config(&local).unwrap();
+    assert_eq!(imported.api_key, "local-key");
+    assert_eq!(imported.pin.as_deref(), Some("pin-secret"));
+    assert_eq!(imported.browse_password.as_deref(), Some("browse-secret"));
+    assert_eq!(imported.mqtt.unwrap().password.as_deref(), Some("mqtt-secret"));
+}
+
+#[test]
+fn newer_bundles_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut bundle = ConfigBundle::new(&config(dir.path()));
+    bundle.version = ConfigBundle::VERSION + 1;
+    assert!(matches!(
+        bundle.into_config(&LocalSendConfig::default()),
+        Err(ProtocolError::InvalidConfig(_))
+    ));
+}
@@ -0,0 +1,174 @@
+//! 剪贴板同步测试
+
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use peersend_protocol::clipboard::{Clipboard, ClipboardSync};
+use peersend_protocol::dto::{FileRequest, FileResponse};
+use peersend_protocol::http::HttpResponse;
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::testing::{peer_device, MockHttp};
+use peersend_protocol::{
+    DiscoveryManager, EventBus, LocalSendClient, LocalSendConfig, ProtocolError, ProtocolEvent,
+    SessionManager,
+};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Clone, Default)]
+struct MockClipboard(Arc<StdMutex<Option<String>>>);
+
+impl MockClipboard {
+    fn copy(&self, text: &str) {
+        *self.0.lock().unwrap() = Some(text.to_string());
+    }
+
+    fn text(&self) -> Option<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Clipboard for MockClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        self.text()
+    }
+
+    fn set_text(&mut self, text: &str) -> peersend_protocol::Result<()> {
+        self.copy(text);
+        Ok(())
+    }
+}
+
+fn text_from(sender_id: &str, text: &str) -> ProtocolEvent {
+    ProtocolEvent::TextReceived {
+        sender_id: sender_id.to_string(),
+        sender: sender_id.to_string(),
+        text: text.to_string(),
+    }
+}
+
+/// 已发送的文本消息
+fn sent(http: &MockHttp) -> Vec<String> {
+    http.requests()
+        .iter()
+        .map(|r| serde_json::from_slice::<FileRequest>(&r.body).unwrap().message)
+        .collect()
+}
+
+async fn settle() {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn syncs_both_ways_without_echo() {
+    let http = MockHttp::new();
+    http.route("/request", |request| {
+        let request: FileRequest = serde_json::from_slice(&request.body).unwrap();
+        let response = FileResponse {
+            id: "peer".to_string(),
+            session_id: request.session_id,
+            accepted: true,
+            token: String::new(),
+        };
+        HttpResponse {
+            status: 200,
+            body: serde_json::to_vec(&response).unwrap(),
+        }
+    });
+    let config = LocalSendConfig {
+        favorites: vec!["peer".to_string()],
+        ..LocalSendConfig::default()
+    };
+    let client = LocalSendClient::new(config).with_http(http.clone());
+    let sync = ClipboardSync::new(client, "peer", 16).with_interval(Duration::from_millis(10));
+    let clipboard = MockClipboard::default();
+    // 启动前已有的内容不推送
+    clipboard.copy("before");
+    let events = EventBus::default();
+    let cancel = CancellationToken::new();
+    let task = {
+        let (clipboard, events, cancel) = (clipboard.clone(), events.subscribe(), cancel.clone());
+        tokio::spawn(async move { sync.sync_with(&peer_device(), clipboard, events, cancel).await })
+    };
+    settle().await;
+    assert!(sent(&http).is_empty());
+
+    clipboard.copy("hello");
+    settle().await;
+    assert_eq!(sent(&http), ["hello"]);
+
+    // 写入收到的文本后不会再推送回去
+    events.emit(text_from("peer", "world"));
+    settle().await;
+    assert_eq!(clipboard.text().as_deref(), Some("world"));
+    assert_eq!(sent(&http), ["hello"]);
+
+    // 其他设备的文本和超长的文本都被忽略
+    events.emit(text_from("stranger", "spam"));
+    events.emit(text_from("peer", "a very long text over the limit"));
+    clipboard.copy("this one is far too long");
+    settle().await;
+    assert_eq!(clipboard.text().as_deref(), Some("this one is far too long"));
+    assert_eq!(sent(&http), ["hello"]);
+
+    cancel.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn refuses_unpaired_device() {
+    let client = LocalSendClient::new(LocalSendConfig::default()).with_http(MockHttp::new());
+    let sync = ClipboardSync::new(client, "peer", 1024);
+    let result = sync
+        .sync_with(
+            &peer_device(),
+            MockClipboard::default(),
+            EventBus::default().subscribe(),
+            CancellationToken::new(),
+        )
+        .await;
+    assert!(matches!(result, Err(ProtocolError::Rejected(_))));
+}
+
+#[tokio::test]
+async fn server_publishes_text_with_sender_id() {
+    let events = EventBus::default();
+    let mut received = events.subscribe();
+    let config = LocalSendConfig {
+        blocklist: vec!["prankster".to_string()],
+        ..LocalSendConfig::default()
+    };
+    let server = LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        config,
+        Arc::new(Mutex::new(SessionManager::with_event_bus(events))),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    );
+    let request = |id: &str| FileRequest {
+        id: id.to_string(),
+        sender: "Phone".to_string(),
+        sender_type: "mobile".to_string(),
+        files: Vec::new(),
+        session_id: "s1".to_string(),
+        token: String::new(),
+        message: "copied".to_string(),
+    };
+
+    let response = server.receive_text("192.0.2.5", request("phone")).await.unwrap();
+    assert!(response.accepted);
+    match received.recv().await.unwrap() {
+        ProtocolEvent::TextReceived {
+            sender_id,
+            sender,
+            text,
+        } => {
+            assert_eq!(sender_id, "phone");
+            assert_eq!(sender, "Phone");
+            assert_eq!(text, "copied");
+        }
+        event => panic!("unexpected event: {:?}", event),
+    }
+
+    let blocked = server.receive_text("192.0.2.6", request("prankster")).await;
+    assert!(matches!(blocked, Err(ProtocolError::Rejected(_))));
+}
@@ -0,0 +1,220 @@
+//! 本地控制通道测试
+#![cfg(unix)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::control::{
+    self, ControlClient, ControlRequest, ControlResponse, Controller, SendRequest,
+};
+use peersend_protocol::server::accept::AcceptGate;
+use peersend_protocol::{
+    DiscoveryManager, FileInfo, LocalSendClient, LocalSendConfig, SessionManager,
+};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+fn controller(sessions: &SessionManager, accept: &AcceptGate) -> Controller {
+    let client =
+        LocalSendClient::new(LocalSendConfig::default()).with_session_manager(sessions.clone());
+    Controller::new(
+        client,
+        Arc::new(Mutex::new(sessions.clone())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+        accept.clone(),
+    )
+}
+
+/// 启动控制通道并等待套接字出现
+async fn start(controller: Controller, path: &Path) -> tokio_util::sync::DropGuard {
+    let cancel = CancellationToken::new();
+    let path_buf = path.to_path_buf();
+    let token = cancel.clone();
+    tokio::spawn(async move { control::serve(controller, &path_buf, token).await });
+    while ControlClient::connect(path).await.is_err() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    cancel.drop_guard()
+}
+
+fn file() -> FileInfo {
+    FileInfo {
+        id: "file".to_string(),
+        name: "photo.jpg".to_string(),
+        size: 42,
+        file_type: "image/jpeg".to_string(),
+        metadata: None,
+        sha256: None,
+    }
+}
+
+#[test]
+fn requests_use_tagged_json() {
+    let request = ControlRequest::Respond {
+        session_id: "abc".to_string(),
+        accept: true,
+    };
+    assert_eq!(
+        serde_json::to_value(&request).unwrap(),
+        serde_json::json!({ "type": "respond", "sessionId": "abc", "accept": true })
+    );
+    let parsed: ControlRequest = serde_json::from_str(r#"{"type":"history","limit":5}"#).unwrap();
+    assert_eq!(parsed, ControlRequest::History { limit: 5 });
+}
+
+#[tokio::test]
+async fn pending_session_can_be_accepted() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("run").join("control.sock");
+    let sessions = SessionManager::new();
+    let accept = AcceptGate::new();
+    let _guard = start(controller(&sessions, &accept), &path).await;
+
+    // 套接字和所在目录仅当前用户可访问
+    let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(path.parent().unwrap()), 0o700);
+    assert_eq!(mode(&path), 0o600);
+
+    let session = sessions
+        .create_session("peer".to_string(), "me".to_string(), vec![file()])
+        .await;
+    let waiting = accept.clone();
+    let id = session.id.clone();
+    let decision = tokio::spawn(async move { waiting.wait(&id, Duration::from_secs(5)).await });
+    while accept.pending().is_empty() {
+        tokio::task::yield_now().await;
+    }
+
+    let mut client = ControlClient::connect(&path).await.unwrap();
+    assert!(matches!(
+        client.request(&ControlRequest::Ping).await.unwrap(),
+        ControlResponse::Pong
+    ));
+    let ControlResponse::Sessions { sessions } =
+        client.request(&ControlRequest::Sessions).await.unwrap()
+    else {
+        panic!("应返回会话列表");
+    };
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].pending);
+
+    let respond = ControlRequest::Respond {
+        session_id: session.id.clone(),
+        accept: true,
+    };
+    assert!(matches!(
+        client.request(&respond).await.unwrap(),
+        ControlResponse::Done
+    ));
+    assert!(decision.await.unwrap());
+    assert!(matches!(
+        client.request(&respond).await.unwrap(),
+        ControlResponse::Error { .. }
+    ));
+}
+
+#[tokio::test]
+async fn invalid_request_gets_error_and_connection_stays_open() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("control.sock");
+    let accept = AcceptGate::new();
+    let _guard = start(controller(&SessionManager::new(), &accept), &path).await;
+
+    let stream = UnixStream::connect(&path).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    writer
+        .write_all(b"not json\n{\"type\":\"ping\"}\n")
+        .await
+        .unwrap();
+    let error: serde_json::Value =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(error["type"], "error");
+    let pong: serde_json::Value =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(pong["type"], "pong");
+}
+
+#[tokio::test]
+async fn refuses_to_replace_live_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("control.sock");
+    let accept = AcceptGate::new();
+    let sessions = SessionManager::new();
+    let _guard = start(controller(&sessions, &accept), &path).await;
+
+    let second = control::serve(
+        controller(&sessions, &accept),
+        &path,
+        CancellationToken::new(),
+    )
+    .await;
+    assert!(second.is_err());
+    // 第一个实例仍可连接
+    assert!(ControlClient::connect(&path).await.is_ok());
+}
+
+#[tokio::test]
+async fn replaces_stale_socket_and_removes_it_on_exit() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("control.sock");
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let accept = AcceptGate::new();
+    let cancel = CancellationToken::new();
+    let task = tokio::spawn({
+        let controller = controller(&SessionManager::new(), &accept);
+        let path = path.clone();
+        let cancel = cancel.clone();
+        async move { control::serve(controller, &path, cancel).await }
+    });
+    while ControlClient::connect(&path).await.is_err() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    cancel.cancel();
+    task.await.unwrap().unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn group_sends_report_each_device() {
+    let config = LocalSendConfig {
+        // 名称不是主机名，未被发现时不会探测
+        groups: [(
+            "family".to_string(),
+            vec!["Alice's phone".to_string(), "Living room tablet".to_string()],
+        )]
+        .into(),
+        ..LocalSendConfig::default()
+    };
+    let controller = Controller::new(
+        LocalSendClient::new(config),
+        Arc::new(Mutex::new(SessionManager::new())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+        AcceptGate::new(),
+    );
+    let send = |device: &str| {
+        ControlRequest::Send(SendRequest {
+            device: device.to_string(),
+            paths: vec!["photo.jpg".into()],
+            pin: None,
+        })
+    };
+
+    let ControlResponse::Recipients { results } = controller.handle(send("@family")).await else {
+        panic!("应返回各设备的结果");
+    };
+    let devices: Vec<_> = results.iter().map(|r| r.device.as_str()).collect();
+    assert_eq!(devices, ["Alice's phone", "Living room tablet"]);
+    assert!(results.iter().all(|r| r.session.is_none() && r.error.is_some()));
+
+    let ControlResponse::Error { message } = controller.handle(send("@work")).await else {
+        panic!("未知的设备组应返回错误");
+    };
+    assert!(message.contains("work"));
+}
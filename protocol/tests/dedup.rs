@@ -0,0 +1,126 @@
+//! 按哈希跳过重复文件测试
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use peersend_protocol::dto::v2::{FileDto, PrepareUploadRequestDto, RegisterDto, PROTOCOL_VERSION};
+use peersend_protocol::hash::sha256_hex;
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::storage::dedup::HashIndex;
+use peersend_protocol::{
+    DiscoveryManager, DuplicatePolicy, LocalSendConfig, QuickSave, SessionManager,
+};
+use tokio::sync::Mutex;
+
+const CONTENTS: &[u8] = b"holiday photos";
+
+fn server(download_dir: &Path, policy: DuplicatePolicy, index: HashIndex) -> LocalSendServer {
+    let config = LocalSendConfig {
+        quick_save: QuickSave::On,
+        download_dir: download_dir.to_string_lossy().into_owned(),
+        duplicate_policy: policy,
+        ..LocalSendConfig::default()
+    };
+    LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        config,
+        Arc::new(Mutex::new(SessionManager::new())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    )
+    .with_hash_index(index)
+}
+
+/// 请求两个文件: `same` 与已接收的文件相同，`new` 是新文件
+fn request() -> PrepareUploadRequestDto {
+    let file = |id: &str, contents: &[u8]| FileDto {
+        id: id.to_string(),
+        file_name: format!("{}.bin", id),
+        size: contents.len() as u64,
+        file_type: "application/octet-stream".to_string(),
+        sha256: Some(sha256_hex(contents)),
+        preview: None,
+        metadata: None,
+    };
+    let files = [file("same", CONTENTS), file("new", b"something else")]
+        .into_iter()
+        .map(|f| (f.id.clone(), f))
+        .collect::<HashMap<_, _>>();
+    PrepareUploadRequestDto {
+        info: RegisterDto {
+            alias: "Phone".to_string(),
+            version: PROTOCOL_VERSION.to_string(),
+            device_model: None,
+            device_type: Some("mobile".to_string()),
+            fingerprint: "phone".to_string(),
+            port: 53317,
+            protocol: "http".to_string(),
+            download: false,
+        },
+        files,
+    }
+}
+
+/// 下载目录中已有一个收到过的文件并记入索引
+fn received(dir: &Path) -> HashIndex {
+    let existing = dir.join("downloads").join("earlier.bin");
+    std::fs::create_dir_all(existing.parent().unwrap()).unwrap();
+    std::fs::write(&existing, CONTENTS).unwrap();
+    let index = HashIndex::open(dir.join("hashes.jsonl")).unwrap();
+    index.insert(&sha256_hex(CONTENTS), &existing).unwrap();
+    index
+}
+
+#[test]
+fn index_survives_reopen_and_ignores_changed_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let index = received(dir.path());
+    let sha256 = sha256_hex(CONTENTS);
+
+    let reopened = HashIndex::open(index.path()).unwrap();
+    let existing = dir.path().join("downloads").join("earlier.bin");
+    assert_eq!(
+        reopened.find(&sha256.to_uppercase(), CONTENTS.len() as u64),
+        Some(existing.clone())
+    );
+
+    std::fs::write(&existing, b"edited").unwrap();
+    assert_eq!(reopened.find(&sha256, CONTENTS.len() as u64), None);
+    std::fs::remove_file(&existing).unwrap();
+    assert_eq!(reopened.find(&sha256, CONTENTS.len() as u64), None);
+}
+
+#[tokio::test]
+async fn skip_policy_leaves_duplicates_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let downloads = dir.path().join("downloads");
+    let server = server(&downloads, DuplicatePolicy::Skip, received(dir.path()));
+
+    let response = server.prepare_upload("192.0.2.5", request(), None).await.unwrap();
+
+    assert_eq!(response.files.keys().collect::<Vec<_>>(), vec!["new"]);
+    assert!(!downloads.join("same.bin").exists());
+}
+
+#[tokio::test]
+async fn link_policy_links_existing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let downloads = dir.path().join("downloads");
+    let server = server(&downloads, DuplicatePolicy::Link, received(dir.path()));
+
+    let response = server.prepare_upload("192.0.2.5", request(), None).await.unwrap();
+
+    assert_eq!(response.files.keys().collect::<Vec<_>>(), vec!["new"]);
+    assert_eq!(std::fs::read(downloads.join("same.bin")).unwrap(), CONTENTS);
+}
+
+#[tokio::test]
+async fn duplicates_are_received_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    let downloads = dir.path().join("downloads");
+    let server = server(&downloads, DuplicatePolicy::default(), received(dir.path()));
+
+    let response = server.prepare_upload("192.0.2.5", request(), None).await.unwrap();
+
+    assert_eq!(response.files.len(), 2);
+}
@@ -0,0 +1,116 @@
+//! 设备允许与屏蔽列表测试
+
+use std::sync::Arc;
+
+use peersend_protocol::discovery::HttpDiscoverer;
+use peersend_protocol::dto::RegisterResponse;
+use peersend_protocol::http::HttpResponse;
+use peersend_protocol::testing::MockHttp;
+use peersend_protocol::{DiscoveryManager, LocalSendConfig, ProtocolError};
+use tokio::sync::Mutex;
+
+fn config(allowlist: &[&str], blocklist: &[&str]) -> LocalSendConfig {
+    LocalSendConfig {
+        allowlist: allowlist.iter().map(|r| r.to_string()).collect(),
+        blocklist: blocklist.iter().map(|r| r.to_string()).collect(),
+        ..LocalSendConfig::default()
+    }
+}
+
+#[test]
+fn rules_match_ids_addresses_and_ranges() {
+    let open = config(&[], &[]);
+    assert!(open.allows_device("prankster", "192.168.1.23"));
+
+    let blocked = config(&[], &["prankster", "10.0.0.0/8", "fd00::1"]);
+    assert!(!blocked.allows_device("prankster", "192.168.1.23"));
+    assert!(!blocked.allows_device("friend", "10.20.30.40"));
+    assert!(!blocked.allows_device("friend", "::ffff:10.0.0.1"));
+    assert!(!blocked.allows_device("friend", "fd00::1"));
+    assert!(blocked.allows_device("friend", "192.168.1.24"));
+
+    // 屏蔽列表优先于允许列表
+    let allowed = config(&["192.168.1.0/24", "laptop"], &["192.168.1.23"]);
+    assert!(allowed.allows_device("phone", "192.168.1.10"));
+    assert!(allowed.allows_device("laptop", "172.16.0.5"));
+    assert!(!allowed.allows_device("phone", "192.168.2.10"));
+    assert!(!allowed.allows_device("laptop", "192.168.1.23"));
+
+    let everyone = config(&["0.0.0.0/0"], &[]);
+    assert!(everyone.allows_device("any", "203.0.113.9"));
+}
+
+#[test]
+fn invalid_rules_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let valid = |allowlist: &[&str], blocklist: &[&str]| LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        ..config(allowlist, blocklist)
+    };
+
+    valid(&["192.168.1.0/24"], &["prankster", "fe80::/10"]).validate().unwrap();
+    for rules in [&["10.0.0.0/33"][..], &["not-an-ip/8"], &[" "]] {
+        assert!(matches!(
+            valid(&[], rules).validate(),
+            Err(ProtocolError::InvalidConfig(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn discovery_skips_blocked_devices() {
+    let http = MockHttp::new();
+    http.route("/register", |_| HttpResponse {
+        status: 200,
+        body: serde_json::to_vec(&RegisterResponse {
+            id: "prankster".to_string(),
+            device_type: "mobile".to_string(),
+            name: "Prankster".to_string(),
+            version: "2.0".to_string(),
+            protocol_version: "2.0".to_string(),
+            download: false,
+            port: None,
+            announcement_id: None,
+            uses_password: false,
+            transports: Vec::new(),
+            addresses: Vec::new(),
+        })
+        .unwrap(),
+    });
+    let manager = Arc::new(Mutex::new(DiscoveryManager::new()));
+
+    let open = HttpDiscoverer::new(config(&[], &[]), manager.clone(), http.clone());
+    assert!(open.check_device("192.0.2.7").await.is_some());
+
+    let blocked = HttpDiscoverer::new(config(&[], &["prankster"]), manager.clone(), http.clone());
+    assert!(blocked.check_device("192.0.2.7").await.is_none());
+    blocked.scan_range("192.0.2.0", 4).await.unwrap();
+    assert_eq!(manager.lock().await.device_count().await, 0);
+}
+
+#[test]
+fn groups_expand_and_are_validated() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        groups: [("family".to_string(), vec!["phone".to_string(), "192.168.1.7".to_string()])]
+            .into(),
+        ..LocalSendConfig::default()
+    };
+    assert!(config.validate().is_ok());
+    assert_eq!(config.expand_target("@family").unwrap(), ["phone", "192.168.1.7"]);
+    assert_eq!(config.expand_target("laptop").unwrap(), ["laptop"]);
+    assert!(matches!(
+        config.expand_target("@work"),
+        Err(ProtocolError::InvalidConfig(_))
+    ));
+
+    // 不支持嵌套和空的设备组
+    config.groups.insert("all".to_string(), vec!["@family".to_string()]);
+    assert!(config.validate().is_err());
+    config.groups.insert("all".to_string(), Vec::new());
+    assert!(config.validate().is_err());
+    config.groups.remove("all");
+    config.groups.insert("my group".to_string(), vec!["phone".to_string()]);
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,123 @@
+//! 设备连接诊断测试
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use peersend_protocol::diagnose::{self, NetworkPath, TlsStatus, PROBE_COUNT};
+use peersend_protocol::dto::v2::{InfoDto, INFO_PATH};
+use peersend_protocol::dto::RegisterResponse;
+use peersend_protocol::http::HttpResponse;
+use peersend_protocol::testing::{peer_device, MockHttp};
+use peersend_protocol::{DeviceInfo, LocalSendClient, LocalSendConfig};
+
+fn peer() -> DeviceInfo {
+    DeviceInfo {
+        ip: "127.0.0.1".to_string(),
+        port: 9,
+        version: "0.1.0-peersend".to_string(),
+        ..peer_device()
+    }
+}
+
+fn json<T: serde::Serialize>(value: &T) -> HttpResponse {
+    HttpResponse {
+        status: 200,
+        body: serde_json::to_vec(value).unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn v2_device_is_probed_and_measured() {
+    let http = MockHttp::new();
+    http.route(INFO_PATH, |_| {
+        json(&InfoDto {
+            alias: "Phone".to_string(),
+            version: "2.1".to_string(),
+            device_model: Some("Pixel".to_string()),
+            device_type: Some("mobile".to_string()),
+            fingerprint: "abc123".to_string(),
+            download: false,
+        })
+    });
+    let client = LocalSendClient::new(LocalSendConfig::default()).with_http(http.clone());
+
+    let report = client.diagnose(&peer()).await;
+    assert!(report.reachable());
+    let info = report.info.as_ref().unwrap();
+    assert_eq!((info.api.as_str(), info.alias.as_str()), ("v2", "Phone"));
+    assert_eq!(report.scheme.as_deref(), Some("https"));
+    assert_eq!(report.address, "127.0.0.1:9");
+    assert_eq!(report.local_address.as_deref(), Some("127.0.0.1"));
+    assert_eq!(report.path, NetworkPath::Lan);
+    let latency = report.latency.as_ref().unwrap();
+    assert_eq!(latency.samples, PROBE_COUNT);
+    assert!(latency.min_ms <= latency.avg_ms && latency.avg_ms <= latency.max_ms);
+    assert_eq!(http.requests().len(), PROBE_COUNT);
+
+    // 端口上没有 HTTPS 服务，无法重新握手取得证书
+    let tls = report.tls.as_ref().unwrap();
+    assert_eq!(tls.status, TlsStatus::Failed);
+    assert_eq!(tls.announced.as_deref(), Some("abc123"));
+    assert!(report.errors.iter().any(|e| e.contains("检查证书失败")), "{:?}", report.errors);
+
+    let value = serde_json::to_value(&report).unwrap();
+    assert_eq!(value["path"], "lan");
+    assert_eq!(value["tls"]["status"], "failed");
+    assert!(value["latency"]["minMs"].is_number());
+}
+
+#[tokio::test]
+async fn v1_device_falls_back_to_register() {
+    let http = MockHttp::new();
+    http.route("/api/v1/localsend/register", |_| {
+        json(&RegisterResponse {
+            id: "peer".to_string(),
+            device_type: "desktop".to_string(),
+            name: "Laptop".to_string(),
+            version: "0.1.0-peersend".to_string(),
+            protocol_version: "1.0".to_string(),
+            download: false,
+            port: None,
+            announcement_id: None,
+            uses_password: false,
+            transports: Vec::new(),
+            addresses: Vec::new(),
+        })
+    });
+    let client = LocalSendClient::new(LocalSendConfig::default()).with_http(http.clone());
+
+    let report = client.diagnose(&peer()).await;
+    let info = report.info.as_ref().unwrap();
+    assert_eq!((info.api.as_str(), info.alias.as_str()), ("v1", "Laptop"));
+    assert_eq!(info.fingerprint, None);
+    assert_eq!(report.latency.as_ref().unwrap().samples, PROBE_COUNT);
+    // v2 接口 404 后改用 v1，之后只请求 v1
+    let requests = http.requests();
+    assert!(requests[0].url.ends_with(INFO_PATH));
+    assert!(requests[1..].iter().all(|r| r.url.ends_with("/api/v1/localsend/register")));
+}
+
+#[tokio::test]
+async fn silent_device_reports_each_scheme() {
+    let http = MockHttp::new();
+    http.respond_status(INFO_PATH, 500);
+    let client = LocalSendClient::new(LocalSendConfig::default()).with_http(http.clone());
+
+    let report = client.diagnose(&peer()).await;
+    assert!(!report.reachable());
+    assert_eq!((report.tls.as_ref(), report.latency.as_ref()), (None, None));
+    assert_eq!(report.errors.len(), 2, "{:?}", report.errors);
+    assert!(report.errors[0].starts_with("https"));
+    assert!(report.errors[1].starts_with("http "));
+}
+
+#[test]
+fn path_follows_the_local_address() {
+    let lan: IpAddr = Ipv4Addr::new(192, 168, 1, 20).into();
+    let tunnel: IpAddr = Ipv4Addr::new(10, 144, 144, 1).into();
+    let tunnels = [tunnel];
+    assert_eq!(diagnose::classify(None, &tunnels, None), NetworkPath::Unknown);
+    assert_eq!(diagnose::classify(Some(lan), &tunnels, Some(true)), NetworkPath::Lan);
+    assert_eq!(diagnose::classify(Some(tunnel), &tunnels, None), NetworkPath::Tunnel);
+    assert_eq!(diagnose::classify(Some(tunnel), &tunnels, Some(false)), NetworkPath::Tunnel);
+    assert_eq!(diagnose::classify(Some(tunnel), &tunnels, Some(true)), NetworkPath::Relay);
+}
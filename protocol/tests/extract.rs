@@ -0,0 +1,131 @@
+//! 收到的压缩包自动解压测试
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use peersend_protocol::storage::extract::{archive_kind, extract, ArchiveKind};
+use peersend_protocol::{ExtractConfig, ProtocolError};
+use zip::write::SimpleFileOptions;
+
+fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+    let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+    for (name, data) in entries {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+fn write_tar_gz(path: &Path, entries: &[(&str, &[u8])]) {
+    let gz = GzEncoder::new(File::create(path).unwrap(), Compression::default());
+    let mut tar = tar::Builder::new(gz);
+    for (name, data) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o755);
+        tar.append_data(&mut header, name, *data).unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap();
+}
+
+#[test]
+fn recognizes_archive_names() {
+    assert_eq!(archive_kind("photos.ZIP"), Some(ArchiveKind::Zip));
+    assert_eq!(archive_kind("src.tar.gz"), Some(ArchiveKind::TarGz));
+    assert_eq!(archive_kind("src.tgz"), Some(ArchiveKind::TarGz));
+    assert_eq!(archive_kind("notes.txt"), None);
+}
+
+#[test]
+fn extracts_into_folder_named_after_archive() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("photos.zip");
+    write_zip(&archive, &[("a.txt", b"hello"), ("album/b.txt", b"world")]);
+    // 同名目录已存在时加序号
+    fs::create_dir(dir.path().join("photos")).unwrap();
+
+    let mut progress = Vec::new();
+    let target = extract(&archive, &ExtractConfig::default(), &[], |entry, n| {
+        progress.push((entry.to_path_buf(), n))
+    })
+    .unwrap();
+    assert_eq!(target, dir.path().join("photos (1)"));
+    assert_eq!(fs::read(target.join("a.txt")).unwrap(), b"hello");
+    assert_eq!(fs::read(target.join("album/b.txt")).unwrap(), b"world");
+    assert_eq!(progress.len(), 2);
+    assert_eq!(progress[1], (Path::new("album").join("b.txt"), 2));
+}
+
+#[test]
+fn extracts_tar_gz_without_quarantined_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("src.tar.gz");
+    write_tar_gz(
+        &archive,
+        &[("src/main.rs", b"fn main() {}"), ("setup.exe", b"MZ")],
+    );
+
+    let quarantine = ["exe".to_string()];
+    let target = extract(&archive, &ExtractConfig::default(), &quarantine, |_, _| {}).unwrap();
+    assert_eq!(target, dir.path().join("src"));
+    assert_eq!(
+        fs::read(target.join("src/main.rs")).unwrap(),
+        b"fn main() {}"
+    );
+    assert!(!target.join("setup.exe").exists());
+    // 不保留权限位
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(target.join("src/main.rs"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o111, 0);
+    }
+}
+
+#[test]
+fn rejects_path_traversal() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("evil.zip");
+    write_zip(
+        &archive,
+        &[("ok.txt", b"ok"), ("../escaped.txt", b"gotcha")],
+    );
+
+    let result = extract(&archive, &ExtractConfig::default(), &[], |_, _| {});
+    assert!(matches!(result, Err(ProtocolError::Rejected(_))));
+    assert!(!dir.path().join("escaped.txt").exists());
+    // 不留下解压了一半的目录
+    let names: Vec<_> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["evil.zip"]);
+}
+
+#[test]
+fn enforces_size_and_entry_limits() {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("bomb.zip");
+    write_zip(&archive, &[("a.bin", &[0; 600]), ("b.bin", &[0; 600])]);
+
+    let config = ExtractConfig {
+        max_size: 1000,
+        ..ExtractConfig::default()
+    };
+    let result = extract(&archive, &config, &[], |_, _| {});
+    assert!(matches!(result, Err(ProtocolError::Rejected(_))));
+
+    let config = ExtractConfig {
+        max_entries: 1,
+        ..ExtractConfig::default()
+    };
+    let result = extract(&archive, &config, &[], |_, _| {});
+    assert!(matches!(result, Err(ProtocolError::Rejected(_))));
+    assert!(!dir.path().join("bomb").exists());
+}
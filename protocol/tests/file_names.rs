@@ -0,0 +1,100 @@
+//! 收到的文件名清理的测试
+
+use std::path::Path;
+use std::sync::Arc;
+
+use peersend_protocol::session::FileReceiver;
+use peersend_protocol::storage::names::sanitize_file_name;
+use peersend_protocol::storage::MemoryStorage;
+use peersend_protocol::{FileInfo, FileSession, ProtocolError};
+
+fn rejected(name: &str) -> bool {
+    matches!(sanitize_file_name(name), Err(ProtocolError::Rejected(_)))
+}
+
+fn receiver(name: &str) -> FileReceiver {
+    let file = FileInfo {
+        id: "file".to_string(),
+        name: name.to_string(),
+        size: 4,
+        file_type: "application/octet-stream".to_string(),
+        metadata: None,
+        sha256: None,
+    };
+    let session =
+        FileSession::new("session".to_string(), "peer".to_string(), "self".to_string(), vec![file]);
+    FileReceiver::new(session, "/downloads".into())
+}
+
+#[test]
+fn keeps_ordinary_names() {
+    assert_eq!(sanitize_file_name("photo.jpg").unwrap(), "photo.jpg");
+    assert_eq!(sanitize_file_name(".bashrc").unwrap(), ".bashrc");
+    assert_eq!(sanitize_file_name("年度 报告.pdf").unwrap(), "年度 报告.pdf");
+    assert_eq!(sanitize_file_name("console.log").unwrap(), "console.log");
+}
+
+#[test]
+fn strips_directory_components() {
+    assert_eq!(sanitize_file_name("../../etc/cron.d/x").unwrap(), "x");
+    assert_eq!(sanitize_file_name("..\\..\\Windows\\win.ini").unwrap(), "win.ini");
+    assert_eq!(sanitize_file_name("photos/2024/a.jpg").unwrap(), "a.jpg");
+    assert!(rejected(".."));
+    assert!(rejected("docs/.."));
+    assert!(rejected("docs/"));
+    assert!(rejected(""));
+}
+
+#[test]
+fn rejects_absolute_paths() {
+    assert!(rejected("/etc/passwd"));
+    assert!(rejected("\\Windows\\System32\\drivers\\etc\\hosts"));
+    assert!(rejected("\\\\server\\share\\x"));
+    assert!(rejected("C:\\Users\\x.txt"));
+    assert!(rejected("c:x.txt"));
+}
+
+#[test]
+fn rejects_reserved_device_names() {
+    for name in ["CON", "nul", "Aux.txt", "com1.tar.gz", "LPT9", "NUL ", "con."] {
+        assert!(rejected(name), "{}", name);
+    }
+    assert_eq!(sanitize_file_name("COM10").unwrap(), "COM10");
+}
+
+#[test]
+fn removes_characters_windows_cannot_store() {
+    assert_eq!(sanitize_file_name("a\0b\r\n.txt").unwrap(), "ab.txt");
+    assert_eq!(sanitize_file_name("report.txt:stream").unwrap(), "report.txt_stream");
+    assert_eq!(sanitize_file_name("what?*.txt").unwrap(), "what__.txt");
+    // Windows 会去掉结尾的点和空格，保存时得到另一个文件名
+    assert_eq!(sanitize_file_name("notes.txt. . ").unwrap(), "notes.txt");
+}
+
+#[test]
+fn normalizes_unicode() {
+    // e + 组合重音符与预组合的 é 保存为同一个文件名
+    assert_eq!(sanitize_file_name("cafe\u{301}.txt").unwrap(), "caf\u{e9}.txt");
+    assert_eq!(sanitize_file_name("caf\u{e9}.txt").unwrap(), "caf\u{e9}.txt");
+    // 全角斜杠不是分隔符，保留为文件名的一部分
+    assert_eq!(sanitize_file_name("..\u{ff0f}x").unwrap(), "..\u{ff0f}x");
+}
+
+#[tokio::test]
+async fn receiver_keeps_files_in_download_dir() {
+    let receiver = receiver("../../etc/cron.d/x");
+    assert_eq!(
+        receiver.get_save_path("../../etc/cron.d/x").unwrap(),
+        Path::new("/downloads/x")
+    );
+    assert!(receiver.get_save_path("/etc/passwd").is_err());
+    assert!(receiver.get_save_path("NUL").is_err());
+
+    let storage = Arc::new(MemoryStorage::new());
+    let mut receiver = receiver.with_storage(storage.clone());
+    receiver.start_file("../../etc/cron.d/x").await.unwrap();
+    receiver.write_chunk(b"data").await.unwrap();
+    receiver.finish_current_file().await.unwrap();
+    assert_eq!(storage.get("x").await.unwrap(), b"data");
+    assert!(receiver.start_file("C:\\autoexec.bat").await.is_err());
+}
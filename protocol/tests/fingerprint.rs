@@ -0,0 +1,167 @@
+//! 源文件改变检测测试
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use peersend_protocol::audit::{self, AuditAction, AuditLog};
+use peersend_protocol::fingerprint::SourceFingerprint;
+use peersend_protocol::fs::LocalFs;
+use peersend_protocol::testing::{peer_device, MockFs, MockHttp};
+use peersend_protocol::{DeviceInfo, LocalSendClient, LocalSendConfig};
+use tokio_util::sync::CancellationToken;
+
+fn peer() -> DeviceInfo {
+    DeviceInfo {
+        version: "0.1.0-peersend".to_string(),
+        ..peer_device()
+    }
+}
+
+/// 接受所有文件，应答前调用 edit 模拟等待确认期间源文件被修改
+fn accept_after(http: &MockHttp, edit: impl Fn() + Send + Sync + 'static) {
+    http.route("/prepare-upload", move |request| {
+        edit();
+        request.accept_files(|_| true)
+    });
+    http.respond_status("/upload", 200);
+}
+
+fn uploads(http: &MockHttp) -> Vec<Vec<u8>> {
+    let requests = http.requests();
+    let uploads = requests.iter().filter(|r| r.url.ends_with("/upload"));
+    uploads.map(|r| r.body.clone()).collect()
+}
+
+fn set_modified(path: &Path, time: SystemTime) {
+    let file = std::fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(time).unwrap();
+}
+
+#[tokio::test]
+async fn fingerprint_detects_each_change() {
+    let fs = MockFs::new();
+    fs.insert("/src/a.bin", vec![1u8; 100 * 1024]);
+    let before = SourceFingerprint::read(&fs, Path::new("/src/a.bin")).await.unwrap();
+    assert_eq!(before.modified, None);
+
+    // 开头以外的修改不影响指纹
+    let mut data = vec![1u8; 100 * 1024];
+    data[90 * 1024] = 2;
+    fs.insert("/src/a.bin", data.clone());
+    let tail = SourceFingerprint::read(&fs, Path::new("/src/a.bin")).await.unwrap();
+    assert!(before.changes(&tail).is_empty());
+
+    data[0] = 2;
+    data.push(0);
+    fs.insert("/src/a.bin", data);
+    let after = SourceFingerprint::read(&fs, Path::new("/src/a.bin")).await.unwrap();
+    assert_eq!(before.changes(&after), ["长度", "开头内容"]);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.bin");
+    std::fs::write(&path, b"contents").unwrap();
+    set_modified(&path, SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000));
+    let before = SourceFingerprint::read(&LocalFs, &path).await.unwrap();
+    set_modified(&path, SystemTime::UNIX_EPOCH + Duration::from_secs(2_000_000));
+    let after = SourceFingerprint::read(&LocalFs, &path).await.unwrap();
+    assert_eq!(before.changes(&after), ["修改时间"]);
+}
+
+#[tokio::test]
+async fn changed_file_is_restarted_and_recorded() {
+    let fs = Arc::new(MockFs::new());
+    fs.insert("/src/report.txt", b"draft one".to_vec());
+    let http = MockHttp::new();
+    let edit = fs.clone();
+    accept_after(&http, move || edit.insert("/src/report.txt", b"final one".to_vec()));
+    let client = LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_fs(fs.clone());
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.jsonl");
+    let sessions = client.session_manager().lock().await.clone();
+    let cancel = CancellationToken::new();
+    let audit = AuditLog::open(&path).unwrap();
+    let run = audit.run(client.subscribe(), sessions, "local".into(), cancel.clone());
+    let task = tokio::spawn(run);
+
+    let session = client.send_files(&peer(), &["/src/report.txt"]).await.unwrap();
+    assert_eq!(uploads(&http), [b"final one".to_vec()]);
+    let warnings = session.warnings.lock().await.clone();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].name, "/src/report.txt");
+    assert!(warnings[0].reason.contains("开头内容"), "{}", warnings[0].reason);
+    assert!(warnings[0].reason.contains("从头发送"), "{}", warnings[0].reason);
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while audit::records(&path).unwrap().len() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let records = audit::records(&path).unwrap();
+    let changed = &records[0].entry;
+    assert_eq!(changed.action, AuditAction::Changed);
+    assert_eq!(changed.session_id.as_deref(), Some(session.id.as_str()));
+    assert_eq!(changed.peer.as_deref(), Some("peer"));
+    assert_eq!(changed.files[0].name, "report.txt");
+    assert_eq!(changed.reason.as_deref(), Some(warnings[0].reason.as_str()));
+    assert_eq!(records[1].entry.action, AuditAction::Completed);
+
+    cancel.cancel();
+    task.await.unwrap();
+}
+
+#[tokio::test]
+async fn file_that_no_longer_matches_is_not_sent() {
+    let fs = Arc::new(MockFs::new());
+    fs.insert("/src/grown.log", b"line 1\n".to_vec());
+    let http = MockHttp::new();
+    let edit = fs.clone();
+    accept_after(&http, move || edit.insert("/src/grown.log", b"line 1\nline 2\n".to_vec()));
+    let client = LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_fs(fs.clone());
+
+    // 长度改变
+    let error = client.send_files(&peer(), &["/src/grown.log"]).await.unwrap_err();
+    assert!(error.to_string().contains("需重新发送"), "{}", error);
+    assert!(uploads(&http).is_empty());
+    let sessions = client.session_manager().lock().await.get_all_sessions().await;
+    let warnings = sessions[0].warnings.lock().await.clone();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].reason.contains("长度"), "{}", warnings[0].reason);
+
+    // 长度不变但与声明的哈希不符
+    fs.insert("/src/hashed.bin", b"old bytes".to_vec());
+    let http = MockHttp::new();
+    let edit = fs.clone();
+    accept_after(&http, move || edit.insert("/src/hashed.bin", b"new bytes".to_vec()));
+    let client = client.with_http(http.clone()).with_hash_verification(true);
+    let error = client.send_files(&peer(), &["/src/hashed.bin"]).await.unwrap_err();
+    assert!(error.to_string().contains("需重新发送"), "{}", error);
+    assert!(uploads(&http).is_empty());
+}
+
+#[tokio::test]
+async fn unchanged_contents_are_sent_without_warning() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("photo.jpg");
+    std::fs::write(&path, b"jpeg bytes").unwrap();
+    set_modified(&path, SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000));
+
+    // 只改变修改时间，内容与声明的哈希一致
+    let http = MockHttp::new();
+    let touched = path.clone();
+    accept_after(&http, move || set_modified(&touched, SystemTime::now()));
+    let client = LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_hash_verification(true);
+
+    let session = client.send_files(&peer(), &[&path]).await.unwrap();
+    assert_eq!(uploads(&http), [b"jpeg bytes".to_vec()]);
+    assert!(session.warnings.lock().await.is_empty());
+}
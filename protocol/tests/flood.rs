@@ -0,0 +1,144 @@
+//! 公告与请求洪泛防护测试
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::dto::v2::{FileDto, PrepareUploadRequestDto, RegisterDto, PROTOCOL_VERSION};
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::testing::peer_device;
+use peersend_protocol::{
+    DeviceInfo, DiscoveryManager, FloodGuard, FloodLimits, LocalSendConfig, ProtocolError,
+    QuickSave, SessionManager,
+};
+use tokio::sync::Mutex;
+
+fn limits(block_duration: Duration) -> FloodLimits {
+    FloodLimits {
+        window: Duration::from_secs(60),
+        max_requests: 5,
+        max_devices: 2,
+        block_duration,
+    }
+}
+
+fn device(id: &str, ip: &str) -> DeviceInfo {
+    DeviceInfo {
+        id: id.to_string(),
+        name: id.to_string(),
+        device_type: "mobile".to_string(),
+        ip: ip.to_string(),
+        version: "2.0".to_string(),
+        ..peer_device()
+    }
+}
+
+fn request(fingerprint: &str) -> PrepareUploadRequestDto {
+    let file = FileDto {
+        id: "file".to_string(),
+        file_name: "photo.jpg".to_string(),
+        size: 42,
+        file_type: "image/jpeg".to_string(),
+        sha256: None,
+        preview: None,
+        metadata: None,
+    };
+    PrepareUploadRequestDto {
+        info: RegisterDto {
+            alias: "Phone".to_string(),
+            version: PROTOCOL_VERSION.to_string(),
+            device_model: None,
+            device_type: Some("mobile".to_string()),
+            fingerprint: fingerprint.to_string(),
+            port: 53317,
+            protocol: "http".to_string(),
+            download: false,
+        },
+        files: HashMap::from([("file".to_string(), file)]),
+    }
+}
+
+#[test]
+fn frequent_requests_block_the_address() {
+    let guard = FloodGuard::new(limits(Duration::from_secs(60)));
+    for _ in 0..5 {
+        assert!(guard.check("192.0.2.7", "phone"));
+    }
+    assert!(!guard.check("192.0.2.7", "phone"));
+    assert!(!guard.check("192.0.2.7", "phone"));
+    assert!(guard.is_blocked("192.0.2.7"));
+
+    // 其他地址不受影响
+    assert!(guard.check("192.0.2.8", "laptop"));
+
+    let stats = guard.stats();
+    assert_eq!((stats.tracked, stats.blocked), (2, 1));
+    assert_eq!((stats.blocks, stats.dropped), (1, 2));
+}
+
+#[test]
+fn too_many_device_ids_block_the_address() {
+    let guard = FloodGuard::new(limits(Duration::from_secs(60)));
+    assert!(guard.check("192.0.2.7", "a"));
+    assert!(guard.check("192.0.2.7", "b"));
+    assert!(guard.check("192.0.2.7", "a"));
+    assert!(!guard.check("192.0.2.7", "c"));
+    // 封禁期间已知的设备 ID 同样被忽略
+    assert!(!guard.check("192.0.2.7", "a"));
+}
+
+#[tokio::test]
+async fn block_expires() {
+    let guard = FloodGuard::new(limits(Duration::from_millis(100)));
+    for id in ["a", "b", "c"] {
+        guard.check("192.0.2.7", id);
+    }
+    assert!(guard.is_blocked("192.0.2.7"));
+
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert!(!guard.is_blocked("192.0.2.7"));
+    assert!(guard.check("192.0.2.7", "c"));
+}
+
+#[tokio::test]
+async fn blocked_address_is_removed_from_discovery() {
+    let manager = DiscoveryManager::new().with_flood_limits(limits(Duration::from_secs(60)));
+    for id in ["a", "b"] {
+        assert!(manager.admit("192.0.2.7", id).await);
+        manager.add_device(device(id, "192.0.2.7")).await;
+    }
+    assert!(manager.admit("192.0.2.8", "laptop").await);
+    manager.add_device(device("laptop", "192.0.2.8")).await;
+
+    assert!(!manager.admit("192.0.2.7", "c").await);
+    let devices = manager.get_devices().await;
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].id, "laptop");
+}
+
+#[tokio::test]
+async fn server_rejects_flooding_sender() {
+    let sessions = SessionManager::new();
+    let discovery = DiscoveryManager::new().with_flood_limits(limits(Duration::from_secs(60)));
+    let server = LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        LocalSendConfig {
+            quick_save: QuickSave::On,
+            ..LocalSendConfig::default()
+        },
+        Arc::new(Mutex::new(sessions.clone())),
+        Arc::new(Mutex::new(discovery)),
+    );
+
+    for _ in 0..5 {
+        server
+            .prepare_upload("192.0.2.7", request("phone"), None)
+            .await
+            .unwrap();
+    }
+    let flooded = server
+        .prepare_upload("192.0.2.7", request("phone"), None)
+        .await;
+    assert!(matches!(flooded, Err(ProtocolError::Rejected(_))));
+    assert_eq!(sessions.get_all_sessions().await.len(), 5);
+}
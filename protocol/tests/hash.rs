@@ -0,0 +1,87 @@
+//! 哈希校验测试
+
+use std::sync::Arc;
+
+use peersend_protocol::hash::{self, HashPipeline};
+use peersend_protocol::session::FileReceiver;
+use peersend_protocol::storage::MemoryStorage;
+use peersend_protocol::transport::{self, StreamHeader};
+use peersend_protocol::{FileInfo, FileSession, ProtocolError};
+
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+fn session(sha256: &str) -> FileSession {
+    FileSession::new(
+        "session".to_string(),
+        "peer".to_string(),
+        "self".to_string(),
+        vec![FileInfo {
+            id: "file".to_string(),
+            name: "a.txt".to_string(),
+            size: 3,
+            file_type: "text/plain".to_string(),
+            metadata: None,
+            sha256: Some(sha256.to_string()),
+        }],
+    )
+}
+
+#[tokio::test]
+async fn pipeline_matches_known_digest() {
+    let pipeline = HashPipeline::new();
+    pipeline.update(b"a").await.unwrap();
+    pipeline.update(b"bc").await.unwrap();
+    assert_eq!(pipeline.finish().await.unwrap(), ABC_SHA256);
+
+    assert_eq!(hash::sha256_reader(&b"abc"[..]).await.unwrap(), ABC_SHA256);
+}
+
+#[tokio::test]
+async fn receiver_rejects_corrupted_file() {
+    let storage = Arc::new(MemoryStorage::new());
+    let mut receiver =
+        FileReceiver::new(session(ABC_SHA256), "/unused".into()).with_storage(storage.clone());
+
+    receiver.start_file("a.txt").await.unwrap();
+    receiver.write_chunk(b"abd").await.unwrap();
+    let result = receiver.finish_current_file().await;
+
+    assert!(matches!(result, Err(ProtocolError::HashMismatch { file_id }) if file_id == "file"));
+    assert!(storage.get("a.txt").await.is_none());
+}
+
+#[tokio::test]
+async fn receiver_accepts_matching_file() {
+    let storage = Arc::new(MemoryStorage::new());
+    let mut receiver = FileReceiver::new(session(&ABC_SHA256.to_uppercase()), "/unused".into())
+        .with_storage(storage.clone());
+
+    receiver.start_file("a.txt").await.unwrap();
+    receiver.write_chunk(b"abc").await.unwrap();
+    receiver.finish_current_file().await.unwrap();
+
+    assert_eq!(storage.get("a.txt").await.unwrap(), b"abc");
+}
+
+#[tokio::test]
+async fn stream_with_wrong_hash_is_removed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.txt");
+    let header = StreamHeader {
+        session_id: "session".to_string(),
+        file_id: "file".to_string(),
+        token: String::new(),
+        size: 3,
+        offset: 0,
+        sparse: None,
+    };
+
+    let result = transport::receive_file(&mut &b"abd"[..], &header, &path, Some(ABC_SHA256)).await;
+    assert!(matches!(result, Err(ProtocolError::HashMismatch { .. })));
+    assert!(!path.exists());
+
+    transport::receive_file(&mut &b"abc"[..], &header, &path, Some(ABC_SHA256))
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"abc");
+}
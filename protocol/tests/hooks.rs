@@ -0,0 +1,138 @@
+//! 接收后命令测试
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use peersend_protocol::audit::{self, AuditAction, AuditLog};
+use peersend_protocol::hooks::{self, HookContext, PostReceiveHooks};
+use peersend_protocol::{
+    EventBus, HookConfig, HookTrigger, LocalSendConfig, ProtocolError, ProtocolEvent,
+    SessionManager,
+};
+use tokio_util::sync::CancellationToken;
+
+#[test]
+fn templates_are_split_before_substitution() {
+    assert_eq!(
+        hooks::split_command(r#"import.sh --tag 'from phone' "{{path}}""#).unwrap(),
+        ["import.sh", "--tag", "from phone", "{{path}}"]
+    );
+    assert!(matches!(
+        hooks::split_command("import.sh 'oops"),
+        Err(ProtocolError::InvalidConfig(_))
+    ));
+    assert!(hooks::split_command("  ").is_err());
+
+    let context = HookContext {
+        session_id: "s1".to_string(),
+        sender: "Alice".to_string(),
+        path: PathBuf::from("/downloads/my photo; rm -rf ~.jpg"),
+        hash: Some("abc".to_string()),
+    };
+    // 文件名中的空格和特殊字符留在同一个参数中
+    assert_eq!(
+        hooks::render("import {{path}} --from={{sender}} {{hash}}", &context).unwrap(),
+        [
+            "import",
+            "/downloads/my photo; rm -rf ~.jpg",
+            "--from=Alice",
+            "abc"
+        ]
+    );
+}
+
+#[test]
+fn invalid_hooks_are_rejected_by_validation() {
+    let mut config = LocalSendConfig {
+        hooks: vec![HookConfig {
+            command: "\"unterminated".to_string(),
+            on: HookTrigger::File,
+            timeout_secs: 10,
+        }],
+        ..LocalSendConfig::default()
+    };
+    assert!(config.validate().is_err());
+    config.hooks[0].command = "true".to_string();
+    assert!(config.validate().is_ok());
+    config.hooks[0].timeout_secs = 0;
+    assert!(config.validate().is_err());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn commands_are_captured_and_timed_out() {
+    let args = |script: &str| ["sh", "-c", script].map(str::to_string);
+
+    let output = hooks::execute(&args("echo out; echo err >&2; exit 3"), Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(output.code, Some(3));
+    assert!(!output.timed_out);
+    assert_eq!(output.output, "out\nerr\n");
+
+    let output = hooks::execute(&args("sleep 5"), Duration::from_millis(100))
+        .await
+        .unwrap();
+    assert!(output.timed_out);
+    assert!(!output.success());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn received_files_run_hooks_and_record_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let log_path = dir.path().join("audit.jsonl");
+    let config = LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        hooks: vec![
+            HookConfig {
+                command: "echo file {{path}} {{sender}}".to_string(),
+                on: HookTrigger::File,
+                timeout_secs: 5,
+            },
+            HookConfig {
+                command: "echo session {{session_id}}".to_string(),
+                on: HookTrigger::Session,
+                timeout_secs: 5,
+            },
+        ],
+        ..LocalSendConfig::default()
+    };
+    let hooks = PostReceiveHooks::from_config(&config)
+        .unwrap()
+        .with_audit_log(AuditLog::open(&log_path).unwrap());
+
+    let events = EventBus::default();
+    let cancel = CancellationToken::new();
+    let task = tokio::spawn(hooks.run(
+        events.subscribe(),
+        SessionManager::with_event_bus(events.clone()),
+        cancel.clone(),
+    ));
+    events.emit(ProtocolEvent::SessionRequested {
+        session_id: "s1".to_string(),
+        sender: "Phone".to_string(),
+        files: Vec::new(),
+    });
+    events.emit(ProtocolEvent::FileReceived {
+        session_id: "s1".to_string(),
+        path: PathBuf::from("a.txt"),
+        sha256: None,
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    cancel.cancel();
+    task.await.unwrap();
+
+    let records = audit::records(&log_path).unwrap();
+    assert_eq!(records.len(), 1);
+    let entry = &records[0].entry;
+    assert_eq!(entry.action, AuditAction::Hook);
+    assert_eq!(entry.session_id.as_deref(), Some("s1"));
+    assert_eq!(entry.reason.as_deref(), Some("退出码 0"));
+    assert_eq!(entry.output.as_deref(), Some("file a.txt Phone\n"));
+}
+
+#[test]
+fn no_hooks_means_nothing_to_run() {
+    assert!(PostReceiveHooks::from_config(&LocalSendConfig::default()).is_none());
+}
@@ -0,0 +1,84 @@
+//! 文本收件箱测试
+
+use std::time::Duration;
+
+use peersend_protocol::inbox::{Inbox, INBOX_CAPACITY};
+use peersend_protocol::{EventBus, ProtocolEvent};
+use tokio_util::sync::CancellationToken;
+
+#[test]
+fn messages_are_kept_across_opens() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("inbox.json");
+    let inbox = Inbox::new(&path);
+    assert!(inbox.list().unwrap().is_empty());
+
+    let first = inbox.push("phone", "Phone", "wifi password: hunter2").unwrap();
+    let second = inbox.push("laptop", "Laptop", "https://example.com\nsecond line").unwrap();
+    assert_eq!((first.id, second.id), (1, 2));
+
+    // 另一个进程打开同一个文件
+    let other = Inbox::new(&path);
+    let messages = other.list().unwrap();
+    assert_eq!(messages, [first.clone(), second.clone()]);
+    assert_eq!(other.get(2).unwrap().unwrap().text, "https://example.com\nsecond line");
+    assert_eq!(second.preview(60), "https://example.com…");
+    assert_eq!(first.preview(4), "wifi…");
+    assert_eq!(first.preview(60), "wifi password: hunter2");
+
+    assert!(other.delete(1).unwrap());
+    assert!(!other.delete(1).unwrap());
+    assert!(inbox.get(1).unwrap().is_none());
+    // 删除后序号不再复用
+    assert_eq!(inbox.push("phone", "Phone", "again").unwrap().id, 3);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn oldest_messages_are_dropped_when_full() {
+    let dir = tempfile::tempdir().unwrap();
+    let inbox = Inbox::new(dir.path().join("inbox.json"));
+    for i in 0..INBOX_CAPACITY + 5 {
+        inbox.push("phone", "Phone", &i.to_string()).unwrap();
+    }
+    let messages = inbox.list().unwrap();
+    assert_eq!(messages.len(), INBOX_CAPACITY);
+    assert_eq!(messages[0].text, "5");
+    assert_eq!(messages.last().unwrap().id, INBOX_CAPACITY as u64 + 5);
+}
+
+#[tokio::test]
+async fn received_texts_are_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("inbox.json");
+    let events = EventBus::default();
+    let cancel = CancellationToken::new();
+    let task = tokio::spawn(Inbox::new(&path).run(events.subscribe(), cancel.clone()));
+
+    events.emit(ProtocolEvent::TextReceived {
+        sender_id: "phone".to_string(),
+        sender: "Phone".to_string(),
+        text: "hello".to_string(),
+    });
+    let inbox = Inbox::new(&path);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while inbox.list().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    let messages = inbox.list().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!((messages[0].sender_id.as_str(), messages[0].text.as_str()), ("phone", "hello"));
+
+    cancel.cancel();
+    task.await.unwrap();
+}
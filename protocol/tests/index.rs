@@ -0,0 +1,136 @@
+//! 已接收文件浏览页面测试
+
+use std::sync::Arc;
+
+use peersend_protocol::server::index::FileIndex;
+use peersend_protocol::{FileInfo, LocalSendConfig, ProtocolError, SessionManager};
+use reqwest::StatusCode;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+async fn serve(dir: &std::path::Path, sessions: SessionManager) -> (String, CancellationToken) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let index = Arc::new(FileIndex::new(dir, "secret", Arc::new(Mutex::new(sessions))));
+    let cancel = CancellationToken::new();
+    tokio::spawn(index.serve(listener, cancel.clone()));
+    (url, cancel)
+}
+
+#[tokio::test]
+async fn requires_password() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.txt"), b"hello").unwrap();
+    let (url, cancel) = serve(dir.path(), SessionManager::new()).await;
+    let client = reqwest::Client::new();
+
+    let response = client.get(format!("{}/", url)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers()["www-authenticate"]
+        .to_str()
+        .unwrap()
+        .starts_with("Basic realm="));
+
+    let response = client
+        .get(format!("{}/files/a.txt", url))
+        .basic_auth("anyone", Some("wrong"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    cancel.cancel();
+}
+
+#[tokio::test]
+async fn lists_and_downloads_completed_files() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("report <1>.txt"), b"0123456789").unwrap();
+    std::fs::write(dir.path().join("incoming.bin"), b"partial").unwrap();
+    std::fs::write(dir.path().join(".hidden"), b"").unwrap();
+    std::fs::create_dir(dir.path().join("folder")).unwrap();
+
+    // 仍在传输中的会话的文件不列出
+    let sessions = SessionManager::new();
+    sessions
+        .create_session(
+            "sender".to_string(),
+            "receiver".to_string(),
+            vec![FileInfo {
+                id: "file".to_string(),
+                name: "incoming.bin".to_string(),
+                size: 100,
+                file_type: "application/octet-stream".to_string(),
+                metadata: None,
+                sha256: None,
+            }],
+        )
+        .await;
+    let (url, cancel) = serve(dir.path(), sessions).await;
+    let client = reqwest::Client::new();
+
+    let page = client
+        .get(format!("{}/", url))
+        .basic_auth("", Some("secret"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(page.status(), StatusCode::OK);
+    let html = page.text().await.unwrap();
+    assert!(html.contains("href=\"/files/report%20%3C1%3E%2Etxt\""));
+    assert!(html.contains("report &lt;1&gt;.txt"));
+    assert!(!html.contains("incoming.bin"));
+    assert!(!html.contains("hidden"));
+    assert!(!html.contains("folder"));
+
+    let file = client
+        .get(format!("{}/files/report%20%3C1%3E%2Etxt", url))
+        .basic_auth("", Some("secret"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(file.status(), StatusCode::OK);
+    assert!(file.headers()["content-disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    assert_eq!(file.bytes().await.unwrap().as_ref(), b"0123456789");
+
+    let partial = client
+        .get(format!("{}/files/report%20%3C1%3E.txt", url))
+        .basic_auth("", Some("secret"))
+        .header("Range", "bytes=2-4")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(partial.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(partial.headers()["content-range"], "bytes 2-4/10");
+    assert_eq!(partial.bytes().await.unwrap().as_ref(), b"234");
+
+    for path in ["incoming.bin", "..%2Fetc%2Fpasswd", "missing.txt", ".hidden"] {
+        let response = client
+            .get(format!("{}/files/{}", url, path))
+            .basic_auth("", Some("secret"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+    }
+    cancel.cancel();
+}
+
+#[test]
+fn browse_requires_password() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        browse_port: Some(8080),
+        ..LocalSendConfig::default()
+    };
+    assert!(matches!(config.validate(), Err(ProtocolError::InvalidConfig(_))));
+
+    config.browse_password = Some("secret".to_string());
+    config.validate().unwrap();
+
+    config.browse_port = Some(config.port);
+    assert!(matches!(config.validate(), Err(ProtocolError::InvalidConfig(_))));
+}
@@ -0,0 +1,110 @@
+//! 会话日志与 .part 文件测试
+
+use std::path::Path;
+
+use peersend_protocol::journal::{JournalFile, SessionJournal};
+use peersend_protocol::storage;
+use peersend_protocol::transport::{self, StreamHeader};
+
+fn file(dir: &Path, id: &str, size: u64) -> JournalFile {
+    JournalFile {
+        id: id.to_string(),
+        name: format!("{}.bin", id),
+        size,
+        sha256: None,
+        path: dir.join(format!("{}.bin", id)),
+    }
+}
+
+#[test]
+fn interrupted_session_survives_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("journal.jsonl");
+    let journal = SessionJournal::open(&path).unwrap();
+    journal
+        .begin("s1", "phone", "Phone", vec![file(dir.path(), "a", 10), file(dir.path(), "b", 20)])
+        .unwrap();
+    journal.offset("s1", "a", 10).unwrap();
+    journal.offset("s1", "b", 4).unwrap();
+    // .part 的实际长度比日志中的偏移更新
+    std::fs::write(storage::part_path(&dir.path().join("b.bin"), "s1", "b"), [0u8; 7]).unwrap();
+    drop(journal);
+
+    let journal = SessionJournal::open(&path).unwrap();
+    let sessions = journal.incomplete().unwrap();
+    assert_eq!(sessions.len(), 1);
+    let session = &sessions[0];
+    assert_eq!((session.session_id.as_str(), session.peer.as_str()), ("s1", "phone"));
+    assert!(session.files[0].is_complete());
+    assert_eq!(session.files[1].offset, 7);
+    assert_eq!((session.received_bytes(), session.total_bytes()), (17, 30));
+
+    // 重新打开后仍可继续记录，全部完成时会话结束
+    journal.offset("s1", "b", 20).unwrap();
+    assert!(journal.incomplete().unwrap().is_empty());
+}
+
+#[test]
+fn finished_sessions_are_compacted() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("journal.jsonl");
+    let journal = SessionJournal::open(&path).unwrap();
+    journal.begin("done", "phone", "Phone", vec![file(dir.path(), "a", 1)]).unwrap();
+    journal.offset("done", "a", 1).unwrap();
+    journal.begin("open", "phone", "Phone", vec![file(dir.path(), "b", 1)]).unwrap();
+    drop(journal);
+
+    let journal = SessionJournal::open(&path).unwrap();
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert!(!contents.contains("\"done\""));
+    assert_eq!(contents.lines().count(), 1);
+    assert_eq!(journal.incomplete().unwrap()[0].session_id, "open");
+}
+
+#[test]
+fn discard_removes_part_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let journal = SessionJournal::open(dir.path().join("journal.jsonl")).unwrap();
+    journal.begin("s1", "phone", "Phone", vec![file(dir.path(), "a", 10)]).unwrap();
+    let part = storage::part_path(&dir.path().join("a.bin"), "s1", "a");
+    std::fs::write(&part, b"abc").unwrap();
+
+    assert_eq!(journal.discard("s1").unwrap(), Some(1));
+    assert!(!part.exists());
+    assert!(journal.incomplete().unwrap().is_empty());
+    assert_eq!(journal.discard("s1").unwrap(), None);
+}
+
+#[tokio::test]
+async fn truncated_stream_leaves_part_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("a.txt");
+    let header = StreamHeader {
+        session_id: "session".to_string(),
+        file_id: "file".to_string(),
+        token: String::new(),
+        size: 6,
+        offset: 0,
+        sparse: None,
+    };
+
+    let result = transport::receive_file(&mut &b"abc"[..], &header, &path, None).await;
+    assert!(result.is_err());
+    assert!(!path.exists());
+    let part = storage::part_path(&path, "session", "file");
+    assert_eq!(std::fs::read(&part).unwrap(), b"abc");
+    // 其他会话接收同名文件时写入自己的 .part
+    let other = StreamHeader {
+        session_id: "other".to_string(),
+        ..header.clone()
+    };
+    assert!(transport::receive_file(&mut &b"xy"[..], &other, &path, None).await.is_err());
+    assert_eq!(std::fs::read(&part).unwrap(), b"abc");
+    assert_eq!(std::fs::read(storage::part_path(&path, "other", "file")).unwrap(), b"xy");
+
+    transport::receive_file(&mut &b"abcdef"[..], &header, &path, None)
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"abcdef");
+    assert!(!part.exists());
+}
@@ -0,0 +1,55 @@
+//! 文件流保活测试
+
+use std::io::ErrorKind;
+
+use peersend_protocol::keepalive::{self, IdleTimeout, Liveness, LIVENESS_TIMEOUT};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[tokio::test(start_paused = true)]
+async fn liveness_ends_when_receiver_closes() {
+    let (mut receiver, sender) = tokio::io::duplex(64);
+    let mut liveness = Liveness::new(sender);
+    receiver.write_all(&[0, 0]).await.unwrap();
+    drop(receiver);
+    liveness.wait().await.unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn liveness_times_out_after_pings_stop() {
+    let (mut receiver, sender) = tokio::io::duplex(64);
+    let mut liveness = Liveness::new(sender);
+
+    // 旧版本接收端不回写保活字节，不做检测
+    let silent = tokio::time::timeout(LIVENESS_TIMEOUT * 2, liveness.wait()).await;
+    assert!(silent.is_err());
+
+    receiver.write_all(&[0]).await.unwrap();
+    let error = liveness.wait().await.unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::TimedOut);
+}
+
+#[tokio::test(start_paused = true)]
+async fn pings_keep_liveness_alive() {
+    let (mut receiver, sender) = tokio::io::duplex(64);
+    let mut liveness = Liveness::new(sender);
+    let pinging = tokio::time::timeout(LIVENESS_TIMEOUT * 4, keepalive::ping(&mut receiver));
+    tokio::select! {
+        result = liveness.wait() => panic!("接收端仍在回写保活字节: {:?}", result),
+        _ = pinging => {}
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn idle_reader_times_out() {
+    let (mut sender, receiver) = tokio::io::duplex(64);
+    let mut reader = IdleTimeout::new(receiver);
+    sender.write_all(b"data").await.unwrap();
+
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"data");
+
+    let error = reader.read(&mut buf).await.unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::TimedOut);
+    assert!(keepalive::is_timed_out(&error.into()));
+}
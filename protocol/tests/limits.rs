@@ -0,0 +1,129 @@
+//! 接收文件大小和数量上限测试
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use peersend_protocol::dto::v2::{FileDto, PrepareUploadRequestDto, RegisterDto, PROTOCOL_VERSION};
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::{
+    DiscoveryManager, LocalSendConfig, ProtocolError, QuickSave, SessionManager,
+};
+use tokio::sync::Mutex;
+
+const GB: u64 = 1024 * 1024 * 1024;
+
+fn server(config: LocalSendConfig) -> (LocalSendServer, SessionManager) {
+    let sessions = SessionManager::new();
+    let config = LocalSendConfig {
+        quick_save: QuickSave::On,
+        ..config
+    };
+    let server = LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        config,
+        Arc::new(Mutex::new(sessions.clone())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    );
+    (server, sessions)
+}
+
+fn request(sizes: &[u64]) -> PrepareUploadRequestDto {
+    let files = sizes
+        .iter()
+        .enumerate()
+        .map(|(i, &size)| {
+            let id = format!("file{}", i);
+            let file = FileDto {
+                id: id.clone(),
+                file_name: format!("video{}.mkv", i),
+                size,
+                file_type: "video/x-matroska".to_string(),
+                sha256: None,
+                preview: None,
+                metadata: None,
+            };
+            (id, file)
+        })
+        .collect::<HashMap<_, _>>();
+    PrepareUploadRequestDto {
+        info: RegisterDto {
+            alias: "Phone".to_string(),
+            version: PROTOCOL_VERSION.to_string(),
+            device_model: None,
+            device_type: Some("mobile".to_string()),
+            fingerprint: "phone".to_string(),
+            port: 53317,
+            protocol: "http".to_string(),
+            download: false,
+        },
+        files,
+    }
+}
+
+fn rejection(result: peersend_protocol::Result<impl std::fmt::Debug>) -> String {
+    match result {
+        Err(ProtocolError::Rejected(reason)) => reason,
+        other => panic!("应被拒绝: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn oversized_requests_are_rejected() {
+    let (server, sessions) = server(LocalSendConfig {
+        max_file_size: Some(4 * GB),
+        max_session_size: Some(10 * GB),
+        max_files: Some(3),
+        ..LocalSendConfig::default()
+    });
+
+    let reason = rejection(
+        server
+            .prepare_upload("192.0.2.5", request(&[GB, 200 * GB]), None)
+            .await,
+    );
+    assert!(reason.contains("video1.mkv"), "{}", reason);
+    assert!(reason.contains("200.0 GB"), "{}", reason);
+
+    let reason = rejection(
+        server
+            .prepare_upload("192.0.2.5", request(&[4 * GB, 4 * GB, 4 * GB]), None)
+            .await,
+    );
+    assert!(reason.contains("12.0 GB"), "{}", reason);
+
+    let reason = rejection(
+        server
+            .prepare_upload("192.0.2.5", request(&[1, 1, 1, 1]), None)
+            .await,
+    );
+    assert!(reason.contains("文件数 4"), "{}", reason);
+    assert!(sessions.get_all_sessions().await.is_empty());
+
+    server
+        .prepare_upload("192.0.2.5", request(&[4 * GB, 4 * GB, 2 * GB]), None)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn no_limits_by_default() {
+    let (server, _) = server(LocalSendConfig::default());
+    server
+        .prepare_upload("192.0.2.5", request(&[200 * GB, 200 * GB]), None)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn zero_limits_are_invalid() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        max_files: Some(0),
+        ..LocalSendConfig::default()
+    };
+    assert!(matches!(
+        config.validate(),
+        Err(ProtocolError::InvalidConfig(_))
+    ));
+}
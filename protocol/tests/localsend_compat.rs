@@ -0,0 +1,146 @@
+//! 与官方 LocalSend 客户端的线上格式兼容性测试
+//!
+//! 样本位于 `tests/fixtures/localsend`，说明见该目录的 README。
+//! 设置 `PEERSEND_LOCALSEND_URL` 后，被忽略的测试会访问该地址上运行的 LocalSend 实例，例如
+//! `PEERSEND_LOCALSEND_URL=https://192.168.1.20:53317 cargo test -p peersend-protocol --test localsend_compat -- --ignored`
+
+use std::path::{Path, PathBuf};
+
+use peersend_protocol::dto::v2::{
+    self, InfoDto, MulticastDto, PrepareUploadRequestDto, PrepareUploadResponseDto, RegisterDto,
+};
+use peersend_protocol::http::{HttpClient, HttpRequest, ReqwestClient};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/localsend");
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// 反序列化后再序列化
+fn round_trip<T: DeserializeOwned + Serialize>(body: &Value) -> Result<Value, String> {
+    let parsed: T = serde_json::from_value(body.clone()).map_err(|e| e.to_string())?;
+    serde_json::to_value(parsed).map_err(|e| e.to_string())
+}
+
+/// expected 中的每个字段都须以相同的值出现在 actual 中
+fn assert_preserved(expected: &Value, actual: &Value, path: &str) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, value) in expected {
+                let field = format!("{}.{}", path, key);
+                let Some(actual) = actual.get(key) else {
+                    panic!("缺少字段 {}", field);
+                };
+                assert_preserved(value, actual, &field);
+            }
+        }
+        _ => assert_eq!(expected, actual, "字段 {} 不一致", path),
+    }
+}
+
+#[test]
+fn fixtures_round_trip_without_losing_fields() {
+    let paths = fixtures();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let fixture: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        let body = &fixture["body"];
+        let result = match fixture["kind"].as_str().unwrap_or_default() {
+            "info" => round_trip::<InfoDto>(body),
+            "register" => round_trip::<RegisterDto>(body),
+            "multicast" => round_trip::<MulticastDto>(body),
+            "prepare-upload-request" => round_trip::<PrepareUploadRequestDto>(body),
+            "prepare-upload-response" => round_trip::<PrepareUploadResponseDto>(body),
+            kind => panic!("{}: 未知的样本类型 {:?}", name, kind),
+        };
+        let actual = result.unwrap_or_else(|e| panic!("{}: 无法解析: {}", name, e));
+        assert_preserved(body, &actual, &name);
+    }
+}
+
+#[test]
+fn v1_announcement_flag_is_accepted() {
+    let multicast: MulticastDto = serde_json::from_value(serde_json::json!({
+        "alias": "Old Phone",
+        "version": "1.0",
+        "fingerprint": "random string",
+        "port": 53317,
+        "protocol": "http",
+        "announcement": true
+    }))
+    .unwrap();
+    assert_eq!(multicast.announce, Some(true));
+}
+
+#[test]
+fn registration_maps_to_device() {
+    let register = RegisterDto {
+        alias: "Nice Orange".to_string(),
+        version: v2::PROTOCOL_VERSION.to_string(),
+        device_model: Some("Samsung".to_string()),
+        device_type: None,
+        fingerprint: "fingerprint".to_string(),
+        port: 53318,
+        protocol: "https".to_string(),
+        download: false,
+    };
+
+    let device = register.to_device("192.0.2.7");
+    assert_eq!(device.id, "fingerprint");
+    assert_eq!(device.name, "Nice Orange");
+    assert_eq!(device.device_type, "desktop");
+    assert_eq!((device.ip.as_str(), device.port), ("192.0.2.7", 53318));
+}
+
+#[tokio::test]
+#[ignore = "需要设置 PEERSEND_LOCALSEND_URL 指向运行中的 LocalSend"]
+async fn live_localsend_peer_accepts_registration() {
+    let base = std::env::var("PEERSEND_LOCALSEND_URL").expect("未设置 PEERSEND_LOCALSEND_URL");
+    let base = base.trim_end_matches('/');
+    let http = ReqwestClient::default();
+
+    let info: InfoDto = http
+        .execute(HttpRequest::get(format!("{}{}", base, v2::INFO_PATH)))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(info.version.starts_with("2."), "对方协议版本: {}", info.version);
+
+    let register = RegisterDto {
+        alias: "PeerSend compat".to_string(),
+        version: v2::PROTOCOL_VERSION.to_string(),
+        device_model: Some("PeerSend".to_string()),
+        device_type: Some("headless".to_string()),
+        fingerprint: uuid::Uuid::new_v4().to_string(),
+        port: 53317,
+        protocol: "http".to_string(),
+        download: false,
+    };
+    let response: InfoDto = http
+        .execute(
+            HttpRequest::post(format!("{}{}", base, v2::REGISTER_PATH))
+                .json(&register)
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(response.fingerprint, info.fingerprint);
+}
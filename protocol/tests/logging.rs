@@ -0,0 +1,69 @@
+//! JSON 日志格式测试
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use peersend_protocol::logging::{JsonLayer, LogFormat};
+use serde_json::Value;
+use tracing_subscriber::layer::SubscriberExt;
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn events_are_written_as_json_lines() {
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::registry().with(JsonLayer::new(move || writer.clone()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(
+            "prepare_upload",
+            peer = "192.0.2.7",
+            session_id = tracing::field::Empty
+        );
+        let _guard = span.enter();
+        span.record("session_id", "abc");
+        tracing::info!(file = "photo.jpg", size = 42u64, "收到文件");
+        tracing::warn!(peer = "override", "第二条");
+    });
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+
+    let first = &lines[0];
+    assert_eq!(first["message"], "收到文件");
+    assert_eq!(first["level"], "INFO");
+    assert_eq!(first["file"], "photo.jpg");
+    assert_eq!(first["size"], 42);
+    assert_eq!(first["peer"], "192.0.2.7");
+    assert_eq!(first["session_id"], "abc");
+    assert_eq!(first["span"], "prepare_upload");
+    assert!(first["timestamp"].as_u64().unwrap() > 0);
+
+    // 事件的字段覆盖 span 的同名字段
+    assert_eq!(lines[1]["peer"], "override");
+    assert_eq!(lines[1]["level"], "WARN");
+}
+
+#[test]
+fn format_parses_case_insensitively() {
+    assert_eq!("JSON".parse::<LogFormat>(), Ok(LogFormat::Json));
+    assert_eq!("text".parse::<LogFormat>(), Ok(LogFormat::Text));
+    assert!("yaml".parse::<LogFormat>().is_err());
+}
@@ -0,0 +1,89 @@
+//! 哈希清单测试
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use peersend_protocol::hash::sha256_hex;
+use peersend_protocol::storage::manifest::{self, ManifestWriter};
+use peersend_protocol::{EventBus, LocalSendConfig, ProtocolEvent, SessionState};
+use tokio_util::sync::CancellationToken;
+
+#[test]
+fn lines_match_sha256sum() {
+    assert_eq!(manifest::manifest_line("ab", "a b.txt"), "ab  a b.txt\n");
+    assert_eq!(
+        manifest::manifest_line("ab", "line\nbreak\\x"),
+        "\\ab  line\\nbreak\\\\x\n"
+    );
+}
+
+#[tokio::test]
+async fn manifests_list_existing_files_relative_to_download_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("quarantine")).unwrap();
+    std::fs::write(dir.path().join("photo.jpg"), b"photo").unwrap();
+    std::fs::write(dir.path().join("quarantine/setup.exe"), b"setup").unwrap();
+    let files = ["photo.jpg", "quarantine/setup.exe", "deleted.zip"].map(|f| dir.path().join(f));
+
+    let path = manifest::write_manifest(dir.path(), "0123456789abcdef", &files)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(path, dir.path().join("SHA256SUMS-01234567"));
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        format!(
+            "{}  photo.jpg\n{}  quarantine/setup.exe\n",
+            sha256_hex(b"photo"),
+            sha256_hex(b"setup")
+        )
+    );
+
+    let missing = [dir.path().join("deleted.zip")];
+    assert!(manifest::write_manifest(dir.path(), "s2", &missing).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn manifests_are_written_for_finished_sessions_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        ..LocalSendConfig::default()
+    };
+    assert!(ManifestWriter::from_config(&config).is_none());
+    let writer = ManifestWriter::from_config(&LocalSendConfig {
+        hash_manifest: true,
+        ..config
+    })
+    .unwrap();
+
+    let events = EventBus::default();
+    let cancel = CancellationToken::new();
+    let task = tokio::spawn(writer.run(events.subscribe(), cancel.clone()));
+    for (session_id, state) in [
+        ("finished", SessionState::Finished),
+        ("cancelled", SessionState::Cancelled),
+    ] {
+        let path = dir.path().join(format!("{}.txt", session_id));
+        std::fs::write(&path, session_id).unwrap();
+        events.emit(ProtocolEvent::FileReceived {
+            session_id: session_id.to_string(),
+            path,
+            sha256: None,
+        });
+        events.emit(ProtocolEvent::SessionStateChanged {
+            session_id: session_id.to_string(),
+            state,
+        });
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    cancel.cancel();
+    task.await.unwrap();
+
+    let manifests: Vec<PathBuf> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.to_string_lossy().contains(manifest::MANIFEST_PREFIX))
+        .collect();
+    assert_eq!(manifests, [manifest::manifest_path(dir.path(), "finished")]);
+}
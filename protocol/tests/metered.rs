@@ -0,0 +1,88 @@
+//! 省流模式测试
+
+use std::time::Duration;
+
+use peersend_protocol::metered::{self, Metered};
+use peersend_protocol::throttle::Throttle;
+use peersend_protocol::{LocalSendConfig, MeteredConfig, MeteredMode};
+
+fn metered(mode: MeteredMode) -> Metered {
+    Metered::new(MeteredConfig {
+        mode,
+        bandwidth_limit: Some(1_000),
+        max_file_size: Some(100),
+        announce_interval_secs: 60,
+    })
+}
+
+#[test]
+fn limits_apply_only_while_active() {
+    let normal = Duration::from_secs(5);
+    let metered = metered(MeteredMode::Auto);
+    assert!(!metered.is_active());
+    assert!(metered.may_limit());
+    assert_eq!(metered.limit(), None);
+    assert!(!metered.holds(1_000_000));
+    assert_eq!(metered.announce_interval(normal), normal);
+
+    let changes = metered.subscribe();
+    metered.set_active(true);
+    assert!(changes.has_changed().unwrap());
+    assert_eq!(metered.limit(), Some(1_000));
+    assert!(metered.holds(101));
+    assert!(!metered.holds(100));
+    assert_eq!(metered.announce_interval(normal), Duration::from_secs(60));
+
+    metered.set_active(false);
+    assert_eq!(metered.limit(), None);
+}
+
+#[test]
+fn modes_decide_the_initial_state() {
+    assert!(metered(MeteredMode::On).is_active());
+
+    let off = metered(MeteredMode::Off);
+    off.set_active(true);
+    assert!(!off.is_active());
+    assert!(!off.may_limit());
+
+    // 未配置省流模式
+    let unset = Metered::from_config(&LocalSendConfig::default());
+    unset.set_active(true);
+    assert!(!unset.is_active());
+}
+
+#[test]
+fn throttle_uses_the_smaller_limit() {
+    let metered = metered(MeteredMode::Auto);
+    let throttle = Throttle::new(None, Vec::new()).with_metered(metered.clone());
+    assert!(throttle.is_limited());
+    assert_eq!(throttle.current_limit(), None);
+    metered.set_active(true);
+    assert_eq!(throttle.current_limit(), Some(1_000));
+
+    let throttle = Throttle::new(Some(500), Vec::new()).with_metered(metered.clone());
+    assert_eq!(throttle.current_limit(), Some(500));
+    metered.set_active(false);
+    assert_eq!(throttle.current_limit(), Some(500));
+}
+
+#[test]
+fn pmset_output_is_parsed() {
+    let battery = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=1)\t80%; discharging";
+    assert_eq!(metered::parse_pmset(battery), Some(true));
+    assert_eq!(metered::parse_pmset("Now drawing from 'AC Power'\n"), Some(false));
+    assert_eq!(metered::parse_pmset("No batteries"), None);
+}
+
+#[test]
+fn zero_limits_are_rejected() {
+    let config = LocalSendConfig {
+        metered: Some(MeteredConfig {
+            bandwidth_limit: Some(0),
+            ..MeteredConfig::default()
+        }),
+        ..LocalSendConfig::default()
+    };
+    assert!(config.validate().is_err());
+}
@@ -0,0 +1,105 @@
+//! 运行指标测试
+
+use std::time::Duration;
+
+use peersend_protocol::metrics::{Metrics, TextEncoder};
+use peersend_protocol::{
+    DiscoveryManager, EventBus, FileInfo, LocalSendConfig, ProtocolError, ProtocolEvent,
+    SessionManager, SessionState,
+};
+use tokio_util::sync::CancellationToken;
+
+fn file(size: u64) -> FileInfo {
+    FileInfo {
+        id: "file".to_string(),
+        name: "photo.jpg".to_string(),
+        size,
+        file_type: "image/jpeg".to_string(),
+        metadata: None,
+        sha256: None,
+    }
+}
+
+#[test]
+fn encoder_writes_text_format() {
+    let mut encoder = TextEncoder::new();
+    encoder.counter("demo_total", "计数\n第二行", 3);
+    encoder.gauge_by("demo_state", "状态", "kind", &[("a\"b", 1.5)]);
+    assert_eq!(
+        encoder.finish(),
+        "# HELP demo_total 计数\\n第二行\n\
+         # TYPE demo_total counter\n\
+         demo_total 3\n\
+         # HELP demo_state 状态\n\
+         # TYPE demo_state gauge\n\
+         demo_state{kind=\"a\\\"b\"} 1.5\n"
+    );
+}
+
+#[tokio::test]
+async fn metrics_count_events() {
+    let events = EventBus::default();
+    let sessions = SessionManager::with_event_bus(events.clone());
+    let discovery = DiscoveryManager::with_event_bus(events.clone());
+    let metrics = Metrics::new();
+    let cancel = CancellationToken::new();
+    let task = tokio::spawn(metrics.clone().run(
+        events.subscribe(),
+        sessions.clone(),
+        cancel.clone(),
+    ));
+
+    let finished = sessions
+        .create_session("peer".to_string(), "me".to_string(), vec![file(42)])
+        .await;
+    sessions.report_progress(&finished.id, "file", 42).await;
+    sessions
+        .set_state(&finished.id, SessionState::Finished)
+        .await;
+    let waiting = sessions
+        .create_session("peer".to_string(), "me".to_string(), vec![file(7)])
+        .await;
+    events.emit(ProtocolEvent::Error {
+        session_id: None,
+        message: "boom".to_string(),
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.sessions_created, 2);
+    assert_eq!(snapshot.sessions_finished, 1);
+    assert_eq!(snapshot.bytes_transferred, 42);
+    assert_eq!(snapshot.errors, 1);
+
+    let mut encoder = TextEncoder::new();
+    metrics.encode(&mut encoder, &sessions, &discovery).await;
+    let text = encoder.finish();
+    assert!(text.contains("peersend_sessions_total{state=\"created\"} 2\n"));
+    assert!(text.contains("peersend_transferred_bytes_total 42\n"));
+    assert!(text.contains("peersend_sessions{state=\"waiting\"} 1\n"));
+    assert!(text.contains("peersend_devices 0\n"));
+    assert!(sessions.get_session(&waiting.id).await.is_some());
+
+    cancel.cancel();
+    task.await.unwrap();
+}
+
+#[test]
+fn metrics_port_must_not_clash() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        metrics_port: Some(9617),
+        ..LocalSendConfig::default()
+    };
+    config.validate().unwrap();
+
+    let clash = LocalSendConfig {
+        metrics_port: Some(config.port),
+        ..config
+    };
+    assert!(matches!(
+        clash.validate(),
+        Err(ProtocolError::InvalidConfig(_))
+    ));
+}
@@ -0,0 +1,30 @@
+//! 文件类型检测测试
+
+use peersend_protocol::mime;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+#[test]
+fn content_takes_precedence_over_extension() {
+    assert_eq!(mime::detect("photo.png", PNG), "image/png");
+    // 改过扩展名的图片按内容识别
+    assert_eq!(mime::detect("photo.txt", PNG), "image/png");
+    assert_eq!(mime::detect("scan", b"%PDF-1.7\n"), "application/pdf");
+}
+
+#[test]
+fn extension_is_used_for_featureless_formats() {
+    assert_eq!(mime::detect("notes.txt", b"hello"), "text/plain");
+    assert_eq!(mime::detect("index.HTML", b""), "text/html");
+    assert_eq!(mime::detect("blob", b"hello"), mime::OCTET_STREAM);
+}
+
+#[test]
+fn extension_refines_container_formats() {
+    let zip = b"PK\x03\x04\x14\0\0\0\0\0";
+    assert_eq!(mime::detect("archive", zip), "application/zip");
+    assert_eq!(
+        mime::detect("game.apk", zip),
+        "application/vnd.android.package-archive"
+    );
+}
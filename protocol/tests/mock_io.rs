@@ -0,0 +1,278 @@
+//! 基于内存 HTTP 与文件系统的确定性测试
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::dto::RegisterResponse;
+use peersend_protocol::http::{HttpResponse, Scheme};
+use peersend_protocol::session::{FileReceiver, FileSender};
+use peersend_protocol::testing::{peer_device, Fault, MockFs, MockHttp};
+use peersend_protocol::{
+    net, FileInfo, FileSession, LocalSendClient, LocalSendConfig, ProtocolError,
+};
+
+fn client(http: &Arc<MockHttp>, fs: MockFs) -> LocalSendClient {
+    LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_fs(Arc::new(fs))
+}
+
+#[tokio::test]
+async fn upload_survives_short_reads() {
+    let http = MockHttp::new();
+    http.accept_all();
+    let fs = MockFs::new().with_short_reads(3);
+    fs.insert("/src/a.txt", b"hello peersend".to_vec());
+
+    let session = client(&http, fs)
+        .send_files(&peer_device(), &["/src/a.txt"])
+        .await
+        .unwrap();
+
+    let requests = http.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0].url.ends_with("/prepare-upload"));
+    // 上传使用接收方分配的会话 ID 和该文件的令牌
+    assert_eq!(requests[1].query_param("sessionId"), Some("remote"));
+    assert_eq!(requests[1].query_param("token"), Some(session.files[0].id.as_str()));
+    assert_eq!(requests[1].body, b"hello peersend");
+}
+
+#[tokio::test]
+async fn dropped_upload_fails_session() {
+    let http = MockHttp::new();
+    http.accept_all();
+    http.inject("/upload", Fault::Drop);
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"data".to_vec());
+
+    let result = client(&http, fs).send_files(&peer_device(), &["/src/a.txt"]).await;
+    assert!(matches!(result, Err(ProtocolError::Io(_))));
+}
+
+#[tokio::test]
+async fn error_status_is_reported() {
+    let http = MockHttp::new();
+    http.accept_all();
+    http.inject("/prepare-upload", Fault::Status(403));
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"data".to_vec());
+
+    let result = client(&http, fs).send_files(&peer_device(), &["/src/a.txt"]).await;
+    assert!(matches!(result, Err(ProtocolError::Status(403))));
+}
+
+#[tokio::test]
+async fn delayed_upload_can_be_cancelled_or_timed_out() {
+    let http = MockHttp::new();
+    http.accept_all();
+    http.inject("/upload", Fault::Delay(Duration::from_secs(60)));
+    http.inject("/upload", Fault::Delay(Duration::from_secs(60)));
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"data".to_vec());
+    let client = client(&http, fs);
+
+    let timed_out = tokio::time::timeout(
+        Duration::from_millis(50),
+        client.send_files(&peer_device(), &["/src/a.txt"]),
+    )
+    .await;
+    assert!(timed_out.is_err());
+
+    let canceller = client.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        canceller.cancel();
+    });
+    let result = client.send_files(&peer_device(), &["/src/a.txt"]).await;
+    assert!(matches!(result, Err(ProtocolError::Cancelled)));
+}
+
+#[tokio::test]
+async fn receiver_writes_into_mock_fs() {
+    let fs = Arc::new(MockFs::new());
+    let session = FileSession::new(
+        "session".to_string(),
+        "peer".to_string(),
+        "self".to_string(),
+        vec![FileInfo {
+            id: "file".to_string(),
+            name: "out.txt".to_string(),
+            size: 6,
+            file_type: "text/plain".to_string(),
+            metadata: None,
+            sha256: None,
+        }],
+    );
+
+    let mut receiver = FileReceiver::new(session, "/downloads".into()).with_fs(fs.clone());
+    receiver.start_file("out.txt").await.unwrap();
+    receiver.write_chunk(b"abc").await.unwrap();
+    receiver.write_chunk(b"def").await.unwrap();
+    receiver.finish_current_file().await.unwrap();
+
+    assert_eq!(fs.get("/downloads/out.txt").unwrap(), b"abcdef");
+    assert!(receiver.is_complete());
+}
+
+#[tokio::test]
+async fn sender_surfaces_read_errors() {
+    let fs = MockFs::new().fail_reads_after(0);
+    fs.insert("/src/a.txt", b"data".to_vec());
+    let session = FileSession::new(
+        "session".to_string(),
+        "self".to_string(),
+        "peer".to_string(),
+        vec![FileInfo {
+            id: "file".to_string(),
+            name: "/src/a.txt".to_string(),
+            size: 4,
+            file_type: "text/plain".to_string(),
+            metadata: None,
+            sha256: None,
+        }],
+    );
+
+    let mut sender = FileSender::new(session).with_fs(Arc::new(fs));
+    assert!(matches!(sender.read_chunk().await, Err(ProtocolError::Io(_))));
+}
+
+#[tokio::test]
+async fn sender_streams_files_through_one_handle() {
+    let fs = Arc::new(MockFs::new().with_short_reads(3));
+    fs.insert("/src/a.bin", b"0123456789".to_vec());
+    // 文件比声明的大小长，多出的部分不发送
+    fs.insert("/src/b.bin", b"abcdef".to_vec());
+    let file = |id: &str, size| FileInfo {
+        id: id.to_string(),
+        name: format!("/src/{}.bin", id),
+        size,
+        file_type: "application/octet-stream".to_string(),
+        metadata: None,
+        sha256: None,
+    };
+    let session = FileSession::new(
+        "session".to_string(),
+        "self".to_string(),
+        "peer".to_string(),
+        vec![file("a", 10), file("b", 4)],
+    );
+    let mut sender = FileSender::new(session).with_fs(fs.clone());
+    sender.set_chunk_size(4);
+
+    assert_eq!(sender.read_chunk().await.unwrap().unwrap(), b"0123");
+    // 已打开的句柄继续读取，不再从头打开文件
+    fs.insert("/src/a.bin", b"ABCDEFGHIJ".to_vec());
+    // 克隆从自己的位置重新打开文件，不与原发送器共用句柄
+    let mut clone = sender.clone();
+    assert_eq!(clone.read_chunk().await.unwrap().unwrap(), b"EFGH");
+    assert_eq!(sender.read_chunk().await.unwrap().unwrap(), b"4567");
+    assert_eq!(sender.read_chunk().await.unwrap().unwrap(), b"89");
+    assert_eq!(sender.read_chunk().await.unwrap().unwrap(), b"");
+
+    assert!(sender.next_file());
+    assert_eq!(sender.read_chunk().await.unwrap().unwrap(), b"abcd");
+    assert_eq!(sender.read_chunk().await.unwrap().unwrap(), b"");
+    assert_eq!(sender.get_offset(), 14);
+    assert!(!sender.next_file());
+    assert_eq!(sender.read_chunk().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn sender_fails_when_file_shrinks() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("log.txt");
+    std::fs::write(&path, b"0123456789").unwrap();
+    let file = FileInfo {
+        id: "file".to_string(),
+        name: path.to_string_lossy().into_owned(),
+        size: 10,
+        file_type: "text/plain".to_string(),
+        metadata: None,
+        sha256: None,
+    };
+    let session =
+        FileSession::new("session".to_string(), "self".to_string(), "peer".to_string(), vec![file]);
+    let mut sender = FileSender::new(session);
+    sender.set_chunk_size(4);
+
+    assert_eq!(sender.read_chunk().await.unwrap().unwrap(), b"0123");
+    // 文件在发送过程中被截断，不能把剩下的部分当作完整的文件发送
+    std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(6).unwrap();
+    let err = sender.read_chunk().await.unwrap_err();
+    assert!(matches!(&err, ProtocolError::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof));
+    assert_eq!(sender.get_offset(), 4);
+}
+
+#[tokio::test]
+async fn device_checks_use_the_client_http() {
+    let http = MockHttp::new();
+    http.route("/api/v1/localsend/register", |_| {
+        let response = RegisterResponse {
+            id: "peer".to_string(),
+            device_type: "desktop".to_string(),
+            name: "peer".to_string(),
+            version: "test".to_string(),
+            protocol_version: "2.0".to_string(),
+            download: true,
+            port: Some(53318),
+            announcement_id: None,
+            uses_password: false,
+            transports: Vec::new(),
+            addresses: Vec::new(),
+        };
+        HttpResponse {
+            status: 200,
+            body: serde_json::to_vec(&response).unwrap(),
+        }
+    });
+    let client = LocalSendClient::new(LocalSendConfig::default()).with_http(http.clone());
+
+    let device = client.check_device("192.0.2.1").await.unwrap();
+
+    assert_eq!(device.ip, "192.0.2.1");
+    assert_eq!(device.port, 53318);
+    assert_eq!(http.requests().len(), 1);
+
+    // 主机名解析后探测，设备记录解析出的 IP
+    let device = client.check_device("localhost").await.unwrap();
+    assert!(net::parse_ip(&device.ip).is_some_and(|ip| ip.is_loopback()));
+}
+
+#[tokio::test]
+async fn https_is_tried_first_and_remembered() {
+    let http = MockHttp::new();
+    http.accept_all();
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"data".to_vec());
+    fs.insert("/src/b.txt", b"more".to_vec());
+    let client = client(&http, fs);
+    let device = peer_device();
+
+    client.send_files(&device, &["/src/a.txt"]).await.unwrap();
+    client.send_files(&device, &["/src/b.txt"]).await.unwrap();
+
+    assert_eq!(client.device_scheme(&device), Some(Scheme::Https));
+    let requests = http.requests();
+    assert_eq!(requests.len(), 4);
+    assert!(requests.iter().all(|r| r.url.starts_with("https://")));
+}
+
+#[tokio::test]
+async fn peers_without_tls_fall_back_to_http() {
+    let http = MockHttp::new();
+    http.accept_all();
+    http.inject("/prepare-upload", Fault::Drop);
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"data".to_vec());
+    let client = client(&http, fs);
+    let device = peer_device();
+
+    client.send_files(&device, &["/src/a.txt"]).await.unwrap();
+
+    assert_eq!(client.device_scheme(&device), Some(Scheme::Http));
+    // 中断的 HTTPS 请求不会被记录
+    let requests = http.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests.iter().all(|r| r.url.starts_with("http://")));
+}
@@ -0,0 +1,81 @@
+//! MQTT 事件发布测试
+
+use peersend_protocol::mqtt::{MqttMessage, MqttPublisher};
+use peersend_protocol::testing::peer_device;
+use peersend_protocol::{DeviceInfo, FileInfo, MqttConfig, ProtocolEvent, SessionState};
+
+fn json(message: &MqttMessage) -> serde_json::Value {
+    serde_json::from_slice(&message.payload).unwrap()
+}
+
+#[test]
+fn events_are_mapped_to_topics() {
+    let device = DeviceInfo {
+        id: "phone".to_string(),
+        name: "Alice 的手机".to_string(),
+        device_type: "mobile".to_string(),
+        ip: "192.0.2.7".to_string(),
+        ..peer_device()
+    };
+    let online =
+        MqttMessage::from_event("home/peersend/", &ProtocolEvent::DeviceDiscovered(device))
+            .unwrap();
+    assert_eq!(online.topic, "home/peersend/devices/phone");
+    assert!(online.retain);
+    assert_eq!(json(&online)["name"], "Alice 的手机");
+
+    let requested = MqttMessage::from_event(
+        "peersend",
+        &ProtocolEvent::SessionRequested {
+            session_id: "s1".to_string(),
+            sender: "Alice".to_string(),
+            files: vec![FileInfo {
+                id: "f".to_string(),
+                name: "scan.pdf".to_string(),
+                size: 3,
+                file_type: "application/pdf".to_string(),
+                metadata: None,
+                sha256: None,
+            }],
+        },
+    )
+    .unwrap();
+    assert_eq!(requested.topic, "peersend/sessions/requested");
+    assert!(!requested.retain);
+    assert_eq!(json(&requested)["files"][0]["name"], "scan.pdf");
+
+    let failed = MqttMessage::from_event(
+        "",
+        &ProtocolEvent::SessionStateChanged {
+            session_id: "s1".to_string(),
+            state: SessionState::Error("磁盘已满".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(failed.topic, "sessions/state");
+    assert_eq!(json(&failed)["state"], "error");
+    assert_eq!(json(&failed)["error"], "磁盘已满");
+
+    let progress = ProtocolEvent::FileProgress {
+        session_id: "s1".to_string(),
+        file_id: "f".to_string(),
+        bytes_transferred: 1,
+        total_bytes: 3,
+    };
+    assert!(MqttMessage::from_event("peersend", &progress).is_none());
+}
+
+#[test]
+fn broker_url_is_validated() {
+    let config = |broker: &str| MqttConfig {
+        broker: broker.to_string(),
+        topic: "peersend".to_string(),
+        username: Some("ha".to_string()),
+        password: Some("secret".to_string()),
+    };
+
+    assert!(MqttPublisher::new(&config("mqtt://homeassistant.local"), "me").is_ok());
+    assert!(MqttPublisher::new(&config("mqtt://192.0.2.10:1884"), "me").is_ok());
+    assert!(MqttPublisher::new(&config("mqtts://broker.example"), "me").is_err());
+    assert!(MqttPublisher::new(&config("homeassistant.local"), "me").is_err());
+}
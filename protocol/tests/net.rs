@@ -0,0 +1,98 @@
+//! IPv4 / IPv6 地址处理测试
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+
+use peersend_protocol::{net, IpMode};
+
+/// 本机是否支持 IPv6 回环，不支持时跳过相关测试
+fn ipv6_available() -> bool {
+    std::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0)).is_ok()
+}
+
+#[test]
+fn ipv6_literals_are_bracketed_in_urls() {
+    assert_eq!(net::host_port("192.168.1.5", 53317), "192.168.1.5:53317");
+    assert_eq!(net::host_port("fd00::1", 53317), "[fd00::1]:53317");
+    assert_eq!(net::host_port("[fd00::1]", 53317), "[fd00::1]:53317");
+    assert_eq!(net::host_port("peer.local", 53317), "peer.local:53317");
+}
+
+#[test]
+fn hostnames_are_recognized() {
+    assert!(net::is_host("nas.lan"));
+    assert!(net::is_host("laptop.local."));
+    assert!(net::is_host("fd00::1"));
+    assert!(net::is_host("192.168.1.5"));
+    assert!(!net::is_host("My Phone"));
+    assert!(!net::is_host("-bad.lan"));
+    assert!(!net::is_host("a..b"));
+}
+
+#[test]
+fn mapped_addresses_are_normalized() {
+    assert_eq!(
+        net::parse_ip("::ffff:10.0.0.7"),
+        Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7)))
+    );
+    assert_eq!(net::normalize_host("[FD00::1]"), "fd00::1");
+    assert_eq!(net::normalize_host(" peer.local "), "peer.local");
+    assert_eq!(net::parse_ip("peer.local"), None);
+
+    let mapped: SocketAddr = "[::ffff:10.0.0.7]:53317".parse().unwrap();
+    assert_eq!(net::canonical(mapped), "10.0.0.7:53317".parse().unwrap());
+}
+
+#[test]
+fn unspecified_address_follows_mode() {
+    assert_eq!(IpMode::V4Only.unspecified(), IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    assert_eq!(IpMode::Dual.unspecified(), IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+    assert_eq!(IpMode::V6Only.unspecified(), IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+    assert_eq!(serde_json::to_string(&IpMode::V6Only).unwrap(), r#""v6-only""#);
+}
+
+#[test]
+fn dual_stack_listener_accepts_both_families() {
+    let addr = SocketAddr::new(IpMode::Dual.unspecified(), 0);
+    let listener = net::bind_tcp(addr, IpMode::Dual).unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    assert!(TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_ok());
+    if ipv6_available() {
+        assert!(TcpStream::connect((Ipv6Addr::LOCALHOST, port)).is_ok());
+    }
+}
+
+#[test]
+fn v6_only_listener_rejects_ipv4() {
+    if !ipv6_available() {
+        return;
+    }
+    let addr = SocketAddr::new(IpMode::V6Only.unspecified(), 0);
+    let listener = net::bind_tcp(addr, IpMode::V6Only).unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    assert!(TcpStream::connect((Ipv6Addr::LOCALHOST, port)).is_ok());
+    assert!(TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_err());
+}
+
+#[tokio::test]
+async fn resolve_puts_ipv6_first() {
+    let addrs = net::resolve("[::1]", 53317).await.unwrap();
+    assert_eq!(addrs, vec!["[::1]:53317".parse().unwrap()]);
+
+    let addrs = net::resolve("localhost", 53317).await.unwrap();
+    assert!(!addrs.is_empty());
+    if addrs.iter().any(SocketAddr::is_ipv6) {
+        assert!(addrs[0].is_ipv6());
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+#[test]
+fn local_network_permission_only_applies_to_macos() {
+    use peersend_protocol::ProtocolError;
+
+    let unreachable = std::io::Error::from_raw_os_error(65);
+    assert!(!net::is_local_network_denied(&unreachable));
+    assert!(matches!(net::local_network_error(unreachable), ProtocolError::Io(_)));
+}
@@ -0,0 +1,93 @@
+//! 发件箱目录测试
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::metered::Metered;
+use peersend_protocol::outbox::{Outbox, SENT_DIR};
+use peersend_protocol::testing::{peer_device, Fault, MockHttp};
+use peersend_protocol::{LocalSendClient, LocalSendConfig, MeteredConfig};
+
+fn outbox(http: &Arc<MockHttp>, dir: &std::path::Path) -> Outbox {
+    let client = LocalSendClient::new(LocalSendConfig::default()).with_http(http.clone());
+    Outbox::new(client, dir, "peer").with_settle(Duration::ZERO)
+}
+
+#[tokio::test]
+async fn sends_and_archives_dropped_files() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("scan.pdf"), b"scan").unwrap();
+    std::fs::write(dir.path().join(".partial"), b"").unwrap();
+    // 归档中已有同名文件
+    std::fs::create_dir(dir.path().join(SENT_DIR)).unwrap();
+    std::fs::write(dir.path().join(SENT_DIR).join("scan.pdf"), b"old").unwrap();
+
+    let http = MockHttp::new();
+    http.accept_all();
+    let outbox = outbox(&http, dir.path());
+
+    let archived = outbox.flush_to(&peer_device()).await.unwrap();
+    assert_eq!(archived, vec![dir.path().join(SENT_DIR).join("scan (1).pdf")]);
+    assert!(!dir.path().join("scan.pdf").exists());
+    assert!(dir.path().join(".partial").exists());
+    assert_eq!(std::fs::read(&archived[0]).unwrap(), b"scan");
+
+    let requests = http.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].body, b"scan");
+
+    // 没有新文件时不发起请求
+    assert!(outbox.flush_to(&peer_device()).await.unwrap().is_empty());
+    assert_eq!(http.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn failed_send_keeps_files() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("build.zip"), b"artifact").unwrap();
+
+    let http = MockHttp::new();
+    http.accept_all();
+    http.inject("/upload", Fault::Status(500));
+
+    let result = outbox(&http, dir.path()).flush_to(&peer_device()).await;
+    assert!(result.is_err());
+    assert!(dir.path().join("build.zip").exists());
+    assert!(!dir.path().join(SENT_DIR).exists());
+}
+
+#[tokio::test]
+async fn recently_modified_files_wait() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("writing.bin"), b"data").unwrap();
+
+    let http = MockHttp::new();
+    let outbox = outbox(&http, dir.path()).with_settle(Duration::from_secs(3600));
+    assert!(outbox.pending().await.unwrap().is_empty());
+    assert!(outbox.flush_to(&peer_device()).await.unwrap().is_empty());
+    assert!(http.requests().is_empty());
+}
+
+#[tokio::test]
+async fn large_files_wait_while_metered() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("note.txt"), b"note").unwrap();
+    std::fs::write(dir.path().join("video.mp4"), vec![0u8; 1024]).unwrap();
+
+    let http = MockHttp::new();
+    http.accept_all();
+    let metered = Metered::new(MeteredConfig {
+        max_file_size: Some(100),
+        ..MeteredConfig::default()
+    });
+    let outbox = outbox(&http, dir.path()).with_metered(metered.clone());
+
+    metered.set_active(true);
+    let archived = outbox.flush_to(&peer_device()).await.unwrap();
+    assert_eq!(archived, vec![dir.path().join(SENT_DIR).join("note.txt")]);
+    assert!(dir.path().join("video.mp4").exists());
+
+    metered.set_active(false);
+    let archived = outbox.flush_to(&peer_device()).await.unwrap();
+    assert_eq!(archived, vec![dir.path().join(SENT_DIR).join("video.mp4")]);
+}
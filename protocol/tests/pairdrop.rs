@@ -0,0 +1,169 @@
+//! PairDrop / Snapdrop 信令与数据通道协议测试
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use peersend_protocol::pairdrop::channel::{
+    self, ChannelMessage, DataChannel, FileHeader, Frame, MemoryChannel, OutgoingFile, Received,
+    CHUNK_SIZE, PARTITION_SIZE,
+};
+use peersend_protocol::pairdrop::signal::{ClientMessage, Room, ServerMessage};
+use peersend_protocol::pairdrop::{is_pairdrop_device, PAIRDROP_TRANSPORT};
+use peersend_protocol::storage::MemoryStorage;
+use peersend_protocol::ProtocolError;
+
+#[test]
+fn parses_pairdrop_and_snapdrop_signaling() {
+    let peers: ServerMessage = serde_json::from_str(
+        r#"{"type":"peers","roomType":"ip","roomId":"203.0.113.7","peers":[
+            {"id":"a1","rtcSupported":true,"name":{"model":"Pixel","os":"Android","browser":"Chrome",
+             "type":"mobile","deviceName":"Android Chrome","displayName":"Blue Fox"}}]}"#,
+    )
+    .unwrap();
+    let ServerMessage::Peers { peers, room } = peers else {
+        panic!("应为 peers 消息");
+    };
+    let device = peers[0].to_device(&room);
+    assert_eq!(device.name, "Blue Fox");
+    assert_eq!(device.device_type, "mobile");
+    assert_eq!(device.ip, "203.0.113.7");
+    assert_eq!(device.transports, vec![PAIRDROP_TRANSPORT.to_string()]);
+    assert!(is_pairdrop_device(&device));
+
+    // Snapdrop 的名称嵌套在 message 中，信令发送方为字符串
+    let name: ServerMessage = serde_json::from_str(
+        r#"{"type":"display-name","message":{"displayName":"Red Owl","deviceName":"Linux Firefox"}}"#,
+    )
+    .unwrap();
+    let ServerMessage::DisplayName(name) = name else {
+        panic!("应为 display-name 消息");
+    };
+    assert_eq!(name.display_name(), Some("Red Owl"));
+
+    let signal: ServerMessage = serde_json::from_str(
+        r#"{"type":"signal","sender":"b2","sdp":{"type":"offer","sdp":"v=0"}}"#,
+    )
+    .unwrap();
+    let ServerMessage::Signal(signal) = signal else {
+        panic!("应为 signal 消息");
+    };
+    assert_eq!(signal.sender.id(), "b2");
+    assert!(signal.is_offer());
+
+    let unknown: ServerMessage = serde_json::from_str(r#"{"type":"request-public-room-id"}"#).unwrap();
+    assert!(matches!(unknown, ServerMessage::Unknown));
+}
+
+#[test]
+fn outgoing_signal_carries_room() {
+    let message = ClientMessage::Signal {
+        to: "a1".to_string(),
+        room: Room {
+            room_type: Some("ip".to_string()),
+            room_id: Some("203.0.113.7".to_string()),
+        },
+        sdp: None,
+        ice: Some(serde_json::json!({"candidate": "candidate:1", "sdpMid": "0"})),
+    };
+    let value = serde_json::to_value(&message).unwrap();
+    assert_eq!(value["type"], "signal");
+    assert_eq!(value["to"], "a1");
+    assert_eq!(value["roomType"], "ip");
+    assert_eq!(value["ice"]["sdpMid"], "0");
+    assert!(value.get("sdp").is_none());
+
+    let complete: ChannelMessage =
+        serde_json::from_str(r#"{"type":"transfer-complete"}"#).unwrap();
+    assert_eq!(complete, ChannelMessage::FileTransferComplete);
+}
+
+fn outgoing(name: &str, data: Vec<u8>) -> OutgoingFile {
+    OutgoingFile {
+        header: FileHeader {
+            name: name.to_string(),
+            mime: "application/octet-stream".to_string(),
+            size: data.len() as u64,
+        },
+        reader: Box::pin(std::io::Cursor::new(data)),
+    }
+}
+
+#[tokio::test]
+async fn files_cross_partitions() {
+    let (mut sender, mut receiver) = MemoryChannel::pair();
+    let storage = Arc::new(MemoryStorage::new());
+    let large: Vec<u8> = (0..PARTITION_SIZE as usize * 2 + CHUNK_SIZE + 5)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let files = vec![outgoing("../large.bin", large.clone()), outgoing("empty.txt", Vec::new())];
+
+    let mut sent = Vec::new();
+    let (sent_result, received) = tokio::join!(
+        channel::send_files(&mut sender, files, |index| sent.push(index)),
+        channel::receive(&mut receiver, &*storage, "transfer", |headers| async move {
+            headers.len() == 2
+        })
+    );
+
+    sent_result.unwrap();
+    assert_eq!(sent, vec![0, 1]);
+    let Some(Received::Files(locations)) = received.unwrap() else {
+        panic!("应收到文件");
+    };
+    assert_eq!(locations.len(), 2);
+    assert_eq!(storage.get("large.bin").await.unwrap(), large);
+    assert_eq!(storage.get("empty.txt").await.unwrap(), b"");
+}
+
+#[tokio::test]
+async fn rejected_request_stops_the_sender() {
+    let (mut sender, mut receiver) = MemoryChannel::pair();
+    let storage = MemoryStorage::new();
+
+    let (sent, received) = tokio::join!(
+        channel::send_files(&mut sender, vec![outgoing("a.txt", b"data".to_vec())], |_| {}),
+        channel::receive(&mut receiver, &storage, "transfer", |_| async { false })
+    );
+
+    assert!(matches!(sent, Err(ProtocolError::Rejected(_))));
+    assert!(matches!(received, Err(ProtocolError::Rejected(_))));
+    assert!(storage.names().await.is_empty());
+}
+
+#[tokio::test]
+async fn text_and_snapdrop_style_files() {
+    let (mut sender, mut receiver) = MemoryChannel::pair();
+    let storage = MemoryStorage::new();
+
+    let (sent, received) = tokio::join!(
+        channel::send_text(&mut sender, "你好, PairDrop"),
+        channel::receive(&mut receiver, &storage, "transfer", |_| async { true })
+    );
+    sent.unwrap();
+    assert_eq!(received.unwrap(), Some(Received::Text("你好, PairDrop".to_string())));
+
+    // Snapdrop 不发送 request，直接从 header 开始
+    let header = ChannelMessage::Header(FileHeader {
+        name: "photo.jpg".to_string(),
+        mime: "image/jpeg".to_string(),
+        size: 3,
+    });
+    sender
+        .send(Frame::Text(serde_json::to_string(&header).unwrap()))
+        .await
+        .unwrap();
+    sender.send(Frame::Binary(Bytes::from_static(b"jpg"))).await.unwrap();
+
+    let received = channel::receive(&mut receiver, &storage, "snapdrop", |_| async { true })
+        .await
+        .unwrap();
+    assert!(matches!(received, Some(Received::Files(ref f)) if f.len() == 1));
+    assert_eq!(storage.get("photo.jpg").await.unwrap(), b"jpg");
+
+    drop(sender);
+    let closed = channel::receive(&mut receiver, &storage, "closed", |_| async { true })
+        .await
+        .unwrap();
+    assert_eq!(closed, None);
+    assert_eq!(storage.names().await.len(), 1);
+}
@@ -0,0 +1,61 @@
+//! 指纹确认配对测试
+
+use peersend_protocol::pairing::{short_auth_string, Pairing};
+use peersend_protocol::testing::peer_device;
+use peersend_protocol::{DeviceInfo, LocalSendConfig, PinPolicy};
+
+const LAPTOP: &str = "3f2a9c0d5be1a7e4c6d8f0b2a4c6e8f0a1b3c5d7e9f1a3b5c7d9e1f3a5b7c9d1";
+const PHONE: &str = "8e6c4a2f0d1b3c5e7a9f1d3b5c7e9a1f3d5b7c9e1a3f5d7b9c1e3a5f7d9b1c3e";
+
+fn device(id: &str) -> DeviceInfo {
+    DeviceInfo {
+        id: id.to_string(),
+        name: "Phone".to_string(),
+        device_type: "mobile".to_string(),
+        ip: "192.0.2.7".to_string(),
+        version: "2.0".to_string(),
+        ..peer_device()
+    }
+}
+
+#[test]
+fn both_sides_derive_the_same_code() {
+    let code = short_auth_string(LAPTOP, PHONE);
+    assert_eq!(code, short_auth_string(PHONE, LAPTOP));
+    assert_eq!(code, short_auth_string(&LAPTOP.to_ascii_uppercase(), PHONE));
+
+    let (head, tail) = code.split_once(' ').unwrap();
+    assert_eq!((head.len(), tail.len()), (3, 3));
+    assert!(code.chars().all(|c| c == ' ' || c.is_ascii_digit()));
+}
+
+#[test]
+fn substituted_fingerprint_changes_the_code() {
+    let mitm = "0000000000000000000000000000000000000000000000000000000000000001";
+    let code = short_auth_string(LAPTOP, PHONE);
+    // 中间人分别冒充两端时，两端看到的认证串均与真实配对不同
+    assert_ne!(code, short_auth_string(LAPTOP, mitm));
+    assert_ne!(code, short_auth_string(mitm, PHONE));
+    // 拼接边界不同的指纹不应得到相同的结果
+    assert_ne!(short_auth_string("ab", "c"), short_auth_string("a", "bc"));
+}
+
+#[test]
+fn confirming_marks_the_device_trusted() {
+    let mut config = LocalSendConfig {
+        device_id: LAPTOP.to_string(),
+        pin: Some("1234".to_string()),
+        pin_policy: PinPolicy::Unknown,
+        ..LocalSendConfig::default()
+    };
+    let pairing = Pairing::new(&config, device(PHONE));
+    assert_eq!(pairing.code, short_auth_string(LAPTOP, PHONE));
+    assert!(config.requires_pin(PHONE, "192.0.2.7"));
+
+    assert!(pairing.confirm(&mut config));
+    assert!(config.is_trusted(PHONE));
+    assert!(!config.requires_pin(PHONE, "192.0.2.7"));
+
+    assert!(!pairing.confirm(&mut config));
+    assert_eq!(config.favorites, vec![PHONE.to_string()]);
+}
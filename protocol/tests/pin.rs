@@ -0,0 +1,228 @@
+//! 按设备要求 PIN 的测试
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::dto::v2::{FileDto, PrepareUploadRequestDto, RegisterDto, PROTOCOL_VERSION};
+use peersend_protocol::http::HttpResponse;
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::testing::{peer_device, MockFs, MockHttp};
+use peersend_protocol::{
+    DeviceInfo, DiscoveryManager, LocalSendClient, LocalSendConfig, PinPolicy, ProtocolError,
+    QuickSave, SessionManager,
+};
+use tokio::sync::Mutex;
+
+fn config(policy: PinPolicy, pin_devices: &[&str]) -> LocalSendConfig {
+    LocalSendConfig {
+        pin: Some("1234".to_string()),
+        pin_policy: policy,
+        pin_devices: pin_devices.iter().map(|r| r.to_string()).collect(),
+        favorites: vec!["laptop".to_string()],
+        quick_save: QuickSave::On,
+        ..LocalSendConfig::default()
+    }
+}
+
+fn request(fingerprint: &str) -> PrepareUploadRequestDto {
+    let file = FileDto {
+        id: "file".to_string(),
+        file_name: "photo.jpg".to_string(),
+        size: 42,
+        file_type: "image/jpeg".to_string(),
+        sha256: None,
+        preview: None,
+        metadata: None,
+    };
+    PrepareUploadRequestDto {
+        info: RegisterDto {
+            alias: "Phone".to_string(),
+            version: PROTOCOL_VERSION.to_string(),
+            device_model: None,
+            device_type: Some("mobile".to_string()),
+            fingerprint: fingerprint.to_string(),
+            port: 53317,
+            protocol: "http".to_string(),
+            download: false,
+        },
+        files: HashMap::from([("file".to_string(), file)]),
+    }
+}
+
+#[test]
+fn policy_is_evaluated_per_device() {
+    let all = config(PinPolicy::All, &[]);
+    assert!(all.requires_pin("laptop", "192.0.2.5"));
+    assert!(all.requires_pin("stranger", "192.0.2.6"));
+
+    // 收藏的设备受信任，但 pin_devices 优先
+    let unknown = config(PinPolicy::Unknown, &["192.0.2.128/25"]);
+    assert!(!unknown.requires_pin("laptop", "192.0.2.5"));
+    assert!(unknown.requires_pin("stranger", "192.0.2.6"));
+    assert!(unknown.requires_pin("laptop", "192.0.2.200"));
+
+    let listed = config(PinPolicy::Listed, &["stranger"]);
+    assert!(!listed.requires_pin("laptop", "192.0.2.5"));
+    assert!(listed.requires_pin("stranger", "192.0.2.6"));
+
+    let no_pin = LocalSendConfig {
+        pin: None,
+        ..config(PinPolicy::All, &["stranger"])
+    };
+    assert!(!no_pin.requires_pin("stranger", "192.0.2.6"));
+    assert!(!no_pin.uses_pin());
+}
+
+#[test]
+fn announcement_advertises_pin() {
+    assert!(config(PinPolicy::All, &[]).uses_pin());
+    assert!(config(PinPolicy::Unknown, &[]).uses_pin());
+    assert!(config(PinPolicy::Listed, &["stranger"]).uses_pin());
+    // 仅列出的设备需要 PIN 但列表为空时，没有设备需要
+    assert!(!config(PinPolicy::Listed, &[]).uses_pin());
+}
+
+#[test]
+fn pin_devices_require_a_pin() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        ..config(PinPolicy::Listed, &["stranger", "10.0.0.0/8"])
+    };
+    config.validate().unwrap();
+
+    let without_pin = LocalSendConfig {
+        pin: None,
+        ..config.clone()
+    };
+    assert!(matches!(
+        without_pin.validate(),
+        Err(ProtocolError::InvalidConfig(_))
+    ));
+    let bad_rule = LocalSendConfig {
+        pin_devices: vec!["10.0.0.0/40".to_string()],
+        ..config
+    };
+    assert!(matches!(
+        bad_rule.validate(),
+        Err(ProtocolError::InvalidConfig(_))
+    ));
+}
+
+#[tokio::test]
+async fn server_checks_pin_before_creating_session() {
+    let sessions = SessionManager::new();
+    let server = LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        config(PinPolicy::Unknown, &[]),
+        Arc::new(Mutex::new(sessions.clone())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    )
+    .with_accept_timeout(Duration::from_secs(5));
+
+    let missing = server
+        .prepare_upload("192.0.2.6", request("stranger"), None)
+        .await;
+    assert!(matches!(missing, Err(ProtocolError::PinRequired)));
+    let wrong = server
+        .prepare_upload("192.0.2.6", request("stranger"), Some("0000"))
+        .await;
+    assert!(matches!(wrong, Err(ProtocolError::PinRequired)));
+    assert!(sessions.get_all_sessions().await.is_empty());
+
+    server
+        .prepare_upload("192.0.2.6", request("stranger"), Some("1234"))
+        .await
+        .unwrap();
+    server
+        .prepare_upload("192.0.2.5", request("laptop"), None)
+        .await
+        .unwrap();
+    assert_eq!(sessions.get_all_sessions().await.len(), 2);
+}
+
+/// 只接受 PIN 为 1234 的 prepare-upload 请求
+fn pin_receiver() -> Arc<MockHttp> {
+    let http = MockHttp::new();
+    http.route("/prepare-upload", |request| {
+        if request.query_param("pin") != Some("1234") {
+            return HttpResponse {
+                status: 401,
+                body: Vec::new(),
+            };
+        }
+        request.accept_files(|_| true)
+    });
+    http.respond_status("/upload", 200);
+    http
+}
+
+fn sender(http: &Arc<MockHttp>) -> LocalSendClient {
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"data".to_vec());
+    LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_fs(Arc::new(fs))
+}
+
+fn peer() -> DeviceInfo {
+    DeviceInfo {
+        uses_password: true,
+        ..peer_device()
+    }
+}
+
+fn prepare_count(http: &MockHttp) -> usize {
+    http.requests().iter().filter(|r| r.url.ends_with("/prepare-upload")).count()
+}
+
+#[tokio::test]
+async fn client_sends_configured_pin() {
+    let http = pin_receiver();
+    let client = sender(&http);
+    let device = peer();
+
+    let result = client.send_files(&device, &["/src/a.txt"]).await;
+    assert!(matches!(result, Err(ProtocolError::PinRequired)));
+
+    client.set_pin("peer", Some("1234".to_string()));
+    client.send_files(&device, &["/src/a.txt"]).await.unwrap();
+}
+
+#[tokio::test]
+async fn client_prompts_for_pin_and_retries() {
+    let http = pin_receiver();
+    let attempts = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = attempts.clone();
+    let client = sender(&http).with_pin_prompt(move |device, attempt| {
+        recorded.lock().unwrap().push((device.id.clone(), attempt));
+        async move { Some(if attempt == 1 { "0000" } else { "1234" }.to_string()) }
+    });
+
+    client.send_files(&peer(), &["/src/a.txt"]).await.unwrap();
+    assert_eq!(*attempts.lock().unwrap(), [("peer".to_string(), 1), ("peer".to_string(), 2)]);
+    assert_eq!(prepare_count(&http), 3);
+
+    // 正确的 PIN 已记录，再次发送不再询问
+    client.send_files(&peer(), &["/src/a.txt"]).await.unwrap();
+    assert_eq!(attempts.lock().unwrap().len(), 2);
+    assert_eq!(prepare_count(&http), 4);
+}
+
+#[tokio::test]
+async fn client_gives_up_after_wrong_pins() {
+    let http = pin_receiver();
+    let client = sender(&http).with_pin_prompt(|_, _| async { Some("0000".to_string()) });
+    let result = client.send_files(&peer(), &["/src/a.txt"]).await;
+    assert!(matches!(result, Err(ProtocolError::PinRequired)));
+    // 首次请求加 3 次重试
+    assert_eq!(prepare_count(&http), 4);
+
+    // 用户取消时不再重试
+    let http = pin_receiver();
+    let client = sender(&http).with_pin_prompt(|_, _| async { None });
+    let result = client.send_files(&peer(), &["/src/a.txt"]).await;
+    assert!(matches!(result, Err(ProtocolError::PinRequired)));
+    assert_eq!(prepare_count(&http), 1);
+}
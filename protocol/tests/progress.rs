@@ -0,0 +1,90 @@
+//! 进度计数测试
+
+use peersend_protocol::{FileInfo, ProgressTracker, SessionManager, ThroughputSample};
+
+#[tokio::test]
+async fn session_progress_reaches_subscribers() {
+    let sessions = SessionManager::new();
+    let session = sessions
+        .create_session(
+            "self".to_string(),
+            "peer".to_string(),
+            vec![FileInfo {
+                id: "file".to_string(),
+                name: "a.bin".to_string(),
+                size: 10,
+                file_type: "application/octet-stream".to_string(),
+                metadata: None,
+                sha256: None,
+            }],
+        )
+        .await;
+    let mut snapshots = session.progress.subscribe();
+    assert_eq!(snapshots.borrow().total_bytes, 10);
+
+    sessions.report_progress(&session.id, "file", 4).await;
+    sessions.report_progress(&session.id, "file", 6).await;
+
+    // 完成时总会推送，不受节流影响
+    snapshots.changed().await.unwrap();
+    let last = snapshots.borrow().clone();
+    assert_eq!(last.bytes_transferred, 10);
+    assert_eq!(last.progress(), 1.0);
+}
+
+#[test]
+fn snapshot_reports_average_speed() {
+    let tracker = ProgressTracker::new(1000);
+    assert_eq!(tracker.snapshot().speed_bytes_per_sec, 0.0);
+
+    assert_eq!(tracker.add(300), 300);
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let snapshot = tracker.snapshot();
+    assert_eq!(snapshot.bytes_transferred, 300);
+    assert!(snapshot.speed_bytes_per_sec > 0.0);
+}
+
+#[test]
+fn timeseries_records_bytes_per_second() {
+    let tracker = ProgressTracker::new(1000);
+    assert!(tracker.timeseries().is_empty());
+
+    tracker.add(100);
+    tracker.add(200);
+    let samples = tracker.timeseries();
+    assert_eq!(
+        samples,
+        vec![ThroughputSample {
+            offset_secs: 0,
+            bytes: 300
+        }]
+    );
+}
+
+#[tokio::test]
+async fn finished_session_timeseries_ends_at_last_sample() {
+    let sessions = SessionManager::new();
+    let session = sessions
+        .create_session(
+            "self".to_string(),
+            "peer".to_string(),
+            vec![FileInfo {
+                id: "file".to_string(),
+                name: "a.bin".to_string(),
+                size: 10,
+                file_type: "application/octet-stream".to_string(),
+                metadata: None,
+                sha256: None,
+            }],
+        )
+        .await;
+    sessions.report_progress(&session.id, "file", 4).await;
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    sessions.report_progress(&session.id, "file", 6).await;
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+    let samples = sessions.get_transfer_timeseries(&session.id).await.unwrap();
+    let bytes: Vec<u64> = samples.iter().map(|s| s.bytes).collect();
+    assert_eq!(bytes, vec![4, 6]);
+    assert!(sessions.get_transfer_timeseries("missing").await.is_none());
+}
@@ -0,0 +1,53 @@
+//! HTTP 代理测试
+
+use peersend_protocol::http::{HttpRequest, ReqwestClient};
+use peersend_protocol::LocalSendConfig;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn requests_go_through_configured_proxy() {
+    let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = LocalSendConfig {
+        proxy: Some(format!("http://{}", proxy.local_addr().unwrap())),
+        ..Default::default()
+    };
+    let http = ReqwestClient::for_config(&config);
+
+    let request = tokio::spawn(async move {
+        let (mut stream, _) = proxy.accept().await.unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+            .await
+            .unwrap();
+        String::from_utf8(head).unwrap()
+    });
+
+    let response = http
+        .execute(HttpRequest::get("http://peer.invalid:53317/api/localsend/v2/info"))
+        .await
+        .unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"ok");
+    // 经代理的请求使用绝对 URI
+    let head = request.await.unwrap();
+    assert!(head.starts_with("GET http://peer.invalid:53317/api/localsend/v2/info HTTP/1.1\r\n"));
+}
+
+#[test]
+fn invalid_proxy_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = |proxy: &str| LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        proxy: Some(proxy.to_string()),
+        ..Default::default()
+    };
+
+    assert!(config("http://proxy.example:3128").validate().is_ok());
+    assert!(config("proxy.example:3128").validate().is_err());
+    assert!(config("ftp://proxy.example").validate().is_err());
+}
@@ -0,0 +1,144 @@
+//! QUIC 传输与协商测试
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use peersend_protocol::testing::{peer_device, MockFs, MockHttp};
+use peersend_protocol::transport::quic::{QuicListener, QuicTransport, QUIC_TRANSPORT};
+use peersend_protocol::transport::{self, StreamHeader, Transport, TransportListener, TransportRef};
+use peersend_protocol::{DeviceInfo, LocalSendClient, LocalSendConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn peer(port: u16, transports: &[&str]) -> DeviceInfo {
+    DeviceInfo {
+        ip: "127.0.0.1".to_string(),
+        port,
+        transports: transports.iter().map(|t| t.to_string()).collect(),
+        ..peer_device()
+    }
+}
+
+async fn listen() -> QuicListener {
+    QuicTransport::new()
+        .unwrap()
+        .listen("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap()
+}
+
+/// 接收 count 个文件流，返回文件 ID 到内容的映射和各流的来源地址
+async fn receive_all(
+    listener: &mut QuicListener,
+    count: usize,
+) -> (HashMap<String, Vec<u8>>, Vec<SocketAddr>) {
+    let mut files = HashMap::new();
+    let mut peers = Vec::new();
+    for _ in 0..count {
+        let (mut stream, peer) = listener.accept().await.unwrap();
+        let header = transport::read_header(&mut stream).await.unwrap();
+        let mut body = vec![0u8; header.size as usize];
+        stream.read_exact(&mut body).await.unwrap();
+        stream.shutdown().await.unwrap();
+        files.insert(header.file_id, body);
+        peers.push(peer);
+    }
+    (files, peers)
+}
+
+#[tokio::test]
+async fn negotiation_requires_a_common_transport() {
+    let local: Vec<TransportRef> = vec![Arc::new(QuicTransport::new().unwrap())];
+
+    assert!(transport::negotiate(&local, &[]).is_none());
+    assert!(transport::negotiate(&local, &["easytier".to_string()]).is_none());
+    let (chosen, port) = transport::negotiate(&local, &[QUIC_TRANSPORT.to_string()]).unwrap();
+    assert_eq!(chosen.name(), QUIC_TRANSPORT);
+    assert_eq!(port, None);
+}
+
+#[tokio::test]
+async fn streams_share_one_connection() {
+    let mut listener = listen().await;
+    let addr = listener.local_addr();
+    let transport = QuicTransport::new().unwrap();
+
+    let senders = (0..3).map(|i| {
+        let transport = transport.clone();
+        async move {
+            let data = format!("file {}", i).into_bytes();
+            let header = StreamHeader {
+                session_id: "session".to_string(),
+                file_id: i.to_string(),
+                token: String::new(),
+                size: data.len() as u64,
+                offset: 0,
+                sparse: None,
+            };
+            let mut stream = transport.connect(addr).await.unwrap();
+            transport::send_reader(&mut stream, &header, &mut data.as_slice())
+                .await
+                .unwrap();
+            stream.shutdown().await.unwrap();
+            let mut ack = Vec::new();
+            stream.read_to_end(&mut ack).await.unwrap();
+        }
+    });
+
+    let ((files, peers), _) = tokio::join!(
+        receive_all(&mut listener, 3),
+        futures::future::join_all(senders)
+    );
+
+    for i in 0..3 {
+        assert_eq!(files[&i.to_string()], format!("file {}", i).into_bytes());
+    }
+    assert!(peers.iter().all(|p| *p == peers[0]));
+}
+
+#[tokio::test]
+async fn client_uploads_over_negotiated_quic() {
+    let mut listener = listen().await;
+    let device = peer(listener.local_addr().port(), &[QUIC_TRANSPORT]);
+
+    let http = MockHttp::new();
+    http.accept_all();
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"over quic".to_vec());
+    fs.insert("/src/b.txt", b"second file".to_vec());
+    let client = LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_fs(Arc::new(fs))
+        .with_transport(Arc::new(QuicTransport::new().unwrap()));
+
+    let (sent, (files, _)) = tokio::join!(
+        client.send_files(&device, &["/src/a.txt", "/src/b.txt"]),
+        receive_all(&mut listener, 2)
+    );
+    let session = sent.unwrap();
+
+    // 只有 prepare-upload 走 HTTP
+    assert_eq!(http.requests().len(), 1);
+    for file in &session.files {
+        let expected: &[u8] = if file.name == "a.txt" { b"over quic" } else { b"second file" };
+        assert_eq!(files[&file.id], expected);
+    }
+}
+
+#[tokio::test]
+async fn plain_localsend_peers_use_http() {
+    let http = MockHttp::new();
+    http.accept_all();
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"data".to_vec());
+    let client = LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_fs(Arc::new(fs))
+        .with_transport(Arc::new(QuicTransport::new().unwrap()));
+
+    client.send_files(&peer(53317, &[]), &["/src/a.txt"]).await.unwrap();
+
+    let requests = http.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].body, b"data");
+}
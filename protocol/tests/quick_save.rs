@@ -0,0 +1,167 @@
+//! 快速保存与接收确认测试
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::dto::v2::{FileDto, PrepareUploadRequestDto, RegisterDto, PROTOCOL_VERSION};
+use peersend_protocol::server::accept::AcceptGate;
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::{
+    DiscoveryManager, LocalSendConfig, ProtocolError, ProtocolEvent, QuickSave, SessionManager,
+};
+use tokio::sync::Mutex;
+
+fn server(quick_save: QuickSave, favorites: &[&str]) -> (LocalSendServer, SessionManager) {
+    let config = LocalSendConfig {
+        quick_save,
+        favorites: favorites.iter().map(|id| id.to_string()).collect(),
+        ..LocalSendConfig::default()
+    };
+    let sessions = SessionManager::new();
+    let server = LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        config,
+        Arc::new(Mutex::new(sessions.clone())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    )
+    .with_accept_timeout(Duration::from_secs(5));
+    (server, sessions)
+}
+
+fn request(fingerprint: &str) -> PrepareUploadRequestDto {
+    let file = FileDto {
+        id: "file".to_string(),
+        file_name: "photo.jpg".to_string(),
+        size: 42,
+        file_type: "image/jpeg".to_string(),
+        sha256: None,
+        preview: None,
+        metadata: None,
+    };
+    PrepareUploadRequestDto {
+        info: RegisterDto {
+            alias: "Phone".to_string(),
+            version: PROTOCOL_VERSION.to_string(),
+            device_model: None,
+            device_type: Some("mobile".to_string()),
+            fingerprint: fingerprint.to_string(),
+            port: 53317,
+            protocol: "http".to_string(),
+            download: false,
+        },
+        files: HashMap::from([("file".to_string(), file)]),
+    }
+}
+
+#[tokio::test]
+async fn quick_save_returns_tokens_without_prompt() {
+    let (server, sessions) = server(QuickSave::On, &[]);
+    let mut events = sessions.events().subscribe();
+
+    let response = server.prepare_upload("192.0.2.5", request("phone"), None).await.unwrap();
+
+    assert!(response.files.contains_key("file"));
+    assert!(sessions.get_session(&response.session_id).await.is_some());
+    while let Ok(event) = events.try_recv() {
+        assert!(!matches!(event, ProtocolEvent::SessionRequested { .. }));
+    }
+}
+
+#[tokio::test]
+async fn favorites_skip_prompt_only_for_listed_devices() {
+    let (server, sessions) = server(QuickSave::Favorites, &["phone"]);
+    server.prepare_upload("192.0.2.5", request("phone"), None).await.unwrap();
+
+    let mut events = sessions.events().subscribe();
+    let stranger = server.prepare_upload("192.0.2.6", request("stranger"), None);
+    let answer = async {
+        loop {
+            if let Ok(ProtocolEvent::SessionRequested { session_id, .. }) = events.recv().await {
+                assert!(server.respond(&session_id, false));
+                break;
+            }
+        }
+    };
+    let (result, ()) = tokio::join!(stranger, answer);
+
+    assert!(matches!(result, Err(ProtocolError::Rejected(_))));
+    assert_eq!(sessions.session_count().await, 1);
+}
+
+#[tokio::test]
+async fn prompt_waits_for_answer() {
+    let (server, sessions) = server(QuickSave::Off, &[]);
+    let mut events = sessions.events().subscribe();
+
+    let prepare = server.prepare_upload("192.0.2.5", request("phone"), None);
+    let answer = async {
+        loop {
+            if let Ok(ProtocolEvent::SessionRequested { session_id, files, .. }) = events.recv().await {
+                assert_eq!(files[0].name, "photo.jpg");
+                assert!(server.respond(&session_id, true));
+                break;
+            }
+        }
+    };
+    let (result, ()) = tokio::join!(prepare, answer);
+
+    assert_eq!(result.unwrap().files.len(), 1);
+    assert!(!server.respond("unknown", true));
+}
+
+#[tokio::test]
+async fn unanswered_requests_time_out() {
+    let (server, _) = server(QuickSave::Off, &[]);
+    let server = server.with_accept_timeout(Duration::from_millis(20));
+
+    let result = server.prepare_upload("192.0.2.5", request("phone"), None).await;
+    assert!(matches!(result, Err(ProtocolError::Rejected(_))));
+}
+
+#[tokio::test]
+async fn external_gate_answers_requests() {
+    let gate = AcceptGate::new();
+    let (server, sessions) = server(QuickSave::Off, &[]);
+    let server = server.with_accept_gate(gate.clone());
+    let mut events = sessions.events().subscribe();
+
+    let prepare = server.prepare_upload("192.0.2.5", request("phone"), None);
+    let answer = async {
+        loop {
+            if let Ok(ProtocolEvent::SessionRequested { session_id, .. }) = events.recv().await {
+                assert_eq!(gate.pending(), vec![session_id.clone()]);
+                assert!(gate.respond(&session_id, true));
+                break;
+            }
+        }
+    };
+    let (result, ()) = tokio::join!(prepare, answer);
+
+    assert!(result.is_ok());
+    assert!(gate.pending().is_empty());
+}
+
+#[tokio::test]
+async fn blocked_devices_are_rejected_without_prompt() {
+    let config = LocalSendConfig {
+        quick_save: QuickSave::On,
+        blocklist: vec!["prankster".to_string(), "192.0.2.128/25".to_string()],
+        ..LocalSendConfig::default()
+    };
+    let sessions = SessionManager::new();
+    let server = LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        config,
+        Arc::new(Mutex::new(sessions.clone())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    );
+
+    let by_id = server.prepare_upload("192.0.2.5", request("prankster"), None).await;
+    assert!(matches!(by_id, Err(ProtocolError::Rejected(_))));
+    let by_ip = server.prepare_upload("192.0.2.200", request("phone"), None).await;
+    assert!(matches!(by_ip, Err(ProtocolError::Rejected(_))));
+    assert_eq!(sessions.session_count().await, 0);
+
+    server.prepare_upload("192.0.2.6", request("phone"), None).await.unwrap();
+}
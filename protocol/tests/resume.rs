@@ -0,0 +1,242 @@
+//! 中断后续传的测试
+
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::dto::v2::{
+    FileDto, PrepareUploadRequestDto, PrepareUploadResponseDto, RegisterDto, UploadOffsetDto,
+    PREPARE_UPLOAD_PATH, UPLOAD_PATH,
+};
+use peersend_protocol::hash::sha256_hex;
+use peersend_protocol::http::{HttpMethod, HttpRequest, HttpResponse, ReqwestClient};
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::session::{FileReceiver, FileSender};
+use peersend_protocol::storage::{self, FsStorage, MemoryStorage};
+use peersend_protocol::testing::{peer_device, Fault, MockFs, MockHttp};
+use peersend_protocol::transport::{self, StreamHeader};
+use peersend_protocol::{
+    DeviceInfo, DiscoveryManager, FileInfo, FileSession, LocalSendClient, LocalSendConfig,
+    QuickSave, SessionManager, SessionState,
+};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+fn session(name: &str, size: u64) -> FileSession {
+    let file = FileInfo {
+        id: "file".to_string(),
+        name: name.to_string(),
+        size,
+        file_type: "application/octet-stream".to_string(),
+        metadata: None,
+        sha256: None,
+    };
+    FileSession::new("session".to_string(), "peer".to_string(), "self".to_string(), vec![file])
+}
+
+fn header(size: u64, offset: u64) -> StreamHeader {
+    StreamHeader {
+        session_id: "session".to_string(),
+        file_id: "file".to_string(),
+        token: String::new(),
+        size,
+        offset,
+        sparse: None,
+    }
+}
+
+fn file_dto(file: &FileInfo) -> FileDto {
+    FileDto {
+        id: file.id.clone(),
+        file_name: file.name.clone(),
+        size: file.size,
+        file_type: file.file_type.clone(),
+        sha256: None,
+        preview: None,
+        metadata: None,
+    }
+}
+
+#[tokio::test]
+async fn stream_continues_part_file_from_offset() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("video.bin");
+    // 此前中断时多写入的内容在续传时被截去
+    let part = storage::part_path(&path, "session", "file");
+    std::fs::write(&part, b"abcXY").unwrap();
+
+    let sha256 = sha256_hex(b"abcdef");
+    transport::receive_file(&mut &b"def"[..], &header(6, 3), &path, Some(&sha256))
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"abcdef");
+    assert!(!part.exists());
+
+    // 偏移超出已接收的长度
+    std::fs::write(&part, b"ab").unwrap();
+    let result = transport::receive_file(&mut &b"def"[..], &header(6, 3), &path, None).await;
+    assert!(result.is_err());
+    assert!(transport::receive_file(&mut &b""[..], &header(6, 7), &path, None).await.is_err());
+}
+
+#[tokio::test]
+async fn receiver_resumes_suspended_file() {
+    let storage = Arc::new(MemoryStorage::new());
+    let mut receiver =
+        FileReceiver::new(session("a.bin", 6), "/unused".into()).with_storage(storage.clone());
+    receiver.start_file("a.bin").await.unwrap();
+    receiver.write_chunk(b"abc").await.unwrap();
+    receiver.suspend_current_file().await.unwrap();
+    assert_eq!(receiver.received_len("a.bin").await.unwrap(), 3);
+
+    assert!(receiver.resume_file("a.bin", 2).await.is_err());
+    receiver.resume_file("a.bin", 3).await.unwrap();
+    receiver.write_chunk(b"def").await.unwrap();
+    receiver.finish_current_file().await.unwrap();
+    assert_eq!(storage.get("a.bin").await.unwrap(), b"abcdef");
+
+    // 写入下载目录时保留中断的文件
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(FsStorage::new(dir.path()));
+    let mut receiver =
+        FileReceiver::new(session("b.bin", 4), dir.path().into()).with_storage(storage);
+    receiver.start_file("b.bin").await.unwrap();
+    receiver.write_chunk(b"12").await.unwrap();
+    receiver.suspend_current_file().await.unwrap();
+    assert_eq!(receiver.received_len("b.bin").await.unwrap(), 2);
+    // 未接收完的文件只以 .part 存在
+    assert!(!dir.path().join("b.bin").exists());
+    receiver.resume_file("b.bin", 2).await.unwrap();
+    receiver.write_chunk(b"34").await.unwrap();
+    receiver.finish_current_file().await.unwrap();
+    assert_eq!(std::fs::read(dir.path().join("b.bin")).unwrap(), b"1234");
+}
+
+#[tokio::test]
+async fn sender_reads_from_resume_offset() {
+    let fs = MockFs::new();
+    fs.insert("a.bin", b"0123456789".to_vec());
+    let mut sender = FileSender::new(session("a.bin", 10)).with_fs(Arc::new(fs));
+    sender.set_chunk_size(4);
+
+    assert_eq!(sender.read_chunk().await.unwrap().unwrap(), b"0123");
+    assert_eq!(sender.read_chunk().await.unwrap().unwrap(), b"4567");
+    sender.resume_from(2).unwrap();
+    assert_eq!(sender.get_offset(), 2);
+    assert_eq!(sender.read_chunk().await.unwrap().unwrap(), b"2345");
+    assert_eq!(sender.file_offset(), 6);
+    assert!(sender.resume_from(11).is_err());
+}
+
+#[tokio::test]
+async fn upload_endpoint_accepts_offset() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig {
+        quick_save: QuickSave::On,
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        ..LocalSendConfig::default()
+    };
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let sessions = SessionManager::new();
+    let server = LocalSendServer::new(
+        listener.local_addr().unwrap(),
+        config,
+        Arc::new(Mutex::new(sessions.clone())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    )
+    .with_listener(listener);
+    server.start().await.unwrap();
+
+    let http = ReqwestClient::shared();
+    let session = session("hello.txt", 5);
+    let prepare = PrepareUploadRequestDto {
+        info: RegisterDto::from_config(&LocalSendConfig::default()),
+        files: [("file".to_string(), file_dto(&session.files[0]))].into(),
+    };
+    let request = HttpRequest::post(format!("{}{}", base, PREPARE_UPLOAD_PATH)).json(&prepare);
+    let response = http.execute(request.unwrap()).await.unwrap();
+    let prepared: PrepareUploadResponseDto = response.error_for_status().unwrap().json().unwrap();
+    let upload = |method: fn(String) -> HttpRequest| {
+        method(format!("{}{}", base, UPLOAD_PATH))
+            .query("sessionId", &prepared.session_id)
+            .query("fileId", "file")
+            .query("token", &prepared.files["file"])
+    };
+    let offset = |response: HttpResponse| response.json::<UploadOffsetDto>().unwrap().offset;
+
+    assert_eq!(offset(http.execute(upload(HttpRequest::get)).await.unwrap()), 0);
+    // 其他会话留下的同名 .part 不影响本会话的进度
+    let other = storage::part_path(&dir.path().join("hello.txt"), "other", "file");
+    std::fs::write(other, b"xxxx").unwrap();
+    assert_eq!(offset(http.execute(upload(HttpRequest::get)).await.unwrap()), 0);
+
+    // 上传只发出 3 字节就中断
+    let received = sessions.get_session(&prepared.session_id).await.unwrap();
+    let (mut sender, body) = tokio::io::duplex(64);
+    let interrupted = http.execute(upload(HttpRequest::post).stream(Box::pin(body), 5));
+    let receiving = || received.transfers.lock().unwrap().get("file").is_some_and(|t| t.receiving);
+    let interrupt = async {
+        sender.write_all(b"hel").await.unwrap();
+        while !receiving() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // 同一文件正在上传时不能再次上传
+        let request = upload(HttpRequest::post).stream(Box::pin(&b"hello"[..]), 5);
+        assert_eq!(http.execute(request).await.unwrap().status, 409);
+        drop(sender);
+    };
+    let _ = tokio::join!(interrupted, interrupt);
+    while receiving() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(offset(http.execute(upload(HttpRequest::get)).await.unwrap()), 3);
+
+    let request = upload(HttpRequest::post).query("offset", "4").stream(Box::pin(&b"o"[..]), 1);
+    assert_eq!(http.execute(request).await.unwrap().status, 416);
+    let request = upload(HttpRequest::post).query("offset", "3").stream(Box::pin(&b"lo"[..]), 2);
+    assert_eq!(http.execute(request).await.unwrap().status, 200);
+
+    assert_eq!(std::fs::read(dir.path().join("hello (1).txt")).unwrap(), b"hello");
+    assert_eq!(*received.state.lock().await, SessionState::Finished);
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn client_resumes_interrupted_upload() {
+    let http = MockHttp::new();
+    http.route("/prepare-upload", |request| request.accept_files(|_| true));
+    // 中断前对方已收到 4 字节
+    http.route("/upload", |request| match request.method {
+        HttpMethod::Get => HttpResponse {
+            status: 200,
+            body: serde_json::to_vec(&UploadOffsetDto { offset: 4 }).unwrap(),
+        },
+        _ => {
+            assert_eq!(request.query_param("offset"), Some("4"));
+            assert_eq!(request.body, b"456789");
+            HttpResponse {
+                status: 200,
+                body: Vec::new(),
+            }
+        }
+    });
+    http.inject("/upload", Fault::Drop);
+
+    let fs = MockFs::new();
+    fs.insert("/src/a.bin", b"0123456789".to_vec());
+    let client = LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_fs(Arc::new(fs));
+    let device = DeviceInfo {
+        id: "desk".to_string(),
+        name: "Desk".to_string(),
+        version: "2.0".to_string(),
+        ..peer_device()
+    };
+
+    let session = client.send_files(&device, &["/src/a.bin"]).await.unwrap();
+    assert_eq!(*session.state.lock().await, SessionState::Finished);
+    let methods: Vec<_> = http.requests().iter().map(|r| r.method).collect();
+    assert_eq!(methods, [HttpMethod::Post, HttpMethod::Get, HttpMethod::Post]);
+}
@@ -0,0 +1,118 @@
+//! 下载目录清理测试
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use peersend_protocol::audit::{self, AuditAction, AuditLog};
+use peersend_protocol::retention::Retention;
+use peersend_protocol::{RetentionAction, RetentionConfig};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 写入文件并把修改时间设为 days 天前
+fn received(dir: &Path, name: &str, size: usize, days: u32, now: SystemTime) {
+    let path = dir.join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, vec![0u8; size]).unwrap();
+    let file = std::fs::File::options().write(true).open(&path).unwrap();
+    file.set_modified(now - DAY * days).unwrap();
+}
+
+fn names(dir: &Path, files: &[peersend_protocol::retention::Expired]) -> Vec<String> {
+    files
+        .iter()
+        .map(|f| f.path.strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/"))
+        .collect()
+}
+
+#[test]
+fn old_files_and_overflow_are_planned_oldest_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let now = SystemTime::now();
+    received(dir.path(), "ancient.iso", 10, 40, now);
+    received(dir.path(), "photos/old.jpg", 10, 31, now);
+    received(dir.path(), "report.pdf", 10, 10, now);
+    received(dir.path(), "today.txt", 10, 0, now);
+    received(dir.path(), ".peersend-probe", 10, 90, now);
+    received(dir.path(), "archive/kept.iso", 10, 90, now);
+
+    let by_age = Retention::new(
+        RetentionConfig {
+            max_age_days: Some(30),
+            ..RetentionConfig::default()
+        },
+        dir.path(),
+    );
+    assert_eq!(
+        names(dir.path(), &by_age.plan(now).unwrap()),
+        vec!["ancient.iso", "photos/old.jpg"]
+    );
+
+    // 清理旧文件后仍超出上限，继续清理最旧的文件
+    let by_both = Retention::new(
+        RetentionConfig {
+            max_age_days: Some(30),
+            max_size: Some(15),
+            ..RetentionConfig::default()
+        },
+        dir.path(),
+    );
+    assert_eq!(
+        names(dir.path(), &by_both.plan(now).unwrap()),
+        vec!["ancient.iso", "photos/old.jpg", "report.pdf"]
+    );
+}
+
+#[test]
+fn dry_run_leaves_files_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let now = SystemTime::now();
+    received(dir.path(), "old.bin", 10, 10, now);
+    let retention = Retention::new(
+        RetentionConfig {
+            max_age_days: Some(7),
+            dry_run: true,
+            ..RetentionConfig::default()
+        },
+        dir.path(),
+    );
+
+    let cleanup = retention.apply(now).unwrap();
+
+    assert!(cleanup.dry_run);
+    assert_eq!(cleanup.files.len(), 1);
+    assert!(dir.path().join("old.bin").exists());
+}
+
+#[test]
+fn archived_files_are_moved_and_recorded() {
+    let dir = tempfile::tempdir().unwrap();
+    let downloads = dir.path().join("downloads");
+    let now = SystemTime::now();
+    received(&downloads, "scans/old.pdf", 10, 10, now);
+    received(&downloads, "new.pdf", 10, 1, now);
+    let audit = AuditLog::open(dir.path().join("audit.jsonl")).unwrap();
+    let retention = Retention::new(
+        RetentionConfig {
+            max_age_days: Some(7),
+            action: RetentionAction::Archive,
+            ..RetentionConfig::default()
+        },
+        &downloads,
+    )
+    .with_audit_log(audit.clone());
+
+    let cleanup = retention.apply(now).unwrap();
+
+    assert_eq!(cleanup.files.len(), 1);
+    assert!(!downloads.join("scans/old.pdf").exists());
+    assert!(downloads.join("archive/scans/old.pdf").exists());
+    assert!(downloads.join("new.pdf").exists());
+    // 归档目录中的文件之后不再计入
+    assert!(retention.plan(now).unwrap().is_empty());
+
+    let records = audit::records(&audit.path()).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].entry.action, AuditAction::Expired);
+    assert_eq!(records[0].entry.files[0].name.replace('\\', "/"), "scans/old.pdf");
+}
@@ -0,0 +1,126 @@
+//! 上传途径选择测试
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::routes::{self, RouteStats, MIN_SAMPLE_BYTES};
+use peersend_protocol::testing::{peer_device, MockFs, MockHttp};
+use peersend_protocol::transport::tcp::{TcpTransport, TCP_TRANSPORT};
+use peersend_protocol::transport::TransportListener;
+use peersend_protocol::{DeviceInfo, LocalSendClient, LocalSendConfig};
+
+const MB: u64 = 1024 * 1024;
+
+fn peer(transports: Vec<String>) -> DeviceInfo {
+    DeviceInfo {
+        ip: "127.0.0.1".to_string(),
+        version: "0.1.0-peersend".to_string(),
+        transports,
+        ..peer_device()
+    }
+}
+
+fn client(http: &Arc<MockHttp>, config: LocalSendConfig, stats: &RouteStats) -> LocalSendClient {
+    let fs = MockFs::new();
+    fs.insert("/src/disk.img", vec![7u8; MB as usize]);
+    LocalSendClient::new(config)
+        .with_http(http.clone())
+        .with_fs(Arc::new(fs))
+        .with_transport(Arc::new(TcpTransport::new()))
+        .with_route_stats(stats.clone())
+}
+
+fn uploads(http: &MockHttp) -> usize {
+    http.requests().iter().filter(|r| r.url.contains("/upload")).count()
+}
+
+#[test]
+fn routes_follow_preferences_then_measured_speed() {
+    let available = ["quic", "tcp", "https"];
+    let none = BTreeMap::new();
+    assert_eq!(routes::order(&available, None, &none), [0, 1, 2]);
+
+    // 未列出的途径排在指定的途径之后
+    let preferred = ["https".to_string(), "tcp".to_string()];
+    assert_eq!(routes::order(&available, Some(&preferred), &none), [2, 1, 0]);
+
+    // 未测过的 quic 排在最前，其余从快到慢
+    let speeds = BTreeMap::from([("tcp".to_string(), 10 * MB), ("https".to_string(), 40 * MB)]);
+    assert_eq!(routes::order(&available, None, &speeds), [0, 2, 1]);
+    assert_eq!(routes::order(&available, Some(&preferred), &speeds), [2, 1, 0]);
+}
+
+#[test]
+fn speeds_are_averaged_and_shared_through_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("routes.json");
+    let stats = RouteStats::open(&path);
+    stats.record("peer", "tcp", 10 * MB, Duration::from_secs(1)).unwrap();
+    stats.record("peer", "tcp", 30 * MB, Duration::from_secs(1)).unwrap();
+    // 过小的上传不计入
+    stats.record("peer", "quic", MIN_SAMPLE_BYTES - 1, Duration::from_millis(1)).unwrap();
+
+    let other = RouteStats::open(&path);
+    assert_eq!(other.speeds("peer"), BTreeMap::from([("tcp".to_string(), 20 * MB)]));
+    assert!(other.speeds("laptop").is_empty());
+
+    // 只在内存中记录时不写文件
+    let memory = RouteStats::new();
+    memory.record("peer", "tcp", 10 * MB, Duration::from_secs(1)).unwrap();
+    assert_eq!(memory.speeds("peer").len(), 1);
+    assert_eq!(RouteStats::open(&path).speeds("peer")["tcp"], 20 * MB);
+}
+
+#[tokio::test]
+async fn unreachable_transport_falls_back_to_http() {
+    // 取得一个没有监听的端口
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = closed.local_addr().unwrap().port();
+    drop(closed);
+    let device = peer(vec![format!("{}:{}", TCP_TRANSPORT, port)]);
+
+    let http = MockHttp::new();
+    http.accept_all();
+    let stats = RouteStats::new();
+    let client = client(&http, LocalSendConfig::default(), &stats);
+    client.send_files(&device, &["/src/disk.img"]).await.unwrap();
+
+    assert_eq!(uploads(&http), 1);
+    let speeds = stats.speeds("peer");
+    assert_eq!(speeds.keys().collect::<Vec<_>>(), ["https"]);
+}
+
+#[tokio::test]
+async fn preferred_route_is_used_first() {
+    let listener = TcpTransport::new()
+        .listen("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let device = peer(vec![format!("{}:{}", TCP_TRANSPORT, listener.local_addr().port())]);
+
+    let http = MockHttp::new();
+    http.accept_all();
+    let config = LocalSendConfig {
+        route_preferences: BTreeMap::from([("peer".to_string(), vec!["https".to_string()])]),
+        ..LocalSendConfig::default()
+    };
+    assert!(config.validate().is_ok());
+    let client = client(&http, config, &RouteStats::new());
+    client.send_files(&device, &["/src/disk.img"]).await.unwrap();
+    assert_eq!(uploads(&http), 1);
+}
+
+#[test]
+fn unknown_routes_are_rejected() {
+    let config = |routes: Vec<&str>| LocalSendConfig {
+        route_preferences: BTreeMap::from([(
+            "peer".to_string(),
+            routes.into_iter().map(String::from).collect(),
+        )]),
+        ..LocalSendConfig::default()
+    };
+    assert!(config(vec!["quic", "easytier", "http"]).validate().is_ok());
+    assert!(config(vec!["carrier-pigeon"]).validate().is_err());
+    assert!(config(vec![]).validate().is_err());
+}
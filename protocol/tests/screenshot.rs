@@ -0,0 +1,30 @@
+//! 截屏区域与文件名测试
+
+use std::path::Path;
+
+use peersend_protocol::screenshot::{screenshot_path, Region};
+
+#[test]
+fn parses_regions() {
+    let region: Region = " 10, -20 ,1280,720".parse().unwrap();
+    assert_eq!(
+        region,
+        Region {
+            x: 10,
+            y: -20,
+            width: 1280,
+            height: 720
+        }
+    );
+    for invalid in ["", "1,2,3", "1,2,3,4,5", "a,0,10,10", "0,0,0,10", "0,0,10,-1"] {
+        assert!(invalid.parse::<Region>().is_err(), "{:?}", invalid);
+    }
+}
+
+#[test]
+fn screenshots_are_png_files_in_dir() {
+    let path = screenshot_path(Path::new("/tmp/peersend"));
+    assert_eq!(path.parent(), Some(Path::new("/tmp/peersend")));
+    let name = path.file_name().unwrap().to_string_lossy();
+    assert!(name.starts_with("screenshot-") && name.ends_with(".png"));
+}
@@ -0,0 +1,106 @@
+//! 经 LocalSend v2 接口发送文件的测试
+
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::Arc;
+
+use peersend_protocol::dto::v2::PrepareUploadRequestDto;
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::testing::{peer_device, Fault, MockFs, MockHttp};
+use peersend_protocol::{
+    DeviceInfo, DiscoveryManager, LocalSendClient, LocalSendConfig, QuickSave, SessionManager,
+    SessionState,
+};
+use tokio::sync::Mutex;
+
+fn device(port: u16) -> DeviceInfo {
+    DeviceInfo {
+        id: "desk".to_string(),
+        name: "Desk".to_string(),
+        ip: "127.0.0.1".to_string(),
+        port,
+        version: "2.0".to_string(),
+        ..peer_device()
+    }
+}
+
+/// 接受所有文件，会话 ID 为 remote，令牌为文件 ID
+fn accept_all(http: &MockHttp) {
+    http.route("/prepare-upload", |request| {
+        let prepare: PrepareUploadRequestDto = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(prepare.info.fingerprint, "phone");
+        request.accept_files(|_| true)
+    });
+}
+
+fn client(http: &Arc<MockHttp>) -> LocalSendClient {
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"hello".to_vec());
+    let config = LocalSendConfig {
+        device_id: "phone".to_string(),
+        ..LocalSendConfig::default()
+    };
+    LocalSendClient::new(config)
+        .with_http(http.clone())
+        .with_fs(Arc::new(fs))
+}
+
+#[tokio::test]
+async fn files_are_sent_to_receiver() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig {
+        quick_save: QuickSave::On,
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        ..LocalSendConfig::default()
+    };
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let received = SessionManager::new();
+    let server = LocalSendServer::new(
+        listener.local_addr().unwrap(),
+        config,
+        Arc::new(Mutex::new(received.clone())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    )
+    .with_listener(listener);
+    server.start().await.unwrap();
+
+    let src = tempfile::tempdir().unwrap();
+    let a = src.path().join("a.txt");
+    let b = src.path().join("b.bin");
+    std::fs::write(&a, b"hello").unwrap();
+    std::fs::write(&b, [7u8; 4096]).unwrap();
+    let client = LocalSendClient::new(LocalSendConfig::default());
+    let session = client.send_files(&device(port), &[&a, &b]).await.unwrap();
+
+    assert_eq!(*session.state.lock().await, SessionState::Finished);
+    assert_eq!(std::fs::read(dir.path().join("a.txt")).unwrap(), b"hello");
+    assert_eq!(std::fs::read(dir.path().join("b.bin")).unwrap(), [7u8; 4096]);
+    let sessions = received.get_all_sessions().await;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(*sessions[0].state.lock().await, SessionState::Finished);
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn failed_upload_cancels_the_session() {
+    let http = MockHttp::new();
+    accept_all(&http);
+    http.inject("/upload", Fault::Status(500));
+
+    let result = client(&http).send_files(&device(53317), &["/src/a.txt"]).await;
+    assert!(result.is_err());
+    let requests = http.requests();
+    let cancel = requests.last().unwrap();
+    assert!(cancel.url.ends_with("/api/localsend/v2/cancel"), "{}", cancel.url);
+    assert_eq!(cancel.query_param("sessionId"), Some("remote"));
+}
+
+#[tokio::test]
+async fn nothing_accepted_finishes_without_uploads() {
+    let http = MockHttp::new();
+    http.respond_status("/prepare-upload", 204);
+
+    let session = client(&http).send_files(&device(53317), &["/src/a.txt"]).await.unwrap();
+    assert_eq!(*session.state.lock().await, SessionState::Finished);
+    assert_eq!(http.requests().len(), 1);
+}
@@ -0,0 +1,232 @@
+//! LocalSend v2 HTTP 接口测试
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::Arc;
+
+use peersend_protocol::dto::v2::{
+    FileDto, InfoDto, PrepareUploadRequestDto, PrepareUploadResponseDto, RegisterDto, CANCEL_PATH,
+    INFO_PATH, PREPARE_UPLOAD_PATH, PROTOCOL_VERSION, REGISTER_PATH, UPLOAD_PATH,
+};
+use peersend_protocol::http::{HttpRequest, HttpResponse, ReqwestClient};
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::{DiscoveryManager, LocalSendConfig, QuickSave, SessionManager, SessionState};
+use tokio::sync::Mutex;
+
+struct Receiver {
+    base: String,
+    server: LocalSendServer,
+    sessions: SessionManager,
+    discovery: Arc<Mutex<DiscoveryManager>>,
+    dir: tempfile::TempDir,
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.server.shutdown();
+    }
+}
+
+/// 启动自动接受所有请求的接收服务
+async fn receiver() -> Receiver {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig {
+        device_name: "Desk".to_string(),
+        quick_save: QuickSave::On,
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        ..LocalSendConfig::default()
+    };
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let sessions = SessionManager::new();
+    let discovery = Arc::new(Mutex::new(DiscoveryManager::new()));
+    let server = LocalSendServer::new(
+        listener.local_addr().unwrap(),
+        config,
+        Arc::new(Mutex::new(sessions.clone())),
+        discovery.clone(),
+    )
+    .with_listener(listener);
+    server.start().await.unwrap();
+    Receiver {
+        base,
+        server,
+        sessions,
+        discovery,
+        dir,
+    }
+}
+
+fn register() -> RegisterDto {
+    RegisterDto {
+        alias: "Phone".to_string(),
+        version: PROTOCOL_VERSION.to_string(),
+        device_model: None,
+        device_type: Some("mobile".to_string()),
+        fingerprint: "phone".to_string(),
+        port: 53317,
+        protocol: "http".to_string(),
+        download: false,
+    }
+}
+
+fn prepare(files: &[(&str, &str, u64)]) -> PrepareUploadRequestDto {
+    let files = files
+        .iter()
+        .map(|(id, name, size)| {
+            let file = FileDto {
+                id: id.to_string(),
+                file_name: name.to_string(),
+                size: *size,
+                file_type: "text/plain".to_string(),
+                sha256: None,
+                preview: None,
+                metadata: None,
+            };
+            (id.to_string(), file)
+        })
+        .collect();
+    PrepareUploadRequestDto {
+        info: register(),
+        files,
+    }
+}
+
+async fn post(request: HttpRequest) -> HttpResponse {
+    ReqwestClient::shared().execute(request).await.unwrap()
+}
+
+fn upload(base: &str, session_id: &str, file_id: &str, token: &str, body: &[u8]) -> HttpRequest {
+    HttpRequest::post(format!("{}{}", base, UPLOAD_PATH))
+        .query("sessionId", session_id)
+        .query("fileId", file_id)
+        .query("token", token)
+        .stream(Box::pin(std::io::Cursor::new(body.to_vec())), body.len() as u64)
+}
+
+#[tokio::test]
+async fn info_and_register_describe_the_receiver() {
+    let receiver = receiver().await;
+    let response = post(HttpRequest::get(format!("{}{}", receiver.base, INFO_PATH))).await;
+    let info: InfoDto = response.error_for_status().unwrap().json().unwrap();
+    assert_eq!((info.alias.as_str(), info.version.as_str()), ("Desk", PROTOCOL_VERSION));
+
+    let request = HttpRequest::post(format!("{}{}", receiver.base, REGISTER_PATH))
+        .json(&register())
+        .unwrap();
+    let info: InfoDto = post(request).await.error_for_status().unwrap().json().unwrap();
+    assert_eq!(info.alias, "Desk");
+    let devices = receiver.discovery.lock().await.get_devices().await;
+    assert!(devices.iter().any(|d| d.id == "phone" && d.ip == "127.0.0.1"), "{:?}", devices);
+}
+
+#[tokio::test]
+async fn files_are_uploaded_with_issued_tokens() {
+    let receiver = receiver().await;
+    let mut events = receiver.sessions.events().subscribe();
+    let request = HttpRequest::post(format!("{}{}", receiver.base, PREPARE_UPLOAD_PATH))
+        .json(&prepare(&[("a", "a.txt", 5), ("b", "b.txt", 3)]))
+        .unwrap();
+    let prepared: PrepareUploadResponseDto =
+        post(request).await.error_for_status().unwrap().json().unwrap();
+    assert_eq!(prepared.files.len(), 2);
+    let id = prepared.session_id.as_str();
+
+    // 令牌不对或缺少参数的上传被拒绝
+    let response = post(upload(&receiver.base, id, "a", "wrong", b"hello")).await;
+    assert_eq!(response.status, 403);
+    let request = HttpRequest::post(format!("{}{}", receiver.base, UPLOAD_PATH));
+    assert_eq!(post(request).await.status, 400);
+
+    let response = post(upload(&receiver.base, id, "a", &prepared.files["a"], b"hello")).await;
+    assert_eq!(response.status, 200);
+    let session = receiver.sessions.get_session(id).await.unwrap();
+    assert_eq!(*session.state.lock().await, SessionState::Transferring);
+    let response = post(upload(&receiver.base, id, "b", &prepared.files["b"], b"abc")).await;
+    assert_eq!(response.status, 200);
+
+    assert_eq!(std::fs::read(receiver.dir.path().join("a.txt")).unwrap(), b"hello");
+    assert_eq!(std::fs::read(receiver.dir.path().join("b.txt")).unwrap(), b"abc");
+    assert_eq!(*session.state.lock().await, SessionState::Finished);
+    // 令牌只能使用一次
+    let response = post(upload(&receiver.base, id, "a", &prepared.files["a"], b"hello")).await;
+    assert_eq!(response.status, 403);
+
+    let mut finished = false;
+    while let Ok(event) = events.try_recv() {
+        finished |= matches!(event, peersend_protocol::ProtocolEvent::SessionSummary(_));
+    }
+    assert!(finished);
+}
+
+#[tokio::test]
+async fn sender_can_cancel_the_session() {
+    let receiver = receiver().await;
+    let request = HttpRequest::post(format!("{}{}", receiver.base, PREPARE_UPLOAD_PATH))
+        .json(&prepare(&[("a", "a.txt", 5)]))
+        .unwrap();
+    let prepared: PrepareUploadResponseDto =
+        post(request).await.error_for_status().unwrap().json().unwrap();
+
+    let cancel = |id: &str| {
+        HttpRequest::post(format!("{}{}", receiver.base, CANCEL_PATH)).query("sessionId", id)
+    };
+    assert_eq!(post(cancel(&prepared.session_id)).await.status, 200);
+    let session = receiver.sessions.get_session(&prepared.session_id).await.unwrap();
+    assert_eq!(*session.state.lock().await, SessionState::Cancelled);
+    assert_eq!(post(cancel(&prepared.session_id)).await.status, 403);
+
+    let token = &prepared.files["a"];
+    let response = post(upload(&receiver.base, &prepared.session_id, "a", token, b"hello")).await;
+    assert_eq!(response.status, 403);
+}
+
+#[tokio::test]
+async fn tokens_are_bound_to_session_file_and_sender() {
+    let receiver = receiver().await;
+    let request = HttpRequest::post(format!("{}{}", receiver.base, PREPARE_UPLOAD_PATH))
+        .json(&prepare(&[("a", "a.txt", 5), ("b", "b.txt", 3)]))
+        .unwrap();
+    let prepared: PrepareUploadResponseDto =
+        post(request).await.error_for_status().unwrap().json().unwrap();
+    let id = prepared.session_id.as_str();
+    assert_ne!(prepared.files["a"], prepared.files["b"]);
+
+    // 令牌只对发放时的会话和文件有效
+    let response = post(upload(&receiver.base, "other", "a", &prepared.files["a"], b"hello")).await;
+    assert_eq!(response.status, 403);
+    let response = post(upload(&receiver.base, id, "b", &prepared.files["a"], b"abc")).await;
+    assert_eq!(response.status, 403);
+    let sessions = &receiver.sessions;
+    assert!(sessions.check_token(id, "a", &prepared.files["a"], "127.0.0.1").is_ok());
+    assert!(sessions.check_token(id, "a", &prepared.files["a"], "192.0.2.7").is_err());
+
+    // 由其他地址发起的会话不接受本机的上传和取消
+    let session = sessions
+        .create_session("laptop".to_string(), "desk".to_string(), Vec::new())
+        .await;
+    let tokens = sessions.issue_tokens(&session.id, "192.0.2.7", ["a"]);
+    let response = post(upload(&receiver.base, &session.id, "a", &tokens["a"], b"hello")).await;
+    assert_eq!(response.status, 403);
+    let cancel = HttpRequest::post(format!("{}{}", receiver.base, CANCEL_PATH))
+        .query("sessionId", &session.id);
+    assert_eq!(post(cancel).await.status, 403);
+    assert_eq!(*session.state.lock().await, SessionState::Waiting);
+    assert!(sessions.check_token(&session.id, "a", &tokens["a"], "192.0.2.7").is_ok());
+    assert!(!receiver.dir.path().join("a.txt").exists());
+
+    // 会话移除后令牌失效
+    sessions.remove_session(id).await;
+    let response = post(upload(&receiver.base, id, "a", &prepared.files["a"], b"hello")).await;
+    assert_eq!(response.status, 403);
+}
+
+#[tokio::test]
+async fn invalid_requests_get_client_errors() {
+    let receiver = receiver().await;
+    let url = |path: &str| format!("{}{}", receiver.base, path);
+    let request = HttpRequest::post(url(PREPARE_UPLOAD_PATH)).json(&HashMap::from([("a", 1)]));
+    assert_eq!(post(request.unwrap()).await.status, 400);
+    assert_eq!(post(HttpRequest::get(url(PREPARE_UPLOAD_PATH))).await.status, 405);
+    assert_eq!(post(HttpRequest::get(url("/api/localsend/v2/unknown"))).await.status, 404);
+}
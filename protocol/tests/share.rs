@@ -0,0 +1,85 @@
+//! 分享链接测试
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use peersend_protocol::server::share::ShareStore;
+use peersend_protocol::ProtocolError;
+use reqwest::StatusCode;
+use tokio_util::sync::CancellationToken;
+
+fn store(dir: &std::path::Path) -> ShareStore {
+    ShareStore::new(dir.join("shares.json"))
+}
+
+#[test]
+fn links_expire_and_can_be_revoked() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("a.txt");
+    std::fs::write(&file, b"data").unwrap();
+    let store = store(dir.path());
+
+    let forever = store.create(&file, None, None).unwrap();
+    let expired = store.create(&file, Some(Duration::ZERO), None).unwrap();
+    assert!(matches!(store.claim(&expired.token, false), Err(ProtocolError::Status(410))));
+
+    let hour = store.create(&file, Some(Duration::from_secs(3600)), Some(1)).unwrap();
+    assert_ne!(forever.token, hour.token);
+    assert!(forever.token.len() >= 32);
+    let tokens: Vec<_> = store.list().unwrap().into_iter().map(|l| l.token).collect();
+    assert_eq!(tokens, vec![forever.token.clone(), hour.token.clone()]);
+
+    // 另一个实例读取同一文件
+    assert!(ShareStore::new(dir.path().join("shares.json"))
+        .revoke(&forever.token)
+        .unwrap());
+    assert!(!store.revoke(&forever.token).unwrap());
+    assert!(matches!(store.claim(&forever.token, false), Err(ProtocolError::Status(404))));
+
+    assert!(store.create(&dir.path().join("missing"), None, None).is_err());
+    assert!(store.create(&file, None, Some(0)).is_err());
+}
+
+#[tokio::test]
+async fn download_limit_is_enforced() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("报告.pdf");
+    std::fs::write(&file, b"0123456789").unwrap();
+    let store = Arc::new(store(dir.path()));
+    let link = store.create(&file, Some(Duration::from_secs(3600)), Some(1)).unwrap();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let cancel = CancellationToken::new();
+    tokio::spawn(store.clone().serve(listener, cancel.clone()));
+    let client = reqwest::Client::new();
+    let url = format!("{}{}", base, link.url_path());
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-disposition"]
+        .to_str()
+        .unwrap()
+        .contains("%E6%8A%A5%E5%91%8A%2Epdf"));
+    assert_eq!(response.bytes().await.unwrap().as_ref(), b"0123456789");
+
+    // 续传不计次数
+    let resumed = client
+        .get(&url)
+        .header("Range", "bytes=6-")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resumed.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(resumed.bytes().await.unwrap().as_ref(), b"6789");
+
+    let again = client.get(&url).send().await.unwrap();
+    assert_eq!(again.status(), StatusCode::GONE);
+
+    let unknown = client.get(format!("{}/s/nope", base)).send().await.unwrap();
+    assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+
+    // 用完的链接在下次修改列表时清理
+    assert!(store.list().unwrap().is_empty());
+    cancel.cancel();
+}
@@ -0,0 +1,50 @@
+//! 会话与设备列表快照测试
+
+use std::sync::Arc;
+
+use peersend_protocol::testing::peer_device;
+use peersend_protocol::{DeviceInfo, DiscoveryManager, SessionManager};
+
+fn device(id: &str) -> DeviceInfo {
+    DeviceInfo {
+        id: id.to_string(),
+        name: id.to_string(),
+        ip: "127.0.0.1".to_string(),
+        ..peer_device()
+    }
+}
+
+#[tokio::test]
+async fn unchanged_lists_share_one_snapshot() {
+    let sessions = SessionManager::new();
+    sessions.create_session("a".to_string(), "b".to_string(), Vec::new()).await;
+
+    let first = sessions.get_all_sessions().await;
+    let second = sessions.get_all_sessions().await;
+    assert!(Arc::ptr_eq(&first, &second));
+
+    let devices = DiscoveryManager::new();
+    devices.add_device(device("peer")).await;
+    let first = devices.get_devices().await;
+    assert!(Arc::ptr_eq(&first, &devices.get_devices().await));
+}
+
+#[tokio::test]
+async fn snapshots_are_not_affected_by_later_changes() {
+    let sessions = SessionManager::new();
+    let session = sessions.create_session("a".to_string(), "b".to_string(), Vec::new()).await;
+    let before = sessions.get_all_sessions().await;
+
+    sessions.remove_session(&session.id).await;
+
+    assert_eq!(before.len(), 1);
+    assert_eq!(sessions.session_count().await, 0);
+
+    let devices = DiscoveryManager::new();
+    devices.add_device(device("first")).await;
+    let before = devices.get_devices().await;
+    devices.add_device(device("second")).await;
+
+    assert_eq!(before.len(), 1);
+    assert_eq!(devices.device_count().await, 2);
+}
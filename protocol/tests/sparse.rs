@@ -0,0 +1,121 @@
+//! 稀疏文件传输测试
+
+use peersend_protocol::hash::sha256_hex;
+use peersend_protocol::transport::sparse;
+use peersend_protocol::transport::{self, StreamHeader};
+
+const MB: u64 = 1024 * 1024;
+
+fn header(size: u64, extents: Vec<sparse::Extent>) -> StreamHeader {
+    StreamHeader {
+        session_id: "session".to_string(),
+        file_id: "file".to_string(),
+        token: String::new(),
+        size,
+        offset: 0,
+        sparse: Some(extents),
+    }
+}
+
+/// 带空洞的文件内容，空洞处为 0
+fn contents(size: u64, extents: &[(u64, &[u8])]) -> Vec<u8> {
+    let mut data = vec![0u8; size as usize];
+    for (offset, bytes) in extents {
+        data[*offset as usize..*offset as usize + bytes.len()].copy_from_slice(bytes);
+    }
+    data
+}
+
+#[test]
+fn extents_must_be_ordered_and_inside_file() {
+    assert!(sparse::check_extents(&[(0, 4), (10, 2)], 12).is_ok());
+    assert!(sparse::check_extents(&[], 12).is_ok());
+    assert!(sparse::check_extents(&[(10, 2), (0, 4)], 12).is_err());
+    assert!(sparse::check_extents(&[(0, 4), (2, 4)], 12).is_err());
+    assert!(sparse::check_extents(&[(10, 3)], 12).is_err());
+    assert!(sparse::check_extents(&[(u64::MAX, 2)], 12).is_err());
+    assert_eq!(header(12, vec![(0, 4), (10, 2)]).body_len(), 6);
+}
+
+#[tokio::test]
+async fn sparse_stream_recreates_holes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("disk.img");
+    let size = 3 * MB + 5;
+    let expected = contents(size, &[(0, b"head"), (2 * MB, b"tail!")]);
+    let header = header(size, vec![(0, 4), (2 * MB, 5)]);
+
+    let mut body = &b"headtail!"[..];
+    let received = transport::receive_file(&mut body, &header, &path, Some(&sha256_hex(&expected)))
+        .await
+        .unwrap();
+    assert_eq!(received, size);
+    assert!(std::fs::read(&path).unwrap() == expected);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let allocated = std::fs::metadata(&path).unwrap().blocks() * 512;
+        assert!(allocated < MB, "空洞被写入磁盘: {} 字节", allocated);
+    }
+}
+
+#[tokio::test]
+async fn truncated_sparse_stream_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("disk.img");
+    let truncated = header(2 * MB, vec![(0, 4), (MB, 5)]);
+
+    let result = transport::receive_file(&mut &b"headta"[..], &truncated, &path, None).await;
+    assert!(result.is_err());
+    assert!(!path.exists());
+
+    let overlapping = header(2 * MB, vec![(0, 4), (2, 5)]);
+    let result = transport::receive_file(&mut &b"headtail!"[..], &overlapping, &path, None).await;
+    assert!(result.is_err());
+}
+
+#[cfg(all(feature = "sparse", target_os = "linux"))]
+#[tokio::test]
+async fn holes_are_detected_and_skipped_when_sending() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("vm.qcow2");
+    let size = 8 * MB;
+    let mut file = std::fs::File::create(&path).unwrap();
+    file.set_len(size).unwrap();
+    file.write_all(b"boot").unwrap();
+    file.seek(SeekFrom::Start(6 * MB)).unwrap();
+    file.write_all(b"data").unwrap();
+    drop(file);
+    let expected = contents(size, &[(0, b"boot"), (6 * MB, b"data")]);
+
+    let extents = sparse::data_extents(&path, size).await.unwrap().unwrap();
+    assert!(sparse::data_len(&extents) < MB);
+    assert!(sparse::check_extents(&extents, size).is_ok());
+
+    // 普通文件按原样发送
+    let dense = dir.path().join("dense.bin");
+    std::fs::write(&dense, &expected[..MB as usize]).unwrap();
+    assert!(sparse::data_extents(&dense, MB).await.unwrap().is_none());
+
+    let header = header(size, extents);
+    let (mut tx, mut rx) = tokio::io::duplex(64 * 1024);
+    let received = dir.path().join("received.qcow2");
+    let send = async {
+        let sent = transport::send_file(&mut tx, &header, &path).await.unwrap();
+        drop(tx);
+        sent
+    };
+    let receive = async {
+        let header = transport::read_header(&mut rx).await.unwrap();
+        transport::receive_file(&mut rx, &header, &received, Some(&sha256_hex(&expected)))
+            .await
+            .unwrap()
+    };
+    let (sent, _) = tokio::join!(send, receive);
+
+    assert_eq!(sent, header.body_len());
+    assert!(std::fs::read(&received).unwrap() == expected);
+}
@@ -0,0 +1,234 @@
+//! 接收存储后端测试
+
+use std::sync::Arc;
+
+use peersend_protocol::session::FileReceiver;
+use peersend_protocol::storage::{
+    self, FsStorage, MemoryStorage, ReceiveStorage, StorageKey, QUARANTINE_DIR,
+};
+use peersend_protocol::summary::FileOutcome;
+use peersend_protocol::testing::MockFs;
+use peersend_protocol::{
+    CollisionPolicy, FileInfo, FileSession, FileWarning, SessionState, SessionSummary,
+};
+
+fn session(names: &[&str]) -> FileSession {
+    let files = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| FileInfo {
+            id: i.to_string(),
+            name: name.to_string(),
+            size: 0,
+            file_type: "application/octet-stream".to_string(),
+            metadata: None,
+            sha256: None,
+        })
+        .collect();
+    FileSession::new("session".to_string(), "peer".to_string(), "self".to_string(), files)
+}
+
+#[tokio::test]
+async fn receiver_lands_files_in_memory() {
+    let storage = Arc::new(MemoryStorage::new());
+    let mut receiver = FileReceiver::new(session(&["a.txt", "b.txt"]), "/unused".into())
+        .with_storage(storage.clone());
+
+    receiver.start_file("a.txt").await.unwrap();
+    receiver.write_chunk(b"first").await.unwrap();
+    receiver.finish_current_file().await.unwrap();
+
+    receiver.start_file("b.txt").await.unwrap();
+    receiver.write_chunk(b"partial").await.unwrap();
+    receiver.abort_current_file().await.unwrap();
+
+    assert_eq!(storage.get("a.txt").await.unwrap(), b"first");
+    assert!(storage.get("b.txt").await.is_none());
+    assert_eq!(storage.names().await, vec!["a.txt".to_string()]);
+}
+
+/// 依次接收会话中的文件，内容为文件 ID
+async fn receive_all(receiver: &mut FileReceiver, names: &[&str]) {
+    for (i, name) in names.iter().enumerate() {
+        receiver.start_file(name).await.unwrap();
+        receiver.write_chunk(i.to_string().as_bytes()).await.unwrap();
+        receiver.finish_current_file().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn receiver_renames_colliding_files() {
+    let names = ["photo.jpg", "photo.jpg", "trip/photo.jpg"];
+    let session = session(&names);
+    let storage = Arc::new(MemoryStorage::new());
+    let mut receiver =
+        FileReceiver::new(session.clone(), "/unused".into()).with_storage(storage.clone());
+
+    receiver.start_file("photo.jpg").await.unwrap();
+    assert_eq!(receiver.saved_name(), Some("photo.jpg"));
+    receiver.abort_current_file().await.unwrap();
+    receive_all(&mut receiver, &names).await;
+
+    assert_eq!(storage.get("photo.jpg").await.unwrap(), b"0");
+    assert_eq!(storage.get("photo (1).jpg").await.unwrap(), b"1");
+    assert_eq!(storage.get("photo (2).jpg").await.unwrap(), b"2");
+    let saved_name = |id: &str| session.transfers.lock().unwrap()[id].saved_name.clone();
+    assert_eq!(saved_name("2").as_deref(), Some("photo (2).jpg"));
+    assert_eq!(
+        *session.warnings.lock().await,
+        [
+            FileWarning::new("photo.jpg", "已有同名文件，另存为 photo (1).jpg"),
+            FileWarning::new("photo.jpg", "已有同名文件，另存为 photo (2).jpg"),
+        ]
+    );
+
+    let summary = SessionSummary::new(&session, &SessionState::Finished).await;
+    assert_eq!(summary.files[0].saved_name, None);
+    assert_eq!(summary.files[1].saved_name.as_deref(), Some("photo (1).jpg"));
+}
+
+#[tokio::test]
+async fn receiver_skips_or_overwrites_existing_files() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("notes.txt"), b"old").unwrap();
+
+    let skipped = session(&["notes.txt"]);
+    let mut receiver = FileReceiver::new(skipped.clone(), dir.path().into())
+        .with_collision_policy(CollisionPolicy::Skip);
+    receive_all(&mut receiver, &["notes.txt"]).await;
+    assert_eq!(std::fs::read(dir.path().join("notes.txt")).unwrap(), b"old");
+    assert!(receiver.is_complete());
+    assert!(skipped.transfers.lock().unwrap()["0"].skipped);
+    assert_eq!(skipped.warnings.lock().await.len(), 1);
+    // 会话失败时跳过的文件同样记为跳过
+    let summary = SessionSummary::new(&skipped, &SessionState::Error("x".into())).await;
+    assert_eq!(summary.files[0].outcome, FileOutcome::Skipped);
+
+    let overwritten = session(&["notes.txt"]);
+    let mut receiver = FileReceiver::new(overwritten.clone(), dir.path().into())
+        .with_collision_policy(CollisionPolicy::Overwrite);
+    receive_all(&mut receiver, &["notes.txt"]).await;
+    assert_eq!(std::fs::read(dir.path().join("notes.txt")).unwrap(), b"0");
+    assert!(overwritten.warnings.lock().await.is_empty());
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn fs_storage_abort_removes_partial_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = FsStorage::new(dir.path());
+    let key = StorageKey {
+        session_id: "session".to_string(),
+        file_id: "0".to_string(),
+        name: "partial.bin".to_string(),
+    };
+
+    storage.open(&key).await.unwrap();
+    storage.write_chunk(&key, b"abc").await.unwrap();
+    let part = storage.part_path(&key);
+    assert!(part.exists());
+    assert!(!storage.path(&key).exists());
+
+    storage.abort(&key).await.unwrap();
+    assert!(!part.exists());
+}
+
+#[tokio::test]
+async fn fs_storage_keeps_same_named_files_apart() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = FsStorage::new(dir.path());
+    let key = |session_id: &str, file_id: &str| StorageKey {
+        session_id: session_id.to_string(),
+        file_id: file_id.to_string(),
+        name: "same.bin".to_string(),
+    };
+    let keys = [key("a", "0"), key("b", "0"), key("a", "1")];
+
+    for (key, data) in keys.iter().zip([&b"first"[..], b"second", b"third"]) {
+        storage.open(key).await.unwrap();
+        storage.write_chunk(key, data).await.unwrap();
+        storage.suspend(key).await.unwrap();
+    }
+    // 后打开的文件不会截断其他会话或同一会话中同名文件的 .part
+    assert_eq!(storage.received_len(&keys[0]).await.unwrap(), 5);
+    assert_eq!(storage.received_len(&keys[1]).await.unwrap(), 6);
+    assert_eq!(storage.received_len(&keys[2]).await.unwrap(), 5);
+    assert!(!storage.exists(&keys[0]).await.unwrap());
+
+    storage.resume(&keys[1], 6).await.unwrap();
+    storage.finalize(&keys[1]).await.unwrap();
+    assert_eq!(std::fs::read(dir.path().join("same.bin")).unwrap(), b"second");
+    assert_eq!(storage.received_len(&keys[0]).await.unwrap(), 5);
+    storage.abort(&keys[0]).await.unwrap();
+    storage.abort(&keys[2]).await.unwrap();
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn fs_storage_buffers_until_finalize() {
+    let fs = Arc::new(MockFs::new());
+    let storage = FsStorage::with_fs("/downloads", fs.clone()).with_buffer_size(16);
+    let key = StorageKey {
+        session_id: "session".to_string(),
+        file_id: "0".to_string(),
+        name: "out.bin".to_string(),
+    };
+
+    storage.open(&key).await.unwrap();
+    storage.write_chunk(&key, b"0123456789").await.unwrap();
+    let part = storage.part_path(&key);
+    assert_eq!(fs.get(&part).unwrap(), b"");
+    storage.write_chunk(&key, b"abcdefghij").await.unwrap();
+    storage.write_chunk(&key, b"tail").await.unwrap();
+
+    assert!(fs.get("/downloads/out.bin").is_none());
+
+    storage.finalize(&key).await.unwrap();
+    assert_eq!(fs.get("/downloads/out.bin").unwrap(), b"0123456789abcdefghijtail");
+    assert!(fs.get(&part).is_none());
+    assert!(storage.write_chunk(&key, b"late").await.is_err());
+}
+
+#[test]
+fn risky_extensions_are_matched_case_insensitively() {
+    let extensions = vec!["exe".to_string(), ".js".to_string()];
+    assert!(storage::is_quarantined("setup.EXE", &extensions));
+    assert!(storage::is_quarantined("invoice.pdf.js", &extensions));
+    assert!(!storage::is_quarantined("notes.json", &extensions));
+    assert!(!storage::is_quarantined("exe", &extensions));
+    assert!(!storage::is_quarantined("setup.exe", &[]));
+}
+
+#[tokio::test]
+async fn fs_storage_quarantines_risky_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = FsStorage::new(dir.path()).with_quarantine(vec!["scr".to_string()]);
+    let key = |name: &str| StorageKey {
+        session_id: "session".to_string(),
+        file_id: name.to_string(),
+        name: name.to_string(),
+    };
+
+    for name in ["holiday.scr", "holiday.jpg"] {
+        storage.open(&key(name)).await.unwrap();
+        storage.write_chunk(&key(name), b"MZ").await.unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let part = storage.part_path(&key(name));
+            std::fs::set_permissions(part, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        storage.finalize(&key(name)).await.unwrap();
+    }
+
+    let quarantined = dir.path().join(QUARANTINE_DIR).join("holiday.scr");
+    assert_eq!(std::fs::read(&quarantined).unwrap(), b"MZ");
+    assert!(!dir.path().join("holiday.scr").exists());
+    assert!(dir.path().join("holiday.jpg").exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&quarantined).unwrap().permissions().mode();
+        assert_eq!(mode & 0o111, 0);
+    }
+}
@@ -0,0 +1,137 @@
+//! 会话摘要测试
+
+use std::sync::Arc;
+
+use peersend_protocol::summary::{FileOutcome, Verification};
+use peersend_protocol::testing::{peer_device, Fault, MockFs, MockHttp};
+use peersend_protocol::{
+    DeviceInfo, FileSession, LocalSendClient, LocalSendConfig, ProtocolEvent, SessionManager,
+    SessionState, SessionSummary,
+};
+
+fn peer() -> DeviceInfo {
+    DeviceInfo {
+        ip: "127.0.0.1".to_string(),
+        version: "0.1.0-peersend".to_string(),
+        ..peer_device()
+    }
+}
+
+/// 接受名称不以 skip 开头的文件
+fn accept(http: &MockHttp) {
+    http.route("/prepare-upload", |request| {
+        request.accept_files(|f| !f.file_name.starts_with("skip"))
+    });
+    http.respond_status("/upload", 200);
+}
+
+fn client(http: &Arc<MockHttp>, sessions: &SessionManager) -> LocalSendClient {
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"hello".to_vec());
+    fs.insert("/src/skip.txt", b"later".to_vec());
+    LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_fs(Arc::new(fs))
+        .with_session_manager(sessions.clone())
+}
+
+fn summary(session: &FileSession) -> &SessionSummary {
+    session.summary.get().expect("会话结束时应生成摘要")
+}
+
+#[tokio::test]
+async fn finished_session_is_summarized() {
+    let http = MockHttp::new();
+    accept(&http);
+    let sessions = SessionManager::new();
+    let mut events = sessions.events().subscribe();
+    let client = client(&http, &sessions).with_hash_verification(true);
+
+    let session = client.send_files(&peer(), &["/src/a.txt"]).await.unwrap();
+    let summary = summary(&session);
+    assert_eq!(summary.state, "finished");
+    assert_eq!((summary.transferred, summary.skipped, summary.failed), (1, 0, 0));
+    assert_eq!(summary.total_bytes, 5);
+    assert_eq!((summary.verified, summary.mismatched), (1, 0));
+    assert_eq!(summary.files[0].verification, Verification::Verified);
+
+    // 摘要在会话结束的状态之后发布
+    let mut published = None;
+    while let Ok(event) = events.try_recv() {
+        if let ProtocolEvent::SessionSummary(summary) = event {
+            published = Some(summary);
+        }
+    }
+    assert_eq!(published.as_deref(), Some(summary));
+
+    let value = serde_json::to_value(summary).unwrap();
+    assert_eq!(value["totalBytes"], 5);
+    assert_eq!(value["files"][0]["outcome"], "transferred");
+    assert!(value.get("error").is_none());
+    let parsed: SessionSummary = serde_json::from_value(value).unwrap();
+    assert_eq!(&parsed, summary);
+}
+
+#[tokio::test]
+async fn files_not_accepted_are_skipped() {
+    let http = MockHttp::new();
+    accept(&http);
+    let sessions = SessionManager::new();
+    let session = client(&http, &sessions)
+        .send_files(&peer(), &["/src/a.txt", "/src/skip.txt"])
+        .await
+        .unwrap();
+
+    let summary = summary(&session);
+    assert_eq!((summary.transferred, summary.skipped, summary.failed), (1, 1, 0));
+    assert_eq!(summary.total_bytes, 5);
+    let skipped = summary.files.iter().find(|f| f.outcome == FileOutcome::Skipped).unwrap();
+    assert_eq!(skipped.name, "skip.txt");
+    assert_eq!(skipped.verification, Verification::Unchecked);
+    assert_eq!(skipped.reason, None);
+}
+
+#[tokio::test]
+async fn failed_upload_is_reported() {
+    let http = MockHttp::new();
+    accept(&http);
+    http.inject("/upload", Fault::Status(500));
+    let sessions = SessionManager::new();
+    let mut events = sessions.events().subscribe();
+
+    let result = client(&http, &sessions).send_files(&peer(), &["/src/a.txt"]).await;
+    assert!(result.is_err());
+    let summary = loop {
+        match events.try_recv().expect("应发布会话摘要") {
+            ProtocolEvent::SessionSummary(summary) => break summary,
+            _ => continue,
+        }
+    };
+    assert_eq!(summary.state, "error");
+    assert!(summary.error.is_some());
+    assert_eq!((summary.transferred, summary.failed, summary.total_bytes), (0, 1, 0));
+    assert!(summary.files[0].reason.is_some());
+}
+
+#[tokio::test]
+async fn summary_is_produced_once() {
+    let sessions = SessionManager::new();
+    let session = sessions
+        .create_session("me".to_string(), "peer".to_string(), Vec::new())
+        .await;
+    let mut events = sessions.events().subscribe();
+    // 等待确认时被取消 (如对方拒绝) 不算结束的传输
+    sessions.set_state(&session.id, SessionState::Cancelled).await;
+    sessions.set_state(&session.id, SessionState::Transferring).await;
+    sessions.set_state(&session.id, SessionState::Cancelled).await;
+    sessions.set_state(&session.id, SessionState::Error("重复".to_string())).await;
+
+    let mut summaries = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let ProtocolEvent::SessionSummary(summary) = event {
+            summaries.push(summary);
+        }
+    }
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].state, "cancelled");
+}
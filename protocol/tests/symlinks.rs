@@ -0,0 +1,191 @@
+//! 符号链接与特殊文件测试
+#![cfg(unix)]
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use peersend_protocol::dto::v2::{
+    FileDto, FileTimesDto, PrepareUploadRequestDto, RegisterDto, PROTOCOL_VERSION,
+};
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::testing::{peer_device, MockHttp};
+use peersend_protocol::{
+    DeviceInfo, DiscoveryManager, LocalSendClient, LocalSendConfig, QuickSave, SessionManager,
+    SymlinkPolicy,
+};
+use tokio::sync::Mutex;
+
+fn peer(version: &str) -> DeviceInfo {
+    DeviceInfo {
+        version: version.to_string(),
+        ..peer_device()
+    }
+}
+
+/// 与 PeerSend 接收端一样，接受除链接条目外的所有文件
+fn accept_files(http: &MockHttp) {
+    http.route("/prepare-upload", |request| request.accept_files(|f| f.metadata.is_none()));
+    http.respond_status("/upload", 200);
+}
+
+fn client(http: &Arc<MockHttp>, symlink_policy: SymlinkPolicy) -> LocalSendClient {
+    let config = LocalSendConfig {
+        symlink_policy,
+        ..LocalSendConfig::default()
+    };
+    LocalSendClient::new(config).with_http(http.clone())
+}
+
+/// 准备好的目录: 普通文件、指向它的链接、断开的链接和套接字
+fn fixture(dir: &Path) {
+    std::fs::write(dir.join("data.txt"), b"data").unwrap();
+    std::os::unix::fs::symlink("data.txt", dir.join("link.txt")).unwrap();
+    std::os::unix::fs::symlink("missing.txt", dir.join("broken.txt")).unwrap();
+    std::os::unix::net::UnixListener::bind(dir.join("socket")).unwrap();
+}
+
+fn paths(dir: &Path) -> Vec<std::path::PathBuf> {
+    ["data.txt", "link.txt", "broken.txt", "socket"].map(|f| dir.join(f)).to_vec()
+}
+
+#[tokio::test]
+async fn links_are_followed_and_special_files_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    fixture(dir.path());
+    let http = MockHttp::new();
+    accept_files(&http);
+
+    let session = client(&http, SymlinkPolicy::Follow)
+        .send_files(&peer("0.1.0-peersend"), &paths(dir.path()))
+        .await
+        .unwrap();
+    let names: Vec<_> = session.files.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["data.txt", "link.txt"]);
+    let requests = http.requests();
+    let uploads: Vec<_> = requests.iter().filter(|r| r.url.contains("/upload")).collect();
+    assert_eq!(uploads.len(), 2);
+    assert!(uploads.iter().all(|r| r.body == b"data"));
+
+    let warnings = session.warnings.lock().await.clone();
+    let skipped: Vec<_> = warnings.iter().map(|w| w.name.as_str()).collect();
+    let expected = [dir.path().join("broken.txt"), dir.path().join("socket")];
+    assert_eq!(skipped, expected.iter().map(|p| p.to_str().unwrap()).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn skip_policy_drops_links() {
+    let dir = tempfile::tempdir().unwrap();
+    fixture(dir.path());
+    let http = MockHttp::new();
+    accept_files(&http);
+
+    let session = client(&http, SymlinkPolicy::Skip)
+        .send_files(&peer("0.1.0-peersend"), &paths(dir.path()))
+        .await
+        .unwrap();
+    assert_eq!(session.files.len(), 1);
+    assert_eq!(session.warnings.lock().await.len(), 3);
+
+    // 没有可发送的文件
+    let only_links = [dir.path().join("link.txt")];
+    let result = client(&http, SymlinkPolicy::Skip)
+        .send_files(&peer("0.1.0-peersend"), &only_links)
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn links_are_preserved_only_for_peersend() {
+    let dir = tempfile::tempdir().unwrap();
+    fixture(dir.path());
+    let links = [dir.path().join("data.txt"), dir.path().join("link.txt")];
+
+    let http = MockHttp::new();
+    accept_files(&http);
+    let session = client(&http, SymlinkPolicy::Preserve)
+        .send_files(&peer("0.1.0-peersend"), &links)
+        .await
+        .unwrap();
+    let link = &session.files[1];
+    assert_eq!(link.size, 0);
+    assert_eq!(link.metadata, Some(serde_json::json!({ "symlink": "data.txt" })));
+    let uploads = http.requests().into_iter().filter(|r| r.url.contains("/upload")).count();
+    assert_eq!(uploads, 1);
+
+    // 官方 LocalSend 收到的是链接指向的文件
+    let http = MockHttp::new();
+    accept_files(&http);
+    let session = client(&http, SymlinkPolicy::Preserve)
+        .send_files(&peer("2.1.0"), &links)
+        .await
+        .unwrap();
+    assert!(session.files.iter().all(|f| f.metadata.is_none()));
+    let uploads = http.requests().into_iter().filter(|r| r.url.contains("/upload")).count();
+    assert_eq!(uploads, 2);
+}
+
+fn link_request(targets: &[(&str, &str)]) -> PrepareUploadRequestDto {
+    let files = targets
+        .iter()
+        .map(|(name, target)| {
+            let file = FileDto {
+                id: name.to_string(),
+                file_name: name.to_string(),
+                size: 0,
+                file_type: "inode/symlink".to_string(),
+                sha256: None,
+                preview: None,
+                metadata: Some(FileTimesDto {
+                    modified: None,
+                    accessed: None,
+                    symlink: Some(target.to_string()),
+                }),
+            };
+            (name.to_string(), file)
+        })
+        .collect();
+    PrepareUploadRequestDto {
+        info: RegisterDto {
+            alias: "Laptop".to_string(),
+            version: PROTOCOL_VERSION.to_string(),
+            device_model: None,
+            device_type: Some("desktop".to_string()),
+            fingerprint: "laptop".to_string(),
+            port: 53317,
+            protocol: "http".to_string(),
+            download: false,
+        },
+        files,
+    }
+}
+
+#[tokio::test]
+async fn received_links_are_restored_inside_download_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        quick_save: QuickSave::On,
+        ..LocalSendConfig::default()
+    };
+    let sessions = SessionManager::new();
+    let server = LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        config,
+        Arc::new(Mutex::new(sessions.clone())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    );
+
+    let request = link_request(&[("latest", "releases/v2.zip"), ("passwd", "../../etc/passwd")]);
+    let response = server.prepare_upload("192.0.2.5", request, None).await.unwrap();
+    assert_eq!(response.files, HashMap::new());
+
+    let latest = std::fs::read_link(dir.path().join("latest")).unwrap();
+    assert_eq!(latest, Path::new("releases/v2.zip"));
+    assert!(std::fs::symlink_metadata(dir.path().join("passwd")).is_err());
+
+    let session = sessions.get_session(&response.session_id).await.unwrap();
+    let warnings = session.warnings.lock().await.clone();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].name, "passwd");
+}
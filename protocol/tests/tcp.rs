@@ -0,0 +1,365 @@
+//! 裸 TCP 传输测试
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use peersend_protocol::dto::v2::{FileDto, PrepareUploadRequestDto, RegisterDto};
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::storage::QUARANTINE_DIR;
+use peersend_protocol::summary::FileOutcome;
+use peersend_protocol::testing::{peer_device, MockFs, MockHttp};
+use peersend_protocol::transport::tcp::{TcpTransport, TCP_TRANSPORT};
+use peersend_protocol::transport::{self, StreamHeader, Transport, TransportListener};
+use peersend_protocol::{
+    CollisionPolicy, DeviceInfo, DiscoveryManager, FileInfo, FileSession, LocalSendClient,
+    LocalSendConfig, ProtocolEvent, QuickSave, SessionManager, SessionState,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+#[test]
+fn capability_carries_optional_port() {
+    assert_eq!(transport::parse_capability("tcp:53318"), ("tcp", Some(53318)));
+    assert_eq!(transport::parse_capability("quic"), ("quic", None));
+    assert_eq!(transport::parse_capability("tcp:bad"), ("tcp", None));
+}
+
+/// 在本机启动只接收 TCP 文件流的服务器，返回服务器和监听地址
+async fn start_server(
+    config: LocalSendConfig,
+    sessions: &Arc<Mutex<SessionManager>>,
+) -> (LocalSendServer, SocketAddr) {
+    let listener = TcpTransport::new()
+        .listen("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let addr = listener.local_addr();
+    let server = LocalSendServer::new(
+        "127.0.0.1:0".parse().unwrap(),
+        config,
+        sessions.clone(),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    )
+    .with_transport_listener(Box::new(listener));
+    server.start().await.unwrap();
+    (server, addr)
+}
+
+/// 发送文件流并等待接收端关闭流
+async fn send_stream(addr: SocketAddr, header: &StreamHeader, mut body: &[u8]) {
+    let mut stream = TcpTransport::new().connect(addr).await.unwrap();
+    transport::send_reader(&mut stream, header, &mut body).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut ack = Vec::new();
+    let _ = stream.read_to_end(&mut ack).await;
+}
+
+#[tokio::test]
+async fn server_receives_negotiated_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig::builder()
+        .download_dir(dir.path().to_string_lossy().into_owned())
+        .build()
+        .unwrap();
+    let sessions = Arc::new(Mutex::new(SessionManager::new()));
+    let session = sessions
+        .lock()
+        .await
+        .create_session(
+            "peer".to_string(),
+            config.device_id.clone(),
+            vec![FileInfo {
+                id: "file".to_string(),
+                name: "../escape.txt".to_string(),
+                size: 4,
+                file_type: "text/plain".to_string(),
+                metadata: None,
+                sha256: None,
+            }],
+        )
+        .await;
+    let tokens = sessions.lock().await.issue_tokens(&session.id, "127.0.0.1", ["file"]);
+    let (server, addr) = start_server(config, &sessions).await;
+
+    let header = StreamHeader {
+        session_id: session.id.clone(),
+        file_id: "file".to_string(),
+        token: tokens["file"].clone(),
+        size: 4,
+        offset: 0,
+        sparse: None,
+    };
+    send_stream(addr, &header, b"data").await;
+
+    assert_eq!(std::fs::read(dir.path().join("escape.txt")).unwrap(), b"data");
+    let progress = sessions.lock().await.get_session(&session.id).await.unwrap().progress.clone();
+    assert_eq!(progress.bytes_transferred(), 4);
+    // 接受的文件全部收到后会话结束
+    assert_eq!(*session.state.lock().await, SessionState::Finished);
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn server_rejects_streams_without_valid_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig::builder()
+        .download_dir(dir.path().to_string_lossy().into_owned())
+        .build()
+        .unwrap();
+    let sessions = Arc::new(Mutex::new(SessionManager::new()));
+    let file = FileInfo {
+        id: "file".to_string(),
+        name: "secret.txt".to_string(),
+        size: 4,
+        file_type: "text/plain".to_string(),
+        metadata: None,
+        sha256: None,
+    };
+    let session = sessions
+        .lock()
+        .await
+        .create_session("peer".to_string(), config.device_id.clone(), vec![file])
+        .await;
+    let tokens = sessions.lock().await.issue_tokens(&session.id, "127.0.0.1", ["file"]);
+    let (server, addr) = start_server(config, &sessions).await;
+
+    let header = |token: &str| StreamHeader {
+        session_id: session.id.clone(),
+        file_id: "file".to_string(),
+        token: token.to_string(),
+        size: 4,
+        offset: 0,
+        sparse: None,
+    };
+    send_stream(addr, &header(""), b"data").await;
+    send_stream(addr, &header("wrong"), b"data").await;
+    assert!(!dir.path().join("secret.txt").exists());
+    assert_eq!(*session.state.lock().await, SessionState::Waiting);
+
+    // 令牌只发给了发起会话的地址
+    let elsewhere = sessions.lock().await.clone();
+    let token = &tokens["file"];
+    assert!(elsewhere.check_token(&session.id, "file", token, "192.0.2.9").is_err());
+    send_stream(addr, &header(token), b"data").await;
+    assert_eq!(std::fs::read(dir.path().join("secret.txt")).unwrap(), b"data");
+    server.shutdown();
+}
+
+/// 经 prepare-upload 发送只含 photo.jpg 的会话，服务器未发放令牌时不发送，返回会话
+async fn send_photo(
+    server: &LocalSendServer,
+    sessions: &Arc<Mutex<SessionManager>>,
+    addr: SocketAddr,
+    body: &[u8],
+) -> Arc<FileSession> {
+    let file = FileDto {
+        id: "file".to_string(),
+        file_name: "photo.jpg".to_string(),
+        size: body.len() as u64,
+        file_type: "image/jpeg".to_string(),
+        sha256: None,
+        preview: None,
+        metadata: None,
+    };
+    let request = PrepareUploadRequestDto {
+        info: RegisterDto::from_config(&LocalSendConfig::default()),
+        files: [("file".to_string(), file)].into(),
+    };
+    let response = server.prepare_upload("127.0.0.1", request, None).await.unwrap();
+    if let Some(token) = response.files.get("file") {
+        let header = StreamHeader {
+            session_id: response.session_id.clone(),
+            file_id: "file".to_string(),
+            token: token.clone(),
+            size: body.len() as u64,
+            offset: 0,
+            sparse: None,
+        };
+        send_stream(addr, &header, body).await;
+    }
+    sessions.lock().await.get_session(&response.session_id).await.unwrap()
+}
+
+fn saved_name(session: &FileSession) -> Option<String> {
+    session.transfers.lock().unwrap().get("file").and_then(|t| t.saved_name.clone())
+}
+
+#[tokio::test]
+async fn server_applies_collision_policy() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig::builder()
+        .download_dir(dir.path().to_string_lossy().into_owned())
+        .quick_save(QuickSave::On)
+        .build()
+        .unwrap();
+    let sessions = Arc::new(Mutex::new(SessionManager::new()));
+    let (server, addr) = start_server(config.clone(), &sessions).await;
+
+    let first = send_photo(&server, &sessions, addr, b"one").await;
+    let second = send_photo(&server, &sessions, addr, b"two").await;
+    assert_eq!(std::fs::read(dir.path().join("photo.jpg")).unwrap(), b"one");
+    assert_eq!(std::fs::read(dir.path().join("photo (1).jpg")).unwrap(), b"two");
+    assert_eq!(saved_name(&first).as_deref(), Some("photo.jpg"));
+    assert_eq!(saved_name(&second).as_deref(), Some("photo (1).jpg"));
+    assert_eq!(*second.state.lock().await, SessionState::Finished);
+    server.shutdown();
+
+    let mut skip = config.clone();
+    skip.collision_policy = CollisionPolicy::Skip;
+    let (server, addr) = start_server(skip, &sessions).await;
+    let skipped = send_photo(&server, &sessions, addr, b"three").await;
+    assert_eq!(std::fs::read(dir.path().join("photo.jpg")).unwrap(), b"one");
+    assert!(skipped.transfers.lock().unwrap()["file"].skipped);
+    // 跳过的文件不发放令牌，会话直接结束并在摘要中记为跳过
+    assert_eq!(*skipped.state.lock().await, SessionState::Finished);
+    let summary = skipped.summary.get().unwrap();
+    assert_eq!((summary.transferred, summary.skipped), (0, 1));
+    assert_eq!(summary.files[0].outcome, FileOutcome::Skipped);
+    server.shutdown();
+
+    let mut overwrite = config;
+    overwrite.collision_policy = CollisionPolicy::Overwrite;
+    let (server, addr) = start_server(overwrite, &sessions).await;
+    send_photo(&server, &sessions, addr, b"four").await;
+    assert_eq!(std::fs::read(dir.path().join("photo.jpg")).unwrap(), b"four");
+    assert!(!dir.path().join("photo (2).jpg").exists());
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn server_quarantines_risky_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LocalSendConfig::builder()
+        .download_dir(dir.path().to_string_lossy().into_owned())
+        .build()
+        .unwrap();
+    let sessions = Arc::new(Mutex::new(SessionManager::new()));
+    let mut events = sessions.lock().await.events().subscribe();
+    let session = sessions
+        .lock()
+        .await
+        .create_session(
+            "peer".to_string(),
+            config.device_id.clone(),
+            vec![FileInfo {
+                id: "file".to_string(),
+                name: "invoice.pdf.exe".to_string(),
+                size: 2,
+                file_type: "application/octet-stream".to_string(),
+                metadata: None,
+                sha256: None,
+            }],
+        )
+        .await;
+    let tokens = sessions.lock().await.issue_tokens(&session.id, "127.0.0.1", ["file"]);
+    let (server, addr) = start_server(config, &sessions).await;
+
+    let header = StreamHeader {
+        session_id: session.id.clone(),
+        file_id: "file".to_string(),
+        token: tokens["file"].clone(),
+        size: 2,
+        offset: 0,
+        sparse: None,
+    };
+    send_stream(addr, &header, b"MZ").await;
+
+    let quarantined = dir.path().join(QUARANTINE_DIR).join("invoice.pdf.exe");
+    assert_eq!(std::fs::read(&quarantined).unwrap(), b"MZ");
+    assert!(!dir.path().join("invoice.pdf.exe").exists());
+    let path = loop {
+        if let ProtocolEvent::FileQuarantined { path, .. } = events.recv().await.unwrap() {
+            break path;
+        }
+    };
+    assert_eq!(path, quarantined);
+    server.shutdown();
+}
+
+/// 向 ip 处的设备上传，接收端只在 127.0.0.1 上监听
+async fn upload_to(ip: &str) {
+    let mut listener = TcpTransport::new()
+        .listen("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let device = DeviceInfo {
+        ip: ip.to_string(),
+        transports: vec![format!("{}:{}", TCP_TRANSPORT, listener.local_addr().port())],
+        ..peer_device()
+    };
+
+    let http = MockHttp::new();
+    http.route("/prepare-upload", |request| request.accept_files(|_| true));
+    let fs = MockFs::new();
+    fs.insert("/src/a.txt", b"fast path".to_vec());
+    let client = LocalSendClient::new(LocalSendConfig::default())
+        .with_http(http.clone())
+        .with_fs(Arc::new(fs))
+        .with_transport(Arc::new(TcpTransport::new()));
+
+    let receive = async {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let header = transport::read_header(&mut stream).await.unwrap();
+        let mut body = vec![0u8; header.size as usize];
+        stream.read_exact(&mut body).await.unwrap();
+        stream.shutdown().await.unwrap();
+        body
+    };
+    let (sent, body) = tokio::join!(client.send_files(&device, &["/src/a.txt"]), receive);
+
+    sent.unwrap();
+    assert_eq!(body, b"fast path");
+    assert_eq!(http.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn client_uploads_to_advertised_port() {
+    upload_to("127.0.0.1").await;
+}
+
+#[tokio::test]
+async fn client_falls_back_to_reachable_family() {
+    // localhost 通常先解析为 ::1，连接被拒绝后改用 127.0.0.1
+    upload_to("localhost").await;
+}
+
+#[tokio::test]
+async fn local_file_send_matches_contents() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("large.bin");
+    let data: Vec<u8> = (0..3 * 1024 * 1024 + 17).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&path, &data).unwrap();
+
+    let mut listener = TcpTransport::new()
+        .listen("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let addr = listener.local_addr();
+    let header = StreamHeader {
+        session_id: "session".to_string(),
+        file_id: "file".to_string(),
+        token: String::new(),
+        size: data.len() as u64,
+        offset: 0,
+        sparse: None,
+    };
+
+    let send = async {
+        let mut stream = TcpTransport::new()
+            .send_local_file(addr, &header, &path)
+            .await
+            .unwrap();
+        stream.shutdown().await.unwrap();
+    };
+    let receive = async {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let header = transport::read_header(&mut stream).await.unwrap();
+        let mut body = Vec::new();
+        stream.read_to_end(&mut body).await.unwrap();
+        (header, body)
+    };
+    let (_, (received, body)) = tokio::join!(send, receive);
+
+    assert_eq!(received.size, data.len() as u64);
+    assert!(body == data);
+}
@@ -0,0 +1,89 @@
+//! 带宽限速测试
+
+use std::time::{Duration, Instant};
+
+use peersend_protocol::throttle::{Throttle, Throttled};
+use peersend_protocol::{BandwidthWindow, LocalSendConfig};
+use tokio::io::AsyncReadExt;
+
+fn window(start: &str, end: &str, limit: Option<u64>) -> BandwidthWindow {
+    BandwidthWindow {
+        start: start.to_string(),
+        end: end.to_string(),
+        limit,
+    }
+}
+
+fn minute(time: &str) -> u32 {
+    let (hour, minute) = time.split_once(':').unwrap();
+    hour.parse::<u32>().unwrap() * 60 + minute.parse::<u32>().unwrap()
+}
+
+#[test]
+fn schedule_overrides_default_limit() {
+    let throttle = Throttle::new(
+        Some(10_000_000),
+        vec![
+            window("09:00", "18:00", Some(1_000_000)),
+            window("23:00", "07:00", None),
+        ],
+    );
+    assert!(throttle.is_limited());
+    assert_eq!(throttle.limit_at(minute("08:59")), Some(10_000_000));
+    assert_eq!(throttle.limit_at(minute("09:00")), Some(1_000_000));
+    assert_eq!(throttle.limit_at(minute("17:59")), Some(1_000_000));
+    assert_eq!(throttle.limit_at(minute("18:00")), Some(10_000_000));
+    // 跨越午夜的时段
+    assert_eq!(throttle.limit_at(minute("23:30")), None);
+    assert_eq!(throttle.limit_at(minute("03:00")), None);
+    assert_eq!(throttle.limit_at(minute("07:00")), Some(10_000_000));
+}
+
+#[test]
+fn unconfigured_throttle_never_waits() {
+    let throttle = Throttle::default();
+    assert!(!throttle.is_limited());
+    assert_eq!(throttle.consume(usize::MAX), None);
+
+    let unlimited_windows = Throttle::new(None, vec![window("00:00", "12:00", None)]);
+    assert!(!unlimited_windows.is_limited());
+}
+
+#[test]
+fn validate_rejects_bad_windows() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = |limit, schedule| LocalSendConfig {
+        download_dir: dir.path().to_string_lossy().into_owned(),
+        bandwidth_limit: limit,
+        bandwidth_schedule: schedule,
+        ..Default::default()
+    };
+
+    assert!(config(Some(1_000_000), vec![window("22:00", "06:30", None)]).validate().is_ok());
+    assert!(config(Some(0), Vec::new()).validate().is_err());
+    assert!(config(None, vec![window("09:00", "18:00", Some(0))]).validate().is_err());
+    assert!(config(None, vec![window("9am", "18:00", None)]).validate().is_err());
+    assert!(config(None, vec![window("24:00", "01:00", None)]).validate().is_err());
+    assert!(config(None, vec![window("09:00", "09:00", None)]).validate().is_err());
+}
+
+#[tokio::test]
+async fn throttled_reader_is_paced() {
+    // 200 KB/s，读取 100 KB 约需 0.5 秒
+    let throttle = Throttle::new(Some(200_000), Vec::new());
+    let data = vec![7u8; 100_000];
+    let mut reader = Throttled::new(&data[..], throttle.clone());
+
+    let started = Instant::now();
+    let mut buf = [0u8; 8192];
+    let mut total = 0;
+    loop {
+        let read = reader.read(&mut buf).await.unwrap();
+        if read == 0 {
+            break;
+        }
+        total += read;
+    }
+    assert_eq!(total, data.len());
+    assert!(started.elapsed() >= Duration::from_millis(400));
+}
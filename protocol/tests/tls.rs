@@ -0,0 +1,84 @@
+//! HTTPS 接收服务测试
+
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::Arc;
+
+use peersend_protocol::crypto::tls::{TlsIdentity, CERTIFICATE_FILE_NAME, PRIVATE_KEY_FILE_NAME};
+use peersend_protocol::dto::v2::{InfoDto, INFO_PATH};
+use peersend_protocol::http::{HttpClient, HttpClientConfig, HttpRequest, ReqwestClient};
+use peersend_protocol::server::LocalSendServer;
+use peersend_protocol::{DiscoveryManager, LocalSendConfig, SessionManager};
+use tokio::sync::Mutex;
+
+/// 以 HTTPS 提供接口的接收服务，返回服务和监听地址
+async fn receiver(identity: TlsIdentity) -> (LocalSendServer, String) {
+    let config = LocalSendConfig {
+        device_name: "Desk".to_string(),
+        use_tls: true,
+        ..LocalSendConfig::default()
+    };
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let authority = listener.local_addr().unwrap().to_string();
+    let server = LocalSendServer::new(
+        listener.local_addr().unwrap(),
+        config,
+        Arc::new(Mutex::new(SessionManager::new())),
+        Arc::new(Mutex::new(DiscoveryManager::new())),
+    )
+    .with_listener(listener)
+    .with_tls_identity(identity);
+    server.start().await.unwrap();
+    (server, authority)
+}
+
+#[test]
+fn identity_is_persisted() {
+    let dir = tempfile::tempdir().unwrap();
+    let identity = TlsIdentity::load_or_generate(dir.path()).unwrap();
+    assert_eq!(identity.fingerprint().len(), 64);
+    assert!(identity.certificate_pem().starts_with("-----BEGIN CERTIFICATE-----"));
+
+    // 再次加载得到同一证书
+    let loaded = TlsIdentity::load_or_generate(dir.path()).unwrap();
+    assert_eq!(loaded.fingerprint(), identity.fingerprint());
+    assert_ne!(TlsIdentity::generate().unwrap().fingerprint(), identity.fingerprint());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let key = std::fs::metadata(dir.path().join(PRIVATE_KEY_FILE_NAME)).unwrap();
+        assert_eq!(key.permissions().mode() & 0o777, 0o600);
+    }
+
+    // 私钥损坏时拒绝加载
+    std::fs::write(dir.path().join(PRIVATE_KEY_FILE_NAME), "not a key").unwrap();
+    assert!(TlsIdentity::load(dir.path()).is_err());
+    assert!(dir.path().join(CERTIFICATE_FILE_NAME).exists());
+}
+
+#[tokio::test]
+async fn info_is_served_over_https_with_certificate_fingerprint() {
+    let identity = TlsIdentity::generate().unwrap();
+    let (server, authority) = receiver(identity.clone()).await;
+
+    let config = HttpClientConfig::default()
+        .with_accept_invalid_certs(true)
+        .with_certificate_pinning(true);
+    let client = ReqwestClient::with_config(&config).unwrap();
+    let response = client
+        .execute(HttpRequest::get(format!("https://{}{}", authority, INFO_PATH)))
+        .await
+        .unwrap();
+    let info: InfoDto = response.error_for_status().unwrap().json().unwrap();
+    assert_eq!(info.alias, "Desk");
+    // 公告的指纹与对方看到的证书一致
+    assert_eq!(info.fingerprint, identity.fingerprint());
+    assert_eq!(client.pinned(&authority).as_deref(), Some(identity.fingerprint()));
+
+    // 明文 HTTP 请求无法完成
+    let plain = ReqwestClient::shared()
+        .execute(HttpRequest::get(format!("http://{}{}", authority, INFO_PATH)))
+        .await;
+    assert!(plain.map_or(true, |response| response.status != 200));
+    server.shutdown();
+}
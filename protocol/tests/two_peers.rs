@@ -0,0 +1,72 @@
+//! 回环地址上的双端端到端测试
+
+use peersend_protocol::testing::PeerPair;
+use peersend_protocol::{ProtocolError, ProtocolEvent, SessionState};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn peers_register_with_each_other() {
+    let pair = PeerPair::start().await.unwrap();
+
+    let seen_by_receiver = pair.receiver.discovery.lock().await.get_devices().await;
+    assert_eq!(seen_by_receiver.len(), 1);
+    assert_eq!(seen_by_receiver[0].id, pair.sender.config.device_id);
+    assert_eq!(seen_by_receiver[0].port, pair.sender.config.port);
+
+    let seen_by_sender = pair.sender.discovery.lock().await.get_devices().await;
+    assert_eq!(seen_by_sender.len(), 1);
+    assert_eq!(seen_by_sender[0].id, pair.receiver.config.device_id);
+
+    assert_ne!(pair.sender.config.port, pair.receiver.config.port);
+}
+
+#[tokio::test]
+async fn transfer_delivers_files() {
+    let pair = PeerPair::start().await.unwrap();
+    let mut events = pair.receiver.events.subscribe();
+
+    let session = pair
+        .send_files(&[("hello.txt", b"hello peersend"), ("empty.bin", b"")])
+        .await
+        .unwrap();
+
+    let hello = tokio::fs::read(pair.receiver.download_dir().join("hello.txt"))
+        .await
+        .unwrap();
+    assert_eq!(hello, b"hello peersend");
+    let empty = tokio::fs::read(pair.receiver.download_dir().join("empty.bin"))
+        .await
+        .unwrap();
+    assert!(empty.is_empty());
+
+    assert_eq!(*session.state.lock().await, SessionState::Finished);
+    assert_eq!(session.progress.bytes_transferred(), 14);
+
+    let mut saw_progress = false;
+    while let Ok(event) = events.try_recv() {
+        if let ProtocolEvent::FileProgress { session_id, .. } = event {
+            assert_eq!(session_id, session.id);
+            saw_progress = true;
+        }
+    }
+    assert!(saw_progress);
+}
+
+#[tokio::test]
+async fn cancelled_transfer_stops_both_sides() {
+    let pair = PeerPair::start().await.unwrap();
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+
+    let result = pair.transfer(&[("data.bin", &[7u8; 4096])], cancel).await;
+    assert!(matches!(result, Err(ProtocolError::Cancelled)));
+
+    // 发送端的会话以错误结束，接收端收到 cancel 请求后取消会话
+    let sent = pair.sender.sessions.lock().await.get_all_sessions().await;
+    assert_eq!(sent.len(), 1);
+    assert!(matches!(*sent[0].state.lock().await, SessionState::Error(_)));
+    let received = pair.receiver.sessions.lock().await.get_all_sessions().await;
+    assert_eq!(received.len(), 1);
+    assert_eq!(*received[0].state.lock().await, SessionState::Cancelled);
+    assert!(!pair.receiver.download_dir().join("data.bin").exists());
+}
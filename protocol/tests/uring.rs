@@ -0,0 +1,42 @@
+//! io_uring 文件系统测试
+
+use peersend_protocol::fs::uring::UringFs;
+use peersend_protocol::fs::FileSystem;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[tokio::test]
+async fn write_append_and_read_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("data.bin");
+    let fs = UringFs::new().unwrap();
+
+    let first: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 241) as u8).collect();
+    let mut writer = fs.open_write(&path, false).await.unwrap();
+    writer.write_all(&first).await.unwrap();
+    writer.shutdown().await.unwrap();
+    drop(writer);
+
+    let mut writer = fs.open_write(&path, true).await.unwrap();
+    writer.write_all(b"tail").await.unwrap();
+    writer.shutdown().await.unwrap();
+    drop(writer);
+
+    assert_eq!(fs.file_len(&path).await.unwrap(), first.len() as u64 + 4);
+
+    let mut reader = fs.open_read(&path).await.unwrap();
+    let mut small = [0u8; 10];
+    reader.read_exact(&mut small).await.unwrap();
+    assert_eq!(small, first[..10]);
+
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).await.unwrap();
+    assert_eq!(rest[..rest.len() - 4], first[10..]);
+    assert_eq!(&rest[rest.len() - 4..], b"tail");
+}
+
+#[tokio::test]
+async fn missing_file_is_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let fs = UringFs::new().unwrap();
+    assert!(fs.open_read(&dir.path().join("missing")).await.is_err());
+}
@@ -0,0 +1,139 @@
+//! Webhook 通知测试
+
+use std::time::Duration;
+
+use peersend_protocol::testing::MockHttp;
+use peersend_protocol::webhook::{Notification, NotifiedFile, WebhookNotifier};
+use peersend_protocol::{
+    EventBus, FileInfo, ProtocolEvent, SessionManager, SessionState, WebhookConfig, WebhookEvent,
+};
+use tokio_util::sync::CancellationToken;
+
+fn notification() -> Notification {
+    Notification {
+        event: WebhookEvent::Completed,
+        session_id: "s1".to_string(),
+        sender: "Alice \"Phone\"".to_string(),
+        files: vec![
+            NotifiedFile {
+                name: "a.jpg".to_string(),
+                size: 10,
+            },
+            NotifiedFile {
+                name: "b.pdf".to_string(),
+                size: 32,
+            },
+        ],
+        error: None,
+    }
+}
+
+#[test]
+fn templates_are_rendered() {
+    let n = notification();
+
+    let default: serde_json::Value = serde_json::from_slice(&n.render(None)).unwrap();
+    assert_eq!(default["event"], "completed");
+    assert_eq!(default["sessionId"], "s1");
+    assert_eq!(default["files"][1]["size"], 32);
+    assert!(default.get("error").is_none());
+
+    let text = n.render(Some("{{sender}} 发来 {{count}} 个文件: {{files}} ({{size}} 字节)"));
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "Alice \"Phone\" 发来 2 个文件: a.jpg, b.pdf (42 字节)"
+    );
+
+    // JSON 模板中的值按字符串转义
+    let slack = n.render(Some(r#"{"text": "{{sender}}: {{files}}"}"#));
+    let slack: serde_json::Value = serde_json::from_slice(&slack).unwrap();
+    assert_eq!(slack["text"], "Alice \"Phone\": a.jpg, b.pdf");
+}
+
+#[tokio::test]
+async fn lifecycle_events_fire_subscribed_hooks() {
+    let http = MockHttp::new();
+    http.respond_status("/all", 200);
+    http.respond_status("/failures", 204);
+
+    let hooks = vec![
+        WebhookConfig {
+            url: "http://hooks.test/all".to_string(),
+            events: Vec::new(),
+            template: Some("{{event}} {{sender}} {{files}} {{error}}".to_string()),
+        },
+        WebhookConfig {
+            url: "http://hooks.test/failures".to_string(),
+            events: vec![WebhookEvent::Failed],
+            template: None,
+        },
+    ];
+    let notifier = WebhookNotifier::new(hooks).unwrap().with_http(http.clone());
+
+    let events = EventBus::default();
+    let sessions = SessionManager::with_event_bus(events.clone());
+    let cancel = CancellationToken::new();
+    tokio::spawn(notifier.run(events.subscribe(), sessions.clone(), cancel.clone()));
+
+    let file = FileInfo {
+        id: "f".to_string(),
+        name: "scan.pdf".to_string(),
+        size: 3,
+        file_type: "application/pdf".to_string(),
+        metadata: None,
+        sha256: None,
+    };
+    let ok = sessions
+        .create_session("alice".to_string(), "me".to_string(), vec![file.clone()])
+        .await;
+    events.emit(ProtocolEvent::SessionRequested {
+        session_id: ok.id.clone(),
+        sender: "Alice".to_string(),
+        files: vec![file.clone()],
+    });
+    sessions.set_state(&ok.id, SessionState::Transferring).await;
+    sessions.set_state(&ok.id, SessionState::Finished).await;
+
+    // 快速保存的会话没有请求事件，从会话管理器查询发送方
+    let failed = sessions
+        .create_session("bob".to_string(), "me".to_string(), vec![file])
+        .await;
+    sessions
+        .set_state(&failed.id, SessionState::Error("磁盘已满".to_string()))
+        .await;
+
+    let mut bodies = Vec::new();
+    for _ in 0..50 {
+        bodies = http
+            .requests()
+            .iter()
+            .map(|r| (r.url.clone(), String::from_utf8_lossy(&r.body).into_owned()))
+            .collect::<Vec<_>>();
+        if bodies.len() >= 4 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    bodies.sort();
+    cancel.cancel();
+
+    assert_eq!(bodies.len(), 4, "{:?}", bodies);
+    let all: Vec<_> = bodies
+        .iter()
+        .filter(|(url, _)| url.ends_with("/all"))
+        .map(|(_, body)| body.trim_end().to_string())
+        .collect();
+    assert_eq!(
+        all,
+        vec![
+            "completed Alice scan.pdf",
+            "failed bob scan.pdf 磁盘已满",
+            "requested Alice scan.pdf",
+        ]
+    );
+    let failures: Vec<_> = bodies.iter().filter(|(url, _)| url.ends_with("/failures")).collect();
+    assert_eq!(failures.len(), 1);
+    let failure: serde_json::Value = serde_json::from_str(&failures[0].1).unwrap();
+    assert_eq!(failure["sender"], "bob");
+    assert_eq!(failure["error"], "磁盘已满");
+}